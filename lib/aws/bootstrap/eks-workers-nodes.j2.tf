@@ -7,7 +7,23 @@ resource "aws_eks_node_group" "eks_cluster_workers_{{ loop.index }}" {
   subnet_ids       = flatten([aws_subnet.eks_zone_a[*].id, aws_subnet.eks_zone_b[*].id, aws_subnet.eks_zone_c[*].id])
   instance_types   = ["{{ eks_worker_node.instance_type }}"]
   ami_type         = "AL2_x86_64"
+  capacity_type    = "{{ eks_worker_node.capacity_type | upper | replace(from="-", to="_") }}"
 
+  # databases are kept off spot nodes thanks to this label
+  labels = {
+    "qovery.com/capacity-type" = "{{ eks_worker_node.capacity_type }}"
+    {%- for key, value in eks_worker_node.labels %}
+    "{{ key }}" = "{{ value }}"
+    {%- endfor %}
+  }
+  {%- for taint in eks_worker_node.taints %}
+
+  taint {
+    key    = "{{ taint.key }}"
+    value  = "{{ taint.value }}"
+    effect = "{{ taint.effect }}"
+  }
+  {%- endfor %}
 
   tags = local.tags_eks
 
@@ -1,4 +1,16 @@
 terraform {
+{%- if terraform_backend_s3 %}
+  backend "s3" {
+    access_key     = "{{ terraform_backend_access_key }}"
+    secret_key     = "{{ terraform_backend_secret_key }}"
+    bucket         = "{{ terraform_backend_bucket }}"
+    key            = "{{ terraform_backend_key }}"
+    region         = "{{ terraform_backend_region }}"
+{%- if terraform_backend_dynamodb_table %}
+    dynamodb_table = "{{ terraform_backend_dynamodb_table }}"
+{%- endif %}
+  }
+{%- else %}
   backend "kubernetes" {
     secret_suffix    = "{{ tfstate_suffix_name }}"
     load_config_file = true
@@ -18,4 +30,5 @@ terraform {
       }
     }
   }
+{%- endif %}
 }
@@ -78,10 +78,28 @@ resource "aws_db_parameter_group" "mysql_parameter_group" {
   tags = local.tags_mysql
 
   # Set superuser permission to the default 'username' account
+  {%- if "log_bin_trust_function_creators" not in database_parameters %}
   parameter {
     name  = "log_bin_trust_function_creators"
     value = "1"
   }
+  {%- endif %}
+
+  # Reject clients without TLS, RDS MySQL counterpart of rds.force_ssl
+  {%- if database_require_tls and "require_secure_transport" not in database_parameters %}
+  parameter {
+    name  = "require_secure_transport"
+    value = "1"
+  }
+  {%- endif %}
+
+  # User defined parameters, AWS validates them
+  {%- for name, value in database_parameters %}
+  parameter {
+    name  = "{{ name }}"
+    value = "{{ value }}"
+  }
+  {%- endfor %}
 }
 
 # Non snapshoted version
@@ -107,6 +125,10 @@ resource "aws_db_instance" "mysql_instance" {
   {%- else %}
   allocated_storage = var.disk_size
   storage_type = var.storage_type
+  iops = var.iops
+  {%- if database_storage_throughput is defined %}
+  storage_throughput = var.storage_throughput
+  {%- endif %}
   username = var.username
   engine_version = var.mysql_version
   engine = "mysql"
@@ -133,5 +155,53 @@ resource "aws_db_instance" "mysql_instance" {
   backup_window = var.backup_window
   skip_final_snapshot = var.skip_final_snapshot
   delete_automated_backups = var.delete_automated_backups
+  deletion_protection = var.deletion_protection
+
+}
+
+# Read replicas
+resource "aws_db_instance" "mysql_instance_replica" {
+  count = var.replicas
+  identifier = "${var.mysql_identifier}-replica-${count.index}"
+  replicate_source_db = aws_db_instance.mysql_instance.identifier
+
+  tags = local.tags_mysql
+
+  # MySQL instance basics
+  instance_class = var.instance_class
+  port = var.port
+  timeouts {
+    create = "60m"
+    update = "120m"
+    delete = "60m"
+  }
+  parameter_group_name = aws_db_parameter_group.mysql_parameter_group.name
+
+  # Network
+  vpc_security_group_ids = data.aws_security_group.selected.*.id
+  publicly_accessible = var.publicly_accessible
+
+  # Maintenance and upgrades
+  apply_immediately = var.apply_changes_now
+  auto_minor_version_upgrade = var.upgrade_minor
+  maintenance_window = var.maintenance_window
+
+  # Monitoring
+  monitoring_interval = 10
+  monitoring_role_arn = data.aws_iam_role.rds_enhanced_monitoring.arn
+
+  # Backups are managed on the primary instance
+  skip_final_snapshot = true
+}
+
+output "database_endpoint" {
+  value = aws_db_instance.mysql_instance.address
+}
+
+output "connection_string" {
+  value = "{{ database_connection_string }}"
+}
 
+output "security_group_ids" {
+  value = data.aws_security_group.selected.*.id
 }
@@ -75,11 +75,23 @@ variable "parameter_group_family" {
 }
 
 variable "storage_type" {
-  description = "One of 'standard' (magnetic), 'gp2' (general purpose SSD), or 'io1' (provisioned IOPS SSD)."
+  description = "One of 'standard' (magnetic), 'gp2' (general purpose SSD), 'gp3' (general purpose SSD with provisioned performances), or 'io1' (provisioned IOPS SSD)."
   default = "{{ database_disk_type }}"
   type = string
 }
 
+variable "iops" {
+  description = "Provisioned IOPS, only for 'io1' and 'gp3' storage types"
+  default = {% if database_iops is defined %}{{ database_iops }}{% else %}null{% endif %}
+  type = number
+}
+
+variable "storage_throughput" {
+  description = "Provisioned storage throughput in MiB/s, only for 'gp3' storage type"
+  default = {% if database_storage_throughput is defined %}{{ database_storage_throughput }}{% else %}null{% endif %}
+  type = number
+}
+
 variable "instance_class" {
   description = "Type of instance: https://docs.aws.amazon.com/AmazonRDS/latest/UserGuide/Concepts.DBInstanceClass.html"
   default = "{{database_instance_type}}"
@@ -112,6 +124,12 @@ variable "publicly_accessible" {
   type = bool
 }
 
+variable "replicas" {
+  description = "Number of read replicas, the primary instance is not included"
+  default = {{ database_replicas }}
+  type = number
+}
+
 variable "multi_az" {
   description = "Multi availability zones"
   default = true
@@ -142,13 +160,13 @@ variable "maintenance_window" {
 
 variable "backup_retention_period" {
   description = "Backup rentention period"
-  default = 7
+  default = {{ backup_retention_period }}
   type = number
 }
 
 variable "backup_window" {
-  description = "Maintenance window"
-  default = "00:00-01:00"
+  description = "Daily UTC window automated backups are taken in"
+  default = "{{ backup_window }}"
   type = string
 }
 
@@ -164,6 +182,12 @@ variable "skip_final_snapshot" {
   type = bool
 }
 
+variable "deletion_protection" {
+  description = "Prevent the instance to be deleted"
+  default = {{ deletion_protection }}
+  type = bool
+}
+
 {%- if snapshot is defined %}
 # Snapshots
 variable "snapshot_identifier" {
@@ -117,5 +117,6 @@ resource "aws_db_instance" "postgresql_instance" {
   backup_window = var.backup_window
   skip_final_snapshot = var.skip_final_snapshot
   delete_automated_backups = var.delete_automated_backups
+  deletion_protection = var.deletion_protection
 
 }
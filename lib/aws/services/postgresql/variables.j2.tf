@@ -150,13 +150,13 @@ variable "performance_insights_enabled_retention" {
 
 variable "backup_retention_period" {
   description = "Backup retention period"
-  default = 14
+  default = {{ backup_retention_period }}
   type = number
 }
 
 variable "backup_window" {
-  description = "Maintenance window"
-  default = "00:00-01:00"
+  description = "Daily UTC window automated backups are taken in"
+  default = "{{ backup_window }}"
   type = string
 }
 
@@ -172,12 +172,20 @@ variable "skip_final_snapshot" {
   type = bool
 }
 
+variable "deletion_protection" {
+  description = "Prevent the instance to be deleted"
+  default = {{ deletion_protection }}
+  type = bool
+}
+
+{%- if snapshot is defined %}
 # Snapshots
-#variable "snapshot_identifier" {
-#  description = "Snapshot ID to restore"
-#  default = "{ service_info['snapshot']['snapshot_id'] }"
-#  type = string
-#}
+variable "snapshot_identifier" {
+  description = "Snapshot ID to restore"
+  default = "{{ snapshot['snapshot_id']}}"
+  type = string
+}
+{% endif %}
 
 {%- if resource_expiration_in_seconds is defined %}
 # Pleco ttl
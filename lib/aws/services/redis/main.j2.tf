@@ -28,8 +28,18 @@ data "aws_security_group" "selected" {
   }
 }
 
+locals {
+  {%- if database_cluster_mode_enabled %}
+  elasticache_id = aws_elasticache_replication_group.elasticache_cluster.id
+  elasticache_address = aws_elasticache_replication_group.elasticache_cluster.configuration_endpoint_address
+  {%- else %}
+  elasticache_id = aws_elasticache_cluster.elasticache_cluster.id
+  elasticache_address = aws_elasticache_cluster.elasticache_cluster.cache_nodes.0.address
+  {%- endif %}
+}
+
 resource "helm_release" "elasticache_instance_external_name" {
-  name = "${aws_elasticache_cluster.elasticache_cluster.id}-externalname"
+  name = "${local.elasticache_id}-externalname"
   chart = "external-name-svc"
   namespace = "{{namespace}}"
   atomic = true
@@ -37,7 +47,7 @@ resource "helm_release" "elasticache_instance_external_name" {
 
   set {
     name = "target_hostname"
-    value = aws_elasticache_cluster.elasticache_cluster.cache_nodes.0.address
+    value = local.elasticache_address
   }
 
   set {
@@ -51,10 +61,72 @@ resource "helm_release" "elasticache_instance_external_name" {
   }
 
   depends_on = [
+    {%- if database_cluster_mode_enabled %}
+    aws_elasticache_replication_group.elasticache_cluster
+    {%- else %}
     aws_elasticache_cluster.elasticache_cluster
+    {%- endif %}
   ]
 }
 
+{%- if database_cluster_mode_enabled %}
+resource "aws_elasticache_replication_group" "elasticache_cluster" {
+  replication_group_id = var.elasticache_identifier
+  replication_group_description = "Redis cluster ${var.elasticache_identifier}"
+
+  tags = {
+    cluster_name = var.cluster_name
+    region = var.region
+    q_client_id = var.q_customer_id
+    q_environment_id = var.q_environment_id
+    q_project_id = var.q_project_id
+    database_identifier = var.elasticache_identifier
+    {% if resource_expiration_in_seconds is defined %}ttl = var.resource_expiration_in_seconds{% endif %}
+  }
+
+  # Elasticache instance basics
+  engine = "redis"
+  port = var.port
+  engine_version = var.elasticache_version
+  node_type = var.instance_class
+  parameter_group_name = var.parameter_group_name
+  # https://github.com/hashicorp/terraform-provider-aws/issues/15625
+  lifecycle {
+    ignore_changes = [engine_version]
+  }
+
+  # Cluster mode (sharding), automatic failover is mandatory
+  automatic_failover_enabled = true
+  cluster_mode {
+    num_node_groups = var.elasticache_node_groups_number
+    replicas_per_node_group = var.elasticache_replicas_per_node_group
+  }
+
+  {%- if snapshot is defined and snapshot["snapshot_id"] %}
+  # Snapshot
+  snapshot_name = var.snapshot_identifier
+  {%- endif %}
+
+  # Network
+  # WARNING: this value cna't get fetch from data sources and is linked to the bootstrap phase
+  subnet_group_name = "elasticache-${data.aws_vpc.selected.id}"
+
+  # Security
+  security_group_ids = data.aws_security_group.selected.*.id
+
+  # Maintenance and upgrades
+  apply_immediately = var.apply_changes_now
+  maintenance_window = var.preferred_maintenance_window
+
+  # Backups
+  snapshot_window = var.preferred_backup_window
+  snapshot_retention_limit = var.backup_retention_period
+  {%- if skip_final_snapshot %}
+  final_snapshot_identifier = "${var.elasticache_identifier}-final"
+  {%- endif %}
+}
+{%- else %}
+
 resource "aws_elasticache_cluster" "elasticache_cluster" {
   cluster_id = var.elasticache_identifier
 
@@ -78,14 +150,10 @@ resource "aws_elasticache_cluster" "elasticache_cluster" {
     ignore_changes = [engine_version]
   }
 
-  {%- if replication_group_id is defined %}
-  # todo: add cluster mode and replicas support
-  {%- else %}
   engine = "redis"
   node_type = var.instance_class
   num_cache_nodes = var.elasticache_instances_number
   parameter_group_name = var.parameter_group_name
-  {%- endif %}
 
   {%- if snapshot is defined and snapshot["snapshot_id"] %}
   # Snapshot
@@ -111,3 +179,4 @@ resource "aws_elasticache_cluster" "elasticache_cluster" {
   {%- endif %}
 
 }
+{%- endif %}
@@ -68,6 +68,20 @@ variable "elasticache_instances_number" {
   type = number
 }
 
+{%- if database_cluster_mode_enabled %}
+variable "elasticache_node_groups_number" {
+  description = "Elasticache cluster mode shards number"
+  default = 1
+  type = number
+}
+
+variable "elasticache_replicas_per_node_group" {
+  description = "Elasticache cluster mode replicas number per shard"
+  default = 1
+  type = number
+}
+{% endif %}
+
 variable "port" {
   description = "Elasticache instance port"
   default = {{ database_port }}
@@ -1,16 +1,24 @@
-{% for scw_ks_worker_node in scw_ks_worker_nodes %}
-resource "scaleway_k8s_pool" "kubernetes_cluster_workers_{{ loop.index }}" {
+{% for scw_ks_node_pool in scw_ks_node_pools %}
+{% if scw_ks_node_pool.is_default %}
+# default pool keeps the address and name it had before pools were named, changing them would recreate it
+resource "scaleway_k8s_pool" "kubernetes_cluster_workers_1" {
   cluster_id    = scaleway_k8s_cluster.kubernetes_cluster.id
   name          = var.kubernetes_cluster_id
-  node_type     = "{{ scw_ks_worker_node.instance_type }}"
+{% else %}
+# resource and pool names are derived from the node pool name so they stay stable across applies
+resource "scaleway_k8s_pool" "kubernetes_cluster_workers_{{ scw_ks_node_pool.name | replace(from="-", to="_") }}" {
+  cluster_id    = scaleway_k8s_cluster.kubernetes_cluster.id
+  name          = "${var.kubernetes_cluster_id}-{{ scw_ks_node_pool.name }}"
+{% endif %}  node_type     = "{{ scw_ks_node_pool.instance_type }}"
 
   # use Scaleway built-in cluster autoscaler
-  autoscaling   = {{ scw_ks_pool_autoscale }}
+  autoscaling   = {{ scw_ks_node_pool.autoscaling }}
   autohealing   = true
-  size          = "{{ scw_ks_worker_node.min_size }}"
-  min_size      = "{{ scw_ks_worker_node.min_size }}"
-  max_size      = "{{ scw_ks_worker_node.max_size }}"
+  size          = "{{ scw_ks_node_pool.min_size }}"
+  min_size      = "{{ scw_ks_node_pool.min_size }}"
+  max_size      = "{{ scw_ks_node_pool.max_size }}"
 
-  tags          =  local.tags_ks_list
+  # node labels (noprefix=) and taints (taint=) are set through pool tags
+  tags          = concat(local.tags_ks_list, [{% for tag in scw_ks_node_pool.tags %}"{{ tag }}"{% if not loop.last %}, {% endif %}{% endfor %}])
 }
-{% endfor %}
\ No newline at end of file
+{% endfor %}
@@ -0,0 +1,31 @@
+terraform {
+  required_providers {
+    scaleway = {
+      source = "scaleway/scaleway"
+      version = "~> 2.1.0"
+    }
+    helm = {
+      source = "hashicorp/helm"
+      version = "~> 1.3.2"
+    }
+    local = {
+      source = "hashicorp/local"
+      version = "~> 1.4"
+    }
+  }
+  required_version = ">= 0.14"
+}
+
+provider "scaleway" {
+  access_key = "{{ scaleway_access_key }}"
+  secret_key = "{{ scaleway_secret_key }}"
+  zone       = "{{ database_zone }}"
+  region     = "{{ database_region }}"
+}
+
+provider "helm" {
+  kubernetes {
+    load_config_file = true
+    config_path = "{{ kubeconfig_path }}"
+  }
+}
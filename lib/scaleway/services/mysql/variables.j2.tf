@@ -0,0 +1,98 @@
+# Qovery
+
+variable "cluster_name" {
+  description = "Kubernetes cluster name"
+  default     = "{{ cluster_name }}"
+  type        = string
+}
+
+variable "kubernetes_cluster_id" {
+  description = "Kubernetes cluster id"
+  default     = "{{ kubernetes_cluster_id }}"
+  type        = string
+}
+
+variable "q_project_id" {
+  description = "Qovery project ID"
+  default     = "{{ project_id }}"
+  type        = string
+}
+
+variable "q_customer_id" {
+  description = "Qovery customer ID"
+  default     = "{{ owner_id }}"
+  type        = string
+}
+
+variable "q_environment_id" {
+  description = "Qovery client environment"
+  default     = "{{ environment_id }}"
+  type        = string
+}
+
+# MySQL instance basics
+
+variable "mysql_identifier" {
+  description = "MySQL instance name"
+  default = "{{ fqdn_id }}"
+  type = string
+}
+
+variable "mysql_version" {
+  description = "MySQL major version"
+  default = "{{ version }}"
+  type = string
+}
+
+variable "instance_type" {
+  description = "Type of instance: https://www.scaleway.com/en/pricing/#managed-databases"
+  default = "{{ database_instance_type }}"
+  type = string
+}
+
+variable "disk_size" {
+  description = "disk instance size"
+  default = {{ database_disk_size_in_gib }}
+  type = number
+}
+
+variable "volume_type" {
+  description = "One of 'lssd' (local SSD) or 'bssd' (block SSD), only block SSD volumes can be resized"
+  default = "bssd"
+  type = string
+}
+
+variable "username" {
+  description = "Admin username for the master DB user"
+  default = "{{ database_login }}"
+  type = string
+}
+
+variable "password" {
+  description = "Admin password for the master DB user"
+  default = "{{ database_password }}"
+  type = string
+}
+
+variable "is_ha_cluster" {
+  description = "Add a standby node, promoted if the main one fails"
+  default = true
+  type = bool
+}
+
+# Backups
+
+variable "disable_backup" {
+  description = "Disable automated backups"
+  default = {{ delete_automated_backups }}
+  type = bool
+}
+
+{%- if resource_expiration_in_seconds is defined %}
+# Pleco ttl
+variable "resource_expiration_in_seconds" {
+  description = "Resource expiration in seconds"
+  default = {{ resource_expiration_in_seconds }}
+  type = number
+}
+{% endif %}
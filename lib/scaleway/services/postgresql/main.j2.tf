@@ -0,0 +1,56 @@
+locals {
+  tags_postgresql = [
+    "cluster_name:${var.cluster_name}",
+    "cluster_id:${var.kubernetes_cluster_id}",
+    "q_client_id:${var.q_customer_id}",
+    "q_environment_id:${var.q_environment_id}",
+    "q_project_id:${var.q_project_id}",
+    "database_identifier:${var.postgresql_identifier}",
+    {% if resource_expiration_in_seconds is defined %}"ttl:${var.resource_expiration_in_seconds}",{% endif %}
+  ]
+}
+
+resource "helm_release" "postgresql_instance_external_name" {
+  name = "${scaleway_rdb_instance.postgresql_instance.name}-externalname"
+  chart = "external-name-svc"
+  namespace = "{{namespace}}"
+  atomic = true
+  max_history = 50
+
+  set {
+    name = "target_hostname"
+    value = scaleway_rdb_instance.postgresql_instance.endpoint_ip
+  }
+  set {
+    name = "source_fqdn"
+    value = "{{database_fqdn}}"
+  }
+  set {
+    name = "app_id"
+    value = "{{database_id}}"
+  }
+
+  depends_on = [
+    scaleway_rdb_instance.postgresql_instance
+  ]
+}
+
+resource "scaleway_rdb_instance" "postgresql_instance" {
+  name = var.postgresql_identifier
+
+  tags = local.tags_postgresql
+
+  # PostgreSQL instance basics
+  node_type = var.instance_type
+  engine = "PostgreSQL-${var.postgresql_version}"
+  user_name = var.username
+  password = var.password
+  volume_type = var.volume_type
+  volume_size_in_gb = var.disk_size
+
+  # High availability
+  is_ha_cluster = var.is_ha_cluster
+
+  # Backups
+  disable_backup = var.disable_backup
+}
@@ -1,11 +1,17 @@
 use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
 use std::{env, fs};
 
 use chrono::Duration;
+use git2::Repository;
 use sysinfo::{Disk, DiskExt, SystemExt};
 
 use crate::build_platform::{Build, BuildPlatform, BuildResult, Image, Kind};
-use crate::error::{EngineError, EngineErrorCause, SimpleError, SimpleErrorKind};
+use crate::cmd::docker::{docker_build, docker_pull, docker_tag};
+use crate::container_registry::utilities::image_registry_host;
+use crate::error::{EngineError, EngineErrorCause, SimpleError, SimpleErrorKind, StringError};
 use crate::fs::workspace_directory;
 use crate::git::checkout_submodules;
 use crate::models::{
@@ -14,6 +20,7 @@ use crate::models::{
 use crate::{cmd, git};
 
 const BUILD_DURATION_TIMEOUT_MIN: i64 = 30;
+const BUILD_PROGRESS_INTERVAL_SEC: u64 = 30;
 
 /// https://buildpacks.io/
 const BUILDPACKS_BUILDERS: [&str; 1] = [
@@ -28,6 +35,8 @@ pub struct LocalDocker {
     context: Context,
     id: String,
     name: String,
+    use_buildkit: bool,
+    registry_mirror: Option<String>,
     listeners: Listeners,
 }
 
@@ -37,10 +46,24 @@ impl LocalDocker {
             context,
             id: id.to_string(),
             name: name.to_string(),
+            use_buildkit: true,
+            registry_mirror: None,
             listeners: vec![],
         }
     }
 
+    /// BuildKit is enabled by default, it allows to reuse layers of previously pushed images.
+    pub fn with_buildkit(mut self, use_buildkit: bool) -> Self {
+        self.use_buildkit = use_buildkit;
+        self
+    }
+
+    /// Docker Hub base images are pulled through this mirror (ex: `https://mirror.gcr.io`) before building.
+    pub fn with_registry_mirror(mut self, registry_mirror_url: &str) -> Self {
+        self.registry_mirror = Some(registry_mirror_url.to_string());
+        self
+    }
+
     fn image_does_exist(&self, image: &Image) -> Result<bool, EngineError> {
         Ok(matches!(
             crate::cmd::utilities::exec(
@@ -59,6 +82,113 @@ impl LocalDocker {
         }
     }
 
+    /// Rewrites the registry of the Dockerfile base images, see `Context::rewrite_image`, docker build then pulls
+    /// them from there. The repository is cloned for this build only, so nothing else sees the change.
+    fn rewrite_base_images(&self, dockerfile_complete_path: &str) -> Result<(), EngineError> {
+        if self.context.image_registry_rewrites().is_empty() {
+            return Ok(());
+        }
+
+        let dockerfile = fs::read_to_string(dockerfile_complete_path).map_err(|err| {
+            self.engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "can't read {} to rewrite its base images: {}",
+                    dockerfile_complete_path, err
+                ),
+            )
+        })?;
+
+        let rewritten_dockerfile =
+            rewrite_dockerfile_base_images(dockerfile.as_str(), |image| self.context.rewrite_image(image));
+        if rewritten_dockerfile == dockerfile {
+            return Ok(());
+        }
+
+        fs::write(dockerfile_complete_path, rewritten_dockerfile).map_err(|err| {
+            self.engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "can't write {} with rewritten base images: {}",
+                    dockerfile_complete_path, err
+                ),
+            )
+        })
+    }
+
+    /// Pulls the Docker Hub base images of the Dockerfile through the registry mirror, docker build then finds
+    /// them locally. Failures are only reported, the build falls back to pulling from Docker Hub.
+    fn pull_base_images_through_mirror(
+        &self,
+        dockerfile_complete_path: &str,
+        application_id: &str,
+        lh: &ListenersHelper,
+    ) {
+        let registry_mirror = match &self.registry_mirror {
+            Some(registry_mirror) => registry_mirror,
+            None => return,
+        };
+
+        let warn_user = |message: String| {
+            warn!("{}", message);
+            lh.deployment_in_progress(ProgressInfo::new(
+                ProgressScope::Application {
+                    id: application_id.to_string(),
+                },
+                ProgressLevel::Warn,
+                Some(message),
+                self.context.execution_id(),
+            ));
+        };
+
+        let registry_mirror_host = match registry_mirror_host(registry_mirror) {
+            Ok(host) => host,
+            Err(err) => return warn_user(err),
+        };
+
+        let is_reachable = reqwest::blocking::Client::builder()
+            .timeout(std::time::Duration::from_secs(5))
+            .build()
+            .and_then(|client| {
+                client
+                    .get(format!("{}/v2/", registry_mirror.trim_end_matches('/')))
+                    .send()
+            })
+            .is_ok();
+        if !is_reachable {
+            return warn_user(format!(
+                "registry mirror {} is unreachable, base images are pulled from Docker Hub",
+                registry_mirror
+            ));
+        }
+
+        let dockerfile = match fs::read_to_string(dockerfile_complete_path) {
+            Ok(dockerfile) => dockerfile,
+            Err(err) => {
+                return warn!(
+                    "can't read {} to find its base images: {}",
+                    dockerfile_complete_path, err
+                )
+            }
+        };
+
+        for image in dockerhub_base_images(dockerfile.as_str()) {
+            let mirror_image = mirror_image_reference(registry_mirror_host.as_str(), image.as_str());
+            let docker_envs = self.get_docker_host_envs();
+            let pulled = docker_pull(&docker_envs, mirror_image.as_str())
+                .and_then(|_| docker_tag(&docker_envs, mirror_image.as_str(), image.as_str()));
+
+            if let Err(err) = pulled {
+                warn_user(format!(
+                    "base image {} can't be pulled from registry mirror {}, it is pulled from Docker Hub: {}",
+                    image,
+                    registry_mirror,
+                    err.message.unwrap_or_default()
+                ));
+            }
+        }
+    }
+
     fn build_image_with_docker(
         &self,
         build: Build,
@@ -68,43 +198,35 @@ impl LocalDocker {
         use_build_cache: bool,
         lh: &ListenersHelper,
     ) -> Result<BuildResult, EngineError> {
-        let mut docker_args = if !use_build_cache {
-            vec!["build", "--no-cache"]
-        } else {
-            vec!["build"]
-        };
-
-        let args = self.context.docker_build_options();
-        for v in args.iter() {
-            for s in v.iter() {
-                docker_args.push(String::as_str(s));
-            }
-        }
-
-        let name_with_tag = build.image.name_with_tag();
-
-        docker_args.extend(vec!["-f", dockerfile_complete_path, "-t", name_with_tag.as_str()]);
+        let docker_args = docker_build_args(
+            &build,
+            self.context.docker_build_options().unwrap_or_default(),
+            dockerfile_complete_path,
+            into_dir_docker_style,
+            env_var_args,
+            use_build_cache,
+            self.use_buildkit,
+        );
 
-        let mut docker_args = if env_var_args.is_empty() {
-            docker_args
-        } else {
-            let mut build_args = vec![];
-            env_var_args.iter().for_each(|x| {
-                build_args.push("--build-arg");
-                build_args.push(x.as_str());
-            });
-
-            docker_args.extend(build_args);
-            docker_args
-        };
+        self.rewrite_base_images(dockerfile_complete_path)?;
+        self.pull_base_images_through_mirror(dockerfile_complete_path, build.image.application_id.as_str(), lh);
 
-        docker_args.push(into_dir_docker_style);
+        let mut docker_envs = self.get_docker_host_envs();
+        if self.use_buildkit {
+            docker_envs.push(("DOCKER_BUILDKIT", "1"));
+        }
 
         // docker build
-        let exit_status = cmd::utilities::exec_with_envs_and_output(
-            "docker",
-            docker_args,
-            self.get_docker_host_envs(),
+        let build_timeout = Duration::seconds(build.options.timeout_in_seconds as i64);
+        let build_start_time = Instant::now();
+        let heartbeat = BuildHeartbeat::start(
+            self.listeners.clone(),
+            build.image.application_id.as_str(),
+            self.context.execution_id(),
+        );
+        let exit_status = docker_build(
+            &docker_args,
+            docker_envs,
             |line| {
                 let line_string = line.unwrap();
                 info!("{}", line_string.as_str());
@@ -131,11 +253,15 @@ impl LocalDocker {
                     self.context.execution_id(),
                 ));
             },
-            Duration::minutes(BUILD_DURATION_TIMEOUT_MIN),
+            build_timeout,
         );
+        drop(heartbeat);
 
         match exit_status {
             Ok(_) => Ok(BuildResult { build }),
+            Err(_) if has_build_timed_out(build_start_time, build_timeout) => {
+                Err(self.build_timeout_error(build_timeout))
+            }
             Err(err) => Err(self.engine_error(
                 EngineErrorCause::User(
                     "It looks like there is something wrong in your Dockerfile. Try building the application locally with `docker build --no-cache`.",
@@ -164,7 +290,22 @@ impl LocalDocker {
         let mut exit_status: Result<Vec<String>, SimpleError> =
             Err(SimpleError::new(SimpleErrorKind::Other, Some("no builder names")));
 
+        let build_timeout = Duration::seconds(build.options.timeout_in_seconds as i64);
+        let build_start_time = Instant::now();
+        let heartbeat = BuildHeartbeat::start(
+            self.listeners.clone(),
+            build.image.application_id.as_str(),
+            self.context.execution_id(),
+        );
+
         for builder_name in BUILDPACKS_BUILDERS.iter() {
+            // builders share the build timeout, each one only gets the time the previous ones left
+            let remaining_build_timeout = remaining_build_timeout(build_start_time, build_timeout);
+            if remaining_build_timeout <= Duration::zero() {
+                break;
+            }
+
+            let builder_name = self.context.rewrite_image(builder_name);
             let mut buildpacks_args = if !use_build_cache {
                 vec!["build", name_with_tag.as_str(), "--clear-cache"]
             } else {
@@ -194,7 +335,7 @@ impl LocalDocker {
             };
 
             buildpacks_args.push("-B");
-            buildpacks_args.push(builder_name);
+            buildpacks_args.push(builder_name.as_str());
 
             // Just a fallback for now to help our bot loving users deploy their apps
             // Long term solution requires lots of changes in UI and Core as well
@@ -237,17 +378,21 @@ impl LocalDocker {
                         self.context.execution_id(),
                     ));
                 },
-                Duration::minutes(BUILD_DURATION_TIMEOUT_MIN),
+                remaining_build_timeout,
             );
 
-            if exit_status.is_ok() {
-                // quit now if the builder successfully build the app
+            if exit_status.is_ok() || has_build_timed_out(build_start_time, build_timeout) {
+                // quit now if the builder successfully build the app or if there is no time left
                 break;
             }
         }
+        drop(heartbeat);
 
         match exit_status {
             Ok(_) => Ok(BuildResult { build }),
+            Err(_) if has_build_timed_out(build_start_time, build_timeout) => {
+                Err(self.build_timeout_error(build_timeout))
+            }
             Err(err) => {
                 warn!("{:?}", err);
 
@@ -267,6 +412,56 @@ impl LocalDocker {
     }
 }
 
+impl LocalDocker {
+    fn full_clone(&self, build: &Build, repository_root_path: &str) -> Result<Repository, EngineError> {
+        info!(
+            "cloning repository: {} to {}",
+            build.git_repository.url, repository_root_path
+        );
+        let git_clone = git::clone(
+            build.git_repository.url.as_str(),
+            repository_root_path,
+            &build.git_repository.credentials,
+        );
+
+        if let Err(err) = git_clone {
+            let message = format!(
+                "Error while cloning repository {}. Error: {:?}",
+                &build.git_repository.url, err
+            );
+            error!("{}", message);
+            return Err(self.engine_error(EngineErrorCause::Internal, message));
+        }
+
+        // git checkout to given commit
+        let repo = git_clone.unwrap();
+        let commit_id = &build.git_repository.commit_id;
+        if let Err(err) = git::checkout(&repo, commit_id, build.git_repository.url.as_str()) {
+            let message = format!(
+                "Error while git checkout repository {} with commit id {}. Error: {:?}",
+                &build.git_repository.url, commit_id, err
+            );
+            error!("{}", message);
+            return Err(self.engine_error(EngineErrorCause::Internal, message));
+        }
+
+        Ok(repo)
+    }
+
+    fn build_timeout_error(&self, build_timeout: Duration) -> EngineError {
+        self.engine_error(
+            EngineErrorCause::User(
+                "Your application build takes too long. Try to reduce the build time or increase the build timeout.",
+            ),
+            format!(
+                "container image {} build has been stopped after exceeding its {} seconds timeout",
+                self.name_with_id(),
+                build_timeout.num_seconds()
+            ),
+        )
+    }
+}
+
 impl BuildPlatform for LocalDocker {
     fn context(&self) -> &Context {
         &self.context
@@ -293,13 +488,27 @@ impl BuildPlatform for LocalDocker {
             return Err(self.engine_error(EngineErrorCause::Internal, String::from("pack binary not found")));
         }
 
+        if let Some(registry_mirror) = &self.registry_mirror {
+            registry_mirror_host(registry_mirror).map_err(|err| {
+                self.engine_error(EngineErrorCause::User("Check the docker registry mirror URL"), err)
+            })?;
+        }
+
         Ok(())
     }
 
     fn build(&self, build: Build, force_build: bool) -> Result<BuildResult, EngineError> {
         info!("LocalDocker.build() called for {}", self.name());
 
-        let listeners_helper = ListenersHelper::new(&self.listeners);
+        let listeners_helper = ListenersHelper::new(&self.listeners).with_secrets(
+            build
+                .options
+                .environment_variables
+                .iter()
+                .filter(|ev| ev.is_secret)
+                .map(|ev| ev.value.clone())
+                .collect(),
+        );
 
         if !force_build && self.image_does_exist(&build.image)? {
             info!(
@@ -310,6 +519,20 @@ impl BuildPlatform for LocalDocker {
             return Ok(BuildResult { build });
         }
 
+        if let Some(credentials) = &build.git_repository.credentials {
+            if credentials.is_expired() {
+                return Err(self.engine_error(
+                    EngineErrorCause::User(
+                        "Your git access token has expired. Please reconnect your git provider account to renew it.",
+                    ),
+                    format!(
+                        "git credentials for repository {} have expired, repository can't be cloned",
+                        build.git_repository.url
+                    ),
+                ));
+            }
+        }
+
         // git clone
         let repository_root_path = workspace_directory(
             self.context.workspace_root_dir(),
@@ -319,35 +542,37 @@ impl BuildPlatform for LocalDocker {
         .map_err(|err| self.engine_error(EngineErrorCause::Internal, err.to_string()))?;
 
         info!(
-            "cloning repository: {} to {}",
-            build.git_repository.url, repository_root_path
+            "shallow cloning repository: {} at commit {} to {}",
+            build.git_repository.url, build.git_repository.commit_id, repository_root_path
         );
-        let git_clone = git::clone(
+        let repo = match git::shallow_clone_commit(
             build.git_repository.url.as_str(),
             &repository_root_path,
             &build.git_repository.credentials,
-        );
-
-        if let Err(err) = git_clone {
-            let message = format!(
-                "Error while cloning repository {}. Error: {:?}",
-                &build.git_repository.url, err
-            );
-            error!("{}", message);
-            return Err(self.engine_error(EngineErrorCause::Internal, message));
-        }
+            build.git_repository.commit_id.as_str(),
+            build.git_repository.branch.as_deref(),
+        ) {
+            Ok(repo) => repo,
+            Err(err) => {
+                let message = format!(
+                    "Commit {} can't be fetched alone from repository {}, falling back to a full clone",
+                    build.git_repository.commit_id, build.git_repository.url
+                );
+                warn!("{}. Error: {:?}", message, err);
+                listeners_helper.deployment_in_progress(ProgressInfo::new(
+                    ProgressScope::Application {
+                        id: build.image.application_id.clone(),
+                    },
+                    ProgressLevel::Warn,
+                    Some(message),
+                    self.context.execution_id(),
+                ));
 
-        // git checkout to given commit
-        let repo = git_clone.unwrap();
-        let commit_id = &build.git_repository.commit_id;
-        if let Err(err) = git::checkout(&repo, commit_id, build.git_repository.url.as_str()) {
-            let message = format!(
-                "Error while git checkout repository {} with commit id {}. Error: {:?}",
-                &build.git_repository.url, commit_id, err
-            );
-            error!("{}", message);
-            return Err(self.engine_error(EngineErrorCause::Internal, message));
-        }
+                // a full clone requires an empty directory
+                let _ = fs::remove_dir_all(&repository_root_path);
+                self.full_clone(&build, repository_root_path.as_str())?
+            }
+        };
 
         // git checkout submodules
         if let Err(err) = checkout_submodules(&repo) {
@@ -499,6 +724,236 @@ impl Listen for LocalDocker {
     }
 }
 
+/// Explicit build args are set after the environment variables ones, so they take precedence.
+/// With BuildKit, cache metadata is embedded into the image so it can be used as cache source once pushed.
+fn docker_build_args(
+    build: &Build,
+    docker_build_options: Vec<String>,
+    dockerfile_complete_path: &str,
+    into_dir_docker_style: &str,
+    env_var_args: Vec<String>,
+    use_build_cache: bool,
+    use_buildkit: bool,
+) -> Vec<String> {
+    let mut docker_args = vec!["build".to_string()];
+    if !use_build_cache {
+        docker_args.push("--no-cache".to_string());
+    }
+
+    docker_args.extend(docker_build_options);
+    docker_args.extend(vec![
+        "-f".to_string(),
+        dockerfile_complete_path.to_string(),
+        "-t".to_string(),
+        build.image.name_with_tag(),
+    ]);
+
+    if let Some(target) = &build.options.dockerfile_target {
+        docker_args.extend(vec!["--target".to_string(), target.clone()]);
+    }
+
+    if use_buildkit {
+        docker_args.extend(vec!["--build-arg".to_string(), "BUILDKIT_INLINE_CACHE=1".to_string()]);
+
+        if let (true, Some(cache_from)) = (use_build_cache, &build.options.cache_from) {
+            docker_args.extend(vec!["--cache-from".to_string(), cache_from.clone()]);
+        }
+    }
+
+    let build_args = env_var_args.into_iter().chain(
+        build
+            .options
+            .build_args
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value)),
+    );
+    for build_arg in build_args {
+        docker_args.extend(vec!["--build-arg".to_string(), build_arg]);
+    }
+
+    docker_args.push(into_dir_docker_style.to_string());
+    docker_args
+}
+
+/// Host (and port) of the registry mirror, the URL is rejected if it is not a plain http(s) registry URL.
+fn registry_mirror_host(registry_mirror_url: &str) -> Result<String, StringError> {
+    let url = reqwest::Url::parse(registry_mirror_url)
+        .map_err(|err| format!("registry mirror URL {} is invalid: {}", registry_mirror_url, err))?;
+
+    if !["http", "https"].contains(&url.scheme()) || !["", "/"].contains(&url.path()) {
+        return Err(format!(
+            "registry mirror URL {} must be an http(s) URL without path",
+            registry_mirror_url
+        ));
+    }
+
+    match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => Ok(format!("{}:{}", host, port)),
+        (Some(host), None) => Ok(host.to_string()),
+        (None, _) => Err(format!("registry mirror URL {} has no host", registry_mirror_url)),
+    }
+}
+
+/// Image a `FROM` instruction line builds a stage from, `None` for other lines, `scratch`, previous stages
+/// and images named by build arguments. The stage it names is added to `stages`.
+fn from_instruction_image<'a>(line: &'a str, stages: &mut Vec<String>) -> Option<&'a str> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    if words.first().map(|word| word.eq_ignore_ascii_case("FROM")) != Some(true) {
+        return None;
+    }
+
+    let mut arguments = words[1..].iter().filter(|word| !word.starts_with("--"));
+    let image = *arguments.next()?;
+    if let (Some(keyword), Some(stage)) = (arguments.next(), arguments.next()) {
+        if keyword.eq_ignore_ascii_case("AS") {
+            stages.push(stage.to_lowercase());
+        }
+    }
+
+    if image.contains('$') || image.eq_ignore_ascii_case("scratch") || stages.contains(&image.to_lowercase()) {
+        return None;
+    }
+
+    Some(image)
+}
+
+/// Docker Hub images the Dockerfile stages are built from, tagged `latest` when no tag is set.
+fn dockerhub_base_images(dockerfile: &str) -> Vec<String> {
+    let mut stages: Vec<String> = vec![];
+    let mut images: Vec<String> = vec![];
+
+    for line in dockerfile.lines() {
+        let image = match from_instruction_image(line, &mut stages) {
+            // images from other registries have a host as first path component
+            Some(image) if image_registry_host(image).is_none() => image,
+            _ => continue,
+        };
+
+        let has_tag = image.contains('@') || image.rsplit('/').next().unwrap_or_default().contains(':');
+        let image = match has_tag {
+            true => image.to_string(),
+            false => format!("{}:latest", image),
+        };
+
+        if !images.contains(&image) {
+            images.push(image);
+        }
+    }
+
+    images
+}
+
+/// Dockerfile with the images its stages are built from replaced by `rewrite`, other lines are kept as is.
+fn rewrite_dockerfile_base_images<F>(dockerfile: &str, rewrite: F) -> String
+where
+    F: Fn(&str) -> String,
+{
+    let mut stages: Vec<String> = vec![];
+    let mut rewritten_dockerfile = dockerfile
+        .lines()
+        .map(|line| match from_instruction_image(line, &mut stages) {
+            Some(image) => {
+                // the image is the first whole word matching it, flags before it can't contain it alone
+                let position = line
+                    .match_indices(image)
+                    .map(|(position, _)| position)
+                    .find(|position| {
+                        let after = &line[position + image.len()..];
+                        line[..*position].ends_with(char::is_whitespace)
+                            && (after.is_empty() || after.starts_with(char::is_whitespace))
+                    })
+                    .unwrap_or_default();
+
+                format!(
+                    "{}{}{}",
+                    &line[..position],
+                    rewrite(image),
+                    &line[position + image.len()..]
+                )
+            }
+            None => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if dockerfile.ends_with('\n') {
+        rewritten_dockerfile.push('\n');
+    }
+
+    rewritten_dockerfile
+}
+
+/// Official images live under `library/` in the registries.
+fn mirror_image_reference(registry_mirror_host: &str, image: &str) -> String {
+    match image.contains('/') {
+        true => format!("{}/{}", registry_mirror_host, image),
+        false => format!("{}/library/{}", registry_mirror_host, image),
+    }
+}
+
+fn has_build_timed_out(build_start_time: Instant, build_timeout: Duration) -> bool {
+    remaining_build_timeout(build_start_time, build_timeout) <= Duration::zero()
+}
+
+/// Time left before the build times out, zero once it has.
+fn remaining_build_timeout(build_start_time: Instant, build_timeout: Duration) -> Duration {
+    let elapsed = Duration::from_std(build_start_time.elapsed()).unwrap_or_else(|_| Duration::max_value());
+
+    build_timeout
+        .checked_sub(&elapsed)
+        .unwrap_or_else(Duration::zero)
+        .max(Duration::zero())
+}
+
+/// Notifies listeners at regular intervals while a build is running, until dropped,
+/// so users know a build that doesn't output anything is still in progress.
+struct BuildHeartbeat {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl BuildHeartbeat {
+    fn start(listeners: Listeners, application_id: &str, execution_id: &str) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let application_id = application_id.to_string();
+        let execution_id = execution_id.to_string();
+
+        let thread = thread::spawn(move || {
+            let start_time = Instant::now();
+            let interval = std::time::Duration::from_secs(BUILD_PROGRESS_INTERVAL_SEC);
+
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                ListenersHelper::new(&listeners).deployment_in_progress(ProgressInfo::new(
+                    ProgressScope::Application {
+                        id: application_id.clone(),
+                    },
+                    ProgressLevel::Info,
+                    Some(format!(
+                        "container build is still in progress ({}s elapsed)",
+                        start_time.elapsed().as_secs()
+                    )),
+                    execution_id.as_str(),
+                ));
+            }
+        });
+
+        BuildHeartbeat {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for BuildHeartbeat {
+    fn drop(&mut self) {
+        self.stop.take();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
 fn check_docker_space_usage_and_clean(
     docker_path_size_info: &Disk,
     envs: Vec<(&str, &str)>,
@@ -560,3 +1015,247 @@ fn docker_prune_images(envs: Vec<(&str, &str)>) -> Result<(), SimpleError> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::build_platform::local_docker::{
+        docker_build_args, dockerhub_base_images, has_build_timed_out, mirror_image_reference, registry_mirror_host,
+        remaining_build_timeout, rewrite_dockerfile_base_images, LocalDocker,
+    };
+    use crate::build_platform::{Build, BuildOptions, BuildPlatform, GitRepository, Image};
+    use crate::error::EngineErrorCause;
+    use crate::git::Credentials;
+    use crate::models::Context;
+    use chrono::{Duration, Utc};
+    use std::collections::BTreeMap;
+    use std::time::Instant;
+
+    #[test]
+    fn test_remaining_build_timeout() {
+        // setup:
+        let build_start_time = Instant::now() - std::time::Duration::from_secs(90);
+
+        // execute:
+        let remaining = remaining_build_timeout(build_start_time, Duration::minutes(10));
+        let exhausted = remaining_build_timeout(build_start_time, Duration::minutes(1));
+
+        // verify:
+        assert!(remaining <= Duration::seconds(510) && remaining > Duration::seconds(500));
+        assert!(!has_build_timed_out(build_start_time, Duration::minutes(10)));
+        assert_eq!(exhausted, Duration::zero());
+        assert!(has_build_timed_out(build_start_time, Duration::minutes(1)));
+    }
+
+    #[test]
+    fn test_registry_mirror_base_images() {
+        // setup:
+        let dockerfile = r#"
+ARG RUST_VERSION=1.53
+FROM rust:${RUST_VERSION} as builder
+FROM --platform=linux/amd64 node:14-alpine AS front
+FROM front AS assets
+FROM bitnami/redis
+FROM ghcr.io/qovery/engine:1.0
+from debian@sha256:0123456789abcdef
+FROM scratch
+FROM node:14-alpine
+"#;
+
+        // execute:
+        let images = dockerhub_base_images(dockerfile);
+
+        // verify:
+        assert_eq!(
+            images,
+            vec![
+                "node:14-alpine",
+                "bitnami/redis:latest",
+                "debian@sha256:0123456789abcdef"
+            ]
+        );
+        assert_eq!(
+            mirror_image_reference("mirror.gcr.io", "node:14-alpine"),
+            "mirror.gcr.io/library/node:14-alpine"
+        );
+        assert_eq!(
+            mirror_image_reference("localhost:5000", "bitnami/redis:latest"),
+            "localhost:5000/bitnami/redis:latest"
+        );
+        assert_eq!(registry_mirror_host("https://mirror.gcr.io").unwrap(), "mirror.gcr.io");
+        assert_eq!(
+            registry_mirror_host("http://localhost:5000/").unwrap(),
+            "localhost:5000"
+        );
+        assert!(registry_mirror_host("mirror.gcr.io").is_err());
+        assert!(registry_mirror_host("https://mirror.gcr.io/v2/library").is_err());
+        assert!(registry_mirror_host("ftp://mirror.gcr.io").is_err());
+    }
+
+    #[test]
+    fn test_rewrite_dockerfile_base_images() {
+        // setup:
+        let mut rewrites = BTreeMap::new();
+        rewrites.insert("docker.io".to_string(), "registry.internal/dockerhub".to_string());
+        let context = Context::new(
+            "execution-id".to_string(),
+            "/tmp".to_string(),
+            "lib".to_string(),
+            false,
+            None,
+            vec![],
+            None,
+        )
+        .with_image_registry_rewrites(rewrites);
+        let dockerfile = r#"ARG RUST_VERSION=1.53
+FROM rust:${RUST_VERSION} as builder
+FROM --platform=linux/amd64 node:14-alpine AS node
+FROM node AS assets
+COPY --from=node /app /app
+FROM debian@sha256:0123456789abcdef
+FROM registry.internal/dockerhub/library/nginx:1.21
+FROM scratch
+"#;
+
+        // execute:
+        let rewritten_dockerfile = rewrite_dockerfile_base_images(dockerfile, |image| context.rewrite_image(image));
+
+        // verify:
+        assert_eq!(
+            rewritten_dockerfile,
+            r#"ARG RUST_VERSION=1.53
+FROM rust:${RUST_VERSION} as builder
+FROM --platform=linux/amd64 registry.internal/dockerhub/library/node:14-alpine AS node
+FROM node AS assets
+COPY --from=node /app /app
+FROM registry.internal/dockerhub/library/debian@sha256:0123456789abcdef
+FROM registry.internal/dockerhub/library/nginx:1.21
+FROM scratch
+"#
+        );
+    }
+
+    #[test]
+    fn test_docker_build_args() {
+        // setup:
+        let mut build_args = BTreeMap::new();
+        build_args.insert("NODE_ENV".to_string(), "production".to_string());
+        let build = Build {
+            git_repository: GitRepository {
+                url: "https://github.com/Qovery/engine-testing.git".to_string(),
+                credentials: None,
+                commit_id: "c3dad2bd2b3e5f8d2f0d9b0e5a8dc9d3f4f5d1f6".to_string(),
+                branch: None,
+                dockerfile_path: Some("Dockerfile".to_string()),
+                root_path: "/".to_string(),
+            },
+            image: Image {
+                name: "app".to_string(),
+                tag: "v1".to_string(),
+                ..Default::default()
+            },
+            options: BuildOptions {
+                timeout_in_seconds: 60,
+                environment_variables: vec![],
+                build_args,
+                dockerfile_target: Some("release".to_string()),
+                cache_from: Some("registry.qovery.io/app:build-cache".to_string()),
+            },
+        };
+
+        // execute:
+        let args = docker_build_args(
+            &build,
+            vec![],
+            "/tmp/app/Dockerfile",
+            "/tmp/app/.",
+            vec!["PORT=8080".to_string()],
+            false,
+            false,
+        );
+        let buildkit_args = docker_build_args(&build, vec![], "/tmp/app/Dockerfile", "/tmp/app/.", vec![], true, true);
+
+        // verify:
+        assert_eq!(
+            args,
+            vec![
+                "build",
+                "--no-cache",
+                "-f",
+                "/tmp/app/Dockerfile",
+                "-t",
+                "app:v1",
+                "--target",
+                "release",
+                "--build-arg",
+                "PORT=8080",
+                "--build-arg",
+                "NODE_ENV=production",
+                "/tmp/app/.",
+            ]
+        );
+        assert_eq!(
+            buildkit_args,
+            vec![
+                "build",
+                "-f",
+                "/tmp/app/Dockerfile",
+                "-t",
+                "app:v1",
+                "--target",
+                "release",
+                "--build-arg",
+                "BUILDKIT_INLINE_CACHE=1",
+                "--cache-from",
+                "registry.qovery.io/app:build-cache",
+                "--build-arg",
+                "NODE_ENV=production",
+                "/tmp/app/.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_with_expired_git_credentials() {
+        // setup:
+        let context = Context::new(
+            "execution-id".to_string(),
+            "/tmp/qovery-workspace".to_string(),
+            "/tmp/qovery-lib".to_string(),
+            false,
+            None,
+            vec![],
+            None,
+        );
+        let local_docker = LocalDocker::new(context, "local-docker-id", "local-docker");
+        let build = Build {
+            git_repository: GitRepository {
+                url: "https://github.com/Qovery/private-repository.git".to_string(),
+                credentials: Some(Credentials {
+                    login: "x-access-token".to_string(),
+                    password: "secret-token".to_string(),
+                    expired_at: Some(Utc::now() - Duration::minutes(5)),
+                }),
+                commit_id: "c3dad2bd2b3e5f8d2f0d9b0e5a8dc9d3f4f5d1f6".to_string(),
+                branch: None,
+                dockerfile_path: Some("Dockerfile".to_string()),
+                root_path: "/".to_string(),
+            },
+            image: Image::default(),
+            options: BuildOptions {
+                timeout_in_seconds: 60,
+                environment_variables: vec![],
+                build_args: BTreeMap::new(),
+                dockerfile_target: None,
+                cache_from: None,
+            },
+        };
+
+        // execute:
+        let result = local_docker.build(build, true);
+
+        // verify:
+        let error = result.err().expect("build with expired credentials should fail");
+        assert!(matches!(error.cause, EngineErrorCause::User(_)));
+        assert!(!error.message.unwrap_or_default().contains("secret-token"));
+    }
+}
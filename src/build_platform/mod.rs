@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 use crate::error::{EngineError, EngineErrorCause, EngineErrorScope};
@@ -37,18 +39,36 @@ pub struct Build {
 }
 
 pub struct BuildOptions {
+    pub timeout_in_seconds: u32,
     pub environment_variables: Vec<EnvironmentVariable>,
+    pub build_args: BTreeMap<String, String>,
+    pub dockerfile_target: Option<String>,
+    // previously pushed image to use as build cache: Optional
+    pub cache_from: Option<String>,
+}
+
+pub const DEFAULT_BUILD_TIMEOUT_IN_SECONDS: u32 = 30 * 60;
+
+/// A build specific timeout wins, otherwise the application start timeout is used if it's longer than the default one.
+pub fn build_timeout_in_seconds(build_timeout_in_seconds: Option<u32>, start_timeout_in_seconds: u32) -> u32 {
+    match build_timeout_in_seconds {
+        Some(timeout) if timeout > 0 => timeout,
+        _ => start_timeout_in_seconds.max(DEFAULT_BUILD_TIMEOUT_IN_SECONDS),
+    }
 }
 
 pub struct EnvironmentVariable {
     pub key: String,
     pub value: String,
+    pub is_secret: bool,
 }
 
 pub struct GitRepository {
     pub url: String,
     pub credentials: Option<Credentials>,
     pub commit_id: String,
+    // branch the commit belongs to: Optional
+    pub branch: Option<String>,
     pub dockerfile_path: Option<String>,
     pub root_path: String,
 }
@@ -105,3 +125,16 @@ impl BuildResult {
 pub enum Kind {
     LocalDocker,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::build_platform::{build_timeout_in_seconds, DEFAULT_BUILD_TIMEOUT_IN_SECONDS};
+
+    #[test]
+    fn test_build_timeout_in_seconds() {
+        assert_eq!(build_timeout_in_seconds(Some(120), 60), 120);
+        assert_eq!(build_timeout_in_seconds(None, 60), DEFAULT_BUILD_TIMEOUT_IN_SECONDS);
+        assert_eq!(build_timeout_in_seconds(Some(0), 60), DEFAULT_BUILD_TIMEOUT_IN_SECONDS);
+        assert_eq!(build_timeout_in_seconds(None, 3600), 3600);
+    }
+}
@@ -1,22 +1,26 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use tera::Context as TeraContext;
 
+use crate::cloud_provider::aws::databases::utilities::get_managed_database_version;
 use crate::cloud_provider::environment::Kind;
 use crate::cloud_provider::service::{
-    check_service_version, default_tera_context, delete_stateful_service, deploy_stateful_service, get_tfstate_name,
-    get_tfstate_suffix, scale_down_database, send_progress_on_long_task, Action, Backup, Create, Database,
-    DatabaseOptions, DatabaseType, Delete, Downgrade, Helm, Pause, Service, ServiceType, StatefulService, Terraform,
-    Upgrade,
+    check_service_version, clone_self_hosted_database, default_tera_context, delete_stateful_service,
+    deploy_stateful_service, get_self_hosted_database_disk_size, get_self_hosted_database_version, get_tfstate_name,
+    get_tfstate_suffix, insert_database_node_pool_into_tera_context, insert_database_resources_into_tera_context,
+    insert_resource_expiration_into_tera_context, insert_terraform_backend_into_tera_context, resolve_secret,
+    scale_down_database, send_progress_on_long_task, stateful_service_template_directories,
+    unsupported_database_clone_error, Action, Backup, Create, Database, DatabaseOptions, DatabaseType, Delete,
+    Downgrade, Helm, Pause, Service, ServiceType, StatefulService, Terraform, Upgrade,
 };
 use crate::cloud_provider::utilities::{
-    generate_supported_version, get_self_hosted_mongodb_version, get_supported_version_to_use,
+    generate_supported_version, get_self_hosted_mongodb_version, get_supported_version_to_use_with_extra_versions,
 };
 use crate::cloud_provider::DeploymentTarget;
 use crate::cmd::helm::Timeout;
 use crate::cmd::kubectl;
-use crate::error::{EngineError, EngineErrorScope, StringError};
-use crate::models::{Context, Listen, Listener, Listeners};
+use crate::error::{cast_simple_error_to_engine_error, EngineError, EngineErrorCause, EngineErrorScope, StringError};
+use crate::models::{Context, DatabaseKind, Listen, Listener, Listeners};
 
 pub struct MongoDB {
     context: Context,
@@ -65,11 +69,23 @@ impl MongoDB {
     }
 
     fn matching_correct_version(&self, is_managed_services: bool) -> Result<String, EngineError> {
-        check_service_version(get_mongodb_version(self.version(), is_managed_services), self)
+        check_service_version(self.supported_version(is_managed_services), self)
     }
 }
 
-impl StatefulService for MongoDB {}
+impl StatefulService for MongoDB {
+    fn is_deletion_protected(&self) -> bool {
+        self.options.deletion_protection
+    }
+
+    fn supported_version(&self, is_managed_services: bool) -> Result<String, StringError> {
+        get_mongodb_version(
+            self.version(),
+            is_managed_services,
+            &self.context.extra_supported_database_versions("DocumentDB"),
+        )
+    }
+}
 
 impl Service for MongoDB {
     fn context(&self) -> &Context {
@@ -104,6 +120,30 @@ impl Service for MongoDB {
         self.version.as_str()
     }
 
+    fn deployed_version(&self, target: &DeploymentTarget) -> Result<Option<String>, EngineError> {
+        match target {
+            DeploymentTarget::ManagedServices(kubernetes, _) => cast_simple_error_to_engine_error(
+                self.engine_error_scope(),
+                self.context.execution_id(),
+                get_managed_database_version(
+                    DatabaseKind::Mongodb,
+                    self.fqdn_id.as_str(),
+                    kubernetes.region(),
+                    kubernetes.cloud_provider().credentials_environment_variables(),
+                ),
+            ),
+            DeploymentTarget::SelfHosted(..) => get_self_hosted_database_version(target, self),
+        }
+    }
+
+    fn deployed_disk_size_in_gib(&self, target: &DeploymentTarget) -> Result<Option<u32>, EngineError> {
+        match target {
+            // DocumentDB storage isn't allocated, it grows on its own
+            DeploymentTarget::ManagedServices(..) => Ok(None),
+            DeploymentTarget::SelfHosted(..) => get_self_hosted_database_disk_size(target, self),
+        }
+    }
+
     fn action(&self) -> &Action {
         &self.action
     }
@@ -112,8 +152,20 @@ impl Service for MongoDB {
         Some(self.options.port)
     }
 
+    fn endpoints(&self) -> Vec<String> {
+        vec![format!("{}:{}", self.fqdn, self.options.port)]
+    }
+
     fn start_timeout(&self) -> Timeout<u32> {
-        Timeout::Default
+        Timeout::Value(self.readiness_timeout().as_secs() as u32)
+    }
+
+    fn deploy_timeout(&self) -> Option<std::time::Duration> {
+        self.options.deploy_timeout()
+    }
+
+    fn custom_namespace(&self) -> Option<&str> {
+        self.options.namespace.as_deref()
     }
 
     fn total_cpus(&self) -> String {
@@ -132,6 +184,10 @@ impl Service for MongoDB {
         1
     }
 
+    fn template_directories(&self, target: &DeploymentTarget) -> Vec<String> {
+        stateful_service_template_directories(self, target)
+    }
+
     fn tera_context(&self, target: &DeploymentTarget) -> Result<TeraContext, EngineError> {
         let (kubernetes, environment) = match target {
             DeploymentTarget::ManagedServices(k, env) => (*k, *env),
@@ -150,12 +206,12 @@ impl Service for MongoDB {
         context.insert("kubeconfig_path", &kube_config_file_path);
 
         kubectl::kubectl_exec_create_namespace_without_labels(
-            &environment.namespace(),
+            self.namespace(environment),
             kube_config_file_path.as_str(),
             kubernetes.cloud_provider().credentials_environment_variables(),
         );
 
-        context.insert("namespace", environment.namespace());
+        context.insert("namespace", self.namespace(environment));
 
         let version = self.matching_correct_version(is_managed_services)?;
         context.insert("version", &version);
@@ -172,25 +228,49 @@ impl Service for MongoDB {
 
         context.insert("database_db_name", self.name.as_str());
         context.insert("database_login", self.options.login.as_str());
-        context.insert("database_password", self.options.password.as_str());
+        context.insert(
+            "database_password",
+            resolve_secret(self, self.options.password.as_str())?.as_str(),
+        );
         context.insert("database_port", &self.private_port());
         context.insert("database_disk_size_in_gib", &self.options.disk_size_in_gib);
         context.insert("database_instance_type", &self.database_instance_type);
         context.insert("database_disk_type", &self.options.database_disk_type);
         context.insert("database_ram_size_in_mib", &self.total_ram_in_mib);
         context.insert("database_total_cpus", &self.total_cpus);
+
+        match self.options.resources(self.total_cpus.as_str(), self.total_ram_in_mib) {
+            Ok(resources) => insert_database_resources_into_tera_context(&resources, &mut context),
+            Err(e) => {
+                return Err(self.engine_error(
+                    EngineErrorCause::User("Check the resources overcommit of the database"),
+                    e,
+                ))
+            }
+        }
+        if let Err(e) = insert_database_node_pool_into_tera_context(
+            &self.options,
+            &kubernetes.node_pools_scheduling(),
+            &mut context,
+        ) {
+            return Err(self.engine_error(
+                EngineErrorCause::User("Check the node pool label the database is pinned to"),
+                e,
+            ));
+        }
         context.insert("database_fqdn", &self.options.host.as_str());
         context.insert("database_id", &self.id());
         context.insert("tfstate_suffix_name", &get_tfstate_suffix(self));
         context.insert("tfstate_name", &get_tfstate_name(self));
+        insert_terraform_backend_into_tera_context(
+            kubernetes.cloud_provider().terraform_state_credentials(),
+            self.namespace(environment),
+            &get_tfstate_name(self),
+            &mut context,
+        );
 
         context.insert("skip_final_snapshot", &self.context().is_test_cluster());
-        if self.context.resource_expiration_in_seconds().is_some() {
-            context.insert(
-                "resource_expiration_in_seconds",
-                &self.context.resource_expiration_in_seconds(),
-            )
-        }
+        insert_resource_expiration_into_tera_context(&self.context, &mut context);
 
         Ok(context)
     }
@@ -226,6 +306,10 @@ impl Helm for MongoDB {
     fn helm_chart_external_name_service_dir(&self) -> String {
         format!("{}/common/charts/external-name-svc", self.context.lib_root_dir())
     }
+
+    fn helm_value_overrides(&self) -> BTreeMap<String, String> {
+        self.options.helm_value_overrides.clone()
+    }
 }
 
 impl Terraform for MongoDB {
@@ -297,16 +381,27 @@ impl Delete for MongoDB {
 }
 
 impl crate::cloud_provider::service::Clone for MongoDB {
-    fn on_clone(&self, _target: &DeploymentTarget) -> Result<(), EngineError> {
-        unimplemented!()
+    fn on_clone(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        info!("AWS.MongoDB.on_clone() called for {}", self.name());
+
+        match target {
+            DeploymentTarget::SelfHosted(..) => {
+                send_progress_on_long_task(self, crate::cloud_provider::service::Action::Create, || {
+                    clone_self_hosted_database(target, self)
+                })
+            }
+            DeploymentTarget::ManagedServices(..) => Err(unsupported_database_clone_error(self)),
+        }
     }
 
     fn on_clone_check(&self) -> Result<(), EngineError> {
-        unimplemented!()
+        self.on_create_check()
     }
 
     fn on_clone_error(&self, _target: &DeploymentTarget) -> Result<(), EngineError> {
-        unimplemented!()
+        warn!("AWS.MongoDB.on_clone_error() called for {}", self.name());
+
+        Ok(())
     }
 }
 
@@ -374,15 +469,22 @@ impl Listen for MongoDB {
     }
 }
 
-fn get_mongodb_version(requested_version: &str, is_managed_service: bool) -> Result<String, StringError> {
+fn get_mongodb_version(
+    requested_version: &str,
+    is_managed_service: bool,
+    extra_supported_versions: &HashMap<String, String>,
+) -> Result<String, StringError> {
     if is_managed_service {
-        get_managed_mongodb_version(requested_version)
+        get_managed_mongodb_version(requested_version, extra_supported_versions)
     } else {
         get_self_hosted_mongodb_version(requested_version)
     }
 }
 
-fn get_managed_mongodb_version(requested_version: &str) -> Result<String, StringError> {
+fn get_managed_mongodb_version(
+    requested_version: &str,
+    extra_supported_versions: &HashMap<String, String>,
+) -> Result<String, StringError> {
     let mut supported_mongodb_versions = HashMap::new();
 
     // v3.6.0
@@ -393,7 +495,12 @@ fn get_managed_mongodb_version(requested_version: &str) -> Result<String, String
     let mongo_version = generate_supported_version(4, 0, 0, Some(0), Some(0), None);
     supported_mongodb_versions.extend(mongo_version);
 
-    get_supported_version_to_use("DocumentDB", supported_mongodb_versions, requested_version)
+    get_supported_version_to_use_with_extra_versions(
+        "DocumentDB",
+        supported_mongodb_versions,
+        extra_supported_versions,
+        requested_version,
+    )
 }
 
 #[cfg(test)]
@@ -401,21 +508,22 @@ mod tests_mongodb {
     use crate::cloud_provider::aws::databases::mongodb::{get_mongodb_version, MongoDB};
     use crate::cloud_provider::service::{Action, DatabaseOptions, Service};
     use crate::models::Context;
+    use std::collections::{BTreeMap, HashMap};
 
     #[test]
     fn check_mongodb_version() {
         // managed version
-        assert_eq!(get_mongodb_version("4", true).unwrap(), "4.0.0");
-        assert_eq!(get_mongodb_version("4.0", true).unwrap(), "4.0.0");
+        assert_eq!(get_mongodb_version("4", true, &HashMap::new()).unwrap(), "4.0.0");
+        assert_eq!(get_mongodb_version("4.0", true, &HashMap::new()).unwrap(), "4.0.0");
         assert_eq!(
-            get_mongodb_version("4.4", true).unwrap_err().as_str(),
+            get_mongodb_version("4.4", true, &HashMap::new()).unwrap_err().as_str(),
             "DocumentDB 4.4 version is not supported"
         );
         // self-hosted version
-        assert_eq!(get_mongodb_version("4", false).unwrap(), "4.4.4");
-        assert_eq!(get_mongodb_version("4.2", false).unwrap(), "4.2.12");
+        assert_eq!(get_mongodb_version("4", false, &HashMap::new()).unwrap(), "4.4.4");
+        assert_eq!(get_mongodb_version("4.2", false, &HashMap::new()).unwrap(), "4.2.12");
         assert_eq!(
-            get_mongodb_version("3.4", false).unwrap_err().as_str(),
+            get_mongodb_version("3.4", false, &HashMap::new()).unwrap_err().as_str(),
             "MongoDB 3.4 version is not supported"
         );
     }
@@ -451,6 +559,23 @@ mod tests_mongodb {
                 port: 5432,
                 disk_size_in_gib: 10,
                 database_disk_type: "gp2".to_string(),
+                replicas: 0,
+                iops: None,
+                storage_throughput: None,
+                deletion_protection: false,
+                sentinel_enabled: false,
+                resources_overcommit_percent: None,
+                parameters: BTreeMap::new(),
+                require_tls: false,
+                tls_certificates: None,
+                deletion_timeout_in_seconds: None,
+                helm_value_overrides: BTreeMap::new(),
+                deploy_timeout_in_seconds: None,
+                namespace: None,
+                clone_from: None,
+                backup_retention_days: None,
+                backup_window: None,
+                node_pool_label: None,
             },
             vec![],
         );
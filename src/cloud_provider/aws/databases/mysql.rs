@@ -1,23 +1,34 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use tera::Context as TeraContext;
 
-use crate::cloud_provider::aws::databases::utilities::{get_parameter_group_from_version, rds_name_sanitizer};
+use crate::cloud_provider::aws::databases::utilities::{
+    create_managed_database_snapshot, get_managed_database_disk_size, get_managed_database_version,
+    get_parameter_group_from_version, rds_clone_snapshot_identifier, rds_name_sanitizer,
+    resize_managed_database_storage,
+};
 use crate::cloud_provider::environment::Kind;
+use crate::cloud_provider::kubernetes::Kubernetes;
 use crate::cloud_provider::service::{
-    check_service_version, default_tera_context, delete_stateful_service, deploy_stateful_service, get_tfstate_name,
-    get_tfstate_suffix, scale_down_database, send_progress_on_long_task, Action, Backup, Create, Database,
-    DatabaseOptions, DatabaseType, Delete, Downgrade, Helm, Pause, Service, ServiceType, StatefulService, Terraform,
-    Upgrade,
+    check_service_version, clone_self_hosted_database, default_tera_context, delete_stateful_service,
+    deploy_stateful_service_with_outputs, get_self_hosted_database_disk_size, get_self_hosted_database_version,
+    get_tfstate_name, get_tfstate_suffix, insert_database_backups_into_tera_context,
+    insert_database_node_pool_into_tera_context, insert_database_resources_into_tera_context,
+    insert_database_tls_into_tera_context, insert_resource_expiration_into_tera_context,
+    insert_terraform_backend_into_tera_context, resize_self_hosted_database_storage, resolve_secret,
+    scale_down_database, send_progress_on_long_task, stateful_service_template_directories, Action, Backup, Create,
+    Database, DatabaseOptions, DatabaseType, Delete, Downgrade, Helm, Pause, Service, ServiceType, StatefulService,
+    Terraform, Upgrade,
 };
 use crate::cloud_provider::utilities::{
-    generate_supported_version, get_self_hosted_mysql_version, get_supported_version_to_use,
+    generate_supported_version, get_self_hosted_mysql_version, get_supported_version_to_use_with_extra_versions,
 };
 use crate::cloud_provider::DeploymentTarget;
 use crate::cmd::helm::Timeout;
 use crate::cmd::kubectl;
-use crate::error::{EngineError, EngineErrorCause, EngineErrorScope, StringError};
-use crate::models::{Context, DatabaseKind, Listen, Listener, Listeners};
+use crate::error::{cast_simple_error_to_engine_error, EngineError, EngineErrorCause, EngineErrorScope, StringError};
+use crate::models::{Context, DatabaseKind, Listen, Listener, Listeners, ListenersHelper, ProgressInfo, ProgressLevel};
 
 pub struct MySQL {
     context: Context,
@@ -32,6 +43,8 @@ pub struct MySQL {
     database_instance_type: String,
     options: DatabaseOptions,
     listeners: Listeners,
+    /// Set once the snapshot a clone is restored from has been created.
+    clone_snapshot_created: AtomicBool,
 }
 
 impl MySQL {
@@ -62,15 +75,92 @@ impl MySQL {
             database_instance_type: database_instance_type.to_string(),
             options,
             listeners,
+            clone_snapshot_created: AtomicBool::new(false),
         }
     }
 
+    fn connection_string(&self) -> String {
+        self.options.mysql_connection_string(self.sanitized_name().as_str())
+    }
+
     fn matching_correct_version(&self, is_managed_services: bool) -> Result<String, EngineError> {
-        check_service_version(get_mysql_version(self.version(), is_managed_services), self)
+        check_service_version(self.supported_version(is_managed_services), self)
+    }
+
+    /// Snapshot the managed instance is restored from when it's a clone.
+    fn clone_snapshot_identifier(&self) -> Option<String> {
+        self.clone_source()
+            .map(|source| rds_clone_snapshot_identifier(source.fqdn_id.as_str(), self.fqdn_id.as_str()))
+    }
+
+    fn create_clone_snapshot(&self, kubernetes: &dyn Kubernetes) -> Result<(), EngineError> {
+        let (source, snapshot_identifier) = match (self.clone_source(), self.clone_snapshot_identifier()) {
+            (Some(source), Some(snapshot_identifier)) => (source, snapshot_identifier),
+            _ => return Ok(()),
+        };
+
+        cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context.execution_id(),
+            create_managed_database_snapshot(
+                DatabaseKind::Mysql,
+                source.fqdn_id.as_str(),
+                snapshot_identifier.as_str(),
+                kubernetes.region(),
+                kubernetes.cloud_provider().credentials_environment_variables(),
+            ),
+        )?;
+        self.clone_snapshot_created.store(true, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    /// Snapshot to render in the terraform instance: the one just created for a first deployment, or the one
+    /// an already deployed clone has been restored from, removing it would make terraform replace the instance.
+    fn clone_snapshot_to_restore(&self, target: &DeploymentTarget) -> Result<Option<String>, EngineError> {
+        let snapshot_identifier = match self.clone_snapshot_identifier() {
+            Some(snapshot_identifier) => snapshot_identifier,
+            None => return Ok(None),
+        };
+
+        if self.clone_snapshot_created.load(Ordering::SeqCst) || self.deployed_version(target)?.is_some() {
+            return Ok(Some(snapshot_identifier));
+        }
+
+        Ok(None)
     }
 }
 
-impl StatefulService for MySQL {}
+impl StatefulService for MySQL {
+    fn is_deletion_protected(&self) -> bool {
+        self.options.deletion_protection
+    }
+
+    fn supported_version(&self, is_managed_services: bool) -> Result<String, StringError> {
+        get_mysql_version(
+            self.version(),
+            is_managed_services,
+            &self.context.extra_supported_database_versions("RDS MySQL"),
+        )
+    }
+
+    fn resize_storage(&self, target: &DeploymentTarget, new_size_gib: u32) -> Result<(), EngineError> {
+        match target {
+            DeploymentTarget::ManagedServices(kubernetes, _) => cast_simple_error_to_engine_error(
+                self.engine_error_scope(),
+                self.context.execution_id(),
+                resize_managed_database_storage(
+                    DatabaseKind::Mysql,
+                    self.fqdn_id.as_str(),
+                    kubernetes.region(),
+                    new_size_gib,
+                    kubernetes.cloud_provider().credentials_environment_variables(),
+                ),
+            ),
+            DeploymentTarget::SelfHosted(..) => resize_self_hosted_database_storage(target, self, new_size_gib),
+        }
+    }
+}
 
 impl Service for MySQL {
     fn context(&self) -> &Context {
@@ -100,6 +190,38 @@ impl Service for MySQL {
         self.version.as_str()
     }
 
+    fn deployed_version(&self, target: &DeploymentTarget) -> Result<Option<String>, EngineError> {
+        match target {
+            DeploymentTarget::ManagedServices(kubernetes, _) => cast_simple_error_to_engine_error(
+                self.engine_error_scope(),
+                self.context.execution_id(),
+                get_managed_database_version(
+                    DatabaseKind::Mysql,
+                    self.fqdn_id.as_str(),
+                    kubernetes.region(),
+                    kubernetes.cloud_provider().credentials_environment_variables(),
+                ),
+            ),
+            DeploymentTarget::SelfHosted(..) => get_self_hosted_database_version(target, self),
+        }
+    }
+
+    fn deployed_disk_size_in_gib(&self, target: &DeploymentTarget) -> Result<Option<u32>, EngineError> {
+        match target {
+            DeploymentTarget::ManagedServices(kubernetes, _) => cast_simple_error_to_engine_error(
+                self.engine_error_scope(),
+                self.context.execution_id(),
+                get_managed_database_disk_size(
+                    DatabaseKind::Mysql,
+                    self.fqdn_id.as_str(),
+                    kubernetes.region(),
+                    kubernetes.cloud_provider().credentials_environment_variables(),
+                ),
+            ),
+            DeploymentTarget::SelfHosted(..) => get_self_hosted_database_disk_size(target, self),
+        }
+    }
+
     fn action(&self) -> &Action {
         &self.action
     }
@@ -108,8 +230,20 @@ impl Service for MySQL {
         Some(self.options.port)
     }
 
+    fn endpoints(&self) -> Vec<String> {
+        vec![format!("{}:{}", self.fqdn, self.options.port)]
+    }
+
     fn start_timeout(&self) -> Timeout<u32> {
-        Timeout::Default
+        Timeout::Value(self.readiness_timeout().as_secs() as u32)
+    }
+
+    fn deploy_timeout(&self) -> Option<std::time::Duration> {
+        self.options.deploy_timeout()
+    }
+
+    fn custom_namespace(&self) -> Option<&str> {
+        self.options.namespace.as_deref()
     }
 
     fn total_cpus(&self) -> String {
@@ -125,7 +259,11 @@ impl Service for MySQL {
     }
 
     fn total_instances(&self) -> u16 {
-        1
+        self.options.total_instances()
+    }
+
+    fn template_directories(&self, target: &DeploymentTarget) -> Vec<String> {
+        stateful_service_template_directories(self, target)
     }
 
     fn tera_context(&self, target: &DeploymentTarget) -> Result<TeraContext, EngineError> {
@@ -134,6 +272,21 @@ impl Service for MySQL {
             DeploymentTarget::SelfHosted(k, env) => (*k, *env),
         };
 
+        if let Err(e) = self.options.validate_replicas() {
+            return Err(self.engine_error(EngineErrorCause::User("Reduce the number of database replicas"), e));
+        }
+
+        if let Err(e) = self.options.validate_parameters() {
+            return Err(self.engine_error(EngineErrorCause::User("Remove database parameters without a name"), e));
+        }
+
+        if let Err(e) = self.options.validate_disk_performances() {
+            return Err(self.engine_error(
+                EngineErrorCause::User("Check the IOPS and storage throughput of the database disk type"),
+                e,
+            ));
+        }
+
         let mut context = default_tera_context(self, kubernetes, environment);
 
         let is_managed_services = match environment.kind {
@@ -141,17 +294,30 @@ impl Service for MySQL {
             Kind::Development => false,
         };
 
+        if let Err(e) = self.options.validate_tls(is_managed_services) {
+            return Err(self.engine_error(EngineErrorCause::User("Check the database TLS settings"), e));
+        }
+
+        if is_managed_services {
+            if let Err(e) = self.options.validate_backups() {
+                return Err(self.engine_error(
+                    EngineErrorCause::User("Check the backup retention and window of the database"),
+                    e,
+                ));
+            }
+        }
+
         // we need the kubernetes config file to store tfstates file in kube secrets
         let kube_config_file_path = kubernetes.config_file_path()?;
         context.insert("kubeconfig_path", &kube_config_file_path);
 
         kubectl::kubectl_exec_create_namespace_without_labels(
-            &environment.namespace(),
+            self.namespace(environment),
             kube_config_file_path.as_str(),
             kubernetes.cloud_provider().credentials_environment_variables(),
         );
 
-        context.insert("namespace", environment.namespace());
+        context.insert("namespace", self.namespace(environment));
 
         let version = &self.matching_correct_version(is_managed_services)?;
         context.insert("version", &version);
@@ -160,12 +326,12 @@ impl Service for MySQL {
             let parameter_group_family = match get_parameter_group_from_version(&version, DatabaseKind::Mysql) {
                 Ok(v) => v,
                 Err(e) => {
-                    return Err(EngineError {
-                        cause: EngineErrorCause::Internal,
-                        scope: EngineErrorScope::Engine,
-                        execution_id: (&self.context.execution_id()).to_string(),
-                        message: Some(e),
-                    })
+                    return Err(EngineError::new(
+                        EngineErrorCause::Internal,
+                        EngineErrorScope::Engine,
+                        (&self.context.execution_id()).to_string(),
+                        Some(e),
+                    ))
                 }
             };
             context.insert("parameter_group_family", &parameter_group_family);
@@ -182,28 +348,75 @@ impl Service for MySQL {
         context.insert("fqdn", self.fqdn.as_str());
 
         context.insert("database_login", self.options.login.as_str());
-        context.insert("database_password", self.options.password.as_str());
+        context.insert("database_connection_string", &self.connection_string());
+        context.insert(
+            "database_password",
+            resolve_secret(self, self.options.password.as_str())?.as_str(),
+        );
         context.insert("database_port", &self.private_port());
         context.insert("database_disk_size_in_gib", &self.options.disk_size_in_gib);
         context.insert("database_instance_type", &self.database_instance_type);
         context.insert("database_disk_type", &self.options.database_disk_type);
+        context.insert("database_replicas", &self.options.replicas);
+        context.insert("database_parameters", &self.options.parameters);
+        insert_database_tls_into_tera_context(&self.options, &mut context);
+        if let Some(iops) = self.options.iops {
+            context.insert("database_iops", &iops);
+        }
+        if let Some(storage_throughput) = self.options.storage_throughput {
+            context.insert("database_storage_throughput", &storage_throughput);
+        }
         context.insert("database_name", &self.sanitized_name());
         context.insert("database_ram_size_in_mib", &self.total_ram_in_mib);
         context.insert("database_total_cpus", &self.total_cpus);
+
+        match self.options.resources(self.total_cpus.as_str(), self.total_ram_in_mib) {
+            Ok(resources) => insert_database_resources_into_tera_context(&resources, &mut context),
+            Err(e) => {
+                return Err(self.engine_error(
+                    EngineErrorCause::User("Check the resources overcommit of the database"),
+                    e,
+                ))
+            }
+        }
+        if let Err(e) = insert_database_node_pool_into_tera_context(
+            &self.options,
+            &kubernetes.node_pools_scheduling(),
+            &mut context,
+        ) {
+            return Err(self.engine_error(
+                EngineErrorCause::User("Check the node pool label the database is pinned to"),
+                e,
+            ));
+        }
         context.insert("database_fqdn", &self.options.host.as_str());
         context.insert("database_id", &self.id());
         context.insert("tfstate_suffix_name", &get_tfstate_suffix(self));
         context.insert("tfstate_name", &get_tfstate_name(self));
+        insert_terraform_backend_into_tera_context(
+            kubernetes.cloud_provider().terraform_state_credentials(),
+            self.namespace(environment),
+            &get_tfstate_name(self),
+            &mut context,
+        );
 
-        context.insert("delete_automated_backups", &self.context().is_test_cluster());
+        insert_database_backups_into_tera_context(&self.context, &self.options, 7, &mut context);
         context.insert("skip_final_snapshot", &self.context().is_test_cluster());
-        if self.context.resource_expiration_in_seconds().is_some() {
-            context.insert(
-                "resource_expiration_in_seconds",
-                &self.context.resource_expiration_in_seconds(),
-            )
+        context.insert(
+            "deletion_protection",
+            &(self.options.deletion_protection && !self.context().is_test_cluster()),
+        );
+        // a clone is restored from the snapshot of its source, with its own identifier and password
+        if is_managed_services {
+            if let Some(snapshot_identifier) = self.clone_snapshot_to_restore(target)? {
+                let mut snapshot = BTreeMap::new();
+                snapshot.insert("snapshot_id", snapshot_identifier);
+                context.insert("snapshot", &snapshot);
+            }
         }
 
+        insert_resource_expiration_into_tera_context(&self.context, &mut context);
+
         Ok(context)
     }
 
@@ -238,6 +451,10 @@ impl Helm for MySQL {
     fn helm_chart_external_name_service_dir(&self) -> String {
         format!("{}/common/charts/external-name-svc", self.context.lib_root_dir())
     }
+
+    fn helm_value_overrides(&self) -> BTreeMap<String, String> {
+        self.options.helm_value_overrides.clone()
+    }
 }
 
 impl Terraform for MySQL {
@@ -255,7 +472,29 @@ impl Create for MySQL {
         info!("AWS.MySQL.on_create() called for {}", self.name());
 
         send_progress_on_long_task(self, crate::cloud_provider::service::Action::Create, || {
-            deploy_stateful_service(target, self)
+            let apply_result = deploy_stateful_service_with_outputs(target, self)?;
+
+            // the RDS endpoint is only known once created, the fqdn is a CNAME to it
+            if let Some(endpoint) = apply_result.output_string("database_endpoint") {
+                ListenersHelper::new(&self.listeners).deployment_in_progress(ProgressInfo::new(
+                    self.progress_scope(),
+                    ProgressLevel::Info,
+                    Some(format!(
+                        "Database {} is served by {}:{} behind {}, connect with {}",
+                        self.name(),
+                        endpoint,
+                        self.options.port,
+                        self.fqdn,
+                        apply_result
+                            .output_string("connection_string")
+                            .map(|connection_string| connection_string.to_string())
+                            .unwrap_or_else(|| self.connection_string())
+                    )),
+                    self.context.execution_id(),
+                ));
+            }
+
+            Ok(())
         })
     }
 
@@ -309,16 +548,32 @@ impl Delete for MySQL {
 }
 
 impl crate::cloud_provider::service::Clone for MySQL {
-    fn on_clone(&self, _target: &DeploymentTarget) -> Result<(), EngineError> {
-        unimplemented!()
+    fn on_clone(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        info!("AWS.MySQL.on_clone() called for {}", self.name());
+
+        match target {
+            DeploymentTarget::ManagedServices(kubernetes, _) => {
+                send_progress_on_long_task(self, crate::cloud_provider::service::Action::Create, || {
+                    self.create_clone_snapshot(*kubernetes)
+                })?;
+                self.on_create(target)
+            }
+            DeploymentTarget::SelfHosted(..) => {
+                send_progress_on_long_task(self, crate::cloud_provider::service::Action::Create, || {
+                    clone_self_hosted_database(target, self)
+                })
+            }
+        }
     }
 
     fn on_clone_check(&self) -> Result<(), EngineError> {
-        unimplemented!()
+        self.on_create_check()
     }
 
     fn on_clone_error(&self, _target: &DeploymentTarget) -> Result<(), EngineError> {
-        unimplemented!()
+        warn!("AWS.MySQL.on_clone_error() called for {}", self.name());
+
+        Ok(())
     }
 }
 
@@ -386,15 +641,22 @@ impl Listen for MySQL {
     }
 }
 
-fn get_mysql_version(requested_version: &str, is_managed_service: bool) -> Result<String, StringError> {
+fn get_mysql_version(
+    requested_version: &str,
+    is_managed_service: bool,
+    extra_supported_versions: &HashMap<String, String>,
+) -> Result<String, StringError> {
     if is_managed_service {
-        get_managed_mysql_version(requested_version)
+        get_managed_mysql_version(requested_version, extra_supported_versions)
     } else {
         get_self_hosted_mysql_version(requested_version)
     }
 }
 
-fn get_managed_mysql_version(requested_version: &str) -> Result<String, StringError> {
+fn get_managed_mysql_version(
+    requested_version: &str,
+    extra_supported_versions: &HashMap<String, String>,
+) -> Result<String, StringError> {
     let mut supported_mysql_versions = HashMap::new();
     // https://docs.aws.amazon.com/AmazonRDS/latest/UserGuide/CHAP_MySQL.html#MySQL.Concepts.VersionMgmt
 
@@ -413,33 +675,48 @@ fn get_managed_mysql_version(requested_version: &str) -> Result<String, StringEr
     v8.remove("8.0.12");
     supported_mysql_versions.extend(v8);
 
-    get_supported_version_to_use("RDS MySQL", supported_mysql_versions, requested_version)
+    get_supported_version_to_use_with_extra_versions(
+        "RDS MySQL",
+        supported_mysql_versions,
+        extra_supported_versions,
+        requested_version,
+    )
 }
 
 #[cfg(test)]
 mod tests_mysql {
     use crate::cloud_provider::aws::databases::mysql::{get_mysql_version, MySQL};
-    use crate::cloud_provider::service::{Action, DatabaseOptions, Service};
+    use crate::cloud_provider::service::{check_deletion_protection, Action, DatabaseOptions, Service};
     use crate::models::Context;
+    use std::collections::{BTreeMap, HashMap};
 
     #[test]
     fn check_mysql_version() {
         // managed version
-        assert_eq!(get_mysql_version("8", true).unwrap(), "8.0.21");
-        assert_eq!(get_mysql_version("8.0", true).unwrap(), "8.0.21");
-        assert_eq!(get_mysql_version("8.0.16", true).unwrap(), "8.0.16");
+        assert_eq!(get_mysql_version("8", true, &HashMap::new()).unwrap(), "8.0.21");
+        assert_eq!(get_mysql_version("8.0", true, &HashMap::new()).unwrap(), "8.0.21");
+        assert_eq!(get_mysql_version("8.0.16", true, &HashMap::new()).unwrap(), "8.0.16");
         assert_eq!(
-            get_mysql_version("8.0.18", true).unwrap_err().as_str(),
+            get_mysql_version("8.0.18", true, &HashMap::new()).unwrap_err().as_str(),
             "RDS MySQL 8.0.18 version is not supported"
         );
         // self-hosted version
-        assert_eq!(get_mysql_version("5", false).unwrap(), "5.7.33");
-        assert_eq!(get_mysql_version("5.7", false).unwrap(), "5.7.33");
-        assert_eq!(get_mysql_version("5.7.31", false).unwrap(), "5.7.31");
+        assert_eq!(get_mysql_version("5", false, &HashMap::new()).unwrap(), "5.7.33");
+        assert_eq!(get_mysql_version("5.7", false, &HashMap::new()).unwrap(), "5.7.33");
+        assert_eq!(get_mysql_version("5.7.31", false, &HashMap::new()).unwrap(), "5.7.31");
         assert_eq!(
-            get_mysql_version("1.0", false).unwrap_err().as_str(),
+            get_mysql_version("1.0", false, &HashMap::new()).unwrap_err().as_str(),
             "MySQL 1.0 version is not supported"
         );
+
+        // versions released after this build, only for the managed version
+        let mut extra_versions = HashMap::new();
+        extra_versions.insert("8".to_string(), "8.0.25".to_string());
+        extra_versions.insert("8.0.25".to_string(), "8.0.25".to_string());
+        assert_eq!(get_mysql_version("8", true, &extra_versions).unwrap(), "8.0.25");
+        assert_eq!(get_mysql_version("8.0.25", true, &extra_versions).unwrap(), "8.0.25");
+        assert_eq!(get_mysql_version("8.0.16", true, &extra_versions).unwrap(), "8.0.16");
+        assert_eq!(get_mysql_version("5", false, &extra_versions).unwrap(), "5.7.33");
     }
 
     #[test]
@@ -473,9 +750,93 @@ mod tests_mysql {
                 port: 3306,
                 disk_size_in_gib: 10,
                 database_disk_type: "gp2".to_string(),
+                replicas: 0,
+                iops: None,
+                storage_throughput: None,
+                deletion_protection: false,
+                sentinel_enabled: false,
+                resources_overcommit_percent: None,
+                parameters: BTreeMap::new(),
+                require_tls: false,
+                tls_certificates: None,
+                deletion_timeout_in_seconds: None,
+                helm_value_overrides: BTreeMap::new(),
+                deploy_timeout_in_seconds: None,
+                namespace: None,
+                clone_from: None,
+                backup_retention_days: None,
+                backup_window: None,
+                node_pool_label: None,
             },
             vec![],
         );
         assert_eq!(database.sanitized_name(), db_expected_name);
     }
+
+    #[test]
+    fn mysql_deletion_protection() {
+        // setup:
+        let database = |test_cluster: bool, deletion_protection: bool| {
+            MySQL::new(
+                Context::new(
+                    "".to_string(),
+                    "".to_string(),
+                    "".to_string(),
+                    test_cluster,
+                    None,
+                    vec![],
+                    None,
+                ),
+                "mysqlid",
+                Action::Delete,
+                "mysql",
+                "8",
+                "mysqltest.qovery.io",
+                "mysqlid",
+                "1".to_string(),
+                512,
+                "db.t2.micro",
+                DatabaseOptions {
+                    login: "".to_string(),
+                    password: "".to_string(),
+                    host: "".to_string(),
+                    port: 3306,
+                    disk_size_in_gib: 10,
+                    database_disk_type: "gp2".to_string(),
+                    replicas: 0,
+                    iops: None,
+                    storage_throughput: None,
+                    deletion_protection,
+                    sentinel_enabled: false,
+                    resources_overcommit_percent: None,
+                    parameters: BTreeMap::new(),
+                    require_tls: false,
+                    tls_certificates: None,
+                    deletion_timeout_in_seconds: None,
+                    helm_value_overrides: BTreeMap::new(),
+                    deploy_timeout_in_seconds: None,
+                    namespace: None,
+                    clone_from: None,
+                    backup_retention_days: None,
+                    backup_window: None,
+                    node_pool_label: None,
+                },
+                vec![],
+            )
+        };
+
+        // execute:
+        let protected = check_deletion_protection(&database(false, true));
+        let unprotected = check_deletion_protection(&database(false, false));
+        let protected_on_test_cluster = check_deletion_protection(&database(true, true));
+
+        // verify:
+        let error = protected.unwrap_err();
+        assert_eq!(
+            error.message,
+            Some("MySQL database mysql (mysqlid) can't be deleted, its deletion protection is enabled".to_string())
+        );
+        assert!(unprotected.is_ok());
+        assert!(protected_on_test_cluster.is_ok());
+    }
 }
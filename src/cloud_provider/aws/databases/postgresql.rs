@@ -1,23 +1,32 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use tera::Context as TeraContext;
 
-use crate::cloud_provider::aws::databases::utilities::rds_name_sanitizer;
+use crate::cloud_provider::aws::databases::utilities::{
+    create_managed_database_snapshot, get_managed_database_disk_size, get_managed_database_version,
+    rds_clone_snapshot_identifier, rds_name_sanitizer, resize_managed_database_storage,
+};
 use crate::cloud_provider::environment::Kind;
+use crate::cloud_provider::kubernetes::Kubernetes;
 use crate::cloud_provider::service::{
-    check_service_version, default_tera_context, delete_stateful_service, deploy_stateful_service, get_tfstate_name,
-    get_tfstate_suffix, scale_down_database, send_progress_on_long_task, Action, Backup, Create, Database,
-    DatabaseOptions, DatabaseType, Delete, Downgrade, Helm, Pause, Service, ServiceType, StatefulService, Terraform,
-    Upgrade,
+    check_service_version, clone_self_hosted_database, default_tera_context, delete_stateful_service,
+    deploy_stateful_service, get_self_hosted_database_disk_size, get_self_hosted_database_version, get_tfstate_name,
+    get_tfstate_suffix, insert_database_backups_into_tera_context, insert_database_node_pool_into_tera_context,
+    insert_database_resources_into_tera_context, insert_resource_expiration_into_tera_context,
+    insert_terraform_backend_into_tera_context, resize_self_hosted_database_storage, resolve_secret,
+    scale_down_database, send_progress_on_long_task, stateful_service_template_directories, Action, Backup, Create,
+    Database, DatabaseOptions, DatabaseType, Delete, Downgrade, Helm, Pause, Service, ServiceType, StatefulService,
+    Terraform, Upgrade,
 };
 use crate::cloud_provider::utilities::{
-    generate_supported_version, get_self_hosted_postgres_version, get_supported_version_to_use,
+    generate_supported_version, get_self_hosted_postgres_version, get_supported_version_to_use_with_extra_versions,
 };
 use crate::cloud_provider::DeploymentTarget;
 use crate::cmd::helm::Timeout;
 use crate::cmd::kubectl;
-use crate::error::{EngineError, EngineErrorScope, StringError};
-use crate::models::{Context, Listen, Listener, Listeners};
+use crate::error::{cast_simple_error_to_engine_error, EngineError, EngineErrorCause, EngineErrorScope, StringError};
+use crate::models::{Context, DatabaseKind, Listen, Listener, Listeners};
 
 pub struct PostgreSQL {
     context: Context,
@@ -32,6 +41,8 @@ pub struct PostgreSQL {
     database_instance_type: String,
     options: DatabaseOptions,
     listeners: Listeners,
+    /// Set once the snapshot a clone is restored from has been created.
+    clone_snapshot_created: AtomicBool,
 }
 
 impl PostgreSQL {
@@ -62,15 +73,88 @@ impl PostgreSQL {
             database_instance_type: database_instance_type.to_string(),
             options,
             listeners,
+            clone_snapshot_created: AtomicBool::new(false),
         }
     }
 
     fn matching_correct_version(&self, is_managed_services: bool) -> Result<String, EngineError> {
-        check_service_version(get_postgres_version(self.version(), is_managed_services), self)
+        check_service_version(self.supported_version(is_managed_services), self)
+    }
+
+    /// Snapshot the managed instance is restored from when it's a clone.
+    fn clone_snapshot_identifier(&self) -> Option<String> {
+        self.clone_source()
+            .map(|source| rds_clone_snapshot_identifier(source.fqdn_id.as_str(), self.fqdn_id.as_str()))
+    }
+
+    fn create_clone_snapshot(&self, kubernetes: &dyn Kubernetes) -> Result<(), EngineError> {
+        let (source, snapshot_identifier) = match (self.clone_source(), self.clone_snapshot_identifier()) {
+            (Some(source), Some(snapshot_identifier)) => (source, snapshot_identifier),
+            _ => return Ok(()),
+        };
+
+        cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context.execution_id(),
+            create_managed_database_snapshot(
+                DatabaseKind::Postgresql,
+                source.fqdn_id.as_str(),
+                snapshot_identifier.as_str(),
+                kubernetes.region(),
+                kubernetes.cloud_provider().credentials_environment_variables(),
+            ),
+        )?;
+        self.clone_snapshot_created.store(true, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    /// Snapshot to render in the terraform instance: the one just created for a first deployment, or the one
+    /// an already deployed clone has been restored from, removing it would make terraform replace the instance.
+    fn clone_snapshot_to_restore(&self, target: &DeploymentTarget) -> Result<Option<String>, EngineError> {
+        let snapshot_identifier = match self.clone_snapshot_identifier() {
+            Some(snapshot_identifier) => snapshot_identifier,
+            None => return Ok(None),
+        };
+
+        if self.clone_snapshot_created.load(Ordering::SeqCst) || self.deployed_version(target)?.is_some() {
+            return Ok(Some(snapshot_identifier));
+        }
+
+        Ok(None)
     }
 }
 
-impl StatefulService for PostgreSQL {}
+impl StatefulService for PostgreSQL {
+    fn is_deletion_protected(&self) -> bool {
+        self.options.deletion_protection
+    }
+
+    fn supported_version(&self, is_managed_services: bool) -> Result<String, StringError> {
+        get_postgres_version(
+            self.version(),
+            is_managed_services,
+            &self.context.extra_supported_database_versions("Postgresql"),
+        )
+    }
+
+    fn resize_storage(&self, target: &DeploymentTarget, new_size_gib: u32) -> Result<(), EngineError> {
+        match target {
+            DeploymentTarget::ManagedServices(kubernetes, _) => cast_simple_error_to_engine_error(
+                self.engine_error_scope(),
+                self.context.execution_id(),
+                resize_managed_database_storage(
+                    DatabaseKind::Postgresql,
+                    self.fqdn_id.as_str(),
+                    kubernetes.region(),
+                    new_size_gib,
+                    kubernetes.cloud_provider().credentials_environment_variables(),
+                ),
+            ),
+            DeploymentTarget::SelfHosted(..) => resize_self_hosted_database_storage(target, self, new_size_gib),
+        }
+    }
+}
 
 impl Service for PostgreSQL {
     fn context(&self) -> &Context {
@@ -100,6 +184,38 @@ impl Service for PostgreSQL {
         self.version.as_str()
     }
 
+    fn deployed_version(&self, target: &DeploymentTarget) -> Result<Option<String>, EngineError> {
+        match target {
+            DeploymentTarget::ManagedServices(kubernetes, _) => cast_simple_error_to_engine_error(
+                self.engine_error_scope(),
+                self.context.execution_id(),
+                get_managed_database_version(
+                    DatabaseKind::Postgresql,
+                    self.fqdn_id.as_str(),
+                    kubernetes.region(),
+                    kubernetes.cloud_provider().credentials_environment_variables(),
+                ),
+            ),
+            DeploymentTarget::SelfHosted(..) => get_self_hosted_database_version(target, self),
+        }
+    }
+
+    fn deployed_disk_size_in_gib(&self, target: &DeploymentTarget) -> Result<Option<u32>, EngineError> {
+        match target {
+            DeploymentTarget::ManagedServices(kubernetes, _) => cast_simple_error_to_engine_error(
+                self.engine_error_scope(),
+                self.context.execution_id(),
+                get_managed_database_disk_size(
+                    DatabaseKind::Postgresql,
+                    self.fqdn_id.as_str(),
+                    kubernetes.region(),
+                    kubernetes.cloud_provider().credentials_environment_variables(),
+                ),
+            ),
+            DeploymentTarget::SelfHosted(..) => get_self_hosted_database_disk_size(target, self),
+        }
+    }
+
     fn action(&self) -> &Action {
         &self.action
     }
@@ -108,8 +224,20 @@ impl Service for PostgreSQL {
         Some(self.options.port)
     }
 
+    fn endpoints(&self) -> Vec<String> {
+        vec![format!("{}:{}", self.fqdn, self.options.port)]
+    }
+
     fn start_timeout(&self) -> Timeout<u32> {
-        Timeout::Default
+        Timeout::Value(self.readiness_timeout().as_secs() as u32)
+    }
+
+    fn deploy_timeout(&self) -> Option<std::time::Duration> {
+        self.options.deploy_timeout()
+    }
+
+    fn custom_namespace(&self) -> Option<&str> {
+        self.options.namespace.as_deref()
     }
 
     fn total_cpus(&self) -> String {
@@ -128,6 +256,10 @@ impl Service for PostgreSQL {
         1
     }
 
+    fn template_directories(&self, target: &DeploymentTarget) -> Vec<String> {
+        stateful_service_template_directories(self, target)
+    }
+
     fn tera_context(&self, target: &DeploymentTarget) -> Result<TeraContext, EngineError> {
         let (kubernetes, environment) = match target {
             DeploymentTarget::ManagedServices(k, env) => (*k, *env),
@@ -139,6 +271,15 @@ impl Service for PostgreSQL {
             Kind::Development => false,
         };
 
+        if is_managed_services {
+            if let Err(e) = self.options.validate_backups() {
+                return Err(self.engine_error(
+                    EngineErrorCause::User("Check the backup retention and window of the database"),
+                    e,
+                ));
+            }
+        }
+
         let mut context = default_tera_context(self, kubernetes, environment);
 
         // we need the kubernetes config file to store tfstates file in kube secrets
@@ -146,12 +287,12 @@ impl Service for PostgreSQL {
         context.insert("kubeconfig_path", &kube_config_file_path);
 
         kubectl::kubectl_exec_create_namespace_without_labels(
-            &environment.namespace(),
+            self.namespace(environment),
             kube_config_file_path.as_str(),
             kubernetes.cloud_provider().credentials_environment_variables(),
         );
 
-        context.insert("namespace", environment.namespace());
+        context.insert("namespace", self.namespace(environment));
 
         let version = self.matching_correct_version(is_managed_services)?;
         context.insert("version", &version);
@@ -169,28 +310,65 @@ impl Service for PostgreSQL {
         context.insert("database_name", self.sanitized_name().as_str());
         context.insert("database_db_name", self.name());
         context.insert("database_login", self.options.login.as_str());
-        context.insert("database_password", self.options.password.as_str());
+        context.insert(
+            "database_password",
+            resolve_secret(self, self.options.password.as_str())?.as_str(),
+        );
         context.insert("database_port", &self.private_port());
         context.insert("database_disk_size_in_gib", &self.options.disk_size_in_gib);
         context.insert("database_instance_type", &self.database_instance_type);
         context.insert("database_disk_type", &self.options.database_disk_type);
         context.insert("database_ram_size_in_mib", &self.total_ram_in_mib);
         context.insert("database_total_cpus", &self.total_cpus);
+
+        match self.options.resources(self.total_cpus.as_str(), self.total_ram_in_mib) {
+            Ok(resources) => insert_database_resources_into_tera_context(&resources, &mut context),
+            Err(e) => {
+                return Err(self.engine_error(
+                    EngineErrorCause::User("Check the resources overcommit of the database"),
+                    e,
+                ))
+            }
+        }
+        if let Err(e) = insert_database_node_pool_into_tera_context(
+            &self.options,
+            &kubernetes.node_pools_scheduling(),
+            &mut context,
+        ) {
+            return Err(self.engine_error(
+                EngineErrorCause::User("Check the node pool label the database is pinned to"),
+                e,
+            ));
+        }
         context.insert("database_fqdn", &self.options.host.as_str());
         context.insert("database_id", &self.id());
         context.insert("tfstate_suffix_name", &get_tfstate_suffix(self));
         context.insert("tfstate_name", &get_tfstate_name(self));
+        insert_terraform_backend_into_tera_context(
+            kubernetes.cloud_provider().terraform_state_credentials(),
+            self.namespace(environment),
+            &get_tfstate_name(self),
+            &mut context,
+        );
 
         context.insert("skip_final_snapshot", &self.context().is_test_cluster());
-        context.insert("delete_automated_backups", &self.context().is_test_cluster());
-
-        if self.context.resource_expiration_in_seconds().is_some() {
-            context.insert(
-                "resource_expiration_in_seconds",
-                &self.context.resource_expiration_in_seconds(),
-            )
+        context.insert(
+            "deletion_protection",
+            &(self.options.deletion_protection && !self.context().is_test_cluster()),
+        );
+        insert_database_backups_into_tera_context(&self.context, &self.options, 14, &mut context);
+
+        // a clone is restored from the snapshot of its source, with its own identifier and password
+        if is_managed_services {
+            if let Some(snapshot_identifier) = self.clone_snapshot_to_restore(target)? {
+                let mut snapshot = BTreeMap::new();
+                snapshot.insert("snapshot_id", snapshot_identifier);
+                context.insert("snapshot", &snapshot);
+            }
         }
 
+        insert_resource_expiration_into_tera_context(&self.context, &mut context);
+
         Ok(context)
     }
 
@@ -225,6 +403,10 @@ impl Helm for PostgreSQL {
     fn helm_chart_external_name_service_dir(&self) -> String {
         format!("{}/common/charts/external-name-svc", self.context.lib_root_dir())
     }
+
+    fn helm_value_overrides(&self) -> BTreeMap<String, String> {
+        self.options.helm_value_overrides.clone()
+    }
 }
 
 impl Terraform for PostgreSQL {
@@ -298,16 +480,32 @@ impl Delete for PostgreSQL {
 }
 
 impl crate::cloud_provider::service::Clone for PostgreSQL {
-    fn on_clone(&self, _target: &DeploymentTarget) -> Result<(), EngineError> {
-        unimplemented!()
+    fn on_clone(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        info!("AWS.PostgreSQL.on_clone() called for {}", self.name());
+
+        match target {
+            DeploymentTarget::ManagedServices(kubernetes, _) => {
+                send_progress_on_long_task(self, crate::cloud_provider::service::Action::Create, || {
+                    self.create_clone_snapshot(*kubernetes)
+                })?;
+                self.on_create(target)
+            }
+            DeploymentTarget::SelfHosted(..) => {
+                send_progress_on_long_task(self, crate::cloud_provider::service::Action::Create, || {
+                    clone_self_hosted_database(target, self)
+                })
+            }
+        }
     }
 
     fn on_clone_check(&self) -> Result<(), EngineError> {
-        unimplemented!()
+        self.on_create_check()
     }
 
     fn on_clone_error(&self, _target: &DeploymentTarget) -> Result<(), EngineError> {
-        unimplemented!()
+        warn!("AWS.PostgreSQL.on_clone_error() called for {}", self.name());
+
+        Ok(())
     }
 }
 
@@ -375,15 +573,22 @@ impl Listen for PostgreSQL {
     }
 }
 
-fn get_postgres_version(requested_version: &str, is_managed_service: bool) -> Result<String, StringError> {
+fn get_postgres_version(
+    requested_version: &str,
+    is_managed_service: bool,
+    extra_supported_versions: &HashMap<String, String>,
+) -> Result<String, StringError> {
     if is_managed_service {
-        get_managed_postgres_version(requested_version)
+        get_managed_postgres_version(requested_version, extra_supported_versions)
     } else {
         get_self_hosted_postgres_version(requested_version)
     }
 }
 
-fn get_managed_postgres_version(requested_version: &str) -> Result<String, StringError> {
+fn get_managed_postgres_version(
+    requested_version: &str,
+    extra_supported_versions: &HashMap<String, String>,
+) -> Result<String, StringError> {
     let mut supported_postgres_versions = HashMap::new();
 
     // https://docs.aws.amazon.com/AmazonRDS/latest/UserGuide/CHAP_PostgreSQL.html#PostgreSQL.Concepts
@@ -403,7 +608,16 @@ fn get_managed_postgres_version(requested_version: &str) -> Result<String, Strin
     let v12 = generate_supported_version(12, 2, 5, None, None, None);
     supported_postgres_versions.extend(v12);
 
-    get_supported_version_to_use("Postgresql", supported_postgres_versions, requested_version)
+    // v13
+    let v13 = generate_supported_version(13, 1, 2, None, None, None);
+    supported_postgres_versions.extend(v13);
+
+    get_supported_version_to_use_with_extra_versions(
+        "Postgresql",
+        supported_postgres_versions,
+        extra_supported_versions,
+        requested_version,
+    )
 }
 
 #[cfg(test)]
@@ -411,26 +625,45 @@ mod tests_postgres {
     use crate::cloud_provider::aws::databases::postgresql::{get_postgres_version, PostgreSQL};
     use crate::cloud_provider::service::{Action, DatabaseOptions, Service};
     use crate::models::Context;
+    use std::collections::{BTreeMap, HashMap};
 
     #[test]
     fn check_postgres_version() {
         // managed version
-        assert_eq!(get_postgres_version("12", true).unwrap(), "12.5");
-        assert_eq!(get_postgres_version("12.3", true).unwrap(), "12.3");
+        assert_eq!(get_postgres_version("12", true, &HashMap::new()).unwrap(), "12.5");
+        assert_eq!(get_postgres_version("12.3", true, &HashMap::new()).unwrap(), "12.3");
         assert_eq!(
-            get_postgres_version("12.3.0", true).unwrap_err().as_str(),
+            get_postgres_version("12.3.0", true, &HashMap::new())
+                .unwrap_err()
+                .as_str(),
             "Postgresql 12.3.0 version is not supported"
         );
         assert_eq!(
-            get_postgres_version("11.3", true).unwrap_err().as_str(),
+            get_postgres_version("11.3", true, &HashMap::new())
+                .unwrap_err()
+                .as_str(),
             "Postgresql 11.3 version is not supported"
         );
+        assert_eq!(get_postgres_version("11", true, &HashMap::new()).unwrap(), "11.10");
+        assert_eq!(get_postgres_version("13", true, &HashMap::new()).unwrap(), "13.2");
+        assert_eq!(get_postgres_version("13.1", true, &HashMap::new()).unwrap(), "13.1");
+        assert_eq!(
+            get_postgres_version("13.3", true, &HashMap::new())
+                .unwrap_err()
+                .as_str(),
+            "Postgresql 13.3 version is not supported"
+        );
         // self-hosted version
-        assert_eq!(get_postgres_version("12", false).unwrap(), "12.6.0");
-        assert_eq!(get_postgres_version("12.3", false).unwrap(), "12.3.0");
-        assert_eq!(get_postgres_version("12.3.0", false).unwrap(), "12.3.0");
+        assert_eq!(get_postgres_version("12", false, &HashMap::new()).unwrap(), "12.6.0");
+        assert_eq!(get_postgres_version("12.3", false, &HashMap::new()).unwrap(), "12.3.0");
+        assert_eq!(
+            get_postgres_version("12.3.0", false, &HashMap::new()).unwrap(),
+            "12.3.0"
+        );
         assert_eq!(
-            get_postgres_version("1.0", false).unwrap_err().as_str(),
+            get_postgres_version("1.0", false, &HashMap::new())
+                .unwrap_err()
+                .as_str(),
             "Postgresql 1.0 version is not supported"
         );
     }
@@ -466,6 +699,23 @@ mod tests_postgres {
                 port: 5432,
                 disk_size_in_gib: 10,
                 database_disk_type: "gp2".to_string(),
+                replicas: 0,
+                iops: None,
+                storage_throughput: None,
+                deletion_protection: false,
+                sentinel_enabled: false,
+                resources_overcommit_percent: None,
+                parameters: BTreeMap::new(),
+                require_tls: false,
+                tls_certificates: None,
+                deletion_timeout_in_seconds: None,
+                helm_value_overrides: BTreeMap::new(),
+                deploy_timeout_in_seconds: None,
+                namespace: None,
+                clone_from: None,
+                backup_retention_days: None,
+                backup_window: None,
+                node_pool_label: None,
             },
             vec![],
         );
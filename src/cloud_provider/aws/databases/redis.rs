@@ -1,20 +1,27 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use tera::Context as TeraContext;
 
+use crate::cloud_provider::aws::databases::utilities::get_managed_database_version;
 use crate::cloud_provider::environment::Kind;
 use crate::cloud_provider::service::{
-    check_service_version, default_tera_context, delete_stateful_service, deploy_stateful_service, get_tfstate_name,
-    get_tfstate_suffix, scale_down_database, send_progress_on_long_task, Action, Backup, Create, Database,
+    check_service_version, clone_self_hosted_database, default_tera_context, delete_stateful_service,
+    deploy_stateful_service, get_self_hosted_database_disk_size, get_self_hosted_database_version, get_tfstate_name,
+    get_tfstate_suffix, insert_database_node_pool_into_tera_context, insert_database_resources_into_tera_context,
+    insert_redis_topology_into_tera_context, insert_resource_expiration_into_tera_context,
+    insert_terraform_backend_into_tera_context, resolve_secret, scale_down_database, send_progress_on_long_task,
+    stateful_service_template_directories, unsupported_database_clone_error, Action, Backup, Create, Database,
     DatabaseOptions, DatabaseType, Delete, Downgrade, Helm, Pause, Service, ServiceType, StatefulService, Terraform,
     Upgrade,
 };
-use crate::cloud_provider::utilities::{get_self_hosted_redis_version, get_supported_version_to_use};
+use crate::cloud_provider::utilities::{
+    get_self_hosted_redis_version, get_supported_version_to_use_with_extra_versions,
+};
 use crate::cloud_provider::DeploymentTarget;
 use crate::cmd::helm::Timeout;
 use crate::cmd::kubectl;
-use crate::error::{EngineError, EngineErrorCause, EngineErrorScope, StringError};
-use crate::models::{Context, Listen, Listener, Listeners};
+use crate::error::{cast_simple_error_to_engine_error, EngineError, EngineErrorCause, EngineErrorScope, StringError};
+use crate::models::{Context, DatabaseKind, Listen, Listener, Listeners};
 
 pub struct Redis {
     context: Context,
@@ -28,6 +35,7 @@ pub struct Redis {
     total_ram_in_mib: u32,
     database_instance_type: String,
     options: DatabaseOptions,
+    cluster_mode_enabled: bool,
     listeners: Listeners,
 }
 
@@ -44,6 +52,7 @@ impl Redis {
         total_ram_in_mib: u32,
         database_instance_type: &str,
         options: DatabaseOptions,
+        cluster_mode_enabled: bool,
         listeners: Listeners,
     ) -> Self {
         Self {
@@ -58,16 +67,29 @@ impl Redis {
             total_ram_in_mib,
             database_instance_type: database_instance_type.to_string(),
             options,
+            cluster_mode_enabled,
             listeners,
         }
     }
 
     fn matching_correct_version(&self, is_managed_services: bool) -> Result<String, EngineError> {
-        check_service_version(get_redis_version(self.version(), is_managed_services), self)
+        check_service_version(self.supported_version(is_managed_services), self)
     }
 }
 
-impl StatefulService for Redis {}
+impl StatefulService for Redis {
+    fn is_deletion_protected(&self) -> bool {
+        self.options.deletion_protection
+    }
+
+    fn supported_version(&self, is_managed_services: bool) -> Result<String, StringError> {
+        get_redis_version(
+            self.version(),
+            is_managed_services,
+            &self.context.extra_supported_database_versions("Elasticache"),
+        )
+    }
+}
 
 impl Service for Redis {
     fn context(&self) -> &Context {
@@ -103,6 +125,30 @@ impl Service for Redis {
         self.version.as_str()
     }
 
+    fn deployed_version(&self, target: &DeploymentTarget) -> Result<Option<String>, EngineError> {
+        match target {
+            DeploymentTarget::ManagedServices(kubernetes, _) => cast_simple_error_to_engine_error(
+                self.engine_error_scope(),
+                self.context.execution_id(),
+                get_managed_database_version(
+                    DatabaseKind::Redis,
+                    self.fqdn_id.as_str(),
+                    kubernetes.region(),
+                    kubernetes.cloud_provider().credentials_environment_variables(),
+                ),
+            ),
+            DeploymentTarget::SelfHosted(..) => get_self_hosted_database_version(target, self),
+        }
+    }
+
+    fn deployed_disk_size_in_gib(&self, target: &DeploymentTarget) -> Result<Option<u32>, EngineError> {
+        match target {
+            // Elasticache storage isn't allocated, it grows on its own
+            DeploymentTarget::ManagedServices(..) => Ok(None),
+            DeploymentTarget::SelfHosted(..) => get_self_hosted_database_disk_size(target, self),
+        }
+    }
+
     fn action(&self) -> &Action {
         &self.action
     }
@@ -111,8 +157,20 @@ impl Service for Redis {
         Some(self.options.port)
     }
 
+    fn endpoints(&self) -> Vec<String> {
+        vec![format!("{}:{}", self.fqdn, self.options.port)]
+    }
+
     fn start_timeout(&self) -> Timeout<u32> {
-        Timeout::Default
+        Timeout::Value(self.readiness_timeout().as_secs() as u32)
+    }
+
+    fn deploy_timeout(&self) -> Option<std::time::Duration> {
+        self.options.deploy_timeout()
+    }
+
+    fn custom_namespace(&self) -> Option<&str> {
+        self.options.namespace.as_deref()
     }
 
     fn total_cpus(&self) -> String {
@@ -128,7 +186,11 @@ impl Service for Redis {
     }
 
     fn total_instances(&self) -> u16 {
-        1
+        self.options.total_instances()
+    }
+
+    fn template_directories(&self, target: &DeploymentTarget) -> Vec<String> {
+        stateful_service_template_directories(self, target)
     }
 
     fn tera_context(&self, target: &DeploymentTarget) -> Result<TeraContext, EngineError> {
@@ -142,6 +204,12 @@ impl Service for Redis {
             Kind::Development => false,
         };
 
+        if !is_managed_services {
+            if let Err(e) = self.options.validate_redis_topology() {
+                return Err(self.engine_error(EngineErrorCause::User("Use either 0 or at least 2 Redis replicas"), e));
+            }
+        }
+
         let mut context = default_tera_context(self, kubernetes, environment);
 
         // we need the kubernetes config file to store tfstates file in kube secrets
@@ -149,27 +217,27 @@ impl Service for Redis {
         context.insert("kubeconfig_path", &kube_config_file_path);
 
         kubectl::kubectl_exec_create_namespace_without_labels(
-            &environment.namespace(),
+            self.namespace(environment),
             kube_config_file_path.as_str(),
             kubernetes.cloud_provider().credentials_environment_variables(),
         );
 
         let version = self.matching_correct_version(is_managed_services)?;
 
-        let parameter_group_name = if version.starts_with("5.") {
-            "default.redis5.0"
-        } else if version.starts_with("6.") {
-            "default.redis6.x"
-        } else {
-            return Err(self.engine_error(
-                EngineErrorCause::Internal,
-                "Elasticache parameter group name unknown".to_string(),
-            ));
+        let parameter_group_name = match get_elasticache_parameter_group_name(&version, self.cluster_mode_enabled) {
+            Some(parameter_group_name) => parameter_group_name,
+            None => {
+                return Err(self.engine_error(
+                    EngineErrorCause::Internal,
+                    "Elasticache parameter group name unknown".to_string(),
+                ));
+            }
         };
 
-        context.insert("database_elasticache_parameter_group_name", parameter_group_name);
+        context.insert("database_elasticache_parameter_group_name", &parameter_group_name);
+        context.insert("database_cluster_mode_enabled", &self.cluster_mode_enabled);
 
-        context.insert("namespace", environment.namespace());
+        context.insert("namespace", self.namespace(environment));
         context.insert("version", &version);
 
         for (k, v) in kubernetes.cloud_provider().tera_context_environment_variables() {
@@ -183,25 +251,50 @@ impl Service for Redis {
         context.insert("fqdn", self.fqdn.as_str());
 
         context.insert("database_login", self.options.login.as_str());
-        context.insert("database_password", self.options.password.as_str());
+        context.insert(
+            "database_password",
+            resolve_secret(self, self.options.password.as_str())?.as_str(),
+        );
         context.insert("database_port", &self.private_port());
         context.insert("database_disk_size_in_gib", &self.options.disk_size_in_gib);
         context.insert("database_instance_type", &self.database_instance_type);
         context.insert("database_disk_type", &self.options.database_disk_type);
+        insert_redis_topology_into_tera_context(&self.options, &mut context);
         context.insert("database_ram_size_in_mib", &self.total_ram_in_mib);
         context.insert("database_total_cpus", &self.total_cpus);
+
+        match self.options.resources(self.total_cpus.as_str(), self.total_ram_in_mib) {
+            Ok(resources) => insert_database_resources_into_tera_context(&resources, &mut context),
+            Err(e) => {
+                return Err(self.engine_error(
+                    EngineErrorCause::User("Check the resources overcommit of the database"),
+                    e,
+                ))
+            }
+        }
+        if let Err(e) = insert_database_node_pool_into_tera_context(
+            &self.options,
+            &kubernetes.node_pools_scheduling(),
+            &mut context,
+        ) {
+            return Err(self.engine_error(
+                EngineErrorCause::User("Check the node pool label the database is pinned to"),
+                e,
+            ));
+        }
         context.insert("database_fqdn", &self.options.host.as_str());
         context.insert("database_id", &self.id());
         context.insert("tfstate_suffix_name", &get_tfstate_suffix(self));
         context.insert("tfstate_name", &get_tfstate_name(self));
+        insert_terraform_backend_into_tera_context(
+            kubernetes.cloud_provider().terraform_state_credentials(),
+            self.namespace(environment),
+            &get_tfstate_name(self),
+            &mut context,
+        );
 
         context.insert("skip_final_snapshot", &self.context().is_test_cluster());
-        if self.context.resource_expiration_in_seconds().is_some() {
-            context.insert(
-                "resource_expiration_in_seconds",
-                &self.context.resource_expiration_in_seconds(),
-            )
-        }
+        insert_resource_expiration_into_tera_context(&self.context, &mut context);
 
         Ok(context)
     }
@@ -237,6 +330,10 @@ impl Helm for Redis {
     fn helm_chart_external_name_service_dir(&self) -> String {
         format!("{}/common/charts/external-name-svc", self.context.lib_root_dir())
     }
+
+    fn helm_value_overrides(&self) -> BTreeMap<String, String> {
+        self.options.helm_value_overrides.clone()
+    }
 }
 
 impl Terraform for Redis {
@@ -308,16 +405,27 @@ impl Delete for Redis {
 }
 
 impl crate::cloud_provider::service::Clone for Redis {
-    fn on_clone(&self, _target: &DeploymentTarget) -> Result<(), EngineError> {
-        unimplemented!()
+    fn on_clone(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        info!("AWS.Redis.on_clone() called for {}", self.name());
+
+        match target {
+            DeploymentTarget::SelfHosted(..) => {
+                send_progress_on_long_task(self, crate::cloud_provider::service::Action::Create, || {
+                    clone_self_hosted_database(target, self)
+                })
+            }
+            DeploymentTarget::ManagedServices(..) => Err(unsupported_database_clone_error(self)),
+        }
     }
 
     fn on_clone_check(&self) -> Result<(), EngineError> {
-        unimplemented!()
+        self.on_create_check()
     }
 
     fn on_clone_error(&self, _target: &DeploymentTarget) -> Result<(), EngineError> {
-        unimplemented!()
+        warn!("AWS.Redis.on_clone_error() called for {}", self.name());
+
+        Ok(())
     }
 }
 
@@ -385,45 +493,83 @@ impl Listen for Redis {
     }
 }
 
-fn get_redis_version(requested_version: &str, is_managed_service: bool) -> Result<String, StringError> {
+fn get_redis_version(
+    requested_version: &str,
+    is_managed_service: bool,
+    extra_supported_versions: &HashMap<String, String>,
+) -> Result<String, StringError> {
     if is_managed_service {
-        get_managed_redis_version(requested_version)
+        get_managed_redis_version(requested_version, extra_supported_versions)
     } else {
         get_self_hosted_redis_version(requested_version)
     }
 }
 
-fn get_managed_redis_version(requested_version: &str) -> Result<String, StringError> {
+fn get_managed_redis_version(
+    requested_version: &str,
+    extra_supported_versions: &HashMap<String, String>,
+) -> Result<String, StringError> {
     let mut supported_redis_versions = HashMap::with_capacity(2);
     // https://docs.aws.amazon.com/AmazonElastiCache/latest/red-ug/supported-engine-versions.html
 
     supported_redis_versions.insert("6".to_string(), "6.x".to_string());
+    supported_redis_versions.insert("6.x".to_string(), "6.x".to_string());
+    supported_redis_versions.insert("6.0".to_string(), "6.x".to_string());
     supported_redis_versions.insert("5".to_string(), "5.0.6".to_string());
+    supported_redis_versions.insert("5.0".to_string(), "5.0.6".to_string());
+    supported_redis_versions.insert("5.0.6".to_string(), "5.0.6".to_string());
+
+    get_supported_version_to_use_with_extra_versions(
+        "Elasticache",
+        supported_redis_versions,
+        extra_supported_versions,
+        requested_version,
+    )
+}
+
+/// Cluster mode requires a `cluster.on` parameter group, default ones are not sharded.
+fn get_elasticache_parameter_group_name(version: &str, cluster_mode_enabled: bool) -> Option<String> {
+    let parameter_group_name = if version.starts_with("5.") {
+        "default.redis5.0"
+    } else if version.starts_with("6.") {
+        "default.redis6.x"
+    } else {
+        return None;
+    };
 
-    get_supported_version_to_use("Elasticache", supported_redis_versions, requested_version)
+    match cluster_mode_enabled {
+        true => Some(format!("{}.cluster.on", parameter_group_name)),
+        false => Some(parameter_group_name.to_string()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::cloud_provider::aws::databases::redis::{get_redis_version, Redis};
+    use crate::cloud_provider::aws::databases::redis::{
+        get_elasticache_parameter_group_name, get_redis_version, Redis,
+    };
     use crate::cloud_provider::service::{Action, DatabaseOptions, Service};
     use crate::models::Context;
+    use std::collections::{BTreeMap, HashMap};
 
     #[test]
     fn check_redis_version() {
         // managed version
-        assert_eq!(get_redis_version("6", true).unwrap(), "6.x");
-        assert_eq!(get_redis_version("5", true).unwrap(), "5.0.6");
+        assert_eq!(get_redis_version("6", true, &HashMap::new()).unwrap(), "6.x");
+        assert_eq!(get_redis_version("6.x", true, &HashMap::new()).unwrap(), "6.x");
+        assert_eq!(get_redis_version("6.0", true, &HashMap::new()).unwrap(), "6.x");
+        assert_eq!(get_redis_version("5", true, &HashMap::new()).unwrap(), "5.0.6");
+        assert_eq!(get_redis_version("5.0.6", true, &HashMap::new()).unwrap(), "5.0.6");
         assert_eq!(
-            get_redis_version("1.0", true).unwrap_err().as_str(),
+            get_redis_version("1.0", true, &HashMap::new()).unwrap_err().as_str(),
             "Elasticache 1.0 version is not supported"
         );
 
         // self-hosted version
-        assert_eq!(get_redis_version("6", false).unwrap(), "6.0.9");
-        assert_eq!(get_redis_version("6.0", false).unwrap(), "6.0.9");
+        assert_eq!(get_redis_version("6", false, &HashMap::new()).unwrap(), "6.0.9");
+        assert_eq!(get_redis_version("6.0", false, &HashMap::new()).unwrap(), "6.0.9");
         assert_eq!(
-            get_redis_version("1.0", false).unwrap_err().as_str(),
+            get_redis_version("1.0", false, &HashMap::new()).unwrap_err().as_str(),
             "Redis 1.0 version is not supported"
         );
     }
@@ -459,9 +605,44 @@ mod tests {
                 port: 5432,
                 disk_size_in_gib: 10,
                 database_disk_type: "gp2".to_string(),
+                replicas: 0,
+                iops: None,
+                storage_throughput: None,
+                deletion_protection: false,
+                sentinel_enabled: false,
+                resources_overcommit_percent: None,
+                parameters: BTreeMap::new(),
+                require_tls: false,
+                tls_certificates: None,
+                deletion_timeout_in_seconds: None,
+                helm_value_overrides: BTreeMap::new(),
+                deploy_timeout_in_seconds: None,
+                namespace: None,
+                clone_from: None,
+                backup_retention_days: None,
+                backup_window: None,
+                node_pool_label: None,
             },
+            false,
             vec![],
         );
         assert_eq!(database.sanitized_name(), db_expected_name);
     }
+
+    #[test]
+    fn check_elasticache_parameter_group_name() {
+        assert_eq!(
+            get_elasticache_parameter_group_name("6.x", false).unwrap(),
+            "default.redis6.x"
+        );
+        assert_eq!(
+            get_elasticache_parameter_group_name("6.x", true).unwrap(),
+            "default.redis6.x.cluster.on"
+        );
+        assert_eq!(
+            get_elasticache_parameter_group_name("5.0.6", true).unwrap(),
+            "default.redis5.0.cluster.on"
+        );
+        assert!(get_elasticache_parameter_group_name("4.0.10", false).is_none());
+    }
 }
@@ -1,6 +1,52 @@
+use std::str::FromStr;
+
+use chrono::Duration;
+use rusoto_core::{Client, HttpClient, Region, RusotoError};
+use rusoto_credential::StaticProvider;
+use rusoto_docdb::{DBCluster, DescribeDBClustersError, DescribeDBClustersMessage, Docdb, DocdbClient};
+use rusoto_elasticache::{CacheCluster, DescribeCacheClustersMessage, ElastiCache, ElastiCacheClient};
+use rusoto_rds::{DBInstance, DescribeDBInstancesError, DescribeDBInstancesMessage, Rds, RdsClient};
+
 use crate::cloud_provider::utilities::get_version_number;
-use crate::error::StringError;
+use crate::cmd::utilities::exec_with_envs_and_output;
+use crate::constants::{AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY};
+use crate::error::{SimpleError, SimpleErrorKind, StringError};
 use crate::models::DatabaseKind;
+use crate::runtime::block_on;
+
+/// What the engine reads of a managed database (RDS instance, DocumentDB cluster or Elasticache node).
+#[derive(Clone, Debug, Default, PartialEq)]
+struct ManagedDatabase {
+    engine_version: Option<String>,
+    allocated_storage_in_gib: Option<u32>,
+}
+
+impl From<DBInstance> for ManagedDatabase {
+    fn from(instance: DBInstance) -> Self {
+        ManagedDatabase {
+            engine_version: instance.engine_version,
+            allocated_storage_in_gib: instance.allocated_storage.map(|storage| storage as u32),
+        }
+    }
+}
+
+impl From<DBCluster> for ManagedDatabase {
+    fn from(cluster: DBCluster) -> Self {
+        ManagedDatabase {
+            engine_version: cluster.engine_version,
+            allocated_storage_in_gib: None,
+        }
+    }
+}
+
+impl From<CacheCluster> for ManagedDatabase {
+    fn from(cache_cluster: CacheCluster) -> Self {
+        ManagedDatabase {
+            engine_version: cache_cluster.engine_version,
+            allocated_storage_in_gib: None,
+        }
+    }
+}
 
 pub fn rds_name_sanitizer(max_size: usize, prefix: &str, name: &str) -> String {
     let max_size = max_size - prefix.len();
@@ -11,6 +57,300 @@ pub fn rds_name_sanitizer(max_size: usize, prefix: &str, name: &str) -> String {
     new_name
 }
 
+/// Engine version of a managed database (RDS, DocumentDB or Elasticache), `None` if it doesn't exist yet.
+pub fn get_managed_database_version(
+    database_kind: DatabaseKind,
+    identifier: &str,
+    region: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<Option<String>, SimpleError> {
+    Ok(describe_managed_database(&database_kind, identifier, region, envs)?
+        .and_then(|database| database.engine_version))
+}
+
+/// Allocated storage of a managed RDS database, `None` if it doesn't exist yet or its storage isn't allocated.
+pub fn get_managed_database_disk_size(
+    database_kind: DatabaseKind,
+    identifier: &str,
+    region: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<Option<u32>, SimpleError> {
+    Ok(describe_managed_database(&database_kind, identifier, region, envs)?
+        .and_then(|database| database.allocated_storage_in_gib))
+}
+
+/// Grows the allocated storage of a managed RDS database, the modification is applied right away.
+pub fn resize_managed_database_storage(
+    database_kind: DatabaseKind,
+    identifier: &str,
+    region: &str,
+    new_size_gib: u32,
+    envs: Vec<(&str, &str)>,
+) -> Result<(), SimpleError> {
+    if !matches!(database_kind, DatabaseKind::Mysql | DatabaseKind::Postgresql) {
+        return Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!(
+                "{:?} managed databases don't have an allocated storage",
+                database_kind
+            )),
+        ));
+    }
+
+    let new_size_gib = new_size_gib.to_string();
+    let mut stderr = vec![];
+    let result = exec_with_envs_and_output(
+        "aws",
+        vec![
+            "rds",
+            "modify-db-instance",
+            "--db-instance-identifier",
+            identifier,
+            "--allocated-storage",
+            new_size_gib.as_str(),
+            "--apply-immediately",
+            "--region",
+            region,
+        ],
+        envs,
+        |line| {
+            if let Ok(line) = line {
+                info!("{}", line)
+            }
+        },
+        |line| {
+            if let Ok(line) = line {
+                stderr.push(line)
+            }
+        },
+        Duration::seconds(60),
+    );
+
+    result.map_err(|err| {
+        SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!(
+                "can't resize storage of {:?} database {} to {} GiB: {} {}",
+                database_kind,
+                identifier,
+                new_size_gib,
+                err.message.unwrap_or_default(),
+                stderr.join(" ")
+            )),
+        )
+    })?;
+
+    Ok(())
+}
+
+/// Snapshot a managed RDS database is cloned from: unique by source and clone, a retried clone reuses it.
+/// https://docs.aws.amazon.com/AmazonRDS/latest/APIReference/API_CreateDBSnapshot.html
+pub fn rds_clone_snapshot_identifier(source_identifier: &str, clone_identifier: &str) -> String {
+    let identifier = format!("clone-{}-to-{}", source_identifier, clone_identifier)
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>();
+
+    // hyphens can't be consecutive nor end the identifier
+    let mut snapshot_identifier = String::with_capacity(identifier.len());
+    for c in identifier.chars().take(255) {
+        if !(c == '-' && snapshot_identifier.ends_with('-')) {
+            snapshot_identifier.push(c);
+        }
+    }
+
+    snapshot_identifier.trim_end_matches('-').to_string()
+}
+
+/// Snapshots the managed RDS database `identifier` then waits for the snapshot to be available.
+/// An existing snapshot is reused as is, it isn't taken again.
+pub fn create_managed_database_snapshot(
+    database_kind: DatabaseKind,
+    identifier: &str,
+    snapshot_identifier: &str,
+    region: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<(), SimpleError> {
+    if !matches!(database_kind, DatabaseKind::Mysql | DatabaseKind::Postgresql) {
+        return Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!("{:?} managed databases can't be snapshotted", database_kind)),
+        ));
+    }
+
+    let snapshot_error = |message: String| {
+        SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!(
+                "can't snapshot {:?} database {} as {}: {}",
+                database_kind, identifier, snapshot_identifier, message
+            )),
+        )
+    };
+
+    let describe_args = vec![
+        "rds",
+        "describe-db-snapshots",
+        "--db-snapshot-identifier",
+        snapshot_identifier,
+        "--region",
+        region,
+    ];
+    match rds_exec(describe_args, envs.clone(), Duration::seconds(60)) {
+        Ok(()) => info!("snapshot {} already exists, it's reused", snapshot_identifier),
+        Err(err) if err.contains("DBSnapshotNotFound") => {
+            let create_args = vec![
+                "rds",
+                "create-db-snapshot",
+                "--db-instance-identifier",
+                identifier,
+                "--db-snapshot-identifier",
+                snapshot_identifier,
+                "--region",
+                region,
+            ];
+            rds_exec(create_args, envs.clone(), Duration::seconds(60)).map_err(snapshot_error)?;
+        }
+        Err(err) => return Err(snapshot_error(err)),
+    }
+
+    // the waiter polls every 30 seconds, giving up after 30 minutes
+    let wait_args = vec![
+        "rds",
+        "wait",
+        "db-snapshot-available",
+        "--db-snapshot-identifier",
+        snapshot_identifier,
+        "--region",
+        region,
+    ];
+    rds_exec(wait_args, envs, Duration::minutes(35)).map_err(snapshot_error)
+}
+
+/// Runs an AWS CLI command, its error output is returned when it fails.
+fn rds_exec(args: Vec<&str>, envs: Vec<(&str, &str)>, timeout: Duration) -> Result<(), String> {
+    let mut stderr = vec![];
+    let result = exec_with_envs_and_output(
+        "aws",
+        args,
+        envs,
+        |line| {
+            if let Ok(line) = line {
+                info!("{}", line)
+            }
+        },
+        |line| {
+            if let Ok(line) = line {
+                stderr.push(line)
+            }
+        },
+        timeout,
+    );
+
+    result
+        .map(|_| ())
+        .map_err(|err| format!("{} {}", err.message.unwrap_or_default(), stderr.join(" ")))
+}
+
+/// Describes a managed database with the AWS API, `None` if it doesn't exist.
+fn describe_managed_database(
+    database_kind: &DatabaseKind,
+    identifier: &str,
+    region: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<Option<ManagedDatabase>, SimpleError> {
+    let describe_error = |message: String| {
+        SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!(
+                "can't describe {:?} database {}: {}",
+                database_kind, identifier, message
+            )),
+        )
+    };
+
+    let region = Region::from_str(region).map_err(|e| describe_error(e.to_string()))?;
+    let credential = |name: &str| {
+        envs.iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.to_string())
+            .unwrap_or_default()
+    };
+    let client = Client::new_with(
+        StaticProvider::new(
+            credential(AWS_ACCESS_KEY_ID),
+            credential(AWS_SECRET_ACCESS_KEY),
+            None,
+            None,
+        ),
+        HttpClient::new().unwrap(),
+    );
+
+    match database_kind {
+        DatabaseKind::Mysql | DatabaseKind::Postgresql => {
+            let request = DescribeDBInstancesMessage {
+                db_instance_identifier: Some(identifier.to_string()),
+                ..Default::default()
+            };
+            match block_on(RdsClient::new_with_client(client, region).describe_db_instances(request)) {
+                Ok(result) => Ok(result
+                    .db_instances
+                    .and_then(|instances| instances.into_iter().next())
+                    .map(ManagedDatabase::from)),
+                Err(RusotoError::Service(DescribeDBInstancesError::DBInstanceNotFoundFault(_))) => Ok(None),
+                Err(e) => Err(describe_error(e.to_string())),
+            }
+        }
+        DatabaseKind::Mongodb => {
+            let request = DescribeDBClustersMessage {
+                db_cluster_identifier: Some(identifier.to_string()),
+                ..Default::default()
+            };
+            match block_on(DocdbClient::new_with_client(client, region).describe_db_clusters(request)) {
+                Ok(result) => Ok(result
+                    .db_clusters
+                    .and_then(|clusters| clusters.into_iter().next())
+                    .map(ManagedDatabase::from)),
+                Err(RusotoError::Service(DescribeDBClustersError::DBClusterNotFoundFault(_))) => Ok(None),
+                Err(e) => Err(describe_error(e.to_string())),
+            }
+        }
+        DatabaseKind::Redis => {
+            let elasticache = ElastiCacheClient::new_with_client(client, region);
+            let mut marker = None;
+            loop {
+                let request = DescribeCacheClustersMessage {
+                    marker,
+                    ..Default::default()
+                };
+                let result = block_on(elasticache.describe_cache_clusters(request))
+                    .map_err(|e| describe_error(e.to_string()))?;
+
+                let cache_cluster = result
+                    .cache_clusters
+                    .unwrap_or_default()
+                    .into_iter()
+                    .find(|cache_cluster| is_cache_cluster_of(cache_cluster, identifier));
+                if let Some(cache_cluster) = cache_cluster {
+                    return Ok(Some(ManagedDatabase::from(cache_cluster)));
+                }
+
+                marker = match result.marker {
+                    Some(marker) => Some(marker),
+                    None => return Ok(None),
+                };
+            }
+        }
+    }
+}
+
+/// Nodes of an Elasticache replication group are named after it, ex: `identifier-001`.
+fn is_cache_cluster_of(cache_cluster: &CacheCluster, identifier: &str) -> bool {
+    cache_cluster.cache_cluster_id.as_deref() == Some(identifier)
+        || cache_cluster.replication_group_id.as_deref() == Some(identifier)
+}
+
 pub fn get_parameter_group_from_version(version: &str, database_kind: DatabaseKind) -> Result<String, StringError> {
     let version_number = match get_version_number(version) {
         Ok(v) => {
@@ -37,8 +377,60 @@ pub fn get_parameter_group_from_version(version: &str, database_kind: DatabaseKi
 
 #[cfg(test)]
 mod tests_aws_databases_parameters {
-    use crate::cloud_provider::aws::databases::utilities::get_parameter_group_from_version;
+    use crate::cloud_provider::aws::databases::utilities::{
+        get_parameter_group_from_version, is_cache_cluster_of, rds_clone_snapshot_identifier, ManagedDatabase,
+    };
     use crate::models::DatabaseKind;
+    use rusoto_docdb::DBCluster;
+    use rusoto_elasticache::CacheCluster;
+    use rusoto_rds::DBInstance;
+
+    #[test]
+    fn check_managed_database() {
+        let rds_instance = DBInstance {
+            db_instance_identifier: Some("mysql-z1234".to_string()),
+            engine_version: Some("8.0.23".to_string()),
+            allocated_storage: Some(20),
+            ..Default::default()
+        };
+        let docdb_cluster = DBCluster {
+            db_cluster_identifier: Some("mongodb-z1234".to_string()),
+            engine_version: Some("4.0.0".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            ManagedDatabase::from(rds_instance),
+            ManagedDatabase {
+                engine_version: Some("8.0.23".to_string()),
+                allocated_storage_in_gib: Some(20),
+            }
+        );
+        assert_eq!(
+            ManagedDatabase::from(docdb_cluster),
+            ManagedDatabase {
+                engine_version: Some("4.0.0".to_string()),
+                allocated_storage_in_gib: None,
+            }
+        );
+    }
+
+    #[test]
+    fn check_cache_cluster_of_replication_group() {
+        let cache_cluster = |cache_cluster_id: &str, replication_group_id: Option<&str>| CacheCluster {
+            cache_cluster_id: Some(cache_cluster_id.to_string()),
+            replication_group_id: replication_group_id.map(|id| id.to_string()),
+            engine_version: Some("6.0.5".to_string()),
+            ..Default::default()
+        };
+
+        assert!(is_cache_cluster_of(&cache_cluster("redis-z1234", None), "redis-z1234"));
+        assert!(is_cache_cluster_of(
+            &cache_cluster("redis-z1234-001", Some("redis-z1234")),
+            "redis-z1234"
+        ));
+        assert!(!is_cache_cluster_of(&cache_cluster("redis-other", None), "redis-z1234"));
+    }
 
     #[test]
     fn check_rds_mysql_parameter_groups() {
@@ -54,4 +446,26 @@ mod tests_aws_databases_parameters {
             "Can't determine the minor version, to select parameter group for Mysql version 8"
         );
     }
+
+    #[test]
+    fn check_rds_clone_snapshot_identifier() {
+        assert_eq!(
+            rds_clone_snapshot_identifier("postgresql-z1234", "postgresql-z5678"),
+            "clone-postgresql-z1234-to-postgresql-z5678"
+        );
+        // identifiers are lowercase letters, digits and single hyphens
+        assert_eq!(
+            rds_clone_snapshot_identifier("MySQL_z1234-", "-mysql.z5678"),
+            "clone-mysql-z1234-to-mysql-z5678"
+        );
+
+        let long_identifier = rds_clone_snapshot_identifier(&"a".repeat(200), &"b".repeat(200));
+        assert_eq!(long_identifier.len(), 255);
+        assert!(long_identifier.starts_with("clone-aaa"));
+        assert!(long_identifier.ends_with("bbb"));
+
+        // truncated on a hyphen, it can't end the identifier
+        let truncated_identifier = rds_clone_snapshot_identifier(&"a".repeat(245), "z5678");
+        assert_eq!(truncated_identifier, format!("clone-{}-to", "a".repeat(245)));
+    }
 }
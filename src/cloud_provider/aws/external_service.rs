@@ -1,7 +1,7 @@
 use tera::Context as TeraContext;
 
 use crate::build_platform::Image;
-use crate::cloud_provider::models::{EnvironmentVariable, EnvironmentVariableDataTemplate};
+use crate::cloud_provider::models::{EnvironmentVariable, EnvironmentVariableDataTemplate, InitContainer};
 use crate::cloud_provider::service::{
     default_tera_context, delete_stateless_service, deploy_stateless_service_error, deploy_user_stateless_service,
     send_progress_on_long_task, Action, Application as AApplication, Create, Delete, Helm, Pause, Service, ServiceType,
@@ -21,6 +21,7 @@ pub struct ExternalService {
     total_ram_in_mib: u32,
     image: Image,
     environment_variables: Vec<EnvironmentVariable>,
+    namespace: Option<String>,
     listeners: Listeners,
 }
 
@@ -34,6 +35,7 @@ impl ExternalService {
         total_ram_in_mib: u32,
         image: Image,
         environment_variables: Vec<EnvironmentVariable>,
+        namespace: Option<String>,
         listeners: Listeners,
     ) -> Self {
         ExternalService {
@@ -45,6 +47,7 @@ impl ExternalService {
             total_ram_in_mib,
             image,
             environment_variables,
+            namespace,
             listeners,
         }
     }
@@ -60,6 +63,18 @@ impl crate::cloud_provider::service::Application for ExternalService {
     fn set_image(&mut self, image: Image) {
         self.image = image;
     }
+
+    // external services run once to completion, they have no init containers
+    fn init_containers(&self) -> &[InitContainer] {
+        &[]
+    }
+
+    fn set_init_containers(&mut self, _init_containers: Vec<InitContainer>) {}
+
+    fn on_resume(&self, _target: &DeploymentTarget) -> Result<(), EngineError> {
+        // an external name service doesn't have any instance
+        Ok(())
+    }
 }
 
 impl Helm for ExternalService {
@@ -103,6 +118,10 @@ impl Service for ExternalService {
         format!("ext-service-{}", self.name())
     }
 
+    fn custom_namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
     fn version(&self) -> &str {
         self.image.commit_id.as_str()
     }
@@ -146,17 +165,21 @@ impl Service for ExternalService {
 
         context.insert("helm_app_version", &commit_id[..7]);
 
-        match &self.image().registry_url {
-            Some(registry_url) => context.insert("image_name_with_tag", registry_url.as_str()),
+        let image_name_with_tag = match &self.image().registry_url {
+            Some(registry_url) => registry_url.clone(),
             None => {
                 let image_name_with_tag = self.image().name_with_tag();
                 warn!(
                     "there is no registry url, use image name with tag with the default container registry: {}",
                     image_name_with_tag.as_str()
                 );
-                context.insert("image_name_with_tag", image_name_with_tag.as_str());
+                image_name_with_tag
             }
-        }
+        };
+        context.insert(
+            "image_name_with_tag",
+            self.context.rewrite_image(image_name_with_tag.as_str()).as_str(),
+        );
 
         let environment_variables = self
             .environment_variables
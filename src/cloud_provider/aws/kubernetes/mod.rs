@@ -10,23 +10,25 @@ use serde::{Deserialize, Serialize};
 use tera::Context as TeraContext;
 
 use crate::cloud_provider::aws::kubernetes::helm_charts::{aws_helm_charts, ChartsConfigPrerequisites};
-use crate::cloud_provider::aws::kubernetes::node::Node;
+use crate::cloud_provider::aws::kubernetes::node::{validate_instance_types, worker_nodes_data_templates, Node};
 use crate::cloud_provider::aws::kubernetes::roles::get_default_roles_to_create;
 use crate::cloud_provider::aws::AWS;
 use crate::cloud_provider::environment::Environment;
 use crate::cloud_provider::helm::deploy_charts_levels;
 use crate::cloud_provider::kubernetes::{
     is_kubernetes_upgrade_required, uninstall_cert_manager, Kind, Kubernetes, KubernetesNode, KubernetesNodesType,
-    KubernetesUpgradeStatus,
+    KubernetesUpgradeStatus, NodeCapacityType, NodePoolScheduling,
 };
 use crate::cloud_provider::models::WorkerNodeDataTemplate;
+use crate::cloud_provider::service::insert_resource_expiration_into_tera_context;
+use crate::cloud_provider::utilities::{get_version_number, VersionsNumber};
 use crate::cloud_provider::{kubernetes, CloudProvider};
 use crate::cmd;
 use crate::cmd::kubectl::{
     kubectl_exec_api_custom_metrics, kubectl_exec_get_all_namespaces, kubectl_exec_scale_replicas, ScalingKind,
 };
 use crate::cmd::structs::HelmChart;
-use crate::cmd::terraform::{terraform_exec, terraform_init_validate_plan_apply, terraform_init_validate_state_list};
+use crate::cmd::terraform::{terraform_exec, terraform_init_validate_state_list};
 use crate::deletion_utilities::{get_firsts_namespaces_to_delete, get_qovery_managed_namespaces};
 use crate::dns_provider;
 use crate::dns_provider::DnsProvider;
@@ -47,6 +49,9 @@ pub mod helm_charts;
 pub mod node;
 pub mod roles;
 
+// Kubernetes versions (major.minor) which can be deployed on EKS
+const EKS_SUPPORTED_KUBERNETES_VERSIONS: [&str; 4] = ["1.16", "1.17", "1.18", "1.19"];
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Options {
     // AWS related
@@ -194,19 +199,7 @@ impl<'a> EKS<'a> {
 
         let eks_access_cidr_blocks = format_ips(&self.options.eks_access_cidr_blocks);
 
-        let worker_nodes = self
-            .nodes
-            .iter()
-            .group_by(|e| e.instance_type())
-            .into_iter()
-            .map(|(instance_type, group)| (instance_type, group.collect::<Vec<_>>()))
-            .map(|(instance_type, nodes)| WorkerNodeDataTemplate {
-                instance_type: instance_type.to_string(),
-                desired_size: "3".to_string(),
-                max_size: nodes.len().to_string(),
-                min_size: "3".to_string(),
-            })
-            .collect::<Vec<WorkerNodeDataTemplate>>();
+        let worker_nodes = worker_nodes_data_templates(&self.nodes);
 
         let qovery_api_url = self.options.qovery_api_url.clone();
         let rds_cidr_subnet = self.options.rds_cidr_subnet.clone();
@@ -234,12 +227,7 @@ impl<'a> EKS<'a> {
         );
 
         context.insert("test_cluster", &self.context.is_test_cluster());
-        if self.context.resource_expiration_in_seconds().is_some() {
-            context.insert(
-                "resource_expiration_in_seconds",
-                &self.context.resource_expiration_in_seconds(),
-            )
-        }
+        insert_resource_expiration_into_tera_context(&self.context, &mut context);
         context.insert("force_upgrade", &self.context.requires_forced_upgrade());
 
         // Qovery features
@@ -420,6 +408,13 @@ impl<'a> EKS<'a> {
             .as_str(),
         );
 
+        // make sure the upgrade can be safely started
+        if kubernetes_upgrade_status.required_upgrade_on.is_some() {
+            self.check_kubernetes_upgrade_path(&kubernetes_upgrade_status)?;
+            send_to_customer(format!("Checking Kubernetes cluster {} health before upgrade", self.name()).as_str());
+            self.check_cluster_is_healthy()?;
+        }
+
         let temp_dir = workspace_directory(
             self.context.workspace_root_dir(),
             self.context.execution_id(),
@@ -489,7 +484,7 @@ impl<'a> EKS<'a> {
                 match cast_simple_error_to_engine_error(
                     self.engine_error_scope(),
                     self.context.execution_id(),
-                    terraform_init_validate_plan_apply(temp_dir.as_str(), self.context.is_dry_run_deploy()),
+                    kubernetes::terraform_apply(self, temp_dir.as_str(), self.context.is_dry_run_deploy()),
                 ) {
                     Ok(_) => {
                         let message = format!(
@@ -574,19 +569,19 @@ impl<'a> EKS<'a> {
         ) {
             Ok(_) => {}
             Err(e) => {
-                return Err(EngineError {
-                    cause: EngineErrorCause::Internal,
-                    scope: EngineErrorScope::Engine,
-                    execution_id: self.context.execution_id().to_string(),
-                    message: e.message,
-                })
+                return Err(EngineError::new(
+                    EngineErrorCause::Internal,
+                    EngineErrorScope::Engine,
+                    self.context.execution_id().to_string(),
+                    e.message,
+                ))
             }
         };
 
         match cast_simple_error_to_engine_error(
             self.engine_error_scope(),
             self.context.execution_id(),
-            terraform_init_validate_plan_apply(temp_dir.as_str(), self.context.is_dry_run_deploy()),
+            kubernetes::terraform_apply(self, temp_dir.as_str(), self.context.is_dry_run_deploy()),
         ) {
             Ok(_) => {
                 let message = format!(
@@ -628,12 +623,12 @@ impl<'a> EKS<'a> {
         ) {
             Ok(_) => {}
             Err(e) => {
-                return Err(EngineError {
-                    cause: EngineErrorCause::Internal,
-                    scope: EngineErrorScope::Engine,
-                    execution_id: self.context.execution_id().to_string(),
-                    message: e.message,
-                })
+                return Err(EngineError::new(
+                    EngineErrorCause::Internal,
+                    EngineErrorScope::Engine,
+                    self.context.execution_id().to_string(),
+                    e.message,
+                ))
             }
         };
 
@@ -674,12 +669,41 @@ impl<'a> Kubernetes for EKS<'a> {
         self.dns_provider
     }
 
-    fn config_file_store(&self) -> &dyn ObjectStorage {
-        &self.s3
+    fn config_file_store(&self) -> Option<&dyn ObjectStorage> {
+        Some(&self.s3)
+    }
+
+    fn supported_kubernetes_versions(&self) -> Option<Vec<VersionsNumber>> {
+        Some(
+            EKS_SUPPORTED_KUBERNETES_VERSIONS
+                .iter()
+                .filter_map(|v| get_version_number(v).ok())
+                .collect(),
+        )
+    }
+
+    fn nodes_capacity_types(&self) -> Vec<NodeCapacityType> {
+        self.nodes.iter().map(|node| node.capacity_type()).unique().collect()
+    }
+
+    fn node_pools_scheduling(&self) -> Vec<NodePoolScheduling> {
+        self.nodes.iter().map(|node| node.scheduling()).unique().collect()
+    }
+
+    fn nodes_instance_types(&self) -> Vec<String> {
+        self.nodes.iter().map(|node| node.instance_type().to_string()).collect()
     }
 
     fn is_valid(&self) -> Result<(), EngineError> {
-        Ok(())
+        if let Err(message) = validate_instance_types(&self.nodes, &self.region) {
+            return Err(self.engine_error(
+                EngineErrorCause::User("Choose a node instance type available in the cluster region"),
+                format!("cluster {}: {}", self.name_with_id(), message),
+            ));
+        }
+
+        // the kubeconfig is stored in the object storage
+        self.s3.is_valid()
     }
 
     fn on_create(&self) -> Result<(), EngineError> {
@@ -699,6 +723,9 @@ impl<'a> Kubernetes for EKS<'a> {
 
         send_to_customer(format!("Preparing EKS {} cluster deployment with id {}", self.name(), self.id()).as_str());
 
+        // reject unsupported versions before anything is created
+        self.check_kubernetes_version_is_supported()?;
+
         // upgrade cluster instead if required
         match self.config_file() {
             Ok(f) => match is_kubernetes_upgrade_required(
@@ -781,15 +808,15 @@ impl<'a> Kubernetes for EKS<'a> {
                             match terraform_exec(temp_dir.as_str(), vec!["state", "rm", &entry]) {
                                 Ok(_) => info!("successfully removed {}", &entry),
                                 Err(e) => {
-                                    return Err(EngineError {
-                                        cause: EngineErrorCause::Internal,
-                                        scope: EngineErrorScope::Engine,
-                                        execution_id: self.context.execution_id().to_string(),
-                                        message: Some(format!(
+                                    return Err(EngineError::new(
+                                        EngineErrorCause::Internal,
+                                        EngineErrorScope::Engine,
+                                        self.context.execution_id().to_string(),
+                                        Some(format!(
                                             "error while trying to remove {} out of terraform state file.\n {:?}",
                                             entry, e.message
                                         )),
-                                    })
+                                    ))
                                 }
                             }
                         };
@@ -806,7 +833,7 @@ impl<'a> Kubernetes for EKS<'a> {
         match cast_simple_error_to_engine_error(
             self.engine_error_scope(),
             self.context.execution_id(),
-            terraform_init_validate_plan_apply(temp_dir.as_str(), self.context.is_dry_run_deploy()),
+            kubernetes::terraform_apply(self, temp_dir.as_str(), self.context.is_dry_run_deploy()),
         ) {
             Ok(_) => {}
             Err(e) => {
@@ -878,6 +905,7 @@ impl<'a> Kubernetes for EKS<'a> {
                 &credentials_environment_variables,
                 helm_charts_to_deploy,
                 self.context.is_dry_run_deploy(),
+                self.context.image_registry_rewrites(),
             ),
         )
     }
@@ -910,12 +938,12 @@ impl<'a> Kubernetes for EKS<'a> {
                     e.message
                 );
                 error!("{}", &msg);
-                Err(EngineError {
-                    cause: EngineErrorCause::Internal,
-                    scope: EngineErrorScope::Engine,
-                    execution_id: self.context.execution_id().to_string(),
-                    message: Some(msg),
-                })
+                Err(EngineError::new(
+                    EngineErrorCause::Internal,
+                    EngineErrorScope::Engine,
+                    self.context.execution_id().to_string(),
+                    Some(msg),
+                ))
             }
         }
     }
@@ -1000,21 +1028,21 @@ impl<'a> Kubernetes for EKS<'a> {
                 tf_workers_resources_name
             }
             Err(e) => {
-                return Err(EngineError {
-                    cause: EngineErrorCause::Internal,
-                    scope: EngineErrorScope::Kubernetes(self.id.clone(), self.name.clone()),
-                    execution_id: self.context.execution_id().to_string(),
-                    message: e.message,
-                })
+                return Err(EngineError::new(
+                    EngineErrorCause::Internal,
+                    EngineErrorScope::Kubernetes(self.id.clone(), self.name.clone()),
+                    self.context.execution_id().to_string(),
+                    e.message,
+                ))
             }
         };
         if tf_workers_resources.is_empty() {
-            return Err(EngineError {
-                cause: EngineErrorCause::Internal,
-                scope: EngineErrorScope::Kubernetes(self.id.clone(), self.name.clone()),
-                execution_id: self.context.execution_id().to_string(),
-                message: Some("No worker nodes present, can't Pause the infrastructure. This can happen if there where a manual operations on the workers or the infrastructure is already pause.".to_string()),
-            });
+            return Err(EngineError::new(
+                EngineErrorCause::Internal,
+                EngineErrorScope::Kubernetes(self.id.clone(), self.name.clone()),
+                self.context.execution_id().to_string(),
+                Some("No worker nodes present, can't Pause the infrastructure. This can happen if there where a manual operations on the workers or the infrastructure is already pause.".to_string()),
+            ));
         }
 
         let kubernetes_config_file_path = self.config_file_path()?;
@@ -1065,12 +1093,12 @@ impl<'a> Kubernetes for EKS<'a> {
         match wait_engine_job_finish {
             Ok(_) => info!("no current running jobs on the Engine, infrastructure pause is allowed to start"),
             Err(Operation { error, .. }) => {
-                return Err(EngineError {
-                    cause: EngineErrorCause::Internal,
-                    scope: EngineErrorScope::Engine,
-                    execution_id: self.context.execution_id().to_string(),
-                    message: error.message,
-                })
+                return Err(EngineError::new(
+                    EngineErrorCause::Internal,
+                    EngineErrorScope::Engine,
+                    self.context.execution_id().to_string(),
+                    error.message,
+                ))
             }
             Err(retry::Error::Internal(msg)) => {
                 return Err(EngineError::new(
@@ -1188,7 +1216,7 @@ impl<'a> Kubernetes for EKS<'a> {
         if let Err(e) = cast_simple_error_to_engine_error(
             self.engine_error_scope(),
             self.context.execution_id(),
-            cmd::terraform::terraform_init_validate_plan_apply(temp_dir.as_str(), false),
+            kubernetes::terraform_apply(self, temp_dir.as_str(), false),
         ) {
             error!("An issue occurred during the apply before destroy of Terraform, it may be expected if you're resuming a destroy: {:?}", e.message);
         };
@@ -1357,7 +1385,11 @@ impl<'a> Kubernetes for EKS<'a> {
                 || match cast_simple_error_to_engine_error(
                     self.engine_error_scope(),
                     self.context.execution_id(),
-                    cmd::terraform::terraform_init_validate_destroy(temp_dir.as_str(), false),
+                    cmd::terraform::terraform_init_validate_destroy(
+                        temp_dir.as_str(),
+                        false,
+                        self.context.cancellation_token(),
+                    ),
                 ) {
                     Ok(_) => OperationResult::Ok(()),
                     Err(e) => OperationResult::Retry(e),
@@ -1414,6 +1446,11 @@ impl<'a> Kubernetes for EKS<'a> {
         Ok(())
     }
 
+    fn resume_environment(&self, environment: &Environment) -> Result<(), EngineError> {
+        info!("EKS.resume_environment() called for {}", self.name());
+        kubernetes::resume_environment(self, environment)
+    }
+
     fn delete_environment(&self, environment: &Environment) -> Result<(), EngineError> {
         info!("EKS.delete_environment() called for {}", self.name());
         kubernetes::delete_environment(self, environment)
@@ -1,10 +1,113 @@
 use std::any::Any;
+use std::collections::BTreeMap;
 
-use crate::cloud_provider::kubernetes::KubernetesNode;
+use rusoto_core::Region;
+
+use itertools::Itertools;
+
+use crate::cloud_provider::kubernetes::{
+    KubernetesNode, NodeCapacityType, NodePoolScheduling, Taint, TaintEffect, CAPACITY_TYPE_NODE_LABEL,
+};
+use crate::cloud_provider::models::{WorkerNodeDataTemplate, WorkerNodeTaintDataTemplate};
+
+// regions opened after the t2 generation only offer t3 and later instances
+const REGIONS_WITHOUT_T2: [&str; 5] = ["af-south-1", "ap-east-1", "eu-north-1", "eu-south-1", "me-south-1"];
+
+const INSTANCE_SIZES: [&str; 7] = ["nano", "micro", "small", "medium", "large", "xlarge", "metal"];
+
+/// EC2 instance types are written `<family>.<size>`, ex: `t3a.large`, `m5d.12xlarge`, `c5.metal` or `u-6tb1.112xlarge`.
+/// The family has a generation digit, the size is one of the named sizes, `<n>xlarge` or `metal-<n>xl`.
+fn is_instance_type_well_formed(instance_type: &str) -> bool {
+    let (family, size) = match instance_type.split_once('.') {
+        Some(parts) => parts,
+        None => return false,
+    };
+
+    let is_family_well_formed = family.starts_with(|c: char| c.is_ascii_lowercase())
+        && family.chars().any(|c| c.is_ascii_digit())
+        && family
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+
+    let is_multiple_of = |size: &str, prefix: &str, suffix: &str| {
+        size.strip_prefix(prefix)
+            .and_then(|size| size.strip_suffix(suffix))
+            .map(|multiplier| !multiplier.is_empty() && multiplier.chars().all(|c| c.is_ascii_digit()))
+            .unwrap_or(false)
+    };
+    let is_size_well_formed =
+        INSTANCE_SIZES.contains(&size) || is_multiple_of(size, "", "xlarge") || is_multiple_of(size, "metal-", "xl");
+
+    is_family_well_formed && is_size_well_formed
+}
+
+/// Checks every node instance type is well formed and not an older generation the region doesn't offer,
+/// before anything is created. Whether the region really offers the instance type is left to EC2.
+pub fn validate_instance_types(nodes: &[Node], region: &Region) -> Result<(), String> {
+    for node in nodes {
+        let instance_type = node.instance_type();
+        if !is_instance_type_well_formed(instance_type) {
+            return Err(format!(
+                "node instance type {} is invalid, it should look like <family>.<size>, ex: t3a.large",
+                instance_type
+            ));
+        }
+
+        if instance_type.starts_with("t2.") && REGIONS_WITHOUT_T2.contains(&region.name()) {
+            return Err(format!(
+                "node instance type {} is not available in region {}, use a newer generation such as t3",
+                instance_type,
+                region.name()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// EKS spells taint effects in upper snake case, ex: `NO_SCHEDULE`.
+fn eks_taint_effect(effect: &TaintEffect) -> &str {
+    match effect {
+        TaintEffect::NoSchedule => "NO_SCHEDULE",
+        TaintEffect::PreferNoSchedule => "PREFER_NO_SCHEDULE",
+        TaintEffect::NoExecute => "NO_EXECUTE",
+    }
+}
+
+/// Consecutive nodes sharing the same instance type, capacity type, labels and taints
+/// are rendered into the same EKS node group.
+pub fn worker_nodes_data_templates(nodes: &[Node]) -> Vec<WorkerNodeDataTemplate> {
+    nodes
+        .iter()
+        .group_by(|node| (node.instance_type(), node.capacity_type(), &node.labels, &node.taints))
+        .into_iter()
+        .map(
+            |((instance_type, capacity_type, labels, taints), group)| WorkerNodeDataTemplate {
+                instance_type: instance_type.to_string(),
+                desired_size: "3".to_string(),
+                max_size: group.count().to_string(),
+                min_size: "3".to_string(),
+                capacity_type: capacity_type.as_label_value().to_string(),
+                labels: labels.clone(),
+                taints: taints
+                    .iter()
+                    .map(|taint| WorkerNodeTaintDataTemplate {
+                        key: taint.key.clone(),
+                        value: taint.value.clone(),
+                        effect: eks_taint_effect(&taint.effect).to_string(),
+                    })
+                    .collect(),
+            },
+        )
+        .collect()
+}
 
 #[derive(Clone)]
 pub struct Node {
     instance_type: String,
+    capacity_type: NodeCapacityType,
+    labels: BTreeMap<String, String>,
+    taints: Vec<Taint>,
 }
 
 impl Node {
@@ -48,6 +151,43 @@ impl Node {
     pub fn new<T: Into<String>>(instance_type: T) -> Self {
         Node {
             instance_type: instance_type.into(),
+            capacity_type: NodeCapacityType::OnDemand,
+            labels: BTreeMap::new(),
+            taints: vec![],
+        }
+    }
+
+    /// Spot nodes are cheaper but can be reclaimed by AWS at any time, databases are never scheduled on them.
+    pub fn with_capacity_type(mut self, capacity_type: NodeCapacityType) -> Self {
+        self.capacity_type = capacity_type;
+        self
+    }
+
+    pub fn capacity_type(&self) -> NodeCapacityType {
+        self.capacity_type
+    }
+
+    pub fn with_label(mut self, key: &str, value: &str) -> Self {
+        self.labels.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn with_taint(mut self, taint: Taint) -> Self {
+        self.taints.push(taint);
+        self
+    }
+
+    /// Labels and taints of the node group the node belongs to, the capacity type label included.
+    pub fn scheduling(&self) -> NodePoolScheduling {
+        let mut labels = self.labels.clone();
+        labels.insert(
+            CAPACITY_TYPE_NODE_LABEL.to_string(),
+            self.capacity_type.as_label_value().to_string(),
+        );
+
+        NodePoolScheduling {
+            labels,
+            taints: self.taints.clone(),
         }
     }
 }
@@ -64,8 +204,10 @@ impl KubernetesNode for Node {
 
 #[cfg(test)]
 mod tests {
-    use crate::cloud_provider::aws::kubernetes::node::Node;
-    use crate::cloud_provider::kubernetes::KubernetesNode;
+    use crate::cloud_provider::aws::kubernetes::node::{validate_instance_types, worker_nodes_data_templates, Node};
+    use crate::cloud_provider::kubernetes::{KubernetesNode, NodeCapacityType, Taint, TaintEffect};
+    use rusoto_core::Region;
+    use tera::Context as TeraContext;
 
     #[test]
     fn test_instance_types() {
@@ -87,4 +229,119 @@ mod tests {
         assert_eq!(Node::new_with_cpu_and_mem(8, 32).instance_type(), "t2.2xlarge");
         assert_eq!(Node::new_with_cpu_and_mem(16, 64).instance_type(), "t2.2xlarge");
     }
+
+    #[test]
+    fn test_instance_types_availability() {
+        // setup:
+        let nodes = vec![Node::new("t2.large"), Node::new("t3.large")];
+
+        // execute & verify:
+        assert!(validate_instance_types(&nodes, &Region::EuWest3).is_ok());
+        assert!(validate_instance_types(&nodes[1..], &Region::EuNorth1).is_ok());
+        assert_eq!(
+            validate_instance_types(&nodes, &Region::EuNorth1),
+            Err(
+                "node instance type t2.large is not available in region eu-north-1, use a newer generation such as t3"
+                    .to_string()
+            )
+        );
+        for instance_type in &[
+            "x1.32xlarge",
+            "m5d.12xlarge",
+            "c6gn.medium",
+            "c5.metal",
+            "m6i.metal-24xl",
+            "u-6tb1.112xlarge",
+        ] {
+            assert!(
+                validate_instance_types(&[Node::new(*instance_type)], &Region::EuWest3).is_ok(),
+                "{} should be valid",
+                instance_type
+            );
+        }
+        for instance_type in &[
+            "",
+            "t3",
+            "t3.",
+            ".large",
+            "T3.large",
+            "t.large",
+            "t3.huge",
+            "t3.xxlarge",
+            "t3.large ",
+        ] {
+            assert!(
+                validate_instance_types(&[Node::new(*instance_type)], &Region::EuWest3).is_err(),
+                "{} should be invalid",
+                instance_type
+            );
+        }
+    }
+
+    #[test]
+    fn test_eks_node_groups_capacity_type() {
+        // setup:
+        let nodes = vec![
+            Node::new("t3.large"),
+            Node::new("t3.large"),
+            Node::new("t3.large").with_capacity_type(NodeCapacityType::Spot),
+        ];
+        let mut context = TeraContext::new();
+        context.insert("eks_worker_nodes", &worker_nodes_data_templates(&nodes));
+        let template = std::fs::read_to_string("lib/aws/bootstrap/eks-workers-nodes.j2.tf").unwrap();
+
+        // execute:
+        let rendered = tera::Tera::one_off(template.as_str(), &context, false).unwrap();
+
+        // verify:
+        let node_groups = rendered
+            .split("resource \"aws_eks_node_group\"")
+            .skip(1)
+            .collect::<Vec<_>>();
+        assert_eq!(node_groups.len(), 2);
+        assert!(node_groups[0].contains("capacity_type    = \"ON_DEMAND\""));
+        assert!(node_groups[0].contains("\"qovery.com/capacity-type\" = \"on-demand\""));
+        assert!(node_groups[0].contains("max_size     = \"2\""));
+        assert!(node_groups[1].contains("capacity_type    = \"SPOT\""));
+        assert!(node_groups[1].contains("\"qovery.com/capacity-type\" = \"spot\""));
+        assert!(node_groups[1].contains("max_size     = \"1\""));
+    }
+
+    #[test]
+    fn test_eks_node_groups_labels_and_taints() {
+        // setup:
+        let nodes = vec![
+            Node::new("t3.large"),
+            Node::new("t3.large")
+                .with_label("qovery.com/pool", "databases")
+                .with_taint(Taint {
+                    key: "dedicated".to_string(),
+                    value: "databases".to_string(),
+                    effect: TaintEffect::NoSchedule,
+                }),
+        ];
+        let mut context = TeraContext::new();
+        context.insert("eks_worker_nodes", &worker_nodes_data_templates(&nodes));
+        let template = std::fs::read_to_string("lib/aws/bootstrap/eks-workers-nodes.j2.tf").unwrap();
+
+        // execute:
+        let rendered = tera::Tera::one_off(template.as_str(), &context, false).unwrap();
+
+        // verify:
+        let node_groups = rendered
+            .split("resource \"aws_eks_node_group\"")
+            .skip(1)
+            .collect::<Vec<_>>();
+        assert_eq!(node_groups.len(), 2);
+        assert!(!node_groups[0].contains("qovery.com/pool"));
+        assert!(!node_groups[0].contains("taint {"));
+        assert!(node_groups[1].contains("\"qovery.com/pool\" = \"databases\""));
+        assert!(node_groups[1].contains(
+            "taint {\n    key    = \"dedicated\"\n    value  = \"databases\"\n    effect = \"NO_SCHEDULE\"\n  }"
+        ));
+        assert_eq!(
+            nodes[1].scheduling().labels.get("qovery.com/capacity-type"),
+            Some(&"on-demand".to_string())
+        );
+    }
 }
@@ -6,7 +6,7 @@ use rusoto_sts::{GetCallerIdentityRequest, Sts, StsClient};
 
 use crate::cloud_provider::{CloudProvider, EngineError, Kind, TerraformStateCredentials};
 use crate::constants::{AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY};
-use crate::error::EngineErrorCause;
+use crate::error::{check_credentials_are_set, EngineErrorCause};
 use crate::models::{Context, Listen, Listener, Listeners};
 use crate::runtime::block_on;
 
@@ -16,6 +16,9 @@ pub mod external_service;
 pub mod kubernetes;
 pub mod router;
 
+const BAD_CREDENTIALS: &str = "Your AWS account seems to be no longer valid (bad Credentials). \
+    Please contact your Organization administrator to fix or change the Credentials.";
+
 pub struct AWS {
     context: Context,
     id: String,
@@ -85,20 +88,29 @@ impl CloudProvider for AWS {
     }
 
     fn is_valid(&self) -> Result<(), EngineError> {
+        self.validate_credentials()
+    }
+
+    fn validate_credentials(&self) -> Result<(), EngineError> {
+        check_credentials_are_set(&[
+            ("access key id", self.access_key_id.as_str()),
+            ("secret access key", self.secret_access_key.as_str()),
+        ])
+        .map_err(|err| {
+            self.engine_error(
+                EngineErrorCause::User(BAD_CREDENTIALS),
+                format!("{} for AWS {}", err, self.name_with_id()),
+            )
+        })?;
+
         let client = StsClient::new_with_client(self.client(), Region::default());
-        let s = block_on(client.get_caller_identity(GetCallerIdentityRequest::default()));
-
-        match s {
-            Ok(_x) => Ok(()),
-            Err(_) => {
-                return Err(self.engine_error(
-                    EngineErrorCause::User(
-                        "Your AWS account seems to be no longer valid (bad Credentials). \
-                    Please contact your Organization administrator to fix or change the Credentials.",
-                    ),
-                    format!("failed to login to AWS {}", self.name_with_id()),
-                ));
-            }
+
+        match block_on(client.get_caller_identity(GetCallerIdentityRequest::default())) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(self.engine_error(
+                EngineErrorCause::User(BAD_CREDENTIALS),
+                format!("failed to login to AWS {}", self.name_with_id()),
+            )),
         }
     }
 
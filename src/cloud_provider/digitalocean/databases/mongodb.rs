@@ -1,16 +1,20 @@
+use std::collections::BTreeMap;
 use tera::Context as TeraContext;
 
 use crate::cloud_provider::service::{
-    check_service_version, default_tera_context, delete_stateful_service, deploy_stateful_service, get_tfstate_name,
-    get_tfstate_suffix, scale_down_database, send_progress_on_long_task, Action, Backup, Create, Database,
-    DatabaseOptions, DatabaseType, Delete, Downgrade, Helm, Pause, Service, ServiceType, StatefulService, Terraform,
-    Upgrade,
+    check_service_version, clone_self_hosted_database, default_tera_context, delete_stateful_service,
+    deploy_stateful_service, get_self_hosted_database_disk_size, get_self_hosted_database_version, get_tfstate_name,
+    get_tfstate_suffix, insert_database_node_pool_into_tera_context, insert_database_resources_into_tera_context,
+    insert_resource_expiration_into_tera_context, insert_terraform_backend_into_tera_context, resolve_secret,
+    scale_down_database, send_progress_on_long_task, stateful_service_template_directories,
+    unsupported_database_clone_error, Action, Backup, Create, Database, DatabaseOptions, DatabaseType, Delete,
+    Downgrade, Helm, Pause, Service, ServiceType, StatefulService, Terraform, Upgrade,
 };
 use crate::cloud_provider::utilities::{get_self_hosted_mongodb_version, sanitize_name};
 use crate::cloud_provider::DeploymentTarget;
 use crate::cmd::helm::Timeout;
 use crate::cmd::kubectl;
-use crate::error::{EngineError, EngineErrorScope};
+use crate::error::{EngineError, EngineErrorCause, EngineErrorScope, StringError};
 use crate::models::{Context, Listen, Listener, Listeners};
 
 pub struct MongoDB {
@@ -60,11 +64,19 @@ impl MongoDB {
     }
 
     fn matching_correct_version(&self) -> Result<String, EngineError> {
-        check_service_version(get_self_hosted_mongodb_version(self.version()), self)
+        check_service_version(self.supported_version(false), self)
     }
 }
 
-impl StatefulService for MongoDB {}
+impl StatefulService for MongoDB {
+    fn is_deletion_protected(&self) -> bool {
+        self.options.deletion_protection
+    }
+
+    fn supported_version(&self, _is_managed_services: bool) -> Result<String, StringError> {
+        get_self_hosted_mongodb_version(self.version())
+    }
+}
 
 impl Service for MongoDB {
     fn context(&self) -> &Context {
@@ -91,6 +103,14 @@ impl Service for MongoDB {
         self.version.as_str()
     }
 
+    fn deployed_version(&self, target: &DeploymentTarget) -> Result<Option<String>, EngineError> {
+        get_self_hosted_database_version(target, self)
+    }
+
+    fn deployed_disk_size_in_gib(&self, target: &DeploymentTarget) -> Result<Option<u32>, EngineError> {
+        get_self_hosted_database_disk_size(target, self)
+    }
+
     fn action(&self) -> &Action {
         &self.action
     }
@@ -99,8 +119,20 @@ impl Service for MongoDB {
         Some(self.options.port)
     }
 
+    fn endpoints(&self) -> Vec<String> {
+        vec![format!("{}:{}", self.fqdn, self.options.port)]
+    }
+
     fn start_timeout(&self) -> Timeout<u32> {
-        Timeout::Default
+        Timeout::Value(self.readiness_timeout().as_secs() as u32)
+    }
+
+    fn deploy_timeout(&self) -> Option<std::time::Duration> {
+        self.options.deploy_timeout()
+    }
+
+    fn custom_namespace(&self) -> Option<&str> {
+        self.options.namespace.as_deref()
     }
 
     fn total_cpus(&self) -> String {
@@ -119,6 +151,10 @@ impl Service for MongoDB {
         1
     }
 
+    fn template_directories(&self, target: &DeploymentTarget) -> Vec<String> {
+        stateful_service_template_directories(self, target)
+    }
+
     fn tera_context(&self, target: &DeploymentTarget) -> Result<TeraContext, EngineError> {
         let (kubernetes, environment) = match target {
             DeploymentTarget::ManagedServices(k, env) => (*k, *env),
@@ -132,12 +168,12 @@ impl Service for MongoDB {
         context.insert("kubeconfig_path", &kube_config_file_path);
 
         kubectl::kubectl_exec_create_namespace_without_labels(
-            &environment.namespace(),
+            self.namespace(environment),
             kube_config_file_path.as_str(),
             kubernetes.cloud_provider().credentials_environment_variables(),
         );
 
-        context.insert("namespace", environment.namespace());
+        context.insert("namespace", self.namespace(environment));
 
         let version = self.matching_correct_version()?;
         context.insert("version", &version);
@@ -154,24 +190,48 @@ impl Service for MongoDB {
 
         context.insert("database_db_name", self.name.as_str());
         context.insert("database_login", self.options.login.as_str());
-        context.insert("database_password", self.options.password.as_str());
+        context.insert(
+            "database_password",
+            resolve_secret(self, self.options.password.as_str())?.as_str(),
+        );
         context.insert("database_port", &self.private_port());
         context.insert("database_disk_size_in_gib", &self.options.disk_size_in_gib);
         context.insert("database_instance_type", &self.database_instance_type);
         context.insert("database_disk_type", &self.options.database_disk_type);
         context.insert("database_ram_size_in_mib", &self.total_ram_in_mib);
         context.insert("database_total_cpus", &self.total_cpus);
+
+        match self.options.resources(self.total_cpus.as_str(), self.total_ram_in_mib) {
+            Ok(resources) => insert_database_resources_into_tera_context(&resources, &mut context),
+            Err(e) => {
+                return Err(self.engine_error(
+                    EngineErrorCause::User("Check the resources overcommit of the database"),
+                    e,
+                ))
+            }
+        }
+        if let Err(e) = insert_database_node_pool_into_tera_context(
+            &self.options,
+            &kubernetes.node_pools_scheduling(),
+            &mut context,
+        ) {
+            return Err(self.engine_error(
+                EngineErrorCause::User("Check the node pool label the database is pinned to"),
+                e,
+            ));
+        }
         context.insert("database_fqdn", &self.options.host.as_str());
         context.insert("database_id", &self.id());
         context.insert("tfstate_suffix_name", &get_tfstate_suffix(self));
         context.insert("tfstate_name", &get_tfstate_name(self));
+        insert_terraform_backend_into_tera_context(
+            kubernetes.cloud_provider().terraform_state_credentials(),
+            self.namespace(environment),
+            &get_tfstate_name(self),
+            &mut context,
+        );
 
-        if self.context.resource_expiration_in_seconds().is_some() {
-            context.insert(
-                "resource_expiration_in_seconds",
-                &self.context.resource_expiration_in_seconds(),
-            )
-        }
+        insert_resource_expiration_into_tera_context(&self.context, &mut context);
 
         Ok(context)
     }
@@ -207,6 +267,10 @@ impl Helm for MongoDB {
     fn helm_chart_external_name_service_dir(&self) -> String {
         format!("{}/common/charts/external-name-svc", self.context.lib_root_dir())
     }
+
+    fn helm_value_overrides(&self) -> BTreeMap<String, String> {
+        self.options.helm_value_overrides.clone()
+    }
 }
 
 impl Terraform for MongoDB {
@@ -278,16 +342,27 @@ impl Delete for MongoDB {
 }
 
 impl crate::cloud_provider::service::Clone for MongoDB {
-    fn on_clone(&self, _target: &DeploymentTarget) -> Result<(), EngineError> {
-        unimplemented!()
+    fn on_clone(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        info!("DO.MongoDB.on_clone() called for {}", self.name());
+
+        match target {
+            DeploymentTarget::SelfHosted(..) => {
+                send_progress_on_long_task(self, crate::cloud_provider::service::Action::Create, || {
+                    clone_self_hosted_database(target, self)
+                })
+            }
+            DeploymentTarget::ManagedServices(..) => Err(unsupported_database_clone_error(self)),
+        }
     }
 
     fn on_clone_check(&self) -> Result<(), EngineError> {
-        unimplemented!()
+        self.on_create_check()
     }
 
     fn on_clone_error(&self, _target: &DeploymentTarget) -> Result<(), EngineError> {
-        unimplemented!()
+        warn!("DO.MongoDB.on_clone_error() called for {}", self.name());
+
+        Ok(())
     }
 }
 
@@ -9,6 +9,7 @@ pub const DIGITAL_OCEAN_API_URL: &str = "https://api.digitalocean.com";
 
 #[derive(Clone, Copy, Debug)]
 pub enum DoApiType {
+    Account,
     Doks,
     Vpc,
 }
@@ -22,6 +23,7 @@ impl fmt::Display for DoApiType {
 impl DoApiType {
     pub fn api_url(&self) -> String {
         match self {
+            DoApiType::Account => format!("{}/v2/account", DIGITAL_OCEAN_API_URL),
             DoApiType::Doks => format!("{}/v2/kubernetes", DIGITAL_OCEAN_API_URL),
             DoApiType::Vpc => format!("{}/v2/vpcs", DIGITAL_OCEAN_API_URL),
         }
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::env;
 
 use itertools::Itertools;
@@ -18,12 +19,13 @@ use crate::cloud_provider::digitalocean::network::vpc::{
 use crate::cloud_provider::digitalocean::DO;
 use crate::cloud_provider::environment::Environment;
 use crate::cloud_provider::helm::deploy_charts_levels;
-use crate::cloud_provider::kubernetes::{uninstall_cert_manager, Kind, Kubernetes, KubernetesNode};
+use crate::cloud_provider::kubernetes::{uninstall_cert_manager, Kind, Kubernetes, KubernetesNode, NodeCapacityType};
 use crate::cloud_provider::models::WorkerNodeDataTemplate;
+use crate::cloud_provider::service::insert_resource_expiration_into_tera_context;
 use crate::cloud_provider::{kubernetes, CloudProvider};
 use crate::cmd::kubectl::kubectl_exec_get_all_namespaces;
 use crate::cmd::structs::HelmChart;
-use crate::cmd::terraform::{terraform_exec, terraform_init_validate_plan_apply, terraform_init_validate_state_list};
+use crate::cmd::terraform::{terraform_exec, terraform_init_validate_state_list};
 use crate::deletion_utilities::{get_firsts_namespaces_to_delete, get_qovery_managed_namespaces};
 use crate::dns_provider::DnsProvider;
 use crate::error::EngineErrorCause::Internal;
@@ -104,7 +106,8 @@ impl<'a> DOKS<'a> {
             cloud_provider.spaces_access_id.clone(),
             cloud_provider.spaces_secret_key.clone(),
             region,
-        );
+        )
+        .with_credentials_check_bucket(format!("qovery-kubeconfigs-{}", id));
 
         DOKS {
             context,
@@ -161,12 +164,12 @@ impl<'a> DOKS<'a> {
                             match get_do_random_available_subnet_from_api(&self.cloud_provider.token, self.region) {
                                 Ok(x) => x,
                                 Err(e) => {
-                                    return Err(EngineError {
-                                        cause: EngineErrorCause::Internal,
-                                        scope: EngineErrorScope::Engine,
-                                        execution_id: self.context.execution_id().to_string(),
-                                        message: e.message,
-                                    })
+                                    return Err(EngineError::new(
+                                        EngineErrorCause::Internal,
+                                        EngineErrorScope::Engine,
+                                        self.context.execution_id().to_string(),
+                                        e.message,
+                                    ))
                                 }
                             }
                         }
@@ -174,12 +177,12 @@ impl<'a> DOKS<'a> {
                         Some(vpc) => vpc.ip_range,
                     },
                     Err(e) => {
-                        return Err(EngineError {
-                            cause: EngineErrorCause::Internal,
-                            scope: EngineErrorScope::Engine,
-                            execution_id: self.context.execution_id().to_string(),
-                            message: e.message,
-                        })
+                        return Err(EngineError::new(
+                            EngineErrorCause::Internal,
+                            EngineErrorScope::Engine,
+                            self.context.execution_id().to_string(),
+                            e.message,
+                        ))
                     }
                 }
             }
@@ -224,30 +227,30 @@ impl<'a> DOKS<'a> {
                 // new cluster, we check the wished version is supported by DO
                 None => match get_do_latest_doks_slug_from_api(self.cloud_provider.token.as_str(), self.version()) {
                     Ok(version) => match version {
-                        None => return Err(EngineError {
-                            cause: EngineErrorCause::Internal,
-                            scope: EngineErrorScope::Engine,
-                            execution_id: self.context.execution_id().to_string(),
-                            message: Some(format!("from the DigitalOcean API, no slug version match the required version ({}). This version is not supported anymore or not yet by DigitalOcean.", self.version()))
-                        }),
+                        None => return Err(EngineError::new(
+                            EngineErrorCause::Internal,
+                            EngineErrorScope::Engine,
+                            self.context.execution_id().to_string(),
+                            Some(format!("from the DigitalOcean API, no slug version match the required version ({}). This version is not supported anymore or not yet by DigitalOcean.", self.version())),
+                        )),
                         Some(v) => v,
                     }
-                    Err(e) => return Err(EngineError {
-                        cause: EngineErrorCause::Internal,
-                        scope: EngineErrorScope::Engine,
-                        execution_id: self.context.execution_id().to_string(),
-                        message: e.message,
-                    })
+                    Err(e) => return Err(EngineError::new(
+                        EngineErrorCause::Internal,
+                        EngineErrorScope::Engine,
+                        self.context.execution_id().to_string(),
+                        e.message,
+                    ))
                 },
                 // use the same deployed version number
                 Some(x) => x.version
             }
-            Err(e) => return Err(EngineError {
-                cause: EngineErrorCause::Internal,
-                scope: EngineErrorScope::Engine,
-                execution_id: self.context.execution_id().to_string(),
-                message: e.message
-            })
+            Err(e) => return Err(EngineError::new(
+                EngineErrorCause::Internal,
+                EngineErrorScope::Engine,
+                self.context.execution_id().to_string(),
+                e.message,
+            ))
         };
         context.insert("doks_version", doks_version.as_str());
         context.insert("do_space_kubeconfig_filename", &self.kubeconfig_file_name());
@@ -288,12 +291,7 @@ impl<'a> DOKS<'a> {
             "metrics_history_enabled",
             &self.context.is_feature_enabled(&Features::MetricsHistory),
         );
-        if self.context.resource_expiration_in_seconds().is_some() {
-            context.insert(
-                "resource_expiration_in_seconds",
-                &self.context.resource_expiration_in_seconds(),
-            )
-        }
+        insert_resource_expiration_into_tera_context(&self.context, &mut context);
 
         // grafana credentials
         context.insert("grafana_admin_user", self.options.grafana_admin_user.as_str());
@@ -370,6 +368,10 @@ impl<'a> DOKS<'a> {
                 desired_size: "3".to_string(),
                 max_size: nodes.len().to_string(),
                 min_size: "3".to_string(),
+                // DigitalOcean doesn't offer spot nodes
+                capacity_type: NodeCapacityType::OnDemand.as_label_value().to_string(),
+                labels: BTreeMap::new(),
+                taints: vec![],
             })
             .collect::<Vec<WorkerNodeDataTemplate>>();
 
@@ -438,12 +440,17 @@ impl<'a> Kubernetes for DOKS<'a> {
         self.dns_provider
     }
 
-    fn config_file_store(&self) -> &dyn ObjectStorage {
-        &self.spaces
+    fn config_file_store(&self) -> Option<&dyn ObjectStorage> {
+        Some(&self.spaces)
+    }
+
+    fn nodes_instance_types(&self) -> Vec<String> {
+        self.nodes.iter().map(|node| node.instance_type().to_string()).collect()
     }
 
     fn is_valid(&self) -> Result<(), EngineError> {
-        Ok(())
+        // the kubeconfig is stored in the object storage
+        self.spaces.is_valid()
     }
 
     fn on_create(&self) -> Result<(), EngineError> {
@@ -506,15 +513,15 @@ impl<'a> Kubernetes for DOKS<'a> {
                             match terraform_exec(temp_dir.as_str(), vec!["state", "rm", &entry]) {
                                 Ok(_) => info!("successfully removed {}", &entry),
                                 Err(e) => {
-                                    return Err(EngineError {
-                                        cause: EngineErrorCause::Internal,
-                                        scope: EngineErrorScope::Engine,
-                                        execution_id: self.context.execution_id().to_string(),
-                                        message: Some(format!(
+                                    return Err(EngineError::new(
+                                        EngineErrorCause::Internal,
+                                        EngineErrorScope::Engine,
+                                        self.context.execution_id().to_string(),
+                                        Some(format!(
                                             "error while trying to remove {} out of terraform state file.\n {:?}",
                                             entry, e.message
                                         )),
-                                    })
+                                    ))
                                 }
                             }
                         };
@@ -556,7 +563,7 @@ impl<'a> Kubernetes for DOKS<'a> {
         match cast_simple_error_to_engine_error(
             self.engine_error_scope(),
             self.context.execution_id(),
-            terraform_init_validate_plan_apply(temp_dir.as_str(), self.context.is_dry_run_deploy()),
+            kubernetes::terraform_apply(self, temp_dir.as_str(), self.context.is_dry_run_deploy()),
         ) {
             Ok(_) => {}
             Err(e) => {
@@ -611,24 +618,24 @@ impl<'a> Kubernetes for DOKS<'a> {
         let doks_id = match self.get_doks_info_from_name_api() {
             Ok(info) => match info {
                 None => {
-                    return Err(EngineError {
-                        cause: EngineErrorCause::Internal,
-                        scope: EngineErrorScope::Engine,
-                        execution_id: self.context.execution_id().to_string(),
-                        message: Some(format!(
+                    return Err(EngineError::new(
+                        EngineErrorCause::Internal,
+                        EngineErrorScope::Engine,
+                        self.context.execution_id().to_string(),
+                        Some(format!(
                             "DigitalOcean API reported no cluster id, while it has been deployed, please retry later"
                         )),
-                    })
+                    ))
                 }
                 Some(cluster) => cluster.id,
             },
             Err(e) => {
-                return Err(EngineError {
-                    cause: EngineErrorCause::Internal,
-                    scope: EngineErrorScope::Engine,
-                    execution_id: self.context.execution_id().to_string(),
-                    message: e.message,
-                })
+                return Err(EngineError::new(
+                    EngineErrorCause::Internal,
+                    EngineErrorScope::Engine,
+                    self.context.execution_id().to_string(),
+                    e.message,
+                ))
             }
         };
 
@@ -679,6 +686,7 @@ impl<'a> Kubernetes for DOKS<'a> {
                 &credentials_environment_variables,
                 helm_charts_to_deploy,
                 self.context.is_dry_run_deploy(),
+                self.context.image_registry_rewrites(),
             ),
         )
     }
@@ -781,7 +789,7 @@ impl<'a> Kubernetes for DOKS<'a> {
         if let Err(e) = cast_simple_error_to_engine_error(
             self.engine_error_scope(),
             self.context.execution_id(),
-            cmd::terraform::terraform_init_validate_plan_apply(temp_dir.as_str(), false),
+            kubernetes::terraform_apply(self, temp_dir.as_str(), false),
         ) {
             error!("An issue occurred during the apply before destroy of Terraform, it may be expected if you're resuming a destroy: {:?}", e.message);
         };
@@ -947,7 +955,11 @@ impl<'a> Kubernetes for DOKS<'a> {
                 || match cast_simple_error_to_engine_error(
                     self.engine_error_scope(),
                     self.context.execution_id(),
-                    cmd::terraform::terraform_init_validate_destroy(temp_dir.as_str(), false),
+                    cmd::terraform::terraform_init_validate_destroy(
+                        temp_dir.as_str(),
+                        false,
+                        self.context.cancellation_token(),
+                    ),
                 ) {
                     Ok(_) => OperationResult::Ok(()),
                     Err(e) => OperationResult::Retry(e),
@@ -1003,6 +1015,11 @@ impl<'a> Kubernetes for DOKS<'a> {
         Ok(())
     }
 
+    fn resume_environment(&self, environment: &Environment) -> Result<(), EngineError> {
+        info!("DOKS.resume_environment() called for {}", self.name());
+        kubernetes::resume_environment(self, environment)
+    }
+
     fn delete_environment(&self, environment: &Environment) -> Result<(), EngineError> {
         info!("DOKS.delete_environment() called for {}", self.name());
         kubernetes::delete_environment(self, environment)
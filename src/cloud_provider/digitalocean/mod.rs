@@ -4,9 +4,10 @@ use std::any::Any;
 
 use digitalocean::DigitalOcean;
 
+use crate::cloud_provider::digitalocean::do_api_common::{do_get_from_api, DoApiType};
 use crate::cloud_provider::{CloudProvider, Kind, TerraformStateCredentials};
 use crate::constants::DIGITAL_OCEAN_TOKEN;
-use crate::error::{EngineError, EngineErrorCause};
+use crate::error::{check_credentials_are_set, EngineError, EngineErrorCause};
 use crate::models::{Context, Listen, Listener, Listeners};
 
 pub mod application;
@@ -17,6 +18,9 @@ pub mod models;
 pub mod network;
 pub mod router;
 
+const BAD_CREDENTIALS: &str = "Your DigitalOcean account seems to be no longer valid (bad Credentials). \
+    Please contact your Organization administrator to fix or change the Credentials.";
+
 pub struct DO {
     context: Context,
     id: String,
@@ -85,16 +89,35 @@ impl CloudProvider for DO {
             Ok(_x) => Ok(()),
             Err(_) => {
                 return Err(self.engine_error(
-                    EngineErrorCause::User(
-                        "Your DigitalOcean account seems to be no longer valid (bad Credentials). \
-                    Please contact your Organization administrator to fix or change the Credentials.",
-                    ),
+                    EngineErrorCause::User(BAD_CREDENTIALS),
                     format!("failed to login to Digital Ocean {}", self.name_with_id()),
                 ));
             }
         }
     }
 
+    fn validate_credentials(&self) -> Result<(), EngineError> {
+        check_credentials_are_set(&[("token", self.token.as_str())]).map_err(|err| {
+            self.engine_error(
+                EngineErrorCause::User(BAD_CREDENTIALS),
+                format!("{} for Digital Ocean {}", err, self.name_with_id()),
+            )
+        })?;
+
+        do_get_from_api(self.token.as_str(), DoApiType::Account, DoApiType::Account.api_url())
+            .map(|_| ())
+            .map_err(|err| {
+                self.engine_error(
+                    EngineErrorCause::User(BAD_CREDENTIALS),
+                    format!(
+                        "failed to login to Digital Ocean {}: {}",
+                        self.name_with_id(),
+                        err.message.unwrap_or_default()
+                    ),
+                )
+            })
+    }
+
     fn credentials_environment_variables(&self) -> Vec<(&str, &str)> {
         vec![(DIGITAL_OCEAN_TOKEN, self.token.as_str())]
     }
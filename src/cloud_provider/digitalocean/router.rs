@@ -1,10 +1,11 @@
 use tera::Context as TeraContext;
 
 use crate::cloud_provider::environment::Kind;
-use crate::cloud_provider::models::{CustomDomain, CustomDomainDataTemplate, Route, RouteDataTemplate};
+use crate::cloud_provider::models::{CustomDomain, CustomDomainDataTemplate, Route, RouteDataTemplate, RouterProtocol};
 use crate::cloud_provider::service::{
-    default_tera_context, delete_router, delete_stateless_service, send_progress_on_long_task, Action, Create, Delete,
-    Helm, Pause, Service, ServiceType, StatelessService,
+    cleanup_stale_run_workspace_directories, default_tera_context, delete_router, delete_stateless_service,
+    run_workspace_directory, send_progress_on_long_task, Action, Create, Delete, Helm, Pause, Router as RRouter,
+    Service, ServiceType, StatelessService,
 };
 use crate::cloud_provider::utilities::{check_cname_for, sanitize_name};
 use crate::cloud_provider::DeploymentTarget;
@@ -20,6 +21,9 @@ pub struct Router {
     default_domain: String,
     custom_domains: Vec<CustomDomain>,
     routes: Vec<Route>,
+    protocol: RouterProtocol,
+    public_port: u16,
+    namespace: Option<String>,
     listeners: Listeners,
 }
 
@@ -31,9 +35,14 @@ impl Router {
         action: Action,
         default_domain: &str,
         custom_domains: Vec<CustomDomain>,
-        routes: Vec<Route>,
+        mut routes: Vec<Route>,
+        protocol: RouterProtocol,
+        public_port: u16,
+        namespace: Option<String>,
         listeners: Listeners,
     ) -> Self {
+        Route::sort_by_specificity(&mut routes);
+
         Router {
             context,
             id: id.to_string(),
@@ -42,6 +51,9 @@ impl Router {
             default_domain: default_domain.to_string(),
             custom_domains,
             routes,
+            protocol,
+            public_port,
+            namespace,
             listeners,
         }
     }
@@ -68,6 +80,10 @@ impl Service for Router {
         sanitize_name("router", self.name())
     }
 
+    fn custom_namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
     fn version(&self) -> &str {
         "1.0"
     }
@@ -80,6 +96,10 @@ impl Service for Router {
         None
     }
 
+    fn endpoints(&self) -> Vec<String> {
+        self.domains().iter().map(|domain| domain.to_string()).collect()
+    }
+
     fn start_timeout(&self) -> Timeout<u32> {
         Timeout::Default
     }
@@ -144,11 +164,11 @@ impl Service for Router {
                     .find(|app| app.name() == r.application_name.as_str())
                 {
                     Some(application) => match application.private_port() {
-                        Some(private_port) => Some(RouteDataTemplate {
-                            path: r.path.clone(),
-                            application_name: application.sanitized_name().to_string(),
-                            application_port: private_port,
-                        }),
+                        Some(private_port) => Some(RouteDataTemplate::new(
+                            r,
+                            application.sanitized_name().to_string(),
+                            private_port,
+                        )),
                         _ => None,
                     },
                     _ => None,
@@ -217,6 +237,8 @@ impl Service for Router {
         context.insert("router_default_domain_hash", router_default_domain_hash.as_str());
         context.insert("custom_domains", &custom_domain_data_templates);
         context.insert("routes", &route_data_templates);
+        context.insert("router_protocol", self.protocol.as_str());
+        context.insert("public_port", &self.public_port);
         context.insert("spec_acme_email", "tls@qovery.com"); // TODO CHANGE ME
         context.insert("metadata_annotations_cert_manager_cluster_issuer", "letsencrypt-qovery");
 
@@ -297,7 +319,7 @@ impl Create for Router {
             DeploymentTarget::SelfHosted(k, env) => (k, env),
         };
 
-        let workspace_dir = self.workspace_directory();
+        let workspace_dir = run_workspace_directory(self)?;
         let helm_release_name = self.helm_release_name();
 
         let kubernetes_config_file_path = kubernetes.config_file_path()?;
@@ -319,10 +341,11 @@ impl Create for Router {
             self.context.execution_id(),
             crate::cmd::helm::helm_exec_with_upgrade_history(
                 kubernetes_config_file_path.as_str(),
-                environment.namespace(),
+                self.namespace(environment),
                 helm_release_name.as_str(),
                 workspace_dir.as_str(),
                 Timeout::Default,
+                &self.helm_value_overrides(),
                 kubernetes.cloud_provider().credentials_environment_variables(),
             ),
         )?;
@@ -331,15 +354,15 @@ impl Create for Router {
             return Err(self.engine_error(EngineErrorCause::Internal, "Router has failed to be deployed".into()));
         }
 
+        cleanup_stale_run_workspace_directories(self, workspace_dir.as_str());
+
+        // check non custom domains
+        self.check_domains(kubernetes.dns_provider())?;
+
         Ok(())
     }
 
     fn on_create_check(&self) -> Result<(), EngineError> {
-        use crate::cloud_provider::service::Router;
-
-        // check non custom domains
-        self.check_domains()?;
-
         // Wait/Check that custom domain is a CNAME targeting qovery
         for domain_to_check in self.custom_domains.iter() {
             match check_cname_for(
@@ -39,6 +39,25 @@ impl Environment {
         self.namespace.as_str()
     }
 
+    /// Distinct namespaces of the environment: its own one first, then the ones its services are isolated in.
+    pub fn namespaces(&self) -> Vec<&str> {
+        let mut namespaces = vec![self.namespace()];
+
+        let services_namespaces = self
+            .stateless_services
+            .iter()
+            .map(|service| service.namespace(self))
+            .chain(self.stateful_services.iter().map(|service| service.namespace(self)));
+
+        for namespace in services_namespaces {
+            if !namespaces.contains(&namespace) {
+                namespaces.push(namespace);
+            }
+        }
+
+        namespaces
+    }
+
     pub fn is_valid(&self) -> Result<(), EngineError> {
         for service in self.stateful_services.iter() {
             if let Err(err) = service.is_valid() {
@@ -55,6 +74,17 @@ impl Environment {
         Ok(())
     }
 
+    /// Error messages of all the databases whose version isn't supported by the deployment target.
+    pub fn unsupported_database_versions(&self, is_managed_services: bool) -> Vec<String> {
+        self.stateful_services
+            .iter()
+            .filter_map(|service| match service.supported_version(is_managed_services) {
+                Ok(_) => None,
+                Err(err) => Some(format!("{} ({})", err, service.name_with_id())),
+            })
+            .collect()
+    }
+
     /// compute the required resources for this environment from
     /// applications, external services, routers, and databases
     /// Note: Even if external services don't run on the targeted Kubernetes cluster, it requires CPU and memory resources to run the container(s)
@@ -125,3 +155,112 @@ pub struct EnvironmentResources {
     pub cpu: f32,
     pub ram_in_mib: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cloud_provider::aws::databases::mysql::MySQL;
+    use crate::cloud_provider::aws::databases::postgresql::PostgreSQL;
+    use crate::cloud_provider::environment::{Environment, Kind};
+    use crate::cloud_provider::service::{Action, DatabaseOptions, StatefulService};
+    use crate::models::Context;
+    use std::collections::BTreeMap;
+
+    fn database_options() -> DatabaseOptions {
+        DatabaseOptions {
+            login: "".to_string(),
+            password: "".to_string(),
+            host: "".to_string(),
+            port: 5432,
+            disk_size_in_gib: 10,
+            database_disk_type: "gp2".to_string(),
+            replicas: 0,
+            iops: None,
+            storage_throughput: None,
+            deletion_protection: false,
+            sentinel_enabled: false,
+            resources_overcommit_percent: None,
+            parameters: BTreeMap::new(),
+            require_tls: false,
+            tls_certificates: None,
+            deletion_timeout_in_seconds: None,
+            helm_value_overrides: BTreeMap::new(),
+            deploy_timeout_in_seconds: None,
+            namespace: None,
+            clone_from: None,
+            backup_retention_days: None,
+            backup_window: None,
+            node_pool_label: None,
+        }
+    }
+
+    fn context() -> Context {
+        Context::new(
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            false,
+            None,
+            vec![],
+            None,
+        )
+    }
+
+    #[test]
+    fn test_unsupported_database_versions() {
+        // setup:
+        let mysql = |id: &str, version: &str| -> Box<dyn StatefulService> {
+            Box::new(MySQL::new(
+                context(),
+                id,
+                Action::Create,
+                id,
+                version,
+                "mysql.qovery.io",
+                id,
+                "1".to_string(),
+                512,
+                "db.t2.micro",
+                database_options(),
+                vec![],
+            ))
+        };
+        let postgresql = |id: &str, version: &str| -> Box<dyn StatefulService> {
+            Box::new(PostgreSQL::new(
+                context(),
+                id,
+                Action::Create,
+                id,
+                version,
+                "postgresql.qovery.io",
+                id,
+                "1".to_string(),
+                512,
+                "db.t2.micro",
+                database_options(),
+                vec![],
+            ))
+        };
+        let environment = Environment::new(
+            Kind::Production,
+            "envid",
+            "projectid",
+            "ownerid",
+            "organizationid",
+            vec![],
+            vec![
+                mysql("mysqlok", "8.0"),
+                postgresql("pgko", "1.0"),
+                postgresql("pgok", "12"),
+                mysql("mysqlko", "2"),
+            ],
+        );
+
+        // execute:
+        let unsupported_versions = environment.unsupported_database_versions(true);
+
+        // verify:
+        assert_eq!(unsupported_versions.len(), 2);
+        assert!(unsupported_versions[0].contains("pgko"));
+        assert!(unsupported_versions[1].contains("mysqlko"));
+    }
+}
@@ -0,0 +1,323 @@
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use crate::cloud_provider::environment::Environment;
+use crate::cloud_provider::kubernetes::{Kind, Kubernetes};
+use crate::cloud_provider::utilities::{get_version_number, VersionsNumber};
+use crate::cloud_provider::{kubernetes, CloudProvider};
+use crate::cmd::kubectl::kubectl_exec_version;
+use crate::cmd::structs::ServerVersion;
+use crate::dns_provider::DnsProvider;
+use crate::error::{EngineError, EngineErrorCause, StringError};
+use crate::fs::workspace_directory;
+use crate::models::{
+    Context, Listen, Listener, Listeners, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope, StringPath,
+};
+use crate::object_storage::ObjectStorage;
+
+const EXISTING_CLUSTER_SUPPORTED_KUBERNETES_VERSIONS: [&str; 4] = ["1.18", "1.19", "1.20", "1.21"];
+
+/// Kubernetes cluster created and managed outside of the engine, only reachable through its kubeconfig.
+/// Nothing is created or deleted on the cluster itself, only environments are deployed on it,
+/// the cloud provider gives the charts and credentials used to deploy them.
+pub struct ExistingCluster<'a> {
+    context: Context,
+    id: String,
+    name: String,
+    version: String,
+    region: String,
+    cloud_provider: &'a dyn CloudProvider,
+    dns_provider: &'a dyn DnsProvider,
+    kubeconfig: String,
+    listeners: Listeners,
+}
+
+impl<'a> ExistingCluster<'a> {
+    pub fn new(
+        context: Context,
+        id: &str,
+        name: &str,
+        version: &str,
+        region: &str,
+        cloud_provider: &'a dyn CloudProvider,
+        dns_provider: &'a dyn DnsProvider,
+        kubeconfig: &str,
+    ) -> Self {
+        ExistingCluster {
+            context,
+            id: id.to_string(),
+            name: name.to_string(),
+            version: version.to_string(),
+            region: region.to_string(),
+            cloud_provider,
+            dns_provider,
+            kubeconfig: kubeconfig.to_string(),
+            listeners: cloud_provider.listeners().clone(), // copy listeners from CloudProvider
+        }
+    }
+
+    fn send_to_customer(&self, message: &str) {
+        let listeners_helper = ListenersHelper::new(&self.listeners);
+
+        listeners_helper.deployment_in_progress(ProgressInfo::new(
+            ProgressScope::Infrastructure {
+                execution_id: self.context.execution_id().to_string(),
+            },
+            ProgressLevel::Info,
+            Some(message),
+            self.context.execution_id(),
+        ));
+    }
+}
+
+impl<'a> Kubernetes for ExistingCluster<'a> {
+    fn context(&self) -> &Context {
+        &self.context
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Existing
+    }
+
+    fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn version(&self) -> &str {
+        self.version.as_str()
+    }
+
+    fn region(&self) -> &str {
+        self.region.as_str()
+    }
+
+    fn cloud_provider(&self) -> &dyn CloudProvider {
+        self.cloud_provider
+    }
+
+    fn dns_provider(&self) -> &dyn DnsProvider {
+        self.dns_provider
+    }
+
+    fn config_file_store(&self) -> Option<&dyn ObjectStorage> {
+        // the kubeconfig is given with the cluster, it's never stored by the engine
+        None
+    }
+
+    fn config_file(&self) -> Result<(StringPath, File), EngineError> {
+        let dir = workspace_directory(
+            self.context.workspace_root_dir(),
+            self.context.execution_id(),
+            format!("existing_cluster/{}", self.id()),
+        )
+        .map_err(|err| self.engine_error(EngineErrorCause::Internal, err.to_string()))?;
+
+        let path = Path::new(dir.as_str()).join(format!("{}.yaml", self.id()));
+        let string_path = path.to_string_lossy().to_string();
+
+        let write_kubeconfig = || -> Result<File, std::io::Error> {
+            let mut file = std::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(&path)?;
+            file.write_all(self.kubeconfig.as_bytes())?;
+            File::open(&path)
+        };
+
+        match write_kubeconfig() {
+            Ok(file) => Ok((string_path, file)),
+            Err(err) => Err(self.engine_error(
+                EngineErrorCause::Internal,
+                format!("Can't write kubeconfig of cluster {}: {:?}", self.name_with_id(), err),
+            )),
+        }
+    }
+
+    fn supported_kubernetes_versions(&self) -> Option<Vec<VersionsNumber>> {
+        Some(
+            EXISTING_CLUSTER_SUPPORTED_KUBERNETES_VERSIONS
+                .iter()
+                .filter_map(|v| get_version_number(v).ok())
+                .collect(),
+        )
+    }
+
+    fn is_valid(&self) -> Result<(), EngineError> {
+        self.check_kubernetes_version_is_supported()?;
+
+        let kubernetes_version = match kubectl_exec_version(
+            self.config_file_path()?,
+            self.cloud_provider.credentials_environment_variables(),
+        ) {
+            Ok(kubernetes_version) => kubernetes_version,
+            Err(err) => {
+                return Err(self.engine_error(
+                    EngineErrorCause::User("Check the kubeconfig of your cluster, its API server can't be reached"),
+                    format!(
+                        "Can't reach Kubernetes API server of cluster {}: {:?}",
+                        self.name_with_id(),
+                        err.message
+                    ),
+                ))
+            }
+        };
+
+        check_server_version(&kubernetes_version.server_version, self.version()).map_err(|message| {
+            self.engine_error(
+                EngineErrorCause::User("The Kubernetes version of your cluster doesn't match the declared one"),
+                format!("Cluster {} is invalid: {}", self.name_with_id(), message),
+            )
+        })
+    }
+
+    fn on_create(&self) -> Result<(), EngineError> {
+        info!("ExistingCluster.on_create() called for {}", self.name());
+        self.send_to_customer(
+            format!(
+                "Kubernetes cluster {} with id {} is managed outside of Qovery, nothing to create",
+                self.name(),
+                self.id()
+            )
+            .as_str(),
+        );
+        Ok(())
+    }
+
+    fn on_create_error(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_upgrade(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_upgrade_error(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_downgrade(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_downgrade_error(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_pause(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_pause_error(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_delete(&self) -> Result<(), EngineError> {
+        info!("ExistingCluster.on_delete() called for {}", self.name());
+        self.send_to_customer(
+            format!(
+                "Kubernetes cluster {} with id {} is managed outside of Qovery, nothing to delete",
+                self.name(),
+                self.id()
+            )
+            .as_str(),
+        );
+        Ok(())
+    }
+
+    fn on_delete_error(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn deploy_environment(&self, environment: &Environment) -> Result<(), EngineError> {
+        info!("ExistingCluster.deploy_environment() called for {}", self.name());
+        kubernetes::deploy_environment(self, environment)
+    }
+
+    fn deploy_environment_error(&self, environment: &Environment) -> Result<(), EngineError> {
+        warn!("ExistingCluster.deploy_environment_error() called for {}", self.name());
+        kubernetes::deploy_environment_error(self, environment)
+    }
+
+    fn pause_environment(&self, environment: &Environment) -> Result<(), EngineError> {
+        info!("ExistingCluster.pause_environment() called for {}", self.name());
+        kubernetes::pause_environment(self, environment)
+    }
+
+    fn pause_environment_error(&self, _environment: &Environment) -> Result<(), EngineError> {
+        warn!("ExistingCluster.pause_environment_error() called for {}", self.name());
+        Ok(())
+    }
+
+    fn resume_environment(&self, environment: &Environment) -> Result<(), EngineError> {
+        info!("ExistingCluster.resume_environment() called for {}", self.name());
+        kubernetes::resume_environment(self, environment)
+    }
+
+    fn delete_environment(&self, environment: &Environment) -> Result<(), EngineError> {
+        info!("ExistingCluster.delete_environment() called for {}", self.name());
+        kubernetes::delete_environment(self, environment)
+    }
+
+    fn delete_environment_error(&self, _environment: &Environment) -> Result<(), EngineError> {
+        warn!("ExistingCluster.delete_environment_error() called for {}", self.name());
+        Ok(())
+    }
+}
+
+impl<'a> Listen for ExistingCluster<'a> {
+    fn listeners(&self) -> &Listeners {
+        &self.listeners
+    }
+
+    fn add_listener(&mut self, listener: Listener) {
+        self.listeners.push(listener);
+    }
+}
+
+/// Check the version returned by the API server matches (on major and minor) the declared version.
+/// Some providers suffix the minor version, ex: EKS returns `21+`.
+fn check_server_version(server_version: &ServerVersion, declared_version: &str) -> Result<(), StringError> {
+    let declared_version = get_version_number(declared_version)?;
+    let server_minor = server_version.minor.trim_end_matches(|c: char| !c.is_ascii_digit());
+
+    if server_version.major == declared_version.major && Some(server_minor) == declared_version.minor.as_deref() {
+        return Ok(());
+    }
+
+    Err(format!(
+        "Kubernetes API server runs version `{}.{}` ({}) while version `{}` is declared",
+        server_version.major, server_minor, server_version.git_version, declared_version
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cloud_provider::existing_cluster::check_server_version;
+    use crate::cmd::structs::ServerVersion;
+
+    #[test]
+    fn test_check_server_version() {
+        // setup:
+        let server_version = |major: &str, minor: &str| ServerVersion {
+            major: major.to_string(),
+            minor: minor.to_string(),
+            git_version: format!("v{}.{}.3", major, minor),
+        };
+
+        // execute & verify:
+        assert!(check_server_version(&server_version("1", "19"), "1.19").is_ok());
+        assert!(check_server_version(&server_version("1", "19"), "v1.19").is_ok());
+        assert!(check_server_version(&server_version("1", "21+"), "1.21").is_ok());
+        assert!(check_server_version(&server_version("1", "20"), "1.19").is_err());
+        assert!(check_server_version(&server_version("1", "2"), "1.21").is_err());
+        assert!(check_server_version(&server_version("2", "19"), "1.19").is_err());
+    }
+}
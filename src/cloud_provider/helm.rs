@@ -9,9 +9,10 @@ use crate::cmd::kubectl::{
     kubectl_exec_rollout_restart_deployment, kubectl_exec_with_output,
 };
 use crate::cmd::structs::HelmHistoryRow;
+use crate::container_registry::utilities::rewrite_image_reference;
 use crate::error::{SimpleError, SimpleErrorKind};
 use crate::utilities::calculate_hash;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 use std::{fs, thread};
 use thread::spawn;
@@ -248,7 +249,20 @@ pub fn deploy_charts_levels(
     envs: &Vec<(String, String)>,
     charts: Vec<Vec<Box<dyn HelmChart>>>,
     dry_run: bool,
+    image_registry_rewrites: &BTreeMap<String, String>,
 ) -> Result<(), SimpleError> {
+    // images are pulled from the registries they are rewritten to
+    if !image_registry_rewrites.is_empty() {
+        for chart in charts.iter().flatten() {
+            let chart_info = chart.get_chart_info();
+            if let HelmAction::Deploy = chart_info.action {
+                let mut values_files = vec![format!("{}/values.yaml", chart_info.path)];
+                values_files.extend(chart_info.values_files.iter().cloned());
+                rewrite_values_files_images(image_registry_rewrites, &values_files)?;
+            }
+        }
+    }
+
     // first show diff
     for level in &charts {
         for chart in level {
@@ -644,6 +658,112 @@ impl HelmChart for PrometheusOperatorConfigChart {
 //     }
 // }
 
+/// Chart values with their image registries rewritten, see `rewrite_image_reference`. Images are either
+/// a `repository` (or `image`) naming its registry, or split in `registry` and `repository` siblings as in
+/// Bitnami charts. Tags and digests are other keys, they are kept as the rest of the file.
+pub fn rewrite_values_images(image_registry_rewrites: &BTreeMap<String, String>, values: &str) -> String {
+    let mut lines = values.lines().map(|line| line.to_string()).collect::<Vec<_>>();
+    // indentation, line index and value of the last `registry` key of the current map
+    let mut registry: Option<(usize, usize, String)> = None;
+
+    for index in 0..lines.len() {
+        let line = lines[index].clone();
+        let content = line.trim_start();
+        let indentation = line.len() - content.len();
+        if content.is_empty() || content.starts_with('#') {
+            continue;
+        }
+        if matches!(&registry, Some((registry_indentation, _, _)) if indentation < *registry_indentation) {
+            registry = None;
+        }
+
+        let (key, value) = match content.split_once(':') {
+            Some((key, value)) => (key.trim(), yaml_scalar(value)),
+            None => continue,
+        };
+        if value.is_empty() {
+            continue;
+        }
+
+        let indent = " ".repeat(indentation);
+        let sibling_registry = match &registry {
+            Some((registry_indentation, _, _)) if *registry_indentation == indentation && key == "repository" => {
+                registry.take()
+            }
+            _ => None,
+        };
+
+        match (key, sibling_registry) {
+            ("registry", _) => registry = Some((indentation, index, value.to_string())),
+            ("repository", Some((_, registry_index, registry_value))) => {
+                let image = format!("{}/{}", registry_value, value);
+                let rewritten_image = rewrite_image_reference(image_registry_rewrites, image.as_str());
+                if let Some((host, repository)) = rewritten_image.split_once('/') {
+                    if rewritten_image != image {
+                        lines[registry_index] = format!("{}registry: {}", indent, host);
+                        lines[index] = format!("{}repository: {}", indent, repository);
+                    }
+                }
+            }
+            ("repository", None) | ("image", None) => {
+                let rewritten_image = rewrite_image_reference(image_registry_rewrites, value);
+                if rewritten_image != value {
+                    lines[index] = format!("{}{}: {}", indent, key, rewritten_image);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut rewritten_values = lines.join("\n");
+    if values.ends_with('\n') {
+        rewritten_values.push('\n');
+    }
+
+    rewritten_values
+}
+
+/// Value of a single line YAML scalar, without quotes nor trailing comment.
+fn yaml_scalar(value: &str) -> &str {
+    let value = match value.find(" #") {
+        Some(comment_position) => &value[..comment_position],
+        None => value,
+    };
+
+    value.trim().trim_matches('"').trim_matches('\'')
+}
+
+/// Rewrites the image registries of chart values files in place, see `rewrite_values_images`.
+/// Charts don't always have a values file, missing ones are skipped.
+pub fn rewrite_values_files_images(
+    image_registry_rewrites: &BTreeMap<String, String>,
+    values_files: &[String],
+) -> Result<(), SimpleError> {
+    for values_file in values_files {
+        if !Path::new(values_file).exists() {
+            continue;
+        }
+
+        let rewrite_values_file = || -> Result<(), std::io::Error> {
+            let values = fs::read_to_string(values_file)?;
+            let rewritten_values = rewrite_values_images(image_registry_rewrites, values.as_str());
+            if rewritten_values != values {
+                fs::write(values_file, rewritten_values)?;
+            }
+            Ok(())
+        };
+
+        if let Err(e) = rewrite_values_file() {
+            return Err(SimpleError::new(
+                SimpleErrorKind::Other,
+                Some(format!("can't rewrite images of values file {}: {:?}", values_file, e)),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
 pub fn get_latest_successful_deployment(helm_history_list: &[HelmHistoryRow]) -> Result<HelmHistoryRow, SimpleError> {
     let mut helm_history_reversed = helm_history_list.to_owned();
     helm_history_reversed.reverse();
@@ -665,7 +785,9 @@ pub fn get_latest_successful_deployment(helm_history_list: &[HelmHistoryRow]) ->
 
 #[cfg(test)]
 mod tests {
-    use crate::cloud_provider::helm::get_latest_successful_deployment;
+    use std::collections::BTreeMap;
+
+    use crate::cloud_provider::helm::{get_latest_successful_deployment, rewrite_values_images};
     use crate::cmd::structs::HelmHistoryRow;
 
     #[test]
@@ -703,4 +825,62 @@ mod tests {
         let final_succeed = get_latest_successful_deployment(&results).unwrap();
         assert_eq!(results[1].updated, final_succeed.updated);
     }
+
+    #[test]
+    fn test_rewrite_values_images() {
+        // setup:
+        let mut rewrites = BTreeMap::new();
+        rewrites.insert("docker.io".to_string(), "registry.internal/dockerhub".to_string());
+        rewrites.insert("quay.io".to_string(), "registry.internal/quay".to_string());
+        let values = r#"image:
+  registry: quay.io
+  repository: bitnami/postgresql
+  tag: "10.16.0"
+volumePermissions:
+  image:
+    registry: docker.io
+    repository: "bitnami/minideb" # init container
+    digest: sha256:c3bd7fa2b4
+controller:
+  image:
+    repository: quay.io/kubernetes-ingress-controller/nginx-ingress-controller
+defaultBackend:
+  image: k8s.gcr.io/defaultbackend-amd64:1.5
+  repository: jettech/kube-webhook-certgen
+mirrored:
+  registry: registry.internal
+  repository: dockerhub/bitnami/redis
+"#;
+
+        // execute:
+        let rewritten_values = rewrite_values_images(&rewrites, values);
+
+        // verify:
+        assert_eq!(
+            rewritten_values,
+            r#"image:
+  registry: registry.internal
+  repository: quay/bitnami/postgresql
+  tag: "10.16.0"
+volumePermissions:
+  image:
+    registry: registry.internal
+    repository: dockerhub/bitnami/minideb
+    digest: sha256:c3bd7fa2b4
+controller:
+  image:
+    repository: registry.internal/quay/kubernetes-ingress-controller/nginx-ingress-controller
+defaultBackend:
+  image: k8s.gcr.io/defaultbackend-amd64:1.5
+  repository: registry.internal/dockerhub/jettech/kube-webhook-certgen
+mirrored:
+  registry: registry.internal
+  repository: dockerhub/bitnami/redis
+"#
+        );
+        assert_eq!(
+            rewrite_values_images(&rewrites, rewritten_values.as_str()),
+            rewritten_values
+        );
+    }
 }
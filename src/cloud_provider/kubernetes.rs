@@ -1,4 +1,5 @@
 use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
@@ -8,23 +9,31 @@ use retry::delay::Fibonacci;
 use retry::Error::Operation;
 use retry::OperationResult;
 use serde::{Deserialize, Serialize};
+use tracing::{Level, Span};
 
 use crate::cloud_provider::environment::Environment;
-use crate::cloud_provider::service::CheckAction;
+use crate::cloud_provider::pricing::{pricing_table, CostEstimate};
+use crate::cloud_provider::service::{service_span, CheckAction, Service};
 use crate::cloud_provider::utilities::{get_version_number, VersionsNumber};
 use crate::cloud_provider::{service, CloudProvider, DeploymentTarget};
+use crate::cmd::helm::{helm_exec_uninstall, helm_list_releases};
 use crate::cmd::kubectl;
+use crate::cmd::kubectl::ScalingKind;
 use crate::cmd::kubectl::{
     kubectl_delete_objects_in_all_namespaces, kubectl_exec_count_all_objects, kubectl_exec_get_node,
-    kubectl_exec_version,
+    kubectl_exec_get_scheduled_pods, kubectl_exec_version,
 };
+use crate::cmd::structs::{Helm, KubernetesList, KubernetesScheduledPod, LabelsContent, Namespace, Workload};
+use crate::cmd::terraform::{terraform_init_validate_plan_apply, TerraformApplyResult};
 use crate::dns_provider::DnsProvider;
 use crate::error::SimpleErrorKind::Other;
 use crate::error::{
     cast_simple_error_to_engine_error, EngineError, EngineErrorCause, EngineErrorScope, SimpleError, SimpleErrorKind,
+    StringError,
 };
 use crate::models::{Context, Listen, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope, StringPath};
 use crate::object_storage::ObjectStorage;
+use crate::telemetry;
 use crate::unit_conversion::{any_to_mi, cpu_string_to_float};
 
 pub trait Kubernetes: Listen {
@@ -42,15 +51,110 @@ pub trait Kubernetes: Listen {
     fn region(&self) -> &str;
     fn cloud_provider(&self) -> &dyn CloudProvider;
     fn dns_provider(&self) -> &dyn DnsProvider;
-    fn config_file_store(&self) -> &dyn ObjectStorage;
+    /// Object storage holding the cluster kubeconfig, `None` if the kubeconfig is not managed by the engine.
+    fn config_file_store(&self) -> Option<&dyn ObjectStorage>;
     fn is_valid(&self) -> Result<(), EngineError>;
+    /// Instance type of every node the cluster can scale up to.
+    fn nodes_instance_types(&self) -> Vec<String> {
+        vec![]
+    }
+    /// Capacity type of every node pool of the cluster, empty if it's not known by the engine.
+    fn nodes_capacity_types(&self) -> Vec<NodeCapacityType> {
+        vec![]
+    }
+    /// Labels and taints of every node pool of the cluster, empty if they're not known by the engine.
+    fn node_pools_scheduling(&self) -> Vec<NodePoolScheduling> {
+        vec![]
+    }
+    /// Ballpark monthly cost of the cluster nodes, at their maximum scale.
+    fn cost_estimate(&self) -> CostEstimate {
+        let instance_types = self.nodes_instance_types();
+
+        pricing_table().estimate_instances(
+            &self.cloud_provider().kind(),
+            self.region(),
+            &instance_types.iter().map(|t| t.as_str()).collect::<Vec<_>>(),
+        )
+    }
+    /// Kubernetes versions (major.minor) which can be deployed, `None` if the version is validated by the provider itself.
+    fn supported_kubernetes_versions(&self) -> Option<Vec<VersionsNumber>> {
+        None
+    }
+    fn check_kubernetes_version_is_supported(&self) -> Result<(), EngineError> {
+        let supported_versions = match self.supported_kubernetes_versions() {
+            Some(supported_versions) => supported_versions,
+            None => return Ok(()),
+        };
+
+        match is_kubernetes_version_supported(self.version(), &supported_versions) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(self.engine_error(
+                EngineErrorCause::User("The requested Kubernetes version is not supported"),
+                format!(
+                    "Kubernetes version `{}` is not supported for cluster {}, supported versions are: {}",
+                    self.version(),
+                    self.name_with_id(),
+                    supported_versions
+                        .iter()
+                        .map(|v| v.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            )),
+            Err(e) => Err(self.engine_error(EngineErrorCause::User("The requested Kubernetes version is invalid"), e)),
+        }
+    }
+    /// Make sure every node of the cluster is ready, to avoid starting an upgrade on an already degraded cluster.
+    fn check_cluster_is_healthy(&self) -> Result<(), EngineError> {
+        let nodes = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context().execution_id(),
+            kubectl_exec_get_node(
+                self.config_file_path()?,
+                self.cloud_provider().credentials_environment_variables(),
+            ),
+        )?;
+
+        check_kubernetes_nodes_are_ready(&nodes).map_err(|message| {
+            self.engine_error(
+                EngineErrorCause::User(
+                    "Kubernetes cluster is not healthy, please retry the upgrade once all nodes are ready",
+                ),
+                format!("Can't upgrade cluster {}: {}", self.name_with_id(), message),
+            )
+        })
+    }
+    fn check_kubernetes_upgrade_path(&self, upgrade_status: &KubernetesUpgradeStatus) -> Result<(), EngineError> {
+        for deployed_version in &[
+            &upgrade_status.deployed_masters_version,
+            &upgrade_status.deployed_workers_version,
+        ] {
+            if let Err(message) = validate_kubernetes_upgrade_path(deployed_version, &upgrade_status.requested_version)
+            {
+                return Err(self.engine_error(
+                    EngineErrorCause::User("Kubernetes minor versions can't be skipped during an upgrade"),
+                    format!("Can't upgrade cluster {}: {}", self.name_with_id(), message),
+                ));
+            }
+        }
+
+        Ok(())
+    }
     fn config_file(&self) -> Result<(StringPath, File), EngineError> {
         let bucket_name = format!("qovery-kubeconfigs-{}", self.id());
         let object_key = format!("{}.yaml", self.id());
 
-        let (string_path, file) = self
-            .config_file_store()
-            .get(bucket_name.as_str(), object_key.as_str(), true)?;
+        let config_file_store = match self.config_file_store() {
+            Some(config_file_store) => config_file_store,
+            None => {
+                return Err(self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!("No kubeconfig store is defined for cluster {}", self.name_with_id()),
+                ))
+            }
+        };
+
+        let (string_path, file) = config_file_store.get(bucket_name.as_str(), object_key.as_str(), true)?;
 
         let metadata = match file.metadata() {
             Ok(metadata) => metadata,
@@ -109,6 +213,25 @@ pub trait Kubernetes: Listen {
 
         Ok(resources)
     }
+    /// Cpu and ram of each node of the cluster which are not requested yet by the pods scheduled on it.
+    fn cluster_capacity(&self) -> Result<ClusterCapacity, EngineError> {
+        let kubernetes_config_file_path = self.config_file_path()?;
+        let envs = self.cloud_provider().credentials_environment_variables();
+
+        let nodes = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context().execution_id(),
+            kubectl_exec_get_node(kubernetes_config_file_path.as_str(), envs.clone()),
+        )?;
+
+        let pods = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context().execution_id(),
+            kubectl_exec_get_scheduled_pods(kubernetes_config_file_path.as_str(), envs),
+        )?;
+
+        Ok(ClusterCapacity::new(&nodes, &pods))
+    }
     fn on_create(&self) -> Result<(), EngineError>;
     fn on_create_error(&self) -> Result<(), EngineError>;
     fn on_upgrade(&self) -> Result<(), EngineError>;
@@ -123,6 +246,7 @@ pub trait Kubernetes: Listen {
     fn deploy_environment_error(&self, environment: &Environment) -> Result<(), EngineError>;
     fn pause_environment(&self, environment: &Environment) -> Result<(), EngineError>;
     fn pause_environment_error(&self, environment: &Environment) -> Result<(), EngineError>;
+    fn resume_environment(&self, environment: &Environment) -> Result<(), EngineError>;
     fn delete_environment(&self, environment: &Environment) -> Result<(), EngineError>;
     fn delete_environment_error(&self, environment: &Environment) -> Result<(), EngineError>;
     fn engine_error_scope(&self) -> EngineErrorScope {
@@ -149,6 +273,69 @@ pub enum Kind {
     Eks,
     Doks,
     ScwKapsule,
+    Existing,
+}
+
+/// Node label set on every node with its capacity type, stateful services are kept off `spot` nodes with it.
+pub const CAPACITY_TYPE_NODE_LABEL: &str = "qovery.com/capacity-type";
+
+/// How the nodes of a pool are bought: spot (preemptible) nodes are cheaper but can be reclaimed
+/// by the cloud provider at any time, so they only fit stateless workloads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NodeCapacityType {
+    OnDemand,
+    Spot,
+}
+
+impl NodeCapacityType {
+    /// Value of the `CAPACITY_TYPE_NODE_LABEL` node label.
+    pub fn as_label_value(&self) -> &str {
+        match self {
+            NodeCapacityType::OnDemand => "on-demand",
+            NodeCapacityType::Spot => "spot",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TaintEffect {
+    NoSchedule,
+    PreferNoSchedule,
+    NoExecute,
+}
+
+impl TaintEffect {
+    pub fn as_str(&self) -> &str {
+        match self {
+            TaintEffect::NoSchedule => "NoSchedule",
+            TaintEffect::PreferNoSchedule => "PreferNoSchedule",
+            TaintEffect::NoExecute => "NoExecute",
+        }
+    }
+}
+
+/// Nodes of a pool with a taint only accept pods tolerating it, pools are dedicated to some workloads with them.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Taint {
+    pub key: String,
+    pub value: String,
+    pub effect: TaintEffect,
+}
+
+/// Labels and taints shared by the nodes of a pool, pods are pinned to the pool with them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct NodePoolScheduling {
+    pub labels: BTreeMap<String, String>,
+    pub taints: Vec<Taint>,
+}
+
+impl NodePoolScheduling {
+    pub fn has_label(&self, key: &str, value: &str) -> bool {
+        self.labels
+            .get(key)
+            .map(|label_value| label_value == value)
+            .unwrap_or(false)
+    }
 }
 
 #[derive(Debug)]
@@ -162,13 +349,126 @@ pub struct Resources {
     pub running_nodes: u16,
 }
 
-/// common function to deploy a complete environment through Kubernetes and the different
-/// managed services.
-pub fn deploy_environment(kubernetes: &dyn Kubernetes, environment: &Environment) -> Result<(), EngineError> {
-    let listeners_helper = ListenersHelper::new(kubernetes.listeners());
+/// Cpu and ram left on each node of the cluster, what is allocatable minus what the pods scheduled on it request.
+#[derive(Debug, PartialEq)]
+pub struct ClusterCapacity {
+    pub nodes: Vec<NodeFreeResources>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct NodeFreeResources {
+    pub name: String,
+    pub cpu: f32,
+    pub ram_in_mib: u32,
+}
+
+impl ClusterCapacity {
+    pub fn new(
+        nodes: &KubernetesList<crate::cmd::structs::KubernetesNode>,
+        pods: &KubernetesList<KubernetesScheduledPod>,
+    ) -> Self {
+        let nodes = nodes
+            .items
+            .iter()
+            .map(|node| {
+                let containers = pods
+                    .items
+                    .iter()
+                    .filter(|pod| pod.spec.node_name.as_deref() == Some(node.metadata.name.as_str()))
+                    .flat_map(|pod| pod.spec.containers.iter());
+
+                let (requested_cpu, requested_ram_in_mib) =
+                    containers.fold((0.0, 0), |(cpu, ram_in_mib), container| {
+                        let requests = &container.resources.requests;
+                        (
+                            cpu + requests
+                                .get("cpu")
+                                .map(|cpu| cpu_string_to_float(cpu.as_str()))
+                                .unwrap_or(0.0),
+                            ram_in_mib + requests.get("memory").map(|ram| any_to_mi(ram.as_str())).unwrap_or(0),
+                        )
+                    });
+
+                NodeFreeResources {
+                    name: node.metadata.name.clone(),
+                    cpu: (cpu_string_to_float(node.status.allocatable.cpu.as_str()) - requested_cpu).max(0.0),
+                    ram_in_mib: any_to_mi(node.status.allocatable.memory.as_str()).saturating_sub(requested_ram_in_mib),
+                }
+            })
+            .collect();
 
-    let stateful_deployment_target = match kubernetes.kind() {
-        Kind::Eks => match environment.kind {
+        ClusterCapacity { nodes }
+    }
+
+    /// Check `instances` instances of `cpu` and `ram_in_mib` can be scheduled: one of them has to fit
+    /// on a single node, and all of them in what is left on the whole cluster.
+    pub fn check_can_fit(&self, cpu: f32, ram_in_mib: u32, instances: u32) -> Result<(), StringError> {
+        if !self
+            .nodes
+            .iter()
+            .any(|node| cpu <= node.cpu && ram_in_mib <= node.ram_in_mib)
+        {
+            let largest_free_cpu = self.nodes.iter().map(|node| node.cpu).fold(0.0, f32::max);
+            let largest_free_ram_in_mib = self.nodes.iter().map(|node| node.ram_in_mib).max().unwrap_or(0);
+
+            return Err(format!(
+                "{} CPU and {}mib RAM requested per instance while at most {:.2} CPU and {}mib RAM are free on a single node",
+                cpu, ram_in_mib, largest_free_cpu, largest_free_ram_in_mib
+            ));
+        }
+
+        let free_cpu: f32 = self.nodes.iter().map(|node| node.cpu).sum();
+        let free_ram_in_mib: u32 = self.nodes.iter().map(|node| node.ram_in_mib).sum();
+        let total_cpu = cpu * instances as f32;
+        let total_ram_in_mib = ram_in_mib * instances;
+
+        if total_cpu > free_cpu || total_ram_in_mib > free_ram_in_mib {
+            return Err(format!(
+                "{} CPU and {}mib RAM requested while only {:.2} CPU and {}mib RAM are free on the cluster",
+                total_cpu, total_ram_in_mib, free_cpu, free_ram_in_mib
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Span tagging the logs of a cluster operation with the execution and the cluster ids.
+pub fn kubernetes_span(kubernetes: &dyn Kubernetes, operation: &str) -> Span {
+    span!(
+        Level::INFO,
+        "kubernetes",
+        operation = operation,
+        execution_id = kubernetes.context().execution_id(),
+        service_id = kubernetes.id(),
+        service_type = format!("{:?}", kubernetes.kind()).as_str(),
+    )
+}
+
+/// Terraform init, plan and apply of the cluster resources, recorded as a `terraform_apply` operation.
+pub fn terraform_apply(
+    kubernetes: &dyn Kubernetes,
+    root_dir: &str,
+    dry_run: bool,
+) -> Result<TerraformApplyResult, SimpleError> {
+    telemetry::record(
+        kubernetes.context(),
+        telemetry::Operation::TerraformApply,
+        format!("{:?}", kubernetes.kind()).as_str(),
+        kubernetes.cloud_provider().kind().name(),
+        || terraform_init_validate_plan_apply(root_dir, dry_run, kubernetes.context().cancellation_token()),
+    )
+}
+
+/// Where the stateful services (databases) of the environment live: managed services are only used in production
+/// environments, on clusters whose cloud provider offers them.
+fn stateful_deployment_target<'a>(
+    kubernetes: &'a dyn Kubernetes,
+    environment: &'a Environment,
+) -> DeploymentTarget<'a> {
+    match kubernetes.kind() {
+        // on Scaleway, only MySQL and PostgreSQL have a managed offer (RDB), other databases stay self-hosted
+        Kind::Eks | Kind::ScwKapsule => match environment.kind {
             crate::cloud_provider::environment::Kind::Production => {
                 DeploymentTarget::ManagedServices(kubernetes, environment)
             }
@@ -178,32 +478,341 @@ pub fn deploy_environment(kubernetes: &dyn Kubernetes, environment: &Environment
         },
         // FIXME: We don't have any managed service on DO for now
         Kind::Doks => DeploymentTarget::SelfHosted(kubernetes, environment),
-        // TODO(benjaminch): We don't have any managed service on Scaleway for now
-        Kind::ScwKapsule => DeploymentTarget::SelfHosted(kubernetes, environment),
-    };
+        // an existing cluster has no managed service attached to it
+        Kind::Existing => DeploymentTarget::SelfHosted(kubernetes, environment),
+    }
+}
+
+/// common function to deploy a complete environment through Kubernetes and the different
+/// managed services.
+pub fn deploy_environment(kubernetes: &dyn Kubernetes, environment: &Environment) -> Result<(), EngineError> {
+    let listeners_helper = ListenersHelper::new(kubernetes.listeners());
+
+    let stateful_deployment_target = stateful_deployment_target(kubernetes, environment);
+
+    // stateless services are deployed on kubernetes, that's why we choose the deployment target SelfHosted.
+    let stateless_deployment_target = DeploymentTarget::SelfHosted(kubernetes, environment);
+
+    // do not deploy if a single database version is not supported
+    let _ = check_database_versions(kubernetes, environment, &stateful_deployment_target)?;
 
     // do not deploy if there is not enough resources
     let _ = check_kubernetes_has_enough_resources_to_deploy_environment(kubernetes, environment)?;
 
+    // managed databases don't run on the cluster nodes, only self-hosted ones need on-demand nodes
+    if let DeploymentTarget::SelfHosted(_, _) = stateful_deployment_target {
+        if let Err(message) = check_stateful_services_can_be_scheduled(
+            &kubernetes.nodes_capacity_types(),
+            environment.stateful_services.len(),
+        ) {
+            listeners_helper.deployment_in_progress(ProgressInfo::new(
+                ProgressScope::Environment {
+                    id: environment.id.clone(),
+                },
+                ProgressLevel::Warn,
+                Some(format!("Cluster {}: {}", kubernetes.name_with_id(), message)),
+                kubernetes.context().execution_id(),
+            ));
+        }
+    }
+
+    // services can be isolated in their own namespace, they must all exist before deploying
+    let _ = create_environment_namespaces(kubernetes, environment)?;
+
+    let mut created_services = vec![];
+    let result = deploy_environment_services(
+        kubernetes,
+        environment,
+        &stateful_deployment_target,
+        &stateless_deployment_target,
+        &listeners_helper,
+        &mut created_services,
+    );
+
+    match result {
+        Err(err) if kubernetes.context().rollback_partial_deploy() => Err(rollback_partial_deploy(
+            kubernetes,
+            environment,
+            &created_services,
+            &stateful_deployment_target,
+            &stateless_deployment_target,
+            &listeners_helper,
+            err,
+        )),
+        Ok(_) if kubernetes.context().clean_orphaned_helm_releases() => {
+            uninstall_orphaned_helm_releases(kubernetes, environment, &listeners_helper);
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// Label set on the namespaces created by the engine, with the id of the environment they were created for.
+pub const NAMESPACE_OWNER_LABEL: &str = "qovery.com/environment-id";
+
+/// Creates the distinct namespaces of the environment, labeled to expire with the environment if it has to.
+fn create_environment_namespaces(kubernetes: &dyn Kubernetes, environment: &Environment) -> Result<(), EngineError> {
+    let kubernetes_config_file_path = kubernetes.config_file_path()?;
+
+    cast_simple_error_to_engine_error(
+        kubernetes.engine_error_scope(),
+        kubernetes.context().execution_id(),
+        create_environment_namespaces_with(
+            environment,
+            |namespace| namespace_labels(kubernetes, kubernetes_config_file_path.as_str(), namespace),
+            |namespace| {
+                let mut labels = service::resource_expiration_labels(kubernetes.context()).unwrap_or_default();
+                labels.push(LabelsContent {
+                    name: NAMESPACE_OWNER_LABEL.to_string(),
+                    value: environment.id.clone(),
+                });
+
+                kubectl::kubectl_exec_create_namespace(
+                    kubernetes_config_file_path.as_str(),
+                    namespace,
+                    Some(labels),
+                    kubernetes.cloud_provider().credentials_environment_variables(),
+                )
+            },
+        ),
+    )
+}
+
+/// Namespaces which don't exist yet, or are owned by the environment, are created and labeled.
+/// Other existing namespaces are shared with the cluster or other environments, they are left untouched.
+fn create_environment_namespaces_with<L, F>(
+    environment: &Environment,
+    namespace_labels: L,
+    create_namespace: F,
+) -> Result<(), SimpleError>
+where
+    L: Fn(&str) -> Result<Option<HashMap<String, String>>, SimpleError>,
+    F: Fn(&str) -> Result<(), SimpleError>,
+{
+    for namespace in environment.namespaces() {
+        match namespace_labels(namespace)? {
+            Some(labels) if !is_namespace_owned_by_environment(environment, namespace, &labels) => continue,
+            _ => create_namespace(namespace)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Namespaces of the environment which can be deleted with it, the ones which exist and are owned by it.
+fn environment_owned_namespaces<L>(environment: &Environment, namespace_labels: L) -> Vec<&str>
+where
+    L: Fn(&str) -> Result<Option<HashMap<String, String>>, SimpleError>,
+{
+    environment
+        .namespaces()
+        .into_iter()
+        .filter(|namespace| match namespace_labels(namespace) {
+            Ok(Some(labels)) => is_namespace_owned_by_environment(environment, namespace, &labels),
+            Ok(None) => false,
+            Err(e) => {
+                warn!("can't get labels of namespace {}: {:?}", namespace, e.message);
+                false
+            }
+        })
+        .collect()
+}
+
+/// The environment namespace always belongs to it, the namespaces its services are isolated in
+/// only when the engine created them for this environment.
+fn is_namespace_owned_by_environment(
+    environment: &Environment,
+    namespace: &str,
+    labels: &HashMap<String, String>,
+) -> bool {
+    namespace == environment.namespace() || labels.get(NAMESPACE_OWNER_LABEL) == Some(&environment.id)
+}
+
+/// Labels of the namespace, `None` when it doesn't exist.
+fn namespace_labels(
+    kubernetes: &dyn Kubernetes,
+    kubernetes_config_file_path: &str,
+    namespace: &str,
+) -> Result<Option<HashMap<String, String>>, SimpleError> {
+    let envs = kubernetes.cloud_provider().credentials_environment_variables();
+
+    if !kubectl::kubectl_exec_is_namespace_present(kubernetes_config_file_path, namespace, envs.clone()) {
+        return Ok(None);
+    }
+
+    kubectl::kubectl_exec_get_namespace(kubernetes_config_file_path, namespace, envs)
+        .map(|namespace| Some(namespace.metadata.labels))
+}
+
+/// Helm releases are named after the service kind, see `Helm::helm_release_name()`.
+const SERVICES_HELM_RELEASE_PREFIXES: [&str; 7] = [
+    "application-",
+    "external-service-",
+    "router-",
+    "mongodb-",
+    "mysql-",
+    "postgresql-",
+    "redis-",
+];
+
+/// Releases created by the engine for services which are no longer part of the environment (renamed or removed).
+/// Managed databases `-externalname` releases belong to terraform and other releases to the user, they are kept.
+fn orphaned_helm_releases<'a>(releases: &'a [Helm], environment_release_names: &[String]) -> Vec<&'a Helm> {
+    releases
+        .iter()
+        .filter(|release| {
+            SERVICES_HELM_RELEASE_PREFIXES
+                .iter()
+                .any(|prefix| release.name.starts_with(prefix))
+        })
+        .filter(|release| !release.name.ends_with("-externalname"))
+        .filter(|release| !environment_release_names.contains(&release.name))
+        .collect()
+}
+
+/// Uninstalls orphaned helm releases once the environment is deployed. It is best effort, failures are only reported.
+fn uninstall_orphaned_helm_releases(
+    kubernetes: &dyn Kubernetes,
+    environment: &Environment,
+    listeners_helper: &ListenersHelper,
+) {
+    let progress = |level: ProgressLevel, message: String| {
+        listeners_helper.deployment_in_progress(ProgressInfo::new(
+            ProgressScope::Environment {
+                id: environment.id.clone(),
+            },
+            level,
+            Some(message),
+            kubernetes.context().execution_id(),
+        ))
+    };
+
+    let kubernetes_config_file_path = match kubernetes.config_file_path() {
+        Ok(path) => path,
+        Err(err) => {
+            return progress(
+                ProgressLevel::Warn,
+                format!(
+                    "Orphaned helm releases can't be listed: {}",
+                    err.message.unwrap_or_default()
+                ),
+            )
+        }
+    };
+    let envs = kubernetes.cloud_provider().credentials_environment_variables();
+
+    let mut releases = vec![];
+    for namespace in environment.namespaces() {
+        match helm_list_releases(kubernetes_config_file_path.as_str(), namespace, envs.clone()) {
+            Ok(namespace_releases) => releases.extend(namespace_releases),
+            Err(err) => {
+                return progress(
+                    ProgressLevel::Warn,
+                    format!(
+                        "Orphaned helm releases can't be listed: {}",
+                        err.message.unwrap_or_default()
+                    ),
+                )
+            }
+        }
+    }
+
+    let environment_release_names = environment
+        .stateless_services
+        .iter()
+        .map(|service| service.helm_release_name())
+        .chain(
+            environment
+                .stateful_services
+                .iter()
+                .map(|service| service.helm_release_name()),
+        )
+        .collect::<Vec<_>>();
+
+    for release in orphaned_helm_releases(&releases, &environment_release_names) {
+        match helm_exec_uninstall(
+            kubernetes_config_file_path.as_str(),
+            release.namespace.as_str(),
+            release.name.as_str(),
+            envs.clone(),
+        ) {
+            Ok(_) => progress(
+                ProgressLevel::Info,
+                format!(
+                    "Helm release {} belongs to no service of the environment, it has been uninstalled",
+                    release.name
+                ),
+            ),
+            Err(err) => progress(
+                ProgressLevel::Warn,
+                format!(
+                    "Orphaned helm release {} can't be uninstalled: {}",
+                    release.name,
+                    err.message.unwrap_or_default()
+                ),
+            ),
+        }
+    }
+}
+
+/// Check all the databases versions at once, before creating anything.
+fn check_database_versions(
+    kubernetes: &dyn Kubernetes,
+    environment: &Environment,
+    stateful_deployment_target: &DeploymentTarget,
+) -> Result<(), EngineError> {
+    let is_managed_services = match stateful_deployment_target {
+        DeploymentTarget::ManagedServices(_, _) => true,
+        DeploymentTarget::SelfHosted(_, _) => false,
+    };
+
+    let unsupported_versions = environment.unsupported_database_versions(is_managed_services);
+    if unsupported_versions.is_empty() {
+        return Ok(());
+    }
+
+    Err(kubernetes.engine_error(
+        EngineErrorCause::User("Some databases versions are not supported, use supported versions instead"),
+        format!("unsupported databases versions: {}", unsupported_versions.join(", ")),
+    ))
+}
+
+/// A service successfully created by an environment deployment, by index in the environment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CreatedService {
+    Stateful(usize),
+    Stateless(usize),
+}
+
+fn deploy_environment_services(
+    kubernetes: &dyn Kubernetes,
+    environment: &Environment,
+    stateful_deployment_target: &DeploymentTarget,
+    stateless_deployment_target: &DeploymentTarget,
+    listeners_helper: &ListenersHelper,
+    created_services: &mut Vec<CreatedService>,
+) -> Result<(), EngineError> {
     // create all stateful services (database)
-    for service in &environment.stateful_services {
+    for (index, service) in environment.stateful_services.iter().enumerate() {
         let _ = service::check_kubernetes_service_error(
-            service.exec_action(&stateful_deployment_target),
+            service.exec_action(stateful_deployment_target),
             kubernetes,
             service,
-            &stateful_deployment_target,
-            &listeners_helper,
+            stateful_deployment_target,
+            listeners_helper,
             "deployment",
             CheckAction::Deploy,
         )?;
+        created_services.push(CreatedService::Stateful(index));
+
         // check all deployed services
         for service in &environment.stateful_services {
             let _ = service::check_kubernetes_service_error(
                 service.on_create_check(),
                 kubernetes,
                 service,
-                &stateful_deployment_target,
-                &listeners_helper,
+                stateful_deployment_target,
+                listeners_helper,
                 "check deployment",
                 CheckAction::Deploy,
             )?;
@@ -213,20 +822,18 @@ pub fn deploy_environment(kubernetes: &dyn Kubernetes, environment: &Environment
     // Quick fix: adding 100 ms delay to avoid race condition on service status update
     thread::sleep(std::time::Duration::from_millis(100));
 
-    // stateless services are deployed on kubernetes, that's why we choose the deployment target SelfHosted.
-    let stateless_deployment_target = DeploymentTarget::SelfHosted(kubernetes, environment);
-
     // create all stateless services (router, application...)
-    for service in &environment.stateless_services {
+    for (index, service) in environment.stateless_services.iter().enumerate() {
         let _ = service::check_kubernetes_service_error(
-            service.exec_action(&stateless_deployment_target),
+            service.exec_action(stateless_deployment_target),
             kubernetes,
             service,
-            &stateless_deployment_target,
-            &listeners_helper,
+            stateless_deployment_target,
+            listeners_helper,
             "deployment",
             CheckAction::Deploy,
         )?;
+        created_services.push(CreatedService::Stateless(index));
     }
 
     // Quick fix: adding 100 ms delay to avoid race condition on service status update
@@ -238,8 +845,8 @@ pub fn deploy_environment(kubernetes: &dyn Kubernetes, environment: &Environment
             service.on_create_check(),
             kubernetes,
             service,
-            &stateful_deployment_target,
-            &listeners_helper,
+            stateful_deployment_target,
+            listeners_helper,
             "check deployment",
             CheckAction::Deploy,
         )?;
@@ -253,8 +860,8 @@ pub fn deploy_environment(kubernetes: &dyn Kubernetes, environment: &Environment
             service.on_create_check(),
             kubernetes,
             service,
-            &stateless_deployment_target,
-            &listeners_helper,
+            stateless_deployment_target,
+            listeners_helper,
             "check deployment",
             CheckAction::Deploy,
         )?;
@@ -263,6 +870,85 @@ pub fn deploy_environment(kubernetes: &dyn Kubernetes, environment: &Environment
     Ok(())
 }
 
+/// Reverts the services created by a failed environment deployment, the last created first.
+fn rollback_partial_deploy(
+    kubernetes: &dyn Kubernetes,
+    environment: &Environment,
+    created_services: &[CreatedService],
+    stateful_deployment_target: &DeploymentTarget,
+    stateless_deployment_target: &DeploymentTarget,
+    listeners_helper: &ListenersHelper,
+    error: EngineError,
+) -> EngineError {
+    listeners_helper.deployment_in_progress(ProgressInfo::new(
+        ProgressScope::Environment {
+            id: kubernetes.context().execution_id().to_string(),
+        },
+        ProgressLevel::Warn,
+        Some(format!(
+            "Environment deployment failed, rolling back {} created service(s)",
+            created_services.len()
+        )),
+        kubernetes.context().execution_id(),
+    ));
+
+    let mut rollback_errors = vec![];
+
+    for created_service in created_services.iter().rev() {
+        let result = match *created_service {
+            CreatedService::Stateful(index) => {
+                let service = &environment.stateful_services[index];
+                service::check_kubernetes_service_error(
+                    service_span(service.as_ref(), "create_error")
+                        .in_scope(|| service.on_create_error(stateful_deployment_target)),
+                    kubernetes,
+                    service,
+                    stateful_deployment_target,
+                    listeners_helper,
+                    "rollback deployment",
+                    CheckAction::Deploy,
+                )
+                .map_err(|err| (service.name_with_id(), err))
+            }
+            CreatedService::Stateless(index) => {
+                let service = &environment.stateless_services[index];
+                service::check_kubernetes_service_error(
+                    service_span(service.as_ref(), "create_error")
+                        .in_scope(|| service.on_create_error(stateless_deployment_target)),
+                    kubernetes,
+                    service,
+                    stateless_deployment_target,
+                    listeners_helper,
+                    "rollback deployment",
+                    CheckAction::Deploy,
+                )
+                .map_err(|err| (service.name_with_id(), err))
+            }
+        };
+
+        if let Err((name_with_id, err)) = result {
+            rollback_errors.push(format!("{}: {}", name_with_id, err.message.unwrap_or_default()));
+        }
+    }
+
+    with_rollback_errors(error, rollback_errors)
+}
+
+fn with_rollback_errors(error: EngineError, rollback_errors: Vec<String>) -> EngineError {
+    if rollback_errors.is_empty() {
+        return error;
+    }
+
+    EngineError {
+        message: Some(format!(
+            "{}. Rollback failed for {}",
+            error.message.clone().unwrap_or_default(),
+            rollback_errors.join(", ")
+        )),
+        ..error
+    }
+}
+
 /// common function to react to an error when a environment deployment goes wrong
 pub fn deploy_environment_error(kubernetes: &dyn Kubernetes, environment: &Environment) -> Result<(), EngineError> {
     let listeners_helper = ListenersHelper::new(kubernetes.listeners());
@@ -276,17 +962,13 @@ pub fn deploy_environment_error(kubernetes: &dyn Kubernetes, environment: &Envir
         kubernetes.context().execution_id(),
     ));
 
-    let stateful_deployment_target = match environment.kind {
-        crate::cloud_provider::environment::Kind::Production => {
-            DeploymentTarget::ManagedServices(kubernetes, environment)
-        }
-        crate::cloud_provider::environment::Kind::Development => DeploymentTarget::SelfHosted(kubernetes, environment),
-    };
+    let stateful_deployment_target = stateful_deployment_target(kubernetes, environment);
 
     // clean up all stateful services (database)
     for service in &environment.stateful_services {
         let _ = service::check_kubernetes_service_error(
-            service.on_create_error(&stateful_deployment_target),
+            service_span(service.as_ref(), "create_error")
+                .in_scope(|| service.on_create_error(&stateful_deployment_target)),
             kubernetes,
             service,
             &stateful_deployment_target,
@@ -305,7 +987,8 @@ pub fn deploy_environment_error(kubernetes: &dyn Kubernetes, environment: &Envir
     // clean up all stateless services (router, application...)
     for service in &environment.stateless_services {
         let _ = service::check_kubernetes_service_error(
-            service.on_create_error(&stateless_deployment_target),
+            service_span(service.as_ref(), "create_error")
+                .in_scope(|| service.on_create_error(&stateless_deployment_target)),
             kubernetes,
             service,
             &stateless_deployment_target,
@@ -322,20 +1005,20 @@ pub fn deploy_environment_error(kubernetes: &dyn Kubernetes, environment: &Envir
 pub fn pause_environment(kubernetes: &dyn Kubernetes, environment: &Environment) -> Result<(), EngineError> {
     let listeners_helper = ListenersHelper::new(kubernetes.listeners());
 
-    let stateful_deployment_target = match environment.kind {
-        crate::cloud_provider::environment::Kind::Production => {
-            DeploymentTarget::ManagedServices(kubernetes, environment)
-        }
-        crate::cloud_provider::environment::Kind::Development => DeploymentTarget::SelfHosted(kubernetes, environment),
-    };
+    let stateful_deployment_target = stateful_deployment_target(kubernetes, environment);
 
     // stateless services are deployed on kubernetes, that's why we choose the deployment target SelfHosted.
     let stateless_deployment_target = DeploymentTarget::SelfHosted(kubernetes, environment);
 
+    // replicas have to be known to resume the environment
+    for namespace in environment.namespaces() {
+        record_paused_replicas(kubernetes, namespace)?;
+    }
+
     // create all stateless services (router, application...)
     for service in &environment.stateless_services {
         let _ = service::check_kubernetes_service_error(
-            service.on_pause(&stateless_deployment_target),
+            service_span(service.as_ref(), "pause").in_scope(|| service.on_pause(&stateless_deployment_target)),
             kubernetes,
             service,
             &stateless_deployment_target,
@@ -351,7 +1034,7 @@ pub fn pause_environment(kubernetes: &dyn Kubernetes, environment: &Environment)
     // create all stateful services (database)
     for service in &environment.stateful_services {
         let _ = service::check_kubernetes_service_error(
-            service.on_pause(&stateful_deployment_target),
+            service_span(service.as_ref(), "pause").in_scope(|| service.on_pause(&stateful_deployment_target)),
             kubernetes,
             service,
             &stateful_deployment_target,
@@ -395,16 +1078,192 @@ pub fn pause_environment(kubernetes: &dyn Kubernetes, environment: &Environment)
     Ok(())
 }
 
+/// Namespace annotation keeping the replicas of the workloads scaled down by an environment pause.
+const PAUSED_REPLICAS_ANNOTATION: &str = "qovery.com/paused-replicas";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PausedWorkload {
+    pub kind: String,
+    pub replicas: u32,
+}
+
+/// Replicas by workload selector (`appId=...` or `databaseId=...`), a workload already scaled down by a
+/// previous pause keeps its recorded replicas.
+pub fn paused_workloads(
+    workloads: &[Workload],
+    previously_paused: &BTreeMap<String, PausedWorkload>,
+) -> BTreeMap<String, PausedWorkload> {
+    let mut paused = BTreeMap::new();
+
+    for workload in workloads {
+        let selector = match ["appId", "databaseId"].iter().find_map(|label| {
+            workload
+                .metadata
+                .labels
+                .get(*label)
+                .map(|id| format!("{}={}", label, id))
+        }) {
+            Some(selector) => selector,
+            None => continue,
+        };
+
+        let replicas = workload.spec.replicas.unwrap_or(1);
+        let paused_workload = match previously_paused.get(&selector) {
+            Some(previous) if replicas == 0 => previous.clone(),
+            _ if replicas == 0 => continue,
+            _ => PausedWorkload {
+                kind: workload.kind.clone(),
+                replicas,
+            },
+        };
+
+        paused.insert(selector, paused_workload);
+    }
+
+    paused
+}
+
+fn paused_workloads_from_namespace(namespace: &Namespace) -> Result<BTreeMap<String, PausedWorkload>, StringError> {
+    match namespace.metadata.annotations.get(PAUSED_REPLICAS_ANNOTATION) {
+        Some(annotation) => serde_json::from_str(annotation.as_str())
+            .map_err(|err| format!("annotation {} can't be parsed: {}", PAUSED_REPLICAS_ANNOTATION, err)),
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+fn record_paused_replicas(kubernetes: &dyn Kubernetes, namespace_name: &str) -> Result<(), EngineError> {
+    let kubernetes_config_file_path = kubernetes.config_file_path()?;
+    let envs = kubernetes.cloud_provider().credentials_environment_variables();
+    let to_engine_error = |message: String| {
+        kubernetes.engine_error(
+            EngineErrorCause::Internal,
+            format!(
+                "can't record the replicas of namespace {} before pausing it: {}",
+                namespace_name, message
+            ),
+        )
+    };
+
+    // nothing has been deployed yet, so nothing to resume later
+    if !kubectl::kubectl_exec_is_namespace_present(&kubernetes_config_file_path, namespace_name, envs.clone()) {
+        return Ok(());
+    }
+
+    let namespace = kubectl::kubectl_exec_get_namespace(&kubernetes_config_file_path, namespace_name, envs.clone())
+        .map_err(|err| to_engine_error(err.message.unwrap_or_default()))?;
+    let previously_paused = paused_workloads_from_namespace(&namespace).map_err(to_engine_error)?;
+    let workloads = kubectl::kubectl_exec_get_workloads(&kubernetes_config_file_path, namespace_name, envs.clone())
+        .map_err(|err| to_engine_error(err.message.unwrap_or_default()))?;
+
+    let paused = paused_workloads(&workloads.items, &previously_paused);
+    let annotation = serde_json::to_string(&paused).map_err(|err| to_engine_error(err.to_string()))?;
+
+    kubectl::kubectl_exec_annotate_namespace(
+        &kubernetes_config_file_path,
+        namespace_name,
+        PAUSED_REPLICAS_ANNOTATION,
+        Some(annotation.as_str()),
+        envs,
+    )
+    .map_err(|err| to_engine_error(err.message.unwrap_or_default()))
+}
+
+/// common kubernetes function to resume a paused environment, databases are scaled up before the applications
+/// to the replicas they had before the pause
+pub fn resume_environment(kubernetes: &dyn Kubernetes, environment: &Environment) -> Result<(), EngineError> {
+    let listeners_helper = ListenersHelper::new(kubernetes.listeners());
+    let kubernetes_config_file_path = kubernetes.config_file_path()?;
+    let envs = kubernetes.cloud_provider().credentials_environment_variables();
+
+    let mut paused = BTreeMap::new();
+    for namespace_name in environment.namespaces() {
+        let namespace = kubectl::kubectl_exec_get_namespace(&kubernetes_config_file_path, namespace_name, envs.clone())
+            .map_err(|err| {
+                kubernetes.engine_error(
+                    EngineErrorCause::Internal,
+                    format!(
+                        "can't get namespace {} to resume it: {}",
+                        namespace_name,
+                        err.message.unwrap_or_default()
+                    ),
+                )
+            })?;
+        paused.extend(
+            paused_workloads_from_namespace(&namespace)
+                .map_err(|message| kubernetes.engine_error(EngineErrorCause::Internal, message))?,
+        );
+    }
+
+    let services = environment
+        .stateful_services
+        .iter()
+        .map(|service| (service.as_ref() as &dyn Service, "databaseId"))
+        .chain(
+            environment
+                .stateless_services
+                .iter()
+                .map(|service| (service.as_ref() as &dyn Service, "appId")),
+        );
+
+    for (service, label) in services {
+        let paused_workload = match paused.get(&format!("{}={}", label, service.id())) {
+            Some(paused_workload) => paused_workload,
+            None => continue,
+        };
+
+        listeners_helper.deployment_in_progress(ProgressInfo::new(
+            service.progress_scope(),
+            ProgressLevel::Info,
+            Some(format!(
+                "Resuming {} {} with {} replicas",
+                service.service_type().name(),
+                service.name_with_id(),
+                paused_workload.replicas
+            )),
+            kubernetes.context().execution_id(),
+        ));
+
+        let scaling_kind = match paused_workload.kind.as_str() {
+            "StatefulSet" => ScalingKind::Statefulset,
+            _ => ScalingKind::Deployment,
+        };
+
+        cast_simple_error_to_engine_error(
+            service.engine_error_scope(),
+            service.context().execution_id(),
+            kubectl::kubectl_exec_scale_replicas_by_selector(
+                &kubernetes_config_file_path,
+                envs.clone(),
+                service.namespace(environment),
+                scaling_kind,
+                format!("{}={}", label, service.id()).as_str(),
+                paused_workload.replicas,
+            ),
+        )?;
+    }
+
+    for namespace in environment.namespaces() {
+        cast_simple_error_to_engine_error(
+            kubernetes.engine_error_scope(),
+            kubernetes.context().execution_id(),
+            kubectl::kubectl_exec_annotate_namespace(
+                &kubernetes_config_file_path,
+                namespace,
+                PAUSED_REPLICAS_ANNOTATION,
+                None,
+                envs.clone(),
+            ),
+        )?;
+    }
+
+    Ok(())
+}
+
 /// common kubernetes function to delete a complete environment
 pub fn delete_environment(kubernetes: &dyn Kubernetes, environment: &Environment) -> Result<(), EngineError> {
     let listeners_helper = ListenersHelper::new(kubernetes.listeners());
 
-    let stateful_deployment_target = match environment.kind {
-        crate::cloud_provider::environment::Kind::Production => {
-            DeploymentTarget::ManagedServices(kubernetes, environment)
-        }
-        crate::cloud_provider::environment::Kind::Development => DeploymentTarget::SelfHosted(kubernetes, environment),
-    };
+    let stateful_deployment_target = stateful_deployment_target(kubernetes, environment);
 
     // stateless services are deployed on kubernetes, that's why we choose the deployment target SelfHosted.
     let stateless_deployment_target = DeploymentTarget::SelfHosted(kubernetes, environment);
@@ -412,7 +1271,7 @@ pub fn delete_environment(kubernetes: &dyn Kubernetes, environment: &Environment
     // delete all stateless services (router, application...)
     for service in &environment.stateless_services {
         let _ = service::check_kubernetes_service_error(
-            service.on_delete(&stateful_deployment_target),
+            service_span(service.as_ref(), "delete").in_scope(|| service.on_delete(&stateful_deployment_target)),
             kubernetes,
             service,
             &stateless_deployment_target,
@@ -428,7 +1287,7 @@ pub fn delete_environment(kubernetes: &dyn Kubernetes, environment: &Environment
     // delete all stateful services (database)
     for service in &environment.stateful_services {
         let _ = service::check_kubernetes_service_error(
-            service.on_delete(&stateful_deployment_target),
+            service_span(service.as_ref(), "delete").in_scope(|| service.on_delete(&stateful_deployment_target)),
             kubernetes,
             service,
             &stateful_deployment_target,
@@ -470,11 +1329,18 @@ pub fn delete_environment(kubernetes: &dyn Kubernetes, environment: &Environment
     }
 
     // do not catch potential error - to confirm
-    let _ = kubectl::kubectl_exec_delete_namespace(
-        kubernetes.config_file_path()?,
-        &environment.namespace(),
-        kubernetes.cloud_provider().credentials_environment_variables(),
-    );
+    let kubernetes_config_file_path = kubernetes.config_file_path()?;
+    // namespaces shared with the cluster or other environments must survive the environment
+    let owned_namespaces = environment_owned_namespaces(environment, |namespace| {
+        namespace_labels(kubernetes, kubernetes_config_file_path.as_str(), namespace)
+    });
+    for namespace in owned_namespaces {
+        let _ = kubectl::kubectl_exec_delete_namespace(
+            kubernetes_config_file_path.as_str(),
+            namespace,
+            kubernetes.cloud_provider().credentials_environment_variables(),
+        );
+    }
 
     Ok(())
 }
@@ -674,6 +1540,107 @@ where
     check_kubernetes_upgrade_status(requested_version, deployed_masters_version, deployed_workers_version)
 }
 
+/// Stateful services are never scheduled on spot nodes, they stay pending if every node pool is made of spot nodes.
+pub fn check_stateful_services_can_be_scheduled(
+    capacity_types: &[NodeCapacityType],
+    stateful_services_count: usize,
+) -> Result<(), StringError> {
+    if stateful_services_count == 0 || capacity_types.is_empty() || capacity_types.contains(&NodeCapacityType::OnDemand)
+    {
+        return Ok(());
+    }
+
+    Err(format!(
+        "{} database(s) can't be scheduled, every node pool is made of spot nodes, add an on-demand node pool",
+        stateful_services_count
+    ))
+}
+
+/// Check if the requested Kubernetes version matches (on major and minor) one of the supported versions.
+pub fn is_kubernetes_version_supported(
+    requested_version: &str,
+    supported_versions: &[VersionsNumber],
+) -> Result<bool, StringError> {
+    let requested_version = get_version_number(requested_version)?;
+
+    if requested_version.minor.is_none() {
+        return Err(format!(
+            "Kubernetes version `{}` should be in `major.minor` format",
+            requested_version
+        ));
+    }
+
+    Ok(supported_versions
+        .iter()
+        .any(|v| v.major == requested_version.major && v.minor == requested_version.minor))
+}
+
+/// Kubernetes only supports upgrading one minor version at a time, ex: 1.18 -> 1.20 must go through 1.19.
+pub fn validate_kubernetes_upgrade_path(
+    deployed_version: &VersionsNumber,
+    requested_version: &VersionsNumber,
+) -> Result<(), StringError> {
+    let parse_minor = |version: &VersionsNumber| -> Result<u32, StringError> {
+        match &version.minor {
+            Some(minor) => minor
+                .parse::<u32>()
+                .map_err(|_| format!("Kubernetes version `{}` has an invalid minor version", version)),
+            None => Err(format!(
+                "Kubernetes version `{}` should be in `major.minor` format",
+                version
+            )),
+        }
+    };
+
+    let deployed_minor = parse_minor(deployed_version)?;
+    let requested_minor = parse_minor(requested_version)?;
+
+    if deployed_version.major != requested_version.major {
+        return Err(format!(
+            "Kubernetes major version upgrade is not supported ({}.{} -> {}.{})",
+            deployed_version.major, deployed_minor, requested_version.major, requested_minor
+        ));
+    }
+
+    if requested_minor > deployed_minor + 1 {
+        return Err(format!(
+            "Kubernetes upgrade from {major}.{} to {major}.{} would skip minor versions, upgrade to {major}.{} first",
+            deployed_minor,
+            requested_minor,
+            deployed_minor + 1,
+            major = deployed_version.major,
+        ));
+    }
+
+    Ok(())
+}
+
+/// A cluster is considered healthy when it has at least one node and all of its nodes are `Ready`.
+pub fn check_kubernetes_nodes_are_ready(
+    nodes: &KubernetesList<crate::cmd::structs::KubernetesNode>,
+) -> Result<(), StringError> {
+    if nodes.items.is_empty() {
+        return Err("no nodes found in the cluster".to_string());
+    }
+
+    let not_ready_nodes = nodes
+        .items
+        .iter()
+        .filter(|node| {
+            !node
+                .status
+                .conditions
+                .iter()
+                .any(|condition| condition.type_ == "Ready" && condition.status == "True")
+        })
+        .count();
+
+    match not_ready_nodes {
+        0 => Ok(()),
+        _ => Err(format!("{}/{} nodes are not ready", not_ready_nodes, nodes.items.len())),
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum KubernetesNodesType {
     Masters,
@@ -869,11 +1836,25 @@ pub fn compare_kubernetes_cluster_versions_for_upgrade(
 
 #[cfg(test)]
 mod tests {
+    use crate::cloud_provider::aws::router::Router;
+    use crate::cloud_provider::environment::{Environment, Kind};
     use crate::cloud_provider::kubernetes::{
-        check_kubernetes_upgrade_status, compare_kubernetes_cluster_versions_for_upgrade, KubernetesNodesType,
+        check_kubernetes_nodes_are_ready, check_kubernetes_upgrade_status, check_stateful_services_can_be_scheduled,
+        compare_kubernetes_cluster_versions_for_upgrade, create_environment_namespaces_with,
+        environment_owned_namespaces, is_kubernetes_version_supported, orphaned_helm_releases, paused_workloads,
+        validate_kubernetes_upgrade_path, with_rollback_errors, ClusterCapacity, KubernetesNodesType, NodeCapacityType,
+        PausedWorkload, NAMESPACE_OWNER_LABEL,
     };
+    use crate::cloud_provider::models::{Route, RouterProtocol};
+    use crate::cloud_provider::service::{Action, StatelessService};
     use crate::cloud_provider::utilities::{get_version_number, VersionsNumber};
-    use crate::cmd::structs::{KubernetesList, KubernetesNode, KubernetesVersion};
+    use crate::cmd::structs::{
+        Helm, KubernetesList, KubernetesNode, KubernetesScheduledPod, KubernetesVersion, Workload,
+    };
+    use crate::error::{EngineError, EngineErrorCause, EngineErrorScope, SimpleError};
+    use crate::models::Context;
+    use std::cell::RefCell;
+    use std::collections::{BTreeMap, HashMap};
 
     #[test]
     pub fn check_kubernetes_upgrade_method() {
@@ -928,6 +1909,161 @@ mod tests {
         assert_eq!(result.older_workers_version_detected, false); // not true because we're in an upgrade process
     }
 
+    #[test]
+    pub fn check_kubernetes_version_is_supported() {
+        let supported_versions = vec![get_version_number("1.17").unwrap(), get_version_number("1.18").unwrap()];
+
+        // accepted versions
+        assert_eq!(is_kubernetes_version_supported("1.17", &supported_versions), Ok(true));
+        assert_eq!(is_kubernetes_version_supported("1.18", &supported_versions), Ok(true));
+        assert_eq!(is_kubernetes_version_supported("v1.18", &supported_versions), Ok(true));
+        assert_eq!(is_kubernetes_version_supported("1.18.9", &supported_versions), Ok(true));
+
+        // rejected versions
+        assert_eq!(is_kubernetes_version_supported("1.16", &supported_versions), Ok(false));
+        assert_eq!(is_kubernetes_version_supported("1.19", &supported_versions), Ok(false));
+        assert_eq!(is_kubernetes_version_supported("1.1", &supported_versions), Ok(false));
+        assert_eq!(is_kubernetes_version_supported("2.18", &supported_versions), Ok(false));
+        assert_eq!(is_kubernetes_version_supported("1.18", &[]), Ok(false));
+        assert!(is_kubernetes_version_supported("1", &supported_versions).is_err());
+        assert!(is_kubernetes_version_supported("", &supported_versions).is_err());
+    }
+
+    #[test]
+    pub fn check_stateful_services_scheduling_on_spot_nodes() {
+        let spot_only = vec![NodeCapacityType::Spot, NodeCapacityType::Spot];
+        let mixed = vec![NodeCapacityType::Spot, NodeCapacityType::OnDemand];
+
+        // schedulable
+        assert!(check_stateful_services_can_be_scheduled(&mixed, 2).is_ok());
+        assert!(check_stateful_services_can_be_scheduled(&[NodeCapacityType::OnDemand], 1).is_ok());
+        assert!(check_stateful_services_can_be_scheduled(&spot_only, 0).is_ok());
+        assert!(check_stateful_services_can_be_scheduled(&[], 1).is_ok());
+
+        // only spot nodes
+        assert_eq!(
+            check_stateful_services_can_be_scheduled(&spot_only, 2),
+            Err(
+                "2 database(s) can't be scheduled, every node pool is made of spot nodes, add an on-demand node pool"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    pub fn check_kubernetes_upgrade_path() {
+        let version = |v: &str| get_version_number(v).unwrap();
+
+        // one minor version at a time
+        assert!(validate_kubernetes_upgrade_path(&version("1.18"), &version("1.19")).is_ok());
+        assert!(validate_kubernetes_upgrade_path(&version("1.18.9"), &version("1.19")).is_ok());
+        assert!(validate_kubernetes_upgrade_path(&version("1.19"), &version("1.19")).is_ok());
+
+        // skipping minor versions is refused
+        assert_eq!(
+            validate_kubernetes_upgrade_path(&version("1.18"), &version("1.20")),
+            Err("Kubernetes upgrade from 1.18 to 1.20 would skip minor versions, upgrade to 1.19 first".to_string())
+        );
+
+        // major and malformed versions are refused
+        assert!(validate_kubernetes_upgrade_path(&version("1.21"), &version("2.0")).is_err());
+        assert!(validate_kubernetes_upgrade_path(&version("1"), &version("1.19")).is_err());
+    }
+
+    #[test]
+    pub fn check_kubernetes_nodes_readiness() {
+        let nodes_json = |ready_statuses: &[&str]| -> String {
+            let items = ready_statuses
+                .iter()
+                .map(|status| {
+                    format!(
+                        r#"{{"status": {{
+                            "allocatable": {{"cpu": "2", "memory": "8Gi", "pods": "35"}},
+                            "capacity": {{"cpu": "2", "memory": "8Gi", "pods": "35"}},
+                            "conditions": [
+                                {{"type": "MemoryPressure", "status": "False"}},
+                                {{"type": "Ready", "status": "{}"}}
+                            ],
+                            "nodeInfo": {{"kubeProxyVersion": "v1.18.8", "kubeletVersion": "v1.18.8"}}
+                        }}}}"#,
+                        status
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+            format!(r#"{{"items": [{}]}}"#, items)
+        };
+        let nodes = |ready_statuses: &[&str]| -> KubernetesList<KubernetesNode> {
+            serde_json::from_str(nodes_json(ready_statuses).as_str()).unwrap()
+        };
+
+        assert!(check_kubernetes_nodes_are_ready(&nodes(&["True", "True"])).is_ok());
+        assert_eq!(
+            check_kubernetes_nodes_are_ready(&nodes(&["True", "False", "Unknown"])),
+            Err("2/3 nodes are not ready".to_string())
+        );
+        assert_eq!(
+            check_kubernetes_nodes_are_ready(&nodes(&[])),
+            Err("no nodes found in the cluster".to_string())
+        );
+    }
+
+    #[test]
+    fn test_cluster_capacity() {
+        // setup:
+        let node = |name: &str| {
+            format!(
+                r#"{{"metadata": {{"name": "{}"}}, "status": {{
+                    "allocatable": {{"cpu": "1930m", "memory": "7291588Ki", "pods": "35"}},
+                    "capacity": {{"cpu": "2", "memory": "8049348Ki", "pods": "35"}},
+                    "conditions": [{{"type": "Ready", "status": "True"}}],
+                    "nodeInfo": {{"kubeProxyVersion": "v1.19.13", "kubeletVersion": "v1.19.13"}}
+                }}}}"#,
+                name
+            )
+        };
+        let nodes: KubernetesList<KubernetesNode> =
+            serde_json::from_str(format!(r#"{{"items": [{}, {}]}}"#, node("node-1"), node("node-2")).as_str()).unwrap();
+        // excerpt of `kubectl get pods --all-namespaces -o json`
+        let pods: KubernetesList<KubernetesScheduledPod> = serde_json::from_str(
+            r#"{"items": [
+                {"spec": {"nodeName": "node-1", "containers": [
+                    {"resources": {"requests": {"cpu": "1", "memory": "2Gi"}}},
+                    {"resources": {"requests": {"cpu": "430m"}}}
+                ]}},
+                {"spec": {"nodeName": "node-2", "containers": [
+                    {"resources": {"requests": {"cpu": "500m", "memory": "1Gi"}}},
+                    {"resources": {}}
+                ]}},
+                {"spec": {"containers": [{"resources": {"requests": {"cpu": "1", "memory": "1Gi"}}}]}}
+            ]}"#,
+        )
+        .unwrap();
+
+        // execute:
+        let capacity = ClusterCapacity::new(&nodes, &pods);
+
+        // verify:
+        assert_eq!(capacity.nodes.len(), 2);
+        assert_eq!(capacity.nodes[0].name, "node-1");
+        assert!((capacity.nodes[0].cpu - 0.5).abs() < 0.001);
+        assert_eq!(capacity.nodes[0].ram_in_mib, 5_291);
+        assert!((capacity.nodes[1].cpu - 1.43).abs() < 0.001);
+        assert_eq!(capacity.nodes[1].ram_in_mib, 6_291);
+
+        // fits on the largest node, and twice on the cluster
+        assert!(capacity.check_can_fit(0.5, 4_000, 1).is_ok());
+        assert!(capacity.check_can_fit(0.5, 4_000, 2).is_ok());
+        // each instance fits on a node but not all of them on the cluster
+        assert!(capacity.check_can_fit(0.5, 4_000, 3).is_err());
+        // more than what is free on any single node, even if the cluster has enough in total
+        assert!(capacity.check_can_fit(1.5, 1_000, 1).is_err());
+        assert!(capacity.check_can_fit(0.5, 6_500, 1).is_err());
+        assert!(ClusterCapacity::new(&KubernetesList { items: vec![] }, &pods)
+            .check_can_fit(0.1, 1, 1)
+            .is_err());
+    }
+
     #[allow(dead_code)]
     pub fn print_kubernetes_version(provider_version: &VersionsNumber, provider: &VersionsNumber) {
         println!(
@@ -1423,4 +2559,185 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    pub fn check_rollback_errors_are_aggregated() {
+        // setup:
+        let error = || {
+            EngineError::new(
+                EngineErrorCause::Internal,
+                EngineErrorScope::Engine,
+                "execution-id",
+                Some("application app (appid) failed to deploy"),
+            )
+        };
+
+        // execute:
+        let without_rollback_errors = with_rollback_errors(error(), vec![]);
+        let with_errors = with_rollback_errors(
+            error(),
+            vec![
+                "router (routerid): timeout".to_string(),
+                "my-db (dbid): helm error".to_string(),
+            ],
+        );
+
+        // verify:
+        assert_eq!(
+            without_rollback_errors.message.unwrap(),
+            "application app (appid) failed to deploy"
+        );
+        assert_eq!(
+            with_errors.message.unwrap(),
+            "application app (appid) failed to deploy. Rollback failed for router (routerid): timeout, my-db (dbid): helm error"
+        );
+        assert_eq!(with_errors.execution_id, "execution-id");
+    }
+
+    #[test]
+    fn test_paused_workloads() {
+        // setup:
+        let workloads = serde_json::from_str::<KubernetesList<Workload>>(
+            r#"{"items": [
+                {"kind": "Deployment", "metadata": {"name": "api", "labels": {"appId": "api"}}, "spec": {"replicas": 3}},
+                {"kind": "StatefulSet", "metadata": {"name": "db", "labels": {"databaseId": "db"}}, "spec": {"replicas": 0}},
+                {"kind": "Deployment", "metadata": {"name": "worker", "labels": {"appId": "worker"}}, "spec": {"replicas": 0}},
+                {"kind": "Deployment", "metadata": {"name": "nginx", "labels": {}}, "spec": {"replicas": 2}}
+            ]}"#,
+        )
+        .unwrap();
+        let mut previously_paused = BTreeMap::new();
+        previously_paused.insert(
+            "databaseId=db".to_string(),
+            PausedWorkload {
+                kind: "StatefulSet".to_string(),
+                replicas: 2,
+            },
+        );
+        previously_paused.insert(
+            "appId=api".to_string(),
+            PausedWorkload {
+                kind: "Deployment".to_string(),
+                replicas: 1,
+            },
+        );
+
+        // execute:
+        let paused = paused_workloads(&workloads.items, &previously_paused);
+
+        // verify:
+        assert_eq!(paused.len(), 2);
+        assert_eq!(paused["appId=api"].replicas, 3);
+        assert_eq!(
+            paused["databaseId=db"],
+            PausedWorkload {
+                kind: "StatefulSet".to_string(),
+                replicas: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_orphaned_helm_releases() {
+        // setup:
+        let helm_list_output = r#"[
+            {"name":"application-api-z1a2b3c4","namespace":"z1-env","revision":"4","updated":"2021-06-01 10:00:00.000000 +0000 UTC","status":"deployed","chart":"q-application-0.1.0","app_version":"0.1"},
+            {"name":"application-old-api-z9f8e7d6","namespace":"z1-env","revision":"2","updated":"2021-05-01 10:00:00.000000 +0000 UTC","status":"deployed","chart":"q-application-0.1.0","app_version":"0.1"},
+            {"name":"router-z5c6d7e8","namespace":"z1-env","revision":"1","updated":"2021-05-01 10:00:00.000000 +0000 UTC","status":"failed","chart":"q-ingress-tls-0.1.0","app_version":"0.1"},
+            {"name":"z7a8b9c0-postgresql-externalname","namespace":"z1-env","revision":"1","updated":"2021-05-01 10:00:00.000000 +0000 UTC","status":"deployed","chart":"external-name-svc-0.1.0","app_version":"0.1"},
+            {"name":"postgresql-z7a8b9c0-externalname","namespace":"z1-env","revision":"1","updated":"2021-05-01 10:00:00.000000 +0000 UTC","status":"deployed","chart":"external-name-svc-0.1.0","app_version":"0.1"},
+            {"name":"user-monitoring","namespace":"z1-env","revision":"1","updated":"2021-05-01 10:00:00.000000 +0000 UTC","status":"deployed","chart":"prometheus-14.0.0","app_version":"2.26.0"}
+        ]"#;
+        let releases = serde_json::from_str::<Vec<Helm>>(helm_list_output).unwrap();
+        let environment_release_names = vec!["application-api-z1a2b3c4".to_string(), "mysql-z3e4f5a6".to_string()];
+
+        // execute:
+        let orphans = orphaned_helm_releases(&releases, &environment_release_names);
+
+        // verify:
+        assert_eq!(
+            orphans.iter().map(|release| release.name.as_str()).collect::<Vec<_>>(),
+            vec!["application-old-api-z9f8e7d6", "router-z5c6d7e8"]
+        );
+        assert_eq!(orphans[1].status, "failed");
+    }
+
+    #[test]
+    fn test_create_environment_namespaces() {
+        // setup:
+        let router = |name: &str, namespace: Option<&str>| -> Box<dyn StatelessService> {
+            Box::new(Router::new(
+                Context::new(
+                    "execution-id".to_string(),
+                    "/tmp".to_string(),
+                    "lib".to_string(),
+                    false,
+                    None,
+                    vec![],
+                    None,
+                ),
+                name,
+                name,
+                Action::Create,
+                "qovery.io",
+                vec![],
+                vec![Route {
+                    path: "/".to_string(),
+                    application_name: "api".to_string(),
+                    rewrite_target: None,
+                }],
+                RouterProtocol::Http,
+                443,
+                namespace.map(str::to_string),
+                vec![],
+            ))
+        };
+        let environment = Environment::new(
+            Kind::Development,
+            "env",
+            "project",
+            "owner",
+            "organization",
+            vec![
+                router("main", None),
+                router("payments", Some("payments")),
+                router("billing", Some("billing")),
+                router("payments-admin", Some("payments")),
+                router("shared", Some("shared")),
+                router("orders", Some("orders")),
+            ],
+            vec![],
+        );
+        let created_namespaces = RefCell::new(vec![]);
+        // payments namespace already exists for another environment, shared is not managed by the engine
+        let namespace_labels = |namespace: &str| -> Result<Option<HashMap<String, String>>, SimpleError> {
+            let owner = |environment_id: &str| {
+                vec![(NAMESPACE_OWNER_LABEL.to_string(), environment_id.to_string())]
+                    .into_iter()
+                    .collect()
+            };
+            Ok(match namespace {
+                "project-env" => Some(HashMap::new()),
+                "billing" => Some(owner(environment.id.as_str())),
+                "payments" => Some(owner("another-env")),
+                "shared" => Some(HashMap::new()),
+                _ => None,
+            })
+        };
+
+        // execute:
+        let result = create_environment_namespaces_with(&environment, namespace_labels, |namespace| {
+            created_namespaces.borrow_mut().push(namespace.to_string());
+            Ok(())
+        });
+        let owned_namespaces = environment_owned_namespaces(&environment, namespace_labels);
+
+        // verify:
+        assert!(result.is_ok());
+        assert_eq!(
+            created_namespaces.into_inner(),
+            vec!["project-env", "billing", "orders"]
+        );
+        assert_eq!(owned_namespaces, vec!["project-env", "billing"]);
+    }
 }
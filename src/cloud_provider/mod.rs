@@ -10,11 +10,13 @@ use crate::models::{Context, Listen};
 pub mod aws;
 pub mod digitalocean;
 pub mod environment;
+pub mod existing_cluster;
 pub mod gcp;
 pub mod helm;
 pub mod kubernetes;
 pub mod metrics;
 pub mod models;
+pub mod pricing;
 pub mod qovery;
 pub mod scaleway;
 pub mod service;
@@ -30,6 +32,8 @@ pub trait CloudProvider: Listen {
         format!("{} ({})", self.name(), self.id())
     }
     fn is_valid(&self) -> Result<(), EngineError>;
+    /// Checks the credentials are accepted by the provider API, wrong ones are a user error.
+    fn validate_credentials(&self) -> Result<(), EngineError>;
     /// environment variables containing credentials
     fn credentials_environment_variables(&self) -> Vec<(&str, &str)>;
     /// environment variables to inject to generate Terraform files from templates
@@ -67,10 +71,29 @@ impl Kind {
     }
 }
 
+/// Where Terraform stores the states of the services (ex: managed databases).
+#[derive(Clone, Debug, PartialEq)]
+pub enum TerraformBackend {
+    /// One Kubernetes secret per service, in the environment namespace.
+    KubernetesSecret,
+    /// S3 bucket reached with the tfstates credentials, optionally locked with a DynamoDB table.
+    S3 {
+        bucket: String,
+        dynamodb_table: Option<String>,
+    },
+}
+
+impl Default for TerraformBackend {
+    fn default() -> Self {
+        TerraformBackend::KubernetesSecret
+    }
+}
+
 pub struct TerraformStateCredentials {
     pub access_key_id: String,
     pub secret_access_key: String,
     pub region: String,
+    pub backend: TerraformBackend,
 }
 
 impl TerraformStateCredentials {
@@ -79,8 +102,18 @@ impl TerraformStateCredentials {
             access_key_id: access_key_id.to_string(),
             secret_access_key: secret_access_key.to_string(),
             region: region.to_string(),
+            backend: TerraformBackend::default(),
         }
     }
+
+    /// Store the services tfstates in an S3 bucket instead of Kubernetes secrets.
+    pub fn with_s3_backend(mut self, bucket: &str, dynamodb_table: Option<&str>) -> Self {
+        self.backend = TerraformBackend::S3 {
+            bucket: bucket.to_string(),
+            dynamodb_table: dynamodb_table.map(|table| table.to_string()),
+        };
+        self
+    }
 }
 
 pub enum DeploymentTarget<'a> {
@@ -89,3 +122,13 @@ pub enum DeploymentTarget<'a> {
     // SelfHosted = Kubernetes or anything else that implies management on our side
     SelfHosted(&'a dyn Kubernetes, &'a Environment),
 }
+
+impl<'a> DeploymentTarget<'a> {
+    pub fn kubernetes(&self) -> &'a dyn Kubernetes {
+        match self {
+            DeploymentTarget::ManagedServices(kubernetes, _) | DeploymentTarget::SelfHosted(kubernetes, _) => {
+                *kubernetes
+            }
+        }
+    }
+}
@@ -1,17 +1,34 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::build_platform::Image;
+
 #[derive(Serialize, Deserialize)]
 pub struct WorkerNodeDataTemplate {
     pub instance_type: String,
     pub desired_size: String,
     pub max_size: String,
     pub min_size: String,
+    /// Value of the capacity type node label, `on-demand` or `spot`.
+    pub capacity_type: String,
+    pub labels: BTreeMap<String, String>,
+    pub taints: Vec<WorkerNodeTaintDataTemplate>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WorkerNodeTaintDataTemplate {
+    pub key: String,
+    pub value: String,
+    /// EKS taint effect, ex: `NO_SCHEDULE`.
+    pub effect: String,
 }
 
 #[derive(Clone, Eq, PartialEq, Hash)]
 pub struct EnvironmentVariable {
     pub key: String,
     pub value: String,
+    pub is_secret: bool,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -40,6 +57,107 @@ pub struct StorageDataTemplate {
     pub snapshot_retention_in_days: u16,
 }
 
+/// Container run to completion before the application one starts, e.g. to migrate a database schema.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct InitContainer {
+    /// `name:tag` of an image already in the container registry, replaced by its complete URL once resolved.
+    pub image: String,
+    pub command: Vec<String>,
+    pub environment_variables: BTreeMap<String, String>,
+}
+
+impl InitContainer {
+    pub fn to_image(&self, application_id: &str) -> Image {
+        // a `:` before the last `/` is a registry port, not a tag
+        let (name, tag) = match self.image.rsplit_once(':') {
+            Some((name, tag)) if !tag.contains('/') => (name, tag),
+            _ => (self.image.as_str(), "latest"),
+        };
+
+        Image {
+            application_id: application_id.to_string(),
+            name: name.to_string(),
+            tag: tag.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct InitContainerDataTemplate {
+    pub image: String,
+    pub command: Vec<String>,
+    pub environment_variables: Vec<EnvironmentVariableDataTemplate>,
+}
+
+impl InitContainerDataTemplate {
+    pub fn new(init_container: &InitContainer) -> Self {
+        InitContainerDataTemplate {
+            image: init_container.image.clone(),
+            command: init_container.command.clone(),
+            environment_variables: init_container
+                .environment_variables
+                .iter()
+                .map(|(key, value)| EnvironmentVariableDataTemplate {
+                    key: key.clone(),
+                    value: value.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Replicas follow the CPU usage within the range, `total_instances` being the initial count.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct Autoscaling {
+    pub min_instances: u16,
+    pub max_instances: u16,
+    pub target_cpu_percent: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AutoscalingDataTemplate {
+    pub min_instances: u16,
+    pub max_instances: u16,
+    pub target_cpu_percent: u8,
+}
+
+impl AutoscalingDataTemplate {
+    pub fn new(autoscaling: &Autoscaling) -> Self {
+        AutoscalingDataTemplate {
+            min_instances: autoscaling.min_instances,
+            max_instances: autoscaling.max_instances,
+            target_cpu_percent: autoscaling.target_cpu_percent,
+        }
+    }
+}
+
+/// HTTP probes of an application, the private port is probed over TCP for the ones without a path.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Probes {
+    pub readiness_path: Option<String>,
+    pub liveness_path: Option<String>,
+    /// Port serving the paths, the private port when not set.
+    pub port: Option<u16>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ProbesDataTemplate {
+    pub readiness_path: Option<String>,
+    pub liveness_path: Option<String>,
+    pub port: Option<u16>,
+}
+
+impl ProbesDataTemplate {
+    pub fn new(probes: &Probes, private_port: Option<u16>) -> Self {
+        ProbesDataTemplate {
+            readiness_path: probes.readiness_path.clone(),
+            liveness_path: probes.liveness_path.clone(),
+            port: probes.port.or(private_port),
+        }
+    }
+}
+
 pub struct CustomDomain {
     pub domain: String,
     pub target_domain: String,
@@ -52,9 +170,41 @@ pub struct CustomDomainDataTemplate {
     pub target_domain: String,
 }
 
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum RouterProtocol {
+    Http,
+    Grpc,
+    /// The public port is forwarded as is to a single application, there is no TLS termination nor path routing.
+    Tcp,
+}
+
+impl RouterProtocol {
+    pub fn as_str(&self) -> &str {
+        match self {
+            RouterProtocol::Http => "http",
+            RouterProtocol::Grpc => "grpc",
+            RouterProtocol::Tcp => "tcp",
+        }
+    }
+}
+
 pub struct Route {
     pub path: String,
     pub application_name: String,
+    /// Path prefix the application receives instead of `path`, ex: `/api/users` becomes `/users` with `/`.
+    pub rewrite_target: Option<String>,
+}
+
+impl Route {
+    /// Sorts routes most specific first, so `/api` wins over `/` whatever the order they are declared in.
+    pub fn sort_by_specificity(routes: &mut [Route]) {
+        routes.sort_by(|a, b| b.specificity().cmp(&a.specificity()).then_with(|| a.path.cmp(&b.path)));
+    }
+
+    fn specificity(&self) -> (usize, usize) {
+        let segments = self.path.split('/').filter(|segment| !segment.is_empty()).count();
+        (segments, self.path.len())
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -62,6 +212,30 @@ pub struct RouteDataTemplate {
     pub path: String,
     pub application_name: String,
     pub application_port: u16,
+    /// nginx `rewrite-target` annotation, `path` is then a regex capturing the rest of the path.
+    pub rewrite_target: Option<String>,
+}
+
+impl RouteDataTemplate {
+    pub fn new(route: &Route, application_name: String, application_port: u16) -> Self {
+        let (path, rewrite_target) = match &route.rewrite_target {
+            None => (route.path.clone(), None),
+            Some(rewrite_target) => {
+                let rewrite_target = rewrite_target.trim_end_matches('/');
+                match route.path.trim_end_matches('/') {
+                    "" => ("/(.*)".to_string(), Some(format!("{}/$1", rewrite_target))),
+                    path => (format!("{}(/|$)(.*)", path), Some(format!("{}/$2", rewrite_target))),
+                }
+            }
+        };
+
+        RouteDataTemplate {
+            path,
+            application_name,
+            application_port,
+            rewrite_target,
+        }
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
@@ -69,3 +243,106 @@ pub struct CpuLimits {
     pub cpu_request: String,
     pub cpu_limit: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cloud_provider::models::{CustomDomainDataTemplate, Route, RouteDataTemplate, RouterProtocol};
+    use tera::Context as TeraContext;
+
+    fn route(path: &str, rewrite_target: Option<&str>) -> Route {
+        Route {
+            path: path.to_string(),
+            application_name: format!("app{}", path.replace('/', "-")),
+            rewrite_target: rewrite_target.map(|rewrite_target| rewrite_target.to_string()),
+        }
+    }
+
+    fn render_ingress(routes: &[Route], protocol: RouterProtocol) -> String {
+        let route_data_templates = routes
+            .iter()
+            .map(|route| RouteDataTemplate::new(route, route.application_name.clone(), 80))
+            .collect::<Vec<_>>();
+
+        let mut context = TeraContext::new();
+        for name in &[
+            "sanitized_name",
+            "namespace",
+            "owner_id",
+            "id",
+            "environment_id",
+            "external_ingress_hostname_default",
+            "metadata_annotations_cert_manager_cluster_issuer",
+        ] {
+            context.insert(*name, "value");
+        }
+        context.insert("router_default_domain", "main.qovery.io");
+        context.insert("custom_domains", &Vec::<CustomDomainDataTemplate>::new());
+        context.insert("routes", &route_data_templates);
+        context.insert("router_protocol", protocol.as_str());
+        context.insert("public_port", &5432);
+
+        let template =
+            std::fs::read_to_string("lib/aws/charts/q-ingress-tls/templates/ingress-qovery.j2.yaml").unwrap();
+        tera::Tera::one_off(template.as_str(), &context, false).unwrap()
+    }
+
+    #[test]
+    fn test_ingress_rules_are_sorted_by_specificity() {
+        // setup:
+        let mut routes = vec![
+            route("/", None),
+            route("/api", None),
+            route("/docs", Some("/")),
+            route("/api/v2", None),
+            route("/apis", None),
+        ];
+
+        // execute:
+        Route::sort_by_specificity(&mut routes);
+        let ingress = render_ingress(&routes, RouterProtocol::Http);
+
+        // verify:
+        assert_eq!(
+            routes.iter().map(|route| route.path.as_str()).collect::<Vec<_>>(),
+            vec!["/api/v2", "/apis", "/docs", "/api", "/"]
+        );
+        let paths = ingress
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("- path: "))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec!["\"/api/v2\"", "\"/apis\"", "\"/api\"", "\"/\"", "\"/docs(/|$)(.*)\""]
+        );
+        assert!(ingress.contains("name: value-rewrite-3"));
+        assert!(ingress.contains("nginx.ingress.kubernetes.io/rewrite-target: \"/$2\""));
+        assert_eq!(ingress.matches("kind: Ingress").count(), 2);
+    }
+
+    #[test]
+    fn test_ingress_per_router_protocol() {
+        // setup:
+        let routes = vec![route("/", None)];
+
+        // execute:
+        let http = render_ingress(&routes, RouterProtocol::Http);
+        let grpc = render_ingress(&routes, RouterProtocol::Grpc);
+        let tcp = render_ingress(&routes, RouterProtocol::Tcp);
+
+        // verify:
+        assert!(http.contains("kind: Ingress"));
+        assert!(!http.contains("backend-protocol"));
+        assert!(!http.contains("kind: Service"));
+
+        assert!(grpc.contains("kind: Ingress"));
+        assert!(grpc.contains("nginx.ingress.kubernetes.io/backend-protocol: \"GRPC\""));
+
+        assert!(!tcp.contains("kind: Ingress"));
+        assert!(tcp.contains("kind: Service"));
+        assert!(tcp.contains("type: LoadBalancer"));
+        assert!(tcp.contains("port: 5432"));
+        assert!(tcp.contains("targetPort: 80"));
+        assert!(tcp.contains("app: app-"));
+        assert!(tcp.contains("external-dns.alpha.kubernetes.io/hostname: \"main.qovery.io\""));
+    }
+}
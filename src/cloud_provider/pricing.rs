@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tera::Context as TeraContext;
+
+use crate::cloud_provider::Kind;
+
+/// Prices are rough public on-demand prices in USD, good enough to give a ballpark, not an invoice.
+const EMBEDDED_PRICING_TABLE: &str = include_str!("pricing.json");
+
+/// Used when a provider prices all its regions the same way.
+const ANY_REGION: &str = "*";
+
+const HOURS_PER_MONTH: f64 = 730.0;
+
+lazy_static! {
+    static ref PRICING_TABLE_OVERRIDE: RwLock<Option<PricingTable>> = RwLock::new(None);
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RegionPricing {
+    #[serde(default)]
+    pub instance_hourly_prices: HashMap<String, f64>,
+    #[serde(default)]
+    pub storage_monthly_price_per_gib: Option<f64>,
+}
+
+/// Prices by provider (`aws`, `do`, `scw`), then region (`*` for any region).
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct PricingTable(HashMap<String, HashMap<String, RegionPricing>>);
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct CostEstimate {
+    pub compute_monthly_usd: f64,
+    pub storage_monthly_usd: f64,
+    /// Selections missing from the pricing table, the real cost is higher than the estimate.
+    pub unpriced: Vec<String>,
+}
+
+impl CostEstimate {
+    pub fn total_monthly_usd(&self) -> f64 {
+        self.compute_monthly_usd + self.storage_monthly_usd
+    }
+
+    pub fn add(&mut self, other: CostEstimate) {
+        self.compute_monthly_usd += other.compute_monthly_usd;
+        self.storage_monthly_usd += other.storage_monthly_usd;
+        self.unpriced.extend(other.unpriced);
+    }
+}
+
+impl PricingTable {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    fn embedded() -> Self {
+        PricingTable::from_json(EMBEDDED_PRICING_TABLE).expect("embedded pricing table is invalid")
+    }
+
+    fn region_pricing(&self, provider: &Kind, region: &str) -> Option<&RegionPricing> {
+        let regions = self.0.get(provider_key(provider))?;
+        regions.get(region).or_else(|| regions.get(ANY_REGION))
+    }
+
+    pub fn instance_hourly_price(&self, provider: &Kind, region: &str, instance_type: &str) -> Option<f64> {
+        self.region_pricing(provider, region)
+            .and_then(|pricing| {
+                pricing
+                    .instance_hourly_prices
+                    .get(instance_type.to_lowercase().as_str())
+            })
+            .cloned()
+    }
+
+    pub fn storage_monthly_price_per_gib(&self, provider: &Kind, region: &str) -> Option<f64> {
+        self.region_pricing(provider, region)
+            .and_then(|pricing| pricing.storage_monthly_price_per_gib)
+    }
+
+    /// Monthly cost of running all the given instances.
+    pub fn estimate_instances(&self, provider: &Kind, region: &str, instance_types: &[&str]) -> CostEstimate {
+        let mut estimate = CostEstimate::default();
+
+        for instance_type in instance_types {
+            match self.instance_hourly_price(provider, region, instance_type) {
+                Some(hourly_price) => estimate.compute_monthly_usd += hourly_price * HOURS_PER_MONTH,
+                None => estimate
+                    .unpriced
+                    .push(format!("instance {} in {}", instance_type, region)),
+            }
+        }
+
+        estimate
+    }
+
+    /// Uses `database_instance_type` (managed services only), `database_disk_size_in_gib` and `total_instances`.
+    pub fn estimate_tera_context(
+        &self,
+        provider: &Kind,
+        region: &str,
+        context: &TeraContext,
+        is_managed_service: bool,
+    ) -> CostEstimate {
+        let total_instances = context.get("total_instances").and_then(|v| v.as_u64()).unwrap_or(1);
+        let mut estimate = CostEstimate::default();
+
+        if is_managed_service {
+            if let Some(instance_type) = context.get("database_instance_type").and_then(|v| v.as_str()) {
+                let instance_types = vec![instance_type; total_instances as usize];
+                estimate.add(self.estimate_instances(provider, region, &instance_types));
+            }
+        }
+
+        if let Some(disk_size_in_gib) = context.get("database_disk_size_in_gib").and_then(|v| v.as_u64()) {
+            match self.storage_monthly_price_per_gib(provider, region) {
+                Some(price_per_gib) => {
+                    estimate.storage_monthly_usd += (disk_size_in_gib * total_instances) as f64 * price_per_gib
+                }
+                None => estimate.unpriced.push(format!("storage in {}", region)),
+            }
+        }
+
+        estimate
+    }
+}
+
+fn provider_key(provider: &Kind) -> &'static str {
+    match provider {
+        Kind::Aws => "aws",
+        Kind::Do => "do",
+        Kind::Scw => "scw",
+    }
+}
+
+/// Prices used for estimates, the embedded table unless it has been overridden.
+pub fn pricing_table() -> PricingTable {
+    match PRICING_TABLE_OVERRIDE.read().unwrap().as_ref() {
+        Some(pricing_table) => pricing_table.clone(),
+        None => PricingTable::embedded(),
+    }
+}
+
+/// Replaces the embedded pricing table at runtime, `None` restores it.
+pub fn override_pricing_table(pricing_table: Option<PricingTable>) {
+    *PRICING_TABLE_OVERRIDE.write().unwrap() = pricing_table;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cloud_provider::pricing::{PricingTable, HOURS_PER_MONTH};
+    use crate::cloud_provider::Kind;
+    use tera::Context as TeraContext;
+
+    #[test]
+    fn test_cost_estimate_of_known_instance_types() {
+        // setup:
+        let pricing_table = PricingTable::embedded();
+        let mut rds_context = TeraContext::new();
+        rds_context.insert("database_instance_type", "db.t3.micro");
+        rds_context.insert("database_disk_size_in_gib", &10);
+        rds_context.insert("total_instances", &2);
+
+        // execute:
+        let nodes = pricing_table.estimate_instances(&Kind::Aws, "eu-west-3", &["t3a.large", "t3a.large"]);
+        let scaleway_nodes = pricing_table.estimate_instances(&Kind::Scw, "fr-par-1", &["DEV1-L", "gp1-huge"]);
+        let rds = pricing_table.estimate_tera_context(&Kind::Aws, "eu-west-3", &rds_context, true);
+        let self_hosted = pricing_table.estimate_tera_context(&Kind::Aws, "eu-west-3", &rds_context, false);
+
+        // verify:
+        assert!((nodes.compute_monthly_usd - 2.0 * 0.085 * HOURS_PER_MONTH).abs() < 0.01);
+        assert!(nodes.unpriced.is_empty());
+        assert!((scaleway_nodes.compute_monthly_usd - 0.0396 * HOURS_PER_MONTH).abs() < 0.01);
+        assert_eq!(
+            scaleway_nodes.unpriced,
+            vec!["instance gp1-huge in fr-par-1".to_string()]
+        );
+        assert!((rds.compute_monthly_usd - 2.0 * 0.021 * HOURS_PER_MONTH).abs() < 0.01);
+        assert!((rds.storage_monthly_usd - 20.0 * 0.116).abs() < 0.01);
+        assert!((rds.total_monthly_usd() - (rds.compute_monthly_usd + rds.storage_monthly_usd)).abs() < 0.01);
+        assert_eq!(self_hosted.compute_monthly_usd, 0.0);
+        assert_eq!(self_hosted.storage_monthly_usd, rds.storage_monthly_usd);
+    }
+
+    #[test]
+    fn test_pricing_table_from_json() {
+        // setup:
+        let json = r#"{"aws": {"*": {"instance_hourly_prices": {"t3a.large": 1.0}}}}"#;
+
+        // execute:
+        let pricing_table = PricingTable::from_json(json).unwrap();
+
+        // verify:
+        assert_eq!(
+            pricing_table.instance_hourly_price(&Kind::Aws, "eu-west-3", "t3a.large"),
+            Some(1.0)
+        );
+        assert_eq!(
+            pricing_table.storage_monthly_price_per_gib(&Kind::Aws, "eu-west-3"),
+            None
+        );
+        assert_eq!(
+            pricing_table.instance_hourly_price(&Kind::Do, "nyc3", "s-2vcpu-4gb"),
+            None
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::fmt;
 use std::str::FromStr;
 
@@ -5,20 +6,22 @@ use tera::Context as TeraContext;
 
 use crate::build_platform::Image;
 use crate::cloud_provider::models::{
-    EnvironmentVariable, EnvironmentVariableDataTemplate, Storage, StorageDataTemplate,
+    Autoscaling, AutoscalingDataTemplate, EnvironmentVariable, EnvironmentVariableDataTemplate, InitContainer,
+    InitContainerDataTemplate, Probes, ProbesDataTemplate, Storage, StorageDataTemplate,
 };
 use crate::cloud_provider::service::{
     default_tera_context, delete_stateless_service, deploy_stateless_service_error, deploy_user_stateless_service,
-    scale_down_application, send_progress_on_long_task, Action, Application as CApplication, Create, Delete, Helm,
-    Pause, Service, ServiceType, StatelessService,
+    insert_registry_secret_into_tera_context, insert_resource_expiration_into_tera_context, pause_application,
+    resolve_secret, resume_application, send_progress_on_long_task, Action, Application as CApplication, Create,
+    Delete, Helm, Pause, Service, ServiceType, StatelessService,
 };
 use crate::cloud_provider::utilities::{sanitize_name, validate_k8s_required_cpu_and_burstable};
 use crate::cloud_provider::DeploymentTarget;
 use crate::cmd::helm::Timeout;
-use crate::cmd::kubectl::ScalingKind::{Deployment, Statefulset};
-use crate::error::EngineErrorCause::Internal;
+use crate::cmd::kubectl::ScalingKind;
 use crate::error::{EngineError, EngineErrorScope};
-use crate::models::{Context, Listen, Listener, Listeners, ListenersHelper};
+use crate::models::{Context, Listen, Listener, Listeners};
+use crate::secrets::is_secret_reference;
 
 pub struct Application {
     context: Context,
@@ -34,6 +37,11 @@ pub struct Application {
     image: Image,
     storage: Vec<Storage<StorageType>>,
     environment_variables: Vec<EnvironmentVariable>,
+    helm_value_overrides: BTreeMap<String, String>,
+    init_containers: Vec<InitContainer>,
+    autoscaling: Option<Autoscaling>,
+    probes: Option<Probes>,
+    namespace: Option<String>,
     listeners: Listeners,
 }
 
@@ -52,6 +60,11 @@ impl Application {
         image: Image,
         storage: Vec<Storage<StorageType>>,
         environment_variables: Vec<EnvironmentVariable>,
+        helm_value_overrides: BTreeMap<String, String>,
+        init_containers: Vec<InitContainer>,
+        autoscaling: Option<Autoscaling>,
+        probes: Option<Probes>,
+        namespace: Option<String>,
         listeners: Listeners,
     ) -> Application {
         Application {
@@ -68,6 +81,11 @@ impl Application {
             image,
             storage,
             environment_variables,
+            helm_value_overrides,
+            init_containers,
+            autoscaling,
+            probes,
+            namespace,
             listeners,
         }
     }
@@ -75,6 +93,14 @@ impl Application {
     fn is_stateful(&self) -> bool {
         !self.storage.is_empty()
     }
+
+    fn scaling_kind(&self) -> ScalingKind {
+        if self.is_stateful() {
+            ScalingKind::Statefulset
+        } else {
+            ScalingKind::Deployment
+        }
+    }
 }
 
 impl crate::cloud_provider::service::Application for Application {
@@ -85,6 +111,24 @@ impl crate::cloud_provider::service::Application for Application {
     fn set_image(&mut self, image: Image) {
         self.image = image;
     }
+
+    fn init_containers(&self) -> &[InitContainer] {
+        &self.init_containers
+    }
+
+    fn set_init_containers(&mut self, init_containers: Vec<InitContainer>) {
+        self.init_containers = init_containers;
+    }
+
+    fn on_resume(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        // an autoscaled application restarts from its minimum, the autoscaler adjusts it from there
+        let default_instances = match &self.autoscaling {
+            Some(autoscaling) => autoscaling.min_instances,
+            None => self.total_instances,
+        };
+
+        resume_application(target, self, self.scaling_kind(), default_instances as u32)
+    }
 }
 
 impl Helm for Application {
@@ -103,6 +147,10 @@ impl Helm for Application {
     fn helm_chart_external_name_service_dir(&self) -> String {
         String::new()
     }
+
+    fn helm_value_overrides(&self) -> BTreeMap<String, String> {
+        self.helm_value_overrides.clone()
+    }
 }
 
 impl StatelessService for Application {}
@@ -128,6 +176,10 @@ impl Service for Application {
         sanitize_name("app", self.name())
     }
 
+    fn custom_namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
     fn version(&self) -> &str {
         self.image.commit_id.as_str()
     }
@@ -171,59 +223,58 @@ impl Service for Application {
 
         context.insert("helm_app_version", &commit_id[..7]);
 
-        match &self.image().registry_url {
-            Some(registry_url) => context.insert(
-                "image_name_with_tag",
-                format!("{}/{}", registry_url.as_str(), self.image().name_with_tag()).as_str(),
-            ),
+        let image_name_with_tag = match &self.image().registry_url {
+            Some(registry_url) => format!("{}/{}", registry_url.as_str(), self.image().name_with_tag()),
             None => {
                 let image_name_with_tag = self.image().name_with_tag();
                 warn!(
                     "there is no registry url, use image name with tag with the default container registry: {}",
                     image_name_with_tag.as_str()
                 );
-                context.insert("image_name_with_tag", image_name_with_tag.as_str());
+                image_name_with_tag
             }
-        }
+        };
+        context.insert(
+            "image_name_with_tag",
+            self.context.rewrite_image(image_name_with_tag.as_str()).as_str(),
+        );
 
-        let environment_variables = self
+        // values resolved from a secrets manager are secrets, whatever the variable says
+        let (secret_environment_variables, environment_variables): (Vec<_>, Vec<_>) = self
             .environment_variables
             .iter()
-            .map(|ev| EnvironmentVariableDataTemplate {
-                key: ev.key.clone(),
-                value: ev.value.clone(),
-            })
-            .collect::<Vec<_>>();
+            .partition(|ev| ev.is_secret || is_secret_reference(ev.value.as_str()));
+        let to_data_template = |evs: Vec<&EnvironmentVariable>| {
+            evs.into_iter()
+                .map(|ev| {
+                    Ok(EnvironmentVariableDataTemplate {
+                        key: ev.key.clone(),
+                        value: resolve_secret(self, ev.value.as_str())?,
+                    })
+                })
+                .collect::<Result<Vec<_>, EngineError>>()
+        };
 
-        context.insert("environment_variables", &environment_variables);
+        context.insert("environment_variables", &to_data_template(environment_variables)?);
+        context.insert(
+            "secret_environment_variables",
+            &to_data_template(secret_environment_variables)?,
+        );
 
-        match self.image.registry_name.as_ref() {
-            Some(_) => {
-                context.insert("is_registry_secret", &true);
-                context.insert("registry_secret_name", &format!("registry-token-{}", &self.id));
-            }
-            None => {
-                context.insert("is_registry_secret", &false);
-            }
-        };
+        insert_registry_secret_into_tera_context(self.id(), &self.image, &mut context);
 
-        let cpu_limits = match validate_k8s_required_cpu_and_burstable(
-            &ListenersHelper::new(&self.listeners),
-            &self.context.execution_id(),
-            &self.id,
-            self.total_cpus(),
-            self.cpu_burst(),
-        ) {
+        let cpu_limits = match validate_k8s_required_cpu_and_burstable(self.total_cpus(), self.cpu_burst()) {
             Ok(l) => l,
             Err(e) => {
                 return Err(EngineError::new(
-                    Internal,
+                    e.cause(),
                     EngineErrorScope::Application(self.id().to_string(), self.name().to_string()),
                     self.context.execution_id(),
                     Some(e.to_string()),
                 ));
             }
         };
+        context.insert("total_cpus", &cpu_limits.cpu_request);
         context.insert("cpu_burst", &cpu_limits.cpu_limit);
 
         let storage = self
@@ -252,23 +303,27 @@ impl Service for Application {
         context.insert("is_storage", &is_storage);
         context.insert("clone", &false);
         context.insert("start_timeout_in_seconds", &self.start_timeout_in_seconds);
-
-        if self.context.resource_expiration_in_seconds().is_some() {
-            context.insert(
-                "resource_expiration_in_seconds",
-                &self.context.resource_expiration_in_seconds(),
-            )
-        }
-
-        // container registry credentials
         context.insert(
-            "container_registry_docker_json_config",
-            self.image
-                .clone()
-                .registry_docker_json_config
-                .unwrap_or("".to_string())
-                .as_str(),
+            "init_containers",
+            &self
+                .init_containers
+                .iter()
+                .map(InitContainerDataTemplate::new)
+                .collect::<Vec<_>>(),
         );
+        context.insert(
+            "autoscaling",
+            &self.autoscaling.as_ref().map(AutoscalingDataTemplate::new),
+        );
+        context.insert(
+            "probes",
+            &self
+                .probes
+                .as_ref()
+                .map(|probes| ProbesDataTemplate::new(probes, self.private_port)),
+        );
+
+        insert_resource_expiration_into_tera_context(&self.context, &mut context);
 
         Ok(context)
     }
@@ -309,12 +364,7 @@ impl Pause for Application {
         info!("SCW.application.on_pause() called for {}", self.name());
 
         send_progress_on_long_task(self, crate::cloud_provider::service::Action::Pause, || {
-            scale_down_application(
-                target,
-                self,
-                0,
-                if self.is_stateful() { Statefulset } else { Deployment },
-            )
+            pause_application(target, self, self.scaling_kind())
         })
     }
 
@@ -2,3 +2,4 @@ pub mod mongodb;
 pub mod mysql;
 pub mod postgresql;
 pub mod redis;
+pub mod utilities;
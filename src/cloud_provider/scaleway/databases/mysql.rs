@@ -1,8 +1,18 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
 use tera::Context as TeraContext;
 
+use crate::cloud_provider::environment::Kind;
+use crate::cloud_provider::scaleway::application::Zone;
+use crate::cloud_provider::scaleway::databases::utilities::{get_scaleway_rdb_version, RdbNodeType};
 use crate::cloud_provider::service::{
-    check_service_version, default_tera_context, delete_stateful_service, deploy_stateful_service, get_tfstate_name,
-    get_tfstate_suffix, scale_down_database, send_progress_on_long_task, Action, Backup, Create, Database,
+    check_service_version, clone_self_hosted_database, default_tera_context, delete_stateful_service,
+    deploy_stateful_service, get_self_hosted_database_disk_size, get_self_hosted_database_version, get_tfstate_name,
+    get_tfstate_suffix, insert_database_node_pool_into_tera_context, insert_database_resources_into_tera_context,
+    insert_database_tls_into_tera_context, insert_resource_expiration_into_tera_context,
+    insert_terraform_backend_into_tera_context, resolve_secret, scale_down_database, send_progress_on_long_task,
+    stateful_service_template_directories, unsupported_database_clone_error, Action, Backup, Create, Database,
     DatabaseOptions, DatabaseType, Delete, Downgrade, Helm, Pause, Service, ServiceType, StatefulService, Terraform,
     Upgrade,
 };
@@ -10,8 +20,8 @@ use crate::cloud_provider::utilities::{get_self_hosted_mysql_version, sanitize_n
 use crate::cloud_provider::DeploymentTarget;
 use crate::cmd::helm::Timeout;
 use crate::cmd::kubectl;
-use crate::error::{EngineError, EngineErrorScope};
-use crate::models::{Context, Listen, Listener, Listeners};
+use crate::error::{EngineError, EngineErrorCause, EngineErrorScope, StringError};
+use crate::models::{Context, DatabaseKind, Listen, Listener, Listeners};
 
 pub struct MySQL {
     context: Context,
@@ -59,12 +69,27 @@ impl MySQL {
         }
     }
 
-    fn matching_correct_version(&self) -> Result<String, EngineError> {
-        check_service_version(get_self_hosted_mysql_version(self.version()), self)
+    fn connection_string(&self) -> String {
+        self.options.mysql_connection_string(self.sanitized_name().as_str())
+    }
+
+    fn matching_correct_version(&self, is_managed_services: bool) -> Result<String, EngineError> {
+        check_service_version(self.supported_version(is_managed_services), self)
     }
 }
 
-impl StatefulService for MySQL {}
+impl StatefulService for MySQL {
+    fn is_deletion_protected(&self) -> bool {
+        self.options.deletion_protection
+    }
+
+    fn supported_version(&self, is_managed_services: bool) -> Result<String, StringError> {
+        match is_managed_services {
+            true => get_scaleway_rdb_version(DatabaseKind::Mysql, self.version()),
+            false => get_self_hosted_mysql_version(self.version()),
+        }
+    }
+}
 
 impl Service for MySQL {
     fn context(&self) -> &Context {
@@ -91,6 +116,22 @@ impl Service for MySQL {
         self.version.as_str()
     }
 
+    fn deployed_version(&self, target: &DeploymentTarget) -> Result<Option<String>, EngineError> {
+        match target {
+            // RDB instances are not exposed by the Scaleway API client yet
+            DeploymentTarget::ManagedServices(..) => Ok(None),
+            DeploymentTarget::SelfHosted(..) => get_self_hosted_database_version(target, self),
+        }
+    }
+
+    fn deployed_disk_size_in_gib(&self, target: &DeploymentTarget) -> Result<Option<u32>, EngineError> {
+        match target {
+            // RDB instances are not exposed by the Scaleway API client yet
+            DeploymentTarget::ManagedServices(..) => Ok(None),
+            DeploymentTarget::SelfHosted(..) => get_self_hosted_database_disk_size(target, self),
+        }
+    }
+
     fn action(&self) -> &Action {
         &self.action
     }
@@ -99,8 +140,20 @@ impl Service for MySQL {
         Some(self.options.port)
     }
 
+    fn endpoints(&self) -> Vec<String> {
+        vec![format!("{}:{}", self.fqdn, self.options.port)]
+    }
+
     fn start_timeout(&self) -> Timeout<u32> {
-        Timeout::Default
+        Timeout::Value(self.readiness_timeout().as_secs() as u32)
+    }
+
+    fn deploy_timeout(&self) -> Option<std::time::Duration> {
+        self.options.deploy_timeout()
+    }
+
+    fn custom_namespace(&self) -> Option<&str> {
+        self.options.namespace.as_deref()
     }
 
     fn total_cpus(&self) -> String {
@@ -116,7 +169,11 @@ impl Service for MySQL {
     }
 
     fn total_instances(&self) -> u16 {
-        1
+        self.options.total_instances()
+    }
+
+    fn template_directories(&self, target: &DeploymentTarget) -> Vec<String> {
+        stateful_service_template_directories(self, target)
     }
 
     fn tera_context(&self, target: &DeploymentTarget) -> Result<TeraContext, EngineError> {
@@ -125,21 +182,72 @@ impl Service for MySQL {
             DeploymentTarget::SelfHosted(k, env) => (*k, *env),
         };
 
+        if let Err(e) = self.options.validate_replicas() {
+            return Err(self.engine_error(EngineErrorCause::User("Reduce the number of database replicas"), e));
+        }
+
+        if let Err(e) = self.options.validate_parameters() {
+            return Err(self.engine_error(EngineErrorCause::User("Remove database parameters without a name"), e));
+        }
+
         let mut context = default_tera_context(self, kubernetes, environment);
 
+        let is_managed_services = match environment.kind {
+            Kind::Production => true,
+            Kind::Development => false,
+        };
+
+        if let Err(e) = self.options.validate_tls(is_managed_services) {
+            return Err(self.engine_error(EngineErrorCause::User("Check the database TLS settings"), e));
+        }
+
+        if is_managed_services && !self.options.parameters.is_empty() {
+            return Err(self.engine_error(
+                EngineErrorCause::User("Remove the database parameters, Scaleway managed MySQL doesn't support them"),
+                format!(
+                    "{} database parameters requested on Scaleway RDB",
+                    self.options.parameters.len()
+                ),
+            ));
+        }
+
         // we need the kubernetes config file to store tfstates file in kube secrets
         let kube_config_file_path = kubernetes.config_file_path()?;
         context.insert("kubeconfig_path", &kube_config_file_path);
 
         kubectl::kubectl_exec_create_namespace_without_labels(
-            &environment.namespace(),
+            self.namespace(environment),
             kube_config_file_path.as_str(),
             kubernetes.cloud_provider().credentials_environment_variables(),
         );
 
-        context.insert("namespace", environment.namespace());
-
-        let version = &self.matching_correct_version()?;
+        context.insert("namespace", self.namespace(environment));
+
+        let version = &self.matching_correct_version(is_managed_services)?;
+
+        if is_managed_services {
+            if RdbNodeType::from_str(self.database_instance_type.as_str()).is_err() {
+                return Err(self.engine_error(
+                    EngineErrorCause::User("Use a Scaleway managed database node type, e.g. `db-dev-s`"),
+                    format!(
+                        "`{}` is not a Scaleway managed database node type",
+                        self.database_instance_type
+                    ),
+                ));
+            }
+
+            // managed databases are regional resources, kubernetes region is its zone
+            let zone = kubernetes.region();
+            match Zone::from_str(zone) {
+                Ok(zone) => context.insert("database_region", zone.region().as_str()),
+                Err(_) => {
+                    return Err(
+                        self.engine_error(EngineErrorCause::Internal, format!("unknown Scaleway zone `{}`", zone))
+                    )
+                }
+            };
+            context.insert("database_zone", zone);
+        }
         context.insert("version", &version);
 
         for (k, v) in kubernetes.cloud_provider().tera_context_environment_variables() {
@@ -153,25 +261,53 @@ impl Service for MySQL {
         context.insert("fqdn", self.fqdn.as_str());
 
         context.insert("database_login", self.options.login.as_str());
-        context.insert("database_password", self.options.password.as_str());
+        context.insert("database_connection_string", &self.connection_string());
+        context.insert(
+            "database_password",
+            resolve_secret(self, self.options.password.as_str())?.as_str(),
+        );
         context.insert("database_port", &self.private_port());
         context.insert("database_disk_size_in_gib", &self.options.disk_size_in_gib);
         context.insert("database_instance_type", &self.database_instance_type);
         context.insert("database_disk_type", &self.options.database_disk_type);
+        context.insert("database_replicas", &self.options.replicas);
+        context.insert("database_parameters", &self.options.parameters);
+        insert_database_tls_into_tera_context(&self.options, &mut context);
         context.insert("database_ram_size_in_mib", &self.total_ram_in_mib);
         context.insert("database_total_cpus", &self.total_cpus);
+
+        match self.options.resources(self.total_cpus.as_str(), self.total_ram_in_mib) {
+            Ok(resources) => insert_database_resources_into_tera_context(&resources, &mut context),
+            Err(e) => {
+                return Err(self.engine_error(
+                    EngineErrorCause::User("Check the resources overcommit of the database"),
+                    e,
+                ))
+            }
+        }
+        if let Err(e) = insert_database_node_pool_into_tera_context(
+            &self.options,
+            &kubernetes.node_pools_scheduling(),
+            &mut context,
+        ) {
+            return Err(self.engine_error(
+                EngineErrorCause::User("Check the node pool label the database is pinned to"),
+                e,
+            ));
+        }
         context.insert("database_fqdn", &self.options.host.as_str());
         context.insert("database_id", &self.id());
         context.insert("tfstate_suffix_name", &get_tfstate_suffix(self));
         context.insert("tfstate_name", &get_tfstate_name(self));
+        insert_terraform_backend_into_tera_context(
+            kubernetes.cloud_provider().terraform_state_credentials(),
+            self.namespace(environment),
+            &get_tfstate_name(self),
+            &mut context,
+        );
 
         context.insert("delete_automated_backups", &self.context().is_test_cluster());
-        if self.context.resource_expiration_in_seconds().is_some() {
-            context.insert(
-                "resource_expiration_in_seconds",
-                &self.context.resource_expiration_in_seconds(),
-            )
-        }
+        insert_resource_expiration_into_tera_context(&self.context, &mut context);
 
         Ok(context)
     }
@@ -207,6 +343,10 @@ impl Helm for MySQL {
     fn helm_chart_external_name_service_dir(&self) -> String {
         format!("{}/common/charts/external-name-svc", self.context.lib_root_dir())
     }
+
+    fn helm_value_overrides(&self) -> BTreeMap<String, String> {
+        self.options.helm_value_overrides.clone()
+    }
 }
 
 impl Terraform for MySQL {
@@ -284,16 +424,27 @@ impl Delete for MySQL {
 }
 
 impl crate::cloud_provider::service::Clone for MySQL {
-    fn on_clone(&self, _target: &DeploymentTarget) -> Result<(), EngineError> {
-        unimplemented!()
+    fn on_clone(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        info!("SCW.MySQL.on_clone() called for {}", self.name());
+
+        match target {
+            DeploymentTarget::SelfHosted(..) => {
+                send_progress_on_long_task(self, crate::cloud_provider::service::Action::Create, || {
+                    clone_self_hosted_database(target, self)
+                })
+            }
+            DeploymentTarget::ManagedServices(..) => Err(unsupported_database_clone_error(self)),
+        }
     }
 
     fn on_clone_check(&self) -> Result<(), EngineError> {
-        unimplemented!()
+        self.on_create_check()
     }
 
     fn on_clone_error(&self, _target: &DeploymentTarget) -> Result<(), EngineError> {
-        unimplemented!()
+        warn!("SCW.MySQL.on_clone_error() called for {}", self.name());
+
+        Ok(())
     }
 }
 
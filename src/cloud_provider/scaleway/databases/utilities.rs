@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cloud_provider::utilities::get_supported_version_to_use;
+use crate::cloud_provider::DeploymentTarget;
+use crate::error::StringError;
+use crate::models::DatabaseKind;
+
+/// Scaleway managed databases (RDB) node types.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RdbNodeType {
+    DbDevS,  // 2 cores 2 Go RAM
+    DbDevM,  // 3 cores 4 Go RAM
+    DbDevL,  // 4 cores 8 Go RAM
+    DbDevXl, // 4 cores 12 Go RAM
+    DbGpXs,  // 4 cores 16 Go RAM
+    DbGpS,   // 8 cores 32 Go RAM
+    DbGpM,   // 16 cores 64 Go RAM
+    DbGpL,   // 32 cores 128 Go RAM
+    DbGpXl,  // 48 cores 256 Go RAM
+}
+
+impl RdbNodeType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            RdbNodeType::DbDevS => "db-dev-s",
+            RdbNodeType::DbDevM => "db-dev-m",
+            RdbNodeType::DbDevL => "db-dev-l",
+            RdbNodeType::DbDevXl => "db-dev-xl",
+            RdbNodeType::DbGpXs => "db-gp-xs",
+            RdbNodeType::DbGpS => "db-gp-s",
+            RdbNodeType::DbGpM => "db-gp-m",
+            RdbNodeType::DbGpL => "db-gp-l",
+            RdbNodeType::DbGpXl => "db-gp-xl",
+        }
+    }
+}
+
+impl fmt::Display for RdbNodeType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for RdbNodeType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<RdbNodeType, ()> {
+        match s {
+            "db-dev-s" => Ok(RdbNodeType::DbDevS),
+            "db-dev-m" => Ok(RdbNodeType::DbDevM),
+            "db-dev-l" => Ok(RdbNodeType::DbDevL),
+            "db-dev-xl" => Ok(RdbNodeType::DbDevXl),
+            "db-gp-xs" => Ok(RdbNodeType::DbGpXs),
+            "db-gp-s" => Ok(RdbNodeType::DbGpS),
+            "db-gp-m" => Ok(RdbNodeType::DbGpM),
+            "db-gp-l" => Ok(RdbNodeType::DbGpL),
+            "db-gp-xl" => Ok(RdbNodeType::DbGpXl),
+            _ => Err(()),
+        }
+    }
+}
+
+/// RDB engines are only versioned by their major version (e.g. `MySQL-8`, `PostgreSQL-12`).
+pub fn get_scaleway_rdb_version(database_kind: DatabaseKind, requested_version: &str) -> Result<String, StringError> {
+    // https://developers.scaleway.com/en/products/rdb/api/#get-1eafb7
+    let (database_name, supported_majors) = match database_kind {
+        DatabaseKind::Mysql => ("RDB MySQL", vec!["8"]),
+        DatabaseKind::Postgresql => ("RDB PostgreSQL", vec!["10", "11", "12", "13"]),
+        _ => return Err(format!("{:?} is not supported by Scaleway RDB", database_kind)),
+    };
+
+    let mut supported_versions = HashMap::new();
+    for major in supported_majors {
+        supported_versions.insert(major.to_string(), major.to_string());
+        supported_versions.insert(format!("{}.0", major), major.to_string());
+    }
+
+    get_supported_version_to_use(database_name, supported_versions, requested_version)
+}
+
+/// Scaleway has no managed offer for some databases, those are always deployed on the cluster.
+pub fn self_hosted_deployment_target<'a>(target: &DeploymentTarget<'a>) -> DeploymentTarget<'a> {
+    match target {
+        DeploymentTarget::ManagedServices(kubernetes, environment) => {
+            DeploymentTarget::SelfHosted(*kubernetes, *environment)
+        }
+        DeploymentTarget::SelfHosted(kubernetes, environment) => {
+            DeploymentTarget::SelfHosted(*kubernetes, *environment)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_scaleway_databases {
+    use crate::cloud_provider::scaleway::databases::utilities::{get_scaleway_rdb_version, RdbNodeType};
+    use crate::models::DatabaseKind;
+    use std::str::FromStr;
+
+    #[test]
+    fn check_rdb_versions() {
+        assert_eq!(get_scaleway_rdb_version(DatabaseKind::Mysql, "8").unwrap(), "8");
+        assert_eq!(get_scaleway_rdb_version(DatabaseKind::Mysql, "8.0").unwrap(), "8");
+        assert_eq!(
+            get_scaleway_rdb_version(DatabaseKind::Mysql, "5.7").unwrap_err(),
+            "RDB MySQL 5.7 version is not supported"
+        );
+        assert_eq!(get_scaleway_rdb_version(DatabaseKind::Postgresql, "12").unwrap(), "12");
+        assert_eq!(
+            get_scaleway_rdb_version(DatabaseKind::Postgresql, "13.0").unwrap(),
+            "13"
+        );
+        assert_eq!(
+            get_scaleway_rdb_version(DatabaseKind::Postgresql, "9").unwrap_err(),
+            "RDB PostgreSQL 9 version is not supported"
+        );
+        assert!(get_scaleway_rdb_version(DatabaseKind::Redis, "6").is_err());
+    }
+
+    #[test]
+    fn check_rdb_node_types() {
+        assert_eq!(RdbNodeType::from_str("db-dev-s"), Ok(RdbNodeType::DbDevS));
+        assert_eq!(RdbNodeType::from_str("db-gp-xl").unwrap().as_str(), "db-gp-xl");
+        assert!(RdbNodeType::from_str("db.t2.micro").is_err());
+    }
+}
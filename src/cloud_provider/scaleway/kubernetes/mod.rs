@@ -4,17 +4,18 @@ pub mod node;
 use crate::cloud_provider::environment::Environment;
 use crate::cloud_provider::helm::deploy_charts_levels;
 use crate::cloud_provider::kubernetes::{
-    is_kubernetes_upgrade_required, uninstall_cert_manager, Kind, Kubernetes, KubernetesNode, KubernetesUpgradeStatus,
+    is_kubernetes_upgrade_required, uninstall_cert_manager, Kind, Kubernetes, KubernetesNodesType,
+    KubernetesUpgradeStatus, NodeCapacityType, NodePoolScheduling,
 };
-use crate::cloud_provider::models::WorkerNodeDataTemplate;
 use crate::cloud_provider::scaleway::application::Zone;
 use crate::cloud_provider::scaleway::kubernetes::helm_charts::{scw_helm_charts, ChartsConfigPrerequisites};
-use crate::cloud_provider::scaleway::kubernetes::node::Node;
+use crate::cloud_provider::scaleway::kubernetes::node::{validate_node_pools, validate_node_types, NodePool};
 use crate::cloud_provider::scaleway::Scaleway;
+use crate::cloud_provider::service::insert_resource_expiration_into_tera_context;
+use crate::cloud_provider::utilities::{get_version_number, VersionsNumber};
 use crate::cloud_provider::{kubernetes, CloudProvider};
 use crate::cmd::kubectl::kubectl_exec_get_all_namespaces;
 use crate::cmd::structs::HelmChart;
-use crate::cmd::terraform::terraform_init_validate_plan_apply;
 use crate::deletion_utilities::{get_firsts_namespaces_to_delete, get_qovery_managed_namespaces};
 use crate::dns_provider::DnsProvider;
 use crate::error::EngineErrorCause::Internal;
@@ -24,18 +25,23 @@ use crate::models::{
     Context, Features, Listen, Listener, Listeners, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope,
 };
 use crate::object_storage::scaleway_object_storage::{BucketDeleteStrategy, ScalewayOS};
-use crate::object_storage::ObjectStorage;
+use crate::object_storage::{BucketPurpose, ObjectStorage};
+use crate::runtime::block_on;
 use crate::string::terraform_list_format;
 use crate::{cmd, dns_provider};
 use itertools::Itertools;
 use retry::delay::Fibonacci;
 use retry::Error::Operation;
 use retry::OperationResult;
+use scaleway_api_rs::models::scaleway_k8s_v1_pool::Status as ScalewayPoolStatus;
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::path::PathBuf;
 use tera::Context as TeraContext;
 
+// Kubernetes versions (major.minor) which can be deployed on Kapsule
+const KAPSULE_SUPPORTED_KUBERNETES_VERSIONS: [&str; 4] = ["1.18", "1.19", "1.20", "1.21"];
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct KapsuleOptions {
     // Qovery
@@ -92,6 +98,17 @@ impl KapsuleOptions {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+struct NodePoolDataTemplate {
+    name: String,
+    is_default: bool,
+    instance_type: String,
+    min_size: String,
+    max_size: String,
+    autoscaling: bool,
+    tags: Vec<String>,
+}
+
 pub struct Kapsule<'a> {
     context: Context,
     id: String,
@@ -101,7 +118,7 @@ pub struct Kapsule<'a> {
     cloud_provider: &'a Scaleway,
     dns_provider: &'a dyn DnsProvider,
     object_storage: ScalewayOS,
-    nodes: Vec<Node>,
+    node_pools: Vec<NodePool>,
     template_directory: String,
     options: KapsuleOptions,
     listeners: Listeners,
@@ -116,7 +133,7 @@ impl<'a> Kapsule<'a> {
         region: Zone,
         cloud_provider: &'a Scaleway,
         dns_provider: &'a dyn DnsProvider,
-        nodes: Vec<Node>,
+        node_pools: Vec<NodePool>,
         options: KapsuleOptions,
     ) -> Kapsule<'a> {
         let template_directory = format!("{}/scaleway/bootstrap", context.lib_root_dir());
@@ -130,7 +147,8 @@ impl<'a> Kapsule<'a> {
             region,
             BucketDeleteStrategy::Empty,
             false,
-        );
+        )
+        .with_credentials_check_bucket(format!("qovery-kubeconfigs-{}", id));
 
         Kapsule {
             context,
@@ -141,7 +159,7 @@ impl<'a> Kapsule<'a> {
             cloud_provider,
             dns_provider,
             object_storage,
-            nodes,
+            node_pools,
             template_directory,
             options,
             listeners: cloud_provider.listeners.clone(), // copy listeners from CloudProvider
@@ -156,8 +174,265 @@ impl<'a> Kapsule<'a> {
         format!("qovery-logs-{}", self.id)
     }
 
-    fn upgrade(&self, _kubernetes_upgrade_status: KubernetesUpgradeStatus) -> Result<(), EngineError> {
-        // TODO(benjaminch): to be implemented
+    fn get_scaleway_api_configuration(&self) -> scaleway_api_rs::apis::configuration::Configuration {
+        scaleway_api_rs::apis::configuration::Configuration {
+            api_key: Some(scaleway_api_rs::apis::configuration::ApiKey {
+                key: self.options.scaleway_secret_key.clone(),
+                prefix: None,
+            }),
+            ..scaleway_api_rs::apis::configuration::Configuration::default()
+        }
+    }
+
+    /// Kapsule clusters are tagged with the Qovery cluster id (see ks-locals.j2.tf), use it to find the Scaleway cluster.
+    fn get_scaleway_cluster(&self) -> Result<scaleway_api_rs::models::ScalewayK8sV1Cluster, EngineError> {
+        let cluster_tag = format!("ClusterId={}", self.id());
+
+        let clusters = match block_on(scaleway_api_rs::apis::clusters_api::list_clusters(
+            &self.get_scaleway_api_configuration(),
+            self.zone.region().as_str(),
+            None,
+            Some(self.options.scaleway_project_id.as_str()),
+            None,
+            None,
+            None,
+            Some(self.name()),
+            None,
+            None,
+        )) {
+            Ok(res) => res.clusters.unwrap_or_default(),
+            Err(e) => {
+                return Err(self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!("Can't list Scaleway Kubernetes clusters: {}", e),
+                ))
+            }
+        };
+
+        clusters
+            .into_iter()
+            .find(|cluster| cluster.tags.as_ref().map_or(false, |tags| tags.contains(&cluster_tag)))
+            .ok_or_else(|| {
+                self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!("Can't find Scaleway Kubernetes cluster for {}", self.name_with_id()),
+                )
+            })
+    }
+
+    fn list_scaleway_node_pools(
+        &self,
+        scaleway_cluster_id: &str,
+    ) -> Result<Vec<scaleway_api_rs::models::ScalewayK8sV1Pool>, EngineError> {
+        match block_on(scaleway_api_rs::apis::pools_api::list_pools(
+            &self.get_scaleway_api_configuration(),
+            self.zone.region().as_str(),
+            scaleway_cluster_id,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )) {
+            Ok(res) => Ok(res.pools.unwrap_or_default()),
+            Err(e) => Err(self.engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "Can't list node pools of Kubernetes cluster {}: {}",
+                    self.name_with_id(),
+                    e
+                ),
+            )),
+        }
+    }
+
+    /// Node pools are not upgraded along with the control plane, roll them one by one to the control plane version.
+    fn upgrade_node_pools(&self, send_to_customer: &dyn Fn(&str)) -> Result<(), EngineError> {
+        let scaleway_cluster = self.get_scaleway_cluster()?;
+        let scaleway_cluster_id = scaleway_cluster.id.unwrap_or_default();
+        let control_plane_version = match scaleway_cluster.version {
+            Some(version) => version,
+            None => {
+                return Err(self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!("Can't get control plane version of cluster {}", self.name_with_id()),
+                ))
+            }
+        };
+
+        for pool in self.list_scaleway_node_pools(scaleway_cluster_id.as_str())? {
+            let pool_name = pool.name.unwrap_or_default();
+            if pool.version.as_deref() == Some(control_plane_version.as_str()) {
+                info!("Node pool {} is already up to date", pool_name);
+                continue;
+            }
+
+            send_to_customer(
+                format!(
+                    "Upgrading Kubernetes {} node pool {} to version {}",
+                    self.name(),
+                    pool_name,
+                    control_plane_version
+                )
+                .as_str(),
+            );
+
+            if let Err(e) = block_on(scaleway_api_rs::apis::pools_api::upgrade_pool(
+                &self.get_scaleway_api_configuration(),
+                self.zone.region().as_str(),
+                pool.id.unwrap_or_default().as_str(),
+                scaleway_api_rs::models::InlineObject30::new(control_plane_version.clone()),
+            )) {
+                return Err(self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!("Error while upgrading node pool {}: {}", pool_name, e),
+                ));
+            }
+        }
+
+        // wait for every node pool to be ready with the new version
+        let result = retry::retry(Fibonacci::from_millis(10000).take(12), || {
+            match self.list_scaleway_node_pools(scaleway_cluster_id.as_str()) {
+                Ok(pools) => {
+                    if pools.iter().all(|pool| {
+                        pool.status == Some(ScalewayPoolStatus::Ready)
+                            && pool.version.as_deref() == Some(control_plane_version.as_str())
+                    }) {
+                        OperationResult::Ok(())
+                    } else {
+                        info!("Node pools of cluster {} are still upgrading", self.name_with_id());
+                        OperationResult::Retry(self.engine_error(
+                            EngineErrorCause::Internal,
+                            format!(
+                                "Node pools of cluster {} didn't reach version {} in time",
+                                self.name_with_id(),
+                                control_plane_version
+                            ),
+                        ))
+                    }
+                }
+                Err(e) => OperationResult::Retry(e),
+            }
+        });
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(Operation { error, .. }) => Err(error),
+            Err(retry::Error::Internal(message)) => Err(self.engine_error(EngineErrorCause::Internal, message)),
+        }
+    }
+
+    fn upgrade(&self, kubernetes_upgrade_status: KubernetesUpgradeStatus) -> Result<(), EngineError> {
+        let listeners_helper = ListenersHelper::new(&self.listeners);
+        let send_to_customer = |message: &str| {
+            listeners_helper.upgrade_in_progress(ProgressInfo::new(
+                ProgressScope::Infrastructure {
+                    execution_id: self.context.execution_id().to_string(),
+                },
+                ProgressLevel::Info,
+                Some(message),
+                self.context.execution_id(),
+            ))
+        };
+        send_to_customer(
+            format!(
+                "Start preparing Kapsule upgrade process {} cluster with id {}",
+                self.name(),
+                self.id()
+            )
+            .as_str(),
+        );
+
+        if kubernetes_upgrade_status.required_upgrade_on.is_none() {
+            info!("No Kubernetes upgrade required, control plane and node pools are already up to date");
+            return Ok(());
+        }
+
+        // make sure the upgrade can be safely started
+        self.check_kubernetes_upgrade_path(&kubernetes_upgrade_status)?;
+        send_to_customer(format!("Checking Kubernetes cluster {} health before upgrade", self.name()).as_str());
+        self.check_cluster_is_healthy()?;
+
+        //
+        // Upgrade control plane
+        //
+
+        match &kubernetes_upgrade_status.required_upgrade_on {
+            Some(KubernetesNodesType::Masters) => {
+                let temp_dir = workspace_directory(
+                    self.context.workspace_root_dir(),
+                    self.context.execution_id(),
+                    format!("bootstrap/{}", self.id()),
+                )
+                .map_err(|err| self.engine_error(EngineErrorCause::Internal, err.to_string()))?;
+
+                let _ = cast_simple_error_to_engine_error(
+                    self.engine_error_scope(),
+                    self.context.execution_id(),
+                    crate::template::generate_and_copy_all_files_into_dir(
+                        self.template_directory.as_str(),
+                        temp_dir.as_str(),
+                        &self.tera_context()?,
+                    ),
+                )?;
+
+                let common_charts_temp_dir = format!("{}/common/charts", temp_dir.as_str());
+                let _ = cast_simple_error_to_engine_error(
+                    self.engine_error_scope(),
+                    self.context.execution_id(),
+                    crate::template::copy_non_template_files(
+                        format!("{}/common/bootstrap/charts", self.context.lib_root_dir()),
+                        common_charts_temp_dir.as_str(),
+                    ),
+                )?;
+
+                send_to_customer(
+                    format!(
+                        "Upgrading Kubernetes {} control plane from {} to {}",
+                        self.name(),
+                        kubernetes_upgrade_status.deployed_masters_version,
+                        kubernetes_upgrade_status.requested_version
+                    )
+                    .as_str(),
+                );
+
+                if let Err(e) = cast_simple_error_to_engine_error(
+                    self.engine_error_scope(),
+                    self.context.execution_id(),
+                    kubernetes::terraform_apply(self, temp_dir.as_str(), self.context.is_dry_run_deploy()),
+                ) {
+                    error!(
+                        "Error while upgrading control plane for cluster {} with id {}.",
+                        self.name(),
+                        self.id()
+                    );
+                    return Err(e);
+                }
+
+                send_to_customer(
+                    format!(
+                        "Kubernetes {} control plane has been successfully upgraded",
+                        self.name()
+                    )
+                    .as_str(),
+                );
+            }
+            _ => info!("No need to perform Kubernetes control plane upgrade, it is already up to date"),
+        }
+
+        //
+        // Upgrade node pools
+        //
+
+        if self.context.is_dry_run_deploy() {
+            info!("Dry run mode, node pools upgrade is skipped");
+            return Ok(());
+        }
+
+        self.upgrade_node_pools(&send_to_customer)?;
+
+        send_to_customer(format!("Kubernetes {} node pools have been successfully upgraded", self.name()).as_str());
+
         Ok(())
     }
 
@@ -230,12 +505,7 @@ impl<'a> Kapsule<'a> {
             "metrics_history_enabled",
             &self.context.is_feature_enabled(&Features::MetricsHistory),
         );
-        if self.context.resource_expiration_in_seconds().is_some() {
-            context.insert(
-                "resource_expiration_in_seconds",
-                &self.context.resource_expiration_in_seconds(),
-            )
-        }
+        insert_resource_expiration_into_tera_context(&self.context, &mut context);
 
         // AWS S3 tfstates storage tfstates
         context.insert(
@@ -290,21 +560,20 @@ impl<'a> Kapsule<'a> {
         context.insert("grafana_admin_password", self.options.grafana_admin_password.as_str());
 
         // Kubernetes workers
-        let worker_nodes = self
-            .nodes
+        let node_pools = self
+            .node_pools
             .iter()
-            .group_by(|e| e.instance_type())
-            .into_iter()
-            .map(|(instance_type, group)| (instance_type, group.collect::<Vec<_>>()))
-            .map(|(instance_type, nodes)| WorkerNodeDataTemplate {
-                instance_type: instance_type.to_string().to_uppercase(),
-                desired_size: "3".to_string(),
-                max_size: nodes.len().to_string(),
-                min_size: "3".to_string(),
+            .map(|pool| NodePoolDataTemplate {
+                name: pool.name().to_string(),
+                is_default: pool.is_default(),
+                instance_type: pool.node_type().to_string().to_uppercase(),
+                min_size: pool.min_size().to_string(),
+                max_size: pool.max_size().to_string(),
+                autoscaling: pool.is_autoscaling_enabled(),
+                tags: pool.scaleway_tags(),
             })
-            .collect::<Vec<WorkerNodeDataTemplate>>();
-        context.insert("scw_ks_worker_nodes", &worker_nodes);
-        context.insert("scw_ks_pool_autoscale", &true);
+            .collect::<Vec<NodePoolDataTemplate>>();
+        context.insert("scw_ks_node_pools", &node_pools);
 
         Ok(context)
     }
@@ -362,12 +631,55 @@ impl<'a> Kubernetes for Kapsule<'a> {
         self.dns_provider
     }
 
-    fn config_file_store(&self) -> &dyn ObjectStorage {
-        &self.object_storage
+    fn config_file_store(&self) -> Option<&dyn ObjectStorage> {
+        Some(&self.object_storage)
+    }
+
+    fn supported_kubernetes_versions(&self) -> Option<Vec<VersionsNumber>> {
+        Some(
+            KAPSULE_SUPPORTED_KUBERNETES_VERSIONS
+                .iter()
+                .filter_map(|v| get_version_number(v).ok())
+                .collect(),
+        )
+    }
+
+    fn nodes_capacity_types(&self) -> Vec<NodeCapacityType> {
+        self.node_pools
+            .iter()
+            .map(|pool| pool.capacity_type())
+            .unique()
+            .collect()
+    }
+
+    fn node_pools_scheduling(&self) -> Vec<NodePoolScheduling> {
+        self.node_pools.iter().map(|pool| pool.scheduling()).collect()
+    }
+
+    fn nodes_instance_types(&self) -> Vec<String> {
+        self.node_pools
+            .iter()
+            .flat_map(|pool| vec![pool.node_type().to_string(); pool.max_size() as usize])
+            .collect()
     }
 
     fn is_valid(&self) -> Result<(), EngineError> {
-        Ok(())
+        if let Err(message) = validate_node_pools(&self.node_pools) {
+            return Err(self.engine_error(
+                EngineErrorCause::User("Node pools configuration is invalid"),
+                format!("cluster {}: {}", self.name_with_id(), message),
+            ));
+        }
+
+        if let Err(message) = validate_node_types(&self.node_pools, self.zone) {
+            return Err(self.engine_error(
+                EngineErrorCause::User("Choose a node type available in the cluster zone"),
+                format!("cluster {}: {}", self.name_with_id(), message),
+            ));
+        }
+
+        // the kubeconfig is stored in the object storage
+        self.object_storage.is_valid()
     }
 
     fn on_create(&self) -> Result<(), EngineError> {
@@ -387,6 +699,9 @@ impl<'a> Kubernetes for Kapsule<'a> {
 
         send_to_customer(format!("Preparing SCW {} cluster deployment with id {}", self.name(), self.id()).as_str());
 
+        // reject unsupported versions before anything is created
+        self.check_kubernetes_version_is_supported()?;
+
         // upgrade cluster instead if required
         match self.config_file() {
             Ok(f) => match is_kubernetes_upgrade_required(
@@ -448,7 +763,7 @@ impl<'a> Kubernetes for Kapsule<'a> {
         match cast_simple_error_to_engine_error(
             self.engine_error_scope(),
             self.context.execution_id(),
-            terraform_init_validate_plan_apply(temp_dir.as_str(), self.context.is_dry_run_deploy()),
+            kubernetes::terraform_apply(self, temp_dir.as_str(), self.context.is_dry_run_deploy()),
         ) {
             Ok(_) => {}
             Err(e) => {
@@ -467,7 +782,7 @@ impl<'a> Kubernetes for Kapsule<'a> {
         // Kubeconfig bucket
         if let Err(e) = self
             .object_storage
-            .create_bucket(self.kubeconfig_bucket_name().as_str())
+            .create_bucket_for(self.kubeconfig_bucket_name().as_str(), BucketPurpose::State)
         {
             let message = format!(
                 "Cannot create object storage bucket {} for cluster {} with id {}",
@@ -480,7 +795,10 @@ impl<'a> Kubernetes for Kapsule<'a> {
         }
 
         // Logs bucket
-        if let Err(e) = self.object_storage.create_bucket(self.logs_bucket_name().as_str()) {
+        if let Err(e) = self
+            .object_storage
+            .create_bucket_for(self.logs_bucket_name().as_str(), BucketPurpose::State)
+        {
             let message = format!(
                 "Cannot create object storage bucket {} for cluster {} with id {}",
                 self.logs_bucket_name(),
@@ -565,6 +883,7 @@ impl<'a> Kubernetes for Kapsule<'a> {
                 &credentials_environment_variables,
                 helm_charts_to_deploy,
                 self.context.is_dry_run_deploy(),
+                self.context.image_registry_rewrites(),
             ),
         )
     }
@@ -597,12 +916,12 @@ impl<'a> Kubernetes for Kapsule<'a> {
                     e.message
                 );
                 error!("{}", &msg);
-                Err(EngineError {
-                    cause: EngineErrorCause::Internal,
-                    scope: EngineErrorScope::Engine,
-                    execution_id: self.context.execution_id().to_string(),
-                    message: Some(msg),
-                })
+                Err(EngineError::new(
+                    EngineErrorCause::Internal,
+                    EngineErrorScope::Engine,
+                    self.context.execution_id().to_string(),
+                    Some(msg),
+                ))
             }
         }
     }
@@ -702,7 +1021,7 @@ impl<'a> Kubernetes for Kapsule<'a> {
         if let Err(e) = cast_simple_error_to_engine_error(
             self.engine_error_scope(),
             self.context.execution_id(),
-            cmd::terraform::terraform_init_validate_plan_apply(temp_dir.as_str(), false),
+            kubernetes::terraform_apply(self, temp_dir.as_str(), false),
         ) {
             error!("An issue occurred during the apply before destroy of Terraform, it may be expected if you're resuming a destroy: {:?}", e.message);
         };
@@ -868,7 +1187,11 @@ impl<'a> Kubernetes for Kapsule<'a> {
                 || match cast_simple_error_to_engine_error(
                     self.engine_error_scope(),
                     self.context.execution_id(),
-                    cmd::terraform::terraform_init_validate_destroy(temp_dir.as_str(), false),
+                    cmd::terraform::terraform_init_validate_destroy(
+                        temp_dir.as_str(),
+                        false,
+                        self.context.cancellation_token(),
+                    ),
                 ) {
                     Ok(_) => OperationResult::Ok(()),
                     Err(e) => OperationResult::Retry(e),
@@ -940,9 +1263,9 @@ impl<'a> Kubernetes for Kapsule<'a> {
         kubernetes::deploy_environment_error(self, environment)
     }
 
-    fn pause_environment(&self, _environment: &Environment) -> Result<(), EngineError> {
-        warn!("SCW.pause_environment_error() called for {}", self.name());
-        Ok(())
+    fn pause_environment(&self, environment: &Environment) -> Result<(), EngineError> {
+        info!("SCW.pause_environment() called for {}", self.name());
+        kubernetes::pause_environment(self, environment)
     }
 
     fn pause_environment_error(&self, _environment: &Environment) -> Result<(), EngineError> {
@@ -950,6 +1273,11 @@ impl<'a> Kubernetes for Kapsule<'a> {
         Ok(())
     }
 
+    fn resume_environment(&self, environment: &Environment) -> Result<(), EngineError> {
+        info!("SCW.resume_environment() called for {}", self.name());
+        kubernetes::resume_environment(self, environment)
+    }
+
     fn delete_environment(&self, environment: &Environment) -> Result<(), EngineError> {
         info!("SCW.delete_environment() called for {}", self.name());
         kubernetes::delete_environment(self, environment)
@@ -1,5 +1,9 @@
-use crate::cloud_provider::kubernetes::KubernetesNode;
+use crate::cloud_provider::kubernetes::{
+    KubernetesNode, NodeCapacityType, NodePoolScheduling, Taint, CAPACITY_TYPE_NODE_LABEL,
+};
+use crate::cloud_provider::scaleway::application::Zone;
 use std::any::Any;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt;
 use std::str::FromStr;
 
@@ -30,6 +34,33 @@ impl NodeType {
             NodeType::RenderS => "render-s",
         }
     }
+
+    /// Node types Kapsule offers in the zone, a static table until it's fetched from the Scaleway API.
+    pub fn available_in(zone: Zone) -> Vec<NodeType> {
+        vec![
+            NodeType::Gp1Xs,
+            NodeType::Gp1S,
+            NodeType::Gp1M,
+            NodeType::Gp1L,
+            NodeType::Gp1Xl,
+            NodeType::Dev1M,
+            NodeType::Dev1L,
+            NodeType::Dev1Xl,
+            NodeType::RenderS,
+        ]
+        .into_iter()
+        .filter(|node_type| node_type.is_available_in(zone))
+        .collect()
+    }
+
+    pub fn is_available_in(&self, zone: Zone) -> bool {
+        match (self, zone) {
+            // GPU instances are only hosted in fr-par-1
+            (NodeType::RenderS, zone) => zone == Zone::Paris1,
+            (NodeType::Gp1Xl, Zone::Warsaw1) => false,
+            _ => true,
+        }
+    }
 }
 
 impl fmt::Display for NodeType {
@@ -80,6 +111,200 @@ impl Node {
     }
 }
 
+/// Node pool clusters were created with before pools were named. It keeps the terraform address and the Scaleway name
+/// it had then, renaming them would make terraform recreate the pool and all its nodes.
+pub const DEFAULT_NODE_POOL_NAME: &str = "default";
+
+/// A group of nodes sharing the same node type, scaled by Scaleway's cluster autoscaler between `min_size` and `max_size`.
+/// Pool name is used to identify the pool in terraform, it has to be unique and must not change between applies.
+#[derive(Clone)]
+pub struct NodePool {
+    name: String,
+    node_type: NodeType,
+    min_size: u16,
+    max_size: u16,
+    capacity_type: NodeCapacityType,
+    labels: BTreeMap<String, String>,
+    taints: Vec<Taint>,
+}
+
+impl NodePool {
+    pub fn new(name: &str, node_type: NodeType, min_size: u16, max_size: u16) -> NodePool {
+        NodePool {
+            name: name.to_string(),
+            node_type,
+            min_size,
+            max_size,
+            capacity_type: NodeCapacityType::OnDemand,
+            labels: BTreeMap::new(),
+            taints: vec![],
+        }
+    }
+
+    /// Node pool without autoscaling, it will always have `size` nodes.
+    pub fn new_with_fixed_size(name: &str, node_type: NodeType, size: u16) -> NodePool {
+        NodePool::new(name, node_type, size, size)
+    }
+
+    pub fn with_label(mut self, key: &str, value: &str) -> NodePool {
+        self.labels.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn with_taint(mut self, taint: Taint) -> NodePool {
+        self.taints.push(taint);
+        self
+    }
+
+    pub fn with_capacity_type(mut self, capacity_type: NodeCapacityType) -> NodePool {
+        self.capacity_type = capacity_type;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn node_type(&self) -> &NodeType {
+        &self.node_type
+    }
+
+    pub fn min_size(&self) -> u16 {
+        self.min_size
+    }
+
+    pub fn max_size(&self) -> u16 {
+        self.max_size
+    }
+
+    pub fn capacity_type(&self) -> NodeCapacityType {
+        self.capacity_type
+    }
+
+    pub fn labels(&self) -> &BTreeMap<String, String> {
+        &self.labels
+    }
+
+    pub fn taints(&self) -> &Vec<Taint> {
+        &self.taints
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.name == DEFAULT_NODE_POOL_NAME
+    }
+
+    pub fn is_autoscaling_enabled(&self) -> bool {
+        self.min_size != self.max_size
+    }
+
+    /// Labels and taints of the pool nodes, the capacity type label included.
+    pub fn scheduling(&self) -> NodePoolScheduling {
+        let mut labels = self.labels.clone();
+        labels.insert(
+            CAPACITY_TYPE_NODE_LABEL.to_string(),
+            self.capacity_type.as_label_value().to_string(),
+        );
+
+        NodePoolScheduling {
+            labels,
+            taints: self.taints.clone(),
+        }
+    }
+
+    /// Scaleway propagates pool tags to nodes: `noprefix=` tags become labels and `taint=` tags become taints.
+    /// The capacity type label is always set, databases are kept off spot nodes with it.
+    pub fn scaleway_tags(&self) -> Vec<String> {
+        let labels = self
+            .labels
+            .iter()
+            .map(|(key, value)| format!("noprefix={}={}", key, value))
+            .chain(std::iter::once(format!(
+                "noprefix={}={}",
+                CAPACITY_TYPE_NODE_LABEL,
+                self.capacity_type.as_label_value()
+            )));
+        let taints = self
+            .taints
+            .iter()
+            .map(|taint| format!("taint={}={}:{}", taint.key, taint.value, taint.effect.as_str()));
+
+        labels.chain(taints).collect()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        // names starting with a digit are refused, they could clash with the default pool terraform address
+        if !self.name.starts_with(|c: char| c.is_ascii_lowercase())
+            || !self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            return Err(format!(
+                "node pool name `{}` is invalid, it should start with a lowercase letter and only contain lowercase letters, digits and '-'",
+                self.name
+            ));
+        }
+
+        if self.capacity_type == NodeCapacityType::Spot {
+            return Err(format!(
+                "node pool `{}` can't use spot nodes, Kapsule doesn't offer preemptible instances",
+                self.name
+            ));
+        }
+
+        if self.max_size == 0 {
+            return Err(format!("node pool `{}` max size should be greater than 0", self.name));
+        }
+
+        if self.min_size > self.max_size {
+            return Err(format!(
+                "node pool `{}` min size ({}) should be lower or equal to its max size ({})",
+                self.name, self.min_size, self.max_size
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+pub fn validate_node_pools(node_pools: &[NodePool]) -> Result<(), String> {
+    if node_pools.is_empty() {
+        return Err("at least one node pool is required".to_string());
+    }
+
+    let mut names = HashSet::new();
+    for node_pool in node_pools {
+        node_pool.validate()?;
+
+        if !names.insert(node_pool.name()) {
+            return Err(format!("node pool name `{}` is used more than once", node_pool.name()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks every node pool uses a node type offered in the zone, before anything is created.
+pub fn validate_node_types(node_pools: &[NodePool], zone: Zone) -> Result<(), String> {
+    for node_pool in node_pools {
+        if !node_pool.node_type().is_available_in(zone) {
+            return Err(format!(
+                "node pool `{}` node type {} is not available in zone {}, available node types: {}",
+                node_pool.name(),
+                node_pool.node_type(),
+                zone,
+                NodeType::available_in(zone)
+                    .iter()
+                    .map(|node_type| node_type.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+
+    Ok(())
+}
+
 impl KubernetesNode for Node {
     fn instance_type(&self) -> &str {
         self.node_type.as_str()
@@ -93,7 +318,11 @@ impl KubernetesNode for Node {
 #[cfg(test)]
 mod tests {
     use crate::cloud_provider::kubernetes::KubernetesNode;
-    use crate::cloud_provider::scaleway::kubernetes::node::{Node, NodeType};
+    use crate::cloud_provider::kubernetes::{NodeCapacityType, Taint, TaintEffect};
+    use crate::cloud_provider::scaleway::application::Zone;
+    use crate::cloud_provider::scaleway::kubernetes::node::{
+        validate_node_pools, validate_node_types, Node, NodePool, NodeType, DEFAULT_NODE_POOL_NAME,
+    };
 
     #[test]
     fn test_node_types() {
@@ -107,4 +336,107 @@ mod tests {
         assert_eq!(Node::new(NodeType::Gp1Xl).instance_type(), "gp1-xl");
         assert_eq!(Node::new(NodeType::RenderS).instance_type(), "render-s");
     }
+
+    #[test]
+    fn test_node_pool_validation() {
+        assert!(NodePool::new("default", NodeType::Dev1L, 3, 10).validate().is_ok());
+        assert!(NodePool::new("default", NodeType::Dev1L, 0, 1).validate().is_ok());
+        assert!(NodePool::new_with_fixed_size("default", NodeType::Dev1L, 3)
+            .validate()
+            .is_ok());
+        assert_eq!(
+            NodePool::new("default", NodeType::Dev1L, 10, 3).validate(),
+            Err("node pool `default` min size (10) should be lower or equal to its max size (3)".to_string())
+        );
+        assert_eq!(
+            NodePool::new("default", NodeType::Dev1L, 0, 0).validate(),
+            Err("node pool `default` max size should be greater than 0".to_string())
+        );
+        assert_eq!(
+            NodePool::new("default", NodeType::Dev1L, 1, 3)
+                .with_capacity_type(NodeCapacityType::Spot)
+                .validate(),
+            Err("node pool `default` can't use spot nodes, Kapsule doesn't offer preemptible instances".to_string())
+        );
+        assert_eq!(
+            NodePool::new("Default_Pool", NodeType::Dev1L, 1, 3).validate(),
+            Err(
+                "node pool name `Default_Pool` is invalid, it should start with a lowercase letter and only contain lowercase letters, digits and '-'"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_node_pool_autoscaling() {
+        assert!(NodePool::new("default", NodeType::Gp1S, 3, 10).is_autoscaling_enabled());
+        assert!(!NodePool::new_with_fixed_size("default", NodeType::Gp1S, 3).is_autoscaling_enabled());
+    }
+
+    #[test]
+    fn test_default_node_pool() {
+        assert!(NodePool::new(DEFAULT_NODE_POOL_NAME, NodeType::Gp1S, 3, 10).is_default());
+        assert!(!NodePool::new("general-purpose", NodeType::Gp1S, 3, 10).is_default());
+        assert!(NodePool::new("1", NodeType::Gp1S, 3, 10).validate().is_err());
+    }
+
+    #[test]
+    fn test_node_pools_validation() {
+        let general_purpose = NodePool::new("general-purpose", NodeType::Gp1S, 3, 10);
+        let databases = NodePool::new("databases", NodeType::Gp1M, 1, 3);
+
+        assert!(validate_node_pools(&[general_purpose.clone(), databases]).is_ok());
+        assert_eq!(
+            validate_node_pools(&[]),
+            Err("at least one node pool is required".to_string())
+        );
+        assert_eq!(
+            validate_node_pools(&[
+                general_purpose.clone(),
+                NodePool::new("general-purpose", NodeType::Dev1L, 1, 1)
+            ]),
+            Err("node pool name `general-purpose` is used more than once".to_string())
+        );
+    }
+
+    #[test]
+    fn test_node_types_availability() {
+        // setup:
+        let general_purpose = NodePool::new("general-purpose", NodeType::Gp1S, 3, 10);
+        let gpu = NodePool::new("gpu", NodeType::RenderS, 1, 1);
+
+        // execute & verify:
+        assert!(validate_node_types(&[general_purpose.clone(), gpu.clone()], Zone::Paris1).is_ok());
+        assert!(validate_node_types(&[general_purpose.clone()], Zone::Amsterdam1).is_ok());
+        assert_eq!(
+            validate_node_types(&[general_purpose, gpu], Zone::Amsterdam1),
+            Err(
+                "node pool `gpu` node type render-s is not available in zone nl-ams-1, available node types: \
+                 gp1-xs, gp1-s, gp1-m, gp1-l, gp1-xl, dev1-m, dev1-l, dev1-xl"
+                    .to_string()
+            )
+        );
+        assert_eq!(NodeType::available_in(Zone::Paris1).len(), 9);
+        assert!(!NodeType::Gp1Xl.is_available_in(Zone::Warsaw1));
+    }
+
+    #[test]
+    fn test_node_pool_scaleway_tags() {
+        let node_pool = NodePool::new("databases", NodeType::Gp1M, 1, 3)
+            .with_label("qovery.com/pool", "databases")
+            .with_taint(Taint {
+                key: "dedicated".to_string(),
+                value: "databases".to_string(),
+                effect: TaintEffect::NoSchedule,
+            });
+
+        assert_eq!(
+            node_pool.scaleway_tags(),
+            vec![
+                "noprefix=qovery.com/pool=databases".to_string(),
+                "noprefix=qovery.com/capacity-type=on-demand".to_string(),
+                "taint=dedicated=databases:NoSchedule".to_string(),
+            ]
+        );
+    }
 }
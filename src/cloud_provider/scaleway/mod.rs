@@ -1,8 +1,13 @@
 use std::any::Any;
 
+use reqwest::StatusCode;
+
+use crate::cloud_provider::scaleway::application::Region;
 use crate::cloud_provider::{CloudProvider, EngineError, Kind, TerraformStateCredentials};
 use crate::constants::{SCALEWAY_ACCESS_KEY, SCALEWAY_SECRET_KEY};
+use crate::error::{check_credentials_are_set, EngineErrorCause};
 use crate::models::{Context, Listen, Listener, Listeners};
+use crate::runtime::block_on;
 
 pub mod application;
 pub mod databases;
@@ -69,6 +74,63 @@ impl CloudProvider for Scaleway {
         Ok(())
     }
 
+    fn validate_credentials(&self) -> Result<(), EngineError> {
+        let bad_credentials = "Your Scaleway account seems to be no longer valid (bad Credentials). \
+                Please contact your Organization administrator to fix or change the Credentials.";
+
+        check_credentials_are_set(&[
+            ("access key", self.access_key.as_str()),
+            ("secret key", self.secret_key.as_str()),
+        ])
+        .map_err(|err| {
+            self.engine_error(
+                EngineErrorCause::User(bad_credentials),
+                format!("{} for Scaleway {}", err, self.name_with_id()),
+            )
+        })?;
+
+        // any authenticated read works, the registry namespaces of the organization are the cheapest one
+        // https://developers.scaleway.com/en/products/registry/api/#get-09e004
+        let configuration = scaleway_api_rs::apis::configuration::Configuration {
+            api_key: Some(scaleway_api_rs::apis::configuration::ApiKey {
+                key: self.secret_key.clone(),
+                prefix: None,
+            }),
+            ..scaleway_api_rs::apis::configuration::Configuration::default()
+        };
+
+        block_on(scaleway_api_rs::apis::namespaces_api::list_namespaces(
+            &configuration,
+            Region::Paris.as_str(),
+            None,
+            Some(1.0),
+            None,
+            Some(self.organization_id.as_str()),
+            None,
+            None,
+        ))
+        .map(|_| ())
+        .map_err(|e| {
+            let cause = match &e {
+                scaleway_api_rs::apis::Error::ResponseError(response)
+                    if response.status == StatusCode::UNAUTHORIZED || response.status == StatusCode::FORBIDDEN =>
+                {
+                    EngineErrorCause::User(bad_credentials)
+                }
+                _ => EngineErrorCause::Internal,
+            };
+
+            self.engine_error(
+                cause,
+                format!(
+                    "failed to check the credentials of Scaleway {}: {}",
+                    self.name_with_id(),
+                    e
+                ),
+            )
+        })
+    }
+
     fn credentials_environment_variables(&self) -> Vec<(&str, &str)> {
         vec![
             (SCALEWAY_ACCESS_KEY, self.access_key.as_str()),
@@ -1,9 +1,10 @@
 use tera::Context as TeraContext;
 
-use crate::cloud_provider::models::{CustomDomain, CustomDomainDataTemplate, Route, RouteDataTemplate};
+use crate::cloud_provider::models::{CustomDomain, CustomDomainDataTemplate, Route, RouteDataTemplate, RouterProtocol};
 use crate::cloud_provider::service::{
-    default_tera_context, delete_router, delete_stateless_service, send_progress_on_long_task, Action, Create, Delete,
-    Helm, Pause, Service, ServiceType, StatelessService,
+    cleanup_stale_run_workspace_directories, default_tera_context, delete_router, delete_stateless_service,
+    run_workspace_directory, send_progress_on_long_task, Action, Create, Delete, Helm, Pause, Router as RRouter,
+    Service, ServiceType, StatelessService,
 };
 use crate::cloud_provider::utilities::{check_cname_for, sanitize_name};
 use crate::cloud_provider::DeploymentTarget;
@@ -19,6 +20,9 @@ pub struct Router {
     default_domain: String,
     custom_domains: Vec<CustomDomain>,
     routes: Vec<Route>,
+    protocol: RouterProtocol,
+    public_port: u16,
+    namespace: Option<String>,
     listeners: Listeners,
 }
 
@@ -30,9 +34,14 @@ impl Router {
         action: Action,
         default_domain: &str,
         custom_domains: Vec<CustomDomain>,
-        routes: Vec<Route>,
+        mut routes: Vec<Route>,
+        protocol: RouterProtocol,
+        public_port: u16,
+        namespace: Option<String>,
         listeners: Listeners,
     ) -> Router {
+        Route::sort_by_specificity(&mut routes);
+
         Router {
             context,
             id: id.to_string(),
@@ -41,6 +50,9 @@ impl Router {
             default_domain: default_domain.to_string(),
             custom_domains,
             routes,
+            protocol,
+            public_port,
+            namespace,
             listeners,
         }
     }
@@ -67,6 +79,10 @@ impl Service for Router {
         sanitize_name("router", self.name())
     }
 
+    fn custom_namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
     fn version(&self) -> &str {
         "1.0"
     }
@@ -79,6 +95,10 @@ impl Service for Router {
         None
     }
 
+    fn endpoints(&self) -> Vec<String> {
+        self.domains().iter().map(|domain| domain.to_string()).collect()
+    }
+
     fn start_timeout(&self) -> Timeout<u32> {
         Timeout::Default
     }
@@ -133,11 +153,9 @@ impl Service for Router {
                     .iter()
                     .find(|app| app.name() == r.application_name.as_str())
                 {
-                    Some(application) => application.private_port().map(|private_port| RouteDataTemplate {
-                        path: r.path.clone(),
-                        application_name: application.sanitized_name(),
-                        application_port: private_port,
-                    }),
+                    Some(application) => application
+                        .private_port()
+                        .map(|private_port| RouteDataTemplate::new(r, application.sanitized_name(), private_port)),
                     _ => None,
                 }
             })
@@ -153,6 +171,8 @@ impl Service for Router {
         context.insert("router_default_domain_hash", router_default_domain_hash.as_str());
         context.insert("custom_domains", &custom_domain_data_templates);
         context.insert("routes", &route_data_templates);
+        context.insert("router_protocol", self.protocol.as_str());
+        context.insert("public_port", &self.public_port);
         context.insert("spec_acme_email", "tls@qovery.com"); // TODO CHANGE ME
         context.insert("metadata_annotations_cert_manager_cluster_issuer", "letsencrypt-qovery");
 
@@ -228,7 +248,7 @@ impl Create for Router {
             DeploymentTarget::SelfHosted(k, env) => (*k, *env),
         };
 
-        let workspace_dir = self.workspace_directory();
+        let workspace_dir = run_workspace_directory(self)?;
         let helm_release_name = self.helm_release_name();
 
         let kubernetes_config_file_path = kubernetes.config_file_path()?;
@@ -250,10 +270,11 @@ impl Create for Router {
             self.context.execution_id(),
             crate::cmd::helm::helm_exec_with_upgrade_history(
                 kubernetes_config_file_path.as_str(),
-                environment.namespace(),
+                self.namespace(environment),
                 helm_release_name.as_str(),
                 workspace_dir.as_str(),
                 Timeout::Default,
+                &self.helm_value_overrides(),
                 kubernetes.cloud_provider().credentials_environment_variables(),
             ),
         )?;
@@ -262,15 +283,15 @@ impl Create for Router {
             return Err(self.engine_error(EngineErrorCause::Internal, "Router has failed to be deployed".into()));
         }
 
+        cleanup_stale_run_workspace_directories(self, workspace_dir.as_str());
+
+        // check non custom domains
+        self.check_domains(kubernetes.dns_provider())?;
+
         Ok(())
     }
 
     fn on_create_check(&self) -> Result<(), EngineError> {
-        use crate::cloud_provider::service::Router;
-
-        // check non custom domains
-        self.check_domains()?;
-
         // Wait/Check that custom domain is a CNAME targeting qovery
         for domain_to_check in self.custom_domains.iter() {
             match check_cname_for(
@@ -1,24 +1,47 @@
+use std::collections::{BTreeMap, HashMap};
 use std::net::TcpStream;
 use std::sync::mpsc;
 use std::sync::mpsc::TryRecvError;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use itertools::Itertools;
+use retry::delay::Fibonacci;
+use retry::Error::Operation;
+use retry::OperationResult;
+use serde::{Deserialize, Serialize};
 use tera::Context as TeraContext;
+use tracing::{Level, Span};
 
 use crate::build_platform::Image;
 use crate::cloud_provider::environment::Environment;
-use crate::cloud_provider::kubernetes::Kubernetes;
-use crate::cloud_provider::utilities::check_domain_for;
-use crate::cloud_provider::DeploymentTarget;
+use crate::cloud_provider::kubernetes::{Kubernetes, NodePoolScheduling};
+use crate::cloud_provider::models::InitContainer;
+use crate::cloud_provider::pricing::{pricing_table, CostEstimate};
+use crate::cloud_provider::utilities::{
+    check_domain_for, check_domain_with_dns_provider, convert_k8s_cpu_value_to_f32, DnsCachePolicy,
+    DOMAIN_CHECK_DEADLINE,
+};
+use crate::cloud_provider::{DeploymentTarget, TerraformBackend, TerraformStateCredentials};
 use crate::cmd::helm::Timeout;
 use crate::cmd::kubectl::ScalingKind::Statefulset;
-use crate::cmd::kubectl::{kubectl_exec_delete_secret, kubectl_exec_scale_replicas_by_selector, ScalingKind};
-use crate::cmd::structs::LabelsContent;
-use crate::error::{cast_simple_error_to_engine_error, StringError};
+use crate::cmd::kubectl::{
+    kubectl_exec_annotate_workloads_by_selector, kubectl_exec_delete_secret, kubectl_exec_get_persistent_volume_claims,
+    kubectl_exec_get_pod_names, kubectl_exec_get_storage_classes, kubectl_exec_get_workloads_by_selector,
+    kubectl_exec_patch_persistent_volume_claim_storage, kubectl_exec_pipe_between_pods,
+    kubectl_exec_rollout_restart_statefulset, kubectl_exec_scale_replicas_by_selector, PodCommand, ScalingKind,
+};
+use crate::cmd::structs::{
+    HelmHistoryRow, KubernetesEvent, LabelsContent, PersistentVolumeClaim, Secret, StorageClass, Workload,
+};
+use crate::cmd::terraform::TerraformApplyResult;
+use crate::dns_provider::DnsProvider;
+use crate::error::{cast_simple_error_to_engine_error, SimpleError, SimpleErrorKind, StringError};
 use crate::error::{EngineError, EngineErrorCause, EngineErrorScope};
 use crate::models::ProgressLevel::Info;
 use crate::models::{Context, Listen, Listeners, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope};
+use crate::telemetry;
+use crate::unit_conversion::storage_to_gi;
 
 pub trait Service {
     fn context(&self) -> &Context;
@@ -45,16 +68,77 @@ pub trait Service {
         .unwrap()
     }
     fn version(&self) -> &str;
+    /// Version currently running on `target`, `None` when the service is not deployed yet.
+    fn deployed_version(&self, _target: &DeploymentTarget) -> Result<Option<String>, EngineError> {
+        Ok(None)
+    }
+    /// Disk size currently provisioned on `target`, `None` when unknown or not deployed yet.
+    fn deployed_disk_size_in_gib(&self, _target: &DeploymentTarget) -> Result<Option<u32>, EngineError> {
+        Ok(None)
+    }
     fn action(&self) -> &Action;
     fn private_port(&self) -> Option<u16>;
+    /// Externally relevant endpoints of the service, reported in the deployment summary.
+    fn endpoints(&self) -> Vec<String> {
+        vec![]
+    }
     fn start_timeout(&self) -> Timeout<u32>;
     fn total_cpus(&self) -> String;
     fn cpu_burst(&self) -> String;
     fn total_ram_in_mib(&self) -> u32;
     fn total_instances(&self) -> u16;
     fn tera_context(&self, target: &DeploymentTarget) -> Result<TeraContext, EngineError>;
+    /// Tera context rendered on `target` with its secrets redacted, to debug templates.
+    fn dump_tera_context(&self, target: &DeploymentTarget) -> Result<HashMap<String, serde_json::Value>, EngineError> {
+        Ok(redacted_tera_context(&self.tera_context(target)?))
+    }
+    /// Template directories rendered to deploy on `target`.
+    fn template_directories(&self, _target: &DeploymentTarget) -> Vec<String> {
+        vec![]
+    }
+    /// Render all template directories, to catch broken templates (missing context variables...) before deploying.
+    fn validate_templates(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        validate_templates(self, target)
+    }
     // used to retrieve logs by using Kubernetes labels (selector)
     fn selector(&self) -> String;
+    /// Kubernetes labels (selector) of the pods which have to be running for the service to be ready.
+    fn readiness_selector(&self) -> String {
+        self.selector()
+    }
+    /// Namespace the service is deployed in, the environment one unless the service has its own.
+    fn namespace<'a>(&'a self, environment: &'a Environment) -> &'a str {
+        self.custom_namespace().unwrap_or_else(|| environment.namespace())
+    }
+    /// Namespace isolating the service from the rest of its environment (network policies, quotas...).
+    fn custom_namespace(&self) -> Option<&str> {
+        None
+    }
+    /// Configured deployment timeout, `None` to derive it from the service sizing.
+    fn deploy_timeout(&self) -> Option<Duration> {
+        None
+    }
+    fn readiness_timeout(&self) -> Duration {
+        self.deploy_timeout()
+            .unwrap_or_else(|| readiness_timeout(self.total_instances(), self.total_ram_in_mib()))
+    }
+    fn wait_until_ready(&self, target: &DeploymentTarget, timeout: Duration) -> Result<(), EngineError> {
+        wait_until_ready(self, target, timeout)
+    }
+    /// Ballpark monthly cost of the resources selected in the tera context, shown before applying.
+    fn cost_estimate(&self, target: &DeploymentTarget) -> Result<CostEstimate, EngineError> {
+        let (kubernetes, is_managed_service) = match target {
+            DeploymentTarget::ManagedServices(kubernetes, _) => (*kubernetes, true),
+            DeploymentTarget::SelfHosted(kubernetes, _) => (*kubernetes, false),
+        };
+
+        Ok(pricing_table().estimate_tera_context(
+            &kubernetes.cloud_provider().kind(),
+            kubernetes.region(),
+            &self.tera_context(target)?,
+            is_managed_service,
+        ))
+    }
     fn debug_logs(&self, deployment_target: &DeploymentTarget) -> Vec<String> {
         debug_logs(self, deployment_target)
     }
@@ -108,23 +192,86 @@ pub trait Service {
     }
 }
 
-pub trait StatelessService: Service + Create + Pause + Delete {
+pub trait StatelessService: Service + Create + Pause + Delete + Helm {
     fn exec_action(&self, deployment_target: &DeploymentTarget) -> Result<(), EngineError> {
         match self.action() {
-            crate::cloud_provider::service::Action::Create => self.on_create(deployment_target),
-            crate::cloud_provider::service::Action::Delete => self.on_delete(deployment_target),
-            crate::cloud_provider::service::Action::Pause => self.on_pause(deployment_target),
+            crate::cloud_provider::service::Action::Create => service_span(self, "create").in_scope(|| {
+                telemetry::record(
+                    self.context(),
+                    telemetry::Operation::ServiceDeploy,
+                    self.service_type().name(),
+                    deployment_target.kubernetes().cloud_provider().kind().name(),
+                    || self.on_create(deployment_target),
+                )
+            }),
+            crate::cloud_provider::service::Action::Delete => {
+                service_span(self, "delete").in_scope(|| self.on_delete(deployment_target))
+            }
+            crate::cloud_provider::service::Action::Pause => {
+                service_span(self, "pause").in_scope(|| self.on_pause(deployment_target))
+            }
             crate::cloud_provider::service::Action::Nothing => Ok(()),
         }
     }
 }
 
-pub trait StatefulService: Service + Create + Pause + Delete + Backup + Clone + Upgrade + Downgrade {
+pub trait StatefulService: Service + Create + Pause + Delete + Helm + Backup + Clone + Upgrade + Downgrade {
+    fn is_deletion_protected(&self) -> bool;
+    /// Version which will be deployed for the requested one, an error if it isn't supported.
+    fn supported_version(&self, is_managed_services: bool) -> Result<String, StringError>;
+    /// Hash of the effective tera context, identical as long as the service configuration is unchanged.
+    fn tera_context_hash(&self, target: &DeploymentTarget) -> Result<String, EngineError> {
+        Ok(hash_tera_context(&self.tera_context(target)?))
+    }
+    /// Grows the disk of the deployed service to `new_size_gib`, it can't be shrunk.
+    fn resize_storage(&self, target: &DeploymentTarget, new_size_gib: u32) -> Result<(), EngineError> {
+        match target {
+            DeploymentTarget::SelfHosted(..) => resize_self_hosted_database_storage(target, self, new_size_gib),
+            DeploymentTarget::ManagedServices(..) => Err(self.engine_error(
+                EngineErrorCause::User("The storage of this managed database can't be resized"),
+                format!(
+                    "storage of managed {} {} can't be resized",
+                    self.service_type().name(),
+                    self.name_with_id()
+                ),
+            )),
+        }
+    }
+    /// Configuration changes a deployment would apply since the last one, see `tera_context_plan_diff`.
+    fn plan_diff(&self, target: &DeploymentTarget) -> Result<Option<Vec<String>>, EngineError> {
+        tera_context_plan_diff(target, self)
+    }
+    /// Database the service is seeded from on its first deployment, `None` when it's created empty.
+    fn clone_source(&self) -> Option<&DatabaseCloneSource> {
+        match self.service_type() {
+            ServiceType::Database(database_type) => database_type.options().clone_from.as_ref(),
+            _ => None,
+        }
+    }
     fn exec_action(&self, deployment_target: &DeploymentTarget) -> Result<(), EngineError> {
         match self.action() {
-            crate::cloud_provider::service::Action::Create => self.on_create(deployment_target),
-            crate::cloud_provider::service::Action::Delete => self.on_delete(deployment_target),
-            crate::cloud_provider::service::Action::Pause => self.on_pause(deployment_target),
+            crate::cloud_provider::service::Action::Create => service_span(self, "create").in_scope(|| {
+                telemetry::record(
+                    self.context(),
+                    telemetry::Operation::ServiceDeploy,
+                    self.service_type().name(),
+                    deployment_target.kubernetes().cloud_provider().kind().name(),
+                    || {
+                        // a clone is only seeded on its first deployment, it's then updated like any other service
+                        if self.clone_source().is_some() && self.deployed_version(deployment_target)?.is_none() {
+                            self.on_clone(deployment_target)
+                        } else {
+                            self.on_create(deployment_target)
+                        }
+                    },
+                )
+            }),
+            crate::cloud_provider::service::Action::Delete => {
+                service_span(self, "delete").in_scope(|| self.on_delete(deployment_target))
+            }
+            crate::cloud_provider::service::Action::Pause => {
+                service_span(self, "pause").in_scope(|| self.on_pause(deployment_target))
+            }
             crate::cloud_provider::service::Action::Nothing => Ok(()),
         }
     }
@@ -133,6 +280,11 @@ pub trait StatefulService: Service + Create + Pause + Delete + Backup + Clone +
 pub trait Application: StatelessService {
     fn image(&self) -> &Image;
     fn set_image(&mut self, image: Image);
+    fn init_containers(&self) -> &[InitContainer];
+    /// Init containers images are resolved against the container registry once the application image is pushed.
+    fn set_init_containers(&mut self, init_containers: Vec<InitContainer>);
+    /// Scales a paused application back up, to the instances it had before being paused.
+    fn on_resume(&self, target: &DeploymentTarget) -> Result<(), EngineError>;
 }
 
 pub trait ExternalService: StatelessService {}
@@ -140,26 +292,37 @@ pub trait ExternalService: StatelessService {}
 pub trait Router: StatelessService + Listen + Helm {
     fn domains(&self) -> Vec<&str>;
     fn has_custom_domains(&self) -> bool;
-    fn check_domains(&self) -> Result<(), EngineError> {
-        check_domain_for(
-            ListenersHelper::new(self.listeners()),
-            self.domains(),
-            self.id(),
+    fn check_domains(&self, dns_provider: &dyn DnsProvider) -> Result<(), EngineError> {
+        cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
             self.context().execution_id(),
-        )?;
-        Ok(())
+            check_domain_with_dns_provider(
+                ListenersHelper::new(self.listeners()),
+                dns_provider,
+                self.domains(),
+                self.id(),
+                self.context().execution_id(),
+                self.context().cancellation_token(),
+            ),
+        )
     }
 }
 
 pub trait Database: StatefulService {
     fn check_domains(&self, listeners: Listeners, domains: Vec<&str>) -> Result<(), EngineError> {
-        check_domain_for(
-            ListenersHelper::new(&listeners),
-            domains,
-            self.id(),
+        cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
             self.context().execution_id(),
-        )?;
-        Ok(())
+            check_domain_for(
+                ListenersHelper::new(&listeners),
+                domains,
+                self.id(),
+                self.context().execution_id(),
+                self.context().cancellation_token(),
+                DOMAIN_CHECK_DEADLINE,
+                DnsCachePolicy::Enabled,
+            ),
+        )
     }
 }
 
@@ -218,6 +381,10 @@ pub trait Helm {
     fn helm_chart_dir(&self) -> String;
     fn helm_chart_values_dir(&self) -> String;
     fn helm_chart_external_name_service_dir(&self) -> String;
+    /// Chart values set on top of the rendered values, keyed by their dotted path.
+    fn helm_value_overrides(&self) -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Hash)]
@@ -235,9 +402,342 @@ pub struct DatabaseOptions {
     pub host: String,
     pub port: u16,
     pub disk_size_in_gib: u32,
+    /// One of `standard` (magnetic), `gp2` (general purpose SSD), `gp3` (general purpose SSD with provisioned
+    /// performances) or `io1` (provisioned IOPS SSD).
     pub database_disk_type: String,
+    pub replicas: u8,
+    /// Provisioned IOPS, only valid with `io1` and `gp3` disk types.
+    pub iops: Option<u32>,
+    /// Provisioned storage throughput in MiB/s, only valid with `gp3` disk type.
+    pub storage_throughput: Option<u32>,
+    /// Protected databases are never deleted, except on test clusters.
+    pub deletion_protection: bool,
+    /// Self-hosted Redis only, replicas are monitored by sentinels electing a new master on failure.
+    pub sentinel_enabled: bool,
+    /// Self-hosted only, cpu and ram limits in percent of the requested totals, 100 when not set.
+    pub resources_overcommit_percent: Option<u32>,
+    /// MySQL only, server parameters passed as is to the RDS parameter group or the self-hosted `my.cnf`.
+    pub parameters: BTreeMap<String, String>,
+    /// MySQL only, clients can't connect without TLS.
+    pub require_tls: bool,
+    /// Self-hosted only, the server generates self-signed certificates when they are not provided.
+    pub tls_certificates: Option<DatabaseTlsCertificates>,
+    /// Managed only, how long the deletion is awaited once destroyed, 20 minutes when not set.
+    pub deletion_timeout_in_seconds: Option<u32>,
+    /// Self-hosted only, chart values overriding the rendered ones, like `podAnnotations.team`.
+    pub helm_value_overrides: BTreeMap<String, String>,
+    /// Self-hosted only, how long the helm release and the pods readiness are awaited, derived from the sizing when not set.
+    pub deploy_timeout_in_seconds: Option<u32>,
+    /// Self-hosted only, namespace the database is deployed in, the environment one when not set.
+    pub namespace: Option<String>,
+    /// Database the new database is seeded from, see `DatabaseCloneSource`.
+    pub clone_from: Option<DatabaseCloneSource>,
+    /// Managed PostgreSQL and MySQL only, how long automated backups are kept, from 0 (disabled) to 35 days.
+    pub backup_retention_days: Option<u32>,
+    /// Managed PostgreSQL and MySQL only, daily UTC window automated backups are taken in, like `05:00-06:00`.
+    pub backup_window: Option<String>,
+    /// Self-hosted only, label of the node pool the database is pinned to, scheduled anywhere when not set.
+    pub node_pool_label: Option<NodePoolLabel>,
+}
+
+/// Node label identifying a pool of nodes dedicated to some workloads, ex: `qovery.com/pool=databases`.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct NodePoolLabel {
+    pub key: String,
+    pub value: String,
+}
+
+/// Database of another environment of the project a new database is seeded from, on its first deployment:
+/// managed databases are restored from a snapshot of it, self-hosted ones from a dump of it.
+/// Only the data is copied, the clone keeps its own credentials and fqdn.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct DatabaseCloneSource {
+    pub environment_id: String,
+    pub database_id: String,
+    /// Managed only, identifier of the source database instance.
+    pub fqdn_id: String,
+}
+
+impl DatabaseCloneSource {
+    /// Namespace of a self-hosted source database, the one of its environment.
+    pub fn namespace(&self, project_id: &str) -> String {
+        format!("{}-{}", project_id, self.environment_id)
+    }
+}
+
+/// Kubernetes resources of a self-hosted database: the requested totals are guaranteed, limits allow bursts.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DatabaseResources {
+    pub cpu_request: String,
+    pub cpu_limit: Option<String>,
+    pub ram_request_mib: u32,
+    pub ram_limit_mib: Option<u32>,
+}
+
+/// PEM encoded certificates used by a self-hosted database to serve TLS.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct DatabaseTlsCertificates {
+    pub ca: String,
+    pub certificate: String,
+    pub key: String,
+}
+
+pub const MAX_DATABASE_REPLICAS: u8 = 5;
+pub const MIN_DATABASE_OVERCOMMIT_PERCENT: u32 = 100;
+/// Managed databases can still be deleting when terraform destroy returns, their deletion is awaited this long.
+pub const DEFAULT_DATABASE_DELETION_TIMEOUT_IN_SECONDS: u32 = 1200;
+
+/// Matches ^[a-z_][a-z0-9_.-]*$
+fn is_database_parameter_name_valid(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_lowercase() || c == '_' => {}
+        _ => return false,
+    }
+
+    chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '.' || c == '-')
+}
+
+impl DatabaseOptions {
+    /// The primary always counts as one instance, replicas come on top of it.
+    pub fn total_instances(&self) -> u16 {
+        1 + self.replicas as u16
+    }
+
+    pub fn deploy_timeout(&self) -> Option<Duration> {
+        self.deploy_timeout_in_seconds
+            .map(|deploy_timeout_in_seconds| Duration::from_secs(deploy_timeout_in_seconds as u64))
+    }
+
+    pub fn deletion_timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.deletion_timeout_in_seconds
+                .unwrap_or(DEFAULT_DATABASE_DELETION_TIMEOUT_IN_SECONDS) as u64,
+        )
+    }
+
+    pub fn validate_replicas(&self) -> Result<(), StringError> {
+        if self.replicas > MAX_DATABASE_REPLICAS {
+            return Err(format!(
+                "{} database replicas requested, maximum allowed is {}",
+                self.replicas, MAX_DATABASE_REPLICAS
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Self-hosted Redis runs either a single node or a master with at least 2 replicas, as sentinels need a quorum.
+    pub fn validate_redis_topology(&self) -> Result<(), StringError> {
+        if self.replicas == 1 {
+            return Err("1 Redis replica requested, use either 0 or at least 2 replicas".to_string());
+        }
+
+        if self.sentinel_enabled && self.replicas < 2 {
+            return Err(format!(
+                "Redis sentinel requires at least 2 replicas, {} requested",
+                self.replicas
+            ));
+        }
+
+        self.validate_replicas()
+    }
+
+    /// Parameters are validated by the database itself, names and values are only checked to be safely rendered
+    /// in the RDS parameter group and the self-hosted `my.cnf`.
+    pub fn validate_parameters(&self) -> Result<(), StringError> {
+        for (name, value) in &self.parameters {
+            if !is_database_parameter_name_valid(name) {
+                return Err(format!(
+                    "database parameter name `{}` is invalid, it must match ^[a-z_][a-z0-9_.-]*$",
+                    name
+                ));
+            }
+
+            if value.chars().any(|c| c == '"' || c == '\\' || c.is_control()) {
+                return Err(format!(
+                    "database parameter `{}` value can't contain quotes, backslashes or line breaks",
+                    name
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Disks can grow but never shrink, neither RDS storage nor persistent volume claims.
+    pub fn validate_disk_size(&self, deployed_disk_size_in_gib: Option<u32>) -> Result<(), StringError> {
+        match deployed_disk_size_in_gib {
+            Some(deployed_disk_size_in_gib) if self.disk_size_in_gib < deployed_disk_size_in_gib => Err(format!(
+                "disk size of {} GiB requested, it can't be smaller than the {} GiB already provisioned",
+                self.disk_size_in_gib, deployed_disk_size_in_gib
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn validate_tls(&self, is_managed_services: bool) -> Result<(), StringError> {
+        if is_managed_services && self.tls_certificates.is_some() {
+            return Err("TLS certificates of managed databases are provided by the cloud provider".to_string());
+        }
+
+        // replicas replicate from the primary without TLS
+        if !is_managed_services && self.require_tls && self.replicas > 0 {
+            return Err(format!(
+                "TLS can't be required on a self-hosted database with replicas, {} requested",
+                self.replicas
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Connection string without the password, which is given to applications separately.
+    pub fn mysql_connection_string(&self, database_name: &str) -> String {
+        let connection_string = format!("mysql://{}@{}:{}/{}", self.login, self.host, self.port, database_name);
+
+        match self.require_tls {
+            true => format!("{}?ssl-mode=REQUIRED", connection_string),
+            false => connection_string,
+        }
+    }
+
+    /// Requests are the database totals. Limits are only set when an overcommit is configured,
+    /// they are the totals times the overcommit factor.
+    pub fn resources(&self, total_cpus: &str, total_ram_in_mib: u32) -> Result<DatabaseResources, StringError> {
+        let cpu_request = match convert_k8s_cpu_value_to_f32(total_cpus.to_string()) {
+            Ok(cpu_request) => cpu_request,
+            Err(_) => return Err(format!("{} is not a valid cpu quantity", total_cpus)),
+        };
+
+        let overcommit_percent = match self.resources_overcommit_percent {
+            Some(overcommit_percent) => overcommit_percent,
+            None => {
+                return Ok(DatabaseResources {
+                    cpu_request: total_cpus.to_string(),
+                    cpu_limit: None,
+                    ram_request_mib: total_ram_in_mib,
+                    ram_limit_mib: None,
+                })
+            }
+        };
+
+        if overcommit_percent < MIN_DATABASE_OVERCOMMIT_PERCENT {
+            return Err(format!(
+                "resources overcommit of {}% would set limits lower than requests, it must be at least {}%",
+                overcommit_percent, MIN_DATABASE_OVERCOMMIT_PERCENT
+            ));
+        }
+
+        let cpu_limit = match overcommit_percent {
+            MIN_DATABASE_OVERCOMMIT_PERCENT => total_cpus.to_string(),
+            _ => format!("{}m", (cpu_request * overcommit_percent as f32 * 10.0).round() as u64),
+        };
+
+        Ok(DatabaseResources {
+            cpu_request: total_cpus.to_string(),
+            cpu_limit: Some(cpu_limit),
+            ram_request_mib: total_ram_in_mib,
+            ram_limit_mib: Some((total_ram_in_mib as u64 * overcommit_percent as u64 / 100) as u32),
+        })
+    }
+
+    pub fn validate_disk_performances(&self) -> Result<(), StringError> {
+        let disk_type = self.database_disk_type.as_str();
+
+        if self.iops.is_some() && disk_type != "io1" && disk_type != "gp3" {
+            return Err(format!(
+                "IOPS can't be provisioned with `{}` disk type, only `io1` and `gp3` disk types support it",
+                disk_type
+            ));
+        }
+
+        if self.iops.is_none() && disk_type == "io1" {
+            return Err("IOPS have to be provisioned with `io1` disk type".to_string());
+        }
+
+        if self.storage_throughput.is_some() && disk_type != "gp3" {
+            return Err(format!(
+                "storage throughput can't be provisioned with `{}` disk type, only `gp3` disk type supports it",
+                disk_type
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// https://docs.aws.amazon.com/AmazonRDS/latest/UserGuide/USER_WorkingWithAutomatedBackups.html
+    pub fn validate_backups(&self) -> Result<(), StringError> {
+        if let Some(retention_days) = self.backup_retention_days {
+            if retention_days > MAX_BACKUP_RETENTION_DAYS {
+                return Err(format!(
+                    "backups can't be kept {} days, at most {} days",
+                    retention_days, MAX_BACKUP_RETENTION_DAYS
+                ));
+            }
+
+            if retention_days == 0 && self.replicas > 0 {
+                return Err(format!(
+                    "backups can't be disabled with {} read replicas, replicas are created from backups",
+                    self.replicas
+                ));
+            }
+        }
+
+        if let Some(backup_window) = &self.backup_window {
+            let invalid_window = || {
+                format!(
+                    "backup window `{}` must be formatted `hh24:mi-hh24:mi` in UTC and last at least 30 minutes",
+                    backup_window
+                )
+            };
+            let minutes = |time: &str| -> Option<u32> {
+                let (hours, minutes) = time.split_once(':')?;
+                match (hours.len(), minutes.len(), hours.parse::<u32>(), minutes.parse::<u32>()) {
+                    (2, 2, Ok(hours), Ok(minutes)) if hours < 24 && minutes < 60 => Some(hours * 60 + minutes),
+                    _ => None,
+                }
+            };
+
+            let (start, end) = backup_window.split_once('-').ok_or_else(invalid_window)?;
+            let (start, end) = match (minutes(start), minutes(end)) {
+                (Some(start), Some(end)) => (start, end),
+                _ => return Err(invalid_window()),
+            };
+
+            // the window can span midnight
+            if (end + 24 * 60 - start) % (24 * 60) < 30 {
+                return Err(invalid_window());
+            }
+
+            let (maintenance_start, maintenance_end) = MAINTENANCE_WINDOW_IN_MINUTES;
+            let overlaps_maintenance = |start: u32, end: u32| start < maintenance_end && maintenance_start < end;
+            let overlaps_maintenance = match start < end {
+                true => overlaps_maintenance(start, end),
+                false => overlaps_maintenance(start, 24 * 60) || overlaps_maintenance(0, end),
+            };
+
+            if overlaps_maintenance {
+                return Err(format!(
+                    "backup window `{}` can't overlap the `{}` maintenance window",
+                    backup_window, MAINTENANCE_WINDOW
+                ));
+            }
+        }
+
+        Ok(())
+    }
 }
 
+/// AWS limit of the automated backups retention.
+const MAX_BACKUP_RETENTION_DAYS: u32 = 35;
+const DEFAULT_BACKUP_WINDOW: &str = "00:00-01:00";
+/// Weekly maintenance window of the managed databases, see the `maintenance_window` terraform variables.
+const MAINTENANCE_WINDOW: &str = "Tue:02:00-Tue:04:00";
+/// AWS rejects a daily backup window overlapping the maintenance window hours, whatever the day.
+const MAINTENANCE_WINDOW_IN_MINUTES: (u32, u32) = (2 * 60, 4 * 60);
+
 #[derive(Eq, PartialEq)]
 pub enum DatabaseType<'a> {
     PostgreSQL(&'a DatabaseOptions),
@@ -246,6 +746,17 @@ pub enum DatabaseType<'a> {
     Redis(&'a DatabaseOptions),
 }
 
+impl<'a> DatabaseType<'a> {
+    pub fn options(&self) -> &'a DatabaseOptions {
+        match self {
+            DatabaseType::PostgreSQL(options)
+            | DatabaseType::MongoDB(options)
+            | DatabaseType::MySQL(options)
+            | DatabaseType::Redis(options) => options,
+        }
+    }
+}
+
 #[derive(Eq, PartialEq)]
 pub enum ServiceType<'a> {
     Application,
@@ -293,146 +804,496 @@ where
     }
 }
 
-pub fn default_tera_context(
-    service: &dyn Service,
-    kubernetes: &dyn Kubernetes,
-    environment: &Environment,
-) -> TeraContext {
-    let mut context = TeraContext::new();
-
-    context.insert("id", service.id());
-    context.insert("owner_id", environment.owner_id.as_str());
-    context.insert("project_id", environment.project_id.as_str());
-    context.insert("organization_id", environment.organization_id.as_str());
-    context.insert("environment_id", environment.id.as_str());
-    context.insert("region", kubernetes.region());
-    context.insert("name", service.name());
-    context.insert("sanitized_name", &service.sanitized_name());
-    context.insert("namespace", environment.namespace());
-    context.insert("cluster_name", kubernetes.name());
-    context.insert("total_cpus", &service.total_cpus());
-    context.insert("total_ram_in_mib", &service.total_ram_in_mib());
-    context.insert("total_instances", &service.total_instances());
-
-    context.insert("is_private_port", &service.private_port().is_some());
-    if service.private_port().is_some() {
-        context.insert("private_port", &service.private_port().unwrap());
-    }
-
-    context.insert("version", service.version());
+const READINESS_RETRY_INTERVAL: Duration = Duration::from_secs(10);
 
-    context
+/// Every instance has to be scheduled and started, and bigger instances take longer to start.
+pub fn readiness_timeout(total_instances: u16, total_ram_in_mib: u32) -> Duration {
+    crate::cmd::kubectl::pod_ready_timeout(total_ram_in_mib) + Duration::from_secs(30) * total_instances as u32
 }
 
-/// deploy a stateless service created by the user (E.g: App or External Service)
-/// the difference with `deploy_service(..)` is that this function provides the thrown error in case of failure
-pub fn deploy_user_stateless_service<T>(target: &DeploymentTarget, service: &T) -> Result<(), EngineError>
+/// Span tagging the logs of a service operation with the execution and the service ids.
+pub fn service_span<T>(service: &T, operation: &str) -> Span
 where
-    T: Service + Helm,
+    T: Service + ?Sized,
 {
-    deploy_stateless_service(
-        target,
-        service,
-        service.engine_error(
-            EngineErrorCause::User(
-                "Your application has failed to start. \
-                Ensure you can run it without issues with `qovery run` and check its logs from the web interface or the CLI with `qovery log`. \
-                This issue often occurs due to ports misconfiguration. Make sure you exposed the correct port (using EXPOSE statement in Dockerfile or via Qovery configuration).",
-            ),
-            format!(
-                "{} {} has failed to start ⤬",
-                service.service_type().name(),
-                service.name_with_id()
-            ),
-        ),
+    span!(
+        Level::INFO,
+        "service",
+        operation = operation,
+        execution_id = service.context().execution_id(),
+        service_id = service.id(),
+        service_type = service.service_type().name(),
     )
 }
 
-/// deploy a stateless service (app, router, database...) on Kubernetes
-pub fn deploy_stateless_service<T>(
-    target: &DeploymentTarget,
-    service: &T,
-    thrown_error: EngineError,
-) -> Result<(), EngineError>
+/// Wait until all the service pods are running on the kubernetes cluster, or `timeout` is reached.
+pub fn wait_until_ready<T>(service: &T, target: &DeploymentTarget, timeout: Duration) -> Result<(), EngineError>
 where
-    T: Service + Helm,
+    T: Service + ?Sized,
 {
     let (kubernetes, environment) = match target {
         DeploymentTarget::ManagedServices(k, env) => (*k, *env),
         DeploymentTarget::SelfHosted(k, env) => (*k, *env),
     };
 
-    let workspace_dir = service.workspace_directory();
-    let tera_context = service.tera_context(target)?;
-
-    let _ = cast_simple_error_to_engine_error(
-        service.engine_error_scope(),
-        service.context().execution_id(),
-        crate::template::generate_and_copy_all_files_into_dir(
-            service.helm_chart_dir(),
-            workspace_dir.as_str(),
-            &tera_context,
-        ),
-    )?;
+    let expected_pods = service.total_instances();
+    if expected_pods == 0 {
+        return Ok(());
+    }
 
-    let helm_release_name = service.helm_release_name();
     let kubernetes_config_file_path = kubernetes.config_file_path()?;
+    let selector = service.readiness_selector();
+    let listeners_helper = ListenersHelper::new(kubernetes.listeners());
 
-    // define labels to add to namespace
-    let namespace_labels = service.context().resource_expiration_in_seconds().map(|_| {
-        vec![
-            (LabelsContent {
-                name: "ttl".to_string(),
-                value: format! {"{}", service.context().resource_expiration_in_seconds().unwrap()},
-            }),
-        ]
-    });
-
-    // create a namespace with labels if do not exists
-    let _ = cast_simple_error_to_engine_error(
-        service.engine_error_scope(),
-        service.context().execution_id(),
-        crate::cmd::kubectl::kubectl_exec_create_namespace(
+    wait_until_ready_with(service, &listeners_helper, timeout, READINESS_RETRY_INTERVAL, || {
+        crate::cmd::kubectl::kubectl_exec_is_pod_ready(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
-            namespace_labels,
+            service.namespace(environment),
+            selector.as_str(),
+            expected_pods,
             kubernetes.cloud_provider().credentials_environment_variables(),
-        ),
-    )?;
+        )
+    })
+}
 
-    // do exec helm upgrade and return the last deployment status
-    let helm_history_row = cast_simple_error_to_engine_error(
-        service.engine_error_scope(),
-        service.context().execution_id(),
-        crate::cmd::helm::helm_exec_with_upgrade_history(
-            kubernetes_config_file_path.as_str(),
-            environment.namespace(),
-            helm_release_name.as_str(),
-            workspace_dir.as_str(),
-            service.start_timeout(),
-            kubernetes.cloud_provider().credentials_environment_variables(),
-        ),
-    )?;
+pub fn wait_until_ready_with<T, F>(
+    service: &T,
+    listeners_helper: &ListenersHelper,
+    timeout: Duration,
+    retry_interval: Duration,
+    mut is_ready: F,
+) -> Result<(), EngineError>
+where
+    T: Service + ?Sized,
+    F: FnMut() -> Result<Option<bool>, SimpleError>,
+{
+    let started_at = Instant::now();
 
-    // check deployment status
-    if helm_history_row.is_none() || !helm_history_row.unwrap().is_successfully_deployed() {
-        return Err(thrown_error);
-    }
+    loop {
+        match is_ready() {
+            Ok(Some(true)) => return Ok(()),
+            Ok(_) => {}
+            Err(err) => {
+                return Err(service.engine_error(
+                    EngineErrorCause::Internal,
+                    format!(
+                        "can't check if {} {} is ready: {}",
+                        service.service_type().name(),
+                        service.name_with_id(),
+                        err.message.unwrap_or_else(|| "unknown error".to_string())
+                    ),
+                ))
+            }
+        };
+
+        if started_at.elapsed() + retry_interval > timeout {
+            return Err(service.engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "{} {} is not ready after {} seconds",
+                    service.service_type().name(),
+                    service.name_with_id(),
+                    timeout.as_secs()
+                ),
+            ));
+        }
+
+        let message = format!(
+            "{} {} is not ready yet, waiting for its pods with selector {}",
+            service.service_type().name(),
+            service.name_with_id(),
+            service.readiness_selector()
+        );
+        info!("{}", message);
+        listeners_helper.deployment_in_progress(ProgressInfo::new(
+            service.progress_scope(),
+            ProgressLevel::Info,
+            Some(message),
+            service.context().execution_id(),
+        ));
+
+        thread::sleep(retry_interval);
+    }
+}
+
+const DELETION_RETRY_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Waits until the provider doesn't know the managed database anymore, for a new one with the same id to not collide.
+fn wait_until_deleted<T>(target: &DeploymentTarget, service: &T) -> Result<(), EngineError>
+where
+    T: StatefulService + ?Sized,
+{
+    let kubernetes = match target {
+        DeploymentTarget::ManagedServices(kubernetes, _) => *kubernetes,
+        DeploymentTarget::SelfHosted(..) => return Ok(()),
+    };
+
+    let timeout = match service.service_type() {
+        ServiceType::Database(database_type) => database_type.options().deletion_timeout(),
+        _ => Duration::from_secs(DEFAULT_DATABASE_DELETION_TIMEOUT_IN_SECONDS as u64),
+    };
+    let listeners_helper = ListenersHelper::new(kubernetes.listeners());
+
+    // managed databases only have a deployed version as long as the provider knows them
+    wait_until_deleted_with(service, &listeners_helper, timeout, DELETION_RETRY_INTERVAL, || {
+        service.deployed_version(target).map(|version| version.is_none())
+    })
+}
+
+pub fn wait_until_deleted_with<T, F>(
+    service: &T,
+    listeners_helper: &ListenersHelper,
+    timeout: Duration,
+    retry_interval: Duration,
+    mut is_deleted: F,
+) -> Result<(), EngineError>
+where
+    T: Service + ?Sized,
+    F: FnMut() -> Result<bool, EngineError>,
+{
+    let started_at = Instant::now();
+
+    loop {
+        if is_deleted()? {
+            return Ok(());
+        }
+
+        if started_at.elapsed() + retry_interval > timeout {
+            return Err(service.engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "{} {} still exists {} seconds after its deletion",
+                    service.service_type().name(),
+                    service.name_with_id(),
+                    timeout.as_secs()
+                ),
+            ));
+        }
+
+        let message = format!(
+            "{} {} is still being deleted by the cloud provider, waiting for it to be gone",
+            service.service_type().name(),
+            service.name_with_id()
+        );
+        info!("{}", message);
+        listeners_helper.delete_in_progress(ProgressInfo::new(
+            service.progress_scope(),
+            ProgressLevel::Info,
+            Some(message),
+            service.context().execution_id(),
+        ));
+
+        thread::sleep(retry_interval);
+    }
+}
+
+pub fn validate_templates<T>(service: &T, target: &DeploymentTarget) -> Result<(), EngineError>
+where
+    T: Service + ?Sized,
+{
+    let template_directories = service.template_directories(target);
+    if template_directories.is_empty() {
+        return Ok(());
+    }
+
+    let context = service.tera_context(target)?;
+    let validation_dir = format!("{}/templates-validation", service.workspace_directory());
+
+    let result = crate::template::validate_templates(&template_directories, validation_dir.as_str(), &context);
+    let _ = std::fs::remove_dir_all(validation_dir.as_str());
+
+    result.map_err(|errors| {
+        service.engine_error(
+            EngineErrorCause::Internal,
+            format!(
+                "{} template(s) of {} {} can't be rendered:\n{}",
+                errors.len(),
+                service.service_type().name(),
+                service.name_with_id(),
+                errors.join("\n")
+            ),
+        )
+    })
+}
+
+/// Each deployment works in its own directory, so overlapping runs for the same service don't corrupt each other's
+/// terraform files. Terraform states are stored in secrets named after the service id (see `get_tfstate_name`), not
+/// after this directory.
+pub fn run_workspace_directory<T>(service: &T) -> Result<String, EngineError>
+where
+    T: Service + ?Sized,
+{
+    crate::fs::run_workspace_directory(service.workspace_directory()).map_err(|err| {
+        service.engine_error(
+            EngineErrorCause::Internal,
+            format!(
+                "can't create workspace directory for {} {}: {}",
+                service.service_type().name(),
+                service.name_with_id(),
+                err
+            ),
+        )
+    })
+}
+
+pub fn cleanup_stale_run_workspace_directories<T>(service: &T, current_run_dir: &str)
+where
+    T: Service + ?Sized,
+{
+    if let Err(err) = crate::fs::cleanup_stale_run_directories(service.workspace_directory(), current_run_dir) {
+        warn!(
+            "can't cleanup stale workspace directories of {}: {}",
+            service.name_with_id(),
+            err
+        );
+    }
+}
+
+pub fn stateful_service_template_directories<T>(service: &T, target: &DeploymentTarget) -> Vec<String>
+where
+    T: Service + Helm + Terraform,
+{
+    match target {
+        DeploymentTarget::ManagedServices(_, _) => vec![
+            service.terraform_common_resource_dir_path(),
+            service.terraform_resource_dir_path(),
+            service.helm_chart_external_name_service_dir(),
+        ],
+        DeploymentTarget::SelfHosted(_, _) => vec![service.helm_chart_dir(), service.helm_chart_values_dir()],
+    }
+}
+
+pub fn default_tera_context(
+    service: &dyn Service,
+    kubernetes: &dyn Kubernetes,
+    environment: &Environment,
+) -> TeraContext {
+    let mut context = TeraContext::new();
+
+    context.insert("id", service.id());
+    context.insert("owner_id", environment.owner_id.as_str());
+    context.insert("project_id", environment.project_id.as_str());
+    context.insert("organization_id", environment.organization_id.as_str());
+    context.insert("environment_id", environment.id.as_str());
+    context.insert("region", kubernetes.region());
+    context.insert("name", service.name());
+    context.insert("sanitized_name", &service.sanitized_name());
+    context.insert("namespace", service.namespace(environment));
+    context.insert("cluster_name", kubernetes.name());
+    context.insert("total_cpus", &service.total_cpus());
+    context.insert("total_ram_in_mib", &service.total_ram_in_mib());
+    context.insert("total_instances", &service.total_instances());
+
+    context.insert("is_private_port", &service.private_port().is_some());
+    if service.private_port().is_some() {
+        context.insert("private_port", &service.private_port().unwrap());
+    }
+
+    context.insert("version", service.version());
+
+    context
+}
+
+/// deploy a stateless service created by the user (E.g: App or External Service)
+/// the difference with `deploy_service(..)` is that this function provides the thrown error in case of failure
+pub fn deploy_user_stateless_service<T>(target: &DeploymentTarget, service: &T) -> Result<(), EngineError>
+where
+    T: Service + Helm,
+{
+    deploy_stateless_service(
+        target,
+        service,
+        service.engine_error(
+            EngineErrorCause::User(
+                "Your application has failed to start. \
+                Ensure you can run it without issues with `qovery run` and check its logs from the web interface or the CLI with `qovery log`. \
+                This issue often occurs due to ports misconfiguration. Make sure you exposed the correct port (using EXPOSE statement in Dockerfile or via Qovery configuration).",
+            ),
+            format!(
+                "{} {} has failed to start ⤬",
+                service.service_type().name(),
+                service.name_with_id()
+            ),
+        ),
+    )
+}
+
+/// Rewrites the image registries of the rendered chart values, see `Context::rewrite_image`.
+fn rewrite_chart_values_images<T>(service: &T, chart_dir: &str) -> Result<(), EngineError>
+where
+    T: Service + ?Sized,
+{
+    cast_simple_error_to_engine_error(
+        service.engine_error_scope(),
+        service.context().execution_id(),
+        crate::cloud_provider::helm::rewrite_values_files_images(
+            service.context().image_registry_rewrites(),
+            &[format!("{}/values.yaml", chart_dir)],
+        ),
+    )
+}
+
+/// deploy a stateless service (app, router, database...) on Kubernetes
+pub fn deploy_stateless_service<T>(
+    target: &DeploymentTarget,
+    service: &T,
+    thrown_error: EngineError,
+) -> Result<(), EngineError>
+where
+    T: Service + Helm,
+{
+    let (kubernetes, environment) = match target {
+        DeploymentTarget::ManagedServices(k, env) => (*k, *env),
+        DeploymentTarget::SelfHosted(k, env) => (*k, *env),
+    };
+
+    let helm_value_overrides = checked_helm_value_overrides(service)?;
+    let workspace_dir = run_workspace_directory(service)?;
+    let tera_context = service.tera_context(target)?;
+
+    let _ = cast_simple_error_to_engine_error(
+        service.engine_error_scope(),
+        service.context().execution_id(),
+        crate::template::generate_and_copy_all_files_into_dir(
+            service.helm_chart_dir(),
+            workspace_dir.as_str(),
+            &tera_context,
+        ),
+    )?;
+
+    rewrite_chart_values_images(service, workspace_dir.as_str())?;
 
+    let helm_release_name = service.helm_release_name();
+    let kubernetes_config_file_path = kubernetes.config_file_path()?;
+
+    // define labels to add to namespace
+    let namespace_labels = resource_expiration_labels(service.context());
+
+    // create a namespace with labels if do not exists
     let _ = cast_simple_error_to_engine_error(
         service.engine_error_scope(),
         service.context().execution_id(),
-        crate::cmd::kubectl::kubectl_exec_is_pod_ready_with_retry(
+        crate::cmd::kubectl::kubectl_exec_create_namespace(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
-            service.selector().as_str(),
+            service.namespace(environment),
+            namespace_labels,
+            kubernetes.cloud_provider().credentials_environment_variables(),
+        ),
+    )?;
+
+    // do exec helm upgrade and return the last deployment status
+    let helm_history_row = cast_simple_error_to_engine_error(
+        service.engine_error_scope(),
+        service.context().execution_id(),
+        crate::cmd::helm::helm_exec_with_upgrade_history(
+            kubernetes_config_file_path.as_str(),
+            service.namespace(environment),
+            helm_release_name.as_str(),
+            workspace_dir.as_str(),
+            service.start_timeout(),
+            &helm_value_overrides,
             kubernetes.cloud_provider().credentials_environment_variables(),
         ),
     )?;
 
+    // check deployment status
+    if let Some(details) =
+        helm_deployment_failure(kubernetes, service.namespace(environment), helm_history_row.as_ref())
+    {
+        return Err(EngineError {
+            message: Some(format!(
+                "{}: {}",
+                thrown_error.message.clone().unwrap_or_default(),
+                details
+            )),
+            ..thrown_error
+        });
+    }
+
+    service.wait_until_ready(target, service.readiness_timeout())?;
+
+    cleanup_stale_run_workspace_directories(service, workspace_dir.as_str());
+
     Ok(())
 }
 
+const HELM_FAILURE_EVENTS_COUNT: usize = 5;
+
+/// Why the release isn't deployed, `None` if it is. The last warning events of the namespace are added as
+/// the helm status alone doesn't tell whether it timed out because of an image pull error, a crash loop, etc.
+fn helm_deployment_failure(
+    kubernetes: &dyn Kubernetes,
+    namespace: &str,
+    helm_history_row: Option<&HelmHistoryRow>,
+) -> Option<String> {
+    if helm_history_row.map_or(false, |row| row.is_successfully_deployed()) {
+        return None;
+    }
+
+    // events are only there to help, the deployment error is returned even if they can't be fetched
+    let events = kubernetes
+        .config_file_path()
+        .ok()
+        .and_then(|kubernetes_config_file_path| {
+            crate::cmd::kubectl::kubectl_exec_get_json_events(
+                kubernetes_config_file_path.as_str(),
+                namespace,
+                kubernetes.cloud_provider().credentials_environment_variables(),
+            )
+            .ok()
+        })
+        .map(|events| events.items)
+        .unwrap_or_default();
+
+    Some(helm_deployment_failure_details(helm_history_row, events))
+}
+
+fn helm_deployment_failure_details(helm_history_row: Option<&HelmHistoryRow>, events: Vec<KubernetesEvent>) -> String {
+    let failure_reason = match helm_history_row.and_then(|row| row.failure_reason()) {
+        Some(failure_reason) => format!("helm release is {}", failure_reason),
+        None => "helm release has no history".to_string(),
+    };
+
+    let mut warning_events = events
+        .into_iter()
+        .filter(|event| event.type_ == "Warning")
+        .collect::<Vec<_>>();
+    warning_events.sort_by(|a, b| a.last_timestamp.cmp(&b.last_timestamp));
+    let last_warning_events = warning_events
+        .iter()
+        .skip(warning_events.len().saturating_sub(HELM_FAILURE_EVENTS_COUNT))
+        .map(|event| {
+            format!(
+                "{} {}: {}",
+                event.involved_object.kind,
+                event.involved_object.name,
+                event.message.as_deref().unwrap_or(event.reason.as_str())
+            )
+        })
+        .collect::<Vec<_>>();
+
+    match last_warning_events.is_empty() {
+        true => failure_reason,
+        false => format!(
+            "{}. Last warning events: {}",
+            failure_reason,
+            last_warning_events.join(", ")
+        ),
+    }
+}
+
+fn checked_helm_value_overrides<T>(service: &T) -> Result<BTreeMap<String, String>, EngineError>
+where
+    T: Service + Helm,
+{
+    let helm_value_overrides = service.helm_value_overrides();
+
+    match crate::cmd::helm::validate_value_overrides(&helm_value_overrides) {
+        Ok(_) => Ok(helm_value_overrides),
+        Err(e) => Err(service.engine_error(EngineErrorCause::User("Fix the keys of the Helm value overrides"), e)),
+    }
+}
+
 /// do specific operations on a stateless service deployment error
 pub fn deploy_stateless_service_error<T>(target: &DeploymentTarget, service: &T) -> Result<(), EngineError>
 where
@@ -451,7 +1312,7 @@ where
         service.context().execution_id(),
         crate::cmd::helm::helm_exec_history(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
+            service.namespace(environment),
             helm_release_name.as_str(),
             &kubernetes.cloud_provider().credentials_environment_variables(),
         ),
@@ -463,7 +1324,7 @@ where
             service.context().execution_id(),
             crate::cmd::helm::helm_exec_uninstall(
                 kubernetes_config_file_path.as_str(),
-                environment.namespace(),
+                service.namespace(environment),
                 helm_release_name.as_str(),
                 kubernetes.cloud_provider().credentials_environment_variables(),
             ),
@@ -489,7 +1350,7 @@ pub fn scale_down_database(
     let scaledown_ret = kubectl_exec_scale_replicas_by_selector(
         kubernetes.config_file_path()?,
         kubernetes.cloud_provider().credentials_environment_variables(),
-        environment.namespace(),
+        service.namespace(environment),
         Statefulset,
         format!("databaseId={}", service.id()).as_str(),
         replicas_count as u32,
@@ -502,45 +1363,170 @@ pub fn scale_down_database(
     )
 }
 
-pub fn scale_down_application(
+/// Deployment annotation keeping the instances an application had before being paused.
+const INSTANCES_BEFORE_PAUSE_ANNOTATION: &str = "qovery.com/instances-before-pause";
+
+/// Scales the application down to zero, its current instances are recorded to resume it as it was.
+pub fn pause_application(
     target: &DeploymentTarget,
     service: &impl StatelessService,
-    replicas_count: usize,
     scaling_kind: ScalingKind,
 ) -> Result<(), EngineError> {
     let (kubernetes, environment) = match target {
         DeploymentTarget::ManagedServices(_, _) => {
-            return Err(EngineError {
-                cause: EngineErrorCause::Internal,
-                scope: EngineErrorScope::Engine,
-                execution_id: service.context().execution_id().to_string(),
-                message: Some(format!("Cannot scale down managed service: {}", service.name_with_id())),
-            })
+            return Err(EngineError::new(
+                EngineErrorCause::Internal,
+                EngineErrorScope::Engine,
+                service.context().execution_id(),
+                Some(format!("Cannot scale down managed service: {}", service.name_with_id())),
+            ))
         }
         DeploymentTarget::SelfHosted(k, env) => (*k, *env),
     };
-    let scaledown_ret = kubectl_exec_scale_replicas_by_selector(
-        kubernetes.config_file_path()?,
-        kubernetes.cloud_provider().credentials_environment_variables(),
-        environment.namespace(),
-        scaling_kind,
-        format!("appId={}", service.id()).as_str(),
-        replicas_count as u32,
-    );
 
     cast_simple_error_to_engine_error(
         service.engine_error_scope(),
         service.context().execution_id(),
-        scaledown_ret,
+        pause_workloads(
+            kubernetes.config_file_path()?,
+            kubernetes.cloud_provider().credentials_environment_variables(),
+            service.namespace(environment),
+            scaling_kind,
+            format!("appId={}", service.id()).as_str(),
+        ),
     )
 }
 
-pub fn delete_router<T>(target: &DeploymentTarget, service: &T, is_error: bool) -> Result<(), EngineError>
-where
-    T: Router,
-{
-    send_progress_on_long_task(service, crate::cloud_provider::service::Action::Delete, || {
-        delete_stateless_service(target, service, is_error)
+/// Scales a paused application back to its instances before the pause, `default_instances` when unknown.
+pub fn resume_application(
+    target: &DeploymentTarget,
+    service: &impl StatelessService,
+    scaling_kind: ScalingKind,
+    default_instances: u32,
+) -> Result<(), EngineError> {
+    let (kubernetes, environment) = match target {
+        DeploymentTarget::ManagedServices(_, _) => {
+            return Err(EngineError::new(
+                EngineErrorCause::Internal,
+                EngineErrorScope::Engine,
+                service.context().execution_id(),
+                Some(format!("Cannot scale up managed service: {}", service.name_with_id())),
+            ))
+        }
+        DeploymentTarget::SelfHosted(k, env) => (*k, *env),
+    };
+
+    let instances = cast_simple_error_to_engine_error(
+        service.engine_error_scope(),
+        service.context().execution_id(),
+        resume_workloads(
+            kubernetes.config_file_path()?,
+            kubernetes.cloud_provider().credentials_environment_variables(),
+            service.namespace(environment),
+            scaling_kind,
+            format!("appId={}", service.id()).as_str(),
+            default_instances,
+        ),
+    )?;
+
+    info!(
+        "{} has been resumed with {} instances",
+        service.name_with_id(),
+        instances
+    );
+    Ok(())
+}
+
+/// Instances to record before a pause, `None` for a workload already paused so its recorded instances are kept.
+fn instances_before_pause(workload: &Workload) -> Option<u32> {
+    match workload.spec.replicas.unwrap_or(1) {
+        0 => None,
+        replicas => Some(replicas),
+    }
+}
+
+fn resumed_instances(workload: Option<&Workload>, default_instances: u32) -> u32 {
+    workload
+        .and_then(|workload| workload.metadata.annotations.get(INSTANCES_BEFORE_PAUSE_ANNOTATION))
+        .and_then(|instances| instances.parse::<u32>().ok())
+        .unwrap_or(default_instances)
+}
+
+fn pause_workloads(
+    kubernetes_config: String,
+    envs: Vec<(&str, &str)>,
+    namespace: &str,
+    scaling_kind: ScalingKind,
+    selector: &str,
+) -> Result<(), SimpleError> {
+    let workloads = kubectl_exec_get_workloads_by_selector(
+        kubernetes_config.as_str(),
+        namespace,
+        scaling_kind,
+        selector,
+        envs.clone(),
+    )?;
+
+    if let Some(instances) = workloads.items.first().and_then(instances_before_pause) {
+        kubectl_exec_annotate_workloads_by_selector(
+            kubernetes_config.as_str(),
+            namespace,
+            scaling_kind,
+            selector,
+            INSTANCES_BEFORE_PAUSE_ANNOTATION,
+            Some(instances.to_string().as_str()),
+            envs.clone(),
+        )?;
+    }
+
+    kubectl_exec_scale_replicas_by_selector(kubernetes_config.as_str(), envs, namespace, scaling_kind, selector, 0)
+}
+
+fn resume_workloads(
+    kubernetes_config: String,
+    envs: Vec<(&str, &str)>,
+    namespace: &str,
+    scaling_kind: ScalingKind,
+    selector: &str,
+    default_instances: u32,
+) -> Result<u32, SimpleError> {
+    let workloads = kubectl_exec_get_workloads_by_selector(
+        kubernetes_config.as_str(),
+        namespace,
+        scaling_kind,
+        selector,
+        envs.clone(),
+    )?;
+    let instances = resumed_instances(workloads.items.first(), default_instances);
+
+    kubectl_exec_scale_replicas_by_selector(
+        kubernetes_config.as_str(),
+        envs.clone(),
+        namespace,
+        scaling_kind,
+        selector,
+        instances,
+    )?;
+
+    kubectl_exec_annotate_workloads_by_selector(
+        kubernetes_config.as_str(),
+        namespace,
+        scaling_kind,
+        selector,
+        INSTANCES_BEFORE_PAUSE_ANNOTATION,
+        None,
+        envs,
+    )?;
+
+    Ok(instances)
+}
+
+pub fn delete_router<T>(target: &DeploymentTarget, service: &T, is_error: bool) -> Result<(), EngineError>
+where
+    T: Router,
+{
+    send_progress_on_long_task(service, crate::cloud_provider::service::Action::Delete, || {
+        delete_stateless_service(target, service, is_error)
     })
 }
 
@@ -556,23 +1542,49 @@ where
     let helm_release_name = service.helm_release_name();
 
     if is_error {
-        let _ = get_stateless_resource_information(kubernetes, environment, service.selector().as_str())?;
+        let _ = get_stateless_resource_information(
+            kubernetes,
+            service.namespace(environment),
+            service.selector().as_str(),
+        )?;
     }
 
     // clean the resource
-    let _ = helm_uninstall_release(kubernetes, environment, helm_release_name.as_str())?;
+    let _ = helm_uninstall_release(kubernetes, service.namespace(environment), helm_release_name.as_str())?;
 
     Ok(())
 }
 
 pub fn deploy_stateful_service<T>(target: &DeploymentTarget, service: &T) -> Result<(), EngineError>
 where
-    T: StatefulService + Helm + Terraform,
+    T: StatefulService + Helm + Terraform + Listen,
+{
+    deploy_stateful_service_with_outputs(target, service).map(|_| ())
+}
+
+/// Deploys like `deploy_stateful_service`, the terraform outputs are returned when managed services are applied.
+pub fn deploy_stateful_service_with_outputs<T>(
+    target: &DeploymentTarget,
+    service: &T,
+) -> Result<TerraformApplyResult, EngineError>
+where
+    T: StatefulService + Helm + Terraform + Listen,
 {
-    let workspace_dir = service.workspace_directory();
+    let mut apply_result = TerraformApplyResult::default();
+
+    // a shrink would only fail deep in the terraform apply or the helm upgrade
+    check_database_disk_size(target, service)?;
+
+    // a database which can't fit on the cluster would stay pending until the deploy times out
+    check_database_fits_cluster(target, service)?;
+
+    // make sure all templates can be rendered before deploying anything
+    service.validate_templates(target)?;
+
+    let workspace_dir = run_workspace_directory(service)?;
 
     match target {
-        DeploymentTarget::ManagedServices(kubernetes, _) => {
+        DeploymentTarget::ManagedServices(kubernetes, environment) => {
             // use terraform
             info!(
                 "deploy {} with name {} on {}",
@@ -582,6 +1594,63 @@ where
             );
 
             let context = service.tera_context(target)?;
+            let context_hash = hash_tera_context(&context);
+
+            if service.context().is_dry_run_deploy() {
+                let cost_estimate = pricing_table().estimate_tera_context(
+                    &kubernetes.cloud_provider().kind(),
+                    kubernetes.region(),
+                    &context,
+                    true,
+                );
+
+                ListenersHelper::new(service.listeners()).deployment_in_progress(ProgressInfo::new(
+                    service.progress_scope(),
+                    Info,
+                    Some(format!(
+                        "Estimated cost of {} {}: ~{:.2} USD per month",
+                        service.service_type().name().to_lowercase(),
+                        service.name(),
+                        cost_estimate.total_monthly_usd()
+                    )),
+                    service.context().execution_id(),
+                ));
+            }
+            let kubernetes_config_file_path = kubernetes.config_file_path()?;
+            // fetched once, it doesn't exist until the first apply
+            let applied_tfstate_secret =
+                applied_tfstate_secret(service, *kubernetes, environment, kubernetes_config_file_path.as_str());
+
+            if service.context().is_dry_run_deploy() {
+                if let Some(applied_context) = applied_tfstate_secret.as_ref().and_then(applied_tera_context) {
+                    let changes = tera_context_diff(&applied_context, &comparable_tera_context(&context));
+                    let message = match changes.is_empty() {
+                        true => format!(
+                            "No configuration changes for {} {}",
+                            service.service_type().name().to_lowercase(),
+                            service.name()
+                        ),
+                        false => format!(
+                            "Configuration changes of {} {}:\n  {}",
+                            service.service_type().name().to_lowercase(),
+                            service.name(),
+                            changes.join("\n  ")
+                        ),
+                    };
+
+                    ListenersHelper::new(service.listeners()).deployment_in_progress(ProgressInfo::new(
+                        service.progress_scope(),
+                        Info,
+                        Some(message),
+                        service.context().execution_id(),
+                    ));
+                }
+            }
+
+            let is_unchanged = applied_tfstate_secret
+                .as_ref()
+                .and_then(|secret| secret.metadata.annotations.get(TERA_CONTEXT_HASH_ANNOTATION))
+                == Some(&context_hash);
 
             let _ = cast_simple_error_to_engine_error(
                 service.engine_error_scope(),
@@ -613,14 +1682,80 @@ where
                 ),
             )?;
 
-            let _ = cast_simple_error_to_engine_error(
+            // the resources may have been deleted out of terraform since the last deployment
+            if is_unchanged
+                && is_external_name_service_present(
+                    service,
+                    *kubernetes,
+                    environment,
+                    kubernetes_config_file_path.as_str(),
+                )
+            {
+                info!(
+                    "{} {} is unchanged since its last deployment, skipping terraform apply",
+                    service.service_type().name(),
+                    service.name_with_id()
+                );
+
+                ListenersHelper::new(service.listeners()).deployment_in_progress(ProgressInfo::new(
+                    service.progress_scope(),
+                    Info,
+                    Some(format!(
+                        "No changes for {} {}, nothing to deploy",
+                        service.service_type().name().to_lowercase(),
+                        service.name()
+                    )),
+                    service.context().execution_id(),
+                ));
+
+                // outputs are read from the stored state, as if it had been applied again
+                apply_result = match crate::cmd::terraform::terraform_init_output(
+                    workspace_dir.as_str(),
+                    service.context().cancellation_token(),
+                ) {
+                    Ok(apply_result) => apply_result,
+                    Err(e) => {
+                        warn!(
+                            "error while trying to read terraform outputs of unchanged {}: {:?}",
+                            service.name_with_id(),
+                            e.message
+                        );
+                        TerraformApplyResult::default()
+                    }
+                };
+
+                cleanup_stale_run_workspace_directories(service, workspace_dir.as_str());
+                return Ok(apply_result);
+            }
+
+            apply_result = cast_simple_error_to_engine_error(
                 service.engine_error_scope(),
                 service.context().execution_id(),
-                crate::cmd::terraform::terraform_init_validate_plan_apply(
-                    workspace_dir.as_str(),
-                    service.context().is_dry_run_deploy(),
+                telemetry::record(
+                    service.context(),
+                    telemetry::Operation::TerraformApply,
+                    service.service_type().name(),
+                    kubernetes.cloud_provider().kind().name(),
+                    || {
+                        crate::cmd::terraform::terraform_init_validate_plan_apply(
+                            workspace_dir.as_str(),
+                            service.context().is_dry_run_deploy(),
+                            service.context().cancellation_token(),
+                        )
+                    },
                 ),
             )?;
+
+            if !service.context().is_dry_run_deploy() {
+                store_applied_tera_context(
+                    service,
+                    *kubernetes,
+                    environment,
+                    kubernetes_config_file_path.as_str(),
+                    &context,
+                    context_hash.as_str(),
+                );
+            }
         }
         DeploymentTarget::SelfHosted(kubernetes, environment) => {
             // use helm
@@ -632,6 +1767,7 @@ where
                 kubernetes.id()
             );
 
+            let helm_value_overrides = checked_helm_value_overrides(service)?;
             let context = service.tera_context(target)?;
             let kubernetes_config_file_path = kubernetes.config_file_path()?;
 
@@ -657,15 +1793,10 @@ where
                 ),
             )?;
 
+            rewrite_chart_values_images(service, workspace_dir.as_str())?;
+
             // define labels to add to namespace
-            let namespace_labels = service.context().resource_expiration_in_seconds().map(|_| {
-                vec![
-                    (LabelsContent {
-                        name: "ttl".into(),
-                        value: format!("{}", service.context().resource_expiration_in_seconds().unwrap()),
-                    }),
-                ]
-            });
+            let namespace_labels = resource_expiration_labels(service.context());
 
             // create a namespace with labels if it does not exist
             let _ = cast_simple_error_to_engine_error(
@@ -673,7 +1804,7 @@ where
                 service.context().execution_id(),
                 crate::cmd::kubectl::kubectl_exec_create_namespace(
                     kubernetes_config_file_path.as_str(),
-                    environment.namespace(),
+                    service.namespace(environment),
                     namespace_labels,
                     kubernetes.cloud_provider().credentials_environment_variables(),
                 ),
@@ -685,45 +1816,292 @@ where
                 service.context().execution_id(),
                 crate::cmd::helm::helm_exec_with_upgrade_history(
                     kubernetes_config_file_path.as_str(),
-                    environment.namespace(),
+                    service.namespace(environment),
                     service.helm_release_name().as_str(),
                     workspace_dir.as_str(),
                     service.start_timeout(),
+                    &helm_value_overrides,
                     kubernetes.cloud_provider().credentials_environment_variables(),
                 ),
             )?;
 
             // check deployment status
-            if helm_history_row.is_none() || !helm_history_row.unwrap().is_successfully_deployed() {
+            if let Some(details) =
+                helm_deployment_failure(*kubernetes, service.namespace(environment), helm_history_row.as_ref())
+            {
                 return Err(service.engine_error(
                     EngineErrorCause::Internal,
                     format!(
-                        "{} service fails to be deployed (before start)",
-                        service.service_type().name()
+                        "{} service fails to be deployed (before start): {}",
+                        service.service_type().name(),
+                        details
                     ),
                 ));
             }
 
             // check app status
-            match crate::cmd::kubectl::kubectl_exec_is_pod_ready_with_retry(
-                kubernetes_config_file_path.as_str(),
-                environment.namespace(),
-                service.selector().as_str(),
-                kubernetes.cloud_provider().credentials_environment_variables(),
-            ) {
-                Ok(Some(true)) => {}
-                _ => {
-                    return Err(service.engine_error(
-                        EngineErrorCause::Internal,
-                        format!(
-                            "{} database {} failed to start after several retries",
-                            service.service_type().name(),
-                            service.name_with_id()
-                        ),
-                    ));
+            service.wait_until_ready(target, service.readiness_timeout())?;
+        }
+    }
+
+    cleanup_stale_run_workspace_directories(service, workspace_dir.as_str());
+
+    Ok(apply_result)
+}
+
+/// Annotation of the tfstate secret holding the hash of the last applied tera context.
+const TERA_CONTEXT_HASH_ANNOTATION: &str = "qovery.com/tera-context-hash";
+
+/// Annotation of the tfstate secret holding the last applied tera context, redacted, to diff it on dry runs.
+const APPLIED_TERA_CONTEXT_ANNOTATION: &str = "qovery.com/applied-tera-context";
+
+/// Keys changing from one run to another without any impact on the deployed resources.
+const TERA_CONTEXT_HASH_IGNORED_KEYS: [&str; 1] = ["kubeconfig_path"];
+
+pub fn hash_tera_context(context: &TeraContext) -> String {
+    let mut json = context.clone().into_json();
+
+    if let Some(values) = json.as_object_mut() {
+        for key in TERA_CONTEXT_HASH_IGNORED_KEYS.iter() {
+            values.remove(*key);
+        }
+    }
+
+    // keys are sorted, the serialization is the same for identical contexts
+    crate::crypto::to_sha1(json.to_string().as_str())
+}
+
+/// Keys holding credentials are matched by pattern, so new credentials are redacted without being listed here:
+/// a key containing one of the words, or ending with one of the suffixes, is never shown in tera context dumps.
+const TERA_CONTEXT_REDACTED_KEY_WORDS: [&str; 4] = ["password", "secret", "token", "credentials"];
+const TERA_CONTEXT_REDACTED_KEY_SUFFIXES: [&str; 2] = ["_key", "_config"];
+
+const TERA_CONTEXT_REDACTED_VALUE: &str = "<redacted>";
+
+fn is_redacted_tera_context_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+
+    TERA_CONTEXT_REDACTED_KEY_WORDS.iter().any(|word| key.contains(word))
+        || TERA_CONTEXT_REDACTED_KEY_SUFFIXES
+            .iter()
+            .any(|suffix| key.ends_with(suffix))
+}
+
+fn redact_tera_context_value(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(values) => {
+            for (key, value) in values.iter_mut() {
+                match is_redacted_tera_context_key(key) {
+                    true => *value = serde_json::Value::String(TERA_CONTEXT_REDACTED_VALUE.to_string()),
+                    false => redact_tera_context_value(value),
                 }
             }
         }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(redact_tera_context_value),
+        _ => {}
+    }
+}
+
+pub fn redacted_tera_context(context: &TeraContext) -> HashMap<String, serde_json::Value> {
+    let mut json = context.clone().into_json();
+    redact_tera_context_value(&mut json);
+
+    match json {
+        serde_json::Value::Object(values) => values.into_iter().collect(),
+        _ => HashMap::new(),
+    }
+}
+
+/// Sends the redacted tera context of `service` as a debug deployment progress event.
+pub fn send_tera_context_dump<T>(service: &T, target: &DeploymentTarget) -> Result<(), EngineError>
+where
+    T: Service + Listen,
+{
+    // keys are sorted, to ease comparisons between two dumps
+    let dump: BTreeMap<String, serde_json::Value> = service.dump_tera_context(target)?.into_iter().collect();
+    let dump = serde_json::to_string_pretty(&dump).unwrap_or_default();
+
+    debug!("tera context of {}: {}", service.name_with_id(), dump);
+
+    ListenersHelper::new(service.listeners()).deployment_in_progress(ProgressInfo::new(
+        service.progress_scope(),
+        ProgressLevel::Debug,
+        Some(format!("tera context of {}:\n{}", service.name_with_id(), dump)),
+        service.context().execution_id(),
+    ));
+
+    Ok(())
+}
+
+/// Redacted tera context as compared between two deployments, without the keys changing on every run.
+fn comparable_tera_context(context: &TeraContext) -> BTreeMap<String, serde_json::Value> {
+    let mut values: BTreeMap<String, serde_json::Value> = redacted_tera_context(context).into_iter().collect();
+    for key in TERA_CONTEXT_HASH_IGNORED_KEYS.iter() {
+        values.remove(*key);
+    }
+
+    values
+}
+
+/// Changes from the applied to the desired tera context, one `key: old -> new` line per changed key, sorted by key.
+/// Redacted values are the same on both sides, so credentials changes are never shown.
+pub fn tera_context_diff(
+    applied: &BTreeMap<String, serde_json::Value>,
+    desired: &BTreeMap<String, serde_json::Value>,
+) -> Vec<String> {
+    let display = |value: Option<&serde_json::Value>| match value {
+        None => "(none)".to_string(),
+        Some(serde_json::Value::String(value)) => value.clone(),
+        Some(value) => value.to_string(),
+    };
+
+    let mut keys: Vec<&String> = applied.keys().chain(desired.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    keys.into_iter()
+        .filter(|key| applied.get(*key) != desired.get(*key))
+        .map(|key| {
+            format!(
+                "{}: {} -> {}",
+                key,
+                display(applied.get(key)),
+                display(desired.get(key))
+            )
+        })
+        .collect()
+}
+
+/// Configuration changes a deployment of `service` on managed services would apply, as a `tera_context_diff`.
+/// `None` when it has never been applied or is self-hosted, helm releases keep their own values.
+pub fn tera_context_plan_diff<T>(target: &DeploymentTarget, service: &T) -> Result<Option<Vec<String>>, EngineError>
+where
+    T: Service + ?Sized,
+{
+    let (kubernetes, environment) = match target {
+        DeploymentTarget::ManagedServices(k, env) => (*k, *env),
+        DeploymentTarget::SelfHosted(..) => return Ok(None),
+    };
+
+    let desired = comparable_tera_context(&service.tera_context(target)?);
+    let applied = applied_tfstate_secret(
+        service,
+        kubernetes,
+        environment,
+        kubernetes.config_file_path()?.as_str(),
+    )
+    .as_ref()
+    .and_then(applied_tera_context);
+
+    Ok(applied.map(|applied| tera_context_diff(&applied, &desired)))
+}
+
+/// Tfstate secret of the last applied service, none until the service has been applied once.
+fn applied_tfstate_secret<T>(
+    service: &T,
+    kubernetes: &dyn Kubernetes,
+    environment: &Environment,
+    kubernetes_config_file_path: &str,
+) -> Option<Secret>
+where
+    T: Service + ?Sized,
+{
+    if !has_tfstate_secret(kubernetes) {
+        return None;
+    }
+
+    crate::cmd::kubectl::kubectl_exec_get_secret(
+        kubernetes_config_file_path,
+        service.namespace(environment),
+        get_tfstate_name(service).as_str(),
+        kubernetes.cloud_provider().credentials_environment_variables(),
+    )
+    .ok()
+}
+
+/// The external name service is deployed by the terraform managed helm release, it is gone with the release.
+fn is_external_name_service_present<T>(
+    service: &T,
+    kubernetes: &dyn Kubernetes,
+    environment: &Environment,
+    kubernetes_config_file_path: &str,
+) -> bool
+where
+    T: Service + ?Sized,
+{
+    crate::cmd::kubectl::kubectl_exec_is_service_present(
+        kubernetes_config_file_path,
+        service.namespace(environment),
+        external_name_service_name(service.id()).as_str(),
+        kubernetes.cloud_provider().credentials_environment_variables(),
+    )
+}
+
+/// Name of the service created by the `external-name-svc` chart from its `app_id` value.
+fn external_name_service_name(database_id: &str) -> String {
+    format!("{}-dns", database_id)
+}
+
+fn applied_tera_context(applied_tfstate_secret: &Secret) -> Option<BTreeMap<String, serde_json::Value>> {
+    applied_tfstate_secret
+        .metadata
+        .annotations
+        .get(APPLIED_TERA_CONTEXT_ANNOTATION)
+        .and_then(|applied| serde_json::from_str(applied.as_str()).ok())
+}
+
+fn store_applied_tera_context<T>(
+    service: &T,
+    kubernetes: &dyn Kubernetes,
+    environment: &Environment,
+    kubernetes_config_file_path: &str,
+    context: &TeraContext,
+    context_hash: &str,
+) where
+    T: Service,
+{
+    if !has_tfstate_secret(kubernetes) {
+        return;
+    }
+
+    let applied_context = serde_json::to_string(&comparable_tera_context(context)).unwrap_or_default();
+    let annotations = vec![
+        (TERA_CONTEXT_HASH_ANNOTATION, context_hash),
+        (APPLIED_TERA_CONTEXT_ANNOTATION, applied_context.as_str()),
+    ];
+
+    for annotation in annotations {
+        // not blocking, the next deployment will apply again
+        if let Err(e) = crate::cmd::kubectl::kubectl_exec_annotate_secret(
+            kubernetes_config_file_path,
+            service.namespace(environment),
+            get_tfstate_name(service).as_str(),
+            annotation,
+            kubernetes.cloud_provider().credentials_environment_variables(),
+        ) {
+            warn!(
+                "can't store the applied tera context of {} {}: {:?}",
+                service.service_type().name(),
+                service.name_with_id(),
+                e.message
+            );
+        }
+    }
+}
+
+pub fn check_deletion_protection<T>(service: &T) -> Result<(), EngineError>
+where
+    T: StatefulService,
+{
+    if service.is_deletion_protected() && !service.context().is_test_cluster() {
+        return Err(service.engine_error(
+            EngineErrorCause::User("Disable the database deletion protection before deleting it"),
+            format!(
+                "{} {} can't be deleted, its deletion protection is enabled",
+                service.service_type().name(),
+                service.name_with_id()
+            ),
+        ));
     }
 
     Ok(())
@@ -733,9 +2111,11 @@ pub fn delete_stateful_service<T>(target: &DeploymentTarget, service: &T) -> Res
 where
     T: StatefulService + Helm + Terraform,
 {
+    check_deletion_protection(service)?;
+
     match target {
         DeploymentTarget::ManagedServices(kubernetes, environment) => {
-            let workspace_dir = service.workspace_directory();
+            let workspace_dir = run_workspace_directory(service)?;
             let tera_context = service.tera_context(target)?;
 
             let _ = cast_simple_error_to_engine_error(
@@ -778,13 +2158,45 @@ where
                 ),
             )?;
 
-            match crate::cmd::terraform::terraform_init_validate_destroy(workspace_dir.as_str(), true) {
+            match crate::cmd::terraform::terraform_init_validate_destroy(
+                workspace_dir.as_str(),
+                true,
+                service.context().cancellation_token(),
+            ) {
                 Ok(_) => {
-                    info!("deleting secret containing tfstates");
-                    let _ = delete_terraform_tfstate_secret(
-                        *kubernetes,
-                        environment.namespace(),
-                        &get_tfstate_name(service),
+                    // the tfstates are kept as long as the database may still exist
+                    wait_until_deleted(target, service)?;
+
+                    if has_tfstate_secret(*kubernetes) {
+                        info!("deleting secret containing tfstates");
+                        if let Err(err) = delete_terraform_tfstate_secret(
+                            *kubernetes,
+                            service.namespace(environment),
+                            &get_tfstate_name(service),
+                        ) {
+                            // the database is already gone, a remaining secret must not fail the deletion
+                            let message = format!(
+                                "Unable to delete the tfstate secret of {}, it has to be removed manually: {}",
+                                service.name_with_id(),
+                                err.message.unwrap_or_default()
+                            );
+                            warn!("{}", message);
+
+                            ListenersHelper::new(kubernetes.listeners()).delete_in_progress(ProgressInfo::new(
+                                service.progress_scope(),
+                                ProgressLevel::Warn,
+                                Some(message),
+                                service.context().execution_id(),
+                            ));
+                        }
+                    }
+                    cleanup_stale_run_workspace_directories(service, workspace_dir.as_str());
+                }
+                Err(e) if matches!(e.kind, SimpleErrorKind::Cancelled) => {
+                    return cast_simple_error_to_engine_error(
+                        service.engine_error_scope(),
+                        service.context().execution_id(),
+                        Err(e),
                     );
                 }
                 Err(e) => {
@@ -799,7 +2211,7 @@ where
             let helm_release_name = service.helm_release_name();
 
             // clean the resource
-            let _ = helm_uninstall_release(*kubernetes, *environment, helm_release_name.as_str())?;
+            let _ = helm_uninstall_release(*kubernetes, service.namespace(environment), helm_release_name.as_str())?;
         }
     }
 
@@ -867,6 +2279,11 @@ where
     }
 }
 
+/// Tfstates live in a Kubernetes secret unless another Terraform backend is configured.
+fn has_tfstate_secret(kubernetes: &dyn Kubernetes) -> bool {
+    kubernetes.cloud_provider().terraform_state_credentials().backend == TerraformBackend::KubernetesSecret
+}
+
 fn delete_terraform_tfstate_secret(
     kubernetes: &dyn Kubernetes,
     namespace: &str,
@@ -874,15 +2291,33 @@ fn delete_terraform_tfstate_secret(
 ) -> Result<(), EngineError> {
     let config_file_path = kubernetes.config_file_path()?;
 
-    //create the namespace to insert the tfstate in secrets
-    let _ = kubectl_exec_delete_secret(
-        config_file_path,
-        namespace,
-        secret_name,
-        kubernetes.cloud_provider().credentials_environment_variables(),
+    let result = retry::retry(
+        Fibonacci::from_millis(3000).take(5),
+        || match kubectl_exec_delete_secret(
+            config_file_path.as_str(),
+            namespace,
+            secret_name,
+            kubernetes.cloud_provider().credentials_environment_variables(),
+        ) {
+            Ok(_) => OperationResult::Ok(()),
+            Err(e) => {
+                info!("failed to delete tfstate secret {}, retrying...", secret_name);
+                OperationResult::Retry(e)
+            }
+        },
     );
 
-    Ok(())
+    match result {
+        Ok(_) => Ok(()),
+        Err(Operation { error, .. }) => Err(kubernetes.engine_error(
+            EngineErrorCause::Internal,
+            format!(
+                "failed to delete tfstate secret {} in namespace {}: {:?}",
+                secret_name, namespace, error.message
+            ),
+        )),
+        Err(retry::Error::Internal(err)) => Err(kubernetes.engine_error(EngineErrorCause::Internal, err)),
+    }
 }
 
 pub enum CheckAction {
@@ -1022,7 +2457,7 @@ where
         kubernetes.context().execution_id(),
         crate::cmd::kubectl::kubectl_exec_logs(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
+            service.namespace(environment),
             selector.as_str(),
             kubernetes.cloud_provider().credentials_environment_variables(),
         ),
@@ -1037,7 +2472,7 @@ where
         kubernetes.context().execution_id(),
         crate::cmd::kubectl::kubectl_exec_get_pod(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
+            service.namespace(environment),
             selector.as_str(),
             kubernetes.cloud_provider().credentials_environment_variables(),
         ),
@@ -1080,7 +2515,7 @@ where
         kubernetes.context().execution_id(),
         crate::cmd::kubectl::kubectl_exec_get_json_events(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
+            service.namespace(environment),
             kubernetes.cloud_provider().credentials_environment_variables(),
         ),
     )
@@ -1106,7 +2541,7 @@ where
 /// show different output (kubectl describe, log..) for debug purpose
 pub fn get_stateless_resource_information(
     kubernetes: &dyn Kubernetes,
-    environment: &Environment,
+    namespace: &str,
     selector: &str,
 ) -> Result<(Describe, Logs), EngineError> {
     let kubernetes_config_file_path = kubernetes.config_file_path()?;
@@ -1117,7 +2552,7 @@ pub fn get_stateless_resource_information(
         kubernetes.context().execution_id(),
         crate::cmd::kubectl::kubectl_exec_describe_pod(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
+            namespace,
             selector,
             kubernetes.cloud_provider().credentials_environment_variables(),
         ),
@@ -1138,7 +2573,7 @@ pub fn get_stateless_resource_information(
         kubernetes.context().execution_id(),
         crate::cmd::kubectl::kubectl_exec_logs(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
+            namespace,
             selector,
             kubernetes.cloud_provider().credentials_environment_variables(),
         ),
@@ -1158,7 +2593,7 @@ pub fn get_stateless_resource_information(
 
 pub fn helm_uninstall_release(
     kubernetes: &dyn Kubernetes,
-    environment: &Environment,
+    namespace: &str,
     helm_release_name: &str,
 ) -> Result<(), EngineError> {
     let kubernetes_config_file_path = kubernetes.config_file_path()?;
@@ -1168,7 +2603,7 @@ pub fn helm_uninstall_release(
         kubernetes.context().execution_id(),
         crate::cmd::helm::helm_exec_history(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
+            namespace,
             helm_release_name,
             &kubernetes.cloud_provider().credentials_environment_variables(),
         ),
@@ -1183,7 +2618,7 @@ pub fn helm_uninstall_release(
             kubernetes.context().execution_id(),
             crate::cmd::helm::helm_exec_uninstall(
                 kubernetes_config_file_path.as_str(),
-                environment.namespace(),
+                namespace,
                 helm_release_name,
                 kubernetes.cloud_provider().credentials_environment_variables(),
             ),
@@ -1289,6 +2724,2316 @@ pub fn get_tfstate_suffix(service: &dyn Service) -> String {
 // Name generated from TF secret suffix
 // https://www.terraform.io/docs/backends/types/kubernetes.html#secret_suffix
 // As mention the doc: Secrets will be named in the format: tfstate-{workspace}-{secret_suffix}.
-pub fn get_tfstate_name(service: &dyn Service) -> String {
+pub fn get_tfstate_name<T>(service: &T) -> String
+where
+    T: Service + ?Sized,
+{
     format!("tfstate-default-{}", service.id())
 }
+
+/// Labels to add on namespaces so expired resources get cleaned (ex: test clusters), `None` if resources never expire.
+pub fn resource_expiration_labels(context: &Context) -> Option<Vec<LabelsContent>> {
+    context.resource_expiration_in_seconds().map(|ttl| {
+        vec![LabelsContent {
+            name: "ttl".to_string(),
+            value: ttl.to_string(),
+        }]
+    })
+}
+
+/// Expose TLS requirements and certificates (base64 encoded, as stored in Kubernetes secrets).
+pub fn insert_database_tls_into_tera_context(options: &DatabaseOptions, tera_context: &mut TeraContext) {
+    tera_context.insert("database_require_tls", &options.require_tls);
+
+    if let Some(certificates) = options.tls_certificates.as_ref() {
+        tera_context.insert("database_tls_ca", &base64::encode(certificates.ca.as_bytes()));
+        tera_context.insert(
+            "database_tls_certificate",
+            &base64::encode(certificates.certificate.as_bytes()),
+        );
+        tera_context.insert("database_tls_key", &base64::encode(certificates.key.as_bytes()));
+    }
+}
+
+/// Version of a self-hosted database, which is the image tag of its helm release.
+pub fn get_self_hosted_database_version<T>(
+    target: &DeploymentTarget,
+    service: &T,
+) -> Result<Option<String>, EngineError>
+where
+    T: Service + Helm,
+{
+    let (kubernetes, environment) = match target {
+        DeploymentTarget::ManagedServices(k, env) => (*k, *env),
+        DeploymentTarget::SelfHosted(k, env) => (*k, *env),
+    };
+
+    let kubernetes_config_file_path = kubernetes.config_file_path()?;
+    let values = cast_simple_error_to_engine_error(
+        service.engine_error_scope(),
+        service.context().execution_id(),
+        crate::cmd::helm::helm_exec_get_values(
+            kubernetes_config_file_path.as_str(),
+            service.namespace(environment),
+            service.helm_release_name().as_str(),
+            kubernetes.cloud_provider().credentials_environment_variables(),
+        ),
+    )?;
+
+    Ok(values.as_ref().and_then(helm_values_image_tag))
+}
+
+/// Disk size of a self-hosted database, the largest of its persistent volume claims (replicas have their own).
+pub fn get_self_hosted_database_disk_size<T>(target: &DeploymentTarget, service: &T) -> Result<Option<u32>, EngineError>
+where
+    T: Service,
+{
+    let (kubernetes, environment) = match target {
+        DeploymentTarget::ManagedServices(k, env) => (*k, *env),
+        DeploymentTarget::SelfHosted(k, env) => (*k, *env),
+    };
+
+    let kubernetes_config_file_path = kubernetes.config_file_path()?;
+    let persistent_volume_claims = cast_simple_error_to_engine_error(
+        service.engine_error_scope(),
+        service.context().execution_id(),
+        kubectl_exec_get_persistent_volume_claims(
+            kubernetes_config_file_path.as_str(),
+            service.namespace(environment),
+            kubernetes.cloud_provider().credentials_environment_variables(),
+        ),
+    )?;
+
+    Ok(persistent_volume_claims_disk_size(
+        &persistent_volume_claims.items,
+        service.id(),
+    ))
+}
+
+/// Database charts annotate their persistent volume claims with `databaseId`.
+fn database_persistent_volume_claims<'a>(
+    persistent_volume_claims: &'a [PersistentVolumeClaim],
+    database_id: &str,
+) -> Vec<&'a PersistentVolumeClaim> {
+    persistent_volume_claims
+        .iter()
+        .filter(|pvc| pvc.metadata.annotations.get("databaseId").map(|id| id.as_str()) == Some(database_id))
+        .collect()
+}
+
+fn persistent_volume_claims_disk_size(
+    persistent_volume_claims: &[PersistentVolumeClaim],
+    database_id: &str,
+) -> Option<u32> {
+    database_persistent_volume_claims(persistent_volume_claims, database_id)
+        .into_iter()
+        .filter_map(|pvc| {
+            pvc.status
+                .capacity
+                .get("storage")
+                .or_else(|| pvc.spec.resources.requests.get("storage"))
+        })
+        .filter_map(|storage| storage_to_gi(storage.as_str()))
+        .max()
+}
+
+/// Grows the persistent volume claims of a self-hosted database, then restarts its statefulsets
+/// so volumes which can't be expanded online get their file system resized.
+pub fn resize_self_hosted_database_storage<T>(
+    target: &DeploymentTarget,
+    service: &T,
+    new_size_gib: u32,
+) -> Result<(), EngineError>
+where
+    T: Service + ?Sized,
+{
+    let (kubernetes, environment) = match target {
+        DeploymentTarget::ManagedServices(k, env) => (*k, *env),
+        DeploymentTarget::SelfHosted(k, env) => (*k, *env),
+    };
+
+    let kubernetes_config_file_path = kubernetes.config_file_path()?;
+    let envs = kubernetes.cloud_provider().credentials_environment_variables();
+
+    let persistent_volume_claims = cast_simple_error_to_engine_error(
+        service.engine_error_scope(),
+        service.context().execution_id(),
+        kubectl_exec_get_persistent_volume_claims(
+            kubernetes_config_file_path.as_str(),
+            service.namespace(environment),
+            envs.clone(),
+        ),
+    )?;
+
+    match persistent_volume_claims_disk_size(&persistent_volume_claims.items, service.id()) {
+        None => {
+            return Err(service.engine_error(
+                EngineErrorCause::Internal,
+                format!("no volume found for {}, it can't be resized", service.name_with_id()),
+            ))
+        }
+        Some(disk_size_in_gib) if new_size_gib < disk_size_in_gib => {
+            return Err(service.engine_error(
+                EngineErrorCause::User("Set a disk size at least as large as the one already provisioned"),
+                format!(
+                    "disk size of {} GiB requested, it can't be smaller than the {} GiB already provisioned",
+                    new_size_gib, disk_size_in_gib
+                ),
+            ))
+        }
+        Some(disk_size_in_gib) if new_size_gib == disk_size_in_gib => return Ok(()),
+        Some(_) => {}
+    }
+
+    let database_volume_claims = database_persistent_volume_claims(&persistent_volume_claims.items, service.id());
+    let storage_classes = cast_simple_error_to_engine_error(
+        service.engine_error_scope(),
+        service.context().execution_id(),
+        kubectl_exec_get_storage_classes(kubernetes_config_file_path.as_str(), envs.clone()),
+    )?;
+    check_volume_expansion_is_allowed(&database_volume_claims, &storage_classes.items).map_err(|message| {
+        service.engine_error(
+            EngineErrorCause::User("The storage class of the database volumes doesn't allow to expand them"),
+            message,
+        )
+    })?;
+
+    let storage = format!("{}Gi", new_size_gib);
+    for persistent_volume_claim in database_volume_claims {
+        cast_simple_error_to_engine_error(
+            service.engine_error_scope(),
+            service.context().execution_id(),
+            kubectl_exec_patch_persistent_volume_claim_storage(
+                kubernetes_config_file_path.as_str(),
+                service.namespace(environment),
+                persistent_volume_claim.metadata.name.as_str(),
+                storage.as_str(),
+                envs.clone(),
+            ),
+        )?;
+    }
+
+    let statefulsets = cast_simple_error_to_engine_error(
+        service.engine_error_scope(),
+        service.context().execution_id(),
+        kubectl_exec_get_workloads_by_selector(
+            kubernetes_config_file_path.as_str(),
+            service.namespace(environment),
+            Statefulset,
+            service.selector().as_str(),
+            envs.clone(),
+        ),
+    )?;
+    for statefulset in statefulsets.items {
+        cast_simple_error_to_engine_error(
+            service.engine_error_scope(),
+            service.context().execution_id(),
+            kubectl_exec_rollout_restart_statefulset(
+                kubernetes_config_file_path.as_str(),
+                statefulset.metadata.name.as_str(),
+                service.namespace(environment),
+                &envs,
+            ),
+        )?;
+    }
+
+    service.wait_until_ready(target, service.readiness_timeout())
+}
+
+/// Deploys a self-hosted database then restores a dump of its clone source into it, the dump is streamed
+/// from the source pod to the new one. Both databases are accessed with their own credentials.
+pub fn clone_self_hosted_database<T>(target: &DeploymentTarget, service: &T) -> Result<(), EngineError>
+where
+    T: StatefulService + Helm + Terraform + Listen,
+{
+    let (kubernetes, environment) = match target {
+        DeploymentTarget::ManagedServices(k, env) => (*k, *env),
+        DeploymentTarget::SelfHosted(k, env) => (*k, *env),
+    };
+
+    let source = match service.clone_source() {
+        Some(source) => source,
+        None => {
+            return Err(service.engine_error(
+                EngineErrorCause::Internal,
+                format!("{} has no database to be cloned from", service.name_with_id()),
+            ))
+        }
+    };
+
+    let database_type = match service.service_type() {
+        ServiceType::Database(database_type) => database_type,
+        _ => return Err(unsupported_database_clone_error(service)),
+    };
+    let (dump_command, restore_command) = match self_hosted_database_clone_commands(&database_type) {
+        Some(commands) => commands,
+        None => return Err(unsupported_database_clone_error(service)),
+    };
+
+    let kubernetes_config_file_path = kubernetes.config_file_path()?;
+    let envs = kubernetes.cloud_provider().credentials_environment_variables();
+    let primary_pod = |namespace: &str, database_id: &str| -> Result<String, EngineError> {
+        let pods = cast_simple_error_to_engine_error(
+            service.engine_error_scope(),
+            service.context().execution_id(),
+            kubectl_exec_get_pod_names(
+                kubernetes_config_file_path.as_str(),
+                namespace,
+                self_hosted_database_primary_selector(&database_type, database_id).as_str(),
+                envs.clone(),
+            ),
+        )?;
+
+        pods.into_iter().next().ok_or_else(|| {
+            service.engine_error(
+                EngineErrorCause::User("The database to clone must be running in the environment it's cloned from"),
+                format!("no pod of database {} found in namespace {}", database_id, namespace),
+            )
+        })
+    };
+
+    // the source is looked up first, nothing is deployed when it can't be cloned
+    let source_namespace = source.namespace(environment.project_id.as_str());
+    let source_pod = primary_pod(source_namespace.as_str(), source.database_id.as_str())?;
+
+    deploy_stateful_service(target, service)?;
+
+    let target_pod = primary_pod(service.namespace(environment), service.id())?;
+    cast_simple_error_to_engine_error(
+        service.engine_error_scope(),
+        service.context().execution_id(),
+        kubectl_exec_pipe_between_pods(
+            kubernetes_config_file_path.as_str(),
+            PodCommand {
+                namespace: source_namespace.as_str(),
+                pod: source_pod.as_str(),
+                command: dump_command,
+            },
+            PodCommand {
+                namespace: service.namespace(environment),
+                pod: target_pod.as_str(),
+                command: restore_command,
+            },
+            envs.clone(),
+        ),
+    )
+}
+
+/// The database can't be seeded from its clone source, it must be deployed empty.
+pub fn unsupported_database_clone_error<T>(service: &T) -> EngineError
+where
+    T: Service + ?Sized,
+{
+    service.engine_error(
+        EngineErrorCause::User("This database can't be cloned, deploy it without a database to clone from"),
+        format!(
+            "{} {} can't be cloned",
+            service.service_type().name(),
+            service.name_with_id()
+        ),
+    )
+}
+
+/// Commands dumping a self-hosted database to their output and restoring it from their input, they are run in the
+/// database pods with the credentials of the pod. `None` when the database can't be dumped this way.
+fn self_hosted_database_clone_commands(database_type: &DatabaseType) -> Option<(&'static str, &'static str)> {
+    match database_type {
+        DatabaseType::PostgreSQL(_) => Some((
+            r#"PGPASSWORD="${POSTGRES_PASSWORD:-$(cat "$POSTGRES_PASSWORD_FILE")}" pg_dump --clean --if-exists --no-owner -U "$POSTGRES_USER" -d "$POSTGRES_DB""#,
+            r#"PGPASSWORD="${POSTGRES_PASSWORD:-$(cat "$POSTGRES_PASSWORD_FILE")}" psql -v ON_ERROR_STOP=1 -q -U "$POSTGRES_USER" -d "$POSTGRES_DB""#,
+        )),
+        DatabaseType::MySQL(_) => Some((
+            r#"mysqldump -u root -p"${MYSQL_ROOT_PASSWORD:-$(cat "$MYSQL_ROOT_PASSWORD_FILE")}" --single-transaction --routines "$MYSQL_DATABASE""#,
+            r#"mysql -u root -p"${MYSQL_ROOT_PASSWORD:-$(cat "$MYSQL_ROOT_PASSWORD_FILE")}" "$MYSQL_DATABASE""#,
+        )),
+        DatabaseType::MongoDB(_) => Some((
+            r#"mongodump --quiet --archive -u root -p "$MONGODB_ROOT_PASSWORD" --authenticationDatabase admin --db "$MONGODB_DATABASE""#,
+            r#"mongorestore --quiet --archive --drop -u root -p "$MONGODB_ROOT_PASSWORD" --authenticationDatabase admin --nsFrom '$db$.$collection$' --nsTo "$MONGODB_DATABASE"'.$collection$'"#,
+        )),
+        DatabaseType::Redis(_) => None,
+    }
+}
+
+/// Selects the pod of a self-hosted database receiving the writes, replicas can't be restored.
+fn self_hosted_database_primary_selector(database_type: &DatabaseType, database_id: &str) -> String {
+    match database_type {
+        DatabaseType::PostgreSQL(_) => format!("databaseId={},role=master", database_id),
+        DatabaseType::MySQL(_) => format!("databaseId={},component=master", database_id),
+        DatabaseType::MongoDB(_) | DatabaseType::Redis(_) => format!("databaseId={}", database_id),
+    }
+}
+
+/// Volumes can only be expanded when their storage class allows it, claims without one use the default class.
+fn check_volume_expansion_is_allowed(
+    persistent_volume_claims: &[&PersistentVolumeClaim],
+    storage_classes: &[StorageClass],
+) -> Result<(), StringError> {
+    for persistent_volume_claim in persistent_volume_claims {
+        let storage_class = match &persistent_volume_claim.spec.storage_class_name {
+            Some(name) => storage_classes.iter().find(|class| &class.metadata.name == name),
+            None => storage_classes.iter().find(|class| {
+                class
+                    .metadata
+                    .annotations
+                    .get("storageclass.kubernetes.io/is-default-class")
+                    .map(|is_default| is_default.as_str())
+                    == Some("true")
+            }),
+        };
+
+        match storage_class {
+            Some(storage_class) if storage_class.allow_volume_expansion => {}
+            Some(storage_class) => {
+                return Err(format!(
+                    "storage class `{}` of volume `{}` doesn't allow volume expansion",
+                    storage_class.metadata.name, persistent_volume_claim.metadata.name
+                ))
+            }
+            None => {
+                return Err(format!(
+                    "storage class of volume `{}` can't be found",
+                    persistent_volume_claim.metadata.name
+                ))
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Rejects a database disk smaller than the one already provisioned, as a user error.
+fn check_database_disk_size<T>(target: &DeploymentTarget, service: &T) -> Result<(), EngineError>
+where
+    T: Service + ?Sized,
+{
+    let options = match service.service_type() {
+        ServiceType::Database(database_type) => database_type.options(),
+        _ => return Ok(()),
+    };
+
+    match options.validate_disk_size(service.deployed_disk_size_in_gib(target)?) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(service.engine_error(
+            EngineErrorCause::User("Set a disk size at least as large as the one already provisioned"),
+            e,
+        )),
+    }
+}
+
+/// Rejects a new self-hosted database requesting more cpu or ram than what is left on the cluster, as a user error.
+/// A database already deployed is part of the cluster usage, it's not checked again.
+fn check_database_fits_cluster<T>(target: &DeploymentTarget, service: &T) -> Result<(), EngineError>
+where
+    T: Service + ?Sized,
+{
+    let kubernetes = match (service.service_type(), target) {
+        (ServiceType::Database(_), DeploymentTarget::SelfHosted(kubernetes, _)) => *kubernetes,
+        _ => return Ok(()),
+    };
+
+    if service.deployed_disk_size_in_gib(target)?.is_some() {
+        return Ok(());
+    }
+
+    let requested_cpu = match convert_k8s_cpu_value_to_f32(service.total_cpus()) {
+        Ok(cpu) => cpu,
+        Err(_) => return Ok(()),
+    };
+
+    let cluster_capacity = match kubernetes.cluster_capacity() {
+        Ok(cluster_capacity) => cluster_capacity,
+        Err(e) => {
+            warn!(
+                "can't get the free resources of cluster {}, skipping the capacity check of {}: {:?}",
+                kubernetes.name_with_id(),
+                service.name_with_id(),
+                e.message
+            );
+            return Ok(());
+        }
+    };
+
+    match cluster_capacity.check_can_fit(
+        requested_cpu,
+        service.total_ram_in_mib(),
+        service.total_instances() as u32,
+    ) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(service.engine_error(
+            EngineErrorCause::User(
+                "The cluster doesn't have enough free resources for this database, \
+                lower its resources or add nodes to your cluster",
+            ),
+            format!(
+                "{} {} can't fit on cluster {}: {}",
+                service.service_type().name(),
+                service.name_with_id(),
+                kubernetes.name_with_id(),
+                e
+            ),
+        )),
+    }
+}
+
+fn helm_values_image_tag(values: &serde_json::Value) -> Option<String> {
+    values.get("image")?.get("tag")?.as_str().map(|tag| tag.to_string())
+}
+
+/// Expose the cpu and ram requests and limits of self-hosted databases to the chart values.
+pub fn insert_database_resources_into_tera_context(resources: &DatabaseResources, tera_context: &mut TeraContext) {
+    tera_context.insert("database_cpu_request", &resources.cpu_request);
+    tera_context.insert("database_cpu_limit", &resources.cpu_limit);
+    tera_context.insert("database_ram_request_mib", &resources.ram_request_mib);
+    tera_context.insert("database_ram_limit_mib", &resources.ram_limit_mib);
+}
+
+/// Expose the self-hosted Redis topology to the bitnami chart values.
+pub fn insert_redis_topology_into_tera_context(options: &DatabaseOptions, tera_context: &mut TeraContext) {
+    // with sentinel, all nodes (master included) belong to the same statefulset
+    let slave_count = match options.sentinel_enabled {
+        true => options.total_instances(),
+        false => options.replicas as u16,
+    };
+
+    tera_context.insert("redis_cluster_enabled", &(options.replicas > 0));
+    tera_context.insert("redis_sentinel_enabled", &options.sentinel_enabled);
+    tera_context.insert("redis_slave_count", &slave_count);
+}
+
+/// Expose the Terraform backend storing the tfstate of a service, Kubernetes secrets unless S3 is configured.
+pub fn insert_terraform_backend_into_tera_context(
+    credentials: &TerraformStateCredentials,
+    namespace: &str,
+    tfstate_name: &str,
+    tera_context: &mut TeraContext,
+) {
+    match &credentials.backend {
+        TerraformBackend::KubernetesSecret => tera_context.insert("terraform_backend_s3", &false),
+        TerraformBackend::S3 { bucket, dynamodb_table } => {
+            tera_context.insert("terraform_backend_s3", &true);
+            tera_context.insert("terraform_backend_bucket", bucket);
+            tera_context.insert(
+                "terraform_backend_key",
+                &format!("{}/{}.tfstate", namespace, tfstate_name),
+            );
+            tera_context.insert("terraform_backend_access_key", &credentials.access_key_id);
+            tera_context.insert("terraform_backend_secret_key", &credentials.secret_access_key);
+            tera_context.insert("terraform_backend_region", &credentials.region);
+            if let Some(dynamodb_table) = dynamodb_table {
+                tera_context.insert("terraform_backend_dynamodb_table", dynamodb_table);
+            }
+        }
+    }
+}
+
+/// `value` of a field of the service, the secret it references when it's a secret reference, see `crate::secrets`.
+/// Messages only mention the reference, the resolved value is only rendered under redacted tera context keys.
+pub fn resolve_secret<T>(service: &T, value: &str) -> Result<String, EngineError>
+where
+    T: Service + ?Sized,
+{
+    crate::secrets::resolve(service.context(), value).map_err(|message| {
+        service.engine_error(
+            EngineErrorCause::User(
+                "Check the secret references of the service, they must exist in your secrets manager",
+            ),
+            format!(
+                "can't resolve a secret of {} {}: {}",
+                service.service_type().name(),
+                service.name_with_id(),
+                message
+            ),
+        )
+    })
+}
+
+/// Expose the secret the application image is pulled with to the application chart.
+/// Private registry credentials are rendered into a `kubernetes.io/dockerconfigjson` secret owned by the application,
+/// it's rendered again on every deployment so rotated credentials update the existing secret.
+pub fn insert_registry_secret_into_tera_context(application_id: &str, image: &Image, tera_context: &mut TeraContext) {
+    match (&image.registry_docker_json_config, &image.registry_name) {
+        (Some(docker_json_config), _) => {
+            tera_context.insert("is_registry_secret", &true);
+            tera_context.insert("registry_secret_name", &format!("registry-token-{}", application_id));
+            tera_context.insert("container_registry_docker_json_config", docker_json_config);
+        }
+        // the registry pull secret is provided by the cluster itself (ex: DOCR), it's named after the registry
+        (None, Some(registry_name)) => {
+            tera_context.insert("is_registry_secret", &true);
+            tera_context.insert("registry_secret_name", registry_name);
+        }
+        (None, None) => {
+            tera_context.insert("is_registry_secret", &false);
+        }
+    }
+}
+
+/// Expose the automated backups schedule of a managed database to its terraform templates, `default_retention_days`
+/// when the retention isn't set. Test clusters don't keep backups, they are deleted along with the database.
+pub fn insert_database_backups_into_tera_context(
+    context: &Context,
+    options: &DatabaseOptions,
+    default_retention_days: u32,
+    tera_context: &mut TeraContext,
+) {
+    let backup_retention_days = match context.is_test_cluster() {
+        // read replicas are created from backups, they can't be disabled
+        true if options.replicas > 0 => 1,
+        true => 0,
+        false => options.backup_retention_days.unwrap_or(default_retention_days),
+    };
+
+    tera_context.insert("backup_retention_period", &backup_retention_days);
+    tera_context.insert(
+        "backup_window",
+        options.backup_window.as_deref().unwrap_or(DEFAULT_BACKUP_WINDOW),
+    );
+    tera_context.insert("delete_automated_backups", &context.is_test_cluster());
+}
+
+/// Pin a self-hosted database to the node pool with its `node_pool_label`: the chart values select the pool nodes
+/// and tolerate the pool taints. The database can be scheduled anywhere when it has no node pool label.
+/// Node pools are only checked when the cluster knows them, an existing cluster doesn't.
+pub fn insert_database_node_pool_into_tera_context(
+    options: &DatabaseOptions,
+    node_pools: &[NodePoolScheduling],
+    tera_context: &mut TeraContext,
+) -> Result<(), StringError> {
+    let mut node_selector = BTreeMap::new();
+    let mut tolerations = vec![];
+
+    if let Some(label) = options.node_pool_label.as_ref() {
+        let matching_node_pools = node_pools
+            .iter()
+            .filter(|node_pool| node_pool.has_label(label.key.as_str(), label.value.as_str()))
+            .collect::<Vec<_>>();
+
+        if !node_pools.is_empty() && matching_node_pools.is_empty() {
+            return Err(format!(
+                "the database is pinned to node pool label `{}={}`, but no node pool of the cluster has it",
+                label.key, label.value
+            ));
+        }
+
+        node_selector.insert(label.key.clone(), label.value.clone());
+        for taint in matching_node_pools
+            .iter()
+            .flat_map(|node_pool| node_pool.taints.iter())
+            .unique()
+        {
+            tolerations.push(serde_json::json!({
+                "key": taint.key,
+                "operator": "Equal",
+                "value": taint.value,
+                "effect": taint.effect.as_str(),
+            }));
+        }
+    }
+
+    tera_context.insert("database_node_selector", &node_selector);
+    tera_context.insert("database_tolerations", &tolerations);
+
+    Ok(())
+}
+
+/// Expose `resource_expiration_in_seconds` to terraform and helm templates when resources should expire.
+pub fn insert_resource_expiration_into_tera_context(context: &Context, tera_context: &mut TeraContext) {
+    if let Some(ttl) = context.resource_expiration_in_seconds() {
+        tera_context.insert("resource_expiration_in_seconds", &ttl);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::build_platform::Image;
+    use crate::cloud_provider::aws::databases::mysql::MySQL;
+    use crate::cloud_provider::kubernetes::{NodePoolScheduling, Taint, TaintEffect};
+    use crate::cloud_provider::models::{
+        Autoscaling, AutoscalingDataTemplate, InitContainer, InitContainerDataTemplate, Probes, ProbesDataTemplate,
+    };
+    use crate::cloud_provider::service::{
+        applied_tera_context, check_volume_expansion_is_allowed, comparable_tera_context,
+        database_persistent_volume_claims, external_name_service_name, hash_tera_context,
+        helm_deployment_failure_details, helm_values_image_tag, insert_database_backups_into_tera_context,
+        insert_database_node_pool_into_tera_context, insert_database_resources_into_tera_context,
+        insert_database_tls_into_tera_context, insert_redis_topology_into_tera_context,
+        insert_registry_secret_into_tera_context, insert_resource_expiration_into_tera_context,
+        insert_terraform_backend_into_tera_context, pause_workloads, persistent_volume_claims_disk_size,
+        readiness_timeout, redacted_tera_context, resolve_secret, resource_expiration_labels, resume_workloads,
+        tera_context_diff, wait_until_deleted_with, wait_until_ready_with, Action, DatabaseOptions,
+        DatabaseTlsCertificates, NodePoolLabel, Service,
+    };
+    use crate::cloud_provider::{TerraformBackend, TerraformStateCredentials};
+    use crate::cmd::helm::helm_timeout_in_seconds;
+    use crate::cmd::kubectl::ScalingKind;
+    use crate::cmd::structs::{
+        HelmHistoryRow, KubernetesEvent, KubernetesList, PersistentVolumeClaim, Secret, SecretMetadata, StorageClass,
+    };
+    use crate::error::{EngineErrorCause, StringError};
+    use crate::models::{Context, ListenersHelper, Metadata};
+    use crate::secrets::{SecretReference, Secrets, SecretsResolver};
+    use std::cell::Cell;
+    use std::collections::BTreeMap;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::Duration;
+    use tera::Context as TeraContext;
+
+    #[test]
+    fn test_external_name_service_name() {
+        // setup:
+        let chart_service =
+            std::fs::read_to_string("lib/common/charts/external-name-svc/templates/external-name-service.yaml")
+                .unwrap();
+
+        // verify: the chart names the service after its `app_id` value, the database id
+        assert!(chart_service.contains("name: {{.Values.app_id}}-dns"));
+        assert_eq!(external_name_service_name("z1a2b3c4d"), "z1a2b3c4d-dns");
+    }
+
+    #[test]
+    fn test_tera_context_hash() {
+        // setup:
+        let context = |version: &str, kubeconfig_path: &str| {
+            let mut context = TeraContext::new();
+            context.insert("database_instance_type", "db.t2.micro");
+            context.insert("version", version);
+            context.insert("database_disk_size_in_gib", &10);
+            context.insert("kubeconfig_path", kubeconfig_path);
+            context
+        };
+
+        // execute:
+        let hash = hash_tera_context(&context("8.0", "/tmp/execution-1/kubeconfig"));
+        let same_hash = hash_tera_context(&context("8.0", "/tmp/execution-1/kubeconfig"));
+        let other_run_hash = hash_tera_context(&context("8.0", "/tmp/execution-2/kubeconfig"));
+        let other_version_hash = hash_tera_context(&context("5.7", "/tmp/execution-1/kubeconfig"));
+
+        // verify:
+        assert_eq!(hash, same_hash);
+        assert_eq!(hash, other_run_hash);
+        assert_ne!(hash, other_version_hash);
+    }
+
+    #[test]
+    fn test_redacted_tera_context() {
+        // setup:
+        let mut context = TeraContext::new();
+        context.insert("database_login", "superuser");
+        context.insert("database_password", "p4ssw0rd");
+        context.insert("aws_secret_key", "s3cr3t");
+        context.insert("terraform_backend_access_key", "AKIA");
+        context.insert("container_registry_docker_json_config", "eyJhdXRocyI6e319");
+        context.insert("grafana_admin_password", "gr4f4n4");
+        context.insert("qovery_nats_password", "n4ts");
+        context.insert("cloudflare_api_token", "t0k3n");
+        context.insert("database_disk_size_in_gib", &10);
+        context.insert(
+            "charts",
+            &serde_json::json!([{ "name": "vault", "vault_secret_id": "v4ult", "vault_role_id": "role" }]),
+        );
+
+        // execute:
+        let dump = redacted_tera_context(&context);
+
+        // verify:
+        assert_eq!(dump.len(), 10);
+        assert_eq!(dump["database_login"], serde_json::json!("superuser"));
+        for key in &[
+            "database_password",
+            "aws_secret_key",
+            "terraform_backend_access_key",
+            "container_registry_docker_json_config",
+            "grafana_admin_password",
+            "qovery_nats_password",
+            "cloudflare_api_token",
+        ] {
+            assert_eq!(
+                dump[*key],
+                serde_json::json!("<redacted>"),
+                "{} should be redacted",
+                key
+            );
+        }
+        assert_eq!(dump["database_disk_size_in_gib"], serde_json::json!(10));
+        assert_eq!(
+            dump["charts"],
+            serde_json::json!([{ "name": "vault", "vault_secret_id": "<redacted>", "vault_role_id": "role" }])
+        );
+    }
+
+    #[test]
+    fn test_tera_context_diff() {
+        // setup:
+        let context = |version: &str, instance_type: &str, password: &str, kubeconfig_path: &str| {
+            let mut context = TeraContext::new();
+            context.insert("version", version);
+            context.insert("database_instance_type", instance_type);
+            context.insert("database_password", password);
+            context.insert("database_disk_size_in_gib", &10);
+            context.insert("kubeconfig_path", kubeconfig_path);
+            comparable_tera_context(&context)
+        };
+        let applied = context("8.0.23", "db.t3.micro", "p4ssw0rd", "/tmp/run-1/kubeconfig");
+        let mut desired = context("8.0.28", "db.t3.medium", "n3wp4ssw0rd", "/tmp/run-2/kubeconfig");
+        desired.insert("database_replicas".to_string(), serde_json::json!(1));
+
+        // execute:
+        let changes = tera_context_diff(&applied, &desired);
+        let no_changes = tera_context_diff(&applied, &applied);
+
+        // verify:
+        assert_eq!(
+            changes,
+            vec![
+                "database_instance_type: db.t3.micro -> db.t3.medium".to_string(),
+                "database_replicas: (none) -> 1".to_string(),
+                "version: 8.0.23 -> 8.0.28".to_string(),
+            ]
+        );
+        assert!(no_changes.is_empty());
+    }
+
+    #[test]
+    fn test_applied_tera_context() {
+        // setup:
+        let tfstate_secret = |annotations: Vec<(&str, &str)>| Secret {
+            metadata: SecretMetadata {
+                name: "tfstate-default-mysql-id".to_string(),
+                annotations: annotations
+                    .into_iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+            },
+        };
+
+        // execute:
+        let applied = applied_tera_context(&tfstate_secret(vec![(
+            "qovery.com/applied-tera-context",
+            r#"{"version": "8.0.23"}"#,
+        )]));
+        let never_stored = applied_tera_context(&tfstate_secret(vec![]));
+
+        // verify:
+        assert_eq!(applied.unwrap()["version"], "8.0.23");
+        assert!(never_stored.is_none());
+    }
+
+    #[test]
+    fn test_terraform_backend() {
+        // setup:
+        let kubernetes_secret_credentials = TerraformStateCredentials::new("access", "secret", "eu-west-3");
+        let s3_credentials = TerraformStateCredentials::new("access", "secret", "eu-west-3")
+            .with_s3_backend("qovery-tfstates", Some("qovery-tfstates-lock"));
+
+        // execute:
+        let mut kubernetes_secret_context = TeraContext::new();
+        insert_terraform_backend_into_tera_context(
+            &kubernetes_secret_credentials,
+            "z1234-z5678",
+            "tfstate-default-mysql",
+            &mut kubernetes_secret_context,
+        );
+        let mut s3_context = TeraContext::new();
+        insert_terraform_backend_into_tera_context(
+            &s3_credentials,
+            "z1234-z5678",
+            "tfstate-default-mysql",
+            &mut s3_context,
+        );
+
+        // verify:
+        assert_eq!(
+            kubernetes_secret_credentials.backend,
+            TerraformBackend::KubernetesSecret
+        );
+        assert_eq!(
+            kubernetes_secret_context
+                .get("terraform_backend_s3")
+                .and_then(|v| v.as_bool()),
+            Some(false)
+        );
+        assert!(kubernetes_secret_context.get("terraform_backend_bucket").is_none());
+        assert_eq!(
+            s3_context.get("terraform_backend_s3").and_then(|v| v.as_bool()),
+            Some(true)
+        );
+        assert_eq!(
+            s3_context.get("terraform_backend_key").and_then(|v| v.as_str()),
+            Some("z1234-z5678/tfstate-default-mysql.tfstate")
+        );
+        assert_eq!(
+            s3_context
+                .get("terraform_backend_dynamodb_table")
+                .and_then(|v| v.as_str()),
+            Some("qovery-tfstates-lock")
+        );
+    }
+
+    #[test]
+    fn test_resource_expiration() {
+        // setup:
+        let context = |test_cluster: bool, ttl: Option<u32>| {
+            Context::new(
+                "execution-id".to_string(),
+                "/tmp/workspace".to_string(),
+                "/tmp/lib".to_string(),
+                test_cluster,
+                None,
+                vec![],
+                Some(Metadata::new(None, ttl, None, None, None, None, None)),
+            )
+        };
+        let test_cluster_context = context(true, Some(7200));
+        let prod_context = context(false, None);
+
+        // execute:
+        let test_cluster_labels = resource_expiration_labels(&test_cluster_context);
+        let prod_labels = resource_expiration_labels(&prod_context);
+        let mut test_cluster_tera_context = TeraContext::new();
+        insert_resource_expiration_into_tera_context(&test_cluster_context, &mut test_cluster_tera_context);
+        let mut prod_tera_context = TeraContext::new();
+        insert_resource_expiration_into_tera_context(&prod_context, &mut prod_tera_context);
+
+        // verify:
+        let test_cluster_labels = test_cluster_labels.expect("test cluster should have a ttl label");
+        assert_eq!(test_cluster_labels.len(), 1);
+        assert_eq!(test_cluster_labels[0].name, "ttl");
+        assert_eq!(test_cluster_labels[0].value, "7200");
+        assert!(prod_labels.is_none());
+        assert_eq!(
+            test_cluster_tera_context
+                .get("resource_expiration_in_seconds")
+                .and_then(|v| v.as_u64()),
+            Some(7200)
+        );
+        assert!(prod_tera_context.get("resource_expiration_in_seconds").is_none());
+    }
+
+    #[test]
+    fn test_database_backups() {
+        // setup:
+        let context = |test_cluster: bool| {
+            Context::new(
+                "execution-id".to_string(),
+                "/tmp/workspace".to_string(),
+                "/tmp/lib".to_string(),
+                test_cluster,
+                None,
+                vec![],
+                None,
+            )
+        };
+        let options_with_replicas =
+            |backup_retention_days: Option<u32>, backup_window: Option<&str>, replicas: u8| DatabaseOptions {
+                login: "".to_string(),
+                password: "".to_string(),
+                host: "".to_string(),
+                port: 5432,
+                disk_size_in_gib: 10,
+                database_disk_type: "gp2".to_string(),
+                replicas,
+                iops: None,
+                storage_throughput: None,
+                deletion_protection: false,
+                sentinel_enabled: false,
+                resources_overcommit_percent: None,
+                parameters: BTreeMap::new(),
+                require_tls: false,
+                tls_certificates: None,
+                deletion_timeout_in_seconds: None,
+                helm_value_overrides: BTreeMap::new(),
+                deploy_timeout_in_seconds: None,
+                namespace: None,
+                clone_from: None,
+                backup_retention_days,
+                backup_window: backup_window.map(str::to_string),
+                node_pool_label: None,
+            };
+        let options = |backup_retention_days: Option<u32>, backup_window: Option<&str>| {
+            options_with_replicas(backup_retention_days, backup_window, 0)
+        };
+        let tera_context = |test_cluster: bool, options: &DatabaseOptions| {
+            let mut tera_context = TeraContext::new();
+            insert_database_backups_into_tera_context(&context(test_cluster), options, 14, &mut tera_context);
+            tera_context
+        };
+
+        // execute:
+        let prod_tera_context = tera_context(false, &options(Some(30), Some("05:00-06:00")));
+        let prod_default_tera_context = tera_context(false, &options(None, None));
+        let test_cluster_tera_context = tera_context(true, &options(Some(30), Some("05:00-06:00")));
+        let test_cluster_replicas_tera_context = tera_context(true, &options_with_replicas(None, None, 1));
+
+        // verify:
+        assert_eq!(
+            prod_tera_context
+                .get("backup_retention_period")
+                .and_then(|v| v.as_u64()),
+            Some(30)
+        );
+        assert_eq!(
+            prod_tera_context.get("backup_window").and_then(|v| v.as_str()),
+            Some("05:00-06:00")
+        );
+        assert_eq!(
+            prod_tera_context
+                .get("delete_automated_backups")
+                .and_then(|v| v.as_bool()),
+            Some(false)
+        );
+        assert_eq!(
+            prod_default_tera_context
+                .get("backup_retention_period")
+                .and_then(|v| v.as_u64()),
+            Some(14)
+        );
+        assert_eq!(
+            prod_default_tera_context.get("backup_window").and_then(|v| v.as_str()),
+            Some("00:00-01:00")
+        );
+        assert_eq!(
+            test_cluster_tera_context
+                .get("backup_retention_period")
+                .and_then(|v| v.as_u64()),
+            Some(0)
+        );
+        assert_eq!(
+            test_cluster_replicas_tera_context
+                .get("backup_retention_period")
+                .and_then(|v| v.as_u64()),
+            Some(1)
+        );
+        assert_eq!(
+            test_cluster_tera_context
+                .get("delete_automated_backups")
+                .and_then(|v| v.as_bool()),
+            Some(true)
+        );
+
+        assert!(options(Some(0), None).validate_backups().is_ok());
+        assert!(options(Some(35), Some("23:45-00:15")).validate_backups().is_ok());
+        assert_eq!(
+            options(Some(36), None).validate_backups().unwrap_err(),
+            "backups can't be kept 36 days, at most 35 days"
+        );
+        assert_eq!(
+            options(None, Some("02:00-02:15")).validate_backups().unwrap_err(),
+            "backup window `02:00-02:15` must be formatted `hh24:mi-hh24:mi` in UTC and last at least 30 minutes"
+        );
+        assert!(options(None, Some("2:00-03:00")).validate_backups().is_err());
+        assert!(options(None, Some("24:00-01:00")).validate_backups().is_err());
+        assert!(options(None, Some("02:00")).validate_backups().is_err());
+        assert_eq!(
+            options_with_replicas(Some(0), None, 1).validate_backups().unwrap_err(),
+            "backups can't be disabled with 1 read replicas, replicas are created from backups"
+        );
+        assert!(options_with_replicas(Some(1), None, 1).validate_backups().is_ok());
+        assert_eq!(
+            options(None, Some("02:00-03:00")).validate_backups().unwrap_err(),
+            "backup window `02:00-03:00` can't overlap the `Tue:02:00-Tue:04:00` maintenance window"
+        );
+        assert!(options(None, Some("01:30-02:30")).validate_backups().is_err());
+        assert!(options(None, Some("23:00-03:00")).validate_backups().is_err());
+        assert!(options(None, Some("01:00-02:00")).validate_backups().is_ok());
+        assert!(options(None, Some("04:00-05:00")).validate_backups().is_ok());
+    }
+
+    #[test]
+    fn test_database_node_pool() {
+        // setup:
+        let options = |node_pool_label: Option<NodePoolLabel>| DatabaseOptions {
+            login: "".to_string(),
+            password: "".to_string(),
+            host: "".to_string(),
+            port: 5432,
+            disk_size_in_gib: 10,
+            database_disk_type: "gp2".to_string(),
+            replicas: 1,
+            iops: None,
+            storage_throughput: None,
+            deletion_protection: false,
+            sentinel_enabled: false,
+            resources_overcommit_percent: None,
+            parameters: BTreeMap::new(),
+            require_tls: false,
+            tls_certificates: None,
+            deletion_timeout_in_seconds: None,
+            helm_value_overrides: BTreeMap::new(),
+            deploy_timeout_in_seconds: None,
+            namespace: None,
+            clone_from: None,
+            backup_retention_days: None,
+            backup_window: None,
+            node_pool_label,
+        };
+        let databases_label = NodePoolLabel {
+            key: "qovery.com/pool".to_string(),
+            value: "databases".to_string(),
+        };
+        let node_pools = vec![
+            NodePoolScheduling::default(),
+            NodePoolScheduling {
+                labels: vec![("qovery.com/pool".to_string(), "databases".to_string())]
+                    .into_iter()
+                    .collect(),
+                taints: vec![Taint {
+                    key: "dedicated".to_string(),
+                    value: "databases".to_string(),
+                    effect: TaintEffect::NoSchedule,
+                }],
+            },
+        ];
+        let render = |options: &DatabaseOptions, node_pools: &[NodePoolScheduling]| {
+            let mut tera_context = TeraContext::new();
+            insert_database_node_pool_into_tera_context(options, node_pools, &mut tera_context)?;
+            for key in &[
+                "database_cpu_limit",
+                "database_cpu_request",
+                "database_db_name",
+                "database_login",
+                "database_password",
+                "database_ram_limit_mib",
+                "database_ram_request_mib",
+                "environment_id",
+                "fqdn",
+                "id",
+                "owner_id",
+                "sanitized_name",
+                "version",
+            ] {
+                tera_context.insert(*key, "value");
+            }
+            tera_context.insert("database_disk_size_in_gib", &10);
+            let template = std::fs::read_to_string("lib/aws/chart_values/postgresql/values.j2.yaml").unwrap();
+            Ok::<_, StringError>(tera::Tera::one_off(template.as_str(), &tera_context, false).unwrap())
+        };
+
+        // execute:
+        let pinned = render(&options(Some(databases_label.clone())), &node_pools).unwrap();
+        let unpinned = render(&options(None), &node_pools).unwrap();
+        let unknown_node_pools = render(&options(Some(databases_label.clone())), &[]).unwrap();
+        let missing_node_pool = render(
+            &options(Some(NodePoolLabel {
+                key: "qovery.com/pool".to_string(),
+                value: "analytics".to_string(),
+            })),
+            &node_pools,
+        );
+
+        // verify:
+        assert_eq!(
+            pinned
+                .matches("  nodeSelector: {\"qovery.com/pool\":\"databases\"}\n")
+                .count(),
+            2
+        );
+        assert_eq!(
+            pinned
+                .matches(
+                    "  tolerations: [{\"effect\":\"NoSchedule\",\"key\":\"dedicated\",\"operator\":\"Equal\",\"value\":\"databases\"}]\n"
+                )
+                .count(),
+            2
+        );
+        assert_eq!(unpinned.matches("  nodeSelector: {}\n").count(), 2);
+        assert_eq!(unpinned.matches("  tolerations: []\n").count(), 2);
+        assert_eq!(
+            unknown_node_pools
+                .matches("  nodeSelector: {\"qovery.com/pool\":\"databases\"}\n")
+                .count(),
+            2
+        );
+        assert_eq!(unknown_node_pools.matches("  tolerations: []\n").count(), 2);
+        assert_eq!(
+            missing_node_pool.unwrap_err(),
+            "the database is pinned to node pool label `qovery.com/pool=analytics`, but no node pool of the cluster has it"
+        );
+    }
+
+    #[test]
+    fn test_database_replicas() {
+        // setup:
+        let options = |replicas: u8| DatabaseOptions {
+            login: "".to_string(),
+            password: "".to_string(),
+            host: "".to_string(),
+            port: 3306,
+            disk_size_in_gib: 10,
+            database_disk_type: "gp2".to_string(),
+            replicas,
+            iops: None,
+            storage_throughput: None,
+            deletion_protection: false,
+            sentinel_enabled: false,
+            resources_overcommit_percent: None,
+            parameters: BTreeMap::new(),
+            require_tls: false,
+            tls_certificates: None,
+            deletion_timeout_in_seconds: None,
+            helm_value_overrides: BTreeMap::new(),
+            deploy_timeout_in_seconds: None,
+            namespace: None,
+            clone_from: None,
+            backup_retention_days: None,
+            backup_window: None,
+            node_pool_label: None,
+        };
+
+        // execute:
+        let standalone = options(0);
+        let replicated = options(5);
+        let too_many_replicas = options(6);
+
+        // verify:
+        assert_eq!(standalone.total_instances(), 1);
+        assert!(standalone.validate_replicas().is_ok());
+        assert_eq!(replicated.total_instances(), 6);
+        assert!(replicated.validate_replicas().is_ok());
+        assert_eq!(
+            too_many_replicas.validate_replicas().unwrap_err(),
+            "6 database replicas requested, maximum allowed is 5"
+        );
+    }
+
+    #[test]
+    fn test_redis_topology() {
+        // setup:
+        let options = |replicas: u8, sentinel_enabled: bool| DatabaseOptions {
+            login: "".to_string(),
+            password: "".to_string(),
+            host: "".to_string(),
+            port: 6379,
+            disk_size_in_gib: 10,
+            database_disk_type: "gp2".to_string(),
+            replicas,
+            iops: None,
+            storage_throughput: None,
+            deletion_protection: false,
+            sentinel_enabled,
+            resources_overcommit_percent: None,
+            parameters: BTreeMap::new(),
+            require_tls: false,
+            tls_certificates: None,
+            deletion_timeout_in_seconds: None,
+            helm_value_overrides: BTreeMap::new(),
+            deploy_timeout_in_seconds: None,
+            namespace: None,
+            clone_from: None,
+            backup_retention_days: None,
+            backup_window: None,
+            node_pool_label: None,
+        };
+        let topology = |options: &DatabaseOptions| {
+            let mut context = TeraContext::new();
+            insert_redis_topology_into_tera_context(options, &mut context);
+            (
+                context.get("redis_cluster_enabled").unwrap().as_bool().unwrap(),
+                context.get("redis_sentinel_enabled").unwrap().as_bool().unwrap(),
+                context.get("redis_slave_count").unwrap().as_u64().unwrap(),
+            )
+        };
+
+        // execute:
+        let single_node = options(0, false);
+        let replicated = options(2, false);
+        let sentinel = options(2, true);
+
+        // verify:
+        assert!(single_node.validate_redis_topology().is_ok());
+        assert_eq!(topology(&single_node), (false, false, 0));
+        assert!(replicated.validate_redis_topology().is_ok());
+        assert_eq!(topology(&replicated), (true, false, 2));
+        assert!(sentinel.validate_redis_topology().is_ok());
+        assert_eq!(topology(&sentinel), (true, true, 3));
+        assert_eq!(
+            options(1, false).validate_redis_topology().unwrap_err(),
+            "1 Redis replica requested, use either 0 or at least 2 replicas"
+        );
+        assert_eq!(
+            options(0, true).validate_redis_topology().unwrap_err(),
+            "Redis sentinel requires at least 2 replicas, 0 requested"
+        );
+        assert!(options(6, true).validate_redis_topology().is_err());
+    }
+
+    #[test]
+    fn test_database_disk_performances() {
+        // setup:
+        let options = |disk_type: &str, iops: Option<u32>, storage_throughput: Option<u32>| DatabaseOptions {
+            login: "".to_string(),
+            password: "".to_string(),
+            host: "".to_string(),
+            port: 3306,
+            disk_size_in_gib: 100,
+            database_disk_type: disk_type.to_string(),
+            replicas: 0,
+            iops,
+            storage_throughput,
+            deletion_protection: false,
+            sentinel_enabled: false,
+            resources_overcommit_percent: None,
+            parameters: BTreeMap::new(),
+            require_tls: false,
+            tls_certificates: None,
+            deletion_timeout_in_seconds: None,
+            helm_value_overrides: BTreeMap::new(),
+            deploy_timeout_in_seconds: None,
+            namespace: None,
+            clone_from: None,
+            backup_retention_days: None,
+            backup_window: None,
+            node_pool_label: None,
+        };
+
+        // execute & verify:
+        assert!(options("gp2", None, None).validate_disk_performances().is_ok());
+        assert!(options("io1", Some(3000), None).validate_disk_performances().is_ok());
+        assert!(options("gp3", Some(12000), Some(500))
+            .validate_disk_performances()
+            .is_ok());
+        assert_eq!(
+            options("gp2", Some(3000), None)
+                .validate_disk_performances()
+                .unwrap_err(),
+            "IOPS can't be provisioned with `gp2` disk type, only `io1` and `gp3` disk types support it"
+        );
+        assert_eq!(
+            options("io1", None, None).validate_disk_performances().unwrap_err(),
+            "IOPS have to be provisioned with `io1` disk type"
+        );
+        assert_eq!(
+            options("io1", Some(3000), Some(500))
+                .validate_disk_performances()
+                .unwrap_err(),
+            "storage throughput can't be provisioned with `io1` disk type, only `gp3` disk type supports it"
+        );
+    }
+
+    #[test]
+    fn test_database_disk_size() {
+        // setup:
+        let options = |disk_size_in_gib: u32| DatabaseOptions {
+            login: "".to_string(),
+            password: "".to_string(),
+            host: "".to_string(),
+            port: 5432,
+            disk_size_in_gib,
+            database_disk_type: "gp2".to_string(),
+            replicas: 0,
+            iops: None,
+            storage_throughput: None,
+            deletion_protection: false,
+            sentinel_enabled: false,
+            resources_overcommit_percent: None,
+            parameters: BTreeMap::new(),
+            require_tls: false,
+            tls_certificates: None,
+            deletion_timeout_in_seconds: None,
+            helm_value_overrides: BTreeMap::new(),
+            deploy_timeout_in_seconds: None,
+            namespace: None,
+            clone_from: None,
+            backup_retention_days: None,
+            backup_window: None,
+            node_pool_label: None,
+        };
+        let persistent_volume_claims = serde_json::from_str::<KubernetesList<PersistentVolumeClaim>>(
+            r#"{"items": [
+                {"metadata": {"name": "data-postgresql-0", "annotations": {"databaseId": "z1234"}},
+                 "spec": {"resources": {"requests": {"storage": "20Gi"}}}, "status": {"capacity": {"storage": "20Gi"}}},
+                {"metadata": {"name": "data-postgresql-read-0", "annotations": {"databaseId": "z1234"}},
+                 "spec": {"resources": {"requests": {"storage": "30Gi"}}}},
+                {"metadata": {"name": "data-other-0"}, "spec": {"resources": {"requests": {"storage": "1Ti"}}}}
+            ]}"#,
+        )
+        .unwrap();
+
+        // execute:
+        let deployed_disk_size = persistent_volume_claims_disk_size(&persistent_volume_claims.items, "z1234");
+
+        // verify:
+        assert_eq!(deployed_disk_size, Some(30));
+        assert_eq!(
+            persistent_volume_claims_disk_size(&persistent_volume_claims.items, "z5678"),
+            None
+        );
+        assert!(options(10).validate_disk_size(None).is_ok());
+        assert!(options(30).validate_disk_size(deployed_disk_size).is_ok());
+        assert!(options(50).validate_disk_size(deployed_disk_size).is_ok());
+        assert_eq!(
+            options(20).validate_disk_size(deployed_disk_size).unwrap_err(),
+            "disk size of 20 GiB requested, it can't be smaller than the 30 GiB already provisioned"
+        );
+    }
+
+    #[test]
+    fn test_volume_expansion_is_allowed() {
+        // setup:
+        let persistent_volume_claims = serde_json::from_str::<KubernetesList<PersistentVolumeClaim>>(
+            r#"{"items": [
+                {"metadata": {"name": "data-postgresql-0", "annotations": {"databaseId": "z1234"}},
+                 "spec": {"resources": {"requests": {"storage": "20Gi"}}, "storageClassName": "aws-ebs-gp2-0"}},
+                {"metadata": {"name": "data-mysql-0", "annotations": {"databaseId": "z5678"}},
+                 "spec": {"resources": {"requests": {"storage": "20Gi"}}, "storageClassName": "local-path"}},
+                {"metadata": {"name": "data-redis-0", "annotations": {"databaseId": "z9012"}},
+                 "spec": {"resources": {"requests": {"storage": "20Gi"}}}},
+                {"metadata": {"name": "data-mongodb-0", "annotations": {"databaseId": "z3456"}},
+                 "spec": {"resources": {"requests": {"storage": "20Gi"}}, "storageClassName": "unknown"}}
+            ]}"#,
+        )
+        .unwrap();
+        let storage_classes = |default_allows_expansion: bool| {
+            serde_json::from_value::<KubernetesList<StorageClass>>(serde_json::json!({"items": [
+                {"metadata": {"name": "aws-ebs-gp2-0"}, "allowVolumeExpansion": true},
+                {"metadata": {"name": "local-path"}},
+                {"metadata": {"name": "gp2", "annotations": {"storageclass.kubernetes.io/is-default-class": "true"}},
+                 "allowVolumeExpansion": default_allows_expansion}
+            ]}))
+            .unwrap()
+            .items
+        };
+        let check = |database_id: &str, storage_classes: &[StorageClass]| {
+            check_volume_expansion_is_allowed(
+                &database_persistent_volume_claims(&persistent_volume_claims.items, database_id),
+                storage_classes,
+            )
+        };
+
+        // execute & verify:
+        assert!(check("z1234", &storage_classes(false)).is_ok());
+        assert_eq!(
+            check("z5678", &storage_classes(true)).unwrap_err(),
+            "storage class `local-path` of volume `data-mysql-0` doesn't allow volume expansion"
+        );
+        // claims without storage class use the default one
+        assert!(check("z9012", &storage_classes(true)).is_ok());
+        assert_eq!(
+            check("z9012", &storage_classes(false)).unwrap_err(),
+            "storage class `gp2` of volume `data-redis-0` doesn't allow volume expansion"
+        );
+        assert_eq!(
+            check("z3456", &storage_classes(true)).unwrap_err(),
+            "storage class of volume `data-mongodb-0` can't be found"
+        );
+    }
+
+    #[test]
+    fn test_database_parameters() {
+        // setup:
+        let options = |parameters: Vec<(&str, &str)>| DatabaseOptions {
+            login: "".to_string(),
+            password: "".to_string(),
+            host: "".to_string(),
+            port: 3306,
+            disk_size_in_gib: 10,
+            database_disk_type: "gp2".to_string(),
+            replicas: 0,
+            iops: None,
+            storage_throughput: None,
+            deletion_protection: false,
+            sentinel_enabled: false,
+            resources_overcommit_percent: None,
+            parameters: parameters
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            require_tls: false,
+            tls_certificates: None,
+            deletion_timeout_in_seconds: None,
+            helm_value_overrides: BTreeMap::new(),
+            deploy_timeout_in_seconds: None,
+            namespace: None,
+            clone_from: None,
+            backup_retention_days: None,
+            backup_window: None,
+            node_pool_label: None,
+        };
+        let rendered_parameters = |options: &DatabaseOptions| {
+            let mut context = TeraContext::new();
+            context.insert("database_parameters", &options.parameters);
+            tera::Tera::one_off(
+                "{%- for name, value in database_parameters %}{{ name }}={{ value }};{% endfor %}\
+                 {%- if \"log_bin_trust_function_creators\" not in database_parameters %}default{% endif %}",
+                &context,
+                false,
+            )
+            .unwrap()
+        };
+
+        // execute:
+        let tuned = options(vec![("max_connections", "500"), ("innodb_buffer_pool_size", "1G")]);
+        let overridden = options(vec![("log_bin_trust_function_creators", "0")]);
+
+        // verify:
+        assert!(options(vec![]).validate_parameters().is_ok());
+        assert!(tuned.validate_parameters().is_ok());
+        assert!(options(vec![("innodb.flush-method", "O_DIRECT")])
+            .validate_parameters()
+            .is_ok());
+        assert!(options(vec![(" ", "1")]).validate_parameters().is_err());
+        assert!(options(vec![("Max_connections", "1")]).validate_parameters().is_err());
+        assert!(options(vec![("1max_connections", "1")]).validate_parameters().is_err());
+        assert!(options(vec![("max_connections\ninit_file", "1")])
+            .validate_parameters()
+            .is_err());
+        assert!(options(vec![("init_connect", "1\"\n}")]).validate_parameters().is_err());
+        assert!(options(vec![("init_connect", "1\ninit_file=/tmp/x")])
+            .validate_parameters()
+            .is_err());
+        assert!(options(vec![("init_connect", "1\\")]).validate_parameters().is_err());
+        assert_eq!(
+            rendered_parameters(&tuned),
+            "innodb_buffer_pool_size=1G;max_connections=500;default"
+        );
+        assert_eq!(rendered_parameters(&overridden), "log_bin_trust_function_creators=0;");
+    }
+
+    #[test]
+    fn test_helm_values_image_tag() {
+        // setup:
+        let values =
+            serde_json::json!({"image": {"registry": "docker.io", "tag": "8.0.24"}, "fullnameOverride": "mysql"});
+        let no_image_values = serde_json::json!({"fullnameOverride": "mysql"});
+
+        // execute & verify:
+        assert_eq!(helm_values_image_tag(&values), Some("8.0.24".to_string()));
+        assert_eq!(helm_values_image_tag(&no_image_values), None);
+    }
+
+    #[test]
+    fn test_database_resources() {
+        // setup:
+        let options = |resources_overcommit_percent: Option<u32>| DatabaseOptions {
+            login: "".to_string(),
+            password: "".to_string(),
+            host: "".to_string(),
+            port: 5432,
+            disk_size_in_gib: 10,
+            database_disk_type: "gp2".to_string(),
+            replicas: 0,
+            iops: None,
+            storage_throughput: None,
+            deletion_protection: false,
+            sentinel_enabled: false,
+            resources_overcommit_percent,
+            parameters: BTreeMap::new(),
+            require_tls: false,
+            tls_certificates: None,
+            deletion_timeout_in_seconds: None,
+            helm_value_overrides: BTreeMap::new(),
+            deploy_timeout_in_seconds: None,
+            namespace: None,
+            clone_from: None,
+            backup_retention_days: None,
+            backup_window: None,
+            node_pool_label: None,
+        };
+
+        // execute:
+        let unlimited = options(None).resources("500m", 1024).unwrap();
+        let guaranteed = options(Some(100)).resources("500m", 1024).unwrap();
+        let burstable = options(Some(150)).resources("500m", 1024).unwrap();
+        let burstable_cores = options(Some(200)).resources("1.5", 300).unwrap();
+        let limits_lower_than_requests = options(Some(80)).resources("500m", 1024);
+        let invalid_cpu = options(None).resources("half", 1024);
+
+        // verify:
+        assert_eq!(unlimited.cpu_request, "500m");
+        assert_eq!(unlimited.cpu_limit, None);
+        assert_eq!(unlimited.ram_request_mib, 1024);
+        assert_eq!(unlimited.ram_limit_mib, None);
+        assert_eq!(guaranteed.cpu_request, "500m");
+        assert_eq!(guaranteed.cpu_limit.as_deref(), Some("500m"));
+        assert_eq!(guaranteed.ram_request_mib, 1024);
+        assert_eq!(guaranteed.ram_limit_mib, Some(1024));
+        assert_eq!(burstable.cpu_request, "500m");
+        assert_eq!(burstable.cpu_limit.as_deref(), Some("750m"));
+        assert_eq!(burstable.ram_request_mib, 1024);
+        assert_eq!(burstable.ram_limit_mib, Some(1536));
+        assert_eq!(burstable_cores.cpu_limit.as_deref(), Some("3000m"));
+        assert_eq!(burstable_cores.ram_limit_mib, Some(600));
+        assert_eq!(
+            limits_lower_than_requests.unwrap_err(),
+            "resources overcommit of 80% would set limits lower than requests, it must be at least 100%"
+        );
+        assert_eq!(invalid_cpu.unwrap_err(), "half is not a valid cpu quantity");
+    }
+
+    #[test]
+    fn test_database_tls() {
+        // setup:
+        let options =
+            |require_tls: bool, replicas: u8, tls_certificates: Option<DatabaseTlsCertificates>| DatabaseOptions {
+                login: "superuser".to_string(),
+                password: "".to_string(),
+                host: "mysql.qovery.io".to_string(),
+                port: 3306,
+                disk_size_in_gib: 10,
+                database_disk_type: "gp2".to_string(),
+                replicas,
+                iops: None,
+                storage_throughput: None,
+                deletion_protection: false,
+                sentinel_enabled: false,
+                resources_overcommit_percent: None,
+                parameters: BTreeMap::new(),
+                require_tls,
+                tls_certificates,
+                deletion_timeout_in_seconds: None,
+                helm_value_overrides: BTreeMap::new(),
+                deploy_timeout_in_seconds: None,
+                namespace: None,
+                clone_from: None,
+                backup_retention_days: None,
+                backup_window: None,
+                node_pool_label: None,
+            };
+        let certificates = DatabaseTlsCertificates {
+            ca: "ca".to_string(),
+            certificate: "certificate".to_string(),
+            key: "key".to_string(),
+        };
+        let chart_values = |options: &DatabaseOptions| {
+            let mut context = TeraContext::new();
+            for name in &[
+                "version",
+                "sanitized_name",
+                "owner_id",
+                "environment_id",
+                "id",
+                "database_login",
+                "database_password",
+                "database_total_cpus",
+                "fqdn",
+            ] {
+                context.insert(*name, "value");
+            }
+            context.insert("database_replicas", &options.replicas);
+            context.insert("database_ram_size_in_mib", &512);
+            context.insert("database_disk_size_in_gib", &options.disk_size_in_gib);
+            context.insert("database_parameters", &options.parameters);
+            insert_database_tls_into_tera_context(options, &mut context);
+            insert_database_resources_into_tera_context(&options.resources("500m", 512).unwrap(), &mut context);
+            insert_database_node_pool_into_tera_context(options, &[], &mut context).unwrap();
+
+            let template = std::fs::read_to_string("lib/aws/chart_values/mysql/values.j2.yaml").unwrap();
+            tera::Tera::one_off(template.as_str(), &context, false).unwrap()
+        };
+
+        // execute:
+        let plaintext = options(false, 0, None);
+        let tls = options(true, 0, Some(certificates.clone()));
+
+        // verify:
+        assert!(chart_values(&plaintext).contains("tls:\n  enabled: false"));
+        assert!(!chart_values(&plaintext).contains("require_secure_transport"));
+        assert_eq!(
+            plaintext.mysql_connection_string("mydb"),
+            "mysql://superuser@mysql.qovery.io:3306/mydb"
+        );
+
+        assert!(chart_values(&tls).contains("tls:\n  enabled: true"));
+        assert!(chart_values(&tls).contains("limits: {}\n    requests:\n      cpu: \"500m\"\n      memory: \"512Mi\""));
+        assert!(chart_values(&tls).contains("require_secure_transport=ON"));
+        assert!(chart_values(&tls).contains(&format!("ca: {}", base64::encode("ca"))));
+        assert_eq!(
+            tls.mysql_connection_string("mydb"),
+            "mysql://superuser@mysql.qovery.io:3306/mydb?ssl-mode=REQUIRED"
+        );
+
+        assert!(tls.validate_tls(false).is_ok());
+        assert!(tls.validate_tls(true).is_err());
+        assert!(options(true, 0, None).validate_tls(true).is_ok());
+        assert!(options(true, 2, None).validate_tls(false).is_err());
+    }
+
+    fn application_chart_context(private_port: Option<u16>) -> TeraContext {
+        let mut context = TeraContext::new();
+        for name in &[
+            "sanitized_name",
+            "namespace",
+            "owner_id",
+            "environment_id",
+            "id",
+            "image_name_with_tag",
+        ] {
+            context.insert(*name, "value");
+        }
+        context.insert("is_storage", &false);
+        context.insert("is_registry_secret", &false);
+        context.insert("total_instances", &1);
+        context.insert("total_cpus", "250m");
+        context.insert("cpu_burst", "500m");
+        context.insert("total_ram_in_mib", &256);
+        context.insert("start_timeout_in_seconds", &60);
+        context.insert("environment_variables", &Vec::<String>::new());
+        context.insert("secret_environment_variables", &Vec::<String>::new());
+        context.insert("init_containers", &Vec::<InitContainerDataTemplate>::new());
+        context.insert("autoscaling", &Option::<AutoscalingDataTemplate>::None);
+        context.insert("probes", &Option::<ProbesDataTemplate>::None);
+        context.insert("is_private_port", &private_port.is_some());
+        if let Some(private_port) = private_port {
+            context.insert("private_port", &private_port);
+        }
+        context
+    }
+
+    fn render_application_chart(template: &str, context: &TeraContext) -> String {
+        let template = std::fs::read_to_string(format!("lib/aws/charts/q-application/templates/{}", template)).unwrap();
+        tera::Tera::one_off(template.as_str(), context, false).unwrap()
+    }
+
+    #[test]
+    fn test_application_chart_registry_secret() {
+        // setup:
+        let private_image = |docker_json_config: &str| Image {
+            registry_name: Some("private-registry".to_string()),
+            registry_docker_json_config: Some(docker_json_config.to_string()),
+            ..Default::default()
+        };
+        let docr_image = Image {
+            registry_name: Some("docr-registry".to_string()),
+            ..Default::default()
+        };
+        let render = |template: &str, image: &Image| {
+            let mut context = application_chart_context(Some(8080));
+            insert_registry_secret_into_tera_context("app-id", image, &mut context);
+            render_application_chart(template, &context)
+        };
+
+        // execute:
+        let secret = render("registry-secret.j2.yaml", &private_image("b2xkLWNyZWRlbnRpYWxz"));
+        let rotated_secret = render("registry-secret.j2.yaml", &private_image("bmV3LWNyZWRlbnRpYWxz"));
+        let variables_secret = render("secret.j2.yaml", &private_image("b2xkLWNyZWRlbnRpYWxz"));
+        let deployment = render("deployment.j2.yaml", &private_image("b2xkLWNyZWRlbnRpYWxz"));
+        let docr_secret = render("registry-secret.j2.yaml", &docr_image);
+        let docr_deployment = render("deployment.j2.yaml", &docr_image);
+        let public_deployment = render("deployment.j2.yaml", &Image::default());
+
+        // verify:
+        assert!(secret.contains("name: registry-token-app-id"));
+        assert!(secret.contains(".dockerconfigjson: b2xkLWNyZWRlbnRpYWxz\ntype: kubernetes.io/dockerconfigjson"));
+        assert!(rotated_secret.contains("name: registry-token-app-id"));
+        assert!(rotated_secret.contains(".dockerconfigjson: bmV3LWNyZWRlbnRpYWxz"));
+        // the pods checksum the variables secret, a rotated registry token must not restart them
+        assert!(!variables_secret.contains("dockerconfigjson"));
+        assert!(deployment.contains("imagePullSecrets:\n        - name: registry-token-app-id"));
+        assert!(!docr_secret.contains("kubernetes.io/dockerconfigjson"));
+        assert!(docr_deployment.contains("imagePullSecrets:\n        - name: docr-registry"));
+        assert!(!public_deployment.contains("imagePullSecrets"));
+    }
+
+    #[test]
+    fn test_worker_application_chart() {
+        // setup:
+        let render = |template: &str, private_port: Option<u16>| {
+            render_application_chart(template, &application_chart_context(private_port))
+        };
+
+        // execute:
+        let worker_service = render("service.j2.yaml", None);
+        let worker_deployment = render("deployment.j2.yaml", None);
+        let api_service = render("service.j2.yaml", Some(8080));
+        let api_deployment = render("deployment.j2.yaml", Some(8080));
+
+        // verify:
+        assert!(!worker_service.contains("kind: Service"));
+        assert!(!worker_deployment.contains("readinessProbe"));
+        assert!(!worker_deployment.contains("containerPort"));
+        assert!(!worker_deployment.contains("livenessProbe"));
+
+        assert!(api_service.contains("kind: Service"));
+        assert!(api_deployment.contains("readinessProbe"));
+        assert!(api_deployment.contains("livenessProbe"));
+        assert!(api_deployment.contains(
+            r#"checksum/configmap: {{ include (print $.Template.BasePath "/configmap.yaml") . | sha256sum }}"#
+        ));
+    }
+
+    #[test]
+    fn test_application_chart_init_containers() {
+        // setup:
+        let migrate = InitContainer {
+            image: "registry.local:5000/migrate:v2".to_string(),
+            command: vec!["./migrate".to_string(), "--to=latest".to_string()],
+            environment_variables: vec![("DATABASE_URL".to_string(), "postgres://db:5432".to_string())]
+                .into_iter()
+                .collect(),
+        };
+        let warmup = InitContainer {
+            image: "busybox".to_string(),
+            command: vec![],
+            environment_variables: BTreeMap::new(),
+        };
+        let mut context = application_chart_context(Some(8080));
+        context.insert(
+            "init_containers",
+            &vec![
+                InitContainerDataTemplate::new(&migrate),
+                InitContainerDataTemplate::new(&warmup),
+            ],
+        );
+
+        // execute:
+        let deployment = render_application_chart("deployment.j2.yaml", &context);
+        let without_init_containers =
+            render_application_chart("deployment.j2.yaml", &application_chart_context(Some(8080)));
+
+        // verify:
+        let position = |needle: &str| deployment.find(needle).unwrap();
+        assert!(position("initContainers:") < position("- name: value-init-1"));
+        assert!(position("image: \"registry.local:5000/migrate:v2\"") < position("- \"--to=latest\""));
+        assert!(position("- name: \"DATABASE_URL\"") < position("value: \"postgres://db:5432\""));
+        assert!(position("- name: value-init-1") < position("- name: value-init-2"));
+        assert!(position("- name: value-init-2") < position("image: \"busybox\""));
+        assert!(position("image: \"busybox\"") < position("containers:\n        - name: value\n"));
+        assert_eq!(deployment.matches("command:").count(), 1);
+        assert!(!without_init_containers.contains("initContainers"));
+
+        let image = migrate.to_image("app-id");
+        assert_eq!(image.name, "registry.local:5000/migrate");
+        assert_eq!(image.tag, "v2");
+        assert_eq!(warmup.to_image("app-id").tag, "latest");
+    }
+
+    #[test]
+    fn test_application_chart_autoscaling() {
+        // setup:
+        let autoscaling = Autoscaling {
+            min_instances: 2,
+            max_instances: 10,
+            target_cpu_percent: 75,
+        };
+        let mut context = application_chart_context(Some(8080));
+        context.insert("total_instances", &3);
+        context.insert("autoscaling", &Some(AutoscalingDataTemplate::new(&autoscaling)));
+
+        // execute:
+        let hpa = render_application_chart("hpa.j2.yaml", &context);
+        let deployment = render_application_chart("deployment.j2.yaml", &context);
+        let mut statefulset_context = context.clone();
+        statefulset_context.insert("is_storage", &true);
+        statefulset_context.insert("storage", &Vec::<String>::new());
+        let statefulset = render_application_chart("statefulset.j2.yaml", &statefulset_context);
+        let without_autoscaling = render_application_chart("hpa.j2.yaml", &application_chart_context(Some(8080)));
+        let mut fixed_context = application_chart_context(Some(8080));
+        fixed_context.insert("total_instances", &3);
+        let fixed_deployment = render_application_chart("deployment.j2.yaml", &fixed_context);
+
+        // verify:
+        assert!(hpa.contains("kind: HorizontalPodAutoscaler"));
+        assert!(hpa.contains("    kind: Deployment\n    name: value\n"));
+        assert!(hpa.contains("  minReplicas: 2\n  maxReplicas: 10\n  targetCPUUtilizationPercentage: 75"));
+        assert!(!deployment.contains("replicas:"));
+        assert!(!statefulset.contains("replicas:"));
+        assert!(without_autoscaling.trim().is_empty());
+        assert!(fixed_deployment.contains("spec:\n  replicas: 3\n"));
+    }
+
+    #[test]
+    fn test_application_chart_probes() {
+        // setup:
+        let probes = Probes {
+            readiness_path: Some("/health/ready".to_string()),
+            liveness_path: Some("/health/live".to_string()),
+            port: Some(9000),
+        };
+        let readiness_only = Probes {
+            readiness_path: Some("/health".to_string()),
+            liveness_path: None,
+            port: None,
+        };
+        let context = |private_port: Option<u16>, probes: &Probes| {
+            let mut context = application_chart_context(private_port);
+            context.insert("probes", &Some(ProbesDataTemplate::new(probes, private_port)));
+            context
+        };
+
+        // execute:
+        let deployment = render_application_chart("deployment.j2.yaml", &context(Some(8080), &probes));
+        let mut statefulset_context = context(Some(8080), &probes);
+        statefulset_context.insert("is_storage", &true);
+        statefulset_context.insert("storage", &Vec::<String>::new());
+        let statefulset = render_application_chart("statefulset.j2.yaml", &statefulset_context);
+        let readiness_only_deployment =
+            render_application_chart("deployment.j2.yaml", &context(Some(8080), &readiness_only));
+        let worker_deployment = render_application_chart("deployment.j2.yaml", &context(None, &probes));
+
+        // verify:
+        for rendered in &[&deployment, &statefulset] {
+            assert!(rendered.contains(
+                "readinessProbe:\n            httpGet:\n              path: \"/health/ready\"\n              port: 9000"
+            ));
+            assert!(rendered.contains(
+                "livenessProbe:\n            httpGet:\n              path: \"/health/live\"\n              port: 9000"
+            ));
+            assert!(!rendered.contains("tcpSocket"));
+        }
+        assert!(readiness_only_deployment.contains("path: \"/health\"\n              port: 8080"));
+        assert!(readiness_only_deployment.contains("livenessProbe:\n            tcpSocket:\n              port: 8080"));
+        assert!(worker_deployment.contains("path: \"/health/ready\""));
+        assert!(!worker_deployment.contains("containerPort"));
+        assert!(worker_deployment.contains("path: \"/health/live\""));
+    }
+
+    #[test]
+    fn test_pause_and_resume_workloads() {
+        // setup:
+        // fake kubectl: commands are recorded, `get` answers the deployment of the test
+        let fake_kubectl_dir = tempfile::tempdir().unwrap();
+        let commands_log = fake_kubectl_dir.path().join("commands.log");
+        let deployment_json = fake_kubectl_dir.path().join("deployment.json");
+        let fake_kubectl = fake_kubectl_dir.path().join("kubectl");
+        std::fs::write(
+            &fake_kubectl,
+            format!(
+                "#!/bin/sh\nprintf '%s\\n' \"$*\" >> {}\nif [ \"$1\" = \"get\" ]; then cat {}; fi\n",
+                commands_log.display(),
+                deployment_json.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&fake_kubectl, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let path = format!(
+            "{}:{}",
+            fake_kubectl_dir.path().to_str().unwrap(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+        let deployment = |replicas: u32, annotations: serde_json::Value| {
+            std::fs::write(
+                &deployment_json,
+                serde_json::json!({"items": [{
+                    "kind": "Deployment",
+                    "metadata": {"name": "app-web", "labels": {"appId": "web"}, "annotations": annotations},
+                    "spec": {"replicas": replicas}
+                }]})
+                .to_string(),
+            )
+            .unwrap();
+        };
+        let commands = || {
+            let commands = std::fs::read_to_string(&commands_log).unwrap_or_default();
+            let _ = std::fs::remove_file(&commands_log);
+            commands.lines().map(|line| line.to_string()).collect::<Vec<_>>()
+        };
+        let pause = || {
+            pause_workloads(
+                "/tmp/fake-kubeconfig".to_string(),
+                vec![("PATH", path.as_str())],
+                "env",
+                ScalingKind::Deployment,
+                "appId=web",
+            )
+        };
+        let resume = |default_instances: u32| {
+            resume_workloads(
+                "/tmp/fake-kubeconfig".to_string(),
+                vec![("PATH", path.as_str())],
+                "env",
+                ScalingKind::Deployment,
+                "appId=web",
+                default_instances,
+            )
+        };
+
+        // execute:
+        deployment(3, serde_json::json!({}));
+        let paused = pause();
+        let pause_commands = commands();
+        deployment(0, serde_json::json!({"qovery.com/instances-before-pause": "3"}));
+        let paused_again = pause();
+        let pause_again_commands = commands();
+        let resumed_instances = resume(2);
+        let resume_commands = commands();
+        deployment(0, serde_json::json!({}));
+        let resumed_without_annotation = resume(2);
+
+        // verify:
+        assert!(paused.is_ok());
+        assert_eq!(
+            pause_commands,
+            vec![
+                "get deployment -o json -n env -l appId=web",
+                "-n env annotate deployment --selector appId=web --overwrite qovery.com/instances-before-pause=3",
+                "-n env scale --replicas 0 deployment --selector appId=web",
+            ]
+        );
+        assert!(paused_again.is_ok());
+        assert_eq!(
+            pause_again_commands,
+            vec![
+                "get deployment -o json -n env -l appId=web",
+                "-n env scale --replicas 0 deployment --selector appId=web",
+            ]
+        );
+        assert_eq!(resumed_instances.unwrap(), 3);
+        assert_eq!(
+            resume_commands,
+            vec![
+                "get deployment -o json -n env -l appId=web",
+                "-n env scale --replicas 3 deployment --selector appId=web",
+                "-n env annotate deployment --selector appId=web --overwrite qovery.com/instances-before-pause-",
+            ]
+        );
+        assert_eq!(resumed_without_annotation.unwrap(), 2);
+    }
+
+    #[test]
+    fn test_helm_deployment_failure_details() {
+        // setup:
+        let failed_row = serde_json::from_str::<HelmHistoryRow>(
+            r#"{"revision":3,"updated":"2021-06-01T10:00:00Z","status":"failed","chart":"mysql-8.2.3",
+            "app_version":"8.0.22","description":"Release \"mysql-z1a2b3c4\" failed: timed out waiting for the condition"}"#,
+        )
+        .unwrap();
+        let event = |type_: &str, last_timestamp: &str, name: &str, message: &str| {
+            serde_json::json!({
+                "type": type_, "lastTimestamp": last_timestamp, "reason": "BackOff", "message": message,
+                "involvedObject": {"kind": "Pod", "name": name}
+            })
+        };
+        let events = serde_json::from_value::<KubernetesList<KubernetesEvent>>(serde_json::json!({
+            "items": [
+                event("Warning", "2021-06-01T10:02:00Z", "mysql-0", "Back-off pulling image \"mysql:8.0.99\""),
+                event("Normal", "2021-06-01T10:03:00Z", "mysql-0", "Pulling image \"mysql:8.0.99\""),
+                event("Warning", "2021-06-01T10:01:00Z", "mysql-0", "Failed to pull image \"mysql:8.0.99\": not found"),
+            ]
+        }))
+        .unwrap()
+        .items;
+
+        // execute:
+        let details = helm_deployment_failure_details(Some(&failed_row), events);
+        let no_history_details = helm_deployment_failure_details(None, vec![]);
+
+        // verify:
+        assert_eq!(
+            details,
+            "helm release is failed: Release \"mysql-z1a2b3c4\" failed: timed out waiting for the condition. \
+            Last warning events: Pod mysql-0: Failed to pull image \"mysql:8.0.99\": not found, \
+            Pod mysql-0: Back-off pulling image \"mysql:8.0.99\""
+        );
+        assert_eq!(no_history_details, "helm release has no history");
+    }
+
+    #[test]
+    fn test_wait_until_ready() {
+        // setup:
+        let database = MySQL::new(
+            Context::new(
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                false,
+                None,
+                vec![],
+                None,
+            ),
+            "mysqlid",
+            Action::Create,
+            "mysql",
+            "8",
+            "mysqltest.qovery.io",
+            "mysqlid",
+            "1".to_string(),
+            512,
+            "db.t2.micro",
+            DatabaseOptions {
+                login: "".to_string(),
+                password: "".to_string(),
+                host: "".to_string(),
+                port: 3306,
+                disk_size_in_gib: 10,
+                database_disk_type: "gp2".to_string(),
+                replicas: 0,
+                iops: None,
+                storage_throughput: None,
+                deletion_protection: false,
+                sentinel_enabled: false,
+                resources_overcommit_percent: None,
+                parameters: BTreeMap::new(),
+                require_tls: false,
+                tls_certificates: None,
+                deletion_timeout_in_seconds: None,
+                helm_value_overrides: BTreeMap::new(),
+                deploy_timeout_in_seconds: None,
+                namespace: None,
+                clone_from: None,
+                backup_retention_days: None,
+                backup_window: None,
+                node_pool_label: None,
+            },
+            vec![],
+        );
+        let listeners = vec![];
+        let listeners_helper = ListenersHelper::new(&listeners);
+        let kubectl_calls = Cell::new(0);
+        // fake kubectl: pods are not ready on the first call, then ready
+        let fake_kubectl = || {
+            kubectl_calls.set(kubectl_calls.get() + 1);
+            Ok(Some(kubectl_calls.get() > 1))
+        };
+
+        // execute:
+        let ready = wait_until_ready_with(
+            &database,
+            &listeners_helper,
+            Duration::from_secs(1),
+            Duration::from_millis(1),
+            fake_kubectl,
+        );
+        let never_ready = wait_until_ready_with(
+            &database,
+            &listeners_helper,
+            Duration::from_millis(20),
+            Duration::from_millis(1),
+            || Ok(Some(false)),
+        );
+
+        // verify:
+        assert!(ready.is_ok());
+        assert_eq!(kubectl_calls.get(), 2);
+        assert_eq!(
+            never_ready.unwrap_err().message,
+            Some("MySQL database mysql (mysqlid) is not ready after 0 seconds".to_string())
+        );
+    }
+
+    #[test]
+    fn test_database_deploy_timeout() {
+        // setup:
+        let mysql = |deploy_timeout_in_seconds: Option<u32>| {
+            MySQL::new(
+                Context::new(
+                    "".to_string(),
+                    "".to_string(),
+                    "".to_string(),
+                    false,
+                    None,
+                    vec![],
+                    None,
+                ),
+                "mysqlid",
+                Action::Create,
+                "mysql",
+                "8",
+                "mysqltest.qovery.io",
+                "mysqlid",
+                "1".to_string(),
+                512,
+                "db.t2.micro",
+                DatabaseOptions {
+                    login: "".to_string(),
+                    password: "".to_string(),
+                    host: "".to_string(),
+                    port: 3306,
+                    disk_size_in_gib: 10,
+                    database_disk_type: "gp2".to_string(),
+                    replicas: 0,
+                    iops: None,
+                    storage_throughput: None,
+                    deletion_protection: false,
+                    sentinel_enabled: false,
+                    resources_overcommit_percent: None,
+                    parameters: BTreeMap::new(),
+                    require_tls: false,
+                    tls_certificates: None,
+                    deletion_timeout_in_seconds: None,
+                    helm_value_overrides: BTreeMap::new(),
+                    deploy_timeout_in_seconds,
+                    namespace: None,
+                    clone_from: None,
+                    backup_retention_days: None,
+                    backup_window: None,
+                    node_pool_label: None,
+                },
+                vec![],
+            )
+        };
+
+        // execute:
+        let configured = mysql(Some(1800));
+        let derived = mysql(None);
+
+        // verify:
+        assert_eq!(configured.readiness_timeout(), Duration::from_secs(1800));
+        assert_eq!(helm_timeout_in_seconds(&configured.start_timeout()), 2100);
+        assert_eq!(derived.readiness_timeout(), readiness_timeout(1, 512));
+        assert_eq!(helm_timeout_in_seconds(&derived.start_timeout()), 459 + 300);
+    }
+
+    #[test]
+    fn test_resolve_secret() {
+        // setup:
+        struct VaultOnlyResolver;
+
+        impl SecretsResolver for VaultOnlyResolver {
+            fn resolve(&self, reference: &SecretReference) -> Result<String, StringError> {
+                match reference {
+                    SecretReference::Vault { .. } => Ok("s3cr3t".to_string()),
+                    _ => Err(format!("{} doesn't exist", reference)),
+                }
+            }
+        }
+
+        let mysql = |context: Context| {
+            MySQL::new(
+                context,
+                "mysqlid",
+                Action::Create,
+                "mysql",
+                "8",
+                "mysqltest.qovery.io",
+                "mysqlid",
+                "1".to_string(),
+                512,
+                "db.t2.micro",
+                DatabaseOptions {
+                    login: "superuser".to_string(),
+                    password: "vault://secret/data/mysql#password".to_string(),
+                    host: "".to_string(),
+                    port: 3306,
+                    disk_size_in_gib: 10,
+                    database_disk_type: "gp2".to_string(),
+                    replicas: 0,
+                    iops: None,
+                    storage_throughput: None,
+                    deletion_protection: false,
+                    sentinel_enabled: false,
+                    resources_overcommit_percent: None,
+                    parameters: BTreeMap::new(),
+                    require_tls: false,
+                    tls_certificates: None,
+                    deletion_timeout_in_seconds: None,
+                    helm_value_overrides: BTreeMap::new(),
+                    deploy_timeout_in_seconds: None,
+                    namespace: None,
+                    clone_from: None,
+                    backup_retention_days: None,
+                    backup_window: None,
+                    node_pool_label: None,
+                },
+                vec![],
+            )
+        };
+        let context = Context::new(
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            false,
+            None,
+            vec![],
+            None,
+        );
+        let with_vault = mysql(context.clone().with_secrets(Secrets::new(VaultOnlyResolver)));
+        let without_secrets = mysql(context);
+
+        // execute:
+        let resolved = resolve_secret(&with_vault, "vault://secret/data/mysql#password");
+        let unknown = resolve_secret(&with_vault, "awssm://prod/mysql").unwrap_err();
+        let unresolved = resolve_secret(&without_secrets, "vault://secret/data/mysql#password").unwrap_err();
+
+        // verify:
+        assert_eq!(resolved.unwrap(), "s3cr3t");
+        assert_eq!(resolve_secret(&without_secrets, "superuser").unwrap(), "superuser");
+        assert!(matches!(unknown.cause, EngineErrorCause::User(_)));
+        assert_eq!(
+            unknown.message.as_deref(),
+            Some("can't resolve a secret of MySQL database mysql (mysqlid): awssm://prod/mysql doesn't exist")
+        );
+        assert!(matches!(unresolved.cause, EngineErrorCause::User(_)));
+        assert_eq!(
+            unresolved.message.as_deref(),
+            Some("can't resolve a secret of MySQL database mysql (mysqlid): vault://secret/data/mysql#password can't be resolved, no secrets manager is configured")
+        );
+    }
+
+    #[test]
+    fn test_wait_until_deleted() {
+        // setup:
+        let database = MySQL::new(
+            Context::new(
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                false,
+                None,
+                vec![],
+                None,
+            ),
+            "mysqlid",
+            Action::Delete,
+            "mysql",
+            "8",
+            "mysqltest.qovery.io",
+            "mysqlid",
+            "1".to_string(),
+            512,
+            "db.t2.micro",
+            DatabaseOptions {
+                login: "".to_string(),
+                password: "".to_string(),
+                host: "".to_string(),
+                port: 3306,
+                disk_size_in_gib: 10,
+                database_disk_type: "gp2".to_string(),
+                replicas: 0,
+                iops: None,
+                storage_throughput: None,
+                deletion_protection: false,
+                sentinel_enabled: false,
+                resources_overcommit_percent: None,
+                parameters: BTreeMap::new(),
+                require_tls: false,
+                tls_certificates: None,
+                deletion_timeout_in_seconds: Some(60),
+                helm_value_overrides: BTreeMap::new(),
+                deploy_timeout_in_seconds: None,
+                namespace: None,
+                clone_from: None,
+                backup_retention_days: None,
+                backup_window: None,
+                node_pool_label: None,
+            },
+            vec![],
+        );
+        let listeners = vec![];
+        let listeners_helper = ListenersHelper::new(&listeners);
+        let describe_calls = Cell::new(0);
+        // fake provider API: the instance is still deleting on the first call, then gone
+        let fake_describe = || {
+            describe_calls.set(describe_calls.get() + 1);
+            Ok(describe_calls.get() > 1)
+        };
+
+        // execute:
+        let deleted = wait_until_deleted_with(
+            &database,
+            &listeners_helper,
+            Duration::from_secs(1),
+            Duration::from_millis(1),
+            fake_describe,
+        );
+        let lingering = wait_until_deleted_with(
+            &database,
+            &listeners_helper,
+            Duration::from_millis(20),
+            Duration::from_millis(1),
+            || Ok(false),
+        );
+
+        // verify:
+        assert!(deleted.is_ok());
+        assert_eq!(describe_calls.get(), 2);
+        assert_eq!(
+            lingering.unwrap_err().message,
+            Some("MySQL database mysql (mysqlid) still exists 0 seconds after its deletion".to_string())
+        );
+    }
+
+    #[test]
+    fn test_readiness_timeout() {
+        assert_eq!(readiness_timeout(1, 512), Duration::from_secs(459));
+        assert_eq!(readiness_timeout(3, 4096), Duration::from_secs(759));
+    }
+}
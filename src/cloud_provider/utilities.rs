@@ -1,17 +1,20 @@
 use std::collections::HashMap;
 
 use crate::cloud_provider::models::CpuLimits;
-use crate::error::{EngineError, StringError};
-use crate::models::{Listeners, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope};
+use crate::dns_provider::DnsProvider;
+use crate::error::{EngineErrorCause, SimpleError, SimpleErrorKind, StringError};
+use crate::models::{CancellationToken, Listeners, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope};
 use chrono::Duration;
 use core::option::Option::{None, Some};
 use core::result::Result;
 use core::result::Result::{Err, Ok};
-use retry::delay::Fixed;
+use lazy_static::lazy_static;
+use retry::delay::{Exponential, Fixed};
 use retry::OperationResult;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::num::ParseFloatError;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use trust_dns_resolver::config::*;
 use trust_dns_resolver::proto::rr::{RData, RecordType};
 use trust_dns_resolver::Resolver;
@@ -140,6 +143,24 @@ pub fn get_supported_version_to_use(
     }
 }
 
+/// Same as `get_supported_version_to_use`, but `extra_supported_versions` (e.g. loaded from the API at startup)
+/// are merged over the compiled ones, so newly released versions can be supported without a new release.
+pub fn get_supported_version_to_use_with_extra_versions(
+    database_name: &str,
+    all_supported_versions: HashMap<String, String>,
+    extra_supported_versions: &HashMap<String, String>,
+    version_to_check: &str,
+) -> Result<String, StringError> {
+    let mut all_supported_versions = all_supported_versions;
+    all_supported_versions.extend(
+        extra_supported_versions
+            .iter()
+            .map(|(requested_version, version)| (requested_version.clone(), version.clone())),
+    );
+
+    get_supported_version_to_use(database_name, all_supported_versions, version_to_check)
+}
+
 // Ease the support of multiple versions by range
 pub fn generate_supported_version(
     major: i32,
@@ -265,13 +286,46 @@ pub fn get_version_number(version: &str) -> Result<VersionsNumber, StringError>
     Ok(VersionsNumber { major, minor, patch })
 }
 
-fn cloudflare_dns_resolver() -> Resolver {
+/// Whether DNS answers can come from the cache of the shared resolver.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DnsCachePolicy {
+    /// Every lookup asks the DNS servers, for checks where a stale answer would be wrong.
+    Disabled,
+    /// Resolved records are cached for their TTL, failed lookups are never cached so propagation checks still retry.
+    Enabled,
+}
+
+const DEFAULT_DNS_RESOLVER_CACHE_SIZE: usize = 256;
+static DNS_RESOLVER_CACHE_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_DNS_RESOLVER_CACHE_SIZE);
+
+lazy_static! {
+    static ref CACHING_DNS_RESOLVER: Resolver =
+        cloudflare_dns_resolver_with_cache_size(DNS_RESOLVER_CACHE_SIZE.load(Ordering::SeqCst));
+    static ref NON_CACHING_DNS_RESOLVER: Resolver = cloudflare_dns_resolver_with_cache_size(0);
+}
+
+/// Sets how many records the caching resolver keeps, only effective before its first lookup.
+pub fn configure_dns_resolver_cache_size(cache_size: usize) {
+    DNS_RESOLVER_CACHE_SIZE.store(cache_size, Ordering::SeqCst);
+}
+
+/// Resolvers are shared between calls, building one per lookup opens new sockets and a new runtime each time.
+pub fn dns_resolver(cache_policy: DnsCachePolicy) -> &'static Resolver {
+    match cache_policy {
+        DnsCachePolicy::Disabled => &NON_CACHING_DNS_RESOLVER,
+        DnsCachePolicy::Enabled => &CACHING_DNS_RESOLVER,
+    }
+}
+
+fn cloudflare_dns_resolver_with_cache_size(cache_size: usize) -> Resolver {
     let mut resolver_options = ResolverOpts::default();
 
-    //  We want to avoid cache and using host file of the host, as some provider force caching
+    //  We want to avoid using host file of the host, as some provider force caching
     //  which lead to stale response
-    resolver_options.cache_size = 0;
+    resolver_options.cache_size = cache_size;
     resolver_options.use_hosts_file = false;
+    // a record not propagated yet must not be reported missing until its negative TTL expires
+    resolver_options.negative_max_ttl = Some(std::time::Duration::from_secs(0));
 
     Resolver::new(ResolverConfig::cloudflare(), resolver_options)
         .expect("Invalid cloudflare DNS resolver configuration")
@@ -298,7 +352,8 @@ pub fn check_cname_for(
     cname_to_check: &str,
     execution_id: &str,
 ) -> Result<String, String> {
-    let resolver = cloudflare_dns_resolver();
+    // the CNAME target changes when the load balancer is recreated
+    let resolver = dns_resolver(DnsCachePolicy::Disabled);
     let listener_helper = ListenersHelper::new(listeners);
 
     let send_deployment_progress = |msg: &str| {
@@ -330,7 +385,7 @@ pub fn check_cname_for(
     // Trying for 5 min to resolve CNAME
     let fixed_iterable = Fixed::from_millis(Duration::seconds(5).num_milliseconds() as u64).take(6 * 5);
     let check_result = retry::retry(fixed_iterable, || {
-        match get_cname_record_value(&resolver, cname_to_check) {
+        match get_cname_record_value(resolver, cname_to_check) {
             Some(domain) => OperationResult::Ok(domain),
             None => {
                 let msg = format!(
@@ -360,13 +415,102 @@ pub fn check_cname_for(
     Ok(cname_to_check.to_string())
 }
 
+/// How long a DNS provider is asked for a record before falling back to DNS resolvers.
+const DNS_PROVIDER_PROPAGATION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// Same as `check_domain_for`, but the domains managed by the DNS provider are checked with its API when it has one.
+pub fn check_domain_with_dns_provider(
+    listener_helper: ListenersHelper,
+    dns_provider: &dyn DnsProvider,
+    domains_to_check: Vec<&str>,
+    execution_id: &str,
+    context_id: &str,
+    cancellation_token: &CancellationToken,
+) -> Result<(), SimpleError> {
+    let mut domains_to_resolve = vec![];
+
+    for domain in domains_to_check {
+        if !dns_provider.can_wait_for_propagation() || !is_subdomain_of(domain, dns_provider.domain()) {
+            domains_to_resolve.push(domain);
+            continue;
+        }
+
+        match dns_provider.wait_for_propagation(domain, DNS_PROVIDER_PROPAGATION_TIMEOUT) {
+            Ok(_) => {
+                let x = format!("Domain {} is ready! ⚡️", domain);
+
+                info!("{}", x);
+
+                listener_helper.deployment_in_progress(ProgressInfo::new(
+                    ProgressScope::Environment {
+                        id: execution_id.to_string(),
+                    },
+                    ProgressLevel::Info,
+                    Some(x),
+                    context_id,
+                ));
+            }
+            Err(e) => {
+                warn!(
+                    "{} can't confirm {} propagation, checking it with DNS resolvers: {:?}",
+                    dns_provider.name_with_id(),
+                    domain,
+                    e.message
+                );
+                domains_to_resolve.push(domain);
+            }
+        }
+    }
+
+    if domains_to_resolve.is_empty() {
+        return Ok(());
+    }
+
+    check_domain_for(
+        listener_helper,
+        domains_to_resolve,
+        execution_id,
+        context_id,
+        cancellation_token,
+        DOMAIN_CHECK_DEADLINE,
+        DnsCachePolicy::Enabled,
+    )
+}
+
+fn is_subdomain_of(domain: &str, parent_domain: &str) -> bool {
+    let domain = domain.trim_end_matches('.');
+    let parent_domain = parent_domain.trim_end_matches('.');
+
+    domain == parent_domain || domain.ends_with(format!(".{}", parent_domain).as_str())
+}
+
+/// How long a domain resolution is retried before giving up, records can take a while to propagate.
+pub const DOMAIN_CHECK_DEADLINE: std::time::Duration = std::time::Duration::from_secs(300);
+const DOMAIN_CHECK_INITIAL_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+const DOMAIN_CHECK_MAX_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Delays between domain resolution attempts, doubling up to `DOMAIN_CHECK_MAX_DELAY` until `deadline`.
+fn domain_check_delays(deadline: std::time::Duration) -> impl Iterator<Item = std::time::Duration> {
+    let mut total_delay = std::time::Duration::from_secs(0);
+
+    Exponential::from_millis_with_factor(DOMAIN_CHECK_INITIAL_DELAY.as_millis() as u64, 2.0)
+        .map(|delay| delay.min(DOMAIN_CHECK_MAX_DELAY))
+        .take_while(move |delay| {
+            total_delay += *delay;
+            total_delay <= deadline
+        })
+}
+
 pub fn check_domain_for(
     listener_helper: ListenersHelper,
     domains_to_check: Vec<&str>,
     execution_id: &str,
     context_id: &str,
-) -> Result<(), EngineError> {
-    let resolver = cloudflare_dns_resolver();
+    cancellation_token: &CancellationToken,
+    deadline: std::time::Duration,
+    cache_policy: DnsCachePolicy,
+) -> Result<(), SimpleError> {
+    let resolver = dns_resolver(cache_policy);
 
     for domain in domains_to_check {
         listener_helper.deployment_in_progress(ProgressInfo::new(
@@ -381,27 +525,39 @@ pub fn check_domain_for(
             execution_id,
         ));
 
-        let fixed_iterable = Fixed::from_millis(3000).take(100);
-        let check_result = retry::retry(fixed_iterable, || match resolver.lookup_ip(domain) {
-            Ok(lookup_ip) => OperationResult::Ok(lookup_ip),
-            Err(err) => {
-                let x = format!("Domain resolution check for '{}' is still in progress...", domain);
+        let check_result = retry::retry(domain_check_delays(deadline), || {
+            if cancellation_token.is_cancelled() {
+                return OperationResult::Err(None);
+            }
 
-                info!("{}", x);
+            match resolver.lookup_ip(domain) {
+                Ok(lookup_ip) => OperationResult::Ok(lookup_ip),
+                Err(err) => {
+                    let x = format!("Domain resolution check for '{}' is still in progress...", domain);
 
-                listener_helper.deployment_in_progress(ProgressInfo::new(
-                    ProgressScope::Environment {
-                        id: execution_id.to_string(),
-                    },
-                    ProgressLevel::Info,
-                    Some(x),
-                    execution_id.to_string(),
-                ));
+                    info!("{}", x);
+
+                    listener_helper.deployment_in_progress(ProgressInfo::new(
+                        ProgressScope::Environment {
+                            id: execution_id.to_string(),
+                        },
+                        ProgressLevel::Info,
+                        Some(x),
+                        execution_id.to_string(),
+                    ));
 
-                OperationResult::Retry(err)
+                    OperationResult::Retry(Some(err))
+                }
             }
         });
 
+        if cancellation_token.is_cancelled() {
+            return Err(SimpleError::new(
+                SimpleErrorKind::Cancelled,
+                Some(format!("operation cancelled while checking domain {}", domain)),
+            ));
+        }
+
         match check_result {
             Ok(_) => {
                 let x = format!("Domain {} is ready! ⚡️", domain);
@@ -445,6 +601,7 @@ pub fn sanitize_name(prefix: &str, name: &str) -> String {
     format!("{}-{}", prefix, name).replace("_", "-")
 }
 
+/// Parses a Kubernetes CPU quantity, either in milli cores ("250m") or in cores ("0.25", "2").
 pub fn convert_k8s_cpu_value_to_f32(value: String) -> Result<f32, ParseFloatError> {
     if value.ends_with('m') {
         let mut value_number_string = value;
@@ -463,39 +620,52 @@ pub fn convert_k8s_cpu_value_to_f32(value: String) -> Result<f32, ParseFloatErro
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub enum CpuLimitsError {
+    InvalidQuantity(String),
+    BurstLowerThanRequest { total_cpu: String, cpu_burst: String },
+}
+
+impl CpuLimitsError {
+    pub fn cause(&self) -> EngineErrorCause {
+        match self {
+            CpuLimitsError::InvalidQuantity(_) => EngineErrorCause::Internal,
+            CpuLimitsError::BurstLowerThanRequest { .. } => {
+                EngineErrorCause::User("Set a CPU burst greater than or equal to the total of CPUs")
+            }
+        }
+    }
+}
+
+impl fmt::Display for CpuLimitsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpuLimitsError::InvalidQuantity(value) => write!(f, "'{}' is not a valid CPU quantity", value),
+            CpuLimitsError::BurstLowerThanRequest { total_cpu, cpu_burst } => write!(
+                f,
+                "CPU burst value '{}' is lower than the desired total of CPUs '{}'",
+                cpu_burst, total_cpu
+            ),
+        }
+    }
+}
+
+/// Maps the total of CPUs to the Kubernetes CPU request and the CPU burst to the CPU limit.
 pub fn validate_k8s_required_cpu_and_burstable(
-    listener_helper: &ListenersHelper,
-    execution_id: &str,
-    context_id: &str,
     total_cpu: String,
     cpu_burst: String,
-) -> Result<CpuLimits, ParseFloatError> {
-    let total_cpu_float = convert_k8s_cpu_value_to_f32(total_cpu.clone())?;
-    let cpu_burst_float = convert_k8s_cpu_value_to_f32(cpu_burst.clone())?;
-    let mut set_cpu_burst = cpu_burst.clone();
+) -> Result<CpuLimits, CpuLimitsError> {
+    let total_cpu_float = convert_k8s_cpu_value_to_f32(total_cpu.clone())
+        .map_err(|_| CpuLimitsError::InvalidQuantity(total_cpu.clone()))?;
+    let cpu_burst_float = convert_k8s_cpu_value_to_f32(cpu_burst.clone())
+        .map_err(|_| CpuLimitsError::InvalidQuantity(cpu_burst.clone()))?;
 
     if cpu_burst_float < total_cpu_float {
-        let message = format!(
-            "CPU burst value '{}' was lower than the desired total of CPUs {}, using burstable value. Please ensure your configuration is valid",
-            cpu_burst,
-            total_cpu,
-        );
-
-        warn!("{}", message);
-
-        listener_helper.error(ProgressInfo::new(
-            ProgressScope::Environment {
-                id: execution_id.to_string(),
-            },
-            ProgressLevel::Warn,
-            Some(message),
-            context_id,
-        ));
-        set_cpu_burst = total_cpu.clone();
+        return Err(CpuLimitsError::BurstLowerThanRequest { total_cpu, cpu_burst });
     }
 
     Ok(CpuLimits {
-        cpu_limit: set_cpu_burst,
+        cpu_limit: cpu_burst,
         cpu_request: total_cpu,
     })
 }
@@ -504,10 +674,47 @@ pub fn validate_k8s_required_cpu_and_burstable(
 mod tests {
     use crate::cloud_provider::models::CpuLimits;
     use crate::cloud_provider::utilities::{
-        cloudflare_dns_resolver, convert_k8s_cpu_value_to_f32, get_cname_record_value,
-        validate_k8s_required_cpu_and_burstable,
+        convert_k8s_cpu_value_to_f32, dns_resolver, domain_check_delays, get_cname_record_value,
+        get_supported_version_to_use_with_extra_versions, is_subdomain_of, validate_k8s_required_cpu_and_burstable,
+        CpuLimitsError, DnsCachePolicy,
     };
-    use crate::models::ListenersHelper;
+    use crate::error::EngineErrorCause;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    #[test]
+    fn test_is_subdomain_of() {
+        // setup:
+        let dns_provider_domain = "z1234.qovery.io";
+
+        // execute & verify:
+        assert!(is_subdomain_of("z1234.qovery.io", dns_provider_domain));
+        assert!(is_subdomain_of("app.z1234.qovery.io.", dns_provider_domain));
+        assert!(!is_subdomain_of("app.z12345.qovery.io", dns_provider_domain));
+        assert!(!is_subdomain_of("www.my-company.com", dns_provider_domain));
+    }
+
+    #[test]
+    fn test_domain_check_delays() {
+        // execute:
+        let delays = domain_check_delays(Duration::from_secs(300)).collect::<Vec<_>>();
+        let short_deadline_delays = domain_check_delays(Duration::from_secs(5)).collect::<Vec<_>>();
+
+        // verify:
+        assert_eq!(
+            delays[..7],
+            [1, 2, 4, 8, 16, 30, 30]
+                .iter()
+                .map(|s| Duration::from_secs(*s))
+                .collect::<Vec<_>>()[..]
+        );
+        assert_eq!(delays.len(), 13);
+        assert!(delays.iter().sum::<Duration>() <= Duration::from_secs(300));
+        assert_eq!(
+            short_deadline_delays,
+            vec![Duration::from_secs(1), Duration::from_secs(2)]
+        );
+    }
 
     #[test]
     pub fn test_k8s_milli_cpu_convert() {
@@ -519,40 +726,118 @@ mod tests {
     }
 
     #[test]
-    pub fn test_cpu_set() {
-        let v = vec![];
-        let listener_helper = ListenersHelper::new(&v);
-        let execution_id = "execution_id";
-        let context_id = "context_id";
+    pub fn test_k8s_cpu_quantity_comparison() {
+        let milli_cpu = convert_k8s_cpu_value_to_f32("100m".to_string()).unwrap();
+        let cpu = convert_k8s_cpu_value_to_f32("1".to_string()).unwrap();
+
+        assert!(milli_cpu < cpu);
+        assert_eq!(convert_k8s_cpu_value_to_f32("1000m".to_string()).unwrap(), cpu);
+        assert_eq!(convert_k8s_cpu_value_to_f32("0.1".to_string()).unwrap(), milli_cpu);
+        assert!(convert_k8s_cpu_value_to_f32("1core".to_string()).is_err());
+    }
 
+    #[test]
+    pub fn test_cpu_set() {
         let mut total_cpu = "0.25".to_string();
         let mut cpu_burst = "1".to_string();
         assert_eq!(
-            validate_k8s_required_cpu_and_burstable(&listener_helper, execution_id, context_id, total_cpu, cpu_burst)
-                .unwrap(),
+            validate_k8s_required_cpu_and_burstable(total_cpu, cpu_burst).unwrap(),
             CpuLimits {
                 cpu_request: "0.25".to_string(),
                 cpu_limit: "1".to_string()
             }
         );
 
-        total_cpu = "1".to_string();
-        cpu_burst = "0.5".to_string();
+        total_cpu = "100m".to_string();
+        cpu_burst = "100m".to_string();
         assert_eq!(
-            validate_k8s_required_cpu_and_burstable(&listener_helper, execution_id, context_id, total_cpu, cpu_burst)
-                .unwrap(),
+            validate_k8s_required_cpu_and_burstable(total_cpu, cpu_burst).unwrap(),
             CpuLimits {
-                cpu_request: "1".to_string(),
-                cpu_limit: "1".to_string()
+                cpu_request: "100m".to_string(),
+                cpu_limit: "100m".to_string()
+            }
+        );
+
+        total_cpu = "1".to_string();
+        cpu_burst = "500m".to_string();
+        let error = validate_k8s_required_cpu_and_burstable(total_cpu, cpu_burst).unwrap_err();
+        assert_eq!(
+            error,
+            CpuLimitsError::BurstLowerThanRequest {
+                total_cpu: "1".to_string(),
+                cpu_burst: "500m".to_string()
             }
         );
+        assert!(matches!(error.cause(), EngineErrorCause::User(_)));
+
+        total_cpu = "one".to_string();
+        cpu_burst = "1".to_string();
+        assert_eq!(
+            validate_k8s_required_cpu_and_burstable(total_cpu, cpu_burst).unwrap_err(),
+            CpuLimitsError::InvalidQuantity("one".to_string())
+        );
+    }
+
+    #[test]
+    fn test_shared_dns_resolvers() {
+        // execute:
+        let caching_resolver = dns_resolver(DnsCachePolicy::Enabled);
+        let non_caching_resolver = dns_resolver(DnsCachePolicy::Disabled);
+
+        // verify:
+        assert!(std::ptr::eq(caching_resolver, dns_resolver(DnsCachePolicy::Enabled)));
+        assert!(std::ptr::eq(
+            non_caching_resolver,
+            dns_resolver(DnsCachePolicy::Disabled)
+        ));
+        assert!(!std::ptr::eq(caching_resolver, non_caching_resolver));
     }
 
     #[test]
     pub fn test_cname_resolution() {
-        let resolver = cloudflare_dns_resolver();
-        let cname = get_cname_record_value(&resolver, "ci-test-no-delete.qovery.io");
+        let resolver = dns_resolver(DnsCachePolicy::Disabled);
+        let cname = get_cname_record_value(resolver, "ci-test-no-delete.qovery.io");
 
         assert_eq!(cname, Some(String::from("qovery.io.")));
     }
+
+    #[test]
+    pub fn test_get_supported_version_to_use_with_extra_versions() {
+        // setup:
+        let mut supported_versions = HashMap::new();
+        supported_versions.insert("8".to_string(), "8.0.23".to_string());
+        supported_versions.insert("8.0".to_string(), "8.0.23".to_string());
+        supported_versions.insert("8.0.23".to_string(), "8.0.23".to_string());
+        let mut extra_versions = HashMap::new();
+        extra_versions.insert("8".to_string(), "8.0.25".to_string());
+        extra_versions.insert("8.0".to_string(), "8.0.25".to_string());
+        extra_versions.insert("8.0.25".to_string(), "8.0.25".to_string());
+
+        // execute:
+        let latest_version =
+            get_supported_version_to_use_with_extra_versions("MySQL", supported_versions.clone(), &extra_versions, "8");
+        let new_version = get_supported_version_to_use_with_extra_versions(
+            "MySQL",
+            supported_versions.clone(),
+            &extra_versions,
+            "8.0.25",
+        );
+        let compiled_version = get_supported_version_to_use_with_extra_versions(
+            "MySQL",
+            supported_versions.clone(),
+            &extra_versions,
+            "8.0.23",
+        );
+        let without_extra_versions =
+            get_supported_version_to_use_with_extra_versions("MySQL", supported_versions, &HashMap::new(), "8.0.25");
+
+        // verify:
+        assert_eq!(latest_version, Ok("8.0.25".to_string()));
+        assert_eq!(new_version, Ok("8.0.25".to_string()));
+        assert_eq!(compiled_version, Ok("8.0.23".to_string()));
+        assert_eq!(
+            without_extra_versions,
+            Err("MySQL 8.0.25 version is not supported".to_string())
+        );
+    }
 }
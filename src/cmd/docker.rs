@@ -0,0 +1,270 @@
+use std::io::Error;
+use std::time::Instant;
+
+use chrono::Duration;
+use retry::delay::Fibonacci;
+use retry::Error::Operation;
+use retry::OperationResult;
+
+use crate::cmd::utilities::{exec, exec_with_envs_and_output};
+use crate::container_registry::PushFailureKind;
+use crate::error::{SimpleError, SimpleErrorKind};
+
+const DOCKER_PUSH_TIMEOUT_IN_MINUTES: i64 = 10;
+
+/// Error code of registries, Docker Hub the first, once their rate limit is reached, ex:
+/// `toomanyrequests: You have reached your pull rate limit.`
+pub const RATE_LIMIT_ERROR_CODE: &str = "toomanyrequests";
+
+/// What happened during a `docker push`, for telemetry.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DockerPushOutput {
+    pub duration: std::time::Duration,
+    pub layers_pushed: u32,
+    pub layers_skipped: u32,
+    /// Pushed image pinned to its digest (`repository@sha256:...`), the destination if no digest was returned.
+    pub reference: String,
+}
+
+/// Runs `docker build` with already built arguments, output lines are forwarded as they come.
+pub fn docker_build<F, X>(
+    args: &[String],
+    envs: Vec<(&str, &str)>,
+    stdout_output: F,
+    stderr_output: X,
+    timeout: Duration,
+) -> Result<(), SimpleError>
+where
+    F: FnMut(Result<String, Error>),
+    X: FnMut(Result<String, Error>),
+{
+    exec_with_envs_and_output(
+        "docker",
+        args.iter().map(|arg| arg.as_str()).collect(),
+        envs,
+        stdout_output,
+        stderr_output,
+        timeout,
+    )
+    .map(|_| ())
+}
+
+/// Logs docker in `registry`, Docker Hub when there is none. The password is masked in logs.
+pub fn docker_login(
+    envs: &[(&str, &str)],
+    registry: Option<&str>,
+    username: &str,
+    password: &str,
+) -> Result<(), SimpleError> {
+    exec("docker", docker_login_args(registry, username, password), envs).map_err(|e| {
+        SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!(
+                "failed to login to {} as {}: {:?}",
+                registry.unwrap_or("Docker Hub"),
+                username,
+                e.message
+            )),
+        )
+    })
+}
+
+pub fn docker_pull(envs: &[(&str, &str)], image: &str) -> Result<(), SimpleError> {
+    exec("docker", vec!["pull", image], envs).map_err(|e| {
+        SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!("failed to pull image {}: {:?}", image, e.message)),
+        )
+    })
+}
+
+/// Tags the local image `source` as `dest`, retried as the docker daemon can be busy during deployments.
+pub fn docker_tag(envs: &[(&str, &str)], source: &str, dest: &str) -> Result<(), SimpleError> {
+    match retry::retry(Fibonacci::from_millis(3000).take(5), || {
+        match exec("docker", vec!["tag", source, dest], envs) {
+            Ok(_) => OperationResult::Ok(()),
+            Err(e) => {
+                info!("failed to tag image {}, retrying...", source);
+                OperationResult::Retry(e)
+            }
+        }
+    }) {
+        Ok(_) => Ok(()),
+        Err(Operation { error, .. }) => Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!(
+                "failed to tag image {} as {}: {:?}",
+                source, dest, error.message
+            )),
+        )),
+        Err(e) => Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!(
+                "unknown error while tagging image {} as {}: {:?}",
+                source, dest, e
+            )),
+        )),
+    }
+}
+
+/// Pushes `dest` (`repository:tag`), retried on network failures only. Layers and digest are read from the push
+/// output. The error message contains the registry error output.
+pub fn docker_push(envs: &[(&str, &str)], dest: &str) -> Result<DockerPushOutput, SimpleError> {
+    let push_started_at = Instant::now();
+
+    let push_output_lines = match retry::retry(Fibonacci::from_millis(5000).take(5), || {
+        let mut error_lines = vec![];
+        match exec_with_envs_and_output(
+            "docker",
+            vec!["push", dest],
+            envs.to_vec(),
+            |line| {
+                let line_string = line.unwrap_or_default();
+                info!("{}", line_string.as_str());
+            },
+            |line| {
+                let line_string = line.unwrap_or_default();
+                error!("{}", line_string.as_str());
+                error_lines.push(line_string);
+            },
+            Duration::minutes(DOCKER_PUSH_TIMEOUT_IN_MINUTES),
+        ) {
+            Ok(output) => OperationResult::Ok(output),
+            Err(e) => {
+                // the registry error is kept in the message so the failure can be classified
+                let error = match error_lines.is_empty() {
+                    true => e,
+                    false => SimpleError::new(
+                        SimpleErrorKind::Other,
+                        Some(format!("failed to push image {}: {}", dest, error_lines.join("\n"))),
+                    ),
+                };
+
+                let failure_kind = PushFailureKind::from_push_output(error.message.as_deref().unwrap_or_default());
+                if !failure_kind.is_retryable() {
+                    warn!(
+                        "failed to push image {}, {:?} failure is not retried",
+                        dest, failure_kind
+                    );
+                    return OperationResult::Err(error);
+                }
+
+                warn!("failed to push image {}, {:?} retrying...", dest, error.message);
+                OperationResult::Retry(error)
+            }
+        }
+    }) {
+        Ok(output) => output,
+        Err(Operation { error, .. }) => return Err(error),
+        Err(e) => {
+            return Err(SimpleError::new(
+                SimpleErrorKind::Other,
+                Some(format!("unknown error while pushing image {}: {:?}", dest, e)),
+            ))
+        }
+    };
+
+    let mut push_output = parse_docker_push_output(dest, &push_output_lines);
+    push_output.duration = push_started_at.elapsed();
+
+    info!("image {} has successfully been pushed", dest);
+    Ok(push_output)
+}
+
+fn docker_login_args<'a>(registry: Option<&'a str>, username: &'a str, password: &'a str) -> Vec<&'a str> {
+    let mut args = vec!["login"];
+    if let Some(registry) = registry {
+        args.push(registry);
+    }
+    args.extend(vec!["-u", username, "-p", password]);
+
+    args
+}
+
+/// Reads `docker push` output lines, ex:
+/// `5f70bf18a086: Pushed`, `e2eb06d8af82: Layer already exists` and `v1: digest: sha256:c3bd... size: 528`.
+fn parse_docker_push_output(dest: &str, lines: &[String]) -> DockerPushOutput {
+    let mut push_output = DockerPushOutput {
+        reference: dest.to_string(),
+        ..Default::default()
+    };
+
+    for line in lines {
+        let line = line.trim();
+
+        if line.ends_with(": Pushed") {
+            push_output.layers_pushed += 1;
+        } else if line.ends_with(": Layer already exists") || line.contains(": Mounted from ") {
+            push_output.layers_skipped += 1;
+        } else if let Some((image_tag, digest)) = line.split_once(": digest: ") {
+            if let Some(digest) = digest.split_whitespace().next() {
+                let repository = dest.strip_suffix(format!(":{}", image_tag).as_str()).unwrap_or(dest);
+                push_output.reference = format!("{}@{}", repository, digest);
+            }
+        }
+    }
+
+    push_output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cmd::docker::{docker_login_args, parse_docker_push_output};
+    use crate::cmd::utilities::command_to_string;
+
+    #[test]
+    fn test_docker_login_args() {
+        // execute:
+        let registry_args = docker_login_args(Some("123456789.dkr.ecr.eu-west-3.amazonaws.com"), "AWS", "token");
+        let docker_hub_args = docker_login_args(None, "qovery", "password");
+
+        // verify:
+        assert_eq!(
+            registry_args,
+            vec![
+                "login",
+                "123456789.dkr.ecr.eu-west-3.amazonaws.com",
+                "-u",
+                "AWS",
+                "-p",
+                "token"
+            ]
+        );
+        assert_eq!(docker_hub_args, vec!["login", "-u", "qovery", "-p", "password"]);
+        assert_eq!(
+            command_to_string("docker", &registry_args, &[]),
+            " docker login 123456789.dkr.ecr.eu-west-3.amazonaws.com -u AWS -p ***"
+        );
+    }
+
+    #[test]
+    fn test_parse_docker_push_output() {
+        // setup:
+        let lines: Vec<String> = vec![
+            "The push refers to repository [registry.digitalocean.com/qovery/my-app]",
+            "5f70bf18a086: Preparing",
+            "5f70bf18a086: Pushed",
+            "a3ed95caeb02: Pushed",
+            "e2eb06d8af82: Layer already exists",
+            "b2d5eeeaba3a: Mounted from library/alpine",
+            "abcdef: digest: sha256:c3bd7fa2b4 size: 1571",
+        ]
+        .into_iter()
+        .map(|line| line.to_string())
+        .collect();
+
+        // execute:
+        let push_output = parse_docker_push_output("registry.digitalocean.com/qovery/my-app:abcdef", &lines);
+        let no_digest_output = parse_docker_push_output("my-app:abcdef", &[]);
+
+        // verify:
+        assert_eq!(push_output.layers_pushed, 2);
+        assert_eq!(push_output.layers_skipped, 2);
+        assert_eq!(
+            push_output.reference,
+            "registry.digitalocean.com/qovery/my-app@sha256:c3bd7fa2b4"
+        );
+        assert_eq!(no_digest_output.layers_pushed, 0);
+        assert_eq!(no_digest_output.reference, "my-app:abcdef");
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::io::{Error, Write};
 use std::path::Path;
 
@@ -8,7 +9,7 @@ use crate::cmd::helm::HelmLockErrors::{IncorrectFormatDate, NotYetExpired, Parsi
 use crate::cmd::kubectl::{kubectl_exec_delete_secret, kubectl_exec_get_secrets};
 use crate::cmd::structs::{Helm, HelmChart, HelmHistoryRow, Item, KubernetesList};
 use crate::cmd::utilities::exec_with_envs_and_output;
-use crate::error::{SimpleError, SimpleErrorKind};
+use crate::error::{SimpleError, SimpleErrorKind, StringError};
 use chrono::{DateTime, Duration, Utc};
 use core::time;
 use retry::delay::Fixed;
@@ -24,12 +25,21 @@ pub enum Timeout<T> {
     Value(T),
 }
 
+/// `helm upgrade --timeout`, services start timeouts come on top of the time helm needs to apply the chart.
+pub fn helm_timeout_in_seconds(timeout: &Timeout<u32>) -> u32 {
+    match timeout {
+        Timeout::Value(v) => v + HELM_DEFAULT_TIMEOUT_IN_SECONDS,
+        Timeout::Default => HELM_DEFAULT_TIMEOUT_IN_SECONDS,
+    }
+}
+
 pub fn helm_exec_with_upgrade_history<P>(
     kubernetes_config: P,
     namespace: &str,
     release_name: &str,
     chart_root_dir: P,
     timeout: Timeout<u32>,
+    value_overrides: &BTreeMap<String, String>,
     envs: Vec<(&str, &str)>,
 ) -> Result<Option<HelmHistoryRow>, SimpleError>
 where
@@ -48,6 +58,7 @@ where
         release_name,
         chart_root_dir.as_ref(),
         timeout,
+        value_overrides,
         envs.clone(),
     )?;
 
@@ -211,42 +222,96 @@ where
     }
 }
 
+/// Checks overrides keys are dotted paths to a chart value, like `podAnnotations.team`.
+pub fn validate_value_overrides(value_overrides: &BTreeMap<String, String>) -> Result<(), StringError> {
+    let is_valid_segment = |segment: &str| {
+        !segment.is_empty()
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    };
+
+    let invalid_keys = value_overrides
+        .keys()
+        .filter(|key| !key.split('.').all(is_valid_segment))
+        .map(|key| format!("`{}`", key))
+        .collect::<Vec<_>>();
+
+    if invalid_keys.is_empty() {
+        return Ok(());
+    }
+
+    Err(format!(
+        "helm value overrides keys must be dotted paths like `podAnnotations.team`, invalid keys: {}",
+        invalid_keys.join(", ")
+    ))
+}
+
+fn helm_exec_upgrade_args(
+    kubernetes_config: &str,
+    namespace: &str,
+    release_name: &str,
+    chart_root_dir: &str,
+    timeout: &str,
+    value_overrides: &BTreeMap<String, String>,
+) -> Vec<String> {
+    let mut args: Vec<String> = vec![
+        "upgrade",
+        "--kubeconfig",
+        kubernetes_config,
+        "--create-namespace",
+        "--install",
+        "--history-max",
+        "50",
+        "--timeout",
+        timeout,
+        "--wait",
+        "--namespace",
+        namespace,
+        release_name,
+        chart_root_dir,
+    ]
+    .into_iter()
+    .map(|x| x.to_string())
+    .collect();
+
+    // --set values win over the chart values files
+    for (key, value) in value_overrides {
+        args.push("--set".to_string());
+        // helm splits --set on commas
+        args.push(format!("{}={}", key, value.replace(',', "\\,")));
+    }
+
+    args
+}
+
 pub fn helm_exec_upgrade<P>(
     kubernetes_config: P,
     namespace: &str,
     release_name: &str,
     chart_root_dir: P,
     timeout: Timeout<u32>,
+    value_overrides: &BTreeMap<String, String>,
     envs: Vec<(&str, &str)>,
 ) -> Result<(), SimpleError>
 where
     P: AsRef<Path>,
 {
-    let timeout_i64 = match timeout {
-        Timeout::Value(v) => v + HELM_DEFAULT_TIMEOUT_IN_SECONDS,
-        Timeout::Default => HELM_DEFAULT_TIMEOUT_IN_SECONDS,
-    } as i64;
+    let timeout_i64 = helm_timeout_in_seconds(&timeout) as i64;
     let timeout_string = format!("{}s", &timeout_i64);
+    let args = helm_exec_upgrade_args(
+        kubernetes_config.as_ref().to_str().unwrap(),
+        namespace,
+        release_name,
+        chart_root_dir.as_ref().to_str().unwrap(),
+        timeout_string.as_str(),
+        value_overrides,
+    );
 
     let result = retry::retry(Fixed::from_millis(15000).take(3), || {
         let mut clean_lock = false;
         match helm_exec_with_output(
-            vec![
-                "upgrade",
-                "--kubeconfig",
-                kubernetes_config.as_ref().to_str().unwrap(),
-                "--create-namespace",
-                "--install",
-                "--history-max",
-                "50",
-                "--timeout",
-                timeout_string.as_str(),
-                "--wait",
-                "--namespace",
-                namespace,
-                release_name,
-                chart_root_dir.as_ref().to_str().unwrap(),
-            ],
+            args.iter().map(|arg| arg.as_str()).collect(),
             envs.clone(),
             |out| match out {
                 Ok(line) => info!("{}", line.as_str()),
@@ -707,6 +772,62 @@ where
 }
 
 /// List deployed helm charts
+/// Values of a deployed release, `None` if the release doesn't exist.
+pub fn helm_exec_get_values<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    release_name: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<Option<serde_json::Value>, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let mut output_vec: Vec<String> = Vec::new();
+    let mut release_not_found = false;
+
+    helm_exec_with_output(
+        vec![
+            "get",
+            "values",
+            "--kubeconfig",
+            kubernetes_config.as_ref().to_str().unwrap(),
+            "--namespace",
+            namespace,
+            "-o",
+            "json",
+            release_name,
+        ],
+        envs,
+        |out| match out {
+            Ok(line) => output_vec.push(line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => {
+                if line.contains("release: not found") {
+                    release_not_found = true;
+                    info!("{}", line)
+                } else {
+                    error!("{}", line)
+                }
+            }
+            Err(err) => error!("{:?}", err),
+        },
+    )?;
+
+    if release_not_found {
+        return Ok(None);
+    }
+
+    match serde_json::from_str::<serde_json::Value>(output_vec.join("").as_str()) {
+        Ok(values) => Ok(Some(values)),
+        Err(e) => Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!("can't read values of helm release {}: {}", release_name, e)),
+        )),
+    }
+}
+
 ///
 /// # Arguments
 ///
@@ -747,26 +868,58 @@ where
         },
     );
 
-    let output_string: String = output_vec.join("");
-    let values = serde_json::from_str::<Vec<Helm>>(output_string.as_str());
-    let mut helms_charts: Vec<HelmChart> = Vec::new();
-
-    match values {
-        Ok(all_helms) => {
-            for helm in all_helms {
-                helms_charts.push(HelmChart::new(helm.name, helm.namespace))
-            }
-        }
-        Err(e) => {
-            let message = format!("Error while deserializing all helms names {}", e);
-            error!("{}", message.as_str());
-            return Err(SimpleError::new(SimpleErrorKind::Other, Some(message)));
-        }
-    }
+    let helms_charts = parse_helm_list_output(output_vec.join("").as_str())?
+        .into_iter()
+        .map(|helm| HelmChart::new(helm.name, helm.namespace))
+        .collect();
 
     Ok(helms_charts)
 }
 
+/// Releases of a namespace whatever their status, failed and pending ones included.
+pub fn helm_list_releases<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<Vec<Helm>, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let mut output_vec: Vec<String> = Vec::new();
+
+    helm_exec_with_output(
+        vec![
+            "list",
+            "--all",
+            "--kubeconfig",
+            kubernetes_config.as_ref().to_str().unwrap(),
+            "--namespace",
+            namespace,
+            "-o",
+            "json",
+        ],
+        envs,
+        |out| match out {
+            Ok(line) => output_vec.push(line),
+            Err(err) => error!("{}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line.as_str()),
+            Err(err) => error!("{}", err),
+        },
+    )?;
+
+    parse_helm_list_output(output_vec.join("").as_str())
+}
+
+fn parse_helm_list_output(output: &str) -> Result<Vec<Helm>, SimpleError> {
+    serde_json::from_str::<Vec<Helm>>(output).map_err(|e| {
+        let message = format!("Error while deserializing all helms names {}", e);
+        error!("{}", message.as_str());
+        SimpleError::new(SimpleErrorKind::Other, Some(message))
+    })
+}
+
 pub fn helm_upgrade_diff_with_chart_info<P>(
     kubernetes_config: P,
     envs: &Vec<(String, String)>,
@@ -879,7 +1032,7 @@ where
                 }
                 None => Err(err),
             },
-            SimpleErrorKind::Other => Err(err),
+            SimpleErrorKind::Cancelled | SimpleErrorKind::Other => Err(err),
         },
         _ => Ok(()),
     }
@@ -887,9 +1040,10 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::cmd::helm::helm_get_secret_lock_name;
+    use crate::cmd::helm::{helm_exec_upgrade_args, helm_get_secret_lock_name, validate_value_overrides};
     use crate::cmd::structs::{Item, KubernetesList};
     use chrono::{DateTime, NaiveDateTime, Utc};
+    use std::collections::BTreeMap;
 
     #[test]
     fn test_helm_lock_get_name() {
@@ -967,4 +1121,36 @@ mod tests {
             "helm lock has not yet expired, please wait 330s before retrying".to_string()
         )
     }
+
+    #[test]
+    fn test_helm_value_overrides() {
+        // setup:
+        let mut overrides = BTreeMap::new();
+        overrides.insert("podAnnotations.team".to_string(), "databases".to_string());
+        overrides.insert("master.extraFlags".to_string(), "--a,--b".to_string());
+        let mut invalid_overrides = overrides.clone();
+        invalid_overrides.insert("podAnnotations..team".to_string(), "".to_string());
+        invalid_overrides.insert("pod annotations".to_string(), "".to_string());
+
+        // execute:
+        let args = helm_exec_upgrade_args("kubeconfig", "namespace", "mysql-id", "chart", "300s", &overrides);
+
+        // verify:
+        assert_eq!(
+            args[args.len() - 5..].to_vec(),
+            vec![
+                "chart",
+                "--set",
+                "master.extraFlags=--a\\,--b",
+                "--set",
+                "podAnnotations.team=databases"
+            ]
+        );
+        assert!(validate_value_overrides(&BTreeMap::new()).is_ok());
+        assert!(validate_value_overrides(&overrides).is_ok());
+        assert_eq!(
+            validate_value_overrides(&invalid_overrides).unwrap_err(),
+            "helm value overrides keys must be dotted paths like `podAnnotations.team`, invalid keys: `pod annotations`, `podAnnotations..team`"
+        );
+    }
 }
@@ -10,17 +10,28 @@ use crate::cloud_provider::digitalocean::models::svc::DOKubernetesList;
 use crate::cloud_provider::metrics::KubernetesApiMetrics;
 use crate::cmd::structs::{
     Configmap, Daemonset, Item, KubernetesEvent, KubernetesJob, KubernetesKind, KubernetesList, KubernetesNode,
-    KubernetesPod, KubernetesPodStatusPhase, KubernetesService, KubernetesVersion, LabelsContent,
+    KubernetesPod, KubernetesPodStatusPhase, KubernetesScheduledPod, KubernetesService, KubernetesVersion,
+    LabelsContent, Namespace, PersistentVolumeClaim, Secret, StorageClass, Workload,
 };
 use crate::cmd::utilities::exec_with_envs_and_output;
 use crate::constants::KUBECONFIG;
 use crate::error::{SimpleError, SimpleErrorKind};
 
+#[derive(Clone, Copy)]
 pub enum ScalingKind {
     Deployment,
     Statefulset,
 }
 
+impl ScalingKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ScalingKind::Deployment => "deployment",
+            ScalingKind::Statefulset => "statefulset",
+        }
+    }
+}
+
 pub fn kubectl_exec_with_output<F, X>(
     args: Vec<&str>,
     envs: Vec<(&str, &str)>,
@@ -229,20 +240,56 @@ where
     ))
 }
 
+/// Time given to pods to be ready when the caller has no better estimate, the sum of the 10 fibonacci retries
+/// (3s, 3s, 6s ... 165s) the check has always been doing.
+pub const DEFAULT_POD_READY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(429);
+/// Bigger pods take longer to start, a database has to load its buffers and replay its logs.
+const POD_READY_TIMEOUT_PER_GIB_OF_RAM: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Time given to pods with `total_ram_in_mib` of memory to be ready, never less than the default.
+pub fn pod_ready_timeout(total_ram_in_mib: u32) -> std::time::Duration {
+    DEFAULT_POD_READY_TIMEOUT + POD_READY_TIMEOUT_PER_GIB_OF_RAM * (total_ram_in_mib / 1024)
+}
+
 pub fn kubectl_exec_is_pod_ready_with_retry<P>(
     kubernetes_config: P,
     namespace: &str,
     selector: &str,
+    expected_pods: u16,
     envs: Vec<(&str, &str)>,
 ) -> Result<Option<bool>, SimpleError>
 where
     P: AsRef<Path>,
 {
-    let result = retry::retry(Fibonacci::from_millis(3000).take(10), || {
+    kubectl_exec_is_pod_ready_with_timeout(
+        kubernetes_config,
+        namespace,
+        selector,
+        expected_pods,
+        envs,
+        DEFAULT_POD_READY_TIMEOUT,
+    )
+}
+
+/// Waits for the pods to be ready, retrying until `timeout` is reached. `Some(false)` when they are still not ready.
+pub fn kubectl_exec_is_pod_ready_with_timeout<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    selector: &str,
+    expected_pods: u16,
+    envs: Vec<(&str, &str)>,
+    timeout: std::time::Duration,
+) -> Result<Option<bool>, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let started_at = std::time::Instant::now();
+    let result = retry::retry(retry_delays_within(timeout), || {
         let r = crate::cmd::kubectl::kubectl_exec_is_pod_ready(
             kubernetes_config.as_ref(),
             namespace,
             selector,
+            expected_pods,
             envs.clone(),
         );
 
@@ -264,14 +311,34 @@ where
             retry::Error::Operation {
                 error: _,
                 total_delay: _,
-                tries: _,
-            } => Ok(Some(false)),
+                tries,
+            } => {
+                warn!(
+                    "pod with selector: {} is still not ready after waiting {}s ({} tries)",
+                    selector,
+                    started_at.elapsed().as_secs(),
+                    tries
+                );
+                Ok(Some(false))
+            }
             retry::Error::Internal(err) => Err(SimpleError::new(SimpleErrorKind::Other, Some(err))),
         },
         Ok(_) => Ok(Some(true)),
     }
 }
 
+/// Fibonacci delays, starting at 3s, until their sum reaches `timeout`: the pods are given at least `timeout`.
+fn retry_delays_within(timeout: std::time::Duration) -> impl Iterator<Item = std::time::Duration> {
+    Fibonacci::from_millis(3000).scan(std::time::Duration::from_secs(0), move |total_delay, delay| {
+        if *total_delay >= timeout {
+            None
+        } else {
+            *total_delay += delay;
+            Some(delay)
+        }
+    })
+}
+
 pub fn kubectl_exec_get_secrets<P>(
     kubernetes_config: P,
     namespace: &str,
@@ -288,10 +355,12 @@ where
     )
 }
 
+/// Pods are ready once at least `expected_pods` of them are running.
 pub fn kubectl_exec_is_pod_ready<P>(
     kubernetes_config: P,
     namespace: &str,
     selector: &str,
+    expected_pods: u16,
     envs: Vec<(&str, &str)>,
 ) -> Result<Option<bool>, SimpleError>
 where
@@ -299,14 +368,20 @@ where
 {
     let result = kubectl_exec_get_pod(kubernetes_config, namespace, selector, envs)?;
 
-    if result.items.is_empty() || result.items.first().unwrap().status.container_statuses.is_none() {
+    if result.items.len() < expected_pods as usize
+        || result.items.is_empty()
+        || result.items.iter().any(|pod| pod.status.container_statuses.is_none())
+    {
         return Ok(None);
     }
 
-    let first_item = result.items.first().unwrap();
+    let running_pods = result
+        .items
+        .iter()
+        .filter(|pod| matches!(pod.status.phase, KubernetesPodStatusPhase::Running))
+        .count();
 
-    let is_ready = matches!(first_item.status.phase, KubernetesPodStatusPhase::Running);
-    Ok(Some(is_ready))
+    Ok(Some(running_pods >= expected_pods as usize))
 }
 
 pub fn kubectl_exec_is_job_ready_with_retry<P>(
@@ -405,6 +480,42 @@ where
     result.is_ok()
 }
 
+pub fn kubectl_exec_is_service_present<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    service_name: &str,
+    envs: Vec<(&str, &str)>,
+) -> bool
+where
+    P: AsRef<Path>,
+{
+    let mut _envs = Vec::with_capacity(envs.len() + 1);
+    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
+    _envs.extend(envs);
+
+    let result = kubectl_exec_with_output(
+        vec!["get", "service", service_name, "-n", namespace],
+        _envs,
+        |out| {
+            if let Err(err) = out {
+                error!("{:?}", err)
+            }
+        },
+        |out| match out {
+            Ok(line) => {
+                if line.contains("Error from server (NotFound): services") {
+                    info!("{}", line)
+                } else {
+                    error!("{}", line)
+                }
+            }
+            Err(err) => error!("{:?}", err),
+        },
+    );
+
+    result.is_ok()
+}
+
 pub fn kubectl_exec_create_namespace_without_labels(namespace: &str, kube_config: &str, envs: Vec<(&str, &str)>) {
     let _ = kubectl_exec_create_namespace(kube_config, namespace, None, envs);
 }
@@ -439,17 +550,91 @@ where
         )?;
     }
 
-    // additional labels
-    if labels.is_some() {
-        match kubectl_add_labels_to_namespace(kubernetes_config, namespace, labels.unwrap(), envs) {
-            Ok(_) => {}
-            Err(e) => return Err(e),
-        }
+    // additional labels, reconciled on every call so an existing namespace gets the current ones
+    if let Some(labels) = labels {
+        kubectl_exec_label_namespace(kubernetes_config, namespace, labels, envs)?;
     };
 
     Ok(())
 }
 
+pub fn kubectl_exec_get_namespace<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<Namespace, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    kubectl_exec::<P, Namespace>(
+        vec!["get", "namespace", "-o", "json", namespace],
+        kubernetes_config,
+        envs,
+    )
+}
+
+/// Sets the namespace annotation, or removes it when `value` is `None`.
+pub fn kubectl_exec_annotate_namespace<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    key: &str,
+    value: Option<&str>,
+    envs: Vec<(&str, &str)>,
+) -> Result<(), SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let annotation = match value {
+        Some(value) => format!("{}={}", key, value),
+        None => format!("{}-", key),
+    };
+
+    let mut _envs = Vec::with_capacity(envs.len() + 1);
+    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
+    _envs.extend(envs);
+
+    kubectl_exec_with_output(
+        vec!["annotate", "namespace", namespace, "--overwrite", annotation.as_str()],
+        _envs,
+        |out| match out {
+            Ok(line) => info!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+    )
+}
+
+/// Patch the namespace labels which differ from the expected ones, nothing is done when they all match.
+pub fn kubectl_exec_label_namespace<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    labels: Vec<LabelsContent>,
+    envs: Vec<(&str, &str)>,
+) -> Result<(), SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let current_namespace = kubectl_exec_get_namespace(kubernetes_config.as_ref(), namespace, envs.clone())?;
+    let labels_to_patch = namespace_labels_to_patch(&current_namespace, labels);
+
+    if labels_to_patch.is_empty() {
+        info!("labels of namespace {} are up to date", namespace);
+        return Ok(());
+    }
+
+    kubectl_add_labels_to_namespace(kubernetes_config, namespace, labels_to_patch, envs)
+}
+
+fn namespace_labels_to_patch(namespace: &Namespace, labels: Vec<LabelsContent>) -> Vec<LabelsContent> {
+    labels
+        .into_iter()
+        .filter(|label| namespace.metadata.labels.get(&label.name) != Some(&label.value))
+        .collect()
+}
+
 pub fn kubectl_add_labels_to_namespace<P>(
     kubernetes_config: P,
     namespace: &str,
@@ -473,15 +658,8 @@ where
         ));
     }
 
-    let mut command_args = Vec::new();
-    let mut labels_string = Vec::new();
-    command_args.extend(vec!["label", "namespace", namespace, "--overwrite"]);
-
-    for label in labels.iter() {
-        labels_string.push(format! {"{}={}", label.name, label.value});
-    }
-    let labels_str = labels_string.iter().map(|x| x.as_ref()).collect::<Vec<&str>>();
-    command_args.extend(labels_str);
+    let command_args = label_namespace_command_args(namespace, &labels);
+    let command_args = command_args.iter().map(|x| x.as_str()).collect::<Vec<&str>>();
 
     let mut _envs = Vec::with_capacity(envs.len() + 1);
     _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
@@ -503,6 +681,21 @@ where
     Ok(())
 }
 
+fn label_namespace_command_args(namespace: &str, labels: &[LabelsContent]) -> Vec<String> {
+    let mut command_args = vec![
+        "label".to_string(),
+        "namespace".to_string(),
+        namespace.to_string(),
+        "--overwrite".to_string(),
+    ];
+
+    for label in labels.iter() {
+        command_args.push(format! {"{}={}", label.name, label.value});
+    }
+
+    command_args
+}
+
 // used for testing the does_contain_terraform_tfstate
 pub fn does_contain_terraform_tfstate<P>(
     kubernetes_config: P,
@@ -638,6 +831,7 @@ where
     Ok(())
 }
 
+/// Deleting a secret which doesn't exist is not an error.
 pub fn kubectl_exec_delete_secret<P>(
     kubernetes_config: P,
     namespace: &str,
@@ -652,7 +846,63 @@ where
     _envs.extend(envs);
 
     let _ = kubectl_exec_with_output(
-        vec!["-n", namespace, "delete", "secret", secret],
+        vec!["-n", namespace, "delete", "secret", secret, "--ignore-not-found"],
+        _envs,
+        |out| match out {
+            Ok(line) => info!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+    )?;
+
+    Ok(())
+}
+
+pub fn kubectl_exec_get_secret<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    name: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<Secret, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    kubectl_exec::<P, Secret>(
+        vec!["get", "secret", "-o", "json", "-n", namespace, name],
+        kubernetes_config,
+        envs,
+    )
+}
+
+pub fn kubectl_exec_annotate_secret<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    name: &str,
+    annotation: (&str, &str),
+    envs: Vec<(&str, &str)>,
+) -> Result<(), SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let mut _envs = Vec::with_capacity(envs.len() + 1);
+    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
+    _envs.extend(envs);
+
+    let annotation = format!("{}={}", annotation.0, annotation.1);
+
+    let _ = kubectl_exec_with_output(
+        vec![
+            "-n",
+            namespace,
+            "annotate",
+            "secret",
+            name,
+            "--overwrite",
+            annotation.as_str(),
+        ],
         _envs,
         |out| match out {
             Ok(line) => info!("{}", line),
@@ -785,6 +1035,33 @@ where
     )
 }
 
+pub fn kubectl_exec_rollout_restart_statefulset<P>(
+    kubernetes_config: P,
+    name: &str,
+    namespace: &str,
+    envs: &[(&str, &str)],
+) -> Result<(), SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let mut environment_variables: Vec<(&str, &str)> = envs.to_vec();
+    environment_variables.push(("KUBECONFIG", kubernetes_config.as_ref().to_str().unwrap()));
+    let args = vec!["-n", namespace, "rollout", "restart", "statefulset", name];
+
+    kubectl_exec_with_output(
+        args,
+        environment_variables.clone(),
+        |out| match out {
+            Ok(line) => info!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+    )
+}
+
 pub fn kubectl_exec_get_node<P>(
     kubernetes_config: P,
     envs: Vec<(&str, &str)>,
@@ -829,6 +1106,252 @@ where
     )
 }
 
+/// Names of the pods matching `selector`, in no particular order.
+pub fn kubectl_exec_get_pod_names<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    selector: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<Vec<String>, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let mut _envs = Vec::with_capacity(envs.len() + 1);
+    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
+    _envs.extend(envs);
+
+    let mut names = vec![];
+    kubectl_exec_with_output(
+        vec![
+            "get",
+            "pod",
+            "-n",
+            namespace,
+            "-l",
+            selector,
+            "-o",
+            "jsonpath={.items[*].metadata.name}",
+        ],
+        _envs,
+        |out| match out {
+            Ok(line) => names.extend(line.split_whitespace().map(|name| name.to_string())),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+    )?;
+
+    Ok(names)
+}
+
+/// Command run with `sh -c` in a pod, the pod environment variables are available to it.
+pub struct PodCommand<'a> {
+    pub namespace: &'a str,
+    pub pod: &'a str,
+    pub command: &'a str,
+}
+
+impl<'a> PodCommand<'a> {
+    fn kubectl_args(&self, interactive: bool) -> Vec<&'a str> {
+        let mut args = vec!["-n", self.namespace, "exec"];
+        if interactive {
+            args.push("-i");
+        }
+        args.extend(vec![self.pod, "--", "sh", "-c", self.command]);
+        args
+    }
+}
+
+/// Streams the output of the `source` command to the input of the `target` one, like a `dump | restore`
+/// between two pods. The streamed data never goes through the logs, only the commands errors are reported.
+pub fn kubectl_exec_pipe_between_pods<P>(
+    kubernetes_config: P,
+    source: PodCommand,
+    target: PodCommand,
+    envs: Vec<(&str, &str)>,
+) -> Result<(), SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let kubernetes_config = kubernetes_config.as_ref().to_str().unwrap();
+
+    let mut source_process = std::process::Command::new("kubectl")
+        .args(source.kubectl_args(false))
+        .env(KUBECONFIG, kubernetes_config)
+        .envs(envs.clone())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+
+    let source_output = source_process.stdout.take().ok_or_else(|| {
+        SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!("can't read the output of pod {}", source.pod)),
+        )
+    })?;
+
+    let target_result = std::process::Command::new("kubectl")
+        .args(target.kubectl_args(true))
+        .env(KUBECONFIG, kubernetes_config)
+        .envs(envs)
+        .stdin(source_output)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .output()?;
+    let source_result = source_process.wait_with_output()?;
+
+    for (pod, result) in [(source.pod, source_result), (target.pod, target_result)] {
+        if !result.status.success() {
+            return Err(SimpleError::new(
+                SimpleErrorKind::Other,
+                Some(format!(
+                    "command failed in pod {} ({}): {}",
+                    pod,
+                    result.status,
+                    String::from_utf8_lossy(&result.stderr).trim()
+                )),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn kubectl_exec_get_workloads<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<KubernetesList<Workload>, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    kubectl_exec::<P, KubernetesList<Workload>>(
+        vec!["get", "deployments,statefulsets", "-o", "json", "-n", namespace],
+        kubernetes_config,
+        envs,
+    )
+}
+
+pub fn kubectl_exec_get_workloads_by_selector<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    kind: ScalingKind,
+    selector: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<KubernetesList<Workload>, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    kubectl_exec::<P, KubernetesList<Workload>>(
+        vec!["get", kind.as_str(), "-o", "json", "-n", namespace, "-l", selector],
+        kubernetes_config,
+        envs,
+    )
+}
+
+pub fn kubectl_exec_get_persistent_volume_claims<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<KubernetesList<PersistentVolumeClaim>, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    kubectl_exec::<P, KubernetesList<PersistentVolumeClaim>>(
+        vec!["get", "pvc", "-o", "json", "-n", namespace],
+        kubernetes_config,
+        envs,
+    )
+}
+
+pub fn kubectl_exec_get_storage_classes<P>(
+    kubernetes_config: P,
+    envs: Vec<(&str, &str)>,
+) -> Result<KubernetesList<StorageClass>, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    kubectl_exec::<P, KubernetesList<StorageClass>>(vec!["get", "storageclass", "-o", "json"], kubernetes_config, envs)
+}
+
+/// Requests `storage` (ex: `20Gi`) for the persistent volume claim, its storage class must allow volume expansion.
+pub fn kubectl_exec_patch_persistent_volume_claim_storage<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    name: &str,
+    storage: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<(), SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let patch = serde_json::json!({ "spec": { "resources": { "requests": { "storage": storage } } } }).to_string();
+
+    let mut _envs = Vec::with_capacity(envs.len() + 1);
+    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
+    _envs.extend(envs);
+
+    kubectl_exec_with_output(
+        vec!["-n", namespace, "patch", "pvc", name, "-p", patch.as_str()],
+        _envs,
+        |out| match out {
+            Ok(line) => info!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+    )
+}
+
+/// Sets the annotation `key` on the workloads matching `selector`, it's removed when there is no value.
+pub fn kubectl_exec_annotate_workloads_by_selector<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    kind: ScalingKind,
+    selector: &str,
+    key: &str,
+    value: Option<&str>,
+    envs: Vec<(&str, &str)>,
+) -> Result<(), SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let annotation = match value {
+        Some(value) => format!("{}={}", key, value),
+        None => format!("{}-", key),
+    };
+
+    let mut _envs = Vec::with_capacity(envs.len() + 1);
+    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
+    _envs.extend(envs);
+
+    kubectl_exec_with_output(
+        vec![
+            "-n",
+            namespace,
+            "annotate",
+            kind.as_str(),
+            "--selector",
+            selector,
+            "--overwrite",
+            annotation.as_str(),
+        ],
+        _envs,
+        |out| match out {
+            Ok(line) => info!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+    )
+}
+
 pub fn kubectl_exec_get_configmap<P>(
     kubernetes_config: P,
     namespace: &str,
@@ -944,6 +1467,29 @@ where
     kubectl_exec::<P, KubernetesApiMetrics>(vec!["get", "--raw", api_url.as_str()], kubernetes_config, envs)
 }
 
+/// Pods of every namespace which are not terminated, they are the ones holding resources requests on the nodes.
+pub fn kubectl_exec_get_scheduled_pods<P>(
+    kubernetes_config: P,
+    envs: Vec<(&str, &str)>,
+) -> Result<KubernetesList<KubernetesScheduledPod>, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    kubectl_exec::<P, KubernetesList<KubernetesScheduledPod>>(
+        vec![
+            "get",
+            "pods",
+            "--all-namespaces",
+            "--field-selector",
+            "status.phase!=Succeeded,status.phase!=Failed",
+            "-o",
+            "json",
+        ],
+        kubernetes_config,
+        envs,
+    )
+}
+
 /// scale down replicas by name
 ///
 /// # Arguments
@@ -1019,11 +1565,6 @@ pub fn kubectl_exec_scale_replicas_by_selector<P>(
 where
     P: AsRef<Path>,
 {
-    let kind_formatted = match kind {
-        ScalingKind::Deployment => "deployment",
-        ScalingKind::Statefulset => "statefulset",
-    };
-
     let mut _envs = Vec::with_capacity(envs.len() + 1);
     _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
     _envs.extend(envs);
@@ -1035,7 +1576,7 @@ where
             "scale",
             "--replicas",
             &replicas_count.to_string(),
-            &kind_formatted,
+            kind.as_str(),
             "--selector",
             selector,
         ],
@@ -1102,3 +1643,95 @@ where
 
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cmd::kubectl::{
+        kubectl_exec_is_pod_ready_with_timeout, label_namespace_command_args, namespace_labels_to_patch,
+        pod_ready_timeout, retry_delays_within, DEFAULT_POD_READY_TIMEOUT,
+    };
+    use crate::cmd::structs::{LabelsContent, Namespace};
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::Duration;
+
+    #[test]
+    fn test_namespace_labels_reconciliation() {
+        // setup:
+        let namespace = serde_json::from_str::<Namespace>(
+            r#"{
+    "apiVersion": "v1",
+    "kind": "Namespace",
+    "metadata": {
+        "creationTimestamp": "2021-07-01T10:00:00Z",
+        "labels": {
+            "ttl": "3600"
+        },
+        "name": "project-env"
+    }
+}"#,
+        )
+        .unwrap();
+        let ttl = |value: &str| {
+            vec![LabelsContent {
+                name: "ttl".to_string(),
+                value: value.to_string(),
+            }]
+        };
+
+        // execute:
+        let changed_ttl = namespace_labels_to_patch(&namespace, ttl("7200"));
+        let same_ttl = namespace_labels_to_patch(&namespace, ttl("3600"));
+
+        // verify:
+        assert_eq!(
+            label_namespace_command_args("project-env", &changed_ttl),
+            vec!["label", "namespace", "project-env", "--overwrite", "ttl=7200"]
+        );
+        assert!(same_ttl.is_empty());
+    }
+
+    #[test]
+    fn test_pod_ready_timeout_exceeded() {
+        // setup:
+        let fake_kubectl_dir = tempfile::tempdir().unwrap();
+        let fake_kubectl = fake_kubectl_dir.path().join("kubectl");
+        fs::write(&fake_kubectl, "#!/bin/sh\necho '{\"items\": []}'\n").unwrap();
+        fs::set_permissions(&fake_kubectl, fs::Permissions::from_mode(0o755)).unwrap();
+        let path = format!(
+            "{}:{}",
+            fake_kubectl_dir.path().to_str().unwrap(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+
+        // execute:
+        let result = kubectl_exec_is_pod_ready_with_timeout(
+            "/tmp/fake-kubeconfig",
+            "default",
+            "app=mysql",
+            1,
+            vec![("PATH", path.as_str())],
+            Duration::from_secs(5),
+        );
+
+        // verify:
+        assert_eq!(
+            retry_delays_within(Duration::from_secs(30)).collect::<Vec<_>>(),
+            vec![3, 3, 6, 9, 15]
+                .into_iter()
+                .map(Duration::from_secs)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(retry_delays_within(DEFAULT_POD_READY_TIMEOUT).count(), 10);
+        assert_eq!(
+            retry_delays_within(DEFAULT_POD_READY_TIMEOUT).sum::<Duration>(),
+            DEFAULT_POD_READY_TIMEOUT
+        );
+        assert_eq!(pod_ready_timeout(512), DEFAULT_POD_READY_TIMEOUT);
+        assert_eq!(
+            pod_ready_timeout(4096),
+            DEFAULT_POD_READY_TIMEOUT + Duration::from_secs(240)
+        );
+        assert_eq!(result.unwrap(), Some(false));
+    }
+}
@@ -1,3 +1,4 @@
+pub mod docker;
 pub mod helm;
 pub mod kubectl;
 pub mod structs;
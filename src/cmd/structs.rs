@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
 #[serde(rename_all = "camelCase")]
@@ -42,6 +43,107 @@ pub struct Metadata {
     pub uid: String,
 }
 
+#[derive(Default, Serialize, Deserialize)]
+pub struct Secret {
+    pub metadata: SecretMetadata,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct SecretMetadata {
+    pub name: String,
+    #[serde(default)]
+    pub annotations: HashMap<String, String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Namespace {
+    pub metadata: NamespaceMetadata,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct NamespaceMetadata {
+    pub name: String,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    #[serde(default)]
+    pub annotations: HashMap<String, String>,
+}
+
+/// Deployment or StatefulSet, only what is needed to scale it.
+#[derive(Serialize, Deserialize)]
+pub struct Workload {
+    pub kind: String,
+    pub metadata: WorkloadMetadata,
+    pub spec: WorkloadSpec,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WorkloadMetadata {
+    pub name: String,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    #[serde(default)]
+    pub annotations: HashMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WorkloadSpec {
+    pub replicas: Option<u32>,
+}
+
+/// Persistent volume claim, only what is needed to know its size.
+#[derive(Serialize, Deserialize)]
+pub struct PersistentVolumeClaim {
+    pub metadata: PersistentVolumeClaimMetadata,
+    pub spec: PersistentVolumeClaimSpec,
+    #[serde(default)]
+    pub status: PersistentVolumeClaimStatus,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PersistentVolumeClaimMetadata {
+    pub name: String,
+    #[serde(default)]
+    pub annotations: HashMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PersistentVolumeClaimSpec {
+    pub resources: PersistentVolumeClaimResources,
+    /// The default storage class is used when there is none.
+    #[serde(rename = "storageClassName", default)]
+    pub storage_class_name: Option<String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct PersistentVolumeClaimResources {
+    #[serde(default)]
+    pub requests: HashMap<String, String>,
+}
+
+/// Capacity is only set once the volume is bound, it can differ from the request during a resize.
+#[derive(Default, Serialize, Deserialize)]
+pub struct PersistentVolumeClaimStatus {
+    #[serde(default)]
+    pub capacity: HashMap<String, String>,
+}
+
+/// Storage class, only what is needed to know if its volumes can be expanded.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageClass {
+    pub metadata: StorageClassMetadata,
+    #[serde(default)]
+    pub allow_volume_expansion: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StorageClassMetadata {
+    pub name: String,
+    #[serde(default)]
+    pub annotations: HashMap<String, String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Configmap {
     pub data: ConfigmapData,
@@ -115,6 +217,34 @@ pub struct KubernetesPodStatus {
     pub phase: KubernetesPodStatusPhase,
 }
 
+/// Pod as seen by the scheduler: the node it is bound to and the resources requested by its containers.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KubernetesScheduledPod {
+    pub spec: KubernetesScheduledPodSpec,
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KubernetesScheduledPodSpec {
+    pub node_name: Option<String>,
+    pub containers: Vec<KubernetesPodContainer>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KubernetesPodContainer {
+    #[serde(default)]
+    pub resources: KubernetesPodContainerResources,
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct KubernetesPodContainerResources {
+    #[serde(default)]
+    pub requests: HashMap<String, String>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
 #[serde(rename_all = "camelCase")]
 pub struct KubernetesPodCondition {
@@ -181,15 +311,32 @@ pub struct KubernetesJobStatus {
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
 #[serde(rename_all = "camelCase")]
 pub struct KubernetesNode {
+    #[serde(default)]
+    pub metadata: KubernetesNodeMetadata,
     pub status: KubernetesNodeStatus,
 }
 
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct KubernetesNodeMetadata {
+    pub name: String,
+}
+
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
 #[serde(rename_all = "camelCase")]
 pub struct KubernetesNodeStatus {
     pub allocatable: KubernetesNodeStatusResources,
     pub capacity: KubernetesNodeStatusResources,
     pub node_info: KubernetesNodeInfo,
+    pub conditions: Vec<KubernetesNodeCondition>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct KubernetesNodeCondition {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub status: String,
 }
 
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
@@ -276,12 +423,31 @@ pub struct HelmHistoryRow {
     pub status: String,
     pub chart: String,
     pub app_version: String,
+    #[serde(default)]
+    pub description: String,
 }
 
 impl HelmHistoryRow {
     pub fn is_successfully_deployed(&self) -> bool {
         self.status == "deployed"
     }
+
+    /// Status and helm description of a release which isn't deployed, ex: `failed: timed out waiting for the condition`.
+    pub fn failure_reason(&self) -> Option<String> {
+        match (self.is_successfully_deployed(), self.description.is_empty()) {
+            (true, _) => None,
+            (false, true) => Some(self.status.clone()),
+            (false, false) => Some(format!("{}: {}", self.status, self.description)),
+        }
+    }
+}
+
+/// One value of `terraform output -json`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TerraformOutput {
+    #[serde(default)]
+    pub sensitive: bool,
+    pub value: serde_json::Value,
 }
 
 #[cfg(test)]
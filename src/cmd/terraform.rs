@@ -2,21 +2,38 @@ use dirs::home_dir;
 use retry::delay::Fixed;
 use retry::OperationResult;
 
-use crate::cmd::utilities::exec_with_envs_and_output;
+use crate::cmd::structs::TerraformOutput;
+use crate::cmd::utilities::exec_with_envs_and_output_cancellable;
 use crate::constants::TF_PLUGIN_CACHE_DIR;
 use crate::error::{SimpleError, SimpleErrorKind};
+use crate::models::CancellationToken;
 use chrono::Duration;
 use rand::Rng;
 use retry::Error::Operation;
+use std::collections::BTreeMap;
 use std::{env, fs, thread, time};
 
-fn terraform_init_validate(root_dir: &str) -> Result<(), SimpleError> {
+/// What an apply created, read from the terraform outputs of the applied templates.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TerraformApplyResult {
+    pub outputs: BTreeMap<String, TerraformOutput>,
+}
+
+impl TerraformApplyResult {
+    /// Value of the `name` output if it's a string, ex: the endpoint of a managed database.
+    pub fn output_string(&self, name: &str) -> Option<&str> {
+        self.outputs.get(name).and_then(|output| output.value.as_str())
+    }
+}
+
+fn terraform_init_validate(root_dir: &str, cancellation_token: &CancellationToken) -> Result<(), SimpleError> {
     let terraform_provider_lock = format!("{}/.terraform.lock.hcl", &root_dir);
 
     // terraform init
     let result = retry::retry(Fixed::from_millis(3000).take(5), || {
-        match terraform_exec(root_dir, vec!["init"]) {
+        match terraform_exec_cancellable(root_dir, vec!["init"], cancellation_token) {
             Ok(out) => OperationResult::Ok(out),
+            Err(err) if is_cancelled(&err) => OperationResult::Err(err),
             Err(err) => {
                 // Error: Failed to install provider from shared cache
                 // in order to avoid lock errors on parallel run, let's sleep a bit
@@ -57,7 +74,7 @@ fn terraform_init_validate(root_dir: &str) -> Result<(), SimpleError> {
     }
 
     // validate config
-    match terraform_exec(root_dir, vec!["validate"]) {
+    match terraform_exec_cancellable(root_dir, vec!["validate"], cancellation_token) {
         Err(e) => {
             error!("error while trying to Terraform validate the rendered templates");
             return Err(e);
@@ -66,8 +83,13 @@ fn terraform_init_validate(root_dir: &str) -> Result<(), SimpleError> {
     }
 }
 
-pub fn terraform_init_validate_plan_apply(root_dir: &str, dry_run: bool) -> Result<(), SimpleError> {
-    match terraform_init_validate(root_dir) {
+/// Outputs are only read once applied, the result is empty on dry runs.
+pub fn terraform_init_validate_plan_apply(
+    root_dir: &str,
+    dry_run: bool,
+    cancellation_token: &CancellationToken,
+) -> Result<TerraformApplyResult, SimpleError> {
+    match terraform_init_validate(root_dir, cancellation_token) {
         Err(e) => return Err(e),
         Ok(_) => {}
     }
@@ -75,8 +97,9 @@ pub fn terraform_init_validate_plan_apply(root_dir: &str, dry_run: bool) -> Resu
     if dry_run {
         // plan
         let result = retry::retry(Fixed::from_millis(3000).take(3), || {
-            match terraform_exec(root_dir, vec!["plan", "-out", "tf_plan"]) {
+            match terraform_exec_cancellable(root_dir, vec!["plan", "-out", "tf_plan"], cancellation_token) {
                 Ok(out) => OperationResult::Ok(out),
+                Err(err) if is_cancelled(&err) => OperationResult::Err(err),
                 Err(err) => {
                     error!("While trying to Terraform plan the rendered templates");
                     OperationResult::Retry(err)
@@ -85,28 +108,75 @@ pub fn terraform_init_validate_plan_apply(root_dir: &str, dry_run: bool) -> Resu
         });
 
         return match result {
-            Ok(_) => Ok(()),
+            Ok(_) => Ok(TerraformApplyResult::default()),
             Err(Operation { error, .. }) => Err(error),
             Err(retry::Error::Internal(e)) => Err(SimpleError::new(SimpleErrorKind::Other, Some(e))),
         };
     }
 
-    match terraform_plan_apply(root_dir) {
-        Ok(_) => Ok(()),
-        Err(e) => Err(e),
+    match terraform_plan_apply(root_dir, cancellation_token) {
+        Ok(_) => {}
+        Err(e) => return Err(e),
+    }
+
+    // resources are created at this point, unreadable outputs must not fail the apply
+    match terraform_output(root_dir) {
+        Ok(apply_result) => Ok(apply_result),
+        Err(e) => {
+            warn!("error while trying to read terraform outputs: {:?}", e.message);
+            Ok(TerraformApplyResult::default())
+        }
+    }
+}
+
+/// Outputs of the already applied templates, read from their stored state without applying anything.
+pub fn terraform_init_output(
+    root_dir: &str,
+    cancellation_token: &CancellationToken,
+) -> Result<TerraformApplyResult, SimpleError> {
+    terraform_init_validate(root_dir, cancellation_token)?;
+
+    terraform_output(root_dir)
+}
+
+pub fn terraform_output(root_dir: &str) -> Result<TerraformApplyResult, SimpleError> {
+    // outputs may hold credentials (ex: connection strings), only their names are logged
+    let output =
+        terraform_exec_with_stdout_logging(root_dir, vec!["output", "-json"], &CancellationToken::default(), false)?;
+    let apply_result = parse_terraform_output(&output)?;
+
+    info!(
+        "terraform outputs: {}",
+        apply_result.outputs.keys().cloned().collect::<Vec<String>>().join(", ")
+    );
+
+    Ok(apply_result)
+}
+
+fn parse_terraform_output(lines: &[String]) -> Result<TerraformApplyResult, SimpleError> {
+    match serde_json::from_str::<BTreeMap<String, TerraformOutput>>(lines.join("\n").as_str()) {
+        Ok(outputs) => Ok(TerraformApplyResult { outputs }),
+        Err(e) => Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!("terraform outputs can't be parsed: {}", e)),
+        )),
     }
 }
 
-pub fn terraform_init_validate_destroy(root_dir: &str, run_apply_before_destroy: bool) -> Result<(), SimpleError> {
+pub fn terraform_init_validate_destroy(
+    root_dir: &str,
+    run_apply_before_destroy: bool,
+    cancellation_token: &CancellationToken,
+) -> Result<(), SimpleError> {
     // terraform init
-    match terraform_init_validate(root_dir) {
+    match terraform_init_validate(root_dir, cancellation_token) {
         Err(e) => return Err(e),
         Ok(_) => {}
     }
 
     // better to apply before destroy to ensure terraform destroy will delete on all resources
     if run_apply_before_destroy {
-        match terraform_plan_apply(root_dir) {
+        match terraform_plan_apply(root_dir, cancellation_token) {
             Ok(_) => {}
             Err(e) => return Err(e),
         }
@@ -114,8 +184,9 @@ pub fn terraform_init_validate_destroy(root_dir: &str, run_apply_before_destroy:
 
     // terraform destroy
     let result = retry::retry(Fixed::from_millis(3000).take(5), || {
-        match terraform_exec(root_dir, vec!["destroy", "-auto-approve"]) {
+        match terraform_exec_cancellable(root_dir, vec!["destroy", "-auto-approve"], cancellation_token) {
             Ok(out) => OperationResult::Ok(out),
+            Err(err) if is_cancelled(&err) => OperationResult::Err(err),
             Err(err) => {
                 error!("error while trying to run terraform destroy on rendered templates, retrying...");
                 OperationResult::Retry(err)
@@ -130,19 +201,21 @@ pub fn terraform_init_validate_destroy(root_dir: &str, run_apply_before_destroy:
     }
 }
 
-fn terraform_plan_apply(root_dir: &str) -> Result<(), SimpleError> {
+fn terraform_plan_apply(root_dir: &str, cancellation_token: &CancellationToken) -> Result<(), SimpleError> {
     let result = retry::retry(Fixed::from_millis(3000).take(5), || {
         // plan
-        match terraform_exec(root_dir, vec!["plan", "-out", "tf_plan"]) {
+        match terraform_exec_cancellable(root_dir, vec!["plan", "-out", "tf_plan"], cancellation_token) {
             Ok(_) => {}
+            Err(err) if is_cancelled(&err) => return OperationResult::Err(err),
             Err(err) => {
                 error!("While trying to Terraform plan the rendered templates");
                 return OperationResult::Retry(err);
             }
         };
         // apply
-        match terraform_exec(root_dir, vec!["apply", "-auto-approve", "tf_plan"]) {
+        match terraform_exec_cancellable(root_dir, vec!["apply", "-auto-approve", "tf_plan"], cancellation_token) {
             Ok(out) => OperationResult::Ok(out),
+            Err(err) if is_cancelled(&err) => OperationResult::Err(err),
             Err(err) => {
                 error!("error while trying to run terraform apply on rendered templates, retrying...");
                 OperationResult::Retry(err)
@@ -159,7 +232,7 @@ fn terraform_plan_apply(root_dir: &str) -> Result<(), SimpleError> {
 
 pub fn terraform_init_validate_state_list(root_dir: &str) -> Result<Vec<String>, SimpleError> {
     // terraform init and validate
-    match terraform_init_validate(root_dir) {
+    match terraform_init_validate(root_dir, &CancellationToken::default()) {
         Err(e) => return Err(e),
         Ok(_) => {}
     }
@@ -183,6 +256,28 @@ pub fn terraform_init_validate_state_list(root_dir: &str) -> Result<Vec<String>,
 }
 
 pub fn terraform_exec(root_dir: &str, args: Vec<&str>) -> Result<Vec<String>, SimpleError> {
+    terraform_exec_cancellable(root_dir, args, &CancellationToken::default())
+}
+
+/// Cancelled errors must not be retried, the whole terraform run is aborted.
+fn is_cancelled(err: &SimpleError) -> bool {
+    matches!(err.kind, SimpleErrorKind::Cancelled)
+}
+
+fn terraform_exec_cancellable(
+    root_dir: &str,
+    args: Vec<&str>,
+    cancellation_token: &CancellationToken,
+) -> Result<Vec<String>, SimpleError> {
+    terraform_exec_with_stdout_logging(root_dir, args, cancellation_token, true)
+}
+
+fn terraform_exec_with_stdout_logging(
+    root_dir: &str,
+    args: Vec<&str>,
+    cancellation_token: &CancellationToken,
+    log_stdout: bool,
+) -> Result<Vec<String>, SimpleError> {
     // override if environment variable is set
     let tf_plugin_cache_dir_value = match env::var_os(TF_PLUGIN_CACHE_DIR) {
         Some(val) => format!("{:?}", val),
@@ -194,14 +289,16 @@ pub fn terraform_exec(root_dir: &str, args: Vec<&str>) -> Result<Vec<String>, Si
 
     let mut stdout = Vec::new();
     let mut stderr = Vec::new();
-    let result = exec_with_envs_and_output(
+    let result = exec_with_envs_and_output_cancellable(
         format!("{} terraform", root_dir).as_str(),
         args,
         vec![(TF_PLUGIN_CACHE_DIR, tf_plugin_cache_dir_value.as_str())],
         |line: Result<String, std::io::Error>| {
             let output = line.unwrap();
             stdout.push(output.clone());
-            info!("{}", &output)
+            if log_stdout {
+                info!("{}", &output)
+            }
         },
         |line: Result<String, std::io::Error>| {
             let output = line.unwrap();
@@ -209,6 +306,7 @@ pub fn terraform_exec(root_dir: &str, args: Vec<&str>) -> Result<Vec<String>, Si
             error!("{}", &output);
         },
         Duration::max_value(),
+        cancellation_token,
     );
 
     stdout.extend(stderr);
@@ -224,11 +322,58 @@ pub fn terraform_exec(root_dir: &str, args: Vec<&str>) -> Result<Vec<String>, Si
 
 #[cfg(test)]
 mod tests {
-    use crate::cmd::terraform::terraform_init_validate;
+    use crate::cmd::terraform::{parse_terraform_output, terraform_init_validate};
+    use crate::models::CancellationToken;
     use std::fs;
     use tracing::{span, Level};
     use tracing_test::traced_test;
 
+    #[test]
+    fn test_parse_terraform_output() {
+        // setup:
+        let lines: Vec<String> = r#"{
+  "database_endpoint": {
+    "sensitive": false,
+    "type": "string",
+    "value": "mysql-abc.c2sddxmbdpmu.eu-west-3.rds.amazonaws.com"
+  },
+  "security_group_ids": {
+    "sensitive": false,
+    "type": ["list", "string"],
+    "value": ["sg-0a1b2c3d", "sg-4e5f6a7b"]
+  },
+  "database_password": {
+    "sensitive": true,
+    "type": "string",
+    "value": "p4ssw0rd"
+  }
+}"#
+        .lines()
+        .map(|line| line.to_string())
+        .collect();
+
+        // execute:
+        let apply_result = parse_terraform_output(&lines).unwrap();
+        let no_outputs = parse_terraform_output(&["{}".to_string()]).unwrap();
+        let invalid = parse_terraform_output(&["Error: no state".to_string()]);
+
+        // verify:
+        assert_eq!(apply_result.outputs.len(), 3);
+        assert_eq!(
+            apply_result.output_string("database_endpoint"),
+            Some("mysql-abc.c2sddxmbdpmu.eu-west-3.rds.amazonaws.com")
+        );
+        assert_eq!(
+            apply_result.outputs["security_group_ids"].value,
+            serde_json::json!(["sg-0a1b2c3d", "sg-4e5f6a7b"])
+        );
+        assert_eq!(apply_result.output_string("security_group_ids"), None);
+        assert!(apply_result.outputs["database_password"].sensitive);
+        assert_eq!(apply_result.output_string("unknown"), None);
+        assert!(no_outputs.outputs.is_empty());
+        assert!(invalid.is_err());
+    }
+
     #[test]
     #[traced_test]
     // https://github.com/hashicorp/terraform/issues/28041
@@ -269,7 +414,7 @@ terraform {
         let _ = fs::write(format!("{}/.terraform.lock.hcl", &dest_dir), terraform_lock_file);
         let _ = fs::write(format!("{}/providers.tf", &dest_dir), provider_file);
 
-        let res = terraform_init_validate(dest_dir);
+        let res = terraform_init_validate(dest_dir, &CancellationToken::default());
 
         assert!(res.is_ok());
     }
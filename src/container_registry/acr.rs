@@ -0,0 +1,816 @@
+extern crate reqwest;
+
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, Utc};
+use reqwest::blocking::{Client, Response};
+use reqwest::header::ACCEPT;
+use reqwest::{Method, StatusCode};
+use retry::delay::Fixed;
+use retry::Error::Operation;
+use retry::OperationResult;
+use serde::{Deserialize, Serialize};
+
+use crate::build_platform::Image;
+use crate::cmd::docker::docker_login;
+use crate::container_registry::utilities::docker_tag_and_push_image;
+use crate::container_registry::{
+    ContainerRegistry, EngineError, ImageExistsCache, Kind, PushResult, RegistryCredentials, MANIFEST_MEDIA_TYPES,
+};
+use crate::error::{check_credentials_are_set, EngineErrorCause, StringError};
+use crate::models::{
+    Context, Listen, Listener, Listeners, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope,
+};
+
+const AZURE_LOGIN_URL: &str = "https://login.microsoftonline.com";
+const AZURE_MANAGEMENT_URL: &str = "https://management.azure.com";
+const ACR_API_VERSION: &str = "2019-05-01";
+/// SKU of the registries created by the engine, enough for the images of a cluster.
+const ACR_SKU: &str = "Basic";
+/// Azure AD tokens are valid about an hour, ACR refresh tokens a bit longer: both are renewed a bit before the
+/// Azure AD one expires.
+const AUTH_TOKEN_REFRESH_MARGIN_MINUTES: i64 = 5;
+const AUTH_TOKEN_DEFAULT_VALIDITY_MINUTES: i64 = 60;
+
+/// Azure AD application the engine authenticates with, it needs the `AcrPush` role on the registry.
+#[derive(Clone)]
+pub struct AzureServicePrincipal {
+    pub tenant_id: String,
+    pub client_id: String,
+    /// Asked before every operation, so the secret can be rotated.
+    pub client_secret: RegistryCredentials,
+}
+
+pub struct ACR {
+    context: Context,
+    id: String,
+    /// Registry name, it's also the subdomain of its login server `<name>.azurecr.io`.
+    name: String,
+    subscription_id: String,
+    resource_group: String,
+    /// Azure location the registry is created in when it doesn't exist yet, ex: `westeurope`.
+    location: String,
+    service_principal: AzureServicePrincipal,
+    listeners: Listeners,
+    image_exists_cache: ImageExistsCache,
+    auth_token: Mutex<Option<AcrAuthToken>>,
+}
+
+#[derive(Deserialize)]
+struct AzureAccessToken {
+    access_token: String,
+    /// Validity in seconds.
+    #[serde(default)]
+    expires_in: Option<i64>,
+}
+
+/// Azure AD token of the service principal and the ACR refresh token it has been exchanged for.
+#[derive(Clone, Debug, PartialEq)]
+struct AcrAuthToken {
+    azure_access_token: String,
+    refresh_token: String,
+    expires_at: DateTime<Utc>,
+}
+
+impl AcrAuthToken {
+    fn needs_refresh(&self, now: DateTime<Utc>) -> bool {
+        now + Duration::minutes(AUTH_TOKEN_REFRESH_MARGIN_MINUTES) >= self.expires_at
+    }
+}
+
+#[derive(Deserialize)]
+struct AcrRefreshToken {
+    refresh_token: String,
+}
+
+#[derive(Deserialize)]
+struct TagsList {
+    #[serde(default)]
+    tags: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArmRegistry {
+    location: String,
+    sku: ArmRegistrySku,
+    properties: ArmRegistryProperties,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ArmRegistrySku {
+    name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArmRegistryProperties {
+    #[serde(default)]
+    admin_user_enabled: bool,
+    #[serde(default, skip_serializing)]
+    provisioning_state: Option<String>,
+}
+
+impl ACR {
+    pub fn new(
+        context: Context,
+        id: &str,
+        name: &str,
+        subscription_id: &str,
+        resource_group: &str,
+        location: &str,
+        service_principal: AzureServicePrincipal,
+    ) -> Self {
+        ACR {
+            context,
+            id: id.to_string(),
+            name: name.to_string(),
+            subscription_id: subscription_id.to_string(),
+            resource_group: resource_group.to_string(),
+            location: location.to_string(),
+            service_principal,
+            listeners: vec![],
+            image_exists_cache: ImageExistsCache::default(),
+            auth_token: Mutex::new(None),
+        }
+    }
+
+    /// Host docker pushes to, Azure only serves it in lower case.
+    fn login_server(&self) -> String {
+        format!("{}.azurecr.io", self.name.to_lowercase())
+    }
+
+    fn arm_registry_url(&self) -> String {
+        format!(
+            "{}/subscriptions/{}/resourceGroups/{}/providers/Microsoft.ContainerRegistry/registries/{}?api-version={}",
+            AZURE_MANAGEMENT_URL, self.subscription_id, self.resource_group, self.name, ACR_API_VERSION
+        )
+    }
+
+    fn credentials_error(&self, message: String) -> EngineError {
+        self.engine_error(
+            EngineErrorCause::User(
+                "Your Azure service principal seems to be no longer valid (bad Credentials). \
+                Please contact your Organization administrator to fix or change the Credentials.",
+            ),
+            message,
+        )
+    }
+
+    /// Azure AD token of the service principal, valid for the Azure Resource Manager API and the registry exchange.
+    fn azure_access_token(&self) -> Result<AzureAccessToken, EngineError> {
+        let response = Client::new()
+            .post(
+                format!(
+                    "{}/{}/oauth2/v2.0/token",
+                    AZURE_LOGIN_URL, self.service_principal.tenant_id
+                )
+                .as_str(),
+            )
+            .form(&[
+                ("grant_type", "client_credentials"),
+                ("client_id", self.service_principal.client_id.as_str()),
+                ("client_secret", self.service_principal.client_secret.get().as_str()),
+                ("scope", format!("{}/.default", AZURE_MANAGEMENT_URL).as_str()),
+            ])
+            .send()
+            .map_err(|e| {
+                self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!("No response from the Azure login API : {:?}", e),
+                )
+            })?;
+
+        match response.status() {
+            status if status.is_success() => response.json::<AzureAccessToken>().map_err(|e| {
+                self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!("Unreadable Azure access token : {:?}", e),
+                )
+            }),
+            StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED => Err(self.credentials_error(format!(
+                "failed to authenticate the service principal of ACR {}",
+                self.name_with_id()
+            ))),
+            status => Err(self.engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "Bad status code : {} returned by the Azure login API for ACR {}",
+                    status,
+                    self.name_with_id()
+                ),
+            )),
+        }
+    }
+
+    /// ACR only accepts its own tokens: the Azure AD token is exchanged for a refresh token, valid as long as it.
+    fn get_auth_token(&self) -> Result<AcrAuthToken, EngineError> {
+        let service = self.login_server();
+        let azure_access_token = self.azure_access_token()?;
+        let expires_at = Utc::now()
+            + match azure_access_token.expires_in {
+                Some(expires_in) => Duration::seconds(expires_in),
+                None => Duration::minutes(AUTH_TOKEN_DEFAULT_VALIDITY_MINUTES),
+            };
+
+        let refresh_token = Client::new()
+            .post(format!("https://{}/oauth2/exchange", service).as_str())
+            .form(&[
+                ("grant_type", "access_token"),
+                ("service", service.as_str()),
+                ("tenant", self.service_principal.tenant_id.as_str()),
+                ("access_token", azure_access_token.access_token.as_str()),
+            ])
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.json::<AcrRefreshToken>())
+            .map_err(|e| self.registry_token_error(e))?
+            .refresh_token;
+
+        Ok(AcrAuthToken {
+            azure_access_token: azure_access_token.access_token,
+            refresh_token,
+            expires_at,
+        })
+    }
+
+    /// Cached tokens, exchanged again only when they are about to expire (e.g. during long deployments).
+    fn auth_token(&self) -> Result<AcrAuthToken, EngineError> {
+        cached_auth_token(&self.auth_token, Utc::now(), || self.get_auth_token())
+    }
+
+    fn registry_token_error(&self, e: reqwest::Error) -> EngineError {
+        self.engine_error(
+            EngineErrorCause::Internal,
+            format!(
+                "Failed to get an access token from ACR {} : {:?}",
+                self.name_with_id(),
+                e
+            ),
+        )
+    }
+
+    /// Access token limited to `scope`, ex: `repository:my-app:pull`, from the cached refresh token.
+    fn registry_access_token(&self, scope: &str) -> Result<String, EngineError> {
+        let service = self.login_server();
+        let refresh_token = self.auth_token()?.refresh_token;
+
+        Client::new()
+            .post(format!("https://{}/oauth2/token", service).as_str())
+            .form(&[
+                ("grant_type", "refresh_token"),
+                ("service", service.as_str()),
+                ("scope", scope),
+                ("refresh_token", refresh_token.as_str()),
+            ])
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.json::<AzureAccessToken>())
+            .map(|token| token.access_token)
+            .map_err(|e| self.registry_token_error(e))
+    }
+
+    /// Request on the registry v2 API, the repository of `image_name` being the token scope.
+    fn registry_request(
+        &self,
+        method: Method,
+        image_name: &str,
+        path: &str,
+        accept: Option<&str>,
+    ) -> Result<Response, EngineError> {
+        let access_token = self.registry_access_token(format!("repository:{}:pull", image_name).as_str())?;
+        let request = Client::new()
+            .request(
+                method,
+                format!("https://{}/v2/{}/{}", self.login_server(), image_name, path).as_str(),
+            )
+            .bearer_auth(access_token);
+        let request = match accept {
+            Some(accept) => request.header(ACCEPT, accept),
+            None => request,
+        };
+
+        request.send().map_err(|e| {
+            self.engine_error(
+                EngineErrorCause::Internal,
+                format!("No response from ACR {} : {:?}", self.name_with_id(), e),
+            )
+        })
+    }
+
+    pub fn list_tags(&self, image_name: &str) -> Result<Vec<String>, EngineError> {
+        let response = self.registry_request(Method::GET, image_name, "tags/list", None)?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(vec![]),
+            status if status.is_success() => response
+                .json::<TagsList>()
+                .map(|tags_list| tags_list.tags.unwrap_or_default())
+                .map_err(|e| {
+                    self.engine_error(
+                        EngineErrorCause::Internal,
+                        format!(
+                            "Unreadable tags of {} on ACR {} : {:?}",
+                            image_name,
+                            self.name_with_id(),
+                            e
+                        ),
+                    )
+                }),
+            status => Err(self.engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "Bad status code : {} returned by ACR {} for listing tags of {}",
+                    status,
+                    self.name_with_id(),
+                    image_name
+                ),
+            )),
+        }
+    }
+
+    fn get_registry(&self, azure_access_token: &str) -> Result<Option<ArmRegistry>, EngineError> {
+        let response = Client::new()
+            .get(self.arm_registry_url().as_str())
+            .bearer_auth(azure_access_token)
+            .send()
+            .map_err(|e| {
+                self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!("No response from the Azure Resource Manager API : {:?}", e),
+                )
+            })?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            StatusCode::FORBIDDEN => Err(self.credentials_error(format!(
+                "the service principal isn't allowed to read ACR {} in resource group {}",
+                self.name_with_id(),
+                self.resource_group
+            ))),
+            status if status.is_success() => response.json::<ArmRegistry>().map(Some).map_err(|e| {
+                self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!("Unreadable ACR {} : {:?}", self.name_with_id(), e),
+                )
+            }),
+            status => Err(self.engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "Bad status code : {} returned by the Azure Resource Manager API for getting ACR {}",
+                    status,
+                    self.name_with_id()
+                ),
+            )),
+        }
+    }
+
+    fn create_registry(&self, azure_access_token: &str) -> Result<(), EngineError> {
+        let registry = ArmRegistry {
+            location: self.location.clone(),
+            sku: ArmRegistrySku {
+                name: ACR_SKU.to_string(),
+            },
+            properties: ArmRegistryProperties {
+                admin_user_enabled: false,
+                provisioning_state: None,
+            },
+        };
+
+        let response = Client::new()
+            .put(self.arm_registry_url().as_str())
+            .bearer_auth(azure_access_token)
+            .json(&registry)
+            .send()
+            .map_err(|e| {
+                self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!("No response from the Azure Resource Manager API : {:?}", e),
+                )
+            })?;
+
+        match response.status() {
+            status if status.is_success() => {}
+            StatusCode::CONFLICT => {
+                return Err(self.engine_error(
+                    EngineErrorCause::User("ACR names are global, choose another name for your container registry"),
+                    format!("ACR name {} is already used outside of your subscription", self.name),
+                ))
+            }
+            status => {
+                return Err(self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!(
+                        "Bad status code : {} returned by the Azure Resource Manager API for creating ACR {}",
+                        status,
+                        self.name_with_id()
+                    ),
+                ))
+            }
+        }
+
+        // the registry is provisioned asynchronously, pushes fail until it's done
+        let result = retry::retry(Fixed::from_millis(5000).take(24), || {
+            match self.get_registry(azure_access_token) {
+                Ok(Some(registry)) if registry.properties.provisioning_state.as_deref() == Some("Succeeded") => {
+                    OperationResult::Ok(())
+                }
+                Ok(_) => {
+                    info!("ACR {} is not provisioned yet, retrying...", self.name_with_id());
+                    OperationResult::Retry(self.engine_error(
+                        EngineErrorCause::Internal,
+                        format!("ACR {} has not been provisioned in time", self.name_with_id()),
+                    ))
+                }
+                Err(err) => OperationResult::Err(err),
+            }
+        });
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(Operation { error, .. }) => Err(error),
+            Err(retry::Error::Internal(e)) => Err(self.engine_error(EngineErrorCause::Internal, e)),
+        }
+    }
+
+    fn get_docker_json_config_raw(&self) -> String {
+        base64::encode(
+            format!(
+                r#"{{"auths":{{"{}":{{"auth":"{}"}}}}}}"#,
+                self.login_server(),
+                base64::encode(
+                    format!(
+                        "{}:{}",
+                        self.service_principal.client_id,
+                        self.service_principal.client_secret.get()
+                    )
+                    .as_bytes()
+                )
+            )
+            .as_bytes(),
+        )
+    }
+
+    fn get_docker_envs(&self) -> Vec<(&str, &str)> {
+        match self.context.docker_tcp_socket() {
+            Some(tcp_socket) => vec![("DOCKER_HOST", tcp_socket.as_str())],
+            None => vec![],
+        }
+    }
+}
+
+/// Azure rules for registry names: 5 to 50 alphanumeric characters.
+fn validate_registry_name(name: &str) -> Result<(), StringError> {
+    if !(5..=50).contains(&name.len()) {
+        return Err(format!(
+            "ACR name {} must be between 5 and 50 characters long, it has {}",
+            name,
+            name.len()
+        ));
+    }
+
+    if !name.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("ACR name {} must only contain letters and digits", name));
+    }
+
+    Ok(())
+}
+
+impl ContainerRegistry for ACR {
+    fn context(&self) -> &Context {
+        &self.context
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Acr
+    }
+
+    fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn is_valid(&self) -> Result<(), EngineError> {
+        validate_registry_name(self.name.as_str()).map_err(|err| {
+            self.engine_error(
+                EngineErrorCause::User("Check the name of your Azure container registry"),
+                err,
+            )
+        })
+    }
+
+    fn validate_credentials(&self) -> Result<(), EngineError> {
+        check_credentials_are_set(&[
+            ("tenant id", self.service_principal.tenant_id.as_str()),
+            ("client id", self.service_principal.client_id.as_str()),
+            ("client secret", self.service_principal.client_secret.get().as_str()),
+        ])
+        .map_err(|err| self.credentials_error(format!("{} for ACR {}", err, self.name_with_id())))?;
+
+        self.azure_access_token().map(|_| ())
+    }
+
+    fn on_create(&self) -> Result<(), EngineError> {
+        info!("ACR.on_create() called for {}", self.name_with_id());
+        self.is_valid()?;
+
+        let azure_access_token = self.auth_token()?.azure_access_token;
+        if self.get_registry(azure_access_token.as_str())?.is_some() {
+            return Ok(());
+        }
+
+        // repositories don't have to be created, ACR creates them on push
+        self.create_registry(azure_access_token.as_str())?;
+        info!("ACR {} has been created", self.name_with_id());
+
+        Ok(())
+    }
+
+    fn on_create_error(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_delete(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_delete_error(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn image_exists_cache(&self) -> &ImageExistsCache {
+        &self.image_exists_cache
+    }
+
+    fn does_image_exists_uncached(&self, image: &Image) -> bool {
+        let path = format!("manifests/{}", image.tag);
+
+        match self.registry_request(
+            Method::HEAD,
+            image.name.as_str(),
+            path.as_str(),
+            Some(MANIFEST_MEDIA_TYPES),
+        ) {
+            Ok(response) => response.status().is_success(),
+            Err(e) => {
+                error!(
+                    "While trying to retrieve if image exists on {}: {:?}",
+                    self.name_with_id(),
+                    e.message
+                );
+                false
+            }
+        }
+    }
+
+    fn image_url(&self, image: &Image) -> Option<String> {
+        Some(format!("{}/{}", self.login_server(), image.name_with_tag()))
+    }
+
+    fn push(&self, image: &Image, force_push: bool) -> Result<PushResult, EngineError> {
+        let envs = self.get_docker_envs();
+        let login_server = self.login_server();
+
+        if let Err(e) = docker_login(
+            &envs,
+            Some(login_server.as_str()),
+            self.service_principal.client_id.as_str(),
+            self.service_principal.client_secret.get().as_str(),
+        ) {
+            error!("{:?}", e.message);
+            return Err(self.credentials_error(format!("failed to login to ACR {}", self.name_with_id())));
+        };
+
+        let dest = format!("{}/{}", login_server, image.name_with_tag());
+        let mut pushed_image = image.clone();
+        pushed_image.registry_name = Some(self.name.clone());
+        pushed_image.registry_url = Some(login_server.clone());
+        pushed_image.registry_secret = Some(self.service_principal.client_secret.get());
+        pushed_image.registry_docker_json_config = Some(self.get_docker_json_config_raw());
+
+        let listeners_helper = ListenersHelper::new(&self.listeners);
+
+        if !force_push && self.does_image_exists(image) {
+            // check if image does exist - if yes, do not upload it again
+            let info_message = format!(
+                "image {:?} found on ACR {} repository, container build is not required",
+                image,
+                self.name()
+            );
+
+            info!("{}", info_message.as_str());
+
+            listeners_helper.deployment_in_progress(ProgressInfo::new(
+                ProgressScope::Application {
+                    id: image.application_id.clone(),
+                },
+                ProgressLevel::Info,
+                Some(info_message),
+                self.context.execution_id(),
+            ));
+
+            return Ok(PushResult::already_pushed(pushed_image, dest));
+        }
+
+        let info_message = format!(
+            "image {:?} does not exist on ACR {} repository, starting image upload",
+            image,
+            self.name()
+        );
+
+        info!("{}", info_message.as_str());
+
+        listeners_helper.deployment_in_progress(ProgressInfo::new(
+            ProgressScope::Application {
+                id: image.application_id.clone(),
+            },
+            ProgressLevel::Info,
+            Some(info_message),
+            self.context.execution_id(),
+        ));
+
+        let push_result = docker_tag_and_push_image(
+            self.kind(),
+            envs,
+            image.name.clone(),
+            image.tag.clone(),
+            self.context.rewrite_image(dest.as_str()),
+        );
+        self.image_exists_cache.invalidate(image);
+
+        match push_result {
+            Ok(push_output) => Ok(PushResult::pushed(pushed_image, push_output)),
+            Err(e) => Err(self.push_failure_error(
+                e.message
+                    .unwrap_or_else(|| "unknown error occurring during docker push".to_string()),
+            )),
+        }
+    }
+
+    fn push_error(&self, image: &Image) -> Result<PushResult, EngineError> {
+        let reference = format!("{}/{}", self.login_server(), image.name_with_tag());
+        Ok(PushResult::already_pushed(image.clone(), reference))
+    }
+}
+
+/// Cached token, or a new one from `refresh` if there is none or if it is about to expire.
+fn cached_auth_token<F, E>(
+    cache: &Mutex<Option<AcrAuthToken>>,
+    now: DateTime<Utc>,
+    refresh: F,
+) -> Result<AcrAuthToken, E>
+where
+    F: FnOnce() -> Result<AcrAuthToken, E>,
+{
+    let mut cached_token = cache.lock().unwrap();
+
+    match cached_token.as_ref() {
+        Some(token) if !token.needs_refresh(now) => Ok(token.clone()),
+        _ => {
+            let token = refresh()?;
+            *cached_token = Some(token.clone());
+            Ok(token)
+        }
+    }
+}
+
+impl Listen for ACR {
+    fn listeners(&self) -> &Listeners {
+        &self.listeners
+    }
+
+    fn add_listener(&mut self, listener: Listener) {
+        self.listeners.push(listener);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::container_registry::acr::{
+        cached_auth_token, validate_registry_name, AcrAuthToken, ArmRegistry, ArmRegistryProperties, ArmRegistrySku,
+        AzureServicePrincipal, ACR,
+    };
+    use crate::container_registry::{ContainerRegistry, RegistryCredentials};
+    use crate::error::EngineErrorCause;
+    use crate::models::Context;
+    use chrono::{Duration, TimeZone, Utc};
+    use std::cell::Cell;
+    use std::sync::Mutex;
+
+    fn registry(name: &str, client_secret: &str) -> ACR {
+        let context = Context::new(
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            false,
+            None,
+            vec![],
+            None,
+        );
+
+        ACR::new(
+            context,
+            "id",
+            name,
+            "subscription",
+            "qovery",
+            "westeurope",
+            AzureServicePrincipal {
+                tenant_id: "tenant".to_string(),
+                client_id: "client".to_string(),
+                client_secret: RegistryCredentials::constant(client_secret),
+            },
+        )
+    }
+
+    #[test]
+    fn test_registry_name_validation() {
+        assert!(validate_registry_name("QoveryRegistry01").is_ok());
+        assert_eq!(
+            validate_registry_name("qov").unwrap_err(),
+            "ACR name qov must be between 5 and 50 characters long, it has 3"
+        );
+        assert!(validate_registry_name(&"q".repeat(51)).is_err());
+        assert_eq!(
+            validate_registry_name("qovery-registry").unwrap_err(),
+            "ACR name qovery-registry must only contain letters and digits"
+        );
+
+        let err = registry("qovery_registry", "secret").is_valid().unwrap_err();
+        assert!(matches!(err.cause, EngineErrorCause::User(_)));
+    }
+
+    #[test]
+    fn test_registry_urls_and_payloads() {
+        // setup:
+        let registry = registry("QoveryRegistry", "secret");
+        let created_registry = ArmRegistry {
+            location: "westeurope".to_string(),
+            sku: ArmRegistrySku {
+                name: "Basic".to_string(),
+            },
+            properties: ArmRegistryProperties {
+                admin_user_enabled: false,
+                provisioning_state: None,
+            },
+        };
+
+        // execute:
+        let provisioned_registry = serde_json::from_str::<ArmRegistry>(
+            r#"{"location": "westeurope", "sku": {"name": "Basic", "tier": "Basic"},
+            "properties": {"loginServer": "qoveryregistry.azurecr.io", "provisioningState": "Succeeded"}}"#,
+        )
+        .unwrap();
+
+        // verify:
+        assert_eq!(registry.login_server(), "qoveryregistry.azurecr.io");
+        assert_eq!(
+            registry.arm_registry_url(),
+            "https://management.azure.com/subscriptions/subscription/resourceGroups/qovery/providers/Microsoft.ContainerRegistry/registries/QoveryRegistry?api-version=2019-05-01"
+        );
+        assert_eq!(
+            serde_json::to_string(&created_registry).unwrap(),
+            r#"{"location":"westeurope","sku":{"name":"Basic"},"properties":{"adminUserEnabled":false}}"#
+        );
+        assert_eq!(
+            provisioned_registry.properties.provisioning_state.as_deref(),
+            Some("Succeeded")
+        );
+    }
+
+    #[test]
+    fn test_auth_token_refresh() {
+        // setup:
+        let fetched_at = Utc.ymd(2021, 6, 1).and_hms(8, 0, 0);
+        let token = |expires_at| AcrAuthToken {
+            azure_access_token: "azure-access-token".to_string(),
+            refresh_token: "refresh-token".to_string(),
+            expires_at,
+        };
+        let cache = Mutex::new(None);
+        let refreshes = Cell::new(0);
+        let refresh = || -> Result<AcrAuthToken, String> {
+            refreshes.set(refreshes.get() + 1);
+            Ok(token(fetched_at + Duration::hours(1) * refreshes.get()))
+        };
+
+        // execute:
+        let first = cached_auth_token(&cache, fetched_at, refresh).unwrap();
+        let still_valid = cached_auth_token(&cache, fetched_at + Duration::minutes(50), refresh).unwrap();
+        let expiring = cached_auth_token(&cache, fetched_at + Duration::minutes(57), refresh).unwrap();
+        let failed_refresh = cached_auth_token(&cache, fetched_at + Duration::hours(3), || {
+            Err::<AcrAuthToken, String>("expired credentials".to_string())
+        });
+
+        // verify:
+        assert_eq!(first.expires_at, fetched_at + Duration::hours(1));
+        assert_eq!(still_valid, first);
+        assert_eq!(expiring.expires_at, fetched_at + Duration::hours(2));
+        assert_eq!(refreshes.get(), 2);
+        assert_eq!(failed_refresh.unwrap_err(), "expired credentials");
+        assert_eq!(cache.lock().unwrap().as_ref(), Some(&expiring));
+    }
+}
@@ -1,34 +1,142 @@
 extern crate reqwest;
 
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
 use reqwest::StatusCode;
 
 use crate::build_platform::Image;
 use crate::cmd;
+use crate::cmd::docker::{docker_login, RATE_LIMIT_ERROR_CODE};
 use crate::container_registry::utilities::docker_tag_and_push_image;
-use crate::container_registry::{ContainerRegistry, EngineError, Kind, PushResult};
-use crate::error::EngineErrorCause;
+use crate::container_registry::{
+    ContainerRegistry, EngineError, ImageExistsCache, Kind, PushFailureKind, PushResult, RegistryCredentials,
+};
+use crate::error::{check_credentials_are_set, EngineErrorCause, EngineErrorCode};
 use crate::models::{
     Context, Listen, Listener, Listeners, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope,
 };
 
+const DOCKER_HUB_LOGIN_URL: &str = "https://hub.docker.com/v2/users/login";
+
+const BAD_CREDENTIALS: &str = "Your DockerHub account seems to be no longer valid (bad Credentials). \
+    Please contact your Organization administrator to fix or change the Credentials.";
+
+const RATE_LIMITED: &str = "The DockerHub rate limit has been reached. \
+    Set the credentials of a DockerHub account to get a higher limit, or wait for the limit to be reset.";
+
+/// Unix timestamp the DockerHub rate limit is reset at.
+const RATE_LIMIT_RESET_HEADER: &str = "x-ratelimit-reset";
+
+/// DockerHub answers `429 Too Many Requests` with a `toomanyrequests` error once its rate limit is reached.
+#[derive(Debug, PartialEq)]
+struct RateLimitExceeded {
+    reset_at: Option<DateTime<Utc>>,
+}
+
+impl RateLimitExceeded {
+    fn from_response(status: StatusCode, headers: &HeaderMap, body: &str, now: DateTime<Utc>) -> Option<Self> {
+        if status != StatusCode::TOO_MANY_REQUESTS && !body.to_lowercase().contains(RATE_LIMIT_ERROR_CODE) {
+            return None;
+        }
+
+        let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+        let reset_at = match header(RATE_LIMIT_RESET_HEADER).and_then(|reset| reset.parse::<i64>().ok()) {
+            Some(timestamp) => Utc.timestamp_opt(timestamp, 0).single(),
+            None => header(RETRY_AFTER.as_str())
+                .and_then(|seconds| seconds.parse::<i64>().ok())
+                .map(|seconds| now + Duration::seconds(seconds)),
+        };
+
+        Some(RateLimitExceeded { reset_at })
+    }
+
+    /// Docker CLI only prints the error, without any reset time.
+    fn from_output(output: &str) -> Option<Self> {
+        if output.to_lowercase().contains(RATE_LIMIT_ERROR_CODE) {
+            Some(RateLimitExceeded { reset_at: None })
+        } else {
+            None
+        }
+    }
+}
+
 pub struct DockerHub {
     context: Context,
     id: String,
     name: String,
     login: String,
-    password: String,
+    password: RegistryCredentials,
     listeners: Listeners,
+    image_exists_cache: ImageExistsCache,
 }
 
 impl DockerHub {
     pub fn new(context: Context, id: &str, name: &str, login: &str, password: &str) -> Self {
+        DockerHub::new_with_credentials(context, id, name, login, RegistryCredentials::constant(password))
+    }
+
+    /// The password is asked to `password` before every operation, so it can be rotated.
+    pub fn new_with_credentials(
+        context: Context,
+        id: &str,
+        name: &str,
+        login: &str,
+        password: RegistryCredentials,
+    ) -> Self {
         DockerHub {
             context,
             id: id.to_string(),
             name: name.to_string(),
             login: login.to_string(),
-            password: password.to_string(),
+            password,
             listeners: vec![],
+            image_exists_cache: ImageExistsCache::default(),
+        }
+    }
+
+    fn rate_limit_error(&self, operation: &str, rate_limit: &RateLimitExceeded) -> EngineError {
+        let reset = match rate_limit.reset_at {
+            Some(reset_at) => format!(", it is reset at {}", reset_at.to_rfc3339()),
+            None => String::new(),
+        };
+
+        self.engine_error(
+            EngineErrorCause::User(RATE_LIMITED),
+            format!(
+                "DockerHub rate limit reached while {} for {}{}",
+                operation,
+                self.name_with_id(),
+                reset
+            ),
+        )
+    }
+
+    fn image_exists(&self, image: &Image) -> Result<bool, EngineError> {
+        let path = format!(
+            "https://index.docker.io/v1/repositories/{}/{}/tags",
+            &self.login, image.name
+        );
+        let res = reqwest::blocking::Client::new()
+            .get(path.as_str())
+            .basic_auth(&self.login, Some(self.password.get()))
+            .send()
+            .map_err(|e| {
+                self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!("While trying to retrieve if DockerHub repository exist {:?}", e),
+                )
+            })?;
+
+        match res.status() {
+            StatusCode::OK => Ok(true),
+            status => {
+                let headers = res.headers().clone();
+                let body = res.text().unwrap_or_default();
+                match RateLimitExceeded::from_response(status, &headers, body.as_str(), Utc::now()) {
+                    Some(rate_limit) => Err(self.rate_limit_error("checking if an image exists", &rate_limit)),
+                    None => Ok(false),
+                }
+            }
         }
     }
 }
@@ -70,6 +178,58 @@ impl ContainerRegistry for DockerHub {
         Ok(())
     }
 
+    fn validate_credentials(&self) -> Result<(), EngineError> {
+        let password = self.password.get();
+        check_credentials_are_set(&[("login", self.login.as_str()), ("password", password.as_str())]).map_err(
+            |err| {
+                self.engine_error(
+                    EngineErrorCause::User(BAD_CREDENTIALS),
+                    format!("{} for DockerHub {}", err, self.name_with_id()),
+                )
+            },
+        )?;
+
+        let res = reqwest::blocking::Client::new()
+            .post(DOCKER_HUB_LOGIN_URL)
+            .json(&serde_json::json!({ "username": self.login, "password": password }))
+            .send()
+            .map_err(|e| {
+                self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!(
+                        "No response from DockerHub while checking {}: {:?}",
+                        self.name_with_id(),
+                        e
+                    ),
+                )
+            })?;
+
+        match res.status() {
+            StatusCode::OK => Ok(()),
+            StatusCode::UNAUTHORIZED => Err(self.engine_error(
+                EngineErrorCause::User(BAD_CREDENTIALS),
+                format!("failed to login to DockerHub {}", self.name_with_id()),
+            )),
+            status => {
+                let headers = res.headers().clone();
+                let body = res.text().unwrap_or_default();
+                if let Some(rate_limit) = RateLimitExceeded::from_response(status, &headers, body.as_str(), Utc::now())
+                {
+                    return Err(self.rate_limit_error("checking credentials", &rate_limit));
+                }
+
+                Err(self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!(
+                        "Bad status code : {} returned by DockerHub while checking {}",
+                        status,
+                        self.name_with_id()
+                    ),
+                ))
+            }
+        }
+    }
+
     fn on_create(&self) -> Result<(), EngineError> {
         Ok(())
     }
@@ -86,43 +246,34 @@ impl ContainerRegistry for DockerHub {
         Ok(())
     }
 
-    fn does_image_exists(&self, image: &Image) -> bool {
-        use reqwest::blocking::Client;
-        let client = Client::new();
-        let path = format!(
-            "https://index.docker.io/v1/repositories/{}/{}/tags",
-            &self.login, image.name
-        );
-        let res = client
-            .get(path.as_str())
-            .basic_auth(&self.login, Option::from(&self.password))
-            .send();
+    fn image_exists_cache(&self) -> &ImageExistsCache {
+        &self.image_exists_cache
+    }
 
-        match res {
-            Ok(out) => matches!(out.status(), StatusCode::OK),
+    fn does_image_exists_uncached(&self, image: &Image) -> bool {
+        match self.image_exists(image) {
+            Ok(exists) => exists,
             Err(e) => {
-                error!("While trying to retrieve if DockerHub repository exist {:?}", e);
+                error!("{:?}", e.message);
                 false
             }
         }
     }
 
+    fn image_url(&self, image: &Image) -> Option<String> {
+        Some(format!("{}/{}", self.login.as_str(), image.name_with_tag()))
+    }
+
     fn push(&self, image: &Image, force_push: bool) -> Result<PushResult, EngineError> {
         let envs = match self.context.docker_tcp_socket() {
             Some(tcp_socket) => vec![("DOCKER_HOST", tcp_socket.as_str())],
             None => vec![],
         };
 
-        if let Err(_) = cmd::utilities::exec(
-            "docker",
-            vec!["login", "-u", self.login.as_str(), "-p", self.password.as_str()],
-            &envs,
-        ) {
+        if let Err(e) = docker_login(&envs, None, self.login.as_str(), self.password.get().as_str()) {
+            error!("{:?}", e.message);
             return Err(self.engine_error(
-                EngineErrorCause::User(
-                    "Your DockerHub account seems to be no longer valid (bad Credentials). \
-                Please contact your Organization administrator to fix or change the Credentials.",
-                ),
+                EngineErrorCause::User(BAD_CREDENTIALS),
                 format!("failed to login to DockerHub {}", self.name_with_id()),
             ));
         };
@@ -150,9 +301,9 @@ impl ContainerRegistry for DockerHub {
             ));
 
             let mut image = image.clone();
-            image.registry_url = Some(dest);
+            image.registry_url = Some(dest.clone());
 
-            return Ok(PushResult { image });
+            return Ok(PushResult::already_pushed(image, dest));
         }
 
         let info_message = format!(
@@ -170,17 +321,32 @@ impl ContainerRegistry for DockerHub {
             self.context.execution_id(),
         ));
 
-        match docker_tag_and_push_image(self.kind(), vec![], image.name.clone(), image.tag.clone(), dest.clone()) {
-            Ok(_) => {
+        let push_result = docker_tag_and_push_image(
+            self.kind(),
+            vec![],
+            image.name.clone(),
+            image.tag.clone(),
+            self.context.rewrite_image(dest.as_str()),
+        );
+        self.image_exists_cache.invalidate(image);
+
+        match push_result {
+            Ok(push_output) => {
                 let mut image = image.clone();
                 image.registry_url = Some(dest);
-                Ok(PushResult { image })
+                Ok(PushResult::pushed(image, push_output))
+            }
+            Err(e) => {
+                let message = e
+                    .message
+                    .unwrap_or_else(|| "unknown error occurring during docker push".to_string());
+                match RateLimitExceeded::from_output(message.as_str()) {
+                    Some(rate_limit) => Err(self
+                        .rate_limit_error("pushing an image", &rate_limit)
+                        .with_code(EngineErrorCode::ContainerRegistryPush(PushFailureKind::Quota))),
+                    None => Err(self.push_failure_error(message)),
+                }
             }
-            Err(e) => Err(self.engine_error(
-                EngineErrorCause::Internal,
-                e.message
-                    .unwrap_or_else(|| "unknown error occurring during docker push".to_string()),
-            )),
         }
     }
 
@@ -198,3 +364,89 @@ impl Listen for DockerHub {
         self.listeners.push(listener);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+    use reqwest::StatusCode;
+
+    use crate::container_registry::docker_hub::{DockerHub, RateLimitExceeded, RATE_LIMIT_RESET_HEADER};
+    use crate::error::EngineErrorCause;
+    use crate::models::Context;
+
+    #[test]
+    fn test_rate_limit_detection() {
+        // setup:
+        let now = Utc.ymd(2021, 9, 1).and_hms(12, 0, 0);
+        let body = r#"{"errors":[{"code":"TOOMANYREQUESTS","message":"You have reached your pull rate limit. \
+            You may increase the limit by authenticating and upgrading: https://www.docker.com/increase-rate-limit"}]}"#;
+        let mut headers = HeaderMap::new();
+        headers.insert(RATE_LIMIT_RESET_HEADER, HeaderValue::from_static("1630504800"));
+        let mut retry_after_headers = HeaderMap::new();
+        retry_after_headers.insert(RETRY_AFTER, HeaderValue::from_static("3600"));
+
+        // execute & verify:
+        assert_eq!(
+            RateLimitExceeded::from_response(StatusCode::TOO_MANY_REQUESTS, &headers, body, now),
+            Some(RateLimitExceeded {
+                reset_at: Some(Utc.ymd(2021, 9, 1).and_hms(14, 0, 0))
+            })
+        );
+        assert_eq!(
+            RateLimitExceeded::from_response(StatusCode::TOO_MANY_REQUESTS, &retry_after_headers, body, now),
+            Some(RateLimitExceeded {
+                reset_at: Some(Utc.ymd(2021, 9, 1).and_hms(13, 0, 0))
+            })
+        );
+        assert_eq!(
+            RateLimitExceeded::from_response(StatusCode::TOO_MANY_REQUESTS, &HeaderMap::new(), "", now),
+            Some(RateLimitExceeded { reset_at: None })
+        );
+        assert_eq!(
+            RateLimitExceeded::from_response(StatusCode::NOT_FOUND, &headers, "{}", now),
+            None
+        );
+        assert_eq!(
+            RateLimitExceeded::from_output(
+                "failed to push image my-app:abcdef to DockerHub: \"toomanyrequests: too many requests\""
+            ),
+            Some(RateLimitExceeded { reset_at: None })
+        );
+        assert_eq!(
+            RateLimitExceeded::from_output("denied: requested access to the resource is denied"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_rate_limit_error_is_a_user_error() {
+        // setup:
+        let context = Context::new(
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            false,
+            None,
+            vec![],
+            None,
+        );
+        let docker_hub = DockerHub::new(context, "id", "docker-hub", "login", "password");
+        let rate_limit = RateLimitExceeded {
+            reset_at: Some(Utc.ymd(2021, 9, 1).and_hms(14, 0, 0)),
+        };
+
+        // execute:
+        let err = docker_hub.rate_limit_error("pushing an image", &rate_limit);
+
+        // verify:
+        assert!(matches!(err.cause, EngineErrorCause::User(_)));
+        assert_eq!(
+            err.message,
+            Some(
+                "DockerHub rate limit reached while pushing an image for docker-hub (id), it is reset at 2021-09-01T14:00:00+00:00"
+                    .to_string()
+            )
+        );
+    }
+}
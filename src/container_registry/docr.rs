@@ -4,9 +4,15 @@ use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 
 use crate::build_platform::Image;
+use crate::cloud_provider::digitalocean::application::Region as DoRegion;
 use crate::container_registry::utilities::docker_tag_and_push_image;
-use crate::container_registry::{ContainerRegistry, EngineError, Kind, PushResult};
-use crate::error::{cast_simple_error_to_engine_error, EngineErrorCause, SimpleError, SimpleErrorKind};
+use crate::container_registry::{
+    ContainerRegistry, EngineError, ImageExistsCache, Kind, PushResult, RegistryCredentials,
+};
+use crate::error::{
+    cast_simple_error_to_engine_error, check_credentials_are_set, EngineErrorCause, SimpleError, SimpleErrorKind,
+    StringError,
+};
 use crate::models::{
     Context, Listen, Listener, Listeners, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope,
 };
@@ -14,9 +20,12 @@ use crate::{cmd, utilities};
 use retry::delay::Fixed;
 use retry::Error::Operation;
 use retry::OperationResult;
+use std::str::FromStr;
 
 const CR_API_PATH: &str = "https://api.digitalocean.com/v2/registry";
 const CR_CLUSTER_API_PATH: &str = "https://api.digitalocean.com/v2/kubernetes/registry";
+/// https://docs.digitalocean.com/products/container-registry/details/availability/
+const CR_REGIONS: [&str; 5] = ["nyc3", "sfo3", "ams3", "sgp1", "fra1"];
 
 // TODO : use --output json
 // see https://www.digitalocean.com/community/tutorials/how-to-use-doctl-the-official-digitalocean-command-line-client
@@ -24,19 +33,76 @@ const CR_CLUSTER_API_PATH: &str = "https://api.digitalocean.com/v2/kubernetes/re
 pub struct DOCR {
     pub context: Context,
     pub name: String,
-    pub api_key: String,
+    pub api_key: RegistryCredentials,
     pub id: String,
+    /// Region the registry is created in when it doesn't exist yet, DigitalOcean picks one when not set.
+    pub region: Option<String>,
     pub listeners: Listeners,
+    pub image_exists_cache: ImageExistsCache,
 }
 
 impl DOCR {
     pub fn new(context: Context, id: &str, name: &str, api_key: &str) -> Self {
+        DOCR::new_with_credentials(context, id, name, RegistryCredentials::constant(api_key))
+    }
+
+    /// The API token is asked to `api_key` before every operation, so it can be rotated.
+    pub fn new_with_credentials(context: Context, id: &str, name: &str, api_key: RegistryCredentials) -> Self {
         DOCR {
             context,
             name: name.into(),
-            api_key: api_key.into(),
+            api_key,
             id: id.into(),
+            region: None,
             listeners: vec![],
+            image_exists_cache: ImageExistsCache::default(),
+        }
+    }
+
+    pub fn with_region(mut self, region: &str) -> Self {
+        self.region = Some(region.to_string());
+        self
+    }
+
+    fn validate_region(&self) -> Result<(), EngineError> {
+        match &self.region {
+            Some(region) => validate_registry_region(region.as_str()).map_err(|err| {
+                self.engine_error(
+                    EngineErrorCause::User("Check the region of your DigitalOcean container registry"),
+                    err,
+                )
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn registry_exists(&self) -> Result<bool, EngineError> {
+        let res = reqwest::blocking::Client::new()
+            .get(CR_API_PATH)
+            .headers(utilities::get_header_with_bearer(&self.api_key.get()))
+            .send()
+            .map_err(|e| {
+                self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!("No response from the Digital Ocean API : {:?}", e),
+                )
+            })?;
+
+        match res.status() {
+            StatusCode::OK => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            StatusCode::UNAUTHORIZED => Err(self.engine_error(
+                EngineErrorCause::User("Check your DigitalOcean API token"),
+                format!("DigitalOcean API token of DOCR {} is invalid", self.name_with_id()),
+            )),
+            status => Err(self.engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "Bad status code : {} returned by the DO registry API for getting DOCR {}",
+                    status,
+                    self.name_with_id()
+                ),
+            )),
         }
     }
 
@@ -47,7 +113,7 @@ impl DOCR {
             None => cast_simple_error_to_engine_error(
                 self.engine_error_scope(),
                 self.context().execution_id(),
-                get_current_registry_name(self.api_key.as_str()),
+                get_current_registry_name(self.api_key.get().as_str()),
             )?,
         };
 
@@ -61,12 +127,13 @@ impl DOCR {
             None => self.name.clone(),
         };
 
-        let headers = utilities::get_header_with_bearer(&self.api_key);
+        let headers = utilities::get_header_with_bearer(&self.api_key.get());
         // subscription_tier_slug: https://www.digitalocean.com/products/container-registry/
         // starter and basic tiers are too limited on repository creation
         let repo = DoApiCreateRepository {
             name: registry_name.clone(),
             subscription_tier_slug: "professional".to_string(),
+            region: self.region.clone(),
         };
 
         match serde_json::to_string(&repo) {
@@ -111,17 +178,24 @@ impl DOCR {
     }
 
     fn push_image(&self, registry_name: String, dest: String, image: &Image) -> Result<PushResult, EngineError> {
-        let _ =
-            match docker_tag_and_push_image(self.kind(), vec![], image.name.clone(), image.tag.clone(), dest.clone()) {
-                Ok(_) => {}
-                Err(e) => {
-                    return Err(self.engine_error(
-                        EngineErrorCause::Internal,
-                        e.message
-                            .unwrap_or("unknown error occurring during docker push".to_string()),
-                    ))
-                }
-            };
+        let push_result = docker_tag_and_push_image(
+            self.kind(),
+            vec![],
+            image.name.clone(),
+            image.tag.clone(),
+            self.context.rewrite_image(dest.as_str()),
+        );
+        self.image_exists_cache.invalidate(image);
+
+        let push_output = match push_result {
+            Ok(push_output) => push_output,
+            Err(e) => {
+                return Err(self.push_failure_error(
+                    e.message
+                        .unwrap_or_else(|| "unknown error occurring during docker push".to_string()),
+                ))
+            }
+        };
 
         let mut image = image.clone();
         image.registry_name = Some(registry_name.clone());
@@ -130,7 +204,7 @@ impl DOCR {
         image.registry_url = Some(dest);
 
         let result = retry::retry(Fixed::from_millis(10000).take(12), || {
-            match self.does_image_exists(&image) {
+            match self.does_image_exists_uncached(&image) {
                 true => OperationResult::Ok(&image),
                 false => {
                     warn!("image is not yet available on Digital Ocean Registry, retrying in a few seconds...");
@@ -146,14 +220,14 @@ impl DOCR {
             ),
         ));
         match result {
-            Ok(_) => Ok(PushResult { image }),
+            Ok(_) => Ok(PushResult::pushed(image, push_output)),
             Err(Operation { .. }) => image_not_reachable,
             Err(retry::Error::Internal(_)) => image_not_reachable,
         }
     }
 
     pub fn delete_repository(&self) -> Result<(), EngineError> {
-        let headers = utilities::get_header_with_bearer(&self.api_key);
+        let headers = utilities::get_header_with_bearer(&self.api_key.get());
         let res = reqwest::blocking::Client::new()
             .delete(CR_API_PATH)
             .headers(headers)
@@ -201,10 +275,32 @@ impl ContainerRegistry for DOCR {
     }
 
     fn is_valid(&self) -> Result<(), EngineError> {
-        Ok(())
+        self.validate_region()
+    }
+
+    fn validate_credentials(&self) -> Result<(), EngineError> {
+        check_credentials_are_set(&[("API token", self.api_key.get().as_str())]).map_err(|err| {
+            self.engine_error(
+                EngineErrorCause::User("Check your DigitalOcean API token"),
+                format!("{} for DOCR {}", err, self.name_with_id()),
+            )
+        })?;
+
+        // the registry doesn't have to exist yet, only an unauthorized answer is a failure
+        self.registry_exists().map(|_| ())
     }
 
     fn on_create(&self) -> Result<(), EngineError> {
+        info!("DOCR.on_create() called for {}", self.name_with_id());
+        self.validate_region()?;
+
+        if self.registry_exists()? {
+            return Ok(());
+        }
+
+        self.create_repository(&Image::default())?;
+        info!("DOCR {} has been created", self.name_with_id());
+
         Ok(())
     }
 
@@ -220,7 +316,11 @@ impl ContainerRegistry for DOCR {
         Ok(())
     }
 
-    fn does_image_exists(&self, image: &Image) -> bool {
+    fn image_exists_cache(&self) -> &ImageExistsCache {
+        &self.image_exists_cache
+    }
+
+    fn does_image_exists_uncached(&self, image: &Image) -> bool {
         let registry_name = match self.get_registry_name(image) {
             Ok(registry_name) => registry_name,
             Err(err) => {
@@ -229,7 +329,7 @@ impl ContainerRegistry for DOCR {
             }
         };
 
-        let headers = utilities::get_header_with_bearer(self.api_key.as_str());
+        let headers = utilities::get_header_with_bearer(self.api_key.get().as_str());
         let url = format!(
             "https://api.digitalocean.com/v2/registry/{}/repositories/{}/tags",
             registry_name,
@@ -244,6 +344,15 @@ impl ContainerRegistry for DOCR {
         let body = match res {
             Ok(output) => match output.status() {
                 StatusCode::OK => output.text(),
+                // the registry exists but nothing has been pushed to this repository yet
+                StatusCode::NOT_FOUND => {
+                    info!(
+                        "repository {} doesn't exist yet in DOCR {}",
+                        image.name.as_str(),
+                        registry_name
+                    );
+                    return false;
+                }
                 _ => {
                     error!(
                         "While tyring to get all tags for image: {}, maybe this image not exist !",
@@ -294,6 +403,20 @@ impl ContainerRegistry for DOCR {
     }
 
     // https://www.digitalocean.com/docs/images/container-registry/how-to/use-registry-docker-kubernetes/
+    fn image_url(&self, image: &Image) -> Option<String> {
+        match self.get_registry_name(image) {
+            Ok(registry_name) => Some(format!(
+                "registry.digitalocean.com/{}/{}",
+                registry_name.as_str(),
+                image.name_with_tag()
+            )),
+            Err(err) => {
+                warn!("{:?}", err);
+                None
+            }
+        }
+    }
+
     fn push(&self, image: &Image, force_push: bool) -> Result<PushResult, EngineError> {
         let registry_name = self.get_registry_name(image)?;
 
@@ -304,7 +427,13 @@ impl ContainerRegistry for DOCR {
 
         match cmd::utilities::exec(
             "doctl",
-            vec!["registry", "login", self.name.as_str(), "-t", self.api_key.as_str()],
+            vec![
+                "registry",
+                "login",
+                self.name.as_str(),
+                "-t",
+                self.api_key.get().as_str(),
+            ],
             &vec![],
         ) {
             Err(_) => {
@@ -350,9 +479,9 @@ impl ContainerRegistry for DOCR {
             image.registry_name = Some(registry_name.clone());
             // on DOCR registry secret is the same as registry name
             image.registry_secret = Some(registry_name);
-            image.registry_url = Some(dest);
+            image.registry_url = Some(dest.clone());
 
-            return Ok(PushResult { image });
+            return Ok(PushResult::already_pushed(image, dest));
         }
 
         let info_message = format!(
@@ -373,7 +502,8 @@ impl ContainerRegistry for DOCR {
     }
 
     fn push_error(&self, image: &Image) -> Result<PushResult, EngineError> {
-        Ok(PushResult { image: image.clone() })
+        let reference = image.registry_url.clone().unwrap_or_else(|| image.name_with_tag());
+        Ok(PushResult::already_pushed(image.clone(), reference))
     }
 }
 
@@ -429,6 +559,22 @@ pub fn subscribe_kube_cluster_to_container_registry(api_key: &str, cluster_uuid:
     };
 }
 
+fn validate_registry_region(region: &str) -> Result<(), StringError> {
+    if DoRegion::from_str(region).is_err() {
+        return Err(format!("{} is not a DigitalOcean region", region));
+    }
+
+    if !CR_REGIONS.contains(&region) {
+        return Err(format!(
+            "container registries are not available in region {}, available regions are: {}",
+            region,
+            CR_REGIONS.join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
 pub fn get_current_registry_name(api_key: &str) -> Result<String, SimpleError> {
     let headers = utilities::get_header_with_bearer(api_key);
     let res = reqwest::blocking::Client::new()
@@ -474,6 +620,8 @@ pub fn get_current_registry_name(api_key: &str) -> Result<String, SimpleError> {
 struct DoApiCreateRepository {
     name: String,
     subscription_tier_slug: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    region: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
@@ -530,7 +678,9 @@ pub struct Tier {
 #[derive(Default, Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DescribeTagsForImage {
+    #[serde(default)]
     pub tags: Vec<Tag>,
+    #[serde(default)]
     pub meta: Meta,
 }
 
@@ -556,3 +706,41 @@ pub struct Tag {
 pub struct Meta {
     pub total: i64,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::container_registry::docr::{validate_registry_region, DescribeTagsForImage, DoApiCreateRepository};
+
+    #[test]
+    fn test_registry_region_validation() {
+        assert!(validate_registry_region("fra1").is_ok());
+        assert_eq!(
+            validate_registry_region("eu-west-3").unwrap_err(),
+            "eu-west-3 is not a DigitalOcean region"
+        );
+        assert!(validate_registry_region("lon1")
+            .unwrap_err()
+            .starts_with("container registries are not available in region lon1"));
+    }
+
+    #[test]
+    fn test_registry_api_payloads() {
+        // setup:
+        let repository = |region: Option<&str>| DoApiCreateRepository {
+            name: "qovery".to_string(),
+            subscription_tier_slug: "professional".to_string(),
+            region: region.map(|r| r.to_string()),
+        };
+
+        // execute:
+        let empty_repository_tags = serde_json::from_str::<DescribeTagsForImage>(r#"{"meta": {"total": 0}}"#);
+
+        // verify:
+        assert!(empty_repository_tags.unwrap().tags.is_empty());
+        assert_eq!(
+            serde_json::to_string(&repository(Some("fra1"))).unwrap(),
+            r#"{"name":"qovery","subscription_tier_slug":"professional","region":"fra1"}"#
+        );
+        assert!(!serde_json::to_string(&repository(None)).unwrap().contains("region"));
+    }
+}
@@ -1,35 +1,69 @@
 use std::str::FromStr;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
 
 use rusoto_core::{Client, HttpClient, Region, RusotoError};
 use rusoto_credential::StaticProvider;
 use rusoto_ecr::{
-    CreateRepositoryRequest, DescribeImagesRequest, DescribeRepositoriesError, DescribeRepositoriesRequest, Ecr,
-    EcrClient, GetAuthorizationTokenRequest, ImageDetail, ImageIdentifier, PutLifecyclePolicyRequest, Repository,
+    BatchGetImageRequest, CreateRepositoryRequest, DescribeImagesRequest, DescribeRepositoriesError,
+    DescribeRepositoriesRequest, Ecr, EcrClient, GetAuthorizationTokenRequest, GetRepositoryPolicyError,
+    GetRepositoryPolicyRequest, ImageDetail, ImageIdentifier, PutImageError, PutImageRequest,
+    PutLifecyclePolicyRequest, Repository, SetRepositoryPolicyRequest,
 };
 use rusoto_sts::{GetCallerIdentityRequest, Sts, StsClient};
 
 use crate::build_platform::Image;
-use crate::cmd;
+use crate::cmd::docker::docker_login;
 use crate::container_registry::utilities::docker_tag_and_push_image;
-use crate::container_registry::{ContainerRegistry, Kind, PushResult};
-use crate::error::{EngineError, EngineErrorCause};
+use crate::container_registry::utilities::retagged_reference;
+use crate::container_registry::{ContainerRegistry, ImageExistsCache, Kind, PushResult, RegistryCredentials};
+use crate::error::{
+    cast_simple_error_to_engine_error, check_credentials_are_set, EngineError, EngineErrorCause, SimpleError,
+    SimpleErrorKind,
+};
 use crate::models::{
     Context, Listen, Listener, Listeners, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope,
 };
-use crate::runtime::block_on;
+use crate::runtime::{block_on, block_on_with_throttling_retry};
 use retry::delay::Fixed;
 use retry::Error::Operation;
 use retry::OperationResult;
 use serde_json::json;
 
+const BAD_CREDENTIALS: &str = "Your ECR account seems to be no longer valid (bad Credentials). \
+    Please contact your Organization administrator to fix or change the Credentials.";
+
+/// ECR tokens are valid 12 hours, they are renewed a bit before so a push doesn't start with an expiring token.
+const AUTH_TOKEN_REFRESH_MARGIN_MINUTES: i64 = 30;
+const AUTH_TOKEN_DEFAULT_VALIDITY_HOURS: i64 = 12;
+
 pub struct ECR {
     context: Context,
     id: String,
     name: String,
-    access_key_id: String,
-    secret_access_key: String,
+    access_key_id: RegistryCredentials,
+    secret_access_key: RegistryCredentials,
     region: Region,
+    allowed_pull_account_ids: Vec<String>,
     listeners: Listeners,
+    image_exists_cache: ImageExistsCache,
+    auth_token: Mutex<Option<EcrAuthToken>>,
+}
+
+/// Docker credentials of ECR.
+#[derive(Clone, Debug, PartialEq)]
+struct EcrAuthToken {
+    username: String,
+    password: String,
+    endpoint_url: String,
+    expires_at: DateTime<Utc>,
+}
+
+impl EcrAuthToken {
+    fn needs_refresh(&self, now: DateTime<Utc>) -> bool {
+        now + Duration::minutes(AUTH_TOKEN_REFRESH_MARGIN_MINUTES) >= self.expires_at
+    }
 }
 
 impl ECR {
@@ -40,25 +74,48 @@ impl ECR {
         access_key_id: &str,
         secret_access_key: &str,
         region: &str,
+    ) -> Self {
+        ECR::new_with_credentials(
+            context,
+            id,
+            name,
+            RegistryCredentials::constant(access_key_id),
+            RegistryCredentials::constant(secret_access_key),
+            region,
+        )
+    }
+
+    /// The access keys are asked to the providers before every operation, so they can be rotated.
+    pub fn new_with_credentials(
+        context: Context,
+        id: &str,
+        name: &str,
+        access_key_id: RegistryCredentials,
+        secret_access_key: RegistryCredentials,
+        region: &str,
     ) -> Self {
         ECR {
             context,
             id: id.to_string(),
             name: name.to_string(),
-            access_key_id: access_key_id.to_string(),
-            secret_access_key: secret_access_key.to_string(),
+            access_key_id,
+            secret_access_key,
             region: Region::from_str(region).unwrap(),
+            allowed_pull_account_ids: vec![],
             listeners: vec![],
+            image_exists_cache: ImageExistsCache::default(),
+            auth_token: Mutex::new(None),
         }
     }
 
+    /// AWS accounts allowed to pull images, e.g. when clusters are deployed in another account than the registry one.
+    pub fn with_allowed_pull_account_ids(mut self, allowed_pull_account_ids: Vec<String>) -> Self {
+        self.allowed_pull_account_ids = allowed_pull_account_ids;
+        self
+    }
+
     pub fn credentials(&self) -> StaticProvider {
-        StaticProvider::new(
-            self.access_key_id.to_string(),
-            self.secret_access_key.to_string(),
-            None,
-            None,
-        )
+        StaticProvider::new(self.access_key_id.get(), self.secret_access_key.get(), None, None)
     }
 
     pub fn client(&self) -> Client {
@@ -73,7 +130,8 @@ impl ECR {
         let mut drr = DescribeRepositoriesRequest::default();
         drr.repository_names = Some(vec![image.name.to_string()]);
 
-        let r = block_on(self.ecr_client().describe_repositories(drr));
+        let ecr_client = self.ecr_client();
+        let r = block_on_with_throttling_retry(|| ecr_client.describe_repositories(drr.clone()));
 
         match r {
             Err(_) => None,
@@ -85,6 +143,62 @@ impl ECR {
         }
     }
 
+    fn get_repository_policy(&self, repository_name: &str) -> Result<Option<String>, SimpleError> {
+        let grpr = GetRepositoryPolicyRequest {
+            repository_name: repository_name.to_string(),
+            ..Default::default()
+        };
+
+        let ecr_client = self.ecr_client();
+        match block_on_with_throttling_retry(|| ecr_client.get_repository_policy(grpr.clone())) {
+            Ok(res) => Ok(res.policy_text),
+            Err(RusotoError::Service(GetRepositoryPolicyError::RepositoryPolicyNotFound(_))) => Ok(None),
+            Err(err) => Err(SimpleError::new(
+                SimpleErrorKind::Other,
+                Some(format!(
+                    "can't get policy of ECR repository {} for {}: {}",
+                    repository_name,
+                    self.name_with_id(),
+                    err
+                )),
+            )),
+        }
+    }
+
+    /// Allows the configured accounts to pull from the repository, statements already in its policy are kept.
+    fn set_cross_account_pull_policy(&self, repository_name: &str) -> Result<(), SimpleError> {
+        if self.allowed_pull_account_ids.is_empty() {
+            return Ok(());
+        }
+
+        let existing_policy_text = self.get_repository_policy(repository_name)?;
+        let policy_text =
+            match cross_account_pull_policy(&self.allowed_pull_account_ids, existing_policy_text.as_deref()) {
+                Some(policy_text) if Some(&policy_text) != existing_policy_text.as_ref() => policy_text,
+                _ => return Ok(()),
+            };
+
+        let srpr = SetRepositoryPolicyRequest {
+            repository_name: repository_name.to_string(),
+            policy_text,
+            ..Default::default()
+        };
+
+        let ecr_client = self.ecr_client();
+        match block_on_with_throttling_retry(|| ecr_client.set_repository_policy(srpr.clone())) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(SimpleError::new(
+                SimpleErrorKind::Other,
+                Some(format!(
+                    "can't set cross account pull policy to ECR repository {} for {}: {}",
+                    repository_name,
+                    self.name_with_id(),
+                    err
+                )),
+            )),
+        }
+    }
+
     fn get_image(&self, image: &Image) -> Option<ImageDetail> {
         let mut dir = DescribeImagesRequest::default();
         dir.repository_name = image.name.to_string();
@@ -93,7 +207,8 @@ impl ECR {
         image_identifier.image_tag = Some(image.tag.to_string());
         dir.image_ids = Some(vec![image_identifier]);
 
-        let r = block_on(self.ecr_client().describe_images(dir));
+        let ecr_client = self.ecr_client();
+        let r = block_on_with_throttling_retry(|| ecr_client.describe_images(dir.clone()));
 
         match r {
             Err(_) => None,
@@ -112,24 +227,108 @@ impl ECR {
         }
     }
 
+    fn get_auth_token(&self) -> Result<EcrAuthToken, EngineError> {
+        let ecr_client = self.ecr_client();
+        let r = block_on_with_throttling_retry(|| {
+            ecr_client.get_authorization_token(GetAuthorizationTokenRequest::default())
+        });
+
+        match r {
+            Ok(t) => match t.authorization_data {
+                Some(authorization_data) => {
+                    let ad = authorization_data.first().unwrap();
+                    let b64_token = ad.authorization_token.as_ref().unwrap();
+
+                    let decoded_token = base64::decode(b64_token).unwrap();
+                    let token = std::str::from_utf8(decoded_token.as_slice()).unwrap();
+
+                    let s_token: Vec<&str> = token.split(":").collect::<Vec<_>>();
+
+                    let expires_at = match ad.expires_at {
+                        Some(expires_at) => Utc.timestamp(expires_at as i64, 0),
+                        None => Utc::now() + Duration::hours(AUTH_TOKEN_DEFAULT_VALIDITY_HOURS),
+                    };
+
+                    Ok(EcrAuthToken {
+                        username: s_token.first().unwrap().to_string(),
+                        password: s_token.get(1).unwrap().to_string(),
+                        endpoint_url: ad.clone().proxy_endpoint.unwrap(),
+                        expires_at,
+                    })
+                }
+                None => Err(self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!(
+                        "failed to retrieve credentials and endpoint URL from ECR {}",
+                        self.name_with_id(),
+                    ),
+                )),
+            },
+            _ => Err(self.engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "failed to retrieve credentials and endpoint URL from ECR {}",
+                    self.name_with_id(),
+                ),
+            )),
+        }
+    }
+
+    fn docker_login(&self, token: &EcrAuthToken) -> Result<(), EngineError> {
+        if let Err(e) = docker_login(
+            &self.docker_envs(),
+            Some(token.endpoint_url.as_str()),
+            token.username.as_str(),
+            token.password.as_str(),
+        ) {
+            error!("{:?}", e.message);
+            return Err(self.engine_error(
+                EngineErrorCause::User(
+                    "Your ECR account seems to be no longer valid (bad Credentials). \
+                Please contact your Organization administrator to fix or change the Credentials.",
+                ),
+                format!("failed to login to ECR {}", self.name_with_id()),
+            ));
+        };
+
+        Ok(())
+    }
+
+    /// Logs docker in ECR, again only when the previous token is about to expire (e.g. during long deployments).
+    fn login(&self) -> Result<(), EngineError> {
+        cached_auth_token(&self.auth_token, Utc::now(), || {
+            let token = self.get_auth_token()?;
+            self.docker_login(&token)?;
+            info!(
+                "logged in ECR {}, token expires at {}",
+                self.name_with_id(),
+                token.expires_at
+            );
+            Ok(token)
+        })
+        .map(|_| ())
+    }
+
     fn push_image(&self, dest: String, image: &Image) -> Result<PushResult, EngineError> {
         // READ https://docs.aws.amazon.com/AmazonECR/latest/userguide/docker-push-ecr-image.html
         // docker tag e9ae3c220b23 aws_account_id.dkr.ecr.region.amazonaws.com/my-web-app
 
-        match docker_tag_and_push_image(
+        let push_result = docker_tag_and_push_image(
             self.kind(),
             self.docker_envs(),
             image.name.clone(),
             image.tag.clone(),
-            dest.clone(),
-        ) {
-            Ok(_) => {
+            self.context.rewrite_image(dest.as_str()),
+        );
+        self.image_exists_cache.invalidate(image);
+
+        match push_result {
+            Ok(push_output) => {
                 let mut image = image.clone();
                 image.registry_url = Some(dest);
-                Ok(PushResult { image })
+                Ok(PushResult::pushed(image, push_output))
             }
-            Err(e) => Err(self.engine_error(
-                EngineErrorCause::Internal,
+            Err(e) => Err(self.push_failure_error(
                 e.message
                     .unwrap_or_else(|| "unknown error occurring during docker push".to_string()),
             )),
@@ -152,11 +351,12 @@ impl ECR {
 
         // ensure repository is created
         // need to do all this checks and retry because of several issues encountered like: 200 API response code while repo is not created
-        let repo_created = retry::retry(Fixed::from_millis(5000).take(24), || {
-            match block_on(
-                self.ecr_client()
-                    .describe_repositories(container_registry_request.clone()),
-            ) {
+        let ecr_client = self.ecr_client();
+        let repo_created = retry::retry(
+            Fixed::from_millis(5000).take(24),
+            || match block_on_with_throttling_retry(|| {
+                ecr_client.describe_repositories(container_registry_request.clone())
+            }) {
                 Ok(x) => {
                     debug!("created {:?} repository", x);
                     OperationResult::Ok(())
@@ -178,7 +378,7 @@ impl ECR {
                         _ => warn!("{:?}", e),
                     }
 
-                    let msg = match block_on(self.ecr_client().create_repository(crr.clone())) {
+                    let msg = match block_on_with_throttling_retry(|| ecr_client.create_repository(crr.clone())) {
                         Ok(_) => format!("repository {} created", &repository_name),
                         Err(err) => format!(
                             "can't create ECR repository {} for {}. {:?}",
@@ -190,8 +390,8 @@ impl ECR {
 
                     OperationResult::Retry(Err(self.engine_error(EngineErrorCause::Internal, msg)))
                 }
-            }
-        });
+            },
+        );
 
         match repo_created {
             Ok(_) => info!(
@@ -231,7 +431,8 @@ impl ECR {
             ..Default::default()
         };
 
-        match block_on(self.ecr_client().put_lifecycle_policy(plp)) {
+        let ecr_client = self.ecr_client();
+        match block_on_with_throttling_retry(|| ecr_client.put_lifecycle_policy(plp.clone())) {
             Err(err) => {
                 error!(
                     "can't set lifecycle policy to ECR repository {} for {}: {}",
@@ -249,7 +450,15 @@ impl ECR {
                     ),
                 ))
             }
-            _ => Ok(self.get_repository(&image).unwrap()),
+            _ => {
+                cast_simple_error_to_engine_error(
+                    self.engine_error_scope(),
+                    self.context.execution_id(),
+                    self.set_cross_account_pull_policy(image.name.as_str()),
+                )?;
+
+                Ok(self.get_repository(&image).unwrap())
+            }
         }
     }
 
@@ -258,6 +467,12 @@ impl ECR {
         let repository = self.get_repository(&image);
         if repository.is_some() {
             info!("ECR repository {} already exists", image.name.as_str());
+            cast_simple_error_to_engine_error(
+                self.engine_error_scope(),
+                self.context.execution_id(),
+                self.set_cross_account_pull_policy(image.name.as_str()),
+            )?;
+
             return Ok(repository.unwrap());
         }
 
@@ -283,16 +498,28 @@ impl ContainerRegistry for ECR {
     }
 
     fn is_valid(&self) -> Result<(), EngineError> {
+        self.validate_credentials()
+    }
+
+    fn validate_credentials(&self) -> Result<(), EngineError> {
+        check_credentials_are_set(&[
+            ("access key id", self.access_key_id.get().as_str()),
+            ("secret access key", self.secret_access_key.get().as_str()),
+        ])
+        .map_err(|err| {
+            self.engine_error(
+                EngineErrorCause::User(BAD_CREDENTIALS),
+                format!("{} for ECR {}", err, self.name_with_id()),
+            )
+        })?;
+
         let client = StsClient::new_with_client(self.client(), Region::default());
         let s = block_on(client.get_caller_identity(GetCallerIdentityRequest::default()));
 
         match s {
             Ok(_) => Ok(()),
             Err(_) => Err(self.engine_error(
-                EngineErrorCause::User(
-                    "Your ECR account seems to be no longer valid (bad Credentials). \
-                    Please contact your Organization administrator to fix or change the Credentials.",
-                ),
+                EngineErrorCause::User(BAD_CREDENTIALS),
                 format!("bad ECR credentials for {}", self.name_with_id()),
             )),
         }
@@ -315,53 +542,22 @@ impl ContainerRegistry for ECR {
         unimplemented!()
     }
 
-    fn does_image_exists(&self, image: &Image) -> bool {
-        self.get_image(image).is_some()
+    fn image_exists_cache(&self) -> &ImageExistsCache {
+        &self.image_exists_cache
     }
 
-    fn push(&self, image: &Image, force_push: bool) -> Result<PushResult, EngineError> {
-        let r = block_on(
-            self.ecr_client()
-                .get_authorization_token(GetAuthorizationTokenRequest::default()),
-        );
-
-        let (access_token, password, endpoint_url) = match r {
-            Ok(t) => match t.authorization_data {
-                Some(authorization_data) => {
-                    let ad = authorization_data.first().unwrap();
-                    let b64_token = ad.authorization_token.as_ref().unwrap();
-
-                    let decoded_token = base64::decode(b64_token).unwrap();
-                    let token = std::str::from_utf8(decoded_token.as_slice()).unwrap();
+    fn does_image_exists_uncached(&self, image: &Image) -> bool {
+        self.get_image(image).is_some()
+    }
 
-                    let s_token: Vec<&str> = token.split(":").collect::<Vec<_>>();
+    fn image_url(&self, image: &Image) -> Option<String> {
+        self.get_repository(image)
+            .and_then(|repository| repository.repository_uri)
+            .map(|repository_uri| format!("{}:{}", repository_uri, image.tag.as_str()))
+    }
 
-                    (
-                        s_token.first().unwrap().to_string(),
-                        s_token.get(1).unwrap().to_string(),
-                        ad.clone().proxy_endpoint.unwrap(),
-                    )
-                }
-                None => {
-                    return Err(self.engine_error(
-                        EngineErrorCause::Internal,
-                        format!(
-                            "failed to retrieve credentials and endpoint URL from ECR {}",
-                            self.name_with_id(),
-                        ),
-                    ));
-                }
-            },
-            _ => {
-                return Err(self.engine_error(
-                    EngineErrorCause::Internal,
-                    format!(
-                        "failed to retrieve credentials and endpoint URL from ECR {}",
-                        self.name_with_id(),
-                    ),
-                ));
-            }
-        };
+    fn push(&self, image: &Image, force_push: bool) -> Result<PushResult, EngineError> {
+        self.login()?;
 
         let repository = match if force_push {
             self.create_repository(image)
@@ -381,27 +577,6 @@ impl ContainerRegistry for ECR {
             }
         };
 
-        if let Err(_) = cmd::utilities::exec(
-            "docker",
-            vec![
-                "login",
-                "-u",
-                access_token.as_str(),
-                "-p",
-                password.as_str(),
-                endpoint_url.as_str(),
-            ],
-            &self.docker_envs(),
-        ) {
-            return Err(self.engine_error(
-                EngineErrorCause::User(
-                    "Your ECR account seems to be no longer valid (bad Credentials). \
-                Please contact your Organization administrator to fix or change the Credentials.",
-                ),
-                format!("failed to login to ECR {}", self.name_with_id()),
-            ));
-        };
-
         let dest = format!("{}:{}", repository.repository_uri.unwrap(), image.tag.as_str());
 
         let listeners_helper = ListenersHelper::new(&self.listeners);
@@ -426,9 +601,9 @@ impl ContainerRegistry for ECR {
             ));
 
             let mut image = image.clone();
-            image.registry_url = Some(dest);
+            image.registry_url = Some(dest.clone());
 
-            return Ok(PushResult { image });
+            return Ok(PushResult::already_pushed(image, dest));
         }
 
         let info_message = format!(
@@ -451,9 +626,70 @@ impl ContainerRegistry for ECR {
         self.push_image(dest, image)
     }
 
+    /// ECR is asked to put the pushed manifest under the new tag, docker is not involved.
+    fn add_tag(&self, push_result: &PushResult, tag: &str) -> Result<(), EngineError> {
+        let repository_name = push_result.image.name.clone();
+        let bgir = BatchGetImageRequest {
+            repository_name: repository_name.clone(),
+            image_ids: vec![pushed_image_identifier(push_result)],
+            ..Default::default()
+        };
+
+        let ecr_client = self.ecr_client();
+        let source_image = block_on_with_throttling_retry(|| ecr_client.batch_get_image(bgir.clone()))
+            .ok()
+            .and_then(|res| res.images)
+            .and_then(|images| images.into_iter().next());
+
+        let image_manifest = match source_image.as_ref().and_then(|image| image.image_manifest.clone()) {
+            Some(image_manifest) => image_manifest,
+            None => {
+                return Err(self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!(
+                        "failed to get the manifest of image {} from ECR {}",
+                        push_result.reference,
+                        self.name_with_id()
+                    ),
+                ))
+            }
+        };
+
+        let pir = PutImageRequest {
+            repository_name,
+            image_manifest,
+            image_manifest_media_type: source_image.and_then(|image| image.image_manifest_media_type),
+            image_tag: Some(tag.to_string()),
+            ..Default::default()
+        };
+
+        match block_on_with_throttling_retry(|| ecr_client.put_image(pir.clone())) {
+            // the tag already points to this manifest
+            Ok(_) | Err(RusotoError::Service(PutImageError::ImageAlreadyExists(_))) => {
+                info!(
+                    "image {} has been tagged as {}",
+                    push_result.reference,
+                    retagged_reference(push_result.reference.as_str(), tag)
+                );
+                Ok(())
+            }
+            Err(e) => Err(self.engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "failed to tag image {} as {} on ECR {}: {}",
+                    push_result.reference,
+                    tag,
+                    self.name_with_id(),
+                    e
+                ),
+            )),
+        }
+    }
+
     fn push_error(&self, image: &Image) -> Result<PushResult, EngineError> {
         // TODO change this
-        Ok(PushResult { image: image.clone() })
+        let reference = image.registry_url.clone().unwrap_or_else(|| image.name_with_tag());
+        Ok(PushResult::already_pushed(image.clone(), reference))
     }
 }
 
@@ -466,3 +702,224 @@ impl Listen for ECR {
         self.listeners.push(listener);
     }
 }
+
+/// Pushed image, by digest when docker returned it.
+fn pushed_image_identifier(push_result: &PushResult) -> ImageIdentifier {
+    match push_result.reference.split_once('@') {
+        Some((_, digest)) => ImageIdentifier {
+            image_digest: Some(digest.to_string()),
+            image_tag: None,
+        },
+        None => ImageIdentifier {
+            image_digest: None,
+            image_tag: Some(push_result.image.tag.clone()),
+        },
+    }
+}
+
+/// Cached token, or a new one from `refresh` if there is none or if it is about to expire.
+fn cached_auth_token<F, E>(
+    cache: &Mutex<Option<EcrAuthToken>>,
+    now: DateTime<Utc>,
+    refresh: F,
+) -> Result<EcrAuthToken, E>
+where
+    F: FnOnce() -> Result<EcrAuthToken, E>,
+{
+    let mut cached_token = cache.lock().unwrap();
+
+    match cached_token.as_ref() {
+        Some(token) if !token.needs_refresh(now) => Ok(token.clone()),
+        _ => {
+            let token = refresh()?;
+            *cached_token = Some(token.clone());
+            Ok(token)
+        }
+    }
+}
+
+const CROSS_ACCOUNT_PULL_STATEMENT_ID: &str = "AllowCrossAccountPull";
+
+/// Repository policy allowing other AWS accounts to pull images, `None` if there is no account to allow.
+/// Statements of the existing policy are kept, only the cross account pull one is replaced.
+fn cross_account_pull_policy(account_ids: &[String], existing_policy_text: Option<&str>) -> Option<String> {
+    if account_ids.is_empty() {
+        return None;
+    }
+
+    let principals = account_ids
+        .iter()
+        .map(|account_id| format!("arn:aws:iam::{}:root", account_id))
+        .collect::<Vec<_>>();
+
+    let mut policy = existing_policy_text
+        .and_then(|policy_text| serde_json::from_str::<serde_json::Value>(policy_text).ok())
+        .filter(|policy| policy["Statement"].is_array())
+        .unwrap_or_else(|| json!({ "Version": "2008-10-17", "Statement": [] }));
+
+    let statements = policy["Statement"].as_array_mut().unwrap();
+    statements.retain(|statement| statement["Sid"] != CROSS_ACCOUNT_PULL_STATEMENT_ID);
+    statements.push(json!({
+      "Sid": CROSS_ACCOUNT_PULL_STATEMENT_ID,
+      "Effect": "Allow",
+      "Principal": {
+        "AWS": principals
+      },
+      "Action": [
+        "ecr:GetDownloadUrlForLayer",
+        "ecr:BatchGetImage"
+      ]
+    }));
+
+    Some(policy.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::build_platform::Image;
+    use crate::container_registry::ecr::{
+        cached_auth_token, cross_account_pull_policy, pushed_image_identifier, EcrAuthToken, ECR,
+    };
+    use crate::container_registry::{PushResult, RegistryCredentials};
+    use crate::models::Context;
+    use crate::runtime::block_on;
+    use chrono::{Duration, TimeZone, Utc};
+    use rusoto_credential::ProvideAwsCredentials;
+    use serde_json::{json, Value};
+    use std::cell::Cell;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_credentials_are_asked_before_every_operation() {
+        // setup:
+        let context = Context::new(
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            false,
+            None,
+            vec![],
+            None,
+        );
+        let secret_access_key = Arc::new(Mutex::new("secret-access-key".to_string()));
+        let provided_secret_access_key = secret_access_key.clone();
+        let ecr = ECR::new_with_credentials(
+            context,
+            "id",
+            "ecr",
+            RegistryCredentials::constant("access-key-id"),
+            RegistryCredentials::new(move || provided_secret_access_key.lock().unwrap().clone()),
+            "eu-west-3",
+        );
+
+        // execute:
+        let first_credentials = block_on(ecr.credentials().credentials()).unwrap();
+        *secret_access_key.lock().unwrap() = "rotated-secret-access-key".to_string();
+        let second_credentials = block_on(ecr.credentials().credentials()).unwrap();
+
+        // verify:
+        assert_eq!(first_credentials.aws_access_key_id(), "access-key-id");
+        assert_eq!(first_credentials.aws_secret_access_key(), "secret-access-key");
+        assert_eq!(second_credentials.aws_secret_access_key(), "rotated-secret-access-key");
+    }
+
+    #[test]
+    fn test_auth_token_refresh() {
+        // setup:
+        let fetched_at = Utc.ymd(2021, 6, 1).and_hms(8, 0, 0);
+        let token = |expires_at| EcrAuthToken {
+            username: "AWS".to_string(),
+            password: "password".to_string(),
+            endpoint_url: "https://123456789012.dkr.ecr.eu-west-3.amazonaws.com".to_string(),
+            expires_at,
+        };
+        let cache = Mutex::new(None);
+        let refreshes = Cell::new(0);
+        let refresh = || -> Result<EcrAuthToken, String> {
+            refreshes.set(refreshes.get() + 1);
+            Ok(token(fetched_at + Duration::hours(12) * refreshes.get()))
+        };
+
+        // execute:
+        let first = cached_auth_token(&cache, fetched_at, refresh).unwrap();
+        let still_valid = cached_auth_token(&cache, fetched_at + Duration::hours(11), refresh).unwrap();
+        let expiring = cached_auth_token(&cache, fetched_at + Duration::minutes(11 * 60 + 45), refresh).unwrap();
+        let failed_refresh = cached_auth_token(&cache, fetched_at + Duration::hours(30), || {
+            Err::<EcrAuthToken, String>("expired credentials".to_string())
+        });
+
+        // verify:
+        assert_eq!(first.expires_at, fetched_at + Duration::hours(12));
+        assert_eq!(still_valid, first);
+        assert_eq!(expiring.expires_at, fetched_at + Duration::hours(24));
+        assert_eq!(refreshes.get(), 2);
+        assert_eq!(failed_refresh.unwrap_err(), "expired credentials");
+        assert_eq!(cache.lock().unwrap().as_ref(), Some(&expiring));
+    }
+
+    #[test]
+    fn test_cross_account_pull_policy() {
+        // setup:
+        let account_ids = vec!["123456789012".to_string(), "210987654321".to_string()];
+
+        let existing_policy = json!({
+          "Version": "2012-10-17",
+          "Statement": [
+            { "Sid": "AllowCiPush", "Effect": "Allow", "Principal": { "AWS": "arn:aws:iam::111111111111:root" }, "Action": ["ecr:PutImage"] },
+            { "Sid": "AllowCrossAccountPull", "Effect": "Allow", "Principal": { "AWS": ["arn:aws:iam::999999999999:root"] }, "Action": ["ecr:BatchGetImage"] }
+          ]
+        })
+        .to_string();
+
+        // execute:
+        let policy = cross_account_pull_policy(&account_ids, None);
+        let no_policy = cross_account_pull_policy(&[], Some(existing_policy.as_str()));
+        let merged_policy = cross_account_pull_policy(&account_ids, Some(existing_policy.as_str()));
+        let unchanged_policy = cross_account_pull_policy(&account_ids, merged_policy.as_deref());
+
+        // verify:
+        assert!(no_policy.is_none());
+        let policy: Value = serde_json::from_str(policy.unwrap().as_str()).unwrap();
+        let statement = &policy["Statement"][0];
+        assert_eq!(
+            statement["Principal"]["AWS"],
+            serde_json::json!(["arn:aws:iam::123456789012:root", "arn:aws:iam::210987654321:root"])
+        );
+        assert_eq!(
+            statement["Action"],
+            serde_json::json!(["ecr:GetDownloadUrlForLayer", "ecr:BatchGetImage"])
+        );
+
+        assert_eq!(unchanged_policy, merged_policy);
+        let merged_policy: Value = serde_json::from_str(merged_policy.unwrap().as_str()).unwrap();
+        assert_eq!(merged_policy["Version"], "2012-10-17");
+        let statements = merged_policy["Statement"].as_array().unwrap();
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[0]["Sid"], "AllowCiPush");
+        assert_eq!(statements[1], *statement);
+    }
+
+    #[test]
+    fn test_pushed_image_identifier() {
+        // setup:
+        let image = Image {
+            name: "my-app".to_string(),
+            tag: "abcdef".to_string(),
+            ..Default::default()
+        };
+        let repository = "123456789012.dkr.ecr.eu-west-3.amazonaws.com/my-app";
+
+        // execute:
+        let by_digest = pushed_image_identifier(&PushResult::already_pushed(
+            image.clone(),
+            format!("{}@sha256:c3bd7fa2b4", repository),
+        ));
+        let by_tag = pushed_image_identifier(&PushResult::already_pushed(image, format!("{}:abcdef", repository)));
+
+        // verify:
+        assert_eq!(by_digest.image_digest, Some("sha256:c3bd7fa2b4".to_string()));
+        assert_eq!(by_digest.image_tag, None);
+        assert_eq!(by_tag.image_digest, None);
+        assert_eq!(by_tag.image_tag, Some("abcdef".to_string()));
+    }
+}
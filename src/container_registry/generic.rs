@@ -0,0 +1,597 @@
+extern crate reqwest;
+
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::{ACCEPT, WWW_AUTHENTICATE};
+use reqwest::{Method, StatusCode, Url};
+use serde::Deserialize;
+
+use crate::build_platform::Image;
+use crate::cmd::docker::docker_login;
+use crate::container_registry::utilities::docker_tag_and_push_image;
+use crate::container_registry::{
+    ContainerRegistry, EngineError, ImageExistsCache, Kind, PushResult, RegistryCredentials, MANIFEST_MEDIA_TYPES,
+};
+use crate::error::{check_credentials_are_set, EngineErrorCause};
+use crate::models::{
+    Context, Listen, Listener, Listeners, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope,
+};
+
+/// Any OCI compliant registry (Harbor, Nexus, ...) reachable with a login and a password.
+pub struct GenericRegistry {
+    context: Context,
+    id: String,
+    name: String,
+    /// Registry URL, optionally followed by a path every repository is prefixed with (ex: a Harbor project).
+    url: String,
+    login: String,
+    password: RegistryCredentials,
+    listeners: Listeners,
+    image_exists_cache: ImageExistsCache,
+}
+
+#[derive(Debug, PartialEq)]
+struct BearerChallenge {
+    realm: String,
+    service: Option<String>,
+    scope: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BearerToken {
+    token: Option<String>,
+    access_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TagsList {
+    #[serde(default)]
+    tags: Option<Vec<String>>,
+}
+
+impl GenericRegistry {
+    pub fn new(context: Context, id: &str, name: &str, url: &str, login: &str, password: &str) -> Self {
+        GenericRegistry::new_with_credentials(context, id, name, url, login, RegistryCredentials::constant(password))
+    }
+
+    /// The password is asked to `password` before every operation, so it can be rotated.
+    pub fn new_with_credentials(
+        context: Context,
+        id: &str,
+        name: &str,
+        url: &str,
+        login: &str,
+        password: RegistryCredentials,
+    ) -> Self {
+        GenericRegistry {
+            context,
+            id: id.to_string(),
+            name: name.to_string(),
+            url: url.to_string(),
+            login: login.to_string(),
+            password,
+            listeners: vec![],
+            image_exists_cache: ImageExistsCache::default(),
+        }
+    }
+
+    /// Registry host and path prefix, as used by docker (ex: `harbor.mycompany.com/my-project`).
+    fn registry_host(&self) -> String {
+        let url = self.url.trim_end_matches('/');
+        match url.find("://") {
+            Some(index) => url[index + 3..].to_string(),
+            None => url.to_string(),
+        }
+    }
+
+    fn registry_base_url(&self) -> Result<Url, EngineError> {
+        let url = match self.url.contains("://") {
+            true => self.url.clone(),
+            false => format!("https://{}", self.url),
+        };
+
+        Url::parse(url.as_str()).map_err(|err| {
+            self.engine_error(
+                EngineErrorCause::User("The container registry URL is invalid"),
+                format!("invalid container registry URL {}: {}", self.url, err),
+            )
+        })
+    }
+
+    /// Repository of the image, including the registry path prefix.
+    fn repository(&self, image_name: &str) -> String {
+        let registry_host = self.registry_host();
+        match registry_host.split_once('/') {
+            Some((_, path_prefix)) => format!("{}/{}", path_prefix, image_name),
+            None => image_name.to_string(),
+        }
+    }
+
+    fn api_url(&self, path: &str) -> Result<Url, EngineError> {
+        let base_url = self.registry_base_url()?;
+        base_url.join(path).map_err(|err| {
+            self.engine_error(
+                EngineErrorCause::Internal,
+                format!("invalid container registry API path {}: {}", path, err),
+            )
+        })
+    }
+
+    /// Registries either accept basic auth or ask for a bearer token through a `WWW-Authenticate` challenge.
+    fn registry_request(&self, method: Method, url: Url, accept: Option<&str>) -> Result<Response, reqwest::Error> {
+        let client = Client::new();
+        let password = self.password.get();
+        let request = |client: &Client| -> RequestBuilder {
+            let request = client.request(method.clone(), url.clone());
+            match accept {
+                Some(accept) => request.header(ACCEPT, accept),
+                None => request,
+            }
+        };
+
+        let response = request(&client).basic_auth(&self.login, Some(&password)).send()?;
+
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+
+        let challenge = match response
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|header| header.to_str().ok())
+            .and_then(parse_bearer_challenge)
+        {
+            Some(challenge) => challenge,
+            None => return Ok(response),
+        };
+
+        let mut token_request = client
+            .get(challenge.realm.as_str())
+            .basic_auth(&self.login, Some(&password));
+        if let Some(service) = challenge.service.as_ref() {
+            token_request = token_request.query(&[("service", service)]);
+        }
+        if let Some(scope) = challenge.scope.as_ref() {
+            token_request = token_request.query(&[("scope", scope)]);
+        }
+
+        let token = token_request.send()?.error_for_status()?.json::<BearerToken>()?;
+        match token.token.or(token.access_token) {
+            Some(token) => request(&client).bearer_auth(token).send(),
+            None => Ok(response),
+        }
+    }
+
+    pub fn list_tags(&self, image_name: &str) -> Result<Vec<String>, EngineError> {
+        let url = self.api_url(format!("/v2/{}/tags/list", self.repository(image_name)).as_str())?;
+        let response = self
+            .registry_request(Method::GET, url, None)
+            .and_then(|response| response.error_for_status());
+
+        match response.and_then(|response| response.json::<TagsList>()) {
+            Ok(tags_list) => Ok(tags_list.tags.unwrap_or_default()),
+            Err(err) if err.status() == Some(StatusCode::NOT_FOUND) => Ok(vec![]),
+            Err(err) => Err(self.engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "failed to list tags of {} on {}: {}",
+                    image_name,
+                    self.name_with_id(),
+                    err
+                ),
+            )),
+        }
+    }
+
+    fn get_docker_json_config_raw(&self, password: &str) -> String {
+        base64::encode(
+            format!(
+                r#"{{"auths":{{"{}":{{"auth":"{}"}}}}}}"#,
+                self.registry_host().split('/').next().unwrap_or_default(),
+                base64::encode(format!("{}:{}", self.login, password).as_bytes())
+            )
+            .as_bytes(),
+        )
+    }
+
+    fn get_docker_envs(&self) -> Vec<(&str, &str)> {
+        match self.context.docker_tcp_socket() {
+            Some(tcp_socket) => vec![("DOCKER_HOST", tcp_socket.as_str())],
+            None => vec![],
+        }
+    }
+}
+
+/// Parses a `WWW-Authenticate` header, ex: `Bearer realm="https://harbor.io/service/token",service="harbor-registry"`.
+fn parse_bearer_challenge(header: &str) -> Option<BearerChallenge> {
+    let parameters = header.trim().strip_prefix("Bearer ")?;
+    let mut realm = None;
+    let mut service = None;
+    let mut scope = None;
+
+    let mut remaining = parameters.trim();
+    while !remaining.is_empty() {
+        let (key, rest) = remaining.split_once('=')?;
+        let rest = rest.trim_start();
+        let (value, rest) = match rest.strip_prefix('"') {
+            Some(quoted) => {
+                let (value, rest) = quoted.split_once('"')?;
+                (value, rest)
+            }
+            None => rest.split_at(rest.find(',').unwrap_or_else(|| rest.len())),
+        };
+
+        match key.trim() {
+            "realm" => realm = Some(value.to_string()),
+            "service" => service = Some(value.to_string()),
+            "scope" => scope = Some(value.to_string()),
+            _ => {}
+        }
+
+        remaining = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+    }
+
+    Some(BearerChallenge {
+        realm: realm?,
+        service,
+        scope,
+    })
+}
+
+impl ContainerRegistry for GenericRegistry {
+    fn context(&self) -> &Context {
+        &self.context
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Generic
+    }
+
+    fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn is_valid(&self) -> Result<(), EngineError> {
+        self.validate_credentials()
+    }
+
+    fn validate_credentials(&self) -> Result<(), EngineError> {
+        // anonymous registries don't have any login, but a login always comes with its password
+        if !self.login.is_empty() {
+            check_credentials_are_set(&[("password", self.password.get().as_str())]).map_err(|err| {
+                self.engine_error(
+                    EngineErrorCause::User(
+                        "Your container registry credentials seem to be no longer valid (bad Credentials). \
+                Please contact your Organization administrator to fix or change the Credentials.",
+                    ),
+                    format!("{} for container registry {}", err, self.name_with_id()),
+                )
+            })?;
+        }
+
+        let url = self.api_url("/v2/")?;
+
+        match self.registry_request(Method::GET, url, None) {
+            Ok(response) if response.status().is_success() => Ok(()),
+            Ok(response) if response.status() == StatusCode::UNAUTHORIZED => Err(self.engine_error(
+                EngineErrorCause::User(
+                    "Your container registry credentials seem to be no longer valid (bad Credentials). \
+                Please contact your Organization administrator to fix or change the Credentials.",
+                ),
+                format!("failed to authenticate on container registry {}", self.name_with_id()),
+            )),
+            Ok(response) => Err(self.engine_error(
+                EngineErrorCause::User("Check the container registry URL, it must expose the registry v2 API"),
+                format!(
+                    "container registry {} answered {} on /v2/",
+                    self.name_with_id(),
+                    response.status()
+                ),
+            )),
+            Err(err) => Err(self.engine_error(
+                EngineErrorCause::User("The container registry is unreachable, check its URL"),
+                format!("failed to reach container registry {}: {}", self.name_with_id(), err),
+            )),
+        }
+    }
+
+    fn on_create(&self) -> Result<(), EngineError> {
+        // repositories are created on push
+        Ok(())
+    }
+
+    fn on_create_error(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_delete(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_delete_error(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn image_exists_cache(&self) -> &ImageExistsCache {
+        &self.image_exists_cache
+    }
+
+    fn does_image_exists_uncached(&self, image: &Image) -> bool {
+        let url = match self
+            .api_url(format!("/v2/{}/manifests/{}", self.repository(image.name.as_str()), image.tag).as_str())
+        {
+            Ok(url) => url,
+            Err(_) => return false,
+        };
+
+        match self.registry_request(Method::HEAD, url, Some(MANIFEST_MEDIA_TYPES)) {
+            Ok(response) => response.status().is_success(),
+            Err(e) => {
+                error!(
+                    "While trying to retrieve if image exists on {}: {:?}",
+                    self.name_with_id(),
+                    e
+                );
+                false
+            }
+        }
+    }
+
+    fn image_url(&self, image: &Image) -> Option<String> {
+        Some(format!("{}/{}", self.registry_host(), image.name_with_tag()))
+    }
+
+    fn push(&self, image: &Image, force_push: bool) -> Result<PushResult, EngineError> {
+        let envs = self.get_docker_envs();
+        let password = self.password.get();
+        let registry_host = self.registry_host();
+        let docker_login_host = registry_host.split('/').next().unwrap_or_default();
+
+        if let Err(e) = docker_login(&envs, Some(docker_login_host), self.login.as_str(), password.as_str()) {
+            error!("{:?}", e.message);
+            return Err(self.engine_error(
+                EngineErrorCause::User(
+                    "Your container registry credentials seem to be no longer valid (bad Credentials). \
+                Please contact your Organization administrator to fix or change the Credentials.",
+                ),
+                format!("failed to login to container registry {}", self.name_with_id()),
+            ));
+        };
+
+        let dest = format!("{}/{}", registry_host, image.name_with_tag());
+        let mut pushed_image = image.clone();
+        pushed_image.registry_name = Some(self.name.clone());
+        pushed_image.registry_url = Some(registry_host.clone());
+        // the pull secret is the `.dockerconfigjson` payload, never the raw password
+        let docker_json_config = self.get_docker_json_config_raw(password.as_str());
+        pushed_image.registry_secret = Some(docker_json_config.clone());
+        pushed_image.registry_docker_json_config = Some(docker_json_config);
+
+        let listeners_helper = ListenersHelper::new(&self.listeners);
+
+        if !force_push && self.does_image_exists(image) {
+            // check if image does exist - if yes, do not upload it again
+            let info_message = format!(
+                "image {:?} found on container registry {}, container build is not required",
+                image,
+                self.name()
+            );
+
+            info!("{}", info_message.as_str());
+
+            listeners_helper.deployment_in_progress(ProgressInfo::new(
+                ProgressScope::Application {
+                    id: image.application_id.clone(),
+                },
+                ProgressLevel::Info,
+                Some(info_message),
+                self.context.execution_id(),
+            ));
+
+            return Ok(PushResult::already_pushed(pushed_image, dest));
+        }
+
+        let info_message = format!(
+            "image {:?} does not exist on container registry {}, starting image upload",
+            image,
+            self.name()
+        );
+
+        info!("{}", info_message.as_str());
+
+        listeners_helper.deployment_in_progress(ProgressInfo::new(
+            ProgressScope::Application {
+                id: image.application_id.clone(),
+            },
+            ProgressLevel::Info,
+            Some(info_message),
+            self.context.execution_id(),
+        ));
+
+        let push_result = docker_tag_and_push_image(
+            self.kind(),
+            envs,
+            image.name.clone(),
+            image.tag.clone(),
+            self.context.rewrite_image(dest.as_str()),
+        );
+        self.image_exists_cache.invalidate(image);
+
+        match push_result {
+            Ok(push_output) => Ok(PushResult::pushed(pushed_image, push_output)),
+            Err(e) => Err(self.push_failure_error(
+                e.message
+                    .unwrap_or_else(|| "unknown error occurring during docker push".to_string()),
+            )),
+        }
+    }
+
+    fn push_error(&self, image: &Image) -> Result<PushResult, EngineError> {
+        let reference = format!("{}/{}", self.registry_host(), image.name_with_tag());
+        Ok(PushResult::already_pushed(image.clone(), reference))
+    }
+}
+
+impl Listen for GenericRegistry {
+    fn listeners(&self) -> &Listeners {
+        &self.listeners
+    }
+
+    fn add_listener(&mut self, listener: Listener) {
+        self.listeners.push(listener);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::container_registry::generic::{parse_bearer_challenge, BearerChallenge, GenericRegistry};
+    use crate::container_registry::ContainerRegistry;
+    use crate::error::EngineErrorCause;
+    use crate::models::Context;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_validate_credentials_fails_on_unauthorized_response() {
+        // setup: a registry rejecting the credentials without any bearer challenge
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let registry_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request_lines = vec![];
+            for line in BufReader::new(stream.try_clone().unwrap()).lines() {
+                let line = line.unwrap();
+                if line.is_empty() {
+                    break;
+                }
+                request_lines.push(line);
+            }
+            stream
+                .write_all(b"HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            request_lines
+        });
+        let context = Context::new(
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            false,
+            None,
+            vec![],
+            None,
+        );
+        let registry = GenericRegistry::new(context, "id", "registry", registry_url.as_str(), "login", "wrong");
+
+        // execute:
+        let result = registry.validate_credentials();
+
+        // verify:
+        let request_lines = server.join().unwrap();
+        assert_eq!(request_lines[0], "GET /v2/ HTTP/1.1");
+        assert!(request_lines
+            .iter()
+            .any(|line| line.to_lowercase().starts_with("authorization: basic ")));
+        let err = result.unwrap_err();
+        assert!(matches!(err.cause, EngineErrorCause::User(_)));
+        assert_eq!(
+            err.message,
+            Some("failed to authenticate on container registry registry (id)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_bearer_challenge() {
+        // execute:
+        let harbor = parse_bearer_challenge(
+            r#"Bearer realm="https://harbor.mycompany.com/service/token",service="harbor-registry",scope="repository:project/app:pull""#,
+        );
+        let without_service = parse_bearer_challenge(r#"Bearer realm="https://nexus.mycompany.com/v2/token""#);
+        let basic = parse_bearer_challenge(r#"Basic realm="Sonatype Nexus Repository Manager""#);
+
+        // verify:
+        assert_eq!(
+            harbor,
+            Some(BearerChallenge {
+                realm: "https://harbor.mycompany.com/service/token".to_string(),
+                service: Some("harbor-registry".to_string()),
+                scope: Some("repository:project/app:pull".to_string()),
+            })
+        );
+        assert_eq!(
+            without_service,
+            Some(BearerChallenge {
+                realm: "https://nexus.mycompany.com/v2/token".to_string(),
+                service: None,
+                scope: None,
+            })
+        );
+        assert_eq!(basic, None);
+    }
+
+    #[test]
+    fn test_registry_repository() {
+        // setup:
+        let context = Context::new(
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            false,
+            None,
+            vec![],
+            None,
+        );
+        let registry = |url: &str| GenericRegistry::new(context.clone(), "id", "registry", url, "login", "password");
+
+        // execute & verify:
+        let harbor = registry("https://harbor.mycompany.com/my-project/");
+        assert_eq!(harbor.registry_host(), "harbor.mycompany.com/my-project");
+        assert_eq!(harbor.repository("app"), "my-project/app");
+        assert_eq!(
+            harbor.api_url("/v2/my-project/app/tags/list").unwrap().as_str(),
+            "https://harbor.mycompany.com/v2/my-project/app/tags/list"
+        );
+
+        let nexus = registry("nexus.mycompany.com:8443");
+        assert_eq!(nexus.registry_host(), "nexus.mycompany.com:8443");
+        assert_eq!(nexus.repository("app"), "app");
+        assert_eq!(
+            nexus.api_url("/v2/").unwrap().as_str(),
+            "https://nexus.mycompany.com:8443/v2/"
+        );
+    }
+    #[test]
+    fn test_docker_json_config() {
+        // setup:
+        let context = Context::new(
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            false,
+            None,
+            vec![],
+            None,
+        );
+        let registry = GenericRegistry::new(
+            context,
+            "id",
+            "registry",
+            "https://harbor.mycompany.com/my-project",
+            "login",
+            "password",
+        );
+
+        // execute:
+        let docker_json_config = registry.get_docker_json_config_raw("password");
+
+        // verify: a `.dockerconfigjson` payload for the registry host, without its project path
+        let decoded: serde_json::Value = serde_json::from_slice(&base64::decode(docker_json_config).unwrap()).unwrap();
+        assert_eq!(
+            decoded,
+            serde_json::json!({"auths": {"harbor.mycompany.com": {"auth": base64::encode("login:password")}}})
+        );
+    }
+}
@@ -1,16 +1,46 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rusoto_core::Region;
 use serde::{Deserialize, Serialize};
 
 use crate::build_platform::Image;
-use crate::error::{EngineError, EngineErrorCause, EngineErrorScope};
+use crate::cloud_provider::scaleway::application::Zone;
+use crate::cmd::docker::DockerPushOutput;
+use crate::container_registry::acr::{AzureServicePrincipal, ACR};
+use crate::container_registry::docker_hub::DockerHub;
+use crate::container_registry::docr::DOCR;
+use crate::container_registry::ecr::ECR;
+use crate::container_registry::generic::GenericRegistry;
+use crate::container_registry::scaleway_container_registry::ScalewayCR;
+use crate::container_registry::utilities::docker_add_tag;
+use crate::error::{EngineError, EngineErrorCause, EngineErrorCode, EngineErrorScope};
 use crate::models::{Context, Listen};
 
+pub mod acr;
 pub mod docker_hub;
 pub mod docr;
 pub mod ecr;
+pub mod generic;
 pub mod scaleway_container_registry;
 pub mod utilities;
 
-pub trait ContainerRegistry: Listen {
+/// Every pushed image is also pushed with this tag, so next builds can use it as a cache source.
+pub const BUILD_CACHE_TAG: &str = "build-cache";
+
+/// Manifests media types accepted when checking an image, registries answer 404 for unknown ones.
+pub const MANIFEST_MEDIA_TYPES: &str = "application/vnd.docker.distribution.manifest.v2+json, \
+application/vnd.docker.distribution.manifest.list.v2+json, \
+application/vnd.oci.image.manifest.v1+json, \
+application/vnd.oci.image.index.v1+json";
+
+/// How long an image existence check result is reused before asking the registry again.
+pub const IMAGE_EXISTS_CACHE_TTL: Duration = Duration::from_secs(60);
+
+pub trait ContainerRegistry: Listen + Sync {
     fn context(&self) -> &Context;
     fn kind(&self) -> Kind;
     fn id(&self) -> &str;
@@ -19,12 +49,56 @@ pub trait ContainerRegistry: Listen {
         format!("{} ({})", self.name(), self.id())
     }
     fn is_valid(&self) -> Result<(), EngineError>;
+    /// Checks the credentials are accepted by the registry, wrong ones are a user error.
+    fn validate_credentials(&self) -> Result<(), EngineError>;
     fn on_create(&self) -> Result<(), EngineError>;
     fn on_create_error(&self) -> Result<(), EngineError>;
     fn on_delete(&self) -> Result<(), EngineError>;
     fn on_delete_error(&self) -> Result<(), EngineError>;
-    fn does_image_exists(&self, image: &Image) -> bool;
+    fn image_exists_cache(&self) -> &ImageExistsCache;
+    /// Asks the registry whether the image exists, bypassing the cache.
+    fn does_image_exists_uncached(&self, image: &Image) -> bool;
+    /// Same as `does_image_exists_uncached`, but results are cached for `IMAGE_EXISTS_CACHE_TTL`.
+    fn does_image_exists(&self, image: &Image) -> bool {
+        self.image_exists_cache()
+            .get_or_check(image, || self.does_image_exists_uncached(image))
+    }
+    /// Complete image URL in the registry, `None` if it can't be resolved.
+    fn image_url(&self, _image: &Image) -> Option<String> {
+        None
+    }
     fn push(&self, image: &Image, force_push: bool) -> Result<PushResult, EngineError>;
+    /// Pushes the image once, then applies the additional `tags` without uploading its layers again.
+    fn push_with_tags(&self, image: &Image, tags: &[String], force_push: bool) -> Result<PushResult, EngineError> {
+        let mut push_result = self.push(image, force_push)?;
+
+        for tag in tags {
+            if push_result.tags.contains(tag) {
+                continue;
+            }
+
+            self.add_tag(&push_result, tag.as_str())?;
+            push_result.tags.push(tag.clone());
+        }
+
+        Ok(push_result)
+    }
+    /// Tags an image already in the registry by copying its manifest.
+    fn add_tag(&self, push_result: &PushResult, tag: &str) -> Result<(), EngineError> {
+        let docker_envs = match self.context().docker_tcp_socket() {
+            Some(tcp_socket) => vec![("DOCKER_HOST", tcp_socket.as_str())],
+            None => vec![],
+        };
+
+        let reference = self.context().rewrite_image(push_result.reference.as_str());
+        docker_add_tag(docker_envs, reference.as_str(), tag).map_err(|e| {
+            self.engine_error(
+                EngineErrorCause::Internal,
+                e.message
+                    .unwrap_or_else(|| format!("failed to tag image {}", push_result.reference)),
+            )
+        })
+    }
     fn push_error(&self, image: &Image) -> Result<PushResult, EngineError>;
     fn engine_error_scope(&self) -> EngineErrorScope {
         EngineErrorScope::ContainerRegistry(self.id().to_string(), self.name().to_string())
@@ -37,17 +111,603 @@ pub trait ContainerRegistry: Listen {
             Some(message),
         )
     }
+    /// Error of a failed push, classified from the docker push output contained in `message`.
+    fn push_failure_error(&self, message: String) -> EngineError {
+        let kind = PushFailureKind::from_push_output(message.as_str());
+        self.engine_error(kind.cause(), message)
+            .with_code(EngineErrorCode::ContainerRegistryPush(kind))
+    }
+}
+
+impl dyn ContainerRegistry {
+    /// Creates the registry implementing `kind`, `config` settings must be the ones of this kind.
+    pub fn from_kind(
+        context: Context,
+        kind: Kind,
+        config: ContainerRegistryConfig,
+    ) -> Result<Box<dyn ContainerRegistry>, EngineError> {
+        let ContainerRegistryConfig { id, name, settings } = config;
+        let engine_error = |cause: EngineErrorCause, message: String| {
+            EngineError::new(
+                cause,
+                EngineErrorScope::ContainerRegistry(id.clone(), name.clone()),
+                context.execution_id(),
+                Some(message),
+            )
+        };
+
+        if settings.kind() != kind {
+            return Err(engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "container registry {} ({}) is a {:?} registry but has {:?} settings",
+                    name,
+                    id,
+                    kind,
+                    settings.kind()
+                ),
+            ));
+        }
+
+        let registry: Box<dyn ContainerRegistry> = match settings {
+            ContainerRegistrySettings::DockerHub { login, password } => Box::new(DockerHub::new_with_credentials(
+                context,
+                id.as_str(),
+                name.as_str(),
+                login.as_str(),
+                password,
+            )),
+            ContainerRegistrySettings::Ecr {
+                access_key_id,
+                secret_access_key,
+                region,
+            } => {
+                if Region::from_str(region.as_str()).is_err() {
+                    return Err(engine_error(
+                        EngineErrorCause::User("Check the region of your AWS container registry"),
+                        format!(
+                            "unknown AWS region `{}` for container registry {} ({})",
+                            region, name, id
+                        ),
+                    ));
+                }
+
+                Box::new(ECR::new_with_credentials(
+                    context,
+                    id.as_str(),
+                    name.as_str(),
+                    access_key_id,
+                    secret_access_key,
+                    region.as_str(),
+                ))
+            }
+            ContainerRegistrySettings::Docr { api_key, region } => {
+                let registry = DOCR::new_with_credentials(context, id.as_str(), name.as_str(), api_key);
+                match region {
+                    Some(region) => Box::new(registry.with_region(region.as_str())),
+                    None => Box::new(registry),
+                }
+            }
+            ContainerRegistrySettings::ScalewayCr {
+                secret_key,
+                project_id,
+                zone,
+            } => Box::new(ScalewayCR::new_with_credentials(
+                context,
+                id.as_str(),
+                name.as_str(),
+                secret_key,
+                project_id.as_str(),
+                zone,
+            )),
+            ContainerRegistrySettings::Acr {
+                subscription_id,
+                resource_group,
+                location,
+                service_principal,
+            } => Box::new(ACR::new(
+                context,
+                id.as_str(),
+                name.as_str(),
+                subscription_id.as_str(),
+                resource_group.as_str(),
+                location.as_str(),
+                service_principal,
+            )),
+            ContainerRegistrySettings::Generic { url, login, password } => {
+                Box::new(GenericRegistry::new_with_credentials(
+                    context,
+                    id.as_str(),
+                    name.as_str(),
+                    url.as_str(),
+                    login.as_str(),
+                    password,
+                ))
+            }
+        };
+
+        Ok(registry)
+    }
+}
+
+/// Container registry to create with `ContainerRegistry::from_kind`.
+pub struct ContainerRegistryConfig {
+    pub id: String,
+    pub name: String,
+    pub settings: ContainerRegistrySettings,
+}
+
+/// Settings of each kind of container registry, the ones given to their constructors.
+pub enum ContainerRegistrySettings {
+    DockerHub {
+        login: String,
+        password: RegistryCredentials,
+    },
+    Ecr {
+        access_key_id: RegistryCredentials,
+        secret_access_key: RegistryCredentials,
+        region: String,
+    },
+    Docr {
+        api_key: RegistryCredentials,
+        region: Option<String>,
+    },
+    ScalewayCr {
+        secret_key: RegistryCredentials,
+        project_id: String,
+        zone: Zone,
+    },
+    Acr {
+        subscription_id: String,
+        resource_group: String,
+        location: String,
+        service_principal: AzureServicePrincipal,
+    },
+    Generic {
+        url: String,
+        login: String,
+        password: RegistryCredentials,
+    },
+}
+
+impl ContainerRegistrySettings {
+    pub fn kind(&self) -> Kind {
+        match self {
+            ContainerRegistrySettings::DockerHub { .. } => Kind::DockerHub,
+            ContainerRegistrySettings::Ecr { .. } => Kind::Ecr,
+            ContainerRegistrySettings::Docr { .. } => Kind::Docr,
+            ContainerRegistrySettings::ScalewayCr { .. } => Kind::ScalewayCr,
+            ContainerRegistrySettings::Acr { .. } => Kind::Acr,
+            ContainerRegistrySettings::Generic { .. } => Kind::Generic,
+        }
+    }
 }
 
 pub struct PushResult {
     pub image: Image,
+    /// Time spent pushing the image, zero when it already was in the registry.
+    pub duration: Duration,
+    pub layers_pushed: u32,
+    pub layers_skipped: u32,
+    /// Final reference of the image in the registry, pinned to its digest when known.
+    pub reference: String,
+    /// Every tag applied to the image in the registry.
+    pub tags: Vec<String>,
+}
+
+impl PushResult {
+    pub fn pushed(image: Image, push_output: DockerPushOutput) -> Self {
+        PushResult {
+            tags: vec![image.tag.clone()],
+            image,
+            duration: push_output.duration,
+            layers_pushed: push_output.layers_pushed,
+            layers_skipped: push_output.layers_skipped,
+            reference: push_output.reference,
+        }
+    }
+
+    /// Nothing has been pushed, the image is already available in the registry at `reference`.
+    pub fn already_pushed(image: Image, reference: String) -> Self {
+        PushResult {
+            tags: vec![image.tag.clone()],
+            image,
+            duration: Duration::from_secs(0),
+            layers_pushed: 0,
+            layers_skipped: 0,
+            reference,
+        }
+    }
+}
+
+/// Why an image push failed, tells whether pushing again can succeed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PushFailureKind {
+    /// Credentials are invalid or not allowed to push to the repository.
+    Auth,
+    /// The registry could not be reached or the connection was interrupted.
+    Network,
+    /// Storage quota or pull/push rate limit of the registry account reached.
+    Quota,
+    /// The tag already exists and the repository does not allow overwriting it.
+    Conflict,
+    Unknown,
+}
+
+impl PushFailureKind {
+    /// Classify a failure from the error output of `docker push`.
+    /// Quota and conflict are checked first, registries often report them as `denied`.
+    pub fn from_push_output(output: &str) -> Self {
+        let output = output.to_lowercase();
+        let contains_any = |patterns: &[&str]| patterns.iter().any(|pattern| output.contains(pattern));
+
+        if contains_any(&["toomanyrequests", "quota", "limit exceeded", "429 too many requests"]) {
+            PushFailureKind::Quota
+        } else if contains_any(&["cannot be overwritten", "immutable", "tag invalid", "409 conflict"]) {
+            PushFailureKind::Conflict
+        } else if contains_any(&[
+            "unauthorized",
+            "authentication required",
+            "denied",
+            "no basic auth credentials",
+            "forbidden",
+        ]) {
+            PushFailureKind::Auth
+        } else if contains_any(&[
+            "timeout",
+            "connection refused",
+            "connection reset",
+            "no such host",
+            "dial tcp",
+            "broken pipe",
+            "unexpected eof",
+            "server misbehaving",
+            "502 bad gateway",
+            "503 service unavailable",
+        ]) {
+            PushFailureKind::Network
+        } else {
+            PushFailureKind::Unknown
+        }
+    }
+
+    /// Auth, quota and conflict failures will fail the same way until the user acts on them.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, PushFailureKind::Network)
+    }
+
+    pub fn cause(&self) -> EngineErrorCause {
+        match self {
+            PushFailureKind::Auth => EngineErrorCause::User(
+                "The container registry rejected the credentials, check they are valid and allowed to push images",
+            ),
+            PushFailureKind::Quota => EngineErrorCause::User(
+                "The container registry storage quota or rate limit is reached, free some space or upgrade your plan",
+            ),
+            PushFailureKind::Conflict => EngineErrorCause::User(
+                "The image tag already exists in the container registry and can't be overwritten",
+            ),
+            PushFailureKind::Network | PushFailureKind::Unknown => EngineErrorCause::Internal,
+        }
+    }
+}
+
+/// Results of image existence checks, keyed by image name and tag.
+/// A registry owns one of them, so the cache lives as long as the deployment using it.
+pub struct ImageExistsCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (bool, Instant)>>,
+}
+
+impl ImageExistsCache {
+    pub fn new(ttl: Duration) -> Self {
+        ImageExistsCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get_or_check<F>(&self, image: &Image, check: F) -> bool
+    where
+        F: FnOnce() -> bool,
+    {
+        let key = image.name_with_tag();
+
+        if let Some((exists, checked_at)) = self.entries.lock().unwrap().get(&key) {
+            if checked_at.elapsed() < self.ttl {
+                return *exists;
+            }
+        }
+
+        // do not hold the lock while the registry is requested
+        let exists = check();
+        self.entries.lock().unwrap().insert(key, (exists, Instant::now()));
+
+        exists
+    }
+
+    /// Must be called once an image has been pushed, its previous state is no longer valid.
+    pub fn invalidate(&self, image: &Image) {
+        self.entries.lock().unwrap().remove(&image.name_with_tag());
+    }
+}
+
+impl Default for ImageExistsCache {
+    fn default() -> Self {
+        ImageExistsCache::new(IMAGE_EXISTS_CACHE_TTL)
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+/// Secret a registry authenticates with. The provider is called again before every registry operation,
+/// so a rotated secret is picked up by a running engine without restarting it.
+#[derive(Clone)]
+pub struct RegistryCredentials {
+    provider: Arc<dyn Fn() -> String + Send + Sync>,
+}
+
+impl RegistryCredentials {
+    pub fn new<F>(provider: F) -> Self
+    where
+        F: Fn() -> String + Send + Sync + 'static,
+    {
+        RegistryCredentials {
+            provider: Arc::new(provider),
+        }
+    }
+
+    /// Secret which is never rotated.
+    pub fn constant(secret: &str) -> Self {
+        let secret = secret.to_string();
+        RegistryCredentials::new(move || secret.clone())
+    }
+
+    /// Current value of the secret, must not be kept across registry operations.
+    pub fn get(&self) -> String {
+        (self.provider)()
+    }
+}
+
+impl fmt::Debug for RegistryCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RegistryCredentials").finish()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Kind {
     DockerHub,
     Ecr,
     Docr,
     ScalewayCr,
+    Acr,
+    Generic,
+}
+
+impl Kind {
+    /// Every kind of registry, a new variant must be added here as well.
+    pub const ALL: [Kind; 6] = [
+        Kind::DockerHub,
+        Kind::Ecr,
+        Kind::Docr,
+        Kind::ScalewayCr,
+        Kind::Acr,
+        Kind::Generic,
+    ];
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::build_platform::Image;
+    use crate::cloud_provider::scaleway::application::Zone;
+    use crate::container_registry::acr::AzureServicePrincipal;
+    use crate::container_registry::{
+        ContainerRegistry, ContainerRegistryConfig, ContainerRegistrySettings, ImageExistsCache, Kind, PushFailureKind,
+        RegistryCredentials,
+    };
+    use crate::error::{EngineErrorCause, EngineErrorCode};
+    use crate::models::Context;
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    #[test]
+    fn test_image_exists_cache() {
+        // setup:
+        let cache = ImageExistsCache::new(Duration::from_secs(60));
+        let image = Image {
+            name: "my-app".to_string(),
+            tag: "abcdef".to_string(),
+            ..Default::default()
+        };
+        let registry_calls = Cell::new(0);
+        let check = || {
+            registry_calls.set(registry_calls.get() + 1);
+            true
+        };
+
+        // execute:
+        let first_check = cache.get_or_check(&image, check);
+        let second_check = cache.get_or_check(&image, check);
+
+        // verify:
+        assert!(first_check);
+        assert!(second_check);
+        assert_eq!(registry_calls.get(), 1);
+
+        // execute:
+        cache.invalidate(&image);
+        let _ = cache.get_or_check(&image, check);
+        let expired_cache = ImageExistsCache::new(Duration::from_secs(0));
+        let _ = expired_cache.get_or_check(&image, check);
+        let _ = expired_cache.get_or_check(&image, check);
+
+        // verify:
+        assert_eq!(registry_calls.get(), 4);
+    }
+
+    fn context() -> Context {
+        Context::new(
+            "execution-id".to_string(),
+            "/tmp".to_string(),
+            "lib".to_string(),
+            false,
+            None,
+            vec![],
+            None,
+        )
+    }
+
+    // no wildcard arm, a new kind of registry doesn't compile until it has settings
+    fn settings(kind: Kind) -> ContainerRegistrySettings {
+        match kind {
+            Kind::DockerHub => ContainerRegistrySettings::DockerHub {
+                login: "login".to_string(),
+                password: RegistryCredentials::constant("password"),
+            },
+            Kind::Ecr => ContainerRegistrySettings::Ecr {
+                access_key_id: RegistryCredentials::constant("access-key-id"),
+                secret_access_key: RegistryCredentials::constant("secret-access-key"),
+                region: "eu-west-3".to_string(),
+            },
+            Kind::Docr => ContainerRegistrySettings::Docr {
+                api_key: RegistryCredentials::constant("api-key"),
+                region: Some("fra1".to_string()),
+            },
+            Kind::ScalewayCr => ContainerRegistrySettings::ScalewayCr {
+                secret_key: RegistryCredentials::constant("secret-key"),
+                project_id: "project-id".to_string(),
+                zone: Zone::Paris1,
+            },
+            Kind::Acr => ContainerRegistrySettings::Acr {
+                subscription_id: "subscription-id".to_string(),
+                resource_group: "resource-group".to_string(),
+                location: "westeurope".to_string(),
+                service_principal: AzureServicePrincipal {
+                    tenant_id: "tenant-id".to_string(),
+                    client_id: "client-id".to_string(),
+                    client_secret: RegistryCredentials::constant("client-secret"),
+                },
+            },
+            Kind::Generic => ContainerRegistrySettings::Generic {
+                url: "https://harbor.mycompany.com".to_string(),
+                login: "login".to_string(),
+                password: RegistryCredentials::constant("password"),
+            },
+        }
+    }
+
+    fn config(kind: Kind) -> ContainerRegistryConfig {
+        ContainerRegistryConfig {
+            id: "id".to_string(),
+            name: "registry".to_string(),
+            settings: settings(kind),
+        }
+    }
+
+    #[test]
+    fn test_registry_from_every_kind() {
+        for kind in Kind::ALL.iter() {
+            // execute:
+            let registry = <dyn ContainerRegistry>::from_kind(context(), *kind, config(*kind));
+
+            // verify:
+            let registry = registry.unwrap_or_else(|_| panic!("{:?} registry can't be created", kind));
+            assert_eq!(registry.kind(), *kind);
+            assert_eq!(registry.name_with_id(), "registry (id)");
+        }
+    }
+
+    #[test]
+    fn test_registry_from_kind_with_invalid_config() {
+        // execute:
+        let mismatching_settings = <dyn ContainerRegistry>::from_kind(context(), Kind::Ecr, config(Kind::DockerHub));
+        let unknown_region = <dyn ContainerRegistry>::from_kind(
+            context(),
+            Kind::Ecr,
+            ContainerRegistryConfig {
+                id: "id".to_string(),
+                name: "registry".to_string(),
+                settings: ContainerRegistrySettings::Ecr {
+                    access_key_id: RegistryCredentials::constant("access-key-id"),
+                    secret_access_key: RegistryCredentials::constant("secret-access-key"),
+                    region: "mars-north-1".to_string(),
+                },
+            },
+        );
+
+        // verify:
+        assert!(matches!(
+            mismatching_settings.err().unwrap().cause,
+            EngineErrorCause::Internal
+        ));
+        assert!(matches!(unknown_region.err().unwrap().cause, EngineErrorCause::User(_)));
+    }
+
+    #[test]
+    fn test_push_failure_kind_from_push_output() {
+        // setup:
+        let outputs = vec![
+            ("unauthorized: authentication required", PushFailureKind::Auth),
+            ("denied: requested access to the resource is denied", PushFailureKind::Auth),
+            (
+                "no basic auth credentials",
+                PushFailureKind::Auth,
+            ),
+            (
+                "toomanyrequests: You have reached your pull rate limit. You may increase the limit by authenticating and upgrading",
+                PushFailureKind::Quota,
+            ),
+            ("denied: storage quota exceeded", PushFailureKind::Quota),
+            (
+                "tag invalid: The image tag 'v1' already exists in the 'my-app' repository and cannot be overwritten because the repository is immutable.",
+                PushFailureKind::Conflict,
+            ),
+            (
+                "Get \"https://registry.mycompany.com/v2/\": dial tcp: lookup registry.mycompany.com: i/o timeout",
+                PushFailureKind::Network,
+            ),
+            ("net/http: TLS handshake timeout", PushFailureKind::Network),
+            (
+                "write tcp 172.17.0.2:48792->52.1.2.3:443: write: connection reset by peer",
+                PushFailureKind::Network,
+            ),
+            (
+                "received unexpected HTTP status: 503 Service Unavailable",
+                PushFailureKind::Network,
+            ),
+            ("An image does not exist locally with the tag: my-app", PushFailureKind::Unknown),
+        ];
+
+        for (output, expected_kind) in outputs {
+            // execute:
+            let kind =
+                PushFailureKind::from_push_output(format!("failed to push image my-app:v1: {}", output).as_str());
+
+            // verify:
+            assert_eq!(kind, expected_kind, "{}", output);
+        }
+    }
+
+    #[test]
+    fn test_push_failure_error() {
+        // setup:
+        let registry = <dyn ContainerRegistry>::from_kind(context(), Kind::Generic, config(Kind::Generic)).unwrap();
+
+        // execute:
+        let auth = registry.push_failure_error("unauthorized: authentication required".to_string());
+        let quota = registry.push_failure_error("denied: storage quota exceeded".to_string());
+        let network = registry.push_failure_error("net/http: TLS handshake timeout".to_string());
+
+        // verify:
+        assert_eq!(
+            auth.code,
+            Some(EngineErrorCode::ContainerRegistryPush(PushFailureKind::Auth))
+        );
+        assert!(matches!(auth.cause, EngineErrorCause::User(_)));
+        assert!(!auth.is_retryable());
+        assert!(matches!(quota.cause, EngineErrorCause::User(_)));
+        assert!(!quota.is_retryable());
+        assert!(matches!(network.cause, EngineErrorCause::Internal));
+        assert!(network.is_retryable());
+    }
 }
@@ -3,14 +3,15 @@ extern crate scaleway_api_rs;
 use crate::cloud_provider::scaleway::application::Zone;
 
 use crate::build_platform::Image;
-use crate::cmd;
+use crate::cmd::docker::docker_login;
 use crate::container_registry::utilities::docker_tag_and_push_image;
-use crate::container_registry::{ContainerRegistry, Kind, PushResult};
-use crate::error::{EngineError, EngineErrorCause};
+use crate::container_registry::{ContainerRegistry, ImageExistsCache, Kind, PushResult, RegistryCredentials};
+use crate::error::{check_credentials_are_set, EngineError, EngineErrorCause};
 use crate::models::{
     Context, Listen, Listener, Listeners, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope,
 };
 use crate::runtime::block_on;
+use reqwest::StatusCode;
 use retry::delay::Fixed;
 use retry::Error::Operation;
 use retry::OperationResult;
@@ -20,9 +21,10 @@ pub struct ScalewayCR {
     id: String,
     name: String,
     default_project_id: String,
-    secret_token: String,
+    secret_token: RegistryCredentials,
     zone: Zone,
     listeners: Listeners,
+    image_exists_cache: ImageExistsCache,
 }
 
 impl ScalewayCR {
@@ -33,22 +35,42 @@ impl ScalewayCR {
         secret_token: &str,
         default_project_id: &str,
         zone: Zone,
+    ) -> ScalewayCR {
+        ScalewayCR::new_with_credentials(
+            context,
+            id,
+            name,
+            RegistryCredentials::constant(secret_token),
+            default_project_id,
+            zone,
+        )
+    }
+
+    /// The secret key is asked to `secret_token` before every operation, so it can be rotated.
+    pub fn new_with_credentials(
+        context: Context,
+        id: &str,
+        name: &str,
+        secret_token: RegistryCredentials,
+        default_project_id: &str,
+        zone: Zone,
     ) -> ScalewayCR {
         ScalewayCR {
             context,
             id: id.to_string(),
             name: name.to_string(),
             default_project_id: default_project_id.to_string(),
-            secret_token: secret_token.to_string(),
+            secret_token,
             zone,
             listeners: Vec::new(),
+            image_exists_cache: ImageExistsCache::default(),
         }
     }
 
     fn get_configuration(&self) -> scaleway_api_rs::apis::configuration::Configuration {
         scaleway_api_rs::apis::configuration::Configuration {
             api_key: Some(scaleway_api_rs::apis::configuration::ApiKey {
-                key: self.secret_token.clone(),
+                key: self.secret_token.get(),
                 prefix: None,
             }),
             ..scaleway_api_rs::apis::configuration::Configuration::default()
@@ -165,17 +187,19 @@ impl ScalewayCR {
 
     fn push_image(&self, image_url: String, image: &Image) -> Result<PushResult, EngineError> {
         // https://www.scaleway.com/en/docs/deploy-an-image-from-registry-to-kubernetes-kapsule/
-        match docker_tag_and_push_image(
+        let push_result = docker_tag_and_push_image(
             self.kind(),
             self.get_docker_envs(),
             image.name.clone(),
             image.tag.clone(),
-            image_url.clone(),
-        ) {
-            Ok(_) => {}
+            self.context.rewrite_image(image_url.as_str()),
+        );
+        self.image_exists_cache.invalidate(image);
+
+        let push_output = match push_result {
+            Ok(push_output) => push_output,
             Err(e) => {
-                return Err(self.engine_error(
-                    EngineErrorCause::Internal,
+                return Err(self.push_failure_error(
                     e.message
                         .unwrap_or_else(|| "unknown error occurring during docker push".to_string()),
                 ))
@@ -183,7 +207,7 @@ impl ScalewayCR {
         };
 
         let result = retry::retry(Fixed::from_millis(10000).take(12), || {
-            match self.does_image_exists(&image) {
+            match self.does_image_exists_uncached(&image) {
                 true => OperationResult::Ok(&image),
                 false => {
                     warn!("image is not yet available on Scaleway Registry Namespace, retrying in a few seconds...");
@@ -198,7 +222,7 @@ impl ScalewayCR {
         ));
 
         match result {
-            Ok(_) => Ok(PushResult { image: image.clone() }),
+            Ok(_) => Ok(PushResult::pushed(image.clone(), push_output)),
             Err(Operation { .. }) => image_not_reachable,
             Err(retry::Error::Internal(_)) => image_not_reachable,
         }
@@ -283,12 +307,12 @@ impl ScalewayCR {
         self.create_registry_namespace(&image)
     }
 
-    fn get_docker_json_config_raw(&self) -> String {
+    fn get_docker_json_config_raw(&self, secret_token: &str) -> String {
         base64::encode(
             format!(
                 r#"{{"auths":{{"rg.{}.scw.cloud":{{"auth":"{}"}}}}}}"#,
                 self.zone.region().as_str(),
-                base64::encode(format!("nologin:{}", self.secret_token).as_bytes())
+                base64::encode(format!("nologin:{}", secret_token).as_bytes())
             )
             .as_bytes(),
         )
@@ -301,7 +325,7 @@ impl ContainerRegistry for ScalewayCR {
     }
 
     fn kind(&self) -> Kind {
-        Kind::Docr
+        Kind::ScalewayCr
     }
 
     fn id(&self) -> &str {
@@ -316,6 +340,54 @@ impl ContainerRegistry for ScalewayCR {
         Ok(())
     }
 
+    fn validate_credentials(&self) -> Result<(), EngineError> {
+        let bad_credentials = "Your Scaleway account seems to be no longer valid (bad Credentials). \
+                Please contact your Organization administrator to fix or change the Credentials.";
+
+        check_credentials_are_set(&[
+            ("secret key", self.secret_token.get().as_str()),
+            ("project id", self.default_project_id.as_str()),
+        ])
+        .map_err(|err| {
+            self.engine_error(
+                EngineErrorCause::User(bad_credentials),
+                format!("{} for Scaleway registry {}", err, self.name_with_id()),
+            )
+        })?;
+
+        // https://developers.scaleway.com/en/products/registry/api/#get-09e004
+        block_on(scaleway_api_rs::apis::namespaces_api::list_namespaces(
+            &self.get_configuration(),
+            self.zone.region().to_string().as_str(),
+            None,
+            None,
+            None,
+            None,
+            Some(self.default_project_id.as_str()),
+            None,
+        ))
+        .map(|_| ())
+        .map_err(|e| {
+            let cause = match &e {
+                scaleway_api_rs::apis::Error::ResponseError(response)
+                    if response.status == StatusCode::UNAUTHORIZED || response.status == StatusCode::FORBIDDEN =>
+                {
+                    EngineErrorCause::User(bad_credentials)
+                }
+                _ => EngineErrorCause::Internal,
+            };
+
+            self.engine_error(
+                cause,
+                format!(
+                    "failed to list the namespaces of Scaleway registry {}: {}",
+                    self.name_with_id(),
+                    e
+                ),
+            )
+        })
+    }
+
     fn on_create(&self) -> Result<(), EngineError> {
         Ok(())
     }
@@ -332,12 +404,26 @@ impl ContainerRegistry for ScalewayCR {
         Ok(())
     }
 
-    fn does_image_exists(&self, image: &Image) -> bool {
+    fn image_exists_cache(&self) -> &ImageExistsCache {
+        &self.image_exists_cache
+    }
+
+    fn does_image_exists_uncached(&self, image: &Image) -> bool {
         self.get_image(image).is_some()
     }
 
+    fn image_url(&self, image: &Image) -> Option<String> {
+        let mut image = image.clone();
+        image.registry_name = Some(image.name.clone()); // Note: Repository namespace should have the same name as the image name
+
+        self.get_registry_namespace(&image)
+            .and_then(|registry| registry.endpoint)
+            .map(|registry_url| format!("{}/{}", registry_url, image.name_with_tag()))
+    }
+
     fn push(&self, image: &Image, force_push: bool) -> Result<PushResult, EngineError> {
         let mut image = image.clone();
+        let secret_token = self.secret_token.get();
         let registry_url: String;
         let registry_name: String;
 
@@ -349,8 +435,8 @@ impl ContainerRegistry for ScalewayCR {
                 );
                 image.registry_name = Some(image.name.clone()); // Note: Repository namespace should have the same name as the image name
                 image.registry_url = registry.endpoint.clone();
-                image.registry_secret = Some(self.secret_token.clone());
-                image.registry_docker_json_config = Some(self.get_docker_json_config_raw());
+                image.registry_secret = Some(secret_token.clone());
+                image.registry_docker_json_config = Some(self.get_docker_json_config_raw(secret_token.as_str()));
                 registry_url = registry.endpoint.unwrap_or_else(|| "undefined".to_string());
                 registry_name = registry.name.unwrap();
             }
@@ -366,20 +452,8 @@ impl ContainerRegistry for ScalewayCR {
 
         let envs = self.get_docker_envs();
 
-        if cmd::utilities::exec(
-            "docker",
-            vec![
-                "login",
-                registry_url.as_str(),
-                "-u",
-                "nologin",
-                "-p",
-                self.secret_token.as_str(),
-            ],
-            &envs,
-        )
-        .is_err()
-        {
+        if let Err(e) = docker_login(&envs, Some(registry_url.as_str()), "nologin", secret_token.as_str()) {
+            error!("{:?}", e.message);
             return Err(self.engine_error(
                 EngineErrorCause::User(
                     "Your Scaleway account seems to be no longer valid (bad Credentials). \
@@ -411,7 +485,7 @@ impl ContainerRegistry for ScalewayCR {
                 self.context.execution_id(),
             ));
 
-            return Ok(PushResult { image: image.clone() });
+            return Ok(PushResult::already_pushed(image.clone(), image_url));
         }
 
         let info_message = format!(
@@ -435,7 +509,8 @@ impl ContainerRegistry for ScalewayCR {
     }
 
     fn push_error(&self, image: &Image) -> Result<PushResult, EngineError> {
-        Ok(PushResult { image: image.clone() })
+        let reference = image.registry_url.clone().unwrap_or_else(|| image.name_with_tag());
+        Ok(PushResult::already_pushed(image.clone(), reference))
     }
 }
 
@@ -448,3 +523,45 @@ impl Listen for ScalewayCR {
         self.listeners.push(listener);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cloud_provider::scaleway::application::Zone;
+    use crate::container_registry::scaleway_container_registry::ScalewayCR;
+    use crate::container_registry::RegistryCredentials;
+    use crate::models::Context;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_rotated_secret_key_is_used_by_next_operation() {
+        // setup:
+        let context = Context::new(
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            false,
+            None,
+            vec![],
+            None,
+        );
+        let secret_key = Arc::new(Mutex::new("secret-key".to_string()));
+        let provided_secret_key = secret_key.clone();
+        let registry = ScalewayCR::new_with_credentials(
+            context,
+            "id",
+            "registry",
+            RegistryCredentials::new(move || provided_secret_key.lock().unwrap().clone()),
+            "project-id",
+            Zone::Paris1,
+        );
+
+        // execute:
+        let first_configuration = registry.get_configuration();
+        *secret_key.lock().unwrap() = "rotated-secret-key".to_string();
+        let second_configuration = registry.get_configuration();
+
+        // verify:
+        assert_eq!(first_configuration.api_key.unwrap().key, "secret-key");
+        assert_eq!(second_configuration.api_key.unwrap().key, "rotated-secret-key");
+    }
+}
@@ -1,10 +1,9 @@
+use std::collections::BTreeMap;
+
 use crate::cmd;
-use crate::container_registry::Kind;
+use crate::cmd::docker::{docker_push, docker_tag, DockerPushOutput};
+use crate::container_registry::{Kind, BUILD_CACHE_TAG};
 use crate::error::{SimpleError, SimpleErrorKind};
-use chrono::Duration;
-use retry::delay::Fibonacci;
-use retry::Error::Operation;
-use retry::OperationResult;
 
 pub fn docker_tag_and_push_image(
     container_registry_kind: Kind,
@@ -12,70 +11,243 @@ pub fn docker_tag_and_push_image(
     image_name: String,
     image_tag: String,
     dest: String,
-) -> Result<(), SimpleError> {
+) -> Result<DockerPushOutput, SimpleError> {
     let image_with_tag = format!("{}:{}", image_name, image_tag);
+    let push_output = docker_tag_and_push(container_registry_kind, &docker_envs, &image_with_tag, dest.as_str())?;
+
+    // layers are already pushed, so the build cache tag is cheap to push and is not mandatory
+    if let Some(cache_dest) = dest.strip_suffix(image_tag.as_str()) {
+        let cache_dest = format!("{}{}", cache_dest, BUILD_CACHE_TAG);
+        if let Err(e) = docker_tag_and_push(
+            container_registry_kind,
+            &docker_envs,
+            &image_with_tag,
+            cache_dest.as_str(),
+        ) {
+            warn!("failed to push build cache image {}: {:?}", cache_dest, e.message);
+        }
+    }
+
+    Ok(push_output)
+}
+
+/// Tags the image at `reference` with `tag`, the manifest is copied inside the registry so no layer is uploaded.
+pub fn docker_add_tag(docker_envs: Vec<(&str, &str)>, reference: &str, tag: &str) -> Result<(), SimpleError> {
+    let dest = retagged_reference(reference, tag);
+
+    cmd::utilities::exec(
+        "docker",
+        vec!["buildx", "imagetools", "create", "--tag", dest.as_str(), reference],
+        &docker_envs,
+    )
+    .map_err(|e| {
+        SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!(
+                "failed to tag image {} as {}: {:?}",
+                reference, dest, e.message
+            )),
+        )
+    })?;
+
+    info!("image {} has been tagged as {}", reference, dest);
+    Ok(())
+}
+
+/// Same repository with another tag, ex: `registry/app@sha256:c3bd...` or `registry/app:v1` become `registry/app:latest`.
+pub fn retagged_reference(reference: &str, tag: &str) -> String {
+    let repository = match reference.split_once('@') {
+        Some((repository, _digest)) => repository,
+        None => match reference.rsplit_once(':') {
+            // a colon followed by a slash belongs to the registry port, not to a tag
+            Some((repository, current_tag)) if !current_tag.contains('/') => repository,
+            _ => reference,
+        },
+    };
+
+    format!("{}:{}", repository, tag)
+}
+
+/// Host images are pulled from when a reference doesn't name its registry.
+pub const DOCKER_HUB_HOST: &str = "docker.io";
+
+/// Registry host of an image reference, `None` for Docker Hub images which don't name it.
+pub fn image_registry_host(image: &str) -> Option<&str> {
+    match image.split_once('/') {
+        // a host has a domain, a port or is localhost, otherwise it's a Docker Hub namespace
+        Some((first_component, _))
+            if first_component.contains('.') || first_component.contains(':') || first_component == "localhost" =>
+        {
+            Some(first_component)
+        }
+        _ => None,
+    }
+}
+
+/// Rewrites the registry of an image reference with the longest matching prefix of `rewrites`, ex: with
+/// `docker.io -> registry.internal/dockerhub`, `node:14` becomes `registry.internal/dockerhub/library/node:14`.
+/// Prefixes are a registry host optionally followed by repository path components.
+/// Tags and digests are kept, references already under a rewrite destination are left as is.
+pub fn rewrite_image_reference(rewrites: &BTreeMap<String, String>, image: &str) -> String {
+    if rewrites.is_empty() {
+        return image.to_string();
+    }
+
+    let (name, digest) = match image.split_once('@') {
+        Some((name, digest)) => (name, Some(digest)),
+        None => (image, None),
+    };
+    let (repository, tag) = match name.rsplit_once(':') {
+        // a colon followed by a slash belongs to the registry port, not to a tag
+        Some((repository, tag)) if !tag.contains('/') => (repository, Some(tag)),
+        _ => (name, None),
+    };
+
+    let is_under = |repository: &str, prefix: &str| {
+        repository == prefix || repository.starts_with(format!("{}/", prefix).as_str())
+    };
+    if rewrites
+        .values()
+        .any(|destination| is_under(repository, destination.trim_end_matches('/')))
+    {
+        return image.to_string();
+    }
+
+    let (host, path) = match image_registry_host(repository) {
+        Some(host) if host == "index.docker.io" || host == "registry-1.docker.io" => {
+            (DOCKER_HUB_HOST, &repository[host.len() + 1..])
+        }
+        Some(host) => (host, &repository[host.len() + 1..]),
+        None => (DOCKER_HUB_HOST, repository),
+    };
+    let repository = match host == DOCKER_HUB_HOST && !path.contains('/') {
+        // official images live under `library/`
+        true => format!("{}/library/{}", host, path),
+        false => format!("{}/{}", host, path),
+    };
+
+    let (prefix, destination) = match rewrites
+        .iter()
+        .map(|(prefix, destination)| (prefix.trim_end_matches('/'), destination.trim_end_matches('/')))
+        .filter(|(prefix, _)| is_under(repository.as_str(), prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+    {
+        Some(rewrite) => rewrite,
+        None => return image.to_string(),
+    };
+
+    let mut rewritten = format!("{}{}", destination, &repository[prefix.len()..]);
+    if let Some(tag) = tag {
+        rewritten = format!("{}:{}", rewritten, tag);
+    }
+    if let Some(digest) = digest {
+        rewritten = format!("{}@{}", rewritten, digest);
+    }
+
+    rewritten
+}
+
+fn docker_tag_and_push(
+    container_registry_kind: Kind,
+    docker_envs: &[(&str, &str)],
+    image_with_tag: &str,
+    dest: &str,
+) -> Result<DockerPushOutput, SimpleError> {
     let registry_provider = match container_registry_kind {
         Kind::DockerHub => "DockerHub",
         Kind::Ecr => "AWS ECR",
         Kind::Docr => "DigitalOcean Registry",
         Kind::ScalewayCr => "Scaleway Registry",
+        Kind::Acr => "Azure Container Registry",
+        Kind::Generic => "Container Registry",
     };
 
-    match retry::retry(Fibonacci::from_millis(3000).take(5), || {
-        match cmd::utilities::exec("docker", vec!["tag", &image_with_tag, dest.as_str()], &docker_envs) {
-            Ok(_) => OperationResult::Ok(()),
-            Err(e) => {
-                info!("failed to tag image {}, retrying...", image_with_tag);
-                OperationResult::Retry(e)
-            }
-        }
-    }) {
-        Err(Operation { error, .. }) => {
-            return Err(SimpleError::new(
-                SimpleErrorKind::Other,
-                Some(format!("failed to tag image {}: {:?}", image_with_tag, error.message)),
-            ))
-        }
-        _ => {}
-    }
-
-    match retry::retry(
-        Fibonacci::from_millis(5000).take(5),
-        || match cmd::utilities::exec_with_envs_and_output(
-            "docker",
-            vec!["push", dest.as_str()],
-            docker_envs.clone(),
-            |line| {
-                let line_string = line.unwrap_or_default();
-                info!("{}", line_string.as_str());
-            },
-            |line| {
-                let line_string = line.unwrap_or_default();
-                error!("{}", line_string.as_str());
-            },
-            Duration::minutes(10),
-        ) {
-            Ok(_) => OperationResult::Ok(()),
-            Err(e) => {
-                warn!(
-                    "failed to push image {} on {}, {:?} retrying...",
-                    image_with_tag, registry_provider, e.message
-                );
-                OperationResult::Retry(e)
-            }
-        },
-    ) {
-        Err(Operation { error, .. }) => Err(error),
-        Err(e) => Err(SimpleError::new(
+    docker_tag(docker_envs, image_with_tag, dest)?;
+    docker_push(docker_envs, dest).map_err(|e| {
+        SimpleError::new(
             SimpleErrorKind::Other,
             Some(format!(
-                "unknown error while trying to push image {} to {}. {:?}",
-                image_with_tag, registry_provider, e
+                "failed to push image {} to {}: {:?}",
+                image_with_tag, registry_provider, e.message
             )),
-        )),
-        _ => {
-            info!("image {} has successfully been pushed", image_with_tag);
-            Ok(())
-        }
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::container_registry::utilities::{retagged_reference, rewrite_image_reference};
+
+    #[test]
+    fn test_retagged_reference() {
+        assert_eq!(
+            retagged_reference("registry.digitalocean.com/qovery/my-app@sha256:c3bd7fa2b4", "latest"),
+            "registry.digitalocean.com/qovery/my-app:latest"
+        );
+        assert_eq!(
+            retagged_reference("qovery/my-app:abcdef", "production"),
+            "qovery/my-app:production"
+        );
+        assert_eq!(
+            retagged_reference("localhost:5000/my-app", "latest"),
+            "localhost:5000/my-app:latest"
+        );
+    }
+
+    #[test]
+    fn test_rewrite_image_reference() {
+        // setup:
+        let mut rewrites = BTreeMap::new();
+        rewrites.insert("docker.io".to_string(), "registry.internal/dockerhub".to_string());
+        rewrites.insert(
+            "docker.io/bitnami".to_string(),
+            "registry.internal/bitnami/".to_string(),
+        );
+        rewrites.insert("quay.io".to_string(), "registry.internal:5000/quay".to_string());
+        let rewrite = |image: &str| rewrite_image_reference(&rewrites, image);
+
+        // execute & verify:
+        assert_eq!(rewrite("node:14"), "registry.internal/dockerhub/library/node:14");
+        assert_eq!(rewrite("debian"), "registry.internal/dockerhub/library/debian");
+        assert_eq!(
+            rewrite("qovery/engine:v1"),
+            "registry.internal/dockerhub/qovery/engine:v1"
+        );
+        assert_eq!(
+            rewrite("index.docker.io/library/node:14"),
+            "registry.internal/dockerhub/library/node:14"
+        );
+        // the longest prefix wins
+        assert_eq!(
+            rewrite("docker.io/bitnami/redis:6.0"),
+            "registry.internal/bitnami/redis:6.0"
+        );
+        assert_eq!(
+            rewrite("bitnami-labs/sealed-secrets"),
+            "registry.internal/dockerhub/bitnami-labs/sealed-secrets"
+        );
+        // digests are kept
+        assert_eq!(
+            rewrite("quay.io/bitnami/postgresql@sha256:c3bd7fa2b4"),
+            "registry.internal:5000/quay/bitnami/postgresql@sha256:c3bd7fa2b4"
+        );
+        assert_eq!(
+            rewrite("node:14@sha256:c3bd7fa2b4"),
+            "registry.internal/dockerhub/library/node:14@sha256:c3bd7fa2b4"
+        );
+        // already mirrored or not rewritten
+        assert_eq!(
+            rewrite("registry.internal/dockerhub/library/node:14"),
+            "registry.internal/dockerhub/library/node:14"
+        );
+        assert_eq!(
+            rewrite("registry.internal:5000/quay/bitnami/postgresql:10"),
+            "registry.internal:5000/quay/bitnami/postgresql:10"
+        );
+        assert_eq!(rewrite("ghcr.io/qovery/engine:1.0"), "ghcr.io/qovery/engine:1.0");
+        assert_eq!(rewrite("localhost:5000/app:v1"), "localhost:5000/app:v1");
+        assert_eq!(rewrite_image_reference(&BTreeMap::new(), "node:14"), "node:14");
     }
 }
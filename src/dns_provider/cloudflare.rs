@@ -1,8 +1,36 @@
 use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use retry::delay::Fixed;
+use retry::OperationResult;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
 
 use crate::dns_provider::{DnsProvider, Kind};
-use crate::error::{EngineError, EngineErrorCause};
+use crate::error::{check_credentials_are_set, EngineError, EngineErrorCause, SimpleError, SimpleErrorKind};
 use crate::models::Context;
+use crate::utilities::get_header_with_bearer;
+
+const CLOUDFLARE_API_URL: &str = "https://api.cloudflare.com/client/v4";
+
+const BAD_CREDENTIALS: &str = "Your Cloudflare account seems to be no longer valid (bad Credentials). \
+    Please contact your Organization administrator to fix or change the Credentials.";
+
+#[derive(Deserialize)]
+struct CloudflareListResponse<T> {
+    result: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct CloudflareZone {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct CloudflareDnsRecord {
+    name: String,
+}
 
 pub struct Cloudflare {
     context: Context,
@@ -31,6 +59,47 @@ impl Cloudflare {
             cloudflare_email: cloudflare_email.to_string(),
         }
     }
+
+    fn list<T>(&self, path: &str) -> Result<Vec<T>, SimpleError>
+    where
+        T: DeserializeOwned,
+    {
+        let response = reqwest::blocking::Client::new()
+            .get(format!("{}{}", CLOUDFLARE_API_URL, path).as_str())
+            .headers(get_header_with_bearer(self.cloudflare_api_token.as_str()))
+            .send()
+            .map_err(|e| SimpleError::new(SimpleErrorKind::Other, Some(format!("{:?}", e))))?;
+
+        match response.status() {
+            StatusCode::OK => response
+                .json::<CloudflareListResponse<T>>()
+                .map(|response| response.result)
+                .map_err(|e| SimpleError::new(SimpleErrorKind::Other, Some(format!("{:?}", e)))),
+            status => Err(SimpleError::new(
+                SimpleErrorKind::Other,
+                Some(format!("Cloudflare API returned {} on {}", status, path)),
+            )),
+        }
+    }
+
+    /// The domain can be a subdomain of the Cloudflare zone, parent domains are tried until a zone matches.
+    fn zone_id(&self) -> Result<String, SimpleError> {
+        let labels = self.domain.split('.').collect::<Vec<_>>();
+
+        for i in 0..labels.len().saturating_sub(1) {
+            let zone_name = labels[i..].join(".");
+            let zones = self.list::<CloudflareZone>(format!("/zones?name={}", zone_name).as_str())?;
+
+            if let Some(zone) = zones.into_iter().next() {
+                return Ok(zone.id);
+            }
+        }
+
+        Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!("no Cloudflare zone found for {}", self.domain)),
+        ))
+    }
 }
 
 impl DnsProvider for Cloudflare {
@@ -73,14 +142,109 @@ impl DnsProvider for Cloudflare {
     fn is_valid(&self) -> Result<(), EngineError> {
         if self.cloudflare_api_token.is_empty() || self.cloudflare_email.is_empty() {
             Err(self.engine_error(
-                EngineErrorCause::User(
-                    "Your Cloudflare account seems to be no longer valid (bad Credentials). \
-                    Please contact your Organization administrator to fix or change the Credentials.",
-                ),
+                EngineErrorCause::User(BAD_CREDENTIALS),
                 format!("bad Cloudflare credentials for {}", self.name_with_id()),
             ))
         } else {
             Ok(())
         }
     }
+
+    fn validate_credentials(&self) -> Result<(), EngineError> {
+        check_credentials_are_set(&[
+            ("API token", self.cloudflare_api_token.as_str()),
+            ("email", self.cloudflare_email.as_str()),
+        ])
+        .map_err(|err| {
+            self.engine_error(
+                EngineErrorCause::User(BAD_CREDENTIALS),
+                format!("{} for Cloudflare {}", err, self.name_with_id()),
+            )
+        })?;
+
+        let response = reqwest::blocking::Client::new()
+            .get(format!("{}/user/tokens/verify", CLOUDFLARE_API_URL).as_str())
+            .headers(get_header_with_bearer(self.cloudflare_api_token.as_str()))
+            .send()
+            .map_err(|e| {
+                self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!(
+                        "No response from the Cloudflare API for {}: {:?}",
+                        self.name_with_id(),
+                        e
+                    ),
+                )
+            })?;
+
+        match response.status() {
+            StatusCode::OK => Ok(()),
+            StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(self.engine_error(
+                EngineErrorCause::User(BAD_CREDENTIALS),
+                format!("bad Cloudflare credentials for {}", self.name_with_id()),
+            )),
+            status => Err(self.engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "Cloudflare API returned {} while checking the token of {}",
+                    status,
+                    self.name_with_id()
+                ),
+            )),
+        }
+    }
+
+    fn can_wait_for_propagation(&self) -> bool {
+        true
+    }
+
+    fn wait_for_propagation(&self, record: &str, timeout: Duration) -> Result<(), EngineError> {
+        let zone_id = self
+            .zone_id()
+            .map_err(|e| self.engine_error(EngineErrorCause::Internal, e.message.unwrap_or_default()))?;
+        let record = record.trim_end_matches('.');
+        let path = format!("/zones/{}/dns_records?name={}", zone_id, record);
+
+        let delay = Fixed::from_millis(3000);
+        let attempts = (timeout.as_millis() / 3000).max(1) as usize;
+        let result = retry::retry(delay.take(attempts), || {
+            if self.context.is_cancelled() {
+                return OperationResult::Err(None);
+            }
+
+            match self.list::<CloudflareDnsRecord>(path.as_str()) {
+                Ok(records) if records.iter().any(|r| r.name == record) => OperationResult::Ok(()),
+                Ok(_) => {
+                    info!(
+                        "record {} is not yet in Cloudflare zone {}, retrying...",
+                        record, zone_id
+                    );
+                    OperationResult::Retry(None)
+                }
+                Err(e) => {
+                    warn!("can't list Cloudflare records of zone {}: {:?}", zone_id, e.message);
+                    OperationResult::Retry(e.message)
+                }
+            }
+        });
+
+        if self.context.is_cancelled() {
+            return Err(self.engine_error(
+                EngineErrorCause::User("Operation cancelled"),
+                format!("operation cancelled while waiting for {} propagation", record),
+            ));
+        }
+
+        result.map_err(|e| {
+            self.engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "record {} has not been propagated by Cloudflare after {}s: {:?}",
+                    record,
+                    timeout.as_secs(),
+                    e
+                ),
+            )
+        })
+    }
 }
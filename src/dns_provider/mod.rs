@@ -1,4 +1,5 @@
 use std::net::Ipv4Addr;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 
@@ -24,6 +25,23 @@ pub trait DnsProvider {
     }
     fn resolvers(&self) -> Vec<Ipv4Addr>;
     fn is_valid(&self) -> Result<(), EngineError>;
+    /// Checks the credentials are accepted by the provider API, wrong ones are a user error.
+    fn validate_credentials(&self) -> Result<(), EngineError>;
+    /// Whether `wait_for_propagation` is implemented with the provider API.
+    fn can_wait_for_propagation(&self) -> bool {
+        false
+    }
+    /// Waits until `record` is served by the provider, asking its API rather than public resolvers.
+    fn wait_for_propagation(&self, record: &str, _timeout: Duration) -> Result<(), EngineError> {
+        Err(self.engine_error(
+            EngineErrorCause::Internal,
+            format!(
+                "{} can't check the propagation of {}, use DNS resolvers instead",
+                self.name_with_id(),
+                record
+            ),
+        ))
+    }
     fn engine_error_scope(&self) -> EngineErrorScope {
         EngineErrorScope::DnsProvider(self.id().to_string(), self.name().to_string())
     }
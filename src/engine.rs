@@ -4,8 +4,9 @@ use crate::build_platform::BuildPlatform;
 use crate::cloud_provider::CloudProvider;
 use crate::container_registry::ContainerRegistry;
 use crate::dns_provider::DnsProvider;
-use crate::error::EngineError;
+use crate::error::{EngineError, EngineErrorCause, EngineErrorScope};
 use crate::models::Context;
+use crate::object_storage::ObjectStorage;
 use crate::session::Session;
 
 pub struct Engine {
@@ -64,6 +65,18 @@ impl<'a> Engine {
         Ok(())
     }
 
+    /// Checks the credentials of every provider, all the failures are reported at once.
+    pub fn validate_credentials(&self, object_storages: &[&dyn ObjectStorage]) -> Result<(), EngineError> {
+        let mut results = vec![
+            self.cloud_provider.validate_credentials(),
+            self.container_registry.validate_credentials(),
+            self.dns_provider.validate_credentials(),
+        ];
+        results.extend(object_storages.iter().map(|o| o.validate_credentials()));
+
+        merge_credentials_errors(self.context.execution_id(), results)
+    }
+
     /// check and init the connection to all services
     pub fn session(&'a self) -> Result<Session<'a>, EngineError> {
         match self.is_valid() {
@@ -72,3 +85,255 @@ impl<'a> Engine {
         }
     }
 }
+
+fn merge_credentials_errors(execution_id: &str, results: Vec<Result<(), EngineError>>) -> Result<(), EngineError> {
+    let messages = results
+        .into_iter()
+        .filter_map(|result| result.err())
+        .map(|err| match err.message {
+            Some(message) => message,
+            None => format!("invalid credentials for {:?}", err.scope),
+        })
+        .collect::<Vec<_>>();
+
+    if messages.is_empty() {
+        return Ok(());
+    }
+
+    Err(EngineError::new(
+        EngineErrorCause::User("Fix the credentials of the providers before deploying"),
+        EngineErrorScope::Engine,
+        execution_id,
+        Some(format!("invalid credentials: {}", messages.join("; "))),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cloud_provider::aws::AWS;
+    use crate::cloud_provider::digitalocean::application::Region as DoRegion;
+    use crate::cloud_provider::digitalocean::DO;
+    use crate::cloud_provider::scaleway::application::Zone;
+    use crate::cloud_provider::scaleway::Scaleway;
+    use crate::cloud_provider::{CloudProvider, TerraformBackend, TerraformStateCredentials};
+    use crate::container_registry::acr::{AzureServicePrincipal, ACR};
+    use crate::container_registry::docker_hub::DockerHub;
+    use crate::container_registry::docr::DOCR;
+    use crate::container_registry::ecr::ECR;
+    use crate::container_registry::generic::GenericRegistry;
+    use crate::container_registry::scaleway_container_registry::ScalewayCR;
+    use crate::container_registry::{ContainerRegistry, RegistryCredentials};
+    use crate::dns_provider::cloudflare::Cloudflare;
+    use crate::dns_provider::DnsProvider;
+    use crate::engine::merge_credentials_errors;
+    use crate::error::{EngineError, EngineErrorCause, EngineErrorScope};
+    use crate::models::Context;
+    use crate::object_storage::s3::S3;
+    use crate::object_storage::scaleway_object_storage::{BucketDeleteStrategy, ScalewayOS};
+    use crate::object_storage::spaces::Spaces;
+    use crate::object_storage::ObjectStorage;
+
+    #[test]
+    fn test_validate_credentials_fails_when_credentials_are_missing() {
+        // setup:
+        let context = Context::new(
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            false,
+            None,
+            vec![],
+            None,
+        );
+        let terraform_state_credentials = || TerraformStateCredentials {
+            access_key_id: "".to_string(),
+            secret_access_key: "".to_string(),
+            region: "eu-west-3".to_string(),
+            backend: TerraformBackend::KubernetesSecret,
+        };
+        let aws = AWS::new(
+            context.clone(),
+            "id",
+            "organization",
+            "aws",
+            "",
+            " ",
+            terraform_state_credentials(),
+        );
+        let digital_ocean = DO::new(
+            context.clone(),
+            "id",
+            "organization",
+            "",
+            "spaces-access-id",
+            "spaces-secret-key",
+            "do",
+            terraform_state_credentials(),
+        );
+        let scaleway = Scaleway::new(
+            context.clone(),
+            "id",
+            "organization",
+            "scaleway",
+            "access-key",
+            "",
+            terraform_state_credentials(),
+        );
+        let acr = ACR::new(
+            context.clone(),
+            "id",
+            "QoveryRegistry",
+            "subscription",
+            "qovery",
+            "westeurope",
+            AzureServicePrincipal {
+                tenant_id: "tenant".to_string(),
+                client_id: "client".to_string(),
+                client_secret: RegistryCredentials::constant(""),
+            },
+        );
+        let docker_hub = DockerHub::new(context.clone(), "id", "docker-hub", "", "");
+        let docr = DOCR::new(context.clone(), "id", "docr", " ");
+        let ecr = ECR::new(context.clone(), "id", "ecr", "access-key-id", "", "eu-west-3");
+        let generic = GenericRegistry::new(
+            context.clone(),
+            "id",
+            "registry",
+            "https://harbor.mycompany.com",
+            "login",
+            "",
+        );
+        let scaleway_cr = ScalewayCR::new(context.clone(), "id", "registry", "secret-key", "", Zone::Paris1);
+        let cloudflare = Cloudflare::new(context.clone(), "id", "cloudflare", "example.com", "", "");
+        let s3 = S3::new(
+            context.clone(),
+            "id".to_string(),
+            "s3".to_string(),
+            "".to_string(),
+            "".to_string(),
+        );
+        let spaces = Spaces::new(
+            context.clone(),
+            "id".to_string(),
+            "spaces".to_string(),
+            "".to_string(),
+            "secret-access-key".to_string(),
+            DoRegion::Frankfurt,
+        );
+        let scaleway_os = ScalewayOS::new(
+            context,
+            "id".to_string(),
+            "object-storage".to_string(),
+            "access-key".to_string(),
+            "".to_string(),
+            Zone::Paris1,
+            BucketDeleteStrategy::Empty,
+            false,
+        );
+
+        let test_cases: Vec<(Result<(), EngineError>, &str)> = vec![
+            (
+                aws.validate_credentials(),
+                "missing credentials: access key id, secret access key for AWS aws (id)",
+            ),
+            (
+                digital_ocean.validate_credentials(),
+                "missing credentials: token for Digital Ocean do (id)",
+            ),
+            (
+                scaleway.validate_credentials(),
+                "missing credentials: secret key for Scaleway scaleway (id)",
+            ),
+            (
+                acr.validate_credentials(),
+                "missing credentials: client secret for ACR QoveryRegistry (id)",
+            ),
+            (
+                docker_hub.validate_credentials(),
+                "missing credentials: login, password for DockerHub docker-hub (id)",
+            ),
+            (
+                docr.validate_credentials(),
+                "missing credentials: API token for DOCR docr (id)",
+            ),
+            (
+                ecr.validate_credentials(),
+                "missing credentials: secret access key for ECR ecr (id)",
+            ),
+            (
+                generic.validate_credentials(),
+                "missing credentials: password for container registry registry (id)",
+            ),
+            (
+                scaleway_cr.validate_credentials(),
+                "missing credentials: project id for Scaleway registry registry (id)",
+            ),
+            (
+                cloudflare.validate_credentials(),
+                "missing credentials: API token, email for Cloudflare cloudflare (id)",
+            ),
+            (
+                s3.validate_credentials(),
+                "missing credentials: access key id, secret access key for object storage s3 (id)",
+            ),
+            (
+                spaces.validate_credentials(),
+                "missing credentials: access key id for object storage spaces (id)",
+            ),
+            (
+                scaleway_os.validate_credentials(),
+                "missing credentials: secret key for object storage object-storage (id)",
+            ),
+        ];
+
+        // verify:
+        for (result, expected_message) in test_cases {
+            let err = result.unwrap_err();
+            assert!(matches!(err.cause, EngineErrorCause::User(_)), "{}", expected_message);
+            assert_eq!(err.message.as_deref(), Some(expected_message));
+        }
+    }
+
+    #[test]
+    fn test_merge_credentials_errors() {
+        // setup:
+        let error = |scope: EngineErrorScope, message: Option<&str>| {
+            EngineError::new(
+                EngineErrorCause::User("Check the credentials"),
+                scope,
+                "execution",
+                message,
+            )
+        };
+
+        // execute:
+        let valid = merge_credentials_errors("execution", vec![Ok(()), Ok(())]);
+        let invalid = merge_credentials_errors(
+            "execution",
+            vec![
+                Err(error(
+                    EngineErrorScope::CloudProvider("id".to_string(), "aws".to_string()),
+                    Some("failed to login to AWS aws (id)"),
+                )),
+                Ok(()),
+                Err(error(
+                    EngineErrorScope::DnsProvider("id".to_string(), "cloudflare".to_string()),
+                    Some("bad Cloudflare credentials for cloudflare (id)"),
+                )),
+            ],
+        );
+
+        // verify:
+        assert!(valid.is_ok());
+        let err = invalid.unwrap_err();
+        assert!(matches!(err.cause, EngineErrorCause::User(_)));
+        assert!(matches!(err.scope, EngineErrorScope::Engine));
+        assert_eq!(
+            err.message,
+            Some(
+                "invalid credentials: failed to login to AWS aws (id); bad Cloudflare credentials for cloudflare (id)"
+                    .to_string()
+            )
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use std::process::ExitStatus;
 
+use crate::container_registry::PushFailureKind;
+
 pub type Type = String;
 pub type Id = String;
 pub type Name = String;
@@ -10,6 +12,8 @@ pub struct EngineError {
     pub scope: EngineErrorScope,
     pub execution_id: String,
     pub message: Option<String>,
+    /// Set when the failure has been classified, so the caller can decide to retry or abort.
+    pub code: Option<EngineErrorCode>,
 }
 
 impl EngineError {
@@ -26,10 +30,29 @@ impl EngineError {
                 Some(message) => Some(message.into()),
                 _ => None,
             },
+            code: None,
+        }
+    }
+
+    pub fn with_code(mut self, code: EngineErrorCode) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Only classified failures known to be transient are worth retrying.
+    pub fn is_retryable(&self) -> bool {
+        match self.code {
+            Some(EngineErrorCode::ContainerRegistryPush(kind)) => kind.is_retryable(),
+            None => false,
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EngineErrorCode {
+    ContainerRegistryPush(PushFailureKind),
+}
+
 #[derive(Debug)]
 pub enum EngineErrorScope {
     Engine,
@@ -63,6 +86,8 @@ pub type StringError = String;
 #[derive(Debug)]
 pub enum SimpleErrorKind {
     Command(ExitStatus),
+    /// The operation has been stopped by its `CancellationToken`.
+    Cancelled,
     Other,
 }
 
@@ -90,6 +115,15 @@ pub fn cast_simple_error_to_engine_error<X, T: Into<String>>(
     input: Result<X, SimpleError>,
 ) -> Result<X, EngineError> {
     match input {
+        Err(SimpleError {
+            kind: SimpleErrorKind::Cancelled,
+            message,
+        }) => Err(EngineError::new(
+            EngineErrorCause::User("Operation cancelled"),
+            scope,
+            execution_id,
+            Some(message.unwrap_or_else(|| "operation cancelled".into())),
+        )),
         Err(simple_error) => {
             let message = match simple_error.kind {
                 SimpleErrorKind::Command(exit_status) => format!(
@@ -97,7 +131,9 @@ pub fn cast_simple_error_to_engine_error<X, T: Into<String>>(
                     simple_error.message.unwrap_or("<no message>".into()),
                     exit_status
                 ),
-                SimpleErrorKind::Other => simple_error.message.unwrap_or("<no message>".into()),
+                SimpleErrorKind::Cancelled | SimpleErrorKind::Other => {
+                    simple_error.message.unwrap_or("<no message>".into())
+                }
             };
 
             Err(EngineError::new(
@@ -110,3 +146,17 @@ pub fn cast_simple_error_to_engine_error<X, T: Into<String>>(
         Ok(x) => Ok(x),
     }
 }
+
+/// Fails with the names of the empty credentials, for them to be reported before calling any API.
+pub fn check_credentials_are_set(credentials: &[(&str, &str)]) -> Result<(), StringError> {
+    let missing = credentials
+        .iter()
+        .filter(|(_, value)| value.trim().is_empty())
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
+
+    match missing.is_empty() {
+        true => Ok(()),
+        false => Err(format!("missing credentials: {}", missing.join(", "))),
+    }
+}
@@ -3,10 +3,12 @@ use std::fs;
 use std::fs::{create_dir_all, File};
 use std::io::{Error, ErrorKind};
 use std::path::Path;
+use std::time::Duration;
 
 use flate2::write::GzEncoder;
 use flate2::Compression;
 use std::ffi::OsStr;
+use sysinfo::{Pid, SystemExt};
 use walkdir::WalkDir;
 
 pub fn copy_files(from: &Path, to: &Path, exclude_j2_files: bool) -> Result<(), Error> {
@@ -66,6 +68,89 @@ where
         .ok_or_else(|| Error::from(ErrorKind::NotFound))
 }
 
+const RUN_DIRECTORY_PREFIX: &str = "run-";
+/// File of a run directory holding the pid of the engine process the run belongs to.
+const RUN_DIRECTORY_PID_FILE: &str = ".run-pid";
+/// Run directories are only cleaned up after this long, no deployment lasts that long.
+pub const STALE_RUN_DIRECTORY_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Create a directory dedicated to a single run inside `workspace_dir`.
+/// Concurrent runs on the same workspace (ex: a retry overlapping a still running apply) can't share generated
+/// templates and terraform files this way.
+pub fn run_workspace_directory<P>(workspace_dir: P) -> Result<String, std::io::Error>
+where
+    P: AsRef<Path>,
+{
+    let run_id = format!(
+        "{}{}-{:08x}",
+        RUN_DIRECTORY_PREFIX,
+        chrono::Utc::now().timestamp_millis(),
+        rand::random::<u32>()
+    );
+    let dir = workspace_dir.as_ref().join(run_id);
+
+    create_dir_all(&dir)?;
+    fs::write(dir.join(RUN_DIRECTORY_PID_FILE), std::process::id().to_string())?;
+
+    dir.to_str()
+        .map(|e| e.to_string())
+        .ok_or_else(|| Error::from(ErrorKind::NotFound))
+}
+
+/// Remove run directories of `workspace_dir` left by previous runs, `current_run_dir` is kept.
+/// Other executions can share the workspace, only runs older than `STALE_RUN_DIRECTORY_TTL` are removed.
+pub fn cleanup_stale_run_directories<P, S>(workspace_dir: P, current_run_dir: S) -> Result<(), std::io::Error>
+where
+    P: AsRef<Path>,
+    S: AsRef<Path>,
+{
+    cleanup_run_directories_older_than(workspace_dir, current_run_dir, STALE_RUN_DIRECTORY_TTL)
+}
+
+fn cleanup_run_directories_older_than<P, S>(
+    workspace_dir: P,
+    current_run_dir: S,
+    ttl: Duration,
+) -> Result<(), std::io::Error>
+where
+    P: AsRef<Path>,
+    S: AsRef<Path>,
+{
+    for entry in fs::read_dir(workspace_dir.as_ref())? {
+        let path = entry?.path();
+        let is_run_dir = path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .map(|name| name.starts_with(RUN_DIRECTORY_PREFIX))
+            .unwrap_or(false);
+
+        if is_run_dir && path.is_dir() && path != current_run_dir.as_ref() && is_stale_run_directory(&path, ttl) {
+            fs::remove_dir_all(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A run is stale once untouched for `ttl`, unless another engine process it belongs to is still alive.
+fn is_stale_run_directory(run_dir: &Path, ttl: Duration) -> bool {
+    let is_expired = fs::metadata(run_dir)
+        .and_then(|metadata| metadata.modified())
+        .map(|modified| modified.elapsed().map(|elapsed| elapsed >= ttl).unwrap_or(false))
+        .unwrap_or(false);
+
+    let owner_pid = fs::read_to_string(run_dir.join(RUN_DIRECTORY_PID_FILE))
+        .ok()
+        .and_then(|pid| pid.trim().parse::<u32>().ok());
+    // runs of this process older than the ttl are stale, no need to check it's alive
+    let is_owner_alive = match owner_pid {
+        Some(pid) if pid != std::process::id() => sysinfo::System::new().refresh_process(pid as Pid),
+        _ => false,
+    };
+
+    is_expired && !is_owner_alive
+}
+
 fn archive_workspace_directory(working_root_dir: &str, execution_id: &str) -> Result<String, std::io::Error> {
     let workspace_dir = crate::fs::root_workspace_directory(working_root_dir, execution_id)?;
     let tgz_file_path = format!("{}/.qovery-workspace/{}.tgz", working_root_dir, execution_id);
@@ -163,6 +248,38 @@ mod tests {
     use std::io::BufReader;
     use tempdir::TempDir;
 
+    #[test]
+    fn test_run_workspace_directory() {
+        // setup:
+        let tmp_dir = TempDir::new("run_workspace_directory").expect("error creating temporary dir");
+        let workspace_dir = tmp_dir.path().join("databases/my-db");
+        let other_dir = workspace_dir.join("templates-validation");
+        fs::create_dir_all(&other_dir).expect("error creating directory");
+
+        // execute:
+        let previous_run_dir = run_workspace_directory(&workspace_dir).expect("error creating run directory");
+        let concurrent_run_dir = run_workspace_directory(&workspace_dir).expect("error creating run directory");
+        let current_run_dir = run_workspace_directory(&workspace_dir).expect("error creating run directory");
+        // the concurrent run belongs to another engine process which is still running
+        fs::write(Path::new(concurrent_run_dir.as_str()).join(RUN_DIRECTORY_PID_FILE), "1")
+            .expect("error writing pid file");
+        let recent_cleanup_result = cleanup_stale_run_directories(&workspace_dir, &current_run_dir);
+        let previous_run_is_kept = Path::new(previous_run_dir.as_str()).exists();
+        let expired_cleanup_result =
+            cleanup_run_directories_older_than(&workspace_dir, &current_run_dir, Duration::from_secs(0));
+
+        // verify:
+        assert_ne!(previous_run_dir, current_run_dir);
+        assert!(current_run_dir.starts_with(workspace_dir.to_str().unwrap()));
+        assert!(recent_cleanup_result.is_ok());
+        assert!(previous_run_is_kept);
+        assert!(expired_cleanup_result.is_ok());
+        assert!(!Path::new(previous_run_dir.as_str()).exists());
+        assert!(Path::new(concurrent_run_dir.as_str()).exists());
+        assert!(Path::new(current_run_dir.as_str()).exists());
+        assert!(other_dir.exists());
+    }
+
     #[test]
     fn test_archive_workspace_directory() {
         // setup:
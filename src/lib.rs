@@ -18,8 +18,10 @@ pub mod git;
 pub mod models;
 pub mod object_storage;
 pub mod runtime;
+pub mod secrets;
 pub mod session;
 mod string;
+pub mod telemetry;
 mod template;
 pub mod transaction;
 mod unit_conversion;
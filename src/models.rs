@@ -1,22 +1,36 @@
 use std::hash::{Hash, Hasher};
 
 use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
 use rand::distributions::Alphanumeric;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
 
-use crate::build_platform::{Build, BuildOptions, GitRepository, Image};
+use crate::build_platform::{
+    build_timeout_in_seconds, Build, BuildOptions, GitRepository, Image, DEFAULT_BUILD_TIMEOUT_IN_SECONDS,
+};
 use crate::cloud_provider::aws::databases::mongodb::MongoDB;
 use crate::cloud_provider::aws::databases::mysql::MySQL;
 use crate::cloud_provider::aws::databases::postgresql::PostgreSQL;
 use crate::cloud_provider::aws::databases::redis::Redis;
-use crate::cloud_provider::service::{DatabaseOptions, StatefulService, StatelessService};
+use crate::cloud_provider::service::{
+    DatabaseCloneSource, DatabaseOptions, DatabaseTlsCertificates, NodePoolLabel, StatefulService, StatelessService,
+};
+use crate::cloud_provider::utilities::get_version_number;
 use crate::cloud_provider::CloudProvider;
 use crate::cloud_provider::Kind as CPKind;
+use crate::container_registry::utilities::rewrite_image_reference;
+use crate::error::{EngineError, EngineErrorCause, EngineErrorScope, StringError};
 use crate::git::Credentials;
+use crate::secrets::Secrets;
+use crate::telemetry::Telemetry;
 use itertools::Itertools;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
 
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
 pub enum EnvironmentAction {
@@ -48,6 +62,207 @@ impl Environment {
         Ok(())
     }
 
+    /// Checks the consistency of the environment before deploying it, all violations are returned at once.
+    pub fn validate(&self) -> Result<(), Vec<EngineError>> {
+        let mut errors = vec![];
+        let mut applications_without_private_port = vec![];
+
+        for router in &self.routers {
+            let router_error = |cause, message: String| {
+                EngineError::new(
+                    cause,
+                    EngineErrorScope::Router(router.id.clone(), router.name.clone()),
+                    self.execution_id.as_str(),
+                    Some(message),
+                )
+            };
+
+            let is_single_root_route = router.routes.len() == 1 && router.routes[0].path == "/";
+            if router.protocol == RouterProtocol::Tcp && !is_single_root_route {
+                errors.push(router_error(
+                    EngineErrorCause::User("Use a single `/` route on TCP routers"),
+                    format!(
+                        "TCP router {} forwards its port {} to a single application, it can't have path routes",
+                        router.name, router.public_port
+                    ),
+                ));
+            }
+
+            let mut paths = vec![];
+            for route in &router.routes {
+                if paths.contains(&route.path.as_str()) {
+                    errors.push(router_error(
+                        EngineErrorCause::User("Give a distinct path to each route of the router"),
+                        format!(
+                            "path {} is used by several routes of router {}",
+                            route.path, router.name
+                        ),
+                    ));
+                }
+                paths.push(route.path.as_str());
+
+                match self.applications.iter().find(|app| app.name == route.application_name) {
+                    Some(application) if application.private_port.is_none() => {
+                        if !applications_without_private_port.contains(&application.id) {
+                            applications_without_private_port.push(application.id.clone());
+                            errors.push(EngineError::new(
+                                EngineErrorCause::User("Set a private port on the applications exposed by a router"),
+                                EngineErrorScope::Application(application.id.clone(), application.name.clone()),
+                                self.execution_id.as_str(),
+                                Some(format!(
+                                    "application {} is exposed by router {} but has no private port",
+                                    application.name, router.name
+                                )),
+                            ));
+                        }
+                    }
+                    Some(application) if application.namespace != router.namespace => errors.push(router_error(
+                        EngineErrorCause::User("Deploy the router in the namespace of the applications it exposes"),
+                        format!(
+                            "route {} of router {} targets application {} deployed in another namespace",
+                            route.path, router.name, application.name
+                        ),
+                    )),
+                    Some(_) => {}
+                    None => errors.push(router_error(
+                        EngineErrorCause::User("Make sure the router routes target existing applications"),
+                        format!(
+                            "route {} of router {} targets an unknown application {}",
+                            route.path, router.name, route.application_name
+                        ),
+                    )),
+                }
+            }
+
+            for custom_domain in &router.custom_domains {
+                if !is_valid_domain(custom_domain.domain.as_str()) {
+                    errors.push(router_error(
+                        EngineErrorCause::User("Check the custom domains of the router"),
+                        format!(
+                            "custom domain {} of router {} is not a valid domain name",
+                            custom_domain.domain, router.name
+                        ),
+                    ));
+                }
+            }
+        }
+
+        for application in &self.applications {
+            if let Some(autoscaling) = &application.autoscaling {
+                if let Err(message) = autoscaling.validate(application.total_instances) {
+                    errors.push(EngineError::new(
+                        EngineErrorCause::User("Check the autoscaling of the application"),
+                        EngineErrorScope::Application(application.id.clone(), application.name.clone()),
+                        self.execution_id.as_str(),
+                        Some(format!(
+                            "autoscaling of application {} is invalid: {}",
+                            application.name, message
+                        )),
+                    ));
+                }
+            }
+
+            if let Some(probes) = &application.probes {
+                if let Err(message) = probes.validate(application.private_port) {
+                    errors.push(EngineError::new(
+                        EngineErrorCause::User("Check the probes of the application"),
+                        EngineErrorScope::Application(application.id.clone(), application.name.clone()),
+                        self.execution_id.as_str(),
+                        Some(format!(
+                            "probes of application {} are invalid: {}",
+                            application.name, message
+                        )),
+                    ));
+                }
+            }
+        }
+
+        for database in &self.databases {
+            if !is_valid_database_version(database.version.as_str()) {
+                errors.push(EngineError::new(
+                    EngineErrorCause::User("Check the version of the database"),
+                    EngineErrorScope::Database(
+                        database.id.clone(),
+                        format!("{:?}", database.kind),
+                        database.name.clone(),
+                    ),
+                    self.execution_id.as_str(),
+                    Some(format!(
+                        "version {} of database {} can't be parsed",
+                        database.version, database.name
+                    )),
+                ));
+            }
+
+            if database.clone_from.is_some() && self.clone_from_environment_id.is_none() {
+                errors.push(EngineError::new(
+                    EngineErrorCause::User("Set the environment the databases are cloned from"),
+                    EngineErrorScope::Database(
+                        database.id.clone(),
+                        format!("{:?}", database.kind),
+                        database.name.clone(),
+                    ),
+                    self.execution_id.as_str(),
+                    Some(format!(
+                        "database {} is cloned but the environment has no environment to clone from",
+                        database.name
+                    )),
+                ));
+            }
+        }
+
+        let services_namespaces = self
+            .applications
+            .iter()
+            .map(|a| {
+                let scope = EngineErrorScope::Application(a.id.clone(), a.name.clone());
+                (scope, a.name.as_str(), a.namespace.as_deref())
+            })
+            .chain(self.routers.iter().map(|r| {
+                let scope = EngineErrorScope::Router(r.id.clone(), r.name.clone());
+                (scope, r.name.as_str(), r.namespace.as_deref())
+            }))
+            .chain(self.databases.iter().map(|d| {
+                let scope = EngineErrorScope::Database(d.id.clone(), format!("{:?}", d.kind), d.name.clone());
+                (scope, d.name.as_str(), d.namespace.as_deref())
+            }))
+            .chain(self.external_services.iter().map(|e| {
+                let scope = EngineErrorScope::ExternalService(e.id.clone(), e.name.clone());
+                (scope, e.name.as_str(), e.namespace.as_deref())
+            }));
+
+        for (scope, name, namespace) in services_namespaces {
+            if let Some(namespace) = namespace {
+                if !is_valid_namespace(namespace) {
+                    errors.push(EngineError::new(
+                        EngineErrorCause::User("Check the namespace of the service"),
+                        scope,
+                        self.execution_id.as_str(),
+                        Some(format!(
+                            "namespace {} of {} is not a valid DNS-1123 label: at most 63 lowercase alphanumeric characters or `-`, starting and ending with an alphanumeric character",
+                            namespace, name
+                        )),
+                    ));
+                } else if is_reserved_namespace(namespace) {
+                    errors.push(EngineError::new(
+                        EngineErrorCause::User("Check the namespace of the service"),
+                        scope,
+                        self.execution_id.as_str(),
+                        Some(format!(
+                            "namespace {} of {} is reserved to the cluster, choose a namespace dedicated to the service",
+                            namespace, name
+                        )),
+                    ));
+                }
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+
     pub fn to_qe_environment(
         &self,
         context: &Context,
@@ -94,7 +309,7 @@ impl Environment {
         let databases = self
             .databases
             .iter()
-            .map(|x| x.to_stateful_service(context, cloud_provider))
+            .map(|x| x.to_stateful_service(context, cloud_provider, self.clone_from_environment_id.as_deref()))
             .filter(|x| x.is_some())
             .map(|x| x.unwrap())
             .collect::<Vec<_>>();
@@ -165,6 +380,30 @@ pub struct Application {
     pub total_ram_in_mib: u32,
     pub total_instances: u16,
     pub start_timeout_in_seconds: u32,
+    #[serde(default)]
+    pub build_timeout_in_seconds: Option<u32>,
+    #[serde(default)]
+    pub build_args: BTreeMap<String, String>,
+    #[serde(default)]
+    pub dockerfile_target: Option<String>,
+    /// Rebuild the image even if one already exists for this commit.
+    #[serde(default)]
+    pub force_build: bool,
+    /// Chart values overriding the rendered ones, keyed by their dotted path like `podAnnotations.team`.
+    #[serde(default)]
+    pub helm_value_overrides: BTreeMap<String, String>,
+    /// Run in order before the application starts, the application only starts if they all succeed.
+    #[serde(default)]
+    pub init_containers: Vec<InitContainer>,
+    /// Scale the application on its CPU usage, `total_instances` is then the initial instances count.
+    #[serde(default)]
+    pub autoscaling: Option<Autoscaling>,
+    /// HTTP paths gating the readiness and the liveness of the application, TCP probes are used without them.
+    #[serde(default)]
+    pub probes: Option<Probes>,
+    /// Kubernetes namespace of the service, the environment one when not set.
+    #[serde(default)]
+    pub namespace: Option<String>,
     pub storage: Vec<Storage>,
     pub environment_variables: Vec<EnvironmentVariable>,
 }
@@ -200,6 +439,11 @@ impl Application {
                 image.clone(),
                 self.storage.iter().map(|s| s.to_aws_storage()).collect::<Vec<_>>(),
                 environment_variables,
+                self.helm_value_overrides.clone(),
+                self.init_containers.iter().map(|ic| ic.to_init_container()).collect(),
+                self.autoscaling.as_ref().map(|a| a.to_autoscaling()),
+                self.probes.as_ref().map(|p| p.to_probes()),
+                self.namespace.clone(),
                 listeners,
             ))),
             CPKind::Do => Some(Box::new(
@@ -217,6 +461,11 @@ impl Application {
                     image.clone(),
                     self.storage.iter().map(|s| s.to_do_storage()).collect::<Vec<_>>(),
                     environment_variables,
+                    self.helm_value_overrides.clone(),
+                    self.init_containers.iter().map(|ic| ic.to_init_container()).collect(),
+                    self.autoscaling.as_ref().map(|a| a.to_autoscaling()),
+                    self.probes.as_ref().map(|p| p.to_probes()),
+                    self.namespace.clone(),
                     listeners,
                 ),
             )),
@@ -235,6 +484,11 @@ impl Application {
                     image.clone(),
                     self.storage.iter().map(|s| s.to_scw_storage()).collect::<Vec<_>>(),
                     environment_variables,
+                    self.helm_value_overrides.clone(),
+                    self.init_containers.iter().map(|ic| ic.to_init_container()).collect(),
+                    self.autoscaling.as_ref().map(|a| a.to_autoscaling()),
+                    self.probes.as_ref().map(|p| p.to_probes()),
+                    self.namespace.clone(),
                     listeners,
                 ),
             )),
@@ -271,6 +525,11 @@ impl Application {
                 image,
                 self.storage.iter().map(|s| s.to_aws_storage()).collect::<Vec<_>>(),
                 environment_variables,
+                self.helm_value_overrides.clone(),
+                self.init_containers.iter().map(|ic| ic.to_init_container()).collect(),
+                self.autoscaling.as_ref().map(|a| a.to_autoscaling()),
+                self.probes.as_ref().map(|p| p.to_probes()),
+                self.namespace.clone(),
                 listeners,
             ))),
             CPKind::Do => Some(Box::new(
@@ -288,6 +547,11 @@ impl Application {
                     image,
                     self.storage.iter().map(|s| s.to_do_storage()).collect::<Vec<_>>(),
                     environment_variables,
+                    self.helm_value_overrides.clone(),
+                    self.init_containers.iter().map(|ic| ic.to_init_container()).collect(),
+                    self.autoscaling.as_ref().map(|a| a.to_autoscaling()),
+                    self.probes.as_ref().map(|p| p.to_probes()),
+                    self.namespace.clone(),
                     listeners,
                 ),
             )),
@@ -306,6 +570,11 @@ impl Application {
                     image,
                     self.storage.iter().map(|s| s.to_scw_storage()).collect::<Vec<_>>(),
                     environment_variables,
+                    self.helm_value_overrides.clone(),
+                    self.init_containers.iter().map(|ic| ic.to_init_container()).collect(),
+                    self.autoscaling.as_ref().map(|a| a.to_autoscaling()),
+                    self.probes.as_ref().map(|p| p.to_probes()),
+                    self.namespace.clone(),
                     listeners,
                 ),
             )),
@@ -346,13 +615,19 @@ impl Application {
                 credentials: self.git_credentials.as_ref().map(|credentials| Credentials {
                     login: credentials.login.clone(),
                     password: credentials.access_token.clone(),
+                    expired_at: Some(credentials.expired_at),
                 }),
                 commit_id: self.commit_id.clone(),
+                branch: Some(self.branch.clone()),
                 dockerfile_path: self.dockerfile_path.clone(),
                 root_path: self.root_path.clone(),
             },
             image: self.to_image(),
             options: BuildOptions {
+                timeout_in_seconds: build_timeout_in_seconds(
+                    self.build_timeout_in_seconds,
+                    self.start_timeout_in_seconds,
+                ),
                 environment_variables: self
                     .environment_variables
                     .iter()
@@ -360,8 +635,12 @@ impl Application {
                     .map(|ev| crate::build_platform::EnvironmentVariable {
                         key: ev.key.clone(),
                         value: ev.value.clone(),
+                        is_secret: ev.is_secret,
                     })
                     .collect::<Vec<_>>(),
+                build_args: self.build_args.clone(),
+                dockerfile_target: self.dockerfile_target.clone(),
+                cache_from: None,
             },
         }
     }
@@ -371,6 +650,14 @@ impl Application {
 pub struct EnvironmentVariable {
     pub key: String,
     pub value: String,
+    /// Secrets are mounted from a Kubernetes Secret and never shown in progress messages.
+    /// Variables not flagged are kept secret, only the ones explicitly flagged otherwise go to the ConfigMap.
+    #[serde(default = "default_is_secret_value")]
+    pub is_secret: bool,
+}
+
+fn default_is_secret_value() -> bool {
+    true
 }
 
 impl EnvironmentVariable {
@@ -378,6 +665,7 @@ impl EnvironmentVariable {
         crate::cloud_provider::models::EnvironmentVariable {
             key: self.key.clone(),
             value: self.value.clone(),
+            is_secret: self.is_secret,
         }
     }
 }
@@ -399,6 +687,99 @@ pub struct Storage {
     pub snapshot_retention_in_days: u16,
 }
 
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
+pub struct InitContainer {
+    pub image: String,
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub environment_variables: BTreeMap<String, String>,
+}
+
+impl InitContainer {
+    pub fn to_init_container(&self) -> crate::cloud_provider::models::InitContainer {
+        crate::cloud_provider::models::InitContainer {
+            image: self.image.clone(),
+            command: self.command.clone(),
+            environment_variables: self.environment_variables.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
+pub struct Autoscaling {
+    pub min_instances: u16,
+    pub max_instances: u16,
+    pub target_cpu_percent: u8,
+}
+
+impl Autoscaling {
+    pub fn to_autoscaling(&self) -> crate::cloud_provider::models::Autoscaling {
+        crate::cloud_provider::models::Autoscaling {
+            min_instances: self.min_instances,
+            max_instances: self.max_instances,
+            target_cpu_percent: self.target_cpu_percent,
+        }
+    }
+
+    /// Checks the range contains the initial instances count and the CPU target is a percentage.
+    pub fn validate(&self, initial_instances: u16) -> Result<(), String> {
+        if self.min_instances > initial_instances || initial_instances > self.max_instances {
+            return Err(format!(
+                "{} instances is out of the autoscaling range [{}, {}]",
+                initial_instances, self.min_instances, self.max_instances
+            ));
+        }
+
+        if !(1..=100).contains(&self.target_cpu_percent) {
+            return Err(format!(
+                "target CPU of {}% is not between 1% and 100%",
+                self.target_cpu_percent
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
+pub struct Probes {
+    #[serde(default)]
+    pub readiness_path: Option<String>,
+    #[serde(default)]
+    pub liveness_path: Option<String>,
+    #[serde(default)]
+    pub port: Option<u16>,
+}
+
+impl Probes {
+    pub fn to_probes(&self) -> crate::cloud_provider::models::Probes {
+        crate::cloud_provider::models::Probes {
+            readiness_path: self.readiness_path.clone(),
+            liveness_path: self.liveness_path.clone(),
+            port: self.port,
+        }
+    }
+
+    /// Checks paths are absolute and there is a port to probe them on.
+    pub fn validate(&self, private_port: Option<u16>) -> Result<(), String> {
+        for (probe, path) in [("readiness", &self.readiness_path), ("liveness", &self.liveness_path)] {
+            if let Some(path) = path {
+                if !path.starts_with('/') {
+                    return Err(format!("{} path `{}` must start with `/`", probe, path));
+                }
+            }
+        }
+
+        let has_paths = self.readiness_path.is_some() || self.liveness_path.is_some();
+        if has_paths && self.port.or(private_port).is_none() {
+            return Err("HTTP probes need a port, set the probes port or the application private port".to_string());
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum StorageType {
@@ -461,6 +842,11 @@ pub struct Router {
     pub action: Action,
     pub default_domain: String,
     pub public_port: u16,
+    #[serde(default)]
+    pub protocol: RouterProtocol,
+    /// Kubernetes namespace of the service, the environment one when not set.
+    #[serde(default)]
+    pub namespace: Option<String>,
     pub custom_domains: Vec<CustomDomain>,
     pub routes: Vec<Route>,
 }
@@ -486,6 +872,7 @@ impl Router {
             .map(|x| crate::cloud_provider::models::Route {
                 path: x.path.clone(),
                 application_name: x.application_name.clone(),
+                rewrite_target: x.rewrite_target.clone(),
             })
             .collect::<Vec<_>>();
 
@@ -501,6 +888,9 @@ impl Router {
                     self.default_domain.as_str(),
                     custom_domains,
                     routes,
+                    self.protocol.to_router_protocol(),
+                    self.public_port,
+                    self.namespace.clone(),
                     listeners,
                 ));
                 Some(router)
@@ -515,6 +905,9 @@ impl Router {
                         self.default_domain.as_str(),
                         custom_domains,
                         routes,
+                        self.protocol.to_router_protocol(),
+                        self.public_port,
+                        self.namespace.clone(),
                         listeners,
                     ));
                 Some(router)
@@ -528,6 +921,9 @@ impl Router {
                     self.default_domain.as_str(),
                     custom_domains,
                     routes,
+                    self.protocol.to_router_protocol(),
+                    self.public_port,
+                    self.namespace.clone(),
                     listeners,
                 ));
                 Some(router)
@@ -536,16 +932,111 @@ impl Router {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Hash, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RouterProtocol {
+    Http,
+    Grpc,
+    Tcp,
+}
+
+impl Default for RouterProtocol {
+    fn default() -> Self {
+        RouterProtocol::Http
+    }
+}
+
+impl RouterProtocol {
+    pub fn to_router_protocol(&self) -> crate::cloud_provider::models::RouterProtocol {
+        match self {
+            RouterProtocol::Http => crate::cloud_provider::models::RouterProtocol::Http,
+            RouterProtocol::Grpc => crate::cloud_provider::models::RouterProtocol::Grpc,
+            RouterProtocol::Tcp => crate::cloud_provider::models::RouterProtocol::Tcp,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
 pub struct CustomDomain {
     pub domain: String,
     pub target_domain: String,
 }
 
+/// Domain names made of dot separated labels, the first one can be a wildcard.
+fn is_valid_domain(domain: &str) -> bool {
+    let labels = domain.split('.').collect::<Vec<_>>();
+
+    domain.len() <= 253
+        && labels.len() >= 2
+        && labels.iter().enumerate().all(|(index, label)| {
+            (index == 0 && *label == "*")
+                || (!label.is_empty()
+                    && label.len() <= 63
+                    && !label.starts_with('-')
+                    && !label.ends_with('-')
+                    && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+        })
+}
+
+/// Kubernetes namespaces are DNS-1123 labels.
+fn is_valid_namespace(namespace: &str) -> bool {
+    let is_alphanumeric = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit();
+
+    !namespace.is_empty()
+        && namespace.len() <= 63
+        && namespace.starts_with(is_alphanumeric)
+        && namespace.ends_with(is_alphanumeric)
+        && namespace.chars().all(|c| is_alphanumeric(c) || c == '-')
+}
+
+/// Namespaces of the cluster itself and of the charts installed by the engine.
+const RESERVED_NAMESPACES: [&str; 6] = [
+    "default",
+    "prometheus",
+    "logging",
+    "cert-manager",
+    "nginx-ingress",
+    "qovery",
+];
+
+fn is_reserved_namespace(namespace: &str) -> bool {
+    namespace.starts_with("kube-") || RESERVED_NAMESPACES.contains(&namespace)
+}
+
+/// Versions like "13", "5.7" or "4.4.1".
+fn is_valid_database_version(version: &str) -> bool {
+    let is_number = |n: &str| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit());
+
+    match get_version_number(version) {
+        Ok(v) => {
+            is_number(v.major.as_str())
+                && v.minor.as_deref().map_or(true, is_number)
+                && v.patch.as_deref().map_or(true, is_number)
+        }
+        Err(_) => false,
+    }
+}
+
+/// Database a cloned database is seeded from, in the environment the clone comes from.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
+pub struct CloneSourceDatabase {
+    pub id: String,
+    pub fqdn_id: String,
+}
+
+/// Node label of the pool a self-hosted database is pinned to.
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
+pub struct DatabaseNodePoolLabel {
+    pub key: String,
+    pub value: String,
+}
+
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
 pub struct Route {
     pub path: String,
     pub application_name: String,
+    #[serde(default)]
+    pub rewrite_target: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
@@ -565,6 +1056,44 @@ pub struct Database {
     pub disk_size_in_gib: u32,
     pub database_instance_type: String,
     pub database_disk_type: String,
+    #[serde(default)]
+    pub cluster_mode_enabled: bool,
+    #[serde(default)]
+    pub replicas: u8,
+    #[serde(default)]
+    pub iops: Option<u32>,
+    #[serde(default)]
+    pub storage_throughput: Option<u32>,
+    #[serde(default)]
+    pub deletion_protection: bool,
+    #[serde(default)]
+    pub sentinel_enabled: bool,
+    #[serde(default)]
+    pub resources_overcommit_percent: Option<u32>,
+    #[serde(default)]
+    pub parameters: BTreeMap<String, String>,
+    #[serde(default)]
+    pub require_tls: bool,
+    #[serde(default)]
+    pub tls_certificates: Option<DatabaseTlsCertificates>,
+    #[serde(default)]
+    pub deletion_timeout_in_seconds: Option<u32>,
+    #[serde(default)]
+    pub helm_value_overrides: BTreeMap<String, String>,
+    #[serde(default)]
+    pub deploy_timeout_in_seconds: Option<u32>,
+    /// Kubernetes namespace of the service, the environment one when not set.
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// Database of the `clone_from_environment_id` environment this one is seeded from on its first deployment.
+    #[serde(default)]
+    pub clone_from: Option<CloneSourceDatabase>,
+    #[serde(default)]
+    pub backup_retention_days: Option<u32>,
+    #[serde(default)]
+    pub backup_window: Option<String>,
+    #[serde(default)]
+    pub node_pool_label: Option<DatabaseNodePoolLabel>,
 }
 
 impl Database {
@@ -572,6 +1101,7 @@ impl Database {
         &self,
         context: &Context,
         cloud_provider: &dyn CloudProvider,
+        clone_from_environment_id: Option<&str>,
     ) -> Option<Box<dyn StatefulService>> {
         let database_options = DatabaseOptions {
             login: self.username.clone(),
@@ -580,6 +1110,33 @@ impl Database {
             port: self.port,
             disk_size_in_gib: self.disk_size_in_gib,
             database_disk_type: self.database_disk_type.clone(),
+            replicas: self.replicas,
+            iops: self.iops,
+            storage_throughput: self.storage_throughput,
+            deletion_protection: self.deletion_protection,
+            sentinel_enabled: self.sentinel_enabled,
+            resources_overcommit_percent: self.resources_overcommit_percent,
+            parameters: self.parameters.clone(),
+            require_tls: self.require_tls,
+            tls_certificates: self.tls_certificates.clone(),
+            deletion_timeout_in_seconds: self.deletion_timeout_in_seconds,
+            helm_value_overrides: self.helm_value_overrides.clone(),
+            deploy_timeout_in_seconds: self.deploy_timeout_in_seconds,
+            namespace: self.namespace.clone(),
+            clone_from: match (clone_from_environment_id, &self.clone_from) {
+                (Some(environment_id), Some(database)) => Some(DatabaseCloneSource {
+                    environment_id: environment_id.to_string(),
+                    database_id: database.id.clone(),
+                    fqdn_id: database.fqdn_id.clone(),
+                }),
+                _ => None,
+            },
+            backup_retention_days: self.backup_retention_days,
+            backup_window: self.backup_window.clone(),
+            node_pool_label: self.node_pool_label.as_ref().map(|label| NodePoolLabel {
+                key: label.key.clone(),
+                value: label.value.clone(),
+            }),
         };
 
         let listeners = cloud_provider.listeners().clone();
@@ -653,6 +1210,7 @@ impl Database {
                         self.total_ram_in_mib,
                         self.database_instance_type.as_str(),
                         database_options,
+                        self.cluster_mode_enabled,
                         listeners,
                     ));
 
@@ -843,6 +1401,9 @@ pub struct ExternalService {
     pub on_create_dockerfile_path: String,
     pub on_pause_dockerfile_path: String,
     pub on_delete_dockerfile_path: String,
+    /// Kubernetes namespace of the service, the environment one when not set.
+    #[serde(default)]
+    pub namespace: Option<String>,
     pub environment_variables: Vec<EnvironmentVariable>,
 }
 
@@ -873,6 +1434,7 @@ impl ExternalService {
                     self.total_ram_in_mib,
                     image.clone(),
                     environment_variables,
+                    self.namespace.clone(),
                     listeners,
                 ),
             )),
@@ -906,6 +1468,7 @@ impl ExternalService {
                     self.total_ram_in_mib,
                     image,
                     environment_variables,
+                    self.namespace.clone(),
                     listeners,
                 ),
             )),
@@ -933,8 +1496,10 @@ impl ExternalService {
                 credentials: self.git_credentials.as_ref().map(|credentials| Credentials {
                     login: credentials.login.clone(),
                     password: credentials.access_token.clone(),
+                    expired_at: Some(credentials.expired_at),
                 }),
                 commit_id: self.commit_id.clone(),
+                branch: Some(self.branch.clone()),
                 dockerfile_path: Some(match self.action {
                     Action::Create => self.on_create_dockerfile_path.clone(),
                     Action::Pause => self.on_pause_dockerfile_path.clone(),
@@ -945,6 +1510,7 @@ impl ExternalService {
             },
             image: self.to_image(),
             options: BuildOptions {
+                timeout_in_seconds: DEFAULT_BUILD_TIMEOUT_IN_SECONDS,
                 environment_variables: self
                     .environment_variables
                     .iter()
@@ -952,8 +1518,12 @@ impl ExternalService {
                     .map(|ev| crate::build_platform::EnvironmentVariable {
                         key: ev.key.clone(),
                         value: ev.value.clone(),
+                        is_secret: ev.is_secret,
                     })
                     .collect::<Vec<_>>(),
+                build_args: BTreeMap::new(),
+                dockerfile_target: None,
+                cache_from: None,
             },
         }
     }
@@ -963,13 +1533,43 @@ impl ExternalService {
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum EnvironmentError {}
 
+lazy_static! {
+    static ref PROGRESS_SEQUENCES: Mutex<HashMap<String, u64>> = Mutex::new(HashMap::new());
+}
+
+/// Returns the next progress sequence number of an execution, starting at 1.
+fn next_progress_sequence(execution_id: &str) -> u64 {
+    let mut sequences = match PROGRESS_SEQUENCES.lock() {
+        Ok(sequences) => sequences,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    let sequence = sequences.entry(execution_id.to_string()).or_insert(0);
+    *sequence += 1;
+    *sequence
+}
+
+/// Forgets the progress sequence of a finished execution, for the sequences not to pile up in a long running engine.
+pub fn end_progress_sequence(execution_id: &str) {
+    let mut sequences = match PROGRESS_SEQUENCES.lock() {
+        Ok(sequences) => sequences,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    sequences.remove(execution_id);
+}
+
 #[derive(Clone)]
 pub struct ProgressInfo {
     pub created_at: DateTime<Utc>,
+    /// Monotonically increasing per execution, a gap means an event has been lost.
+    pub sequence: u64,
     pub scope: ProgressScope,
     pub level: ProgressLevel,
     pub message: Option<String>,
     pub execution_id: String,
+    /// Structured data attached to the event, on top of the human readable message.
+    pub payload: Option<ProgressPayload>,
 }
 
 impl ProgressInfo {
@@ -979,14 +1579,37 @@ impl ProgressInfo {
         message: Option<T>,
         execution_id: X,
     ) -> Self {
+        let execution_id = execution_id.into();
+
         ProgressInfo {
             created_at: Utc::now(),
+            sequence: next_progress_sequence(&execution_id),
             scope,
             level,
             message: message.map(|msg| msg.into()),
-            execution_id: execution_id.into(),
+            execution_id,
+            payload: None,
         }
     }
+
+    pub fn with_payload(mut self, payload: ProgressPayload) -> Self {
+        self.payload = Some(payload);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ProgressPayload {
+    /// Sent once an environment is deployed, with what the UI needs to connect to its services.
+    DeploymentSummary { services: Vec<ServiceEndpoints> },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ServiceEndpoints {
+    pub scope: ProgressScope,
+    pub name: String,
+    pub endpoints: Vec<String>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
@@ -1031,60 +1654,199 @@ pub trait Listen {
 pub type Listener = Arc<Box<dyn ProgressListener>>;
 pub type Listeners = Vec<Listener>;
 
+type ProgressEvent = Box<dyn FnOnce(&dyn ProgressListener) + Send>;
+
+/// Non-blocking listener: events are queued and delivered to the wrapped listener by a drain thread,
+/// in the order they were emitted. Pending events are flushed when the listener is dropped.
+pub struct AsyncProgressListener {
+    sender: Mutex<Option<Sender<ProgressEvent>>>,
+    drain_thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl AsyncProgressListener {
+    pub fn new(listener: Box<dyn ProgressListener>) -> Self {
+        let (sender, receiver) = mpsc::channel::<ProgressEvent>();
+        let drain_thread = thread::spawn(move || {
+            for event in receiver {
+                event(listener.as_ref());
+            }
+        });
+
+        AsyncProgressListener {
+            sender: Mutex::new(Some(sender)),
+            drain_thread: Mutex::new(Some(drain_thread)),
+        }
+    }
+
+    pub fn new_listener(listener: Box<dyn ProgressListener>) -> Listener {
+        Arc::new(Box::new(AsyncProgressListener::new(listener)))
+    }
+
+    fn send(&self, event: ProgressEvent) {
+        if let Ok(sender) = self.sender.lock() {
+            if let Some(sender) = sender.as_ref() {
+                let _ = sender.send(event);
+            }
+        }
+    }
+
+    /// Blocks until all queued events are delivered, events emitted afterwards are dropped.
+    pub fn flush(&self) {
+        if let Ok(mut sender) = self.sender.lock() {
+            sender.take();
+        }
+
+        if let Ok(mut drain_thread) = self.drain_thread.lock() {
+            if let Some(drain_thread) = drain_thread.take() {
+                if drain_thread.join().is_err() {
+                    error!("progress listener drain thread panicked, some events may not have been delivered");
+                }
+            }
+        }
+    }
+}
+
+impl Drop for AsyncProgressListener {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+impl ProgressListener for AsyncProgressListener {
+    fn deployment_in_progress(&self, info: ProgressInfo) {
+        self.send(Box::new(move |l| l.deployment_in_progress(info)));
+    }
+
+    fn pause_in_progress(&self, info: ProgressInfo) {
+        self.send(Box::new(move |l| l.pause_in_progress(info)));
+    }
+
+    fn delete_in_progress(&self, info: ProgressInfo) {
+        self.send(Box::new(move |l| l.delete_in_progress(info)));
+    }
+
+    fn error(&self, info: ProgressInfo) {
+        self.send(Box::new(move |l| l.error(info)));
+    }
+
+    fn deployed(&self, info: ProgressInfo) {
+        self.send(Box::new(move |l| l.deployed(info)));
+    }
+
+    fn paused(&self, info: ProgressInfo) {
+        self.send(Box::new(move |l| l.paused(info)));
+    }
+
+    fn deleted(&self, info: ProgressInfo) {
+        self.send(Box::new(move |l| l.deleted(info)));
+    }
+
+    fn deployment_error(&self, info: ProgressInfo) {
+        self.send(Box::new(move |l| l.deployment_error(info)));
+    }
+
+    fn pause_error(&self, info: ProgressInfo) {
+        self.send(Box::new(move |l| l.pause_error(info)));
+    }
+
+    fn delete_error(&self, info: ProgressInfo) {
+        self.send(Box::new(move |l| l.delete_error(info)));
+    }
+}
+
+const REDACTED_SECRET: &str = "***";
+
 pub struct ListenersHelper<'a> {
     listeners: &'a Listeners,
+    secrets: Vec<String>,
 }
 
 impl<'a> ListenersHelper<'a> {
     pub fn new(listeners: &'a Listeners) -> Self {
-        ListenersHelper { listeners }
+        ListenersHelper {
+            listeners,
+            secrets: vec![],
+        }
+    }
+
+    /// Secret values are redacted from every message sent to the listeners.
+    pub fn with_secrets(mut self, secrets: Vec<String>) -> Self {
+        self.secrets = secrets.into_iter().filter(|secret| !secret.is_empty()).collect();
+        self
+    }
+
+    fn redact(&self, mut info: ProgressInfo) -> ProgressInfo {
+        if let Some(message) = info.message.as_mut() {
+            for secret in &self.secrets {
+                if message.contains(secret.as_str()) {
+                    *message = message.replace(secret.as_str(), REDACTED_SECRET);
+                }
+            }
+        }
+
+        info
+    }
+
+    pub fn listeners(&self) -> &'a Listeners {
+        self.listeners
     }
 
     pub fn deployment_in_progress(&self, info: ProgressInfo) {
+        let info = self.redact(info);
         self.listeners
             .iter()
             .for_each(|l| l.deployment_in_progress(info.clone()));
     }
 
     pub fn upgrade_in_progress(&self, info: ProgressInfo) {
+        let info = self.redact(info);
         self.listeners
             .iter()
             .for_each(|l| l.deployment_in_progress(info.clone()));
     }
 
     pub fn pause_in_progress(&self, info: ProgressInfo) {
+        let info = self.redact(info);
         self.listeners.iter().for_each(|l| l.pause_in_progress(info.clone()));
     }
 
     pub fn delete_in_progress(&self, info: ProgressInfo) {
+        let info = self.redact(info);
         self.listeners.iter().for_each(|l| l.delete_in_progress(info.clone()));
     }
 
     pub fn error(&self, info: ProgressInfo) {
+        let info = self.redact(info);
         self.listeners.iter().for_each(|l| l.error(info.clone()));
     }
 
     pub fn deployed(&self, info: ProgressInfo) {
+        let info = self.redact(info);
         self.listeners.iter().for_each(|l| l.deployed(info.clone()));
     }
 
     pub fn paused(&self, info: ProgressInfo) {
+        let info = self.redact(info);
         self.listeners.iter().for_each(|l| l.paused(info.clone()));
     }
 
     pub fn deleted(&self, info: ProgressInfo) {
+        let info = self.redact(info);
         self.listeners.iter().for_each(|l| l.deleted(info.clone()));
     }
 
     pub fn deployment_error(&self, info: ProgressInfo) {
+        let info = self.redact(info);
         self.listeners.iter().for_each(|l| l.deployment_error(info.clone()));
     }
 
     pub fn pause_error(&self, info: ProgressInfo) {
+        let info = self.redact(info);
         self.listeners.iter().for_each(|l| l.pause_error(info.clone()));
     }
 
     pub fn delete_error(&self, info: ProgressInfo) {
+        let info = self.redact(info);
         self.listeners.iter().for_each(|l| l.delete_error(info.clone()));
     }
 }
@@ -1098,6 +1860,49 @@ pub struct Context {
     docker_host: Option<String>,
     features: Vec<Features>,
     metadata: Option<Metadata>,
+    cancellation_token: CancellationToken,
+    max_concurrent_image_pushes: usize,
+    telemetry: Option<Telemetry>,
+    image_registry_rewrites: BTreeMap<String, String>,
+    extra_supported_database_versions: BTreeMap<String, BTreeMap<String, String>>,
+    secrets: Option<Secrets>,
+}
+
+/// Images of an environment pushed at the same time when the context doesn't set it.
+pub const DEFAULT_MAX_CONCURRENT_IMAGE_PUSHES: usize = 4;
+
+/// Set when the deployment is aborted upstream, long running operations stop as soon as they see it.
+/// Clones share the same flag.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+// the token is not part of the context identity
+impl PartialEq for CancellationToken {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for CancellationToken {}
+
+impl Hash for CancellationToken {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, Eq, PartialEq)]
@@ -1145,9 +1950,92 @@ impl Context {
             docker_host,
             features,
             metadata,
+            cancellation_token: CancellationToken::default(),
+            max_concurrent_image_pushes: DEFAULT_MAX_CONCURRENT_IMAGE_PUSHES,
+            telemetry: None,
+            image_registry_rewrites: BTreeMap::new(),
+            extra_supported_database_versions: BTreeMap::new(),
+            secrets: None,
         }
     }
 
+    /// Share a token with the caller, so it can abort the operations using this context.
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = cancellation_token;
+        self
+    }
+
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancellation_token
+    }
+
+    /// Pushes to distinct repositories are independent, so they are run side by side up to this limit.
+    pub fn with_max_concurrent_image_pushes(mut self, max_concurrent_image_pushes: usize) -> Self {
+        self.max_concurrent_image_pushes = max_concurrent_image_pushes.max(1);
+        self
+    }
+
+    pub fn max_concurrent_image_pushes(&self) -> usize {
+        self.max_concurrent_image_pushes
+    }
+
+    /// Record traces and metrics of the engine operations, see `crate::telemetry`.
+    pub fn with_telemetry(mut self, telemetry: Telemetry) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    pub fn telemetry(&self) -> Option<&Telemetry> {
+        self.telemetry.as_ref()
+    }
+
+    /// Registry prefixes replaced in every image reference, ex: `docker.io -> registry.internal/dockerhub`
+    /// to pull images from an internal mirror in air-gapped installs.
+    pub fn with_image_registry_rewrites(mut self, image_registry_rewrites: BTreeMap<String, String>) -> Self {
+        self.image_registry_rewrites = image_registry_rewrites;
+        self
+    }
+
+    pub fn image_registry_rewrites(&self) -> &BTreeMap<String, String> {
+        &self.image_registry_rewrites
+    }
+
+    /// Supported versions merged over the compiled table of a database, by the name of the table (ex: `RDS MySQL`),
+    /// for the versions released after this build to be deployable (ex: a table loaded from the API at startup).
+    pub fn with_extra_supported_database_versions(
+        mut self,
+        extra_supported_database_versions: BTreeMap<String, BTreeMap<String, String>>,
+    ) -> Self {
+        self.extra_supported_database_versions = extra_supported_database_versions;
+        self
+    }
+
+    pub fn extra_supported_database_versions(&self, database_name: &str) -> HashMap<String, String> {
+        self.extra_supported_database_versions
+            .get(database_name)
+            .map(|versions| versions.clone().into_iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// Resolve the secret references of the services (`vault://`, `awssm://`) at deploy time, see `crate::secrets`.
+    pub fn with_secrets(mut self, secrets: Secrets) -> Self {
+        self.secrets = Some(secrets);
+        self
+    }
+
+    pub fn secrets(&self) -> Option<&Secrets> {
+        self.secrets.as_ref()
+    }
+
+    /// The image reference to build, pull or push, see `rewrite_image_reference`.
+    pub fn rewrite_image(&self, image: &str) -> String {
+        rewrite_image_reference(&self.image_registry_rewrites, image)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation_token.is_cancelled()
+    }
+
     pub fn execution_id(&self) -> &str {
         self.execution_id.as_str()
     }
@@ -1189,6 +2077,20 @@ impl Context {
         }
     }
 
+    pub fn rollback_partial_deploy(&self) -> bool {
+        match &self.metadata {
+            Some(meta) => matches!(meta.rollback_partial_deploy, Some(true)),
+            _ => false,
+        }
+    }
+
+    pub fn clean_orphaned_helm_releases(&self) -> bool {
+        match &self.metadata {
+            Some(meta) => matches!(meta.clean_orphaned_helm_releases, Some(true)),
+            _ => false,
+        }
+    }
+
     pub fn is_test_cluster(&self) -> bool {
         self.test_cluster
     }
@@ -1221,6 +2123,177 @@ impl Context {
     }
 }
 
+/// Builds a `Context` whose settings are checked upfront, rather than failing later on a missing file.
+pub struct ContextBuilder {
+    execution_id: String,
+    workspace_root_dir: String,
+    lib_root_dir: String,
+    test_cluster: bool,
+    docker_host: Option<String>,
+    features: Vec<Features>,
+    metadata: Option<Metadata>,
+    cancellation_token: CancellationToken,
+    max_concurrent_image_pushes: usize,
+    telemetry: Option<Telemetry>,
+    image_registry_rewrites: BTreeMap<String, String>,
+    extra_supported_database_versions: BTreeMap<String, BTreeMap<String, String>>,
+    secrets: Option<Secrets>,
+}
+
+impl ContextBuilder {
+    pub fn new(execution_id: &str, workspace_root_dir: &str, lib_root_dir: &str) -> Self {
+        ContextBuilder {
+            execution_id: execution_id.to_string(),
+            workspace_root_dir: workspace_root_dir.to_string(),
+            lib_root_dir: lib_root_dir.to_string(),
+            test_cluster: false,
+            docker_host: None,
+            features: vec![],
+            metadata: None,
+            cancellation_token: CancellationToken::default(),
+            max_concurrent_image_pushes: DEFAULT_MAX_CONCURRENT_IMAGE_PUSHES,
+            telemetry: None,
+            image_registry_rewrites: BTreeMap::new(),
+            extra_supported_database_versions: BTreeMap::new(),
+            secrets: None,
+        }
+    }
+
+    pub fn with_test_cluster(mut self, test_cluster: bool) -> Self {
+        self.test_cluster = test_cluster;
+        self
+    }
+
+    pub fn with_docker_host(mut self, docker_host: &str) -> Self {
+        self.docker_host = Some(docker_host.to_string());
+        self
+    }
+
+    pub fn with_features(mut self, features: Vec<Features>) -> Self {
+        self.features = features;
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = cancellation_token;
+        self
+    }
+
+    pub fn with_max_concurrent_image_pushes(mut self, max_concurrent_image_pushes: usize) -> Self {
+        self.max_concurrent_image_pushes = max_concurrent_image_pushes;
+        self
+    }
+
+    pub fn with_telemetry(mut self, telemetry: Telemetry) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    pub fn with_image_registry_rewrites(mut self, image_registry_rewrites: BTreeMap<String, String>) -> Self {
+        self.image_registry_rewrites = image_registry_rewrites;
+        self
+    }
+
+    pub fn with_extra_supported_database_versions(
+        mut self,
+        extra_supported_database_versions: BTreeMap<String, BTreeMap<String, String>>,
+    ) -> Self {
+        self.extra_supported_database_versions = extra_supported_database_versions;
+        self
+    }
+
+    pub fn with_secrets(mut self, secrets: Secrets) -> Self {
+        self.secrets = Some(secrets);
+        self
+    }
+
+    pub fn build(self) -> Result<Context, EngineError> {
+        if let Err(message) = self.validate() {
+            return Err(EngineError::new(
+                EngineErrorCause::Internal,
+                EngineErrorScope::Engine,
+                self.execution_id.as_str(),
+                Some(message),
+            ));
+        }
+
+        let context = Context::new(
+            self.execution_id,
+            self.workspace_root_dir,
+            self.lib_root_dir,
+            self.test_cluster,
+            self.docker_host,
+            self.features,
+            self.metadata,
+        )
+        .with_cancellation_token(self.cancellation_token)
+        .with_max_concurrent_image_pushes(self.max_concurrent_image_pushes)
+        .with_image_registry_rewrites(self.image_registry_rewrites)
+        .with_extra_supported_database_versions(self.extra_supported_database_versions);
+
+        let context = match self.telemetry {
+            Some(telemetry) => context.with_telemetry(telemetry),
+            None => context,
+        };
+
+        Ok(match self.secrets {
+            Some(secrets) => context.with_secrets(secrets),
+            None => context,
+        })
+    }
+
+    fn validate(&self) -> Result<(), StringError> {
+        if self.execution_id.trim().is_empty() {
+            return Err("execution id can't be empty".to_string());
+        }
+
+        if !std::path::Path::new(self.lib_root_dir.as_str()).is_dir() {
+            return Err(format!(
+                "lib root dir `{}` doesn't exist or isn't a directory",
+                self.lib_root_dir
+            ));
+        }
+
+        if self.max_concurrent_image_pushes == 0 {
+            return Err("at least one image must be pushed at a time".to_string());
+        }
+
+        if let Some((prefix, destination)) = self
+            .image_registry_rewrites
+            .iter()
+            .find(|(prefix, destination)| prefix.trim().is_empty() || destination.trim().is_empty())
+        {
+            return Err(format!(
+                "image registry rewrite `{} -> {}` must have a prefix and a destination",
+                prefix, destination
+            ));
+        }
+
+        if let Some(metadata) = &self.metadata {
+            // expired resources are deleted by pleco
+            if metadata.resource_expiration_in_seconds.is_some() && metadata.disable_pleco == Some(true) {
+                return Err("resources can't expire while pleco is disabled".to_string());
+            }
+
+            // nothing is deployed during a dry run, so there is nothing to revert
+            if metadata.dry_run_deploy == Some(true) && metadata.rollback_partial_deploy == Some(true) {
+                return Err("partial deployments can't be rolled back during a dry run".to_string());
+            }
+
+            if metadata.dry_run_deploy == Some(true) && metadata.clean_orphaned_helm_releases == Some(true) {
+                return Err("orphaned helm releases can't be uninstalled during a dry run".to_string());
+            }
+        }
+
+        Ok(())
+    }
+}
+
 /// put everything you want here that is required to change the behaviour of the request.
 /// E.g you can indicate that this request is a test, then you can adapt the behaviour as you want.
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
@@ -1230,6 +2303,10 @@ pub struct Metadata {
     pub docker_build_options: Option<String>,
     pub forced_upgrade: Option<bool>,
     pub disable_pleco: Option<bool>,
+    /// Revert the services already created when an environment deployment fails.
+    pub rollback_partial_deploy: Option<bool>,
+    /// Uninstall the helm releases of the environment namespace which belong to no service anymore.
+    pub clean_orphaned_helm_releases: Option<bool>,
 }
 
 impl Metadata {
@@ -1239,6 +2316,8 @@ impl Metadata {
         docker_build_options: Option<String>,
         forced_upgrade: Option<bool>,
         disable_pleco: Option<bool>,
+        rollback_partial_deploy: Option<bool>,
+        clean_orphaned_helm_releases: Option<bool>,
     ) -> Self {
         Metadata {
             dry_run_deploy,
@@ -1246,9 +2325,471 @@ impl Metadata {
             docker_build_options,
             forced_upgrade,
             disable_pleco,
+            rollback_partial_deploy,
+            clean_orphaned_helm_releases,
         }
     }
 }
 
 /// Represent a String path instead of passing a PathBuf struct
 pub type StringPath = String;
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{
+        end_progress_sequence, AsyncProgressListener, Autoscaling, CloneSourceDatabase, ContextBuilder, Environment,
+        EnvironmentVariable, Listeners, ListenersHelper, Metadata, Probes, ProgressInfo, ProgressLevel,
+        ProgressListener, ProgressPayload, ProgressScope, RouterProtocol, ServiceEndpoints,
+    };
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    struct SlowListener {
+        received: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl SlowListener {
+        fn record(&self, info: ProgressInfo) {
+            thread::sleep(Duration::from_millis(20));
+            self.received.lock().unwrap().push(info.message.unwrap_or_default());
+        }
+    }
+
+    impl ProgressListener for SlowListener {
+        fn deployment_in_progress(&self, info: ProgressInfo) {
+            self.record(info)
+        }
+        fn pause_in_progress(&self, info: ProgressInfo) {
+            self.record(info)
+        }
+        fn delete_in_progress(&self, info: ProgressInfo) {
+            self.record(info)
+        }
+        fn error(&self, info: ProgressInfo) {
+            self.record(info)
+        }
+        fn deployed(&self, info: ProgressInfo) {
+            self.record(info)
+        }
+        fn paused(&self, info: ProgressInfo) {
+            self.record(info)
+        }
+        fn deleted(&self, info: ProgressInfo) {
+            self.record(info)
+        }
+        fn deployment_error(&self, info: ProgressInfo) {
+            self.record(info)
+        }
+        fn pause_error(&self, info: ProgressInfo) {
+            self.record(info)
+        }
+        fn delete_error(&self, info: ProgressInfo) {
+            self.record(info)
+        }
+    }
+
+    #[test]
+    fn test_async_progress_listener_delivers_in_order_on_flush() {
+        // setup:
+        let received = Arc::new(Mutex::new(vec![]));
+        let listener = AsyncProgressListener::new(Box::new(SlowListener {
+            received: received.clone(),
+        }));
+        let info = |message: &str| {
+            ProgressInfo::new(
+                ProgressScope::Queued,
+                ProgressLevel::Info,
+                Some(message),
+                "execution-id",
+            )
+        };
+
+        // execute:
+        listener.deployment_in_progress(info("first"));
+        listener.error(info("second"));
+        listener.deployed(info("third"));
+        let delivered_before_flush = received.lock().unwrap().len();
+        listener.flush();
+        listener.deployed(info("after flush"));
+
+        // verify:
+        assert!(delivered_before_flush < 3);
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec!["first".to_string(), "second".to_string(), "third".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_environment_validation() {
+        // setup:
+        let application = |name: &str, private_port: Option<u16>| {
+            serde_json::json!({
+                "id": format!("{}-id", name), "name": name, "action": "CREATE",
+                "git_url": "https://github.com/Qovery/engine.git", "git_credentials": null, "branch": "main", "commit_id": "abc", "dockerfile_path": "Dockerfile",
+                "private_port": private_port, "total_cpus": "100m", "cpu_burst": "100m", "total_ram_in_mib": 256,
+                "total_instances": 1, "start_timeout_in_seconds": 60, "storage": [], "environment_variables": []
+            })
+        };
+        let environment = |routes: serde_json::Value, custom_domain: &str, database_version: &str| {
+            serde_json::from_value::<Environment>(serde_json::json!({
+                "execution_id": "execution-id", "id": "env-id", "kind": "DEVELOPMENT", "owner_id": "owner",
+                "project_id": "project", "organization_id": "organization", "action": "CREATE",
+                "applications": [application("api", Some(8080)), application("worker", None)],
+                "routers": [{
+                    "id": "router-id", "name": "main", "action": "CREATE", "default_domain": "main.qovery.io",
+                    "public_port": 443, "routes": routes,
+                    "custom_domains": [{"domain": custom_domain, "target_domain": "main.qovery.io"}]
+                }],
+                "databases": [{
+                    "kind": "POSTGRESQL", "action": "CREATE", "id": "db-id", "name": "db", "version": database_version,
+                    "fqdn_id": "db", "fqdn": "db.qovery.io", "port": 5432, "username": "superuser",
+                    "password": "password", "total_cpus": "100m", "total_ram_in_mib": 512, "disk_size_in_gib": 10,
+                    "database_instance_type": "db.t2.micro", "database_disk_type": "gp2"
+                }],
+                "external_services": [], "clone_from_environment_id": null
+            }))
+            .unwrap()
+        };
+
+        // execute:
+        let valid = environment(
+            serde_json::json!([{"path": "/", "application_name": "api"}]),
+            "*.api.example.com",
+            "13.2",
+        )
+        .validate();
+        let invalid = environment(
+            serde_json::json!([
+                {"path": "/", "application_name": "front"},
+                {"path": "/jobs", "application_name": "worker"},
+                {"path": "/jobs/v2", "application_name": "worker"},
+                {"path": "/jobs", "application_name": "api", "rewrite_target": "/"}
+            ]),
+            "api..example.com",
+            "latest",
+        )
+        .validate();
+
+        let mut tcp_router_with_paths = environment(
+            serde_json::json!([
+                {"path": "/", "application_name": "api"},
+                {"path": "/v2", "application_name": "api"}
+            ]),
+            "api.example.com",
+            "13.2",
+        );
+        tcp_router_with_paths.routers[0].protocol = RouterProtocol::Tcp;
+        let mut tcp_router = environment(
+            serde_json::json!([{"path": "/", "application_name": "api"}]),
+            "api.example.com",
+            "13.2",
+        );
+        tcp_router.routers[0].protocol = RouterProtocol::Tcp;
+
+        let autoscaling = |min_instances: u16, max_instances: u16, target_cpu_percent: u8| {
+            let mut environment = environment(
+                serde_json::json!([{"path": "/", "application_name": "api"}]),
+                "api.example.com",
+                "13.2",
+            );
+            environment.applications[0].autoscaling = Some(Autoscaling {
+                min_instances,
+                max_instances,
+                target_cpu_percent,
+            });
+            environment.validate()
+        };
+        let probes = |application_index: usize, readiness_path: &str, port: Option<u16>| {
+            let mut environment = environment(
+                serde_json::json!([{"path": "/", "application_name": "api"}]),
+                "api.example.com",
+                "13.2",
+            );
+            environment.applications[application_index].probes = Some(Probes {
+                readiness_path: Some(readiness_path.to_string()),
+                liveness_path: None,
+                port,
+            });
+            environment.validate()
+        };
+
+        let namespace = |application_namespace: Option<&str>, router_namespace: Option<&str>| {
+            let mut environment = environment(
+                serde_json::json!([{"path": "/", "application_name": "api"}]),
+                "api.example.com",
+                "13.2",
+            );
+            environment.applications[0].namespace = application_namespace.map(str::to_string);
+            environment.routers[0].namespace = router_namespace.map(str::to_string);
+            environment.databases[0].namespace = Some("databases".to_string());
+            environment.validate()
+        };
+        let clone = |clone_from_environment_id: Option<&str>| {
+            let mut environment = environment(
+                serde_json::json!([{"path": "/", "application_name": "api"}]),
+                "api.example.com",
+                "13.2",
+            );
+            environment.clone_from_environment_id = clone_from_environment_id.map(str::to_string);
+            environment.databases[0].clone_from = Some(CloneSourceDatabase {
+                id: "production-db-id".to_string(),
+                fqdn_id: "production-db".to_string(),
+            });
+            environment.validate()
+        };
+
+        // verify:
+        assert!(valid.is_ok());
+        assert!(tcp_router.validate().is_ok());
+        assert!(namespace(Some("api-1"), Some("api-1")).is_ok());
+        assert_eq!(
+            namespace(Some("Api_1"), Some("Api_1")).unwrap_err()[0].message.as_deref(),
+            Some("namespace Api_1 of api is not a valid DNS-1123 label: at most 63 lowercase alphanumeric characters or `-`, starting and ending with an alphanumeric character")
+        );
+        assert!(namespace(Some("-api"), Some("-api")).is_err());
+        assert!(namespace(Some(&"a".repeat(64)), Some(&"a".repeat(64))).is_err());
+        assert_eq!(
+            namespace(Some("kube-system"), Some("kube-system")).unwrap_err()[0]
+                .message
+                .as_deref(),
+            Some(
+                "namespace kube-system of api is reserved to the cluster, choose a namespace dedicated to the service"
+            )
+        );
+        assert!(namespace(Some("default"), Some("default")).is_err());
+        assert!(namespace(Some("cert-manager"), Some("cert-manager")).is_err());
+        assert_eq!(
+            namespace(Some("api"), None).unwrap_err()[0].message.as_deref(),
+            Some("route / of router main targets application api deployed in another namespace")
+        );
+        assert!(clone(Some("production-env-id")).is_ok());
+        assert_eq!(
+            clone(None).unwrap_err()[0].message.as_deref(),
+            Some("database db is cloned but the environment has no environment to clone from")
+        );
+        assert!(autoscaling(1, 3, 80).is_ok());
+        assert_eq!(
+            autoscaling(2, 4, 80).unwrap_err()[0].message.as_deref(),
+            Some("autoscaling of application api is invalid: 1 instances is out of the autoscaling range [2, 4]")
+        );
+        assert_eq!(
+            autoscaling(1, 4, 0).unwrap_err()[0].message.as_deref(),
+            Some("autoscaling of application api is invalid: target CPU of 0% is not between 1% and 100%")
+        );
+        assert!(probes(0, "/health", None).is_ok());
+        assert!(probes(1, "/health", Some(9000)).is_ok());
+        assert_eq!(
+            probes(0, "health", None).unwrap_err()[0].message.as_deref(),
+            Some("probes of application api are invalid: readiness path `health` must start with `/`")
+        );
+        assert_eq!(
+            probes(1, "/health", None).unwrap_err()[0].message.as_deref(),
+            Some("probes of application worker are invalid: HTTP probes need a port, set the probes port or the application private port")
+        );
+        assert_eq!(
+            tcp_router_with_paths.validate().unwrap_err()[0].message.as_deref(),
+            Some("TCP router main forwards its port 443 to a single application, it can't have path routes")
+        );
+        let messages = invalid
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.message.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "route / of router main targets an unknown application front",
+                "application worker is exposed by router main but has no private port",
+                "path /jobs is used by several routes of router main",
+                "custom domain api..example.com of router main is not a valid domain name",
+                "version latest of database db can't be parsed",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_environment_variables_are_secret_unless_flagged() {
+        // execute:
+        let variables: Vec<EnvironmentVariable> = serde_json::from_str(
+            r#"[
+                {"key": "DB_PASSWORD", "value": "s3cr3t-p4ssw0rd"},
+                {"key": "API_TOKEN", "value": "t0k3n", "is_secret": true},
+                {"key": "LOG_LEVEL", "value": "debug", "is_secret": false}
+            ]"#,
+        )
+        .unwrap();
+
+        // verify:
+        assert_eq!(
+            variables.iter().map(|ev| ev.is_secret).collect::<Vec<_>>(),
+            vec![true, true, false]
+        );
+    }
+
+    #[test]
+    fn test_listeners_helper_redacts_secrets() {
+        // setup:
+        let received = Arc::new(Mutex::new(vec![]));
+        let listeners: Listeners = vec![Arc::new(Box::new(SlowListener {
+            received: received.clone(),
+        }))];
+        let listeners_helper =
+            ListenersHelper::new(&listeners).with_secrets(vec!["s3cr3t-p4ssw0rd".to_string(), "".to_string()]);
+        let info = |message: &str| {
+            ProgressInfo::new(
+                ProgressScope::Application { id: "app".to_string() },
+                ProgressLevel::Info,
+                Some(message),
+                "execution-id",
+            )
+        };
+
+        // execute:
+        listeners_helper.deployment_in_progress(info("Step 4/6 : RUN echo s3cr3t-p4ssw0rd"));
+        listeners_helper.error(info("DB_PASSWORD=s3cr3t-p4ssw0rd is rejected"));
+        listeners_helper.deployed(info("application is deployed"));
+
+        // verify:
+        let received = received.lock().unwrap();
+        assert!(received.iter().all(|message| !message.contains("s3cr3t-p4ssw0rd")));
+        assert_eq!(
+            *received,
+            vec![
+                "Step 4/6 : RUN echo ***".to_string(),
+                "DB_PASSWORD=*** is rejected".to_string(),
+                "application is deployed".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_progress_info_sequence() {
+        // setup:
+        let info = |execution_id: &str| {
+            ProgressInfo::new(ProgressScope::Queued, ProgressLevel::Info, None::<&str>, execution_id)
+        };
+
+        // execute:
+        let first = info("sequence-execution-a");
+        let second = info("sequence-execution-a");
+        let other_execution = info("sequence-execution-b");
+        end_progress_sequence("sequence-execution-b");
+        let next_execution = info("sequence-execution-b");
+
+        // verify:
+        assert_eq!(first.sequence, 1);
+        assert_eq!(second.sequence, 2);
+        assert_eq!(other_execution.sequence, 1);
+        assert_eq!(next_execution.sequence, 1);
+        assert!(second.created_at >= first.created_at);
+    }
+
+    #[test]
+    fn test_deployment_summary_payload_serialization() {
+        // setup:
+        let payload = ProgressPayload::DeploymentSummary {
+            services: vec![ServiceEndpoints {
+                scope: ProgressScope::Database {
+                    id: "mysqlid".to_string(),
+                },
+                name: "my-db".to_string(),
+                endpoints: vec!["mysqltest.qovery.io:3306".to_string()],
+            }],
+        };
+
+        // execute:
+        let json = serde_json::to_value(&payload).unwrap();
+        let deserialized: ProgressPayload = serde_json::from_value(json.clone()).unwrap();
+
+        // verify:
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "kind": "DEPLOYMENT_SUMMARY",
+                "services": [{
+                    "scope": { "kind": "DATABASE", "id": "mysqlid" },
+                    "name": "my-db",
+                    "endpoints": ["mysqltest.qovery.io:3306"],
+                }],
+            })
+        );
+        assert_eq!(deserialized, payload);
+    }
+
+    #[test]
+    fn test_context_builder_validation() {
+        // setup:
+        let metadata = |resource_expiration_in_seconds: Option<u32>, dry_run_deploy: Option<bool>| {
+            Metadata::new(
+                dry_run_deploy,
+                resource_expiration_in_seconds,
+                None,
+                None,
+                Some(true),
+                Some(true),
+                None,
+            )
+        };
+        let error_message = |builder: ContextBuilder| builder.build().err().unwrap().message.unwrap();
+
+        // execute & verify:
+        let context = ContextBuilder::new("execution-id", "/tmp", "lib")
+            .with_test_cluster(true)
+            .build()
+            .unwrap();
+        assert_eq!(context.execution_id(), "execution-id");
+        assert!(context.is_test_cluster());
+        assert_eq!(context.max_concurrent_image_pushes(), 4);
+
+        assert_eq!(
+            error_message(ContextBuilder::new(" ", "/tmp", "lib")),
+            "execution id can't be empty"
+        );
+        assert_eq!(
+            error_message(ContextBuilder::new("execution-id", "/tmp", "")),
+            "lib root dir `` doesn't exist or isn't a directory"
+        );
+        assert_eq!(
+            error_message(ContextBuilder::new("execution-id", "/tmp", "Cargo.toml")),
+            "lib root dir `Cargo.toml` doesn't exist or isn't a directory"
+        );
+        assert_eq!(
+            error_message(ContextBuilder::new("execution-id", "/tmp", "lib").with_metadata(metadata(Some(3600), None))),
+            "resources can't expire while pleco is disabled"
+        );
+        assert_eq!(
+            error_message(ContextBuilder::new("execution-id", "/tmp", "lib").with_metadata(metadata(None, Some(true)))),
+            "partial deployments can't be rolled back during a dry run"
+        );
+        assert_eq!(
+            error_message(
+                ContextBuilder::new("execution-id", "/tmp", "lib").with_metadata(Metadata::new(
+                    Some(true),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    Some(true)
+                ))
+            ),
+            "orphaned helm releases can't be uninstalled during a dry run"
+        );
+        assert_eq!(
+            error_message(ContextBuilder::new("execution-id", "/tmp", "lib").with_max_concurrent_image_pushes(0)),
+            "at least one image must be pushed at a time"
+        );
+        let mut image_registry_rewrites = BTreeMap::new();
+        image_registry_rewrites.insert("docker.io".to_string(), " ".to_string());
+        assert_eq!(
+            error_message(
+                ContextBuilder::new("execution-id", "/tmp", "lib")
+                    .with_image_registry_rewrites(image_registry_rewrites)
+            ),
+            "image registry rewrite `docker.io ->  ` must have a prefix and a destination"
+        );
+    }
+}
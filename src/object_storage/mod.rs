@@ -1,8 +1,11 @@
+use rusoto_core::RusotoError;
+use rusoto_s3::{HeadBucketError, LifecycleExpiration, LifecycleRule, LifecycleRuleFilter, Transition};
 use serde::{Deserialize, Serialize};
 
-use crate::error::{EngineError, EngineErrorCause, EngineErrorScope};
-use crate::models::{Context, StringPath};
+use crate::error::{EngineError, EngineErrorCause, EngineErrorScope, StringError};
+use crate::models::{Context, Listeners, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope, StringPath};
 use std::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 pub mod s3;
 pub mod scaleway_object_storage;
@@ -16,11 +19,36 @@ pub trait ObjectStorage {
     fn name_with_id(&self) -> String {
         format!("{} ({})", self.name(), self.id())
     }
+    /// Checks the configuration and the credentials, for a misconfiguration to fail before any bucket operation.
     fn is_valid(&self) -> Result<(), EngineError>;
+    /// Checks the credentials are accepted by the object storage, wrong ones are a user error.
+    fn validate_credentials(&self) -> Result<(), EngineError>;
     fn create_bucket(&self, bucket_name: &str) -> Result<(), EngineError>;
+    /// Creates the bucket with the lifecycle rule configured for its purpose, if any.
+    fn create_bucket_for(&self, bucket_name: &str, _purpose: BucketPurpose) -> Result<(), EngineError> {
+        self.create_bucket(bucket_name)
+    }
     fn delete_bucket(&self, bucket_name: &str) -> Result<(), EngineError>;
-    fn get(&self, bucket_name: &str, object_key: &str, use_cache: bool) -> Result<(StringPath, File), EngineError>;
-    fn put(&self, bucket_name: &str, object_key: &str, file_path: &str) -> Result<(), EngineError>;
+    fn get(&self, bucket_name: &str, object_key: &str, use_cache: bool) -> Result<(StringPath, File), EngineError> {
+        self.get_with_progress(bucket_name, object_key, use_cache, None)
+    }
+    fn get_with_progress(
+        &self,
+        bucket_name: &str,
+        object_key: &str,
+        use_cache: bool,
+        listeners_helper: Option<&ListenersHelper>,
+    ) -> Result<(StringPath, File), EngineError>;
+    fn put(&self, bucket_name: &str, object_key: &str, file_path: &str) -> Result<(), EngineError> {
+        self.put_with_progress(bucket_name, object_key, file_path, None)
+    }
+    fn put_with_progress(
+        &self,
+        bucket_name: &str,
+        object_key: &str,
+        file_path: &str,
+        listeners_helper: Option<&ListenersHelper>,
+    ) -> Result<(), EngineError>;
     fn engine_error_scope(&self) -> EngineErrorScope {
         EngineErrorScope::ObjectStorage(self.id().to_string(), self.name().to_string())
     }
@@ -41,3 +69,428 @@ pub enum Kind {
     Spaces,
     ScalewayOs,
 }
+
+/// Database backups are written under this prefix, for the backups lifecycle rule to apply to them.
+pub const BACKUPS_PREFIX: &str = "backups/";
+
+/// Storage class objects are moved to when they transition to cold storage.
+const COLD_STORAGE_CLASS: &str = "GLACIER";
+
+pub fn backup_object_key(service_id: &str, file_name: &str) -> String {
+    format!("{}{}/{}", BACKUPS_PREFIX, service_id, file_name)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BucketPurpose {
+    State,
+    Backups,
+}
+
+impl BucketPurpose {
+    /// Objects the lifecycle rule applies to, state buckets are covered entirely.
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            BucketPurpose::State => "",
+            BucketPurpose::Backups => BACKUPS_PREFIX,
+        }
+    }
+}
+
+/// Expires objects (and/or moves them to cold storage) some days after their creation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BucketLifecycle {
+    pub purpose: BucketPurpose,
+    pub expiration_in_days: Option<u32>,
+    pub transition_in_days: Option<u32>,
+}
+
+impl BucketLifecycle {
+    pub fn new(purpose: BucketPurpose, expiration_in_days: Option<u32>, transition_in_days: Option<u32>) -> Self {
+        BucketLifecycle {
+            purpose,
+            expiration_in_days,
+            transition_in_days,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), StringError> {
+        match (self.expiration_in_days, self.transition_in_days) {
+            (None, None) => Err("the lifecycle rule has neither an expiration nor a transition".to_string()),
+            (Some(0), _) => Err("the lifecycle expiration must be at least 1 day".to_string()),
+            (_, Some(0)) => Err("the lifecycle transition must be at least 1 day".to_string()),
+            (Some(expiration), Some(transition)) if transition >= expiration => Err(format!(
+                "the lifecycle transition ({} days) must happen before the expiration ({} days)",
+                transition, expiration
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn rule_id(&self) -> String {
+        match self.purpose.prefix().trim_end_matches('/') {
+            "" => "qovery-lifecycle".to_string(),
+            prefix => format!("qovery-lifecycle-{}", prefix.replace('/', "-")),
+        }
+    }
+
+    /// Lifecycle configuration as expected by `aws s3api put-bucket-lifecycle-configuration`.
+    pub(crate) fn configuration(&self) -> String {
+        let mut rule = serde_json::json!({
+            "ID": self.rule_id(),
+            "Status": "Enabled",
+            "Filter": { "Prefix": self.purpose.prefix() },
+        });
+        if let Some(days) = self.expiration_in_days {
+            rule["Expiration"] = serde_json::json!({ "Days": days });
+        }
+        if let Some(days) = self.transition_in_days {
+            rule["Transitions"] = serde_json::json!([{ "Days": days, "StorageClass": COLD_STORAGE_CLASS }]);
+        }
+
+        serde_json::json!({ "Rules": [rule] }).to_string()
+    }
+
+    pub(crate) fn lifecycle_rule(&self) -> LifecycleRule {
+        LifecycleRule {
+            id: Some(self.rule_id()),
+            status: "Enabled".to_string(),
+            filter: Some(LifecycleRuleFilter {
+                prefix: Some(self.purpose.prefix().to_string()),
+                ..Default::default()
+            }),
+            expiration: self.expiration_in_days.map(|days| LifecycleExpiration {
+                days: Some(days as i64),
+                ..Default::default()
+            }),
+            transitions: self.transition_in_days.map(|days| {
+                vec![Transition {
+                    days: Some(days as i64),
+                    storage_class: Some(COLD_STORAGE_CLASS.to_string()),
+                    ..Default::default()
+                }]
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+/// Error codes returned by S3 compatible APIs (and the AWS CLI) when the credentials are wrong.
+const AUTHENTICATION_ERROR_CODES: [&str; 6] = [
+    "InvalidAccessKeyId",
+    "SignatureDoesNotMatch",
+    "AccessDenied",
+    "InvalidClientTokenId",
+    "InvalidToken",
+    "Unable to locate credentials",
+];
+
+pub(crate) fn is_authentication_failure(message: &str) -> bool {
+    AUTHENTICATION_ERROR_CODES.iter().any(|code| message.contains(code))
+}
+
+/// Wrong credentials are a user error, anything else (ex: connectivity issues) is considered transient.
+pub(crate) fn credentials_check_error_cause<E>(error: &RusotoError<E>) -> EngineErrorCause {
+    match error {
+        RusotoError::Credentials(_) => EngineErrorCause::User("Check the object storage credentials"),
+        RusotoError::Unknown(response)
+            if response.status.as_u16() == 401
+                || response.status.as_u16() == 403
+                || is_authentication_failure(response.body_as_str()) =>
+        {
+            EngineErrorCause::User("Check the object storage credentials")
+        }
+        _ => EngineErrorCause::Internal,
+    }
+}
+
+/// HeadBucket answers 404 to authenticated requests on a missing bucket, wrong credentials get a 403 instead.
+pub(crate) fn is_bucket_not_found(error: &RusotoError<HeadBucketError>) -> bool {
+    match error {
+        RusotoError::Service(HeadBucketError::NoSuchBucket(_)) => true,
+        RusotoError::Unknown(response) => response.status.as_u16() == 404,
+        _ => false,
+    }
+}
+
+// progress is reported every 10% when the object size is known, every 10 MiB otherwise
+const TRANSFER_PROGRESS_STEP_PERCENTAGE: u64 = 10;
+const TRANSFER_PROGRESS_STEP_BYTES: u64 = 10 * 1024 * 1024;
+pub(crate) const TRANSFER_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Emits object storage upload/download progress events to the listeners.
+pub(crate) struct TransferProgress {
+    listeners: Listeners,
+    execution_id: String,
+    description: String,
+    total_bytes: Option<u64>,
+    transferred_bytes: u64,
+    last_reported_step: u64,
+}
+
+impl TransferProgress {
+    pub(crate) fn new(
+        listeners_helper: Option<&ListenersHelper>,
+        execution_id: &str,
+        description: String,
+        total_bytes: Option<u64>,
+    ) -> Self {
+        TransferProgress {
+            listeners: listeners_helper.map(|l| l.listeners().clone()).unwrap_or_default(),
+            execution_id: execution_id.to_string(),
+            description,
+            total_bytes,
+            transferred_bytes: 0,
+            last_reported_step: 0,
+        }
+    }
+
+    pub(crate) fn started(&self) {
+        let message = match self.total_bytes {
+            Some(total_bytes) => format!("{} ({} bytes)", self.description, total_bytes),
+            None => self.description.clone(),
+        };
+
+        self.report(message);
+    }
+
+    pub(crate) fn advance(&mut self, bytes: u64) {
+        self.transferred_bytes += bytes;
+
+        let step = match self.total_bytes {
+            Some(total_bytes) if total_bytes > 0 => {
+                (self.transferred_bytes * 100 / total_bytes) / TRANSFER_PROGRESS_STEP_PERCENTAGE
+            }
+            _ => self.transferred_bytes / TRANSFER_PROGRESS_STEP_BYTES,
+        };
+
+        if step <= self.last_reported_step {
+            return;
+        }
+
+        self.last_reported_step = step;
+        let message = match self.total_bytes {
+            Some(total_bytes) if total_bytes > 0 => format!(
+                "{}: {}/{} bytes transferred ({}%)",
+                self.description,
+                self.transferred_bytes,
+                total_bytes,
+                self.transferred_bytes * 100 / total_bytes
+            ),
+            _ => format!("{}: {} bytes transferred", self.description, self.transferred_bytes),
+        };
+
+        self.report(message);
+    }
+
+    pub(crate) fn finished(&self) {
+        self.report(format!(
+            "{}: done, {} bytes transferred",
+            self.description, self.transferred_bytes
+        ));
+    }
+
+    fn report(&self, message: String) {
+        if self.listeners.is_empty() {
+            return;
+        }
+
+        ListenersHelper::new(&self.listeners).deployment_in_progress(ProgressInfo::new(
+            ProgressScope::Environment {
+                id: self.execution_id.clone(),
+            },
+            ProgressLevel::Info,
+            Some(message),
+            self.execution_id.as_str(),
+        ));
+    }
+}
+
+/// Same as `tokio::io::copy` but reports the amount of copied bytes to `progress`.
+pub(crate) async fn copy_with_progress<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    progress: &mut TransferProgress,
+) -> std::io::Result<u64>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buffer = vec![0u8; TRANSFER_CHUNK_SIZE];
+    let mut copied_bytes = 0;
+
+    loop {
+        let read_bytes = reader.read(&mut buffer).await?;
+        if read_bytes == 0 {
+            writer.flush().await?;
+            return Ok(copied_bytes);
+        }
+
+        writer.write_all(&buffer[..read_bytes]).await?;
+        copied_bytes += read_bytes as u64;
+        progress.advance(read_bytes as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProgressListener;
+    use std::sync::{Arc, Mutex};
+
+    struct RecordingListener {
+        messages: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl ProgressListener for RecordingListener {
+        fn deployment_in_progress(&self, info: ProgressInfo) {
+            self.messages.lock().unwrap().push(info.message.unwrap_or_default());
+        }
+        fn pause_in_progress(&self, _info: ProgressInfo) {}
+        fn delete_in_progress(&self, _info: ProgressInfo) {}
+        fn error(&self, _info: ProgressInfo) {}
+        fn deployed(&self, _info: ProgressInfo) {}
+        fn paused(&self, _info: ProgressInfo) {}
+        fn deleted(&self, _info: ProgressInfo) {}
+        fn deployment_error(&self, _info: ProgressInfo) {}
+        fn pause_error(&self, _info: ProgressInfo) {}
+        fn delete_error(&self, _info: ProgressInfo) {}
+    }
+
+    #[test]
+    fn test_transfer_progress_is_reported_every_step() {
+        // setup:
+        let messages = Arc::new(Mutex::new(vec![]));
+        let listeners: Listeners = vec![Arc::new(Box::new(RecordingListener {
+            messages: messages.clone(),
+        }))];
+        let listeners_helper = ListenersHelper::new(&listeners);
+        let mut progress = TransferProgress::new(
+            Some(&listeners_helper),
+            "execution_id",
+            "Uploading".to_string(),
+            Some(100),
+        );
+
+        // execute:
+        progress.started();
+        for _ in 0..20 {
+            progress.advance(5);
+        }
+        progress.finished();
+
+        // verify:
+        let messages = messages.lock().unwrap();
+        assert_eq!(12, messages.len());
+        assert_eq!("Uploading (100 bytes)", messages[0]);
+        assert_eq!("Uploading: 10/100 bytes transferred (10%)", messages[1]);
+        assert_eq!("Uploading: 100/100 bytes transferred (100%)", messages[10]);
+        assert_eq!("Uploading: done, 100 bytes transferred", messages[11]);
+    }
+
+    #[test]
+    fn test_credentials_check_error_cause() {
+        // setup:
+        let response = |status: u16, body: &str| {
+            RusotoError::<()>::Unknown(rusoto_core::request::BufferedHttpResponse {
+                status: reqwest::StatusCode::from_u16(status).unwrap(),
+                body: body.to_string().into(),
+                headers: Default::default(),
+            })
+        };
+
+        // execute:
+        let forbidden = credentials_check_error_cause(&response(403, ""));
+        let bad_request = credentials_check_error_cause(&response(400, "<Code>InvalidAccessKeyId</Code>"));
+        let unavailable = credentials_check_error_cause(&response(503, "SlowDown"));
+        let unreachable = credentials_check_error_cause(&RusotoError::<()>::HttpDispatch(
+            rusoto_core::request::HttpDispatchError::new("connection refused".to_string()),
+        ));
+
+        // verify:
+        assert!(matches!(forbidden, EngineErrorCause::User(_)));
+        assert!(matches!(bad_request, EngineErrorCause::User(_)));
+        assert!(matches!(unavailable, EngineErrorCause::Internal));
+        assert!(matches!(unreachable, EngineErrorCause::Internal));
+        assert!(is_authentication_failure(
+            "An error occurred (InvalidAccessKeyId) when calling the ListBuckets operation"
+        ));
+        assert!(!is_authentication_failure("Could not connect to the endpoint URL"));
+    }
+
+    #[test]
+    fn test_is_bucket_not_found() {
+        // setup:
+        let response = |status: u16| {
+            RusotoError::<HeadBucketError>::Unknown(rusoto_core::request::BufferedHttpResponse {
+                status: reqwest::StatusCode::from_u16(status).unwrap(),
+                body: Default::default(),
+                headers: Default::default(),
+            })
+        };
+
+        // execute & verify:
+        assert!(is_bucket_not_found(&response(404)));
+        assert!(is_bucket_not_found(&RusotoError::Service(
+            HeadBucketError::NoSuchBucket("qovery-kubeconfigs-z1234".to_string())
+        )));
+        assert!(!is_bucket_not_found(&response(403)));
+        assert!(!is_bucket_not_found(&response(500)));
+    }
+
+    #[test]
+    fn test_bucket_lifecycle() {
+        // setup:
+        let backups = BucketLifecycle::new(BucketPurpose::Backups, Some(30), Some(7));
+        let state = BucketLifecycle::new(BucketPurpose::State, Some(90), None);
+
+        // execute:
+        let configuration: serde_json::Value = serde_json::from_str(backups.configuration().as_str()).unwrap();
+        let state_rule = state.lifecycle_rule();
+
+        // verify:
+        assert!(backups.validate().is_ok());
+        assert!(state.validate().is_ok());
+        assert!(BucketLifecycle::new(BucketPurpose::Backups, None, Some(7))
+            .validate()
+            .is_ok());
+        assert!(BucketLifecycle::new(BucketPurpose::Backups, None, None)
+            .validate()
+            .is_err());
+        assert!(BucketLifecycle::new(BucketPurpose::Backups, Some(0), None)
+            .validate()
+            .is_err());
+        assert!(BucketLifecycle::new(BucketPurpose::Backups, Some(30), Some(0))
+            .validate()
+            .is_err());
+        assert!(BucketLifecycle::new(BucketPurpose::Backups, Some(7), Some(7))
+            .validate()
+            .is_err());
+        assert_eq!(configuration["Rules"][0]["ID"], "qovery-lifecycle-backups");
+        assert_eq!(configuration["Rules"][0]["Filter"]["Prefix"], "backups/");
+        assert_eq!(configuration["Rules"][0]["Expiration"]["Days"], 30);
+        assert_eq!(configuration["Rules"][0]["Transitions"][0]["Days"], 7);
+        assert_eq!(configuration["Rules"][0]["Transitions"][0]["StorageClass"], "GLACIER");
+        assert_eq!(state_rule.id, Some("qovery-lifecycle".to_string()));
+        assert_eq!(state_rule.expiration.unwrap().days, Some(90));
+        assert!(state_rule.transitions.is_none());
+        assert_eq!(
+            backup_object_key("postgresql-id", "dump.gz"),
+            "backups/postgresql-id/dump.gz"
+        );
+    }
+
+    #[test]
+    fn test_transfer_progress_without_listeners() {
+        // setup:
+        let mut progress = TransferProgress::new(None, "execution_id", "Downloading".to_string(), None);
+
+        // execute:
+        progress.started();
+        progress.advance(TRANSFER_PROGRESS_STEP_BYTES * 2);
+        progress.finished();
+
+        // verify:
+        assert_eq!(TRANSFER_PROGRESS_STEP_BYTES * 2, progress.transferred_bytes);
+        assert_eq!(2, progress.last_reported_step);
+    }
+}
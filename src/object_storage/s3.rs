@@ -2,11 +2,65 @@ use std::fs::File;
 
 use retry::delay::Fibonacci;
 use retry::{Error, OperationResult};
+use rusoto_core::{Client, HttpClient, Region};
+use rusoto_credential::StaticProvider;
+use rusoto_sts::{GetCallerIdentityRequest, Sts, StsClient};
 
 use crate::constants::{AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY};
-use crate::error::{cast_simple_error_to_engine_error, EngineError, EngineErrorCause};
-use crate::models::{Context, StringPath};
-use crate::object_storage::{Kind, ObjectStorage};
+use crate::error::{
+    cast_simple_error_to_engine_error, check_credentials_are_set, EngineError, EngineErrorCause, StringError,
+};
+use crate::models::{Context, ListenersHelper, StringPath};
+use crate::object_storage::{
+    credentials_check_error_cause, BucketLifecycle, BucketPurpose, Kind, ObjectStorage, TransferProgress,
+};
+use crate::runtime::block_on;
+
+/// Replicates the created buckets to another region (ex: for the tfstates to survive a regional outage).
+#[derive(Clone, Debug, PartialEq)]
+pub struct BucketReplication {
+    pub region: String,
+    pub secondary_region: String,
+    /// IAM role assumed by S3 to copy the objects to the replica bucket.
+    pub role_arn: String,
+}
+
+impl BucketReplication {
+    pub fn validate(&self) -> Result<(), StringError> {
+        if self.secondary_region.trim().is_empty() {
+            return Err("the replication secondary region is missing".to_string());
+        }
+
+        if self.region.trim().eq_ignore_ascii_case(self.secondary_region.trim()) {
+            return Err(format!(
+                "the replication secondary region must differ from the primary region {}",
+                self.region
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Replica buckets live in the secondary region and are kept when the primary bucket is deleted.
+    pub fn replica_bucket_name(&self, bucket_name: &str) -> String {
+        format!("{}-replica-{}", bucket_name, self.secondary_region)
+    }
+
+    fn configuration(&self, bucket_name: &str) -> String {
+        serde_json::json!({
+            "Role": self.role_arn,
+            "Rules": [{
+                "ID": "replicate-to-secondary-region",
+                "Status": "Enabled",
+                "Priority": 1,
+                "Filter": {},
+                "DeleteMarkerReplication": { "Status": "Disabled" },
+                "Destination": { "Bucket": format!("arn:aws:s3:::{}", self.replica_bucket_name(bucket_name)) }
+            }]
+        })
+        .to_string()
+    }
+}
 
 pub struct S3 {
     context: Context,
@@ -14,6 +68,8 @@ pub struct S3 {
     name: String,
     access_key_id: String,
     secret_access_key: String,
+    replication: Option<BucketReplication>,
+    lifecycles: Vec<BucketLifecycle>,
 }
 
 impl S3 {
@@ -24,15 +80,92 @@ impl S3 {
             name,
             access_key_id,
             secret_access_key,
+            replication: None,
+            lifecycles: vec![],
         }
     }
 
+    /// Buckets are created versioned, and replicated to the secondary region.
+    pub fn with_replication(mut self, replication: BucketReplication) -> Self {
+        self.replication = Some(replication);
+        self
+    }
+
+    /// Buckets created for the lifecycle purpose get its expiration and transition rule.
+    pub fn with_lifecycle(mut self, lifecycle: BucketLifecycle) -> Self {
+        self.lifecycles.retain(|l| l.purpose != lifecycle.purpose);
+        self.lifecycles.push(lifecycle);
+        self
+    }
+
     fn credentials_environment_variables(&self) -> Vec<(&str, &str)> {
         vec![
             (AWS_ACCESS_KEY_ID, self.access_key_id.as_str()),
             (AWS_SECRET_ACCESS_KEY, self.secret_access_key.as_str()),
         ]
     }
+
+    fn aws_s3api(&self, args: Vec<&str>) -> Result<(), EngineError> {
+        let mut s3api_args = vec!["s3api"];
+        s3api_args.extend(args);
+
+        cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context().execution_id(),
+            crate::cmd::utilities::exec("aws", s3api_args, &self.credentials_environment_variables()),
+        )
+    }
+
+    fn create_versioned_bucket(&self, bucket_name: &str, region: &str) -> Result<(), EngineError> {
+        let location_constraint = format!("LocationConstraint={}", region);
+        let mut args = vec!["create-bucket", "--bucket", bucket_name, "--region", region];
+        // us-east-1 is the default location and is refused as a location constraint
+        if region != "us-east-1" {
+            args.extend(vec!["--create-bucket-configuration", location_constraint.as_str()]);
+        }
+        self.aws_s3api(args)?;
+
+        self.aws_s3api(vec![
+            "put-bucket-versioning",
+            "--bucket",
+            bucket_name,
+            "--region",
+            region,
+            "--versioning-configuration",
+            "Status=Enabled",
+        ])
+    }
+
+    fn create_replicated_bucket(&self, bucket_name: &str, replication: &BucketReplication) -> Result<(), EngineError> {
+        replication
+            .validate()
+            .map_err(|err| self.engine_error(EngineErrorCause::User("Check the object storage replication"), err))?;
+
+        let replica_bucket_name = replication.replica_bucket_name(bucket_name);
+        self.create_versioned_bucket(bucket_name, replication.region.as_str())?;
+        self.create_versioned_bucket(replica_bucket_name.as_str(), replication.secondary_region.as_str())?;
+
+        self.aws_s3api(vec![
+            "put-bucket-replication",
+            "--bucket",
+            bucket_name,
+            "--region",
+            replication.region.as_str(),
+            "--replication-configuration",
+            replication.configuration(bucket_name).as_str(),
+        ])
+    }
+
+    fn put_bucket_lifecycle(&self, bucket_name: &str, lifecycle: &BucketLifecycle) -> Result<(), EngineError> {
+        let mut args = vec!["put-bucket-lifecycle-configuration", "--bucket", bucket_name];
+        if let Some(replication) = &self.replication {
+            args.extend(vec!["--region", replication.region.as_str()]);
+        }
+        let configuration = lifecycle.configuration();
+        args.extend(vec!["--lifecycle-configuration", configuration.as_str()]);
+
+        self.aws_s3api(args)
+    }
 }
 
 impl ObjectStorage for S3 {
@@ -53,11 +186,54 @@ impl ObjectStorage for S3 {
     }
 
     fn is_valid(&self) -> Result<(), EngineError> {
-        // TODO check valid credentials
-        Ok(())
+        if let Some(replication) = &self.replication {
+            replication.validate().map_err(|err| {
+                self.engine_error(EngineErrorCause::User("Check the object storage replication"), err)
+            })?;
+        }
+
+        for lifecycle in &self.lifecycles {
+            lifecycle
+                .validate()
+                .map_err(|err| self.engine_error(EngineErrorCause::User("Check the object storage lifecycle"), err))?;
+        }
+
+        self.validate_credentials()
+    }
+
+    fn validate_credentials(&self) -> Result<(), EngineError> {
+        check_credentials_are_set(&[
+            ("access key id", self.access_key_id.as_str()),
+            ("secret access key", self.secret_access_key.as_str()),
+        ])
+        .map_err(|err| {
+            self.engine_error(
+                EngineErrorCause::User("Check the object storage credentials"),
+                format!("{} for object storage {}", err, self.name_with_id()),
+            )
+        })?;
+
+        let credentials = StaticProvider::new(self.access_key_id.clone(), self.secret_access_key.clone(), None, None);
+        let client = StsClient::new_with_client(
+            Client::new_with(credentials, HttpClient::new().unwrap()),
+            Region::default(),
+        );
+
+        block_on(client.get_caller_identity(GetCallerIdentityRequest::default()))
+            .map(|_| ())
+            .map_err(|e| {
+                self.engine_error(
+                    credentials_check_error_cause(&e),
+                    format!("Can't check S3 credentials of {}: {}", self.name_with_id(), e),
+                )
+            })
     }
 
     fn create_bucket(&self, bucket_name: &str) -> Result<(), EngineError> {
+        if let Some(replication) = &self.replication {
+            return self.create_replicated_bucket(bucket_name, replication);
+        }
+
         cast_simple_error_to_engine_error(
             self.engine_error_scope(),
             self.context().execution_id(),
@@ -69,6 +245,19 @@ impl ObjectStorage for S3 {
         )
     }
 
+    fn create_bucket_for(&self, bucket_name: &str, purpose: BucketPurpose) -> Result<(), EngineError> {
+        let lifecycle = match self.lifecycles.iter().find(|l| l.purpose == purpose) {
+            Some(lifecycle) => lifecycle,
+            None => return self.create_bucket(bucket_name),
+        };
+        lifecycle
+            .validate()
+            .map_err(|err| self.engine_error(EngineErrorCause::User("Check the object storage lifecycle"), err))?;
+
+        self.create_bucket(bucket_name)?;
+        self.put_bucket_lifecycle(bucket_name, lifecycle)
+    }
+
     fn delete_bucket(&self, bucket_name: &str) -> Result<(), EngineError> {
         cast_simple_error_to_engine_error(
             self.engine_error_scope(),
@@ -87,7 +276,13 @@ impl ObjectStorage for S3 {
         )
     }
 
-    fn get(&self, bucket_name: &str, object_key: &str, use_cache: bool) -> Result<(StringPath, File), EngineError> {
+    fn get_with_progress(
+        &self,
+        bucket_name: &str,
+        object_key: &str,
+        use_cache: bool,
+        listeners_helper: Option<&ListenersHelper>,
+    ) -> Result<(StringPath, File), EngineError> {
         let workspace_directory = crate::fs::workspace_directory(
             self.context().workspace_root_dir(),
             self.context().execution_id(),
@@ -109,6 +304,15 @@ impl ObjectStorage for S3 {
             }
         }
 
+        // the AWS CLI doesn't expose the transfer progress, so only the beginning and the end are reported
+        let mut progress = TransferProgress::new(
+            listeners_helper,
+            self.context().execution_id(),
+            format!("Downloading object `{}` from bucket `{}`", object_key, bucket_name),
+            None,
+        );
+        progress.started();
+
         // retrieve config file from object storage
         let result = retry::retry(Fibonacci::from_millis(3000).take(5), || {
             // we choose to use the AWS CLI instead of Rusoto S3 due to reliability problems we faced.
@@ -145,12 +349,33 @@ impl ObjectStorage for S3 {
         };
 
         match file {
-            Ok(file) => Ok((file_path, file)),
+            Ok(file) => {
+                progress.advance(file.metadata().map(|m| m.len()).unwrap_or(0));
+                progress.finished();
+                Ok((file_path, file))
+            }
             Err(err) => Err(self.engine_error(EngineErrorCause::Internal, format!("{:?}", err))),
         }
     }
 
-    fn put(&self, bucket_name: &str, object_key: &str, file_path: &str) -> Result<(), EngineError> {
+    fn put_with_progress(
+        &self,
+        bucket_name: &str,
+        object_key: &str,
+        file_path: &str,
+        listeners_helper: Option<&ListenersHelper>,
+    ) -> Result<(), EngineError> {
+        let file_size = std::fs::metadata(file_path).map(|m| m.len()).ok();
+
+        // the AWS CLI doesn't expose the transfer progress, so only the beginning and the end are reported
+        let mut progress = TransferProgress::new(
+            listeners_helper,
+            self.context().execution_id(),
+            format!("Uploading object `{}` to bucket `{}`", object_key, bucket_name),
+            file_size,
+        );
+        progress.started();
+
         cast_simple_error_to_engine_error(
             self.engine_error_scope(),
             self.context().execution_id(),
@@ -164,6 +389,45 @@ impl ObjectStorage for S3 {
                 ],
                 &self.credentials_environment_variables(),
             ),
-        )
+        )?;
+
+        progress.advance(file_size.unwrap_or(0));
+        progress.finished();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::object_storage::s3::BucketReplication;
+
+    #[test]
+    fn test_bucket_replication() {
+        // setup:
+        let replication = |region: &str, secondary_region: &str| BucketReplication {
+            region: region.to_string(),
+            secondary_region: secondary_region.to_string(),
+            role_arn: "arn:aws:iam::123456789012:role/s3-replication".to_string(),
+        };
+
+        // execute:
+        let replicated = replication("eu-west-3", "eu-central-1");
+        let configuration: serde_json::Value =
+            serde_json::from_str(replicated.configuration("qovery-tfstates").as_str()).unwrap();
+
+        // verify:
+        assert!(replicated.validate().is_ok());
+        assert!(replication("eu-west-3", "EU-WEST-3").validate().is_err());
+        assert!(replication("eu-west-3", "").validate().is_err());
+        assert_eq!(
+            replicated.replica_bucket_name("qovery-tfstates"),
+            "qovery-tfstates-replica-eu-central-1"
+        );
+        assert_eq!(configuration["Role"], "arn:aws:iam::123456789012:role/s3-replication");
+        assert_eq!(
+            configuration["Rules"][0]["Destination"]["Bucket"],
+            "arn:aws:s3:::qovery-tfstates-replica-eu-central-1"
+        );
     }
 }
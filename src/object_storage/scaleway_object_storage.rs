@@ -1,26 +1,141 @@
 use std::fs::File;
 use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use crate::cloud_provider::scaleway::application::Zone;
-use crate::error::{EngineError, EngineErrorCause};
-use crate::models::{Context, StringPath};
-use crate::object_storage::{Kind, ObjectStorage};
+use crate::error::{check_credentials_are_set, EngineError, EngineErrorCause};
+use crate::models::{Context, ListenersHelper, StringPath};
+use crate::object_storage::{
+    copy_with_progress, credentials_check_error_cause, is_bucket_not_found, BucketLifecycle, BucketPurpose, Kind,
+    ObjectStorage, TransferProgress, TRANSFER_CHUNK_SIZE,
+};
 
 use crate::runtime::block_on;
-use rusoto_core::{Client, HttpClient, Region as RusotoRegion};
+use chrono::{DateTime, Utc};
+use rusoto_core::{ByteStream, Client, HttpClient, Region as RusotoRegion};
 use rusoto_credential::StaticProvider;
 use rusoto_s3::{
-    CreateBucketConfiguration, CreateBucketRequest, Delete, DeleteBucketRequest, DeleteObjectsRequest,
-    GetObjectRequest, HeadBucketRequest, ListObjectsRequest, ObjectIdentifier, PutBucketVersioningRequest,
-    PutObjectRequest, S3Client, StreamingBody, S3,
+    BucketLifecycleConfiguration, CreateBucketConfiguration, CreateBucketRequest, Delete, DeleteBucketRequest,
+    DeleteObjectsRequest, GetObjectRequest, HeadBucketRequest, ListObjectVersionsRequest, ObjectIdentifier,
+    PutBucketLifecycleConfigurationRequest, PutBucketVersioningRequest, PutObjectRequest, S3Client, S3,
 };
-use tokio::io;
+
+// S3 API doesn't allow to delete more than 1000 objects in a single request
+const MAX_OBJECTS_PER_DELETE_REQUEST: usize = 1000;
 
 pub enum BucketDeleteStrategy {
+    /// Empty the bucket then delete it right away.
     HardDelete,
+    /// Empty the bucket then delete it only if it is older than the given duration.
+    HardDeleteAfter(Duration),
+    /// Empty the bucket but keep it, Scaleway takes up to 24 hours to release a deleted bucket name.
     Empty,
 }
 
+impl BucketDeleteStrategy {
+    fn allows_bucket_deletion(&self, bucket_created_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+        match self {
+            BucketDeleteStrategy::HardDelete => true,
+            BucketDeleteStrategy::HardDeleteAfter(min_age) => match bucket_created_at {
+                Some(created_at) => match chrono::Duration::from_std(*min_age) {
+                    Ok(min_age) => now.signed_duration_since(created_at) >= min_age,
+                    Err(_) => false,
+                },
+                // bucket age is unknown, better keep it
+                None => false,
+            },
+            BucketDeleteStrategy::Empty => false,
+        }
+    }
+}
+
+struct ObjectVersionsPage {
+    objects: Vec<ObjectIdentifier>,
+    next_key_marker: Option<String>,
+    next_version_id_marker: Option<String>,
+}
+
+trait ObjectVersionsLister {
+    fn list_object_versions(
+        &self,
+        bucket_name: &str,
+        key_marker: Option<String>,
+        version_id_marker: Option<String>,
+    ) -> Result<ObjectVersionsPage, String>;
+}
+
+impl ObjectVersionsLister for S3Client {
+    fn list_object_versions(
+        &self,
+        bucket_name: &str,
+        key_marker: Option<String>,
+        version_id_marker: Option<String>,
+    ) -> Result<ObjectVersionsPage, String> {
+        let res = block_on(S3::list_object_versions(
+            self,
+            ListObjectVersionsRequest {
+                bucket: bucket_name.to_string(),
+                key_marker,
+                version_id_marker,
+                ..Default::default()
+            },
+        ))
+        .map_err(|e| e.to_string())?;
+
+        // both object versions and delete markers have to be removed for the bucket to be empty
+        let versions = res
+            .versions
+            .unwrap_or_default()
+            .into_iter()
+            .map(|v| (v.key, v.version_id));
+        let delete_markers = res
+            .delete_markers
+            .unwrap_or_default()
+            .into_iter()
+            .map(|m| (m.key, m.version_id));
+
+        let objects = versions
+            .chain(delete_markers)
+            .filter_map(|(key, version_id)| key.map(|key| ObjectIdentifier { key, version_id }))
+            .collect::<Vec<_>>();
+
+        Ok(match res.is_truncated {
+            Some(true) => ObjectVersionsPage {
+                objects,
+                next_key_marker: res.next_key_marker,
+                next_version_id_marker: res.next_version_id_marker,
+            },
+            _ => ObjectVersionsPage {
+                objects,
+                next_key_marker: None,
+                next_version_id_marker: None,
+            },
+        })
+    }
+}
+
+fn list_all_object_versions(
+    lister: &dyn ObjectVersionsLister,
+    bucket_name: &str,
+) -> Result<Vec<ObjectIdentifier>, String> {
+    let mut objects = vec![];
+    let mut key_marker = None;
+    let mut version_id_marker = None;
+
+    loop {
+        let page = lister.list_object_versions(bucket_name, key_marker, version_id_marker)?;
+        objects.extend(page.objects);
+
+        if page.next_key_marker.is_none() && page.next_version_id_marker.is_none() {
+            return Ok(objects);
+        }
+
+        key_marker = page.next_key_marker;
+        version_id_marker = page.next_version_id_marker;
+    }
+}
+
 // doc: https://www.scaleway.com/en/docs/object-storage-feature/
 pub struct ScalewayOS {
     context: Context,
@@ -31,6 +146,8 @@ pub struct ScalewayOS {
     zone: Zone,
     bucket_delete_strategy: BucketDeleteStrategy,
     bucket_versioning_activated: bool,
+    lifecycles: Vec<BucketLifecycle>,
+    credentials_check_bucket: Option<String>,
 }
 
 impl ScalewayOS {
@@ -53,9 +170,24 @@ impl ScalewayOS {
             zone,
             bucket_delete_strategy,
             bucket_versioning_activated,
+            lifecycles: vec![],
+            credentials_check_bucket: None,
         }
     }
 
+    /// The credentials are checked with a HeadBucket on this bucket, it does not need to exist yet.
+    pub fn with_credentials_check_bucket(mut self, bucket_name: String) -> Self {
+        self.credentials_check_bucket = Some(bucket_name);
+        self
+    }
+
+    /// Buckets created for the lifecycle purpose get its expiration and transition rule.
+    pub fn with_lifecycle(mut self, lifecycle: BucketLifecycle) -> Self {
+        self.lifecycles.retain(|l| l.purpose != lifecycle.purpose);
+        self.lifecycles.push(lifecycle);
+        self
+    }
+
     fn get_s3_client(&self) -> S3Client {
         let region = RusotoRegion::Custom {
             name: self.zone.region().to_string(),
@@ -105,35 +237,28 @@ impl ScalewayOS {
 
         let s3_client = self.get_s3_client();
 
-        // make sure to delete all bucket content before trying to delete the bucket
-        let objects_to_be_deleted = match block_on(s3_client.list_objects(ListObjectsRequest {
-            bucket: bucket_name.to_string(),
-            ..Default::default()
-        })) {
-            Ok(res) => res.contents.unwrap_or_default(),
-            Err(_) => {
-                vec![]
+        // make sure to delete all bucket content (including all object versions) before trying to delete the bucket
+        let objects_to_be_deleted = match list_all_object_versions(&s3_client, bucket_name) {
+            Ok(objects) => objects,
+            Err(e) => {
+                let message = format!(
+                    "While trying to delete object-storage bucket `{}`, cannot list content: {}",
+                    bucket_name, e
+                );
+                error!("{}", message);
+                return Err(self.engine_error(EngineErrorCause::Internal, message));
             }
         };
 
-        if !objects_to_be_deleted.is_empty() {
-            if let Err(e) = block_on(
-                s3_client.delete_objects(DeleteObjectsRequest {
-                    bucket: bucket_name.to_string(),
-                    delete: Delete {
-                        objects: objects_to_be_deleted
-                            .iter()
-                            .filter_map(|e| e.key.clone())
-                            .map(|e| ObjectIdentifier {
-                                key: e,
-                                version_id: None,
-                            })
-                            .collect(),
-                        ..Default::default()
-                    },
+        for objects in objects_to_be_deleted.chunks(MAX_OBJECTS_PER_DELETE_REQUEST) {
+            if let Err(e) = block_on(s3_client.delete_objects(DeleteObjectsRequest {
+                bucket: bucket_name.to_string(),
+                delete: Delete {
+                    objects: objects.to_vec(),
                     ..Default::default()
-                }),
-            ) {
+                },
+                ..Default::default()
+            })) {
                 let message = format!(
                     "While trying to delete object-storage bucket `{}`, cannot delete content: {}",
                     bucket_name, e
@@ -146,6 +271,52 @@ impl ScalewayOS {
         Ok(())
     }
 
+    fn bucket_creation_date(&self, bucket_name: &str) -> Option<DateTime<Utc>> {
+        let s3_client = self.get_s3_client();
+
+        match block_on(s3_client.list_buckets()) {
+            Ok(res) => res
+                .buckets
+                .unwrap_or_default()
+                .into_iter()
+                .find(|b| b.name.as_deref() == Some(bucket_name))
+                .and_then(|b| b.creation_date)
+                .and_then(|creation_date| DateTime::parse_from_rfc3339(creation_date.as_str()).ok())
+                .map(|creation_date| creation_date.with_timezone(&Utc)),
+            Err(e) => {
+                error!(
+                    "While trying to get object-storage bucket `{}` creation date: {}",
+                    bucket_name, e
+                );
+                None
+            }
+        }
+    }
+
+    fn put_bucket_lifecycle(&self, bucket_name: &str, lifecycle: &BucketLifecycle) -> Result<(), EngineError> {
+        let s3_client = self.get_s3_client();
+
+        // an existing configuration is replaced, so reused buckets get the current rule as well
+        block_on(
+            s3_client.put_bucket_lifecycle_configuration(PutBucketLifecycleConfigurationRequest {
+                bucket: bucket_name.to_string(),
+                lifecycle_configuration: Some(BucketLifecycleConfiguration {
+                    rules: vec![lifecycle.lifecycle_rule()],
+                }),
+                ..Default::default()
+            }),
+        )
+        .map(|_| ())
+        .map_err(|e| {
+            let message = format!(
+                "While trying to set the lifecycle of object-storage bucket, name `{}`: {}",
+                bucket_name, e
+            );
+            error!("{}", message);
+            self.engine_error(EngineErrorCause::Internal, message)
+        })
+    }
+
     pub fn bucket_exists(&self, bucket_name: &str) -> bool {
         let s3_client = self.get_s3_client();
 
@@ -175,7 +346,50 @@ impl ObjectStorage for ScalewayOS {
     }
 
     fn is_valid(&self) -> Result<(), EngineError> {
-        todo!()
+        self.validate_credentials()?;
+
+        for lifecycle in &self.lifecycles {
+            lifecycle
+                .validate()
+                .map_err(|err| self.engine_error(EngineErrorCause::User("Check the object storage lifecycle"), err))?;
+        }
+
+        Ok(())
+    }
+
+    fn validate_credentials(&self) -> Result<(), EngineError> {
+        check_credentials_are_set(&[
+            ("access key", self.access_key.as_str()),
+            ("secret key", self.secret_token.as_str()),
+        ])
+        .map_err(|err| {
+            self.engine_error(
+                EngineErrorCause::User("Check the object storage credentials"),
+                format!("{} for object storage {}", err, self.name_with_id()),
+            )
+        })?;
+
+        let bucket_name = match &self.credentials_check_bucket {
+            Some(bucket_name) => bucket_name,
+            None => return Ok(()),
+        };
+
+        match block_on(self.get_s3_client().head_bucket(HeadBucketRequest {
+            bucket: bucket_name.to_string(),
+            expected_bucket_owner: None,
+        })) {
+            Ok(_) => Ok(()),
+            Err(e) if is_bucket_not_found(&e) => Ok(()),
+            Err(e) => Err(self.engine_error(
+                credentials_check_error_cause(&e),
+                format!(
+                    "Can't access object-storage bucket `{}` in zone {}: {}",
+                    bucket_name,
+                    self.zone.as_str(),
+                    e
+                ),
+            )),
+        }
     }
 
     fn create_bucket(&self, bucket_name: &str) -> Result<(), EngineError> {
@@ -234,6 +448,19 @@ impl ObjectStorage for ScalewayOS {
         }
     }
 
+    fn create_bucket_for(&self, bucket_name: &str, purpose: BucketPurpose) -> Result<(), EngineError> {
+        let lifecycle = match self.lifecycles.iter().find(|l| l.purpose == purpose) {
+            Some(lifecycle) => lifecycle,
+            None => return self.create_bucket(bucket_name),
+        };
+        lifecycle
+            .validate()
+            .map_err(|err| self.engine_error(EngineErrorCause::User("Check the object storage lifecycle"), err))?;
+
+        self.create_bucket(bucket_name)?;
+        self.put_bucket_lifecycle(bucket_name, lifecycle)
+    }
+
     fn delete_bucket(&self, bucket_name: &str) -> Result<(), EngineError> {
         // TODO(benjamin): switch to `scaleway-api-rs` once object storage will be supported (https://github.com/Qovery/scaleway-api-rs/issues/12).
         if let Err(message) = ScalewayOS::is_bucket_name_valid(bucket_name) {
@@ -255,26 +482,41 @@ impl ObjectStorage for ScalewayOS {
         // Note: Do not delete the bucket entirely but empty its content.
         // Bucket deletion might take up to 24 hours and during this time we are not able to create a bucket with the same name.
         // So emptying bucket allows future reuse.
-        return match &self.bucket_delete_strategy {
-            BucketDeleteStrategy::HardDelete => match block_on(s3_client.delete_bucket(DeleteBucketRequest {
-                bucket: bucket_name.to_string(),
-                ..Default::default()
-            })) {
-                Ok(_) => Ok(()),
-                Err(e) => {
-                    let message = format!(
-                        "While trying to delete object-storage bucket, name `{}`: {}",
-                        bucket_name, e
-                    );
-                    error!("{}", message);
-                    return Err(self.engine_error(EngineErrorCause::Internal, message));
-                }
-            },
-            BucketDeleteStrategy::Empty => Ok(()), // Do not delete the bucket
+        let bucket_created_at = match &self.bucket_delete_strategy {
+            BucketDeleteStrategy::HardDeleteAfter(_) => self.bucket_creation_date(bucket_name),
+            _ => None,
         };
+
+        if !self
+            .bucket_delete_strategy
+            .allows_bucket_deletion(bucket_created_at, Utc::now())
+        {
+            return Ok(()); // Do not delete the bucket
+        }
+
+        match block_on(s3_client.delete_bucket(DeleteBucketRequest {
+            bucket: bucket_name.to_string(),
+            ..Default::default()
+        })) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                let message = format!(
+                    "While trying to delete object-storage bucket, name `{}`: {}",
+                    bucket_name, e
+                );
+                error!("{}", message);
+                Err(self.engine_error(EngineErrorCause::Internal, message))
+            }
+        }
     }
 
-    fn get(&self, bucket_name: &str, object_key: &str, use_cache: bool) -> Result<(StringPath, File), EngineError> {
+    fn get_with_progress(
+        &self,
+        bucket_name: &str,
+        object_key: &str,
+        use_cache: bool,
+        listeners_helper: Option<&ListenersHelper>,
+    ) -> Result<(StringPath, File), EngineError> {
         // TODO(benjamin): switch to `scaleway-api-rs` once object storage will be supported (https://github.com/Qovery/scaleway-api-rs/issues/12).
         if let Err(message) = ScalewayOS::is_bucket_name_valid(bucket_name) {
             let message = format!(
@@ -317,6 +559,14 @@ impl ObjectStorage for ScalewayOS {
                 let body = res.body.take();
                 let mut body = body.unwrap().into_async_read();
 
+                let mut progress = TransferProgress::new(
+                    listeners_helper,
+                    self.context().execution_id(),
+                    format!("Downloading object `{}` from bucket `{}`", object_key, bucket_name),
+                    res.content_length.map(|length| length as u64),
+                );
+                progress.started();
+
                 // create parent dir
                 let path = Path::new(file_path.as_str());
                 let parent_dir = path.parent().unwrap();
@@ -324,17 +574,20 @@ impl ObjectStorage for ScalewayOS {
 
                 // create file
                 match block_on(tokio::fs::File::create(path)) {
-                    Ok(mut created_file) => match block_on(io::copy(&mut body, &mut created_file)) {
-                        Ok(_) => {
-                            let file = File::open(path).unwrap();
-                            Ok((file_path, file))
+                    Ok(mut created_file) => {
+                        match block_on(copy_with_progress(&mut body, &mut created_file, &mut progress)) {
+                            Ok(_) => {
+                                progress.finished();
+                                let file = File::open(path).unwrap();
+                                Ok((file_path, file))
+                            }
+                            Err(e) => {
+                                let message = format!("{}", e);
+                                error!("{}", message);
+                                Err(self.engine_error(EngineErrorCause::Internal, message))
+                            }
                         }
-                        Err(e) => {
-                            let message = format!("{}", e);
-                            error!("{}", message);
-                            Err(self.engine_error(EngineErrorCause::Internal, message))
-                        }
-                    },
+                    }
                     Err(e) => {
                         let message = format!("{}", e);
                         error!("{}", message);
@@ -353,7 +606,13 @@ impl ObjectStorage for ScalewayOS {
         }
     }
 
-    fn put(&self, bucket_name: &str, object_key: &str, file_path: &str) -> Result<(), EngineError> {
+    fn put_with_progress(
+        &self,
+        bucket_name: &str,
+        object_key: &str,
+        file_path: &str,
+        listeners_helper: Option<&ListenersHelper>,
+    ) -> Result<(), EngineError> {
         // TODO(benjamin): switch to `scaleway-api-rs` once object storage will be supported (https://github.com/Qovery/scaleway-api-rs/issues/12).
         if let Err(message) = ScalewayOS::is_bucket_name_valid(bucket_name) {
             let message = format!(
@@ -365,26 +624,53 @@ impl ObjectStorage for ScalewayOS {
             return Err(self.engine_error(EngineErrorCause::Internal, message));
         }
 
+        let content = match std::fs::read(file_path) {
+            Ok(x) => x,
+            Err(e) => {
+                return Err(self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!(
+                        "error while uploading object {} to bucket {}. {}",
+                        object_key, bucket_name, e
+                    ),
+                ))
+            }
+        };
+        let content_length = content.len();
+
+        let progress = TransferProgress::new(
+            listeners_helper,
+            self.context().execution_id(),
+            format!("Uploading object `{}` to bucket `{}`", object_key, bucket_name),
+            Some(content_length as u64),
+        );
+        progress.started();
+
+        // body is streamed by chunks so we can count uploaded bytes
+        let progress = Arc::new(Mutex::new(progress));
+        let chunks_progress = progress.clone();
+        let chunks = content
+            .chunks(TRANSFER_CHUNK_SIZE)
+            .map(|chunk| chunk.to_vec())
+            .collect::<Vec<_>>();
+        let body = futures::stream::iter(chunks.into_iter().map(move |chunk| {
+            chunks_progress.lock().unwrap().advance(chunk.len() as u64);
+            Ok(chunk.into())
+        }));
+
         let s3_client = self.get_s3_client();
 
         match block_on(s3_client.put_object(PutObjectRequest {
             bucket: bucket_name.to_string(),
             key: object_key.to_string(),
-            body: Some(StreamingBody::from(match std::fs::read(file_path.clone()) {
-                Ok(x) => x,
-                Err(e) => {
-                    return Err(self.engine_error(
-                        EngineErrorCause::Internal,
-                        format!(
-                            "error while uploading object {} to bucket {}. {}",
-                            object_key, bucket_name, e
-                        ),
-                    ))
-                }
-            })),
+            content_length: Some(content_length as i64),
+            body: Some(ByteStream::new_with_size(body, content_length)),
             ..Default::default()
         })) {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                progress.lock().unwrap().finished();
+                Ok(())
+            }
             Err(e) => {
                 let message = format!(
                     "While trying to put object `{}` from bucket `{}`, error: {}",
@@ -400,6 +686,7 @@ impl ObjectStorage for ScalewayOS {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::RefCell;
 
     struct TestCase<'a> {
         bucket_name_input: &'a str,
@@ -438,4 +725,183 @@ mod tests {
             assert_eq!(tc.expected_output, result, "{}", tc.description);
         }
     }
+
+    struct MockObjectVersionsLister {
+        pages: Vec<Result<ObjectVersionsPage, String>>,
+        received_markers: RefCell<Vec<(Option<String>, Option<String>)>>,
+    }
+
+    impl MockObjectVersionsLister {
+        fn new(pages: Vec<Result<ObjectVersionsPage, String>>) -> Self {
+            MockObjectVersionsLister {
+                pages,
+                received_markers: RefCell::new(vec![]),
+            }
+        }
+    }
+
+    impl ObjectVersionsLister for MockObjectVersionsLister {
+        fn list_object_versions(
+            &self,
+            _bucket_name: &str,
+            key_marker: Option<String>,
+            version_id_marker: Option<String>,
+        ) -> Result<ObjectVersionsPage, String> {
+            let mut received_markers = self.received_markers.borrow_mut();
+            let page_index = received_markers.len();
+            received_markers.push((key_marker, version_id_marker));
+
+            match self.pages.get(page_index) {
+                Some(Ok(page)) => Ok(ObjectVersionsPage {
+                    objects: page.objects.clone(),
+                    next_key_marker: page.next_key_marker.clone(),
+                    next_version_id_marker: page.next_version_id_marker.clone(),
+                }),
+                Some(Err(e)) => Err(e.clone()),
+                None => panic!("listing page {} has not been mocked", page_index),
+            }
+        }
+    }
+
+    fn object(key: &str, version_id: Option<&str>) -> ObjectIdentifier {
+        ObjectIdentifier {
+            key: key.to_string(),
+            version_id: version_id.map(|v| v.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_list_all_object_versions_single_page() {
+        // setup:
+        let lister = MockObjectVersionsLister::new(vec![Ok(ObjectVersionsPage {
+            objects: vec![object("a", Some("1")), object("b", None)],
+            next_key_marker: None,
+            next_version_id_marker: None,
+        })]);
+
+        // execute:
+        let result = list_all_object_versions(&lister, "bucket");
+
+        // verify:
+        assert_eq!(Ok(vec![object("a", Some("1")), object("b", None)]), result);
+        assert_eq!(vec![(None, None)], *lister.received_markers.borrow());
+    }
+
+    #[test]
+    fn test_list_all_object_versions_pages_through_all_objects() {
+        // setup:
+        let lister = MockObjectVersionsLister::new(vec![
+            Ok(ObjectVersionsPage {
+                objects: vec![object("a", Some("1")), object("a", Some("2"))],
+                next_key_marker: Some("a".to_string()),
+                next_version_id_marker: Some("2".to_string()),
+            }),
+            Ok(ObjectVersionsPage {
+                objects: vec![object("b", Some("1"))],
+                next_key_marker: Some("b".to_string()),
+                next_version_id_marker: None,
+            }),
+            Ok(ObjectVersionsPage {
+                objects: vec![object("c", Some("1"))],
+                next_key_marker: None,
+                next_version_id_marker: None,
+            }),
+        ]);
+
+        // execute:
+        let result = list_all_object_versions(&lister, "bucket");
+
+        // verify:
+        assert_eq!(
+            Ok(vec![
+                object("a", Some("1")),
+                object("a", Some("2")),
+                object("b", Some("1")),
+                object("c", Some("1")),
+            ]),
+            result
+        );
+        assert_eq!(
+            vec![
+                (None, None),
+                (Some("a".to_string()), Some("2".to_string())),
+                (Some("b".to_string()), None),
+            ],
+            *lister.received_markers.borrow()
+        );
+    }
+
+    #[test]
+    fn test_list_all_object_versions_fails_if_a_page_fails() {
+        // setup:
+        let lister = MockObjectVersionsLister::new(vec![
+            Ok(ObjectVersionsPage {
+                objects: vec![object("a", Some("1"))],
+                next_key_marker: Some("a".to_string()),
+                next_version_id_marker: Some("1".to_string()),
+            }),
+            Err("listing error".to_string()),
+        ]);
+
+        // execute:
+        let result = list_all_object_versions(&lister, "bucket");
+
+        // verify:
+        assert_eq!(Err("listing error".to_string()), result);
+    }
+
+    #[test]
+    fn test_bucket_delete_strategy_allows_bucket_deletion() {
+        // setup:
+        let now = Utc::now();
+        let one_hour = Duration::from_secs(3600);
+
+        struct TestCase<'a> {
+            strategy: BucketDeleteStrategy,
+            bucket_created_at: Option<DateTime<Utc>>,
+            expected_output: bool,
+            description: &'a str,
+        }
+
+        let test_cases: Vec<TestCase> = vec![
+            TestCase {
+                strategy: BucketDeleteStrategy::HardDelete,
+                bucket_created_at: Some(now),
+                expected_output: true,
+                description: "hard delete always deletes the bucket",
+            },
+            TestCase {
+                strategy: BucketDeleteStrategy::Empty,
+                bucket_created_at: Some(now - chrono::Duration::days(2)),
+                expected_output: false,
+                description: "empty never deletes the bucket",
+            },
+            TestCase {
+                strategy: BucketDeleteStrategy::HardDeleteAfter(one_hour),
+                bucket_created_at: Some(now - chrono::Duration::hours(2)),
+                expected_output: true,
+                description: "hard delete after, bucket is older than the given duration",
+            },
+            TestCase {
+                strategy: BucketDeleteStrategy::HardDeleteAfter(one_hour),
+                bucket_created_at: Some(now - chrono::Duration::minutes(10)),
+                expected_output: false,
+                description: "hard delete after, bucket is younger than the given duration",
+            },
+            TestCase {
+                strategy: BucketDeleteStrategy::HardDeleteAfter(one_hour),
+                bucket_created_at: None,
+                expected_output: false,
+                description: "hard delete after, bucket creation date is unknown",
+            },
+        ];
+
+        for tc in test_cases {
+            // execute:
+            let result = tc.strategy.allows_bucket_deletion(tc.bucket_created_at, now);
+
+            // verify:
+            assert_eq!(tc.expected_output, result, "{}", tc.description);
+        }
+    }
 }
@@ -11,9 +11,11 @@ use rusoto_s3::{
 use tokio::io;
 
 use crate::cloud_provider::digitalocean::application::Region as DoRegion;
-use crate::error::{EngineError, EngineErrorCause};
-use crate::models::{Context, StringPath};
-use crate::object_storage::{Kind, ObjectStorage};
+use crate::error::{check_credentials_are_set, EngineError, EngineErrorCause};
+use crate::models::{Context, ListenersHelper, StringPath};
+use crate::object_storage::{
+    credentials_check_error_cause, is_bucket_not_found, Kind, ObjectStorage, TransferProgress,
+};
 use crate::runtime;
 use crate::runtime::block_on;
 
@@ -24,6 +26,7 @@ pub struct Spaces {
     access_key_id: String,
     secret_access_key: String,
     region: DoRegion,
+    credentials_check_bucket: Option<String>,
 }
 
 impl Spaces {
@@ -42,9 +45,16 @@ impl Spaces {
             access_key_id,
             secret_access_key,
             region,
+            credentials_check_bucket: None,
         }
     }
 
+    /// The credentials are checked with a HeadBucket on this bucket, it does not need to exist yet.
+    pub fn with_credentials_check_bucket(mut self, bucket_name: String) -> Self {
+        self.credentials_check_bucket = Some(bucket_name);
+        self
+    }
+
     fn get_endpoint_url_for_region(&self) -> String {
         format!("https://{}.digitaloceanspaces.com", self.region)
     }
@@ -214,8 +224,40 @@ impl ObjectStorage for Spaces {
     }
 
     fn is_valid(&self) -> Result<(), EngineError> {
-        // TODO check valid credentials
-        Ok(())
+        self.validate_credentials()
+    }
+
+    fn validate_credentials(&self) -> Result<(), EngineError> {
+        check_credentials_are_set(&[
+            ("access key id", self.access_key_id.as_str()),
+            ("secret access key", self.secret_access_key.as_str()),
+        ])
+        .map_err(|err| {
+            self.engine_error(
+                EngineErrorCause::User("Check the object storage credentials"),
+                format!("{} for object storage {}", err, self.name_with_id()),
+            )
+        })?;
+
+        let bucket_name = match &self.credentials_check_bucket {
+            Some(bucket_name) => bucket_name,
+            None => return Ok(()),
+        };
+
+        match block_on(self.get_s3_client().head_bucket(HeadBucketRequest {
+            bucket: bucket_name.to_string(),
+            expected_bucket_owner: None,
+        })) {
+            Ok(_) => Ok(()),
+            Err(e) if is_bucket_not_found(&e) => Ok(()),
+            Err(e) => Err(self.engine_error(
+                credentials_check_error_cause(&e),
+                format!(
+                    "Can't access Spaces bucket `{}` in region {}: {}",
+                    bucket_name, self.region, e
+                ),
+            )),
+        }
     }
 
     fn create_bucket(&self, bucket_name: &str) -> Result<(), EngineError> {
@@ -256,7 +298,13 @@ impl ObjectStorage for Spaces {
         unimplemented!()
     }
 
-    fn get(&self, bucket_name: &str, object_key: &str, use_cache: bool) -> Result<(StringPath, File), EngineError> {
+    fn get_with_progress(
+        &self,
+        bucket_name: &str,
+        object_key: &str,
+        use_cache: bool,
+        listeners_helper: Option<&ListenersHelper>,
+    ) -> Result<(StringPath, File), EngineError> {
         let workspace_directory = crate::fs::workspace_directory(
             self.context().workspace_root_dir(),
             self.context().execution_id(),
@@ -277,6 +325,14 @@ impl ObjectStorage for Spaces {
             }
         }
 
+        let mut progress = TransferProgress::new(
+            listeners_helper,
+            self.context().execution_id(),
+            format!("Downloading object `{}` from bucket `{}`", object_key, bucket_name),
+            None,
+        );
+        progress.started();
+
         // retrieve config file from object storage
         let result = retry::retry(Fibonacci::from_millis(3000).take(5), || {
             match runtime::block_on(self.get_object(bucket_name, object_key, file_path.as_str())) {
@@ -302,12 +358,22 @@ impl ObjectStorage for Spaces {
         };
 
         match file {
-            Ok(file) => Ok((file_path, file)),
+            Ok(file) => {
+                progress.advance(file.metadata().map(|m| m.len()).unwrap_or(0));
+                progress.finished();
+                Ok((file_path, file))
+            }
             Err(err) => Err(self.engine_error(EngineErrorCause::Internal, format!("{:?}", err))),
         }
     }
 
-    fn put(&self, bucket_name: &str, object_key: &str, file_path: &str) -> Result<(), EngineError> {
+    fn put_with_progress(
+        &self,
+        bucket_name: &str,
+        object_key: &str,
+        file_path: &str,
+        listeners_helper: Option<&ListenersHelper>,
+    ) -> Result<(), EngineError> {
         // TODO(benjamin): switch to `digitalocean-api-rs` once we'll made the auo-generated lib
         if let Err(message) = Spaces::is_bucket_name_valid(bucket_name) {
             let message = format!(
@@ -320,6 +386,13 @@ impl ObjectStorage for Spaces {
         }
 
         let s3_client = self.get_s3_client();
+        let mut progress = TransferProgress::new(
+            listeners_helper,
+            self.context().execution_id(),
+            format!("Uploading object `{}` to bucket `{}`", object_key, bucket_name),
+            std::fs::metadata(file_path).map(|m| m.len()).ok(),
+        );
+        progress.started();
 
         match block_on(s3_client.put_object(PutObjectRequest {
             bucket: bucket_name.to_string(),
@@ -338,7 +411,11 @@ impl ObjectStorage for Spaces {
             })),
             ..Default::default()
         })) {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                progress.advance(std::fs::metadata(file_path).map(|m| m.len()).unwrap_or(0));
+                progress.finished();
+                Ok(())
+            }
             Err(e) => {
                 let message = format!(
                     "While trying to put object `{}` from bucket `{}`, error: {}",
@@ -1,6 +1,11 @@
 use lazy_static::lazy_static;
+use retry::delay::Exponential;
+use retry::Error::Operation;
+use retry::OperationResult;
+use rusoto_core::RusotoError;
 use std::future::Future;
 use std::sync::Mutex;
+use std::time::Duration;
 use tokio::runtime::{Builder, Runtime};
 
 lazy_static! {
@@ -13,6 +18,145 @@ lazy_static! {
     });
 }
 
+/// Throttled calls are retried 5 times, waiting 1s, 2s, 4s, 8s then 16s.
+const THROTTLING_FIRST_RETRY_DELAY_IN_MILLIS: u64 = 1000;
+const THROTTLING_MAX_RETRIES: usize = 5;
+
+/// Error codes returned by AWS (and compatible) APIs when calls are rate limited.
+const THROTTLING_ERROR_CODES: [&str; 4] = [
+    "ThrottlingException",
+    "TooManyRequestsException",
+    "RequestLimitExceeded",
+    "SlowDown",
+];
+
 pub fn block_on<F: Future>(future: F) -> F::Output {
     TOKIO_RUNTIME.lock().unwrap().block_on(future)
 }
+
+/// Runs the rusoto call built by `call`, retried with an exponential backoff as long as the API throttles it.
+/// Any other error is returned right away.
+pub fn block_on_with_throttling_retry<T, E, F, Fut>(call: F) -> Result<T, RusotoError<E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, RusotoError<E>>>,
+{
+    retry_on_throttling(
+        Exponential::from_millis_with_factor(THROTTLING_FIRST_RETRY_DELAY_IN_MILLIS, 2.0).take(THROTTLING_MAX_RETRIES),
+        call,
+    )
+}
+
+pub fn is_throttling<E>(error: &RusotoError<E>) -> bool {
+    match error {
+        RusotoError::Unknown(response) => {
+            response.status.as_u16() == 429
+                || THROTTLING_ERROR_CODES
+                    .iter()
+                    .any(|code| response.body_as_str().contains(code))
+        }
+        _ => false,
+    }
+}
+
+fn retry_on_throttling<T, E, F, Fut, D>(delays: D, mut call: F) -> Result<T, RusotoError<E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, RusotoError<E>>>,
+    D: IntoIterator<Item = Duration>,
+{
+    let result = retry::retry(delays, || match block_on(call()) {
+        Ok(output) => OperationResult::Ok(output),
+        Err(err) if is_throttling(&err) => {
+            warn!("cloud provider API call throttled, retrying...");
+            OperationResult::Retry(err)
+        }
+        Err(err) => OperationResult::Err(err),
+    });
+
+    match result {
+        Ok(output) => Ok(output),
+        Err(Operation { error, .. }) => Err(error),
+        Err(retry::Error::Internal(message)) => Err(RusotoError::Validation(message)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::{is_throttling, retry_on_throttling};
+    use rusoto_core::request::BufferedHttpResponse;
+    use rusoto_core::RusotoError;
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    fn response(status: u16, body: &str) -> RusotoError<()> {
+        RusotoError::Unknown(BufferedHttpResponse {
+            status: reqwest::StatusCode::from_u16(status).unwrap(),
+            body: body.to_string().into(),
+            headers: Default::default(),
+        })
+    }
+
+    /// Answers like ECR under load: the first calls are throttled.
+    struct ThrottlingClient {
+        throttled_calls: u32,
+        calls: Cell<u32>,
+    }
+
+    impl ThrottlingClient {
+        async fn describe_images(&self) -> Result<Vec<String>, RusotoError<()>> {
+            self.calls.set(self.calls.get() + 1);
+
+            match self.calls.get() <= self.throttled_calls {
+                true => Err(response(
+                    400,
+                    r#"{"__type": "ThrottlingException", "message": "Rate exceeded"}"#,
+                )),
+                false => Ok(vec!["v1".to_string()]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_throttling() {
+        assert!(is_throttling(&response(400, r#"{"__type": "ThrottlingException"}"#)));
+        assert!(is_throttling(&response(429, "")));
+        assert!(is_throttling(&response(503, "<Code>SlowDown</Code>")));
+        assert!(!is_throttling(&response(
+            400,
+            r#"{"__type": "RepositoryNotFoundException"}"#
+        )));
+        assert!(!is_throttling(&RusotoError::<()>::Validation("invalid".to_string())));
+    }
+
+    #[test]
+    fn test_retry_on_throttling() {
+        // setup:
+        let delays = vec![Duration::from_millis(1); 3];
+        let throttled_twice = ThrottlingClient {
+            throttled_calls: 2,
+            calls: Cell::new(0),
+        };
+        let always_throttled = ThrottlingClient {
+            throttled_calls: u32::MAX,
+            calls: Cell::new(0),
+        };
+        let failing_calls = Cell::new(0);
+
+        // execute:
+        let result = retry_on_throttling(delays.clone(), || throttled_twice.describe_images());
+        let throttled_result = retry_on_throttling(delays.clone(), || always_throttled.describe_images());
+        let failing_result = retry_on_throttling(delays, || {
+            failing_calls.set(failing_calls.get() + 1);
+            async { Err::<(), _>(response(400, r#"{"__type": "RepositoryNotFoundException"}"#)) }
+        });
+
+        // verify:
+        assert_eq!(result.unwrap(), vec!["v1".to_string()]);
+        assert_eq!(throttled_twice.calls.get(), 3);
+        assert!(is_throttling(&throttled_result.unwrap_err()));
+        assert_eq!(always_throttled.calls.get(), 4);
+        assert!(failing_result.is_err());
+        assert_eq!(failing_calls.get(), 1);
+    }
+}
@@ -0,0 +1,443 @@
+//! Secret references resolved at deploy time, so raw secrets don't have to be part of the engine requests.
+//!
+//! Fields accepting a reference hold either a plain value or one of:
+//!
+//! | reference               | resolved value                                                  |
+//! |-------------------------|-----------------------------------------------------------------|
+//! | `vault://<path>#<key>`  | `key` of the Vault KV secret at `path` (KV v1 or v2 engine)     |
+//! | `awssm://<name>`        | secret string of the AWS Secrets Manager secret `name`          |
+//! | `awssm://<name>#<key>`  | `key` of the JSON object stored by the secret `name`            |
+//!
+//! References are resolved through the [`Secrets`] set on the [`Context`], messages only ever show the reference.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use chrono::Duration;
+use serde_json::Value;
+
+use crate::cmd::utilities::exec_with_envs_and_output;
+use crate::error::StringError;
+use crate::models::Context;
+
+const VAULT_SCHEME: &str = "vault://";
+const AWS_SECRETS_MANAGER_SCHEME: &str = "awssm://";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SecretReference {
+    Vault { path: String, key: String },
+    AwsSecretsManager { name: String, key: Option<String> },
+}
+
+impl SecretReference {
+    /// `None` for plain values, an error for references missing their path, name or key.
+    pub fn parse(value: &str) -> Result<Option<SecretReference>, StringError> {
+        let split_key = |reference: &str| match reference.split_once('#') {
+            Some((location, key)) => (location.to_string(), Some(key.to_string())),
+            None => (reference.to_string(), None),
+        };
+
+        let reference = if let Some(reference) = value.strip_prefix(VAULT_SCHEME) {
+            match split_key(reference) {
+                (path, Some(key)) => SecretReference::Vault { path, key },
+                (_, None) => return Err(format!("Vault secret reference `{}` has no `#key`", value)),
+            }
+        } else if let Some(reference) = value.strip_prefix(AWS_SECRETS_MANAGER_SCHEME) {
+            let (name, key) = split_key(reference);
+            SecretReference::AwsSecretsManager { name, key }
+        } else {
+            return Ok(None);
+        };
+
+        let is_incomplete = match &reference {
+            SecretReference::Vault { path, key } => path.is_empty() || key.is_empty(),
+            SecretReference::AwsSecretsManager { name, key } => name.is_empty() || key.as_deref() == Some(""),
+        };
+
+        match is_incomplete {
+            true => Err(format!("secret reference `{}` is incomplete", value)),
+            false => Ok(Some(reference)),
+        }
+    }
+}
+
+impl fmt::Display for SecretReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretReference::Vault { path, key } => write!(f, "{}{}#{}", VAULT_SCHEME, path, key),
+            SecretReference::AwsSecretsManager { name, key: Some(key) } => {
+                write!(f, "{}{}#{}", AWS_SECRETS_MANAGER_SCHEME, name, key)
+            }
+            SecretReference::AwsSecretsManager { name, key: None } => {
+                write!(f, "{}{}", AWS_SECRETS_MANAGER_SCHEME, name)
+            }
+        }
+    }
+}
+
+/// Fetches the value of a secret reference from a secrets manager.
+pub trait SecretsResolver: Send + Sync {
+    fn resolve(&self, reference: &SecretReference) -> Result<String, StringError>;
+}
+
+/// Vault server reached over its HTTP API with a token allowed to read the referenced paths.
+pub struct VaultSecretsResolver {
+    address: String,
+    token: String,
+}
+
+impl VaultSecretsResolver {
+    pub fn new(address: &str, token: &str) -> Self {
+        VaultSecretsResolver {
+            address: address.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+        }
+    }
+}
+
+impl SecretsResolver for VaultSecretsResolver {
+    fn resolve(&self, reference: &SecretReference) -> Result<String, StringError> {
+        let (path, key) = match reference {
+            SecretReference::Vault { path, key } => (path, key),
+            _ => return Err(format!("{} is not a Vault secret", reference)),
+        };
+
+        let response = reqwest::blocking::Client::new()
+            .get(format!("{}/v1/{}", self.address, path.trim_start_matches('/')))
+            .header("X-Vault-Token", self.token.as_str())
+            .send()
+            .map_err(|err| format!("can't reach Vault to read {}: {}", reference, err))?;
+
+        if !response.status().is_success() {
+            return Err(format!("Vault answered {} to read {}", response.status(), reference));
+        }
+
+        let body = response
+            .json::<Value>()
+            .map_err(|err| format!("can't parse Vault answer to read {}: {}", reference, err))?;
+
+        vault_secret_value(&body, key.as_str()).ok_or_else(|| format!("{} doesn't exist", reference))
+    }
+}
+
+/// KV v2 secrets are nested under `data.data`, KV v1 ones are directly under `data`.
+fn vault_secret_value(body: &Value, key: &str) -> Option<String> {
+    let data = &body["data"];
+    let secret = match data["data"].is_object() && data["metadata"].is_object() {
+        true => &data["data"],
+        false => data,
+    };
+
+    json_string(&secret[key])
+}
+
+/// AWS Secrets Manager read with the AWS CLI, the credentials need the `secretsmanager:GetSecretValue` permission.
+pub struct AwsSecretsManagerResolver {
+    region: String,
+    access_key_id: String,
+    secret_access_key: String,
+}
+
+impl AwsSecretsManagerResolver {
+    pub fn new(region: &str, access_key_id: &str, secret_access_key: &str) -> Self {
+        AwsSecretsManagerResolver {
+            region: region.to_string(),
+            access_key_id: access_key_id.to_string(),
+            secret_access_key: secret_access_key.to_string(),
+        }
+    }
+}
+
+impl SecretsResolver for AwsSecretsManagerResolver {
+    fn resolve(&self, reference: &SecretReference) -> Result<String, StringError> {
+        let (name, key) = match reference {
+            SecretReference::AwsSecretsManager { name, key } => (name, key),
+            _ => return Err(format!("{} is not an AWS Secrets Manager secret", reference)),
+        };
+
+        // the secret is read from the output, it must not be logged
+        let mut secret_lines = vec![];
+        let mut stderr = vec![];
+        let result = exec_with_envs_and_output(
+            "aws",
+            vec![
+                "secretsmanager",
+                "get-secret-value",
+                "--secret-id",
+                name.as_str(),
+                "--query",
+                "SecretString",
+                "--output",
+                "text",
+                "--region",
+                self.region.as_str(),
+            ],
+            vec![
+                ("AWS_ACCESS_KEY_ID", self.access_key_id.as_str()),
+                ("AWS_SECRET_ACCESS_KEY", self.secret_access_key.as_str()),
+            ],
+            |line| {
+                if let Ok(line) = line {
+                    secret_lines.push(line)
+                }
+            },
+            |line| {
+                if let Ok(line) = line {
+                    stderr.push(line)
+                }
+            },
+            Duration::seconds(30),
+        );
+
+        if let Err(err) = result {
+            return Err(format!(
+                "can't read {}: {} {}",
+                reference,
+                err.message.unwrap_or_default(),
+                stderr.join(" ")
+            ));
+        }
+
+        aws_secret_value(secret_lines.join("\n").as_str(), key.as_deref())
+            .ok_or_else(|| format!("{} doesn't exist", reference))
+    }
+}
+
+/// The whole secret string without key, otherwise the key of the JSON object it holds.
+fn aws_secret_value(secret_string: &str, key: Option<&str>) -> Option<String> {
+    match key {
+        None => Some(secret_string.to_string()),
+        Some(key) => serde_json::from_str::<Value>(secret_string)
+            .ok()
+            .and_then(|secret| json_string(&secret[key])),
+    }
+}
+
+fn json_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(_) | Value::Bool(_) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Vault and AWS Secrets Manager together, each reference is resolved by the manager of its scheme.
+#[derive(Default)]
+pub struct SecretsManagers {
+    vault: Option<VaultSecretsResolver>,
+    aws_secrets_manager: Option<AwsSecretsManagerResolver>,
+}
+
+impl SecretsManagers {
+    pub fn with_vault(mut self, vault: VaultSecretsResolver) -> Self {
+        self.vault = Some(vault);
+        self
+    }
+
+    pub fn with_aws_secrets_manager(mut self, aws_secrets_manager: AwsSecretsManagerResolver) -> Self {
+        self.aws_secrets_manager = Some(aws_secrets_manager);
+        self
+    }
+}
+
+impl SecretsResolver for SecretsManagers {
+    fn resolve(&self, reference: &SecretReference) -> Result<String, StringError> {
+        let resolver: Option<&dyn SecretsResolver> = match reference {
+            SecretReference::Vault { .. } => self.vault.as_ref().map(|r| r as &dyn SecretsResolver),
+            SecretReference::AwsSecretsManager { .. } => {
+                self.aws_secrets_manager.as_ref().map(|r| r as &dyn SecretsResolver)
+            }
+        };
+
+        match resolver {
+            Some(resolver) => resolver.resolve(reference),
+            None => Err(format!(
+                "{} can't be resolved, its secrets manager isn't configured",
+                reference
+            )),
+        }
+    }
+}
+
+/// Secrets manager the references are resolved with. Clones share the same resolver.
+#[derive(Clone)]
+pub struct Secrets {
+    resolver: Arc<dyn SecretsResolver>,
+}
+
+impl Secrets {
+    pub fn new<R>(resolver: R) -> Self
+    where
+        R: SecretsResolver + 'static,
+    {
+        Secrets {
+            resolver: Arc::new(resolver),
+        }
+    }
+}
+
+// secrets are not part of the context identity
+impl PartialEq for Secrets {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Secrets {}
+
+impl Hash for Secrets {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+impl fmt::Debug for Secrets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Secrets").finish()
+    }
+}
+
+/// `value` itself when it's a plain value, otherwise the secret it references.
+pub fn resolve(context: &Context, value: &str) -> Result<String, StringError> {
+    let reference = match SecretReference::parse(value)? {
+        Some(reference) => reference,
+        None => return Ok(value.to_string()),
+    };
+
+    match context.secrets() {
+        Some(secrets) => secrets.resolver.resolve(&reference),
+        None => Err(format!(
+            "{} can't be resolved, no secrets manager is configured",
+            reference
+        )),
+    }
+}
+
+/// Whether `value` is a secret reference rather than a plain value.
+pub fn is_secret_reference(value: &str) -> bool {
+    value.starts_with(VAULT_SCHEME) || value.starts_with(AWS_SECRETS_MANAGER_SCHEME)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::error::StringError;
+    use crate::models::Context;
+    use crate::secrets::{
+        aws_secret_value, resolve, vault_secret_value, SecretReference, Secrets, SecretsManagers, SecretsResolver,
+    };
+
+    struct MockSecretsResolver {
+        secrets: BTreeMap<String, String>,
+    }
+
+    impl SecretsResolver for MockSecretsResolver {
+        fn resolve(&self, reference: &SecretReference) -> Result<String, StringError> {
+            self.secrets
+                .get(&reference.to_string())
+                .cloned()
+                .ok_or_else(|| format!("{} doesn't exist", reference))
+        }
+    }
+
+    fn context() -> Context {
+        Context::new(
+            "execution-id".to_string(),
+            "/tmp".to_string(),
+            "lib".to_string(),
+            false,
+            None,
+            vec![],
+            None,
+        )
+    }
+
+    #[test]
+    fn test_parse_secret_reference() {
+        // execute & verify:
+        assert_eq!(SecretReference::parse("p4ssw0rd"), Ok(None));
+        assert_eq!(
+            SecretReference::parse("vault://secret/data/db#password"),
+            Ok(Some(SecretReference::Vault {
+                path: "secret/data/db".to_string(),
+                key: "password".to_string(),
+            }))
+        );
+        assert_eq!(
+            SecretReference::parse("awssm://prod/db"),
+            Ok(Some(SecretReference::AwsSecretsManager {
+                name: "prod/db".to_string(),
+                key: None,
+            }))
+        );
+        assert_eq!(
+            SecretReference::parse("awssm://prod/db#password"),
+            Ok(Some(SecretReference::AwsSecretsManager {
+                name: "prod/db".to_string(),
+                key: Some("password".to_string()),
+            }))
+        );
+        assert!(SecretReference::parse("vault://secret/data/db").is_err());
+        assert!(SecretReference::parse("vault://#password").is_err());
+        assert!(SecretReference::parse("awssm://prod/db#").is_err());
+        assert_eq!(
+            SecretReference::parse("awssm://prod/db#password")
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "awssm://prod/db#password"
+        );
+    }
+
+    #[test]
+    fn test_resolve_secrets() {
+        // setup:
+        let resolver = MockSecretsResolver {
+            secrets: vec![("vault://secret/data/db#password".to_string(), "s3cr3t".to_string())]
+                .into_iter()
+                .collect(),
+        };
+        let context = context().with_secrets(Secrets::new(resolver));
+
+        // execute & verify:
+        assert_eq!(resolve(&context, "plain"), Ok("plain".to_string()));
+        assert_eq!(
+            resolve(&context, "vault://secret/data/db#password"),
+            Ok("s3cr3t".to_string())
+        );
+        assert_eq!(
+            resolve(&context, "vault://secret/data/db#login"),
+            Err("vault://secret/data/db#login doesn't exist".to_string())
+        );
+        assert_eq!(
+            resolve(&self::context(), "awssm://prod/db"),
+            Err("awssm://prod/db can't be resolved, no secrets manager is configured".to_string())
+        );
+        assert_eq!(
+            SecretsManagers::default().resolve(&SecretReference::AwsSecretsManager {
+                name: "prod/db".to_string(),
+                key: None,
+            }),
+            Err("awssm://prod/db can't be resolved, its secrets manager isn't configured".to_string())
+        );
+    }
+
+    #[test]
+    fn test_secret_values() {
+        // setup:
+        let kv_v1 = serde_json::json!({"data": {"password": "v1-s3cr3t", "port": 5432}});
+        let kv_v2 = serde_json::json!({"data": {"data": {"password": "v2-s3cr3t"}, "metadata": {"version": 3}}});
+
+        // execute & verify:
+        assert_eq!(vault_secret_value(&kv_v1, "password"), Some("v1-s3cr3t".to_string()));
+        assert_eq!(vault_secret_value(&kv_v1, "port"), Some("5432".to_string()));
+        assert_eq!(vault_secret_value(&kv_v2, "password"), Some("v2-s3cr3t".to_string()));
+        assert_eq!(vault_secret_value(&kv_v2, "login"), None);
+        assert_eq!(aws_secret_value("s3cr3t", None), Some("s3cr3t".to_string()));
+        assert_eq!(
+            aws_secret_value(r#"{"password": "s3cr3t"}"#, Some("password")),
+            Some("s3cr3t".to_string())
+        );
+        assert_eq!(aws_secret_value("s3cr3t", Some("password")), None);
+    }
+}
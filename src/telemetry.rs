@@ -0,0 +1,269 @@
+//! Optional OpenTelemetry traces and metrics around the major engine operations.
+//!
+//! Nothing is recorded unless a [`Telemetry`] is set on the [`Context`], the operation is then just called.
+//! Exporters are installed by the engine caller, the engine only uses the tracer and meter it's given.
+//!
+//! Every operation is recorded as a span named after it (`build`, `push`, `terraform_apply`, `service_deploy`),
+//! with `service_type` and `provider` attributes, and as the following metrics labeled the same way:
+//!
+//! | metric                                   | kind      | unit | description                            |
+//! |------------------------------------------|-----------|------|----------------------------------------|
+//! | `qovery_engine.build.duration`           | histogram | s    | application image build                |
+//! | `qovery_engine.push.duration`            | histogram | s    | application image push to the registry |
+//! | `qovery_engine.terraform_apply.duration` | histogram | s    | terraform init, plan and apply         |
+//! | `qovery_engine.service_deploy.duration`  | histogram | s    | deployment of a single service         |
+//! | `qovery_engine.operation.failures`       | counter   |      | failed operations, labeled `operation` |
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::Instant;
+
+use opentelemetry::global::BoxedTracer;
+use opentelemetry::metrics::{Counter, Meter, ValueRecorder};
+use opentelemetry::trace::{StatusCode, TraceContextExt, Tracer};
+use opentelemetry::{global, KeyValue, Unit};
+
+use crate::models::Context;
+
+const INSTRUMENTATION_NAME: &str = "qovery-engine";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Build,
+    Push,
+    TerraformApply,
+    ServiceDeploy,
+}
+
+impl Operation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::Build => "build",
+            Operation::Push => "push",
+            Operation::TerraformApply => "terraform_apply",
+            Operation::ServiceDeploy => "service_deploy",
+        }
+    }
+
+    fn duration_metric_name(&self) -> String {
+        format!("qovery_engine.{}.duration", self.name())
+    }
+}
+
+/// Tracer and instruments the engine operations are recorded with. Clones share the same instruments.
+#[derive(Clone)]
+pub struct Telemetry {
+    instruments: Arc<Instruments>,
+}
+
+struct Instruments {
+    tracer: BoxedTracer,
+    build_duration: ValueRecorder<f64>,
+    push_duration: ValueRecorder<f64>,
+    terraform_apply_duration: ValueRecorder<f64>,
+    service_deploy_duration: ValueRecorder<f64>,
+    failures: Counter<u64>,
+}
+
+impl Telemetry {
+    pub fn new(tracer: BoxedTracer, meter: Meter) -> Self {
+        let duration = |operation: Operation| {
+            meter
+                .f64_value_recorder(operation.duration_metric_name())
+                .with_description(format!("Duration of the engine {} operations", operation.name()))
+                .with_unit(Unit::new("s"))
+                .init()
+        };
+
+        Telemetry {
+            instruments: Arc::new(Instruments {
+                tracer,
+                build_duration: duration(Operation::Build),
+                push_duration: duration(Operation::Push),
+                terraform_apply_duration: duration(Operation::TerraformApply),
+                service_deploy_duration: duration(Operation::ServiceDeploy),
+                failures: meter
+                    .u64_counter("qovery_engine.operation.failures")
+                    .with_description("Failed engine operations")
+                    .init(),
+            }),
+        }
+    }
+
+    /// Use the tracer and meter providers installed globally by the exporter pipelines.
+    pub fn from_global() -> Self {
+        Telemetry::new(
+            global::tracer_with_version(INSTRUMENTATION_NAME, env!("CARGO_PKG_VERSION")),
+            global::meter_with_version(INSTRUMENTATION_NAME, env!("CARGO_PKG_VERSION")),
+        )
+    }
+
+    fn duration_recorder(&self, operation: Operation) -> &ValueRecorder<f64> {
+        match operation {
+            Operation::Build => &self.instruments.build_duration,
+            Operation::Push => &self.instruments.push_duration,
+            Operation::TerraformApply => &self.instruments.terraform_apply_duration,
+            Operation::ServiceDeploy => &self.instruments.service_deploy_duration,
+        }
+    }
+
+    fn record<T, E, F>(&self, operation: Operation, service_type: &str, provider: &str, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        let labels = vec![
+            KeyValue::new("service_type", service_type.to_string()),
+            KeyValue::new("provider", provider.to_string()),
+        ];
+
+        self.instruments.tracer.in_span(operation.name(), |cx| {
+            let span = cx.span();
+            for label in labels.iter() {
+                span.set_attribute(label.clone());
+            }
+
+            let started_at = Instant::now();
+            let result = f();
+            self.duration_recorder(operation)
+                .record(started_at.elapsed().as_secs_f64(), &labels);
+
+            if result.is_err() {
+                span.set_status(StatusCode::Error, format!("{} failed", operation.name()));
+
+                let mut failure_labels = labels.clone();
+                failure_labels.push(KeyValue::new("operation", operation.name()));
+                self.instruments.failures.add(1, &failure_labels);
+            }
+
+            result
+        })
+    }
+}
+
+// telemetry is not part of the context identity
+impl PartialEq for Telemetry {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Telemetry {}
+
+impl Hash for Telemetry {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+impl fmt::Debug for Telemetry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Telemetry").finish()
+    }
+}
+
+/// Record `f` as `operation` when telemetry is enabled on the context, otherwise `f` is just called.
+pub fn record<T, E, F>(
+    context: &Context,
+    operation: Operation,
+    service_type: &str,
+    provider: &str,
+    f: F,
+) -> Result<T, E>
+where
+    F: FnOnce() -> Result<T, E>,
+{
+    match context.telemetry() {
+        Some(telemetry) => telemetry.record(operation, service_type, provider, f),
+        None => f(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    use opentelemetry::global;
+    use opentelemetry::metrics::noop::NoopMeterProvider;
+    use opentelemetry::metrics::MeterProvider;
+    use opentelemetry::sdk::export::trace::stdout;
+    use opentelemetry::sdk::trace::TracerProvider;
+
+    use crate::models::Context;
+    use crate::telemetry::{record, Operation, Telemetry};
+
+    #[derive(Clone, Debug, Default)]
+    struct SpansBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SpansBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn context() -> Context {
+        Context::new(
+            "execution-id".to_string(),
+            "/tmp".to_string(),
+            "lib".to_string(),
+            false,
+            None,
+            vec![],
+            None,
+        )
+    }
+
+    #[test]
+    fn test_operations_are_traced() {
+        // setup:
+        let spans = SpansBuffer::default();
+        let _ = global::set_tracer_provider(
+            TracerProvider::builder()
+                .with_simple_exporter(stdout::Exporter::new(spans.clone(), false))
+                .build(),
+        );
+        let telemetry = Telemetry::new(
+            global::tracer("engine-test"),
+            NoopMeterProvider::new().meter("engine-test", None),
+        );
+        let context = context().with_telemetry(telemetry);
+
+        // execute: a stubbed deploy, applying terraform then failing
+        let result: Result<(), String> = record(&context, Operation::ServiceDeploy, "PostgreSQL", "AWS", || {
+            record(&context, Operation::TerraformApply, "PostgreSQL", "AWS", || {
+                Ok::<_, String>(())
+            })?;
+            Err("helm upgrade failed".to_string())
+        });
+
+        // verify:
+        assert!(result.is_err());
+        let spans = String::from_utf8(spans.0.lock().unwrap().clone()).unwrap();
+        let spans = spans.lines().collect::<Vec<_>>();
+        assert_eq!(spans.len(), 2);
+        assert!(spans[0].contains("name: \"terraform_apply\""));
+        assert!(spans[0].contains("status_code: Unset"));
+        assert!(spans[1].contains("name: \"service_deploy\""));
+        assert!(spans[1].contains("status_code: Error"));
+        for span in spans {
+            assert!(span.contains("String(\"PostgreSQL\")"));
+            assert!(span.contains("String(\"AWS\")"));
+        }
+    }
+
+    #[test]
+    fn test_operations_without_telemetry() {
+        // setup:
+        let context = context();
+
+        // execute & verify:
+        assert!(context.telemetry().is_none());
+        assert_eq!(
+            record(&context, Operation::Build, "Application", "AWS", || Ok::<_, String>(42)),
+            Ok(42)
+        );
+    }
+}
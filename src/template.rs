@@ -17,34 +17,20 @@ where
     P: AsRef<Path>,
 {
     // generate j2 templates
-    let rendered_templates = match generate_j2_template_files(from_dir.as_ref(), context) {
-        Ok(rt) => rt,
+    let rendered_templates = match render_j2_template_files(from_dir.as_ref(), context, true) {
+        Ok((rt, errors)) if errors.is_empty() => rt,
+        Ok((_, errors)) => {
+            let (template, e) = &errors[0];
+            error!("{:?}", e);
+            let error_msg = template_error_message(format!("{}{}", from_dir.as_ref().display(), template).as_str(), e);
+
+            error!("{}", context.clone().into_json());
+            error!("{}", error_msg.as_str());
+            return Err(SimpleError::new(SimpleErrorKind::Other, Some(error_msg)));
+        }
         Err(e) => {
             error!("{:?}", &e);
-            let error_msg = match e.kind {
-                tera::ErrorKind::TemplateNotFound(x) => format!("template not found: {}", x),
-                tera::ErrorKind::Msg(x) => format!("tera error: {}", x),
-                tera::ErrorKind::CircularExtend { tpl, inheritance_chain } => format!(
-                    "circular extend - template: {}, inheritance chain: {:?}",
-                    tpl, inheritance_chain
-                ),
-                tera::ErrorKind::MissingParent { current, parent } => {
-                    format!("missing parent - current: {}, parent: {}", current, parent)
-                }
-                tera::ErrorKind::FilterNotFound(x) => format!("filter not found: {}", x),
-                tera::ErrorKind::TestNotFound(x) => format!("test not found: {}", x),
-                tera::ErrorKind::InvalidMacroDefinition(x) => {
-                    format!("invalid macro definition: {}", x)
-                }
-                tera::ErrorKind::FunctionNotFound(x) => format!("function not found: {}", x),
-                tera::ErrorKind::Json(x) => format!("json error: {:?}", x),
-                tera::ErrorKind::CallFunction(x) => format!("call function: {}", x),
-                tera::ErrorKind::CallFilter(x) => format!("call filter: {}", x),
-                tera::ErrorKind::CallTest(x) => format!("call test: {}", x),
-                tera::ErrorKind::__Nonexhaustive => "non exhaustive error".to_string(),
-                tera::ErrorKind::Io(x) => format!("io error {:?}", x),
-                tera::ErrorKind::Utf8Conversion { .. } => format!("utf-8 conversion issue"),
-            };
+            let error_msg = tera_error_message(&e);
 
             error!("{}", context.clone().into_json());
             error!("{}", error_msg.as_str());
@@ -72,7 +58,54 @@ where
     }
 }
 
-pub fn generate_j2_template_files<P>(root_dir: P, context: &Context) -> Result<Vec<RenderedTemplate>, TeraError>
+/// Render every j2 template of `from_dirs` into `to_dir` without stopping at the first failure.
+/// Returns one message per template which can't be rendered, so all broken templates can be fixed at once.
+pub fn validate_templates<S, P>(from_dirs: &[S], to_dir: P, context: &Context) -> Result<(), Vec<String>>
+where
+    S: AsRef<Path>,
+    P: AsRef<Path>,
+{
+    let mut errors: Vec<String> = vec![];
+
+    for from_dir in from_dirs {
+        let from_dir_str = from_dir.as_ref().to_str().unwrap_or_default();
+
+        match render_j2_template_files(from_dir.as_ref(), context, false) {
+            Ok((rendered_templates, render_errors)) => {
+                for (template, err) in render_errors {
+                    errors.push(template_error_message(
+                        format!("{}{}", from_dir_str, template).as_str(),
+                        &err,
+                    ));
+                }
+
+                if let Err(err) = write_rendered_templates(&rendered_templates, to_dir.as_ref()) {
+                    errors.push(format!(
+                        "{}: {}",
+                        from_dir_str,
+                        err.message
+                            .unwrap_or_else(|| "can't write rendered templates".to_string())
+                    ));
+                }
+            }
+            // templates can't even be parsed
+            Err(err) => errors.push(format!("{}: {}", from_dir_str, tera_error_message(&err))),
+        }
+    }
+
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors),
+    }
+}
+
+/// Render all j2 templates of `root_dir`, returns rendered templates along with the (template path, error) of the
+/// templates which failed to render. Stops at the first failure when `stop_on_error` is set.
+fn render_j2_template_files<P>(
+    root_dir: P,
+    context: &Context,
+    stop_on_error: bool,
+) -> Result<(Vec<RenderedTemplate>, Vec<(String, TeraError)>), TeraError>
 where
     P: AsRef<Path>,
 {
@@ -91,6 +124,7 @@ where
         .collect::<Vec<_>>();
 
     let mut results: Vec<RenderedTemplate> = vec![];
+    let mut errors: Vec<(String, TeraError)> = vec![];
 
     for file in files.into_iter() {
         let path_str = file.path().to_str().unwrap();
@@ -101,12 +135,76 @@ where
         let j2_root_path: String = j2_path_split.as_slice()[..j2_path_split.len() - 1].join("/");
         let file_name = j2_file_name.replace(".j2", "");
 
-        let content = tera.render(&j2_path[1..], &context)?;
+        match tera.render(&j2_path[1..], &context) {
+            Ok(content) => results.push(RenderedTemplate::new(j2_root_path, file_name, content)),
+            Err(err) => {
+                errors.push((j2_path.clone(), err));
+                if stop_on_error {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok((results, errors))
+}
+
+/// Error message of a template which can't be rendered, naming the missing variable if that's the cause.
+fn template_error_message(template_path: &str, e: &TeraError) -> String {
+    match missing_variable_name(e) {
+        Some(variable) => format!(
+            "variable `{}` is missing from the context while rendering template `{}`",
+            variable, template_path
+        ),
+        None => format!("template `{}`: {}", template_path, tera_error_message(e)),
+    }
+}
+
+/// Tera nests the "Variable `x` not found in context" error in the render error sources.
+fn missing_variable_name(e: &TeraError) -> Option<String> {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(e);
 
-        results.push(RenderedTemplate::new(j2_root_path, file_name, content));
+    while let Some(err) = source {
+        let message = err.to_string();
+        if message.contains("` not found in context") {
+            if let Some(variable) = message
+                .strip_prefix("Variable `")
+                .and_then(|m| m.split("` not found in context").next())
+            {
+                return Some(variable.to_string());
+            }
+        }
+        source = err.source();
     }
 
-    Ok(results)
+    None
+}
+
+fn tera_error_message(e: &TeraError) -> String {
+    match &e.kind {
+        tera::ErrorKind::TemplateNotFound(x) => format!("template not found: {}", x),
+        tera::ErrorKind::Msg(x) => format!("tera error: {}", x),
+        tera::ErrorKind::CircularExtend { tpl, inheritance_chain } => format!(
+            "circular extend - template: {}, inheritance chain: {:?}",
+            tpl, inheritance_chain
+        ),
+        tera::ErrorKind::MissingParent { current, parent } => {
+            format!("missing parent - current: {}, parent: {}", current, parent)
+        }
+        tera::ErrorKind::FilterNotFound(x) => format!("filter not found: {}", x),
+        tera::ErrorKind::TestNotFound(x) => format!("test not found: {}", x),
+        tera::ErrorKind::InvalidMacroDefinition(x) => {
+            format!("invalid macro definition: {}", x)
+        }
+        tera::ErrorKind::FunctionNotFound(x) => format!("function not found: {}", x),
+        tera::ErrorKind::Json(x) => format!("json error: {:?}", x),
+        tera::ErrorKind::CallFunction(x) => format!("call function: {}", x),
+        tera::ErrorKind::CallFilter(x) => format!("call filter: {}", x),
+        tera::ErrorKind::CallTest(x) => format!("call test: {}", x),
+        tera::ErrorKind::__Nonexhaustive => "non exhaustive error".to_string(),
+        tera::ErrorKind::Io(x) => format!("io error {:?}", x),
+        tera::ErrorKind::Utf8Conversion { .. } => format!("utf-8 conversion issue"),
+    }
 }
 
 pub fn write_rendered_templates(rendered_templates: &[RenderedTemplate], into: &Path) -> Result<(), SimpleError> {
@@ -169,3 +267,78 @@ impl RenderedTemplate {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_validate_templates_reports_all_failures() {
+        // setup:
+        let tmp_dir = TempDir::new("validate_templates").expect("error creating temporary dir");
+        let templates_dir = tmp_dir.path().join("templates");
+        let rendered_dir = tmp_dir.path().join("rendered");
+        fs::create_dir_all(templates_dir.join("sub")).expect("error creating directory");
+
+        let templates = vec![
+            ("valid.j2.tf", "name = \"{{ name }}\""),
+            ("broken.j2.tf", "port = {{ port }}"),
+            ("sub/broken.j2.yaml", "disk: {{ disk_size }}"),
+        ];
+        for (path, content) in templates {
+            let mut file = File::create(templates_dir.join(path)).expect("error creating file");
+            file.write_all(content.as_bytes()).expect("error writing into file");
+        }
+
+        let mut context = Context::new();
+        context.insert("name", "my-database");
+
+        // execute:
+        let result = validate_templates(&[templates_dir.as_path()], rendered_dir.as_path(), &context);
+
+        // verify:
+        let errors = result.expect_err("broken templates should be reported");
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|e| e.contains("/broken.j2.tf")));
+        assert!(errors.iter().any(|e| e.contains("/sub/broken.j2.yaml")));
+        assert!(rendered_dir.join("valid.tf").exists());
+
+        assert!(errors
+            .iter()
+            .any(|e| e.contains("variable `port` is missing from the context") && e.contains("/broken.j2.tf")));
+
+        context.insert("port", &3306);
+        context.insert("disk_size", &10);
+        assert!(validate_templates(&[templates_dir.as_path()], rendered_dir.as_path(), &context).is_ok());
+    }
+
+    #[test]
+    fn test_missing_variable_error() {
+        // setup:
+        let tmp_dir = TempDir::new("missing_variable").expect("error creating temporary dir");
+        let templates_dir = tmp_dir.path().join("templates");
+        fs::create_dir_all(templates_dir.as_path()).expect("error creating directory");
+        let mut file = File::create(templates_dir.join("main.j2.tf")).expect("error creating file");
+        file.write_all("name = \"{{ database_name }}\"\nlogin = \"{{ database_logn }}\"".as_bytes())
+            .expect("error writing into file");
+
+        let mut context = Context::new();
+        context.insert("database_name", "my-database");
+        context.insert("database_login", "superuser");
+
+        // execute:
+        let result =
+            generate_and_copy_all_files_into_dir(templates_dir.as_path(), tmp_dir.path().join("rendered"), &context);
+
+        // verify:
+        let message = result
+            .expect_err("rendering should fail")
+            .message
+            .expect("error should have a message");
+        assert!(message.contains("`database_logn`"), "unexpected message: {}", message);
+        assert!(message.contains("main.j2.tf"), "unexpected message: {}", message);
+    }
+}
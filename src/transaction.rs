@@ -1,16 +1,19 @@
 use std::collections::HashMap;
 use std::thread;
 
-use crate::build_platform::BuildResult;
-use crate::cloud_provider::kubernetes::Kubernetes;
-use crate::cloud_provider::service::{Application, Service};
-use crate::container_registry::PushResult;
+use crate::build_platform::{BuildResult, Image};
+use crate::cloud_provider::kubernetes::{kubernetes_span, Kubernetes};
+use crate::cloud_provider::service::{service_span, Application, Service, ServiceType};
+use crate::container_registry::{PushResult, BUILD_CACHE_TAG};
 use crate::engine::Engine;
-use crate::error::EngineError;
+use crate::error::{EngineError, EngineErrorCause, EngineErrorScope};
 use crate::models::{
-    Action, Environment, EnvironmentAction, EnvironmentError, ListenersHelper, ProgressInfo, ProgressLevel,
-    ProgressScope,
+    end_progress_sequence, Action, Environment, EnvironmentAction, EnvironmentError, ListenersHelper, ProgressInfo,
+    ProgressLevel, ProgressPayload, ProgressScope, ServiceEndpoints,
 };
+use crate::object_storage::ObjectStorage;
+use crate::telemetry;
+use crate::utilities::run_concurrently;
 
 pub struct Transaction<'a> {
     engine: &'a Engine,
@@ -100,6 +103,18 @@ impl<'a> Transaction<'a> {
         Ok(())
     }
 
+    /// Scales a paused environment back to the replicas it had before being paused.
+    pub fn resume_environment(
+        &mut self,
+        kubernetes: &'a dyn Kubernetes,
+        environment_action: &'a EnvironmentAction,
+    ) -> Result<(), EnvironmentError> {
+        let _ = self.check_environment_action(environment_action)?;
+
+        self.steps.push(Step::ResumeEnvironment(kubernetes, environment_action));
+        Ok(())
+    }
+
     pub fn delete_environment(
         &mut self,
         kubernetes: &'a dyn Kubernetes,
@@ -133,6 +148,36 @@ impl<'a> Transaction<'a> {
         Ok(())
     }
 
+    /// Image previously pushed with the build cache tag, if any.
+    fn build_cache_image_url(&self, image: &Image) -> Option<String> {
+        let cache_image = Image {
+            tag: BUILD_CACHE_TAG.to_string(),
+            ..image.clone()
+        };
+
+        match self.engine.container_registry().does_image_exists(&cache_image) {
+            true => self
+                .engine
+                .container_registry()
+                .image_url(&cache_image)
+                .map(|image_url| self.engine.context().rewrite_image(image_url.as_str())),
+            false => None,
+        }
+    }
+
+    fn send_build_skipped(&self, scope: ProgressScope, image: &Image, execution_id: &str) {
+        ListenersHelper::new(self.engine.cloud_provider().listeners()).deployment_in_progress(ProgressInfo::new(
+            scope,
+            ProgressLevel::Info,
+            Some(format!(
+                "image {} already exists for commit {}, build skipped",
+                image.name_with_tag(),
+                image.commit_id
+            )),
+            execution_id,
+        ));
+    }
+
     fn _build_applications(
         &self,
         environment: &Environment,
@@ -150,9 +195,22 @@ impl<'a> Transaction<'a> {
                 let build_result = if option.force_build || !self.engine.container_registry().does_image_exists(&image)
                 {
                     // only if the build is forced OR if the image does not exist in the registry
-                    self.engine.build_platform().build(es.to_build(), option.force_build)
+                    let mut build = es.to_build();
+                    build.options.cache_from = self.build_cache_image_url(&image);
+                    telemetry::record(
+                        self.engine.context(),
+                        telemetry::Operation::Build,
+                        ServiceType::ExternalService.name(),
+                        self.engine.cloud_provider().kind().name(),
+                        || self.engine.build_platform().build(build, option.force_build),
+                    )
                 } else {
                     // use the cache
+                    self.send_build_skipped(
+                        ProgressScope::ExternalService { id: es.id.clone() },
+                        &image,
+                        environment.execution_id.as_str(),
+                    );
                     Ok(BuildResult::new(es.to_build()))
                 };
 
@@ -170,12 +228,25 @@ impl<'a> Transaction<'a> {
         let application_and_result_tuples = apps_to_build
             .map(|app| {
                 let image = app.to_image();
-                let build_result = if option.force_build || !self.engine.container_registry().does_image_exists(&image)
-                {
+                let force_build = option.force_build || app.force_build;
+                let build_result = if force_build || !self.engine.container_registry().does_image_exists(&image) {
                     // only if the build is forced OR if the image does not exist in the registry
-                    self.engine.build_platform().build(app.to_build(), option.force_build)
+                    let mut build = app.to_build();
+                    build.options.cache_from = self.build_cache_image_url(&image);
+                    telemetry::record(
+                        self.engine.context(),
+                        telemetry::Operation::Build,
+                        ServiceType::Application.name(),
+                        self.engine.cloud_provider().kind().name(),
+                        || self.engine.build_platform().build(build, force_build),
+                    )
                 } else {
                     // use the cache
+                    self.send_build_skipped(
+                        ProgressScope::Application { id: app.id.clone() },
+                        &image,
+                        environment.execution_id.as_str(),
+                    );
                     Ok(BuildResult::new(app.to_build()))
                 };
 
@@ -233,37 +304,101 @@ impl<'a> Transaction<'a> {
 
     fn _push_applications(
         &self,
-        applications: Vec<Box<dyn Application>>,
+        environment: &Environment,
+        mut applications: Vec<Box<dyn Application>>,
         option: &DeploymentOption,
     ) -> Result<Vec<(Box<dyn Application>, PushResult)>, EngineError> {
-        let application_and_push_results: Vec<_> = applications
-            .into_iter()
-            .map(|mut app| {
-                match self.engine.container_registry().push(app.image(), option.force_push) {
-                    Ok(push_result) => {
-                        // I am not a big fan of doing that but it's the most effective way
-                        app.set_image(push_result.image.clone());
-                        Ok((app, push_result))
-                    }
-                    Err(err) => Err(err),
-                }
+        // fail before pushing anything if an init container can't be pulled
+        for app in applications.iter_mut() {
+            self.resolve_init_container_images(app.as_mut())?;
+        }
+
+        // applications are not shareable between threads, only what the push needs is
+        let pushes = applications
+            .iter()
+            .map(|app| {
+                // a forced build replaces the image already pushed for this commit
+                let force_push = option.force_push
+                    || environment
+                        .applications
+                        .iter()
+                        .any(|application| application.id == app.id() && application.force_build);
+
+                (service_span(app.as_ref(), "push"), app.image().clone(), force_push)
             })
-            .collect();
+            .collect::<Vec<_>>();
 
-        let mut results: Vec<(Box<dyn Application>, PushResult)> = vec![];
-        for result in application_and_push_results.into_iter() {
-            match result {
-                Ok(tuple) => results.push(tuple),
-                Err(err) => {
+        // images go to distinct repositories, so they are pushed side by side
+        let container_registry = self.engine.container_registry();
+        let context = self.engine.context();
+        let provider_kind = self.engine.cloud_provider().kind();
+        let provider = provider_kind.name();
+        let push_results = run_concurrently(
+            &pushes,
+            context.max_concurrent_image_pushes(),
+            |(span, image, force_push)| {
+                span.in_scope(|| {
+                    telemetry::record(
+                        context,
+                        telemetry::Operation::Push,
+                        ServiceType::Application.name(),
+                        provider,
+                        || container_registry.push(image, *force_push),
+                    )
+                })
+                .map_err(|err| {
                     error!("error pushing docker image {:?}", err);
-                    return Err(err);
-                }
-            }
+                    err
+                })
+            },
+        )?;
+
+        let mut results: Vec<(Box<dyn Application>, PushResult)> = Vec::with_capacity(push_results.len());
+        for (mut app, push_result) in applications.into_iter().zip(push_results) {
+            info!(
+                "image {} pushed in {}s ({} layers pushed, {} already present)",
+                push_result.reference,
+                push_result.duration.as_secs(),
+                push_result.layers_pushed,
+                push_result.layers_skipped
+            );
+
+            // I am not a big fan of doing that but it's the most effective way
+            app.set_image(push_result.image.clone());
+            results.push((app, push_result));
         }
 
         Ok(results)
     }
 
+    /// Init containers images are not built by the engine, they must already be in the container registry.
+    fn resolve_init_container_images(&self, app: &mut dyn Application) -> Result<(), EngineError> {
+        let container_registry = self.engine.container_registry();
+        let mut init_containers = app.init_containers().to_vec();
+
+        for init_container in init_containers.iter_mut() {
+            let image = init_container.to_image(app.id());
+            if !container_registry.does_image_exists(&image) {
+                return Err(app.engine_error(
+                    EngineErrorCause::User("Push the init containers images to the container registry"),
+                    format!(
+                        "init container image {} of {} doesn't exist in {}",
+                        init_container.image,
+                        app.name_with_id(),
+                        container_registry.name_with_id()
+                    ),
+                ));
+            }
+
+            if let Some(image_url) = container_registry.image_url(&image) {
+                init_container.image = self.engine.context().rewrite_image(image_url.as_str());
+            }
+        }
+
+        app.set_init_containers(init_containers);
+        Ok(())
+    }
+
     fn check_environment(&self, environment: &crate::cloud_provider::environment::Environment) -> TransactionResult {
         if let Err(engine_error) = environment.is_valid() {
             warn!("ROLLBACK STARTED! an error occurred {:?}", engine_error);
@@ -279,6 +414,48 @@ impl<'a> Transaction<'a> {
         TransactionResult::Ok
     }
 
+    fn validate_environment(&self, environment: &Environment) -> TransactionResult {
+        let errors = match environment.validate() {
+            Ok(_) => return TransactionResult::Ok,
+            Err(errors) => errors,
+        };
+
+        let listeners_helper = ListenersHelper::new(self.engine.cloud_provider().listeners());
+        for error in &errors {
+            listeners_helper.deployment_error(ProgressInfo::new(
+                ProgressScope::Environment {
+                    id: environment.id.clone(),
+                },
+                ProgressLevel::Error,
+                error.message.clone(),
+                environment.execution_id.as_str(),
+            ));
+        }
+
+        let engine_error = EngineError::new(
+            EngineErrorCause::User("Fix the environment configuration"),
+            EngineErrorScope::Environment(environment.id.clone(), environment.id.clone()),
+            environment.execution_id.as_str(),
+            Some(format!(
+                "environment is not valid: {}",
+                errors
+                    .iter()
+                    .filter_map(|error| error.message.clone())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        );
+
+        warn!("ROLLBACK STARTED! an error occurred {:?}", engine_error);
+        match self.rollback() {
+            Ok(_) => TransactionResult::Rollback(engine_error),
+            Err(err) => {
+                error!("ROLLBACK FAILED! fatal error: {:?}", err);
+                TransactionResult::UnrecoverableError(engine_error, err)
+            }
+        }
+    }
+
     pub fn rollback(&self) -> Result<(), RollbackError> {
         for step in self.executed_steps.iter() {
             match step {
@@ -310,6 +487,9 @@ impl<'a> Transaction<'a> {
                 Step::PauseEnvironment(kubernetes, environment_action) => {
                     self.rollback_environment(*kubernetes, *environment_action)?;
                 }
+                Step::ResumeEnvironment(kubernetes, environment_action) => {
+                    self.rollback_environment(*kubernetes, *environment_action)?;
+                }
                 Step::DeleteEnvironment(kubernetes, environment_action) => {
                     self.rollback_environment(*kubernetes, *environment_action)?;
                 }
@@ -408,7 +588,48 @@ impl<'a> Transaction<'a> {
         }
     }
 
+    /// Checks the credentials of the providers the steps rely on, before any of them is executed.
+    fn validate_credentials(&self) -> Result<(), EngineError> {
+        let mut object_storages: Vec<&dyn ObjectStorage> = vec![];
+
+        for step in self.steps.iter() {
+            let kubernetes = match step {
+                Step::CreateKubernetes(kubernetes)
+                | Step::DeleteKubernetes(kubernetes)
+                | Step::PauseKubernetes(kubernetes)
+                | Step::DeployEnvironment(kubernetes, _)
+                | Step::PauseEnvironment(kubernetes, _)
+                | Step::ResumeEnvironment(kubernetes, _)
+                | Step::DeleteEnvironment(kubernetes, _) => *kubernetes,
+                Step::BuildEnvironment(_, _) => continue,
+            };
+
+            let object_storage = match kubernetes.config_file_store() {
+                Some(object_storage) => object_storage,
+                None => continue,
+            };
+            if !object_storages.iter().any(|o| o.id() == object_storage.id()) {
+                object_storages.push(object_storage);
+            }
+        }
+
+        self.engine.validate_credentials(&object_storages)
+    }
+
+    /// The commit is the whole execution, rollbacks included, no progress event is sent once it returns.
     pub fn commit(&mut self) -> TransactionResult {
+        let result = self.commit_steps();
+        end_progress_sequence(self.engine.context().execution_id());
+        result
+    }
+
+    fn commit_steps(&mut self) -> TransactionResult {
+        // nothing has been executed yet, there is nothing to roll back
+        if let Err(err) = self.validate_credentials() {
+            error!("Invalid credentials: {:?}", err);
+            return TransactionResult::Rollback(err);
+        }
+
         let mut applications_by_environment: HashMap<&Environment, Vec<Box<dyn Application>>> = HashMap::new();
 
         for step in self.steps.iter() {
@@ -418,7 +639,11 @@ impl<'a> Transaction<'a> {
             match step {
                 Step::CreateKubernetes(kubernetes) => {
                     // create kubernetes
-                    match self.commit_infrastructure(*kubernetes, Action::Create, kubernetes.on_create()) {
+                    match self.commit_infrastructure(
+                        *kubernetes,
+                        Action::Create,
+                        kubernetes_span(*kubernetes, "create").in_scope(|| kubernetes.on_create()),
+                    ) {
                         TransactionResult::Ok => {}
                         err => {
                             error!("Error while creating infrastructure: {:?}", err);
@@ -428,7 +653,11 @@ impl<'a> Transaction<'a> {
                 }
                 Step::DeleteKubernetes(kubernetes) => {
                     // delete kubernetes
-                    match self.commit_infrastructure(*kubernetes, Action::Delete, kubernetes.on_delete()) {
+                    match self.commit_infrastructure(
+                        *kubernetes,
+                        Action::Delete,
+                        kubernetes_span(*kubernetes, "delete").in_scope(|| kubernetes.on_delete()),
+                    ) {
                         TransactionResult::Ok => {}
                         err => {
                             error!("Error while deleting infrastructure: {:?}", err);
@@ -438,7 +667,11 @@ impl<'a> Transaction<'a> {
                 }
                 Step::PauseKubernetes(kubernetes) => {
                     // pause kubernetes
-                    match self.commit_infrastructure(*kubernetes, Action::Pause, kubernetes.on_pause()) {
+                    match self.commit_infrastructure(
+                        *kubernetes,
+                        Action::Pause,
+                        kubernetes_span(*kubernetes, "pause").in_scope(|| kubernetes.on_pause()),
+                    ) {
                         TransactionResult::Ok => {}
                         err => {
                             error!("Error while pausing infrastructure: {:?}", err);
@@ -453,8 +686,14 @@ impl<'a> Transaction<'a> {
                         EnvironmentAction::EnvironmentWithFailover(te, _) => te,
                     };
 
+                    // do not build anything if the environment can't be deployed
+                    match self.validate_environment(target_environment) {
+                        TransactionResult::Ok => {}
+                        err => return err,
+                    };
+
                     let apps_result = match self._build_applications(target_environment, option) {
-                        Ok(applications) => match self._push_applications(applications, option) {
+                        Ok(applications) => match self._push_applications(target_environment, applications, option) {
                             Ok(results) => {
                                 let applications = results.into_iter().map(|(app, _)| app).collect::<Vec<_>>();
 
@@ -484,7 +723,7 @@ impl<'a> Transaction<'a> {
                     // build as well the failover environment, retention could remove the application image
                     if let EnvironmentAction::EnvironmentWithFailover(_, fe) = environment_action {
                         let apps_result = match self._build_applications(fe, option) {
-                            Ok(applications) => match self._push_applications(applications, option) {
+                            Ok(applications) => match self._push_applications(fe, applications, option) {
                                 Ok(results) => {
                                     let applications = results.into_iter().map(|(app, _)| app).collect::<Vec<_>>();
 
@@ -531,6 +770,21 @@ impl<'a> Transaction<'a> {
                         }
                     };
                 }
+                Step::ResumeEnvironment(kubernetes, environment_action) => {
+                    // resume complete environment
+                    match self.commit_environment(
+                        *kubernetes,
+                        *environment_action,
+                        &applications_by_environment,
+                        |qe_env| kubernetes.resume_environment(qe_env),
+                    ) {
+                        TransactionResult::Ok => {}
+                        err => {
+                            error!("Error while resuming environment: {:?}", err);
+                            return err;
+                        }
+                    };
+                }
                 Step::DeleteEnvironment(kubernetes, environment_action) => {
                     // delete complete environment
                     match self.commit_environment(
@@ -726,6 +980,10 @@ impl<'a> Transaction<'a> {
                 for service in &qe_environment.stateless_services {
                     send_progress(kubernetes, &target_environment.action, service, execution_id, false);
                 }
+
+                if target_environment.action == Action::Create {
+                    send_deployment_summary(kubernetes, &qe_environment, execution_id);
+                }
             }
         };
 
@@ -733,6 +991,47 @@ impl<'a> Transaction<'a> {
     }
 }
 
+/// Single event listing how to reach each deployed service, so the UI can render connection details.
+fn send_deployment_summary(
+    kubernetes: &dyn Kubernetes,
+    environment: &crate::cloud_provider::environment::Environment,
+    execution_id: &str,
+) {
+    let stateful_services = environment.stateful_services.iter().map(|service| {
+        (
+            service.progress_scope(),
+            service.name().to_string(),
+            service.endpoints(),
+        )
+    });
+    let stateless_services = environment.stateless_services.iter().map(|service| {
+        (
+            service.progress_scope(),
+            service.name().to_string(),
+            service.endpoints(),
+        )
+    });
+
+    let services = stateful_services
+        .chain(stateless_services)
+        .filter(|(_, _, endpoints)| !endpoints.is_empty())
+        .map(|(scope, name, endpoints)| ServiceEndpoints { scope, name, endpoints })
+        .collect::<Vec<_>>();
+
+    let lh = ListenersHelper::new(kubernetes.listeners());
+    lh.deployed(
+        ProgressInfo::new(
+            ProgressScope::Environment {
+                id: environment.id.clone(),
+            },
+            ProgressLevel::Info,
+            Some("deployment summary"),
+            execution_id,
+        )
+        .with_payload(ProgressPayload::DeploymentSummary { services }),
+    );
+}
+
 #[derive(Clone)]
 pub struct DeploymentOption {
     pub force_build: bool,
@@ -747,6 +1046,7 @@ enum Step<'a> {
     BuildEnvironment(&'a EnvironmentAction, DeploymentOption),
     DeployEnvironment(&'a dyn Kubernetes, &'a EnvironmentAction),
     PauseEnvironment(&'a dyn Kubernetes, &'a EnvironmentAction),
+    ResumeEnvironment(&'a dyn Kubernetes, &'a EnvironmentAction),
     DeleteEnvironment(&'a dyn Kubernetes, &'a EnvironmentAction),
 }
 
@@ -759,6 +1059,7 @@ impl<'a> Clone for Step<'a> {
             Step::BuildEnvironment(e, option) => Step::BuildEnvironment(*e, option.clone()),
             Step::DeployEnvironment(k, e) => Step::DeployEnvironment(*k, *e),
             Step::PauseEnvironment(k, e) => Step::PauseEnvironment(*k, *e),
+            Step::ResumeEnvironment(k, e) => Step::ResumeEnvironment(*k, *e),
             Step::DeleteEnvironment(k, e) => Step::DeleteEnvironment(*k, *e),
         }
     }
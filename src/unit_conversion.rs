@@ -4,12 +4,20 @@
 /// 500m = 0.50 cpu
 /// 1000m = 1 cpu
 /// 1.25 = 1.25
+/// 250000000n = 0.25 cpu (metrics API usages are in nano cpu)
 pub fn cpu_string_to_float<T: Into<String>>(cpu: T) -> f32 {
     let cpu = cpu.into();
     if cpu.is_empty() {
         return 0.0;
     }
 
+    if let Some(nano_cpu) = cpu.strip_suffix('n') {
+        return match nano_cpu.parse::<f64>() {
+            Ok(v) if v >= 0.0 => (v / 1_000_000_000.0) as f32,
+            _ => 0.0,
+        };
+    }
+
     if !cpu.ends_with('m') {
         // the value is not in millis
         return match cpu.parse::<f32>() {
@@ -65,10 +73,22 @@ pub fn any_to_mi<T: Into<String>>(ram: T) -> u32 {
     }
 }
 
+/// convert a kubernetes storage quantity (Ki, Mi, Gi, Ti or bytes) to gi, rounded up as disks are never smaller
+pub fn storage_to_gi<T: Into<String>>(storage: T) -> Option<u32> {
+    let storage = storage.into();
+    let (value, bytes_per_unit) = [("Ki", 1u64 << 10), ("Mi", 1 << 20), ("Gi", 1 << 30), ("Ti", 1 << 40)]
+        .iter()
+        .find_map(|(suffix, bytes_per_unit)| storage.strip_suffix(suffix).map(|value| (value, *bytes_per_unit)))
+        .unwrap_or((storage.as_str(), 1));
+
+    let bytes = value.parse::<f64>().ok().filter(|v| *v >= 0.0)? * bytes_per_unit as f64;
+    Some((bytes / (1u64 << 30) as f64).ceil() as u32)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::unit_conversion::ki_to_mi;
-    use crate::unit_conversion::{any_to_mi, cpu_string_to_float};
+    use crate::unit_conversion::{any_to_mi, cpu_string_to_float, storage_to_gi};
 
     #[test]
     fn test_cpu_conversions() {
@@ -81,6 +101,8 @@ mod tests {
         assert_eq!(cpu_string_to_float("-250m"), 0.0);
         assert_eq!(cpu_string_to_float("-10"), 0.0);
         assert_eq!(cpu_string_to_float("1000"), 1000.0);
+        assert_eq!(cpu_string_to_float("250000000n"), 0.25);
+        assert_eq!(cpu_string_to_float("-250000000n"), 0.0);
     }
 
     #[test]
@@ -96,4 +118,14 @@ mod tests {
         assert_eq!(any_to_mi("1.5Gi"), 1_500);
         assert_eq!(any_to_mi("150.0Gi"), 150_000);
     }
+
+    #[test]
+    fn test_storage_to_gib_conversions() {
+        assert_eq!(storage_to_gi("10Gi"), Some(10));
+        assert_eq!(storage_to_gi("1Ti"), Some(1024));
+        assert_eq!(storage_to_gi("512Mi"), Some(1));
+        assert_eq!(storage_to_gi("10737418240"), Some(10));
+        assert_eq!(storage_to_gi("10GB"), None);
+        assert_eq!(storage_to_gi(""), None);
+    }
 }
@@ -2,6 +2,9 @@ use reqwest::header;
 use reqwest::header::{HeaderMap, HeaderValue};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
 
 // generate the right header for digital ocean with token
 pub fn get_header_with_bearer(token: &str) -> HeaderMap<HeaderValue> {
@@ -16,3 +19,84 @@ pub fn calculate_hash<T: Hash>(t: &T) -> u64 {
     t.hash(&mut s);
     s.finish()
 }
+
+/// Runs `operation` on every item with at most `max_concurrency` items in progress at once.
+/// Once an operation fails no other item is started, the ones in progress are awaited and the first error
+/// (in items order) is returned. Results are in items order.
+pub fn run_concurrently<T, R, E, F>(items: &[T], max_concurrency: usize, operation: F) -> Result<Vec<R>, E>
+where
+    T: Sync,
+    R: Send,
+    E: Send,
+    F: Fn(&T) -> Result<R, E> + Sync,
+{
+    let next_item = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let results: Mutex<Vec<Option<Result<R, E>>>> = Mutex::new(items.iter().map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..max_concurrency.max(1).min(items.len()) {
+            scope.spawn(|| {
+                while !failed.load(Ordering::SeqCst) {
+                    let index = next_item.fetch_add(1, Ordering::SeqCst);
+                    let item = match items.get(index) {
+                        Some(item) => item,
+                        None => return,
+                    };
+
+                    let result = operation(item);
+                    if result.is_err() {
+                        failed.store(true, Ordering::SeqCst);
+                    }
+                    results.lock().unwrap()[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    // items not started because of a failure have no result
+    results.into_inner().unwrap().into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utilities::run_concurrently;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_run_concurrently() {
+        // setup:
+        let items = (0..8).collect::<Vec<u32>>();
+        let in_progress = AtomicUsize::new(0);
+        let max_in_progress = AtomicUsize::new(0);
+        let started = AtomicUsize::new(0);
+        let double = |item: &u32| -> Result<u32, String> {
+            started.fetch_add(1, Ordering::SeqCst);
+            let current = in_progress.fetch_add(1, Ordering::SeqCst) + 1;
+            max_in_progress.fetch_max(current, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(20));
+            in_progress.fetch_sub(1, Ordering::SeqCst);
+
+            match item {
+                1 => Err(format!("item {} failed", item)),
+                _ => Ok(item * 2),
+            }
+        };
+
+        // execute:
+        let succeeded = run_concurrently(&items[2..], 3, double);
+        let max_in_progress_on_success = max_in_progress.load(Ordering::SeqCst);
+        started.store(0, Ordering::SeqCst);
+        let failed = run_concurrently(&items, 2, double);
+
+        // verify:
+        assert_eq!(succeeded, Ok(vec![4, 6, 8, 10, 12, 14]));
+        assert_eq!(max_in_progress_on_success, 3);
+        assert_eq!(failed, Err("item 1 failed".to_string()));
+        // items after the failure are never started
+        assert!(started.load(Ordering::SeqCst) < items.len());
+        assert_eq!(run_concurrently(&Vec::<u32>::new(), 2, double), Ok(vec![]));
+    }
+}
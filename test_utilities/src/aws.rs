@@ -1,21 +1,21 @@
 extern crate serde;
 extern crate serde_derive;
-use tracing::error;
 
-use chrono::Utc;
+use chrono::{Duration, Utc};
+use std::collections::BTreeMap;
 
 use qovery_engine::cloud_provider::aws::kubernetes::node::Node;
 use qovery_engine::cloud_provider::aws::kubernetes::{Options, EKS};
 use qovery_engine::cloud_provider::aws::AWS;
 use qovery_engine::cloud_provider::utilities::sanitize_name;
-use qovery_engine::cloud_provider::TerraformStateCredentials;
+use qovery_engine::cloud_provider::{TerraformBackend, TerraformStateCredentials};
 use qovery_engine::container_registry::docker_hub::DockerHub;
 use qovery_engine::container_registry::ecr::ECR;
 use qovery_engine::dns_provider::DnsProvider;
 use qovery_engine::engine::Engine;
 use qovery_engine::models::{
     Action, Application, Context, Database, DatabaseKind, Environment, EnvironmentVariable, GitCredentials, Kind,
-    Route, Router, Storage, StorageType,
+    Route, Router, RouterProtocol, Storage, StorageType,
 };
 
 use crate::cloudflare::dns_provider_cloudflare;
@@ -36,21 +36,20 @@ pub fn execution_id() -> String {
 
 pub fn container_registry_ecr(context: &Context) -> ECR {
     let secrets = FuncTestsSecrets::new();
-    if secrets.AWS_ACCESS_KEY_ID.is_none()
-        || secrets.AWS_SECRET_ACCESS_KEY.is_none()
-        || secrets.AWS_DEFAULT_REGION.is_none()
-    {
-        error!("Please check your Vault connectivity (token/address) or AWS_ACCESS_KEY_ID/AWS_SECRET_ACCESS_KEY/AWS_DEFAULT_REGION envrionment variables are set");
-        std::process::exit(1)
-    }
+    // missing secrets are reported by the credentials check of the engine, the region is not a credential
+    let access_key_id = secrets.AWS_ACCESS_KEY_ID.unwrap_or_default();
+    let secret_access_key = secrets.AWS_SECRET_ACCESS_KEY.unwrap_or_default();
+    let region = secrets
+        .AWS_DEFAULT_REGION
+        .unwrap_or_else(|| AWS_REGION_FOR_S3.to_string());
 
     ECR::new(
         context.clone(),
         "default-ecr-registry-Qovery Test",
         "ea59qe62xaw3wjai",
-        secrets.AWS_ACCESS_KEY_ID.unwrap().as_str(),
-        secrets.AWS_SECRET_ACCESS_KEY.unwrap().as_str(),
-        secrets.AWS_DEFAULT_REGION.unwrap().as_str(),
+        access_key_id.as_str(),
+        secret_access_key.as_str(),
+        region.as_str(),
     )
 }
 
@@ -92,6 +91,7 @@ pub fn cloud_provider_aws(context: &Context) -> AWS {
             access_key_id: secrets.TERRAFORM_AWS_ACCESS_KEY_ID.unwrap(),
             secret_access_key: secrets.TERRAFORM_AWS_SECRET_ACCESS_KEY.unwrap(),
             region: "eu-west-3".to_string(),
+            backend: TerraformBackend::KubernetesSecret,
         },
     )
 }
@@ -338,7 +338,7 @@ pub fn environment_3_apps_3_routers_3_databases(context: &Context, secrets: Func
                 git_credentials: Some(GitCredentials {
                     login: "x-access-token".to_string(),
                     access_token: "xxx".to_string(),
-                    expired_at: Utc::now(),
+                    expired_at: Utc::now() + Duration::hours(1),
                 }),
                 storage: vec![Storage {
                     id: generate_id(),
@@ -352,22 +352,27 @@ pub fn environment_3_apps_3_routers_3_databases(context: &Context, secrets: Func
                     EnvironmentVariable {
                         key: "PG_DBNAME".to_string(),
                         value: database_name.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_HOST".to_string(),
                         value: fqdn.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_PORT".to_string(),
                         value: database_port.clone().to_string(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_USERNAME".to_string(),
                         value: database_username.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_PASSWORD".to_string(),
                         value: database_password.clone(),
+                        is_secret: true,
                     },
                 ],
                 branch: "master".to_string(),
@@ -377,6 +382,15 @@ pub fn environment_3_apps_3_routers_3_databases(context: &Context, secrets: Func
                 total_instances: 2,
                 cpu_burst: "100m".to_string(),
                 start_timeout_in_seconds: 60,
+                build_timeout_in_seconds: None,
+                build_args: BTreeMap::new(),
+                dockerfile_target: None,
+                force_build: false,
+                helm_value_overrides: BTreeMap::new(),
+                init_containers: vec![],
+                autoscaling: None,
+                probes: None,
+                namespace: None,
             },
             Application {
                 id: generate_id(),
@@ -389,7 +403,7 @@ pub fn environment_3_apps_3_routers_3_databases(context: &Context, secrets: Func
                 git_credentials: Some(GitCredentials {
                     login: "x-access-token".to_string(),
                     access_token: "xxx".to_string(),
-                    expired_at: Utc::now(),
+                    expired_at: Utc::now() + Duration::hours(1),
                 }),
                 storage: vec![Storage {
                     id: generate_id(),
@@ -403,22 +417,27 @@ pub fn environment_3_apps_3_routers_3_databases(context: &Context, secrets: Func
                     EnvironmentVariable {
                         key: "PG_DBNAME".to_string(),
                         value: database_name_2.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_HOST".to_string(),
                         value: fqdn_2.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_PORT".to_string(),
                         value: database_port.clone().to_string(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_USERNAME".to_string(),
                         value: database_username_2.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_PASSWORD".to_string(),
                         value: database_password.clone(),
+                        is_secret: true,
                     },
                 ],
                 branch: "master".to_string(),
@@ -428,6 +447,15 @@ pub fn environment_3_apps_3_routers_3_databases(context: &Context, secrets: Func
                 total_instances: 2,
                 cpu_burst: "100m".to_string(),
                 start_timeout_in_seconds: 60,
+                build_timeout_in_seconds: None,
+                build_args: BTreeMap::new(),
+                dockerfile_target: None,
+                force_build: false,
+                helm_value_overrides: BTreeMap::new(),
+                init_containers: vec![],
+                autoscaling: None,
+                probes: None,
+                namespace: None,
             },
             Application {
                 id: generate_id(),
@@ -440,7 +468,7 @@ pub fn environment_3_apps_3_routers_3_databases(context: &Context, secrets: Func
                 git_credentials: Some(GitCredentials {
                     login: "x-access-token".to_string(),
                     access_token: "xxx".to_string(),
-                    expired_at: Utc::now(),
+                    expired_at: Utc::now() + Duration::hours(1),
                 }),
                 storage: vec![Storage {
                     id: generate_id(),
@@ -454,30 +482,37 @@ pub fn environment_3_apps_3_routers_3_databases(context: &Context, secrets: Func
                     EnvironmentVariable {
                         key: "IS_DOCUMENTDB".to_string(),
                         value: "false".to_string(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "QOVERY_DATABASE_TESTING_DATABASE_FQDN".to_string(),
                         value: database_host_mongo.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "QOVERY_DATABASE_MY_DDB_CONNECTION_URI".to_string(),
                         value: database_uri_mongo.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "QOVERY_DATABASE_TESTING_DATABASE_PORT".to_string(),
                         value: database_port_mongo.clone().to_string(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "MONGODB_DBNAME".to_string(),
                         value: database_db_name_mongo.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "QOVERY_DATABASE_TESTING_DATABASE_USERNAME".to_string(),
                         value: database_username_mongo.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "QOVERY_DATABASE_TESTING_DATABASE_PASSWORD".to_string(),
                         value: database_password_mongo.clone(),
+                        is_secret: true,
                     },
                 ],
                 branch: "master".to_string(),
@@ -487,6 +522,15 @@ pub fn environment_3_apps_3_routers_3_databases(context: &Context, secrets: Func
                 total_instances: 2,
                 cpu_burst: "100m".to_string(),
                 start_timeout_in_seconds: 60,
+                build_timeout_in_seconds: None,
+                build_args: BTreeMap::new(),
+                dockerfile_target: None,
+                force_build: false,
+                helm_value_overrides: BTreeMap::new(),
+                init_containers: vec![],
+                autoscaling: None,
+                probes: None,
+                namespace: None,
             },
         ],
         routers: vec![
@@ -496,10 +540,13 @@ pub fn environment_3_apps_3_routers_3_databases(context: &Context, secrets: Func
                 action: Action::Create,
                 default_domain: format!("{}.{}", generate_id(), test_domain),
                 public_port: 443,
+                protocol: RouterProtocol::Http,
+                namespace: None,
                 custom_domains: vec![],
                 routes: vec![Route {
                     path: "/app1".to_string(),
                     application_name: app_name_1.clone(),
+                    rewrite_target: None,
                 }],
             },
             Router {
@@ -508,10 +555,13 @@ pub fn environment_3_apps_3_routers_3_databases(context: &Context, secrets: Func
                 action: Action::Create,
                 default_domain: format!("{}.{}", generate_id(), test_domain),
                 public_port: 443,
+                protocol: RouterProtocol::Http,
+                namespace: None,
                 custom_domains: vec![],
                 routes: vec![Route {
                     path: "/app2".to_string(),
                     application_name: app_name_2.clone(),
+                    rewrite_target: None,
                 }],
             },
             Router {
@@ -520,10 +570,13 @@ pub fn environment_3_apps_3_routers_3_databases(context: &Context, secrets: Func
                 action: Action::Create,
                 default_domain: format!("{}.{}", generate_id(), test_domain),
                 public_port: 443,
+                protocol: RouterProtocol::Http,
+                namespace: None,
                 custom_domains: vec![],
                 routes: vec![Route {
                     path: "/app3".to_string(),
                     application_name: app_name_3.clone(),
+                    rewrite_target: None,
                 }],
             },
         ],
@@ -544,6 +597,24 @@ pub fn environment_3_apps_3_routers_3_databases(context: &Context, secrets: Func
                 disk_size_in_gib: 10,
                 database_instance_type: "db.t2.micro".to_string(),
                 database_disk_type: "gp2".to_string(),
+                cluster_mode_enabled: false,
+                replicas: 0,
+                iops: None,
+                storage_throughput: None,
+                deletion_protection: false,
+                sentinel_enabled: false,
+                resources_overcommit_percent: None,
+                parameters: BTreeMap::new(),
+                require_tls: false,
+                tls_certificates: None,
+                deletion_timeout_in_seconds: None,
+                helm_value_overrides: BTreeMap::new(),
+                deploy_timeout_in_seconds: None,
+                namespace: None,
+                clone_from: None,
+                backup_retention_days: None,
+                backup_window: None,
+                node_pool_label: None,
             },
             Database {
                 kind: DatabaseKind::Postgresql,
@@ -561,6 +632,24 @@ pub fn environment_3_apps_3_routers_3_databases(context: &Context, secrets: Func
                 disk_size_in_gib: 10,
                 database_instance_type: "db.t2.micro".to_string(),
                 database_disk_type: "gp2".to_string(),
+                cluster_mode_enabled: false,
+                replicas: 0,
+                iops: None,
+                storage_throughput: None,
+                deletion_protection: false,
+                sentinel_enabled: false,
+                resources_overcommit_percent: None,
+                parameters: BTreeMap::new(),
+                require_tls: false,
+                tls_certificates: None,
+                deletion_timeout_in_seconds: None,
+                helm_value_overrides: BTreeMap::new(),
+                deploy_timeout_in_seconds: None,
+                namespace: None,
+                clone_from: None,
+                backup_retention_days: None,
+                backup_window: None,
+                node_pool_label: None,
             },
             Database {
                 kind: DatabaseKind::Mongodb,
@@ -578,6 +667,24 @@ pub fn environment_3_apps_3_routers_3_databases(context: &Context, secrets: Func
                 disk_size_in_gib: 10,
                 database_instance_type: "db.t3.medium".to_string(),
                 database_disk_type: "gp2".to_string(),
+                cluster_mode_enabled: false,
+                replicas: 0,
+                iops: None,
+                storage_throughput: None,
+                deletion_protection: false,
+                sentinel_enabled: false,
+                resources_overcommit_percent: None,
+                parameters: BTreeMap::new(),
+                require_tls: false,
+                tls_certificates: None,
+                deletion_timeout_in_seconds: None,
+                helm_value_overrides: BTreeMap::new(),
+                deploy_timeout_in_seconds: None,
+                namespace: None,
+                clone_from: None,
+                backup_retention_days: None,
+                backup_window: None,
+                node_pool_label: None,
             },
         ],
         external_services: vec![],
@@ -607,7 +714,7 @@ pub fn working_minimal_environment(context: &Context, secrets: FuncTestsSecrets)
             git_credentials: Some(GitCredentials {
                 login: "x-access-token".to_string(),
                 access_token: "xxx".to_string(),
-                expired_at: Utc::now(),
+                expired_at: Utc::now() + Duration::hours(1),
             }),
             storage: vec![],
             environment_variables: vec![],
@@ -618,6 +725,15 @@ pub fn working_minimal_environment(context: &Context, secrets: FuncTestsSecrets)
             total_instances: 2,
             cpu_burst: "100m".to_string(),
             start_timeout_in_seconds: 60,
+            build_timeout_in_seconds: None,
+            build_args: BTreeMap::new(),
+            dockerfile_target: None,
+            force_build: false,
+            helm_value_overrides: BTreeMap::new(),
+            init_containers: vec![],
+            autoscaling: None,
+            probes: None,
+            namespace: None,
         }],
         routers: vec![Router {
             id: generate_id(),
@@ -625,10 +741,13 @@ pub fn working_minimal_environment(context: &Context, secrets: FuncTestsSecrets)
             action: Action::Create,
             default_domain: format!("{}.{}", generate_id(), secrets.DEFAULT_TEST_DOMAIN.unwrap()),
             public_port: 443,
+            protocol: RouterProtocol::Http,
+            namespace: None,
             custom_domains: vec![],
             routes: vec![Route {
                 path: "/".to_string(),
                 application_name: format!("{}-{}", "simple-app".to_string(), &suffix),
+                rewrite_target: None,
             }],
         }],
         databases: vec![],
@@ -675,6 +794,24 @@ pub fn environnement_2_app_2_routers_1_psql(context: &Context, secrets: FuncTest
             disk_size_in_gib: 10,
             database_instance_type: "db.t2.micro".to_string(),
             database_disk_type: "gp2".to_string(),
+            cluster_mode_enabled: false,
+            replicas: 0,
+            iops: None,
+            storage_throughput: None,
+            deletion_protection: false,
+            sentinel_enabled: false,
+            resources_overcommit_percent: None,
+            parameters: BTreeMap::new(),
+            require_tls: false,
+            tls_certificates: None,
+            deletion_timeout_in_seconds: None,
+            helm_value_overrides: BTreeMap::new(),
+            deploy_timeout_in_seconds: None,
+            namespace: None,
+            clone_from: None,
+            backup_retention_days: None,
+            backup_window: None,
+            node_pool_label: None,
         }],
         applications: vec![
             Application {
@@ -688,7 +825,7 @@ pub fn environnement_2_app_2_routers_1_psql(context: &Context, secrets: FuncTest
                 git_credentials: Some(GitCredentials {
                     login: "x-access-token".to_string(),
                     access_token: "xxx".to_string(),
-                    expired_at: Utc::now(),
+                    expired_at: Utc::now() + Duration::hours(1),
                 }),
                 storage: vec![Storage {
                     id: generate_id(),
@@ -702,22 +839,27 @@ pub fn environnement_2_app_2_routers_1_psql(context: &Context, secrets: FuncTest
                     EnvironmentVariable {
                         key: "PG_DBNAME".to_string(),
                         value: database_name.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_HOST".to_string(),
                         value: fqdn.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_PORT".to_string(),
                         value: database_port.clone().to_string(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_USERNAME".to_string(),
                         value: database_username.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_PASSWORD".to_string(),
                         value: database_password.clone(),
+                        is_secret: true,
                     },
                 ],
                 branch: "master".to_string(),
@@ -727,6 +869,15 @@ pub fn environnement_2_app_2_routers_1_psql(context: &Context, secrets: FuncTest
                 total_instances: 2,
                 cpu_burst: "100m".to_string(),
                 start_timeout_in_seconds: 60,
+                build_timeout_in_seconds: None,
+                build_args: BTreeMap::new(),
+                dockerfile_target: None,
+                force_build: false,
+                helm_value_overrides: BTreeMap::new(),
+                init_containers: vec![],
+                autoscaling: None,
+                probes: None,
+                namespace: None,
             },
             Application {
                 id: generate_id(),
@@ -739,7 +890,7 @@ pub fn environnement_2_app_2_routers_1_psql(context: &Context, secrets: FuncTest
                 git_credentials: Some(GitCredentials {
                     login: "x-access-token".to_string(),
                     access_token: "xxx".to_string(),
-                    expired_at: Utc::now(),
+                    expired_at: Utc::now() + Duration::hours(1),
                 }),
                 storage: vec![Storage {
                     id: generate_id(),
@@ -753,22 +904,27 @@ pub fn environnement_2_app_2_routers_1_psql(context: &Context, secrets: FuncTest
                     EnvironmentVariable {
                         key: "PG_DBNAME".to_string(),
                         value: database_name.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_HOST".to_string(),
                         value: fqdn.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_PORT".to_string(),
                         value: database_port.clone().to_string(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_USERNAME".to_string(),
                         value: database_username.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_PASSWORD".to_string(),
                         value: database_password.clone(),
+                        is_secret: true,
                     },
                 ],
                 branch: "master".to_string(),
@@ -778,6 +934,15 @@ pub fn environnement_2_app_2_routers_1_psql(context: &Context, secrets: FuncTest
                 total_instances: 2,
                 cpu_burst: "100m".to_string(),
                 start_timeout_in_seconds: 60,
+                build_timeout_in_seconds: None,
+                build_args: BTreeMap::new(),
+                dockerfile_target: None,
+                force_build: false,
+                helm_value_overrides: BTreeMap::new(),
+                init_containers: vec![],
+                autoscaling: None,
+                probes: None,
+                namespace: None,
             },
         ],
         routers: vec![
@@ -787,10 +952,13 @@ pub fn environnement_2_app_2_routers_1_psql(context: &Context, secrets: FuncTest
                 action: Action::Create,
                 default_domain: format!("{}.{}", generate_id(), &test_domain),
                 public_port: 443,
+                protocol: RouterProtocol::Http,
+                namespace: None,
                 custom_domains: vec![],
                 routes: vec![Route {
                     path: "/".to_string(),
                     application_name: application_name1.to_string(),
+                    rewrite_target: None,
                 }],
             },
             Router {
@@ -799,10 +967,13 @@ pub fn environnement_2_app_2_routers_1_psql(context: &Context, secrets: FuncTest
                 action: Action::Create,
                 default_domain: format!("{}.{}", generate_id(), &test_domain),
                 public_port: 443,
+                protocol: RouterProtocol::Http,
+                namespace: None,
                 custom_domains: vec![],
                 routes: vec![Route {
                     path: "/coco".to_string(),
                     application_name: application_name2.to_string(),
+                    rewrite_target: None,
                 }],
             },
         ],
@@ -853,12 +1024,13 @@ pub fn echo_app_environment(context: &Context, secrets: FuncTestsSecrets) -> Env
             git_credentials: Some(GitCredentials {
                 login: "x-access-token".to_string(),
                 access_token: "xxx".to_string(),
-                expired_at: Utc::now(),
+                expired_at: Utc::now() + Duration::hours(1),
             }),
             storage: vec![],
             environment_variables: vec![EnvironmentVariable {
                 key: "ECHO_TEXT".to_string(),
                 value: "42".to_string(),
+                is_secret: false,
             }],
             branch: "echo-app".to_string(),
             private_port: Some(5678),
@@ -867,6 +1039,15 @@ pub fn echo_app_environment(context: &Context, secrets: FuncTestsSecrets) -> Env
             total_instances: 2,
             cpu_burst: "100m".to_string(),
             start_timeout_in_seconds: 60,
+            build_timeout_in_seconds: None,
+            build_args: BTreeMap::new(),
+            dockerfile_target: None,
+            force_build: false,
+            helm_value_overrides: BTreeMap::new(),
+            init_containers: vec![],
+            autoscaling: None,
+            probes: None,
+            namespace: None,
         }],
         routers: vec![Router {
             id: generate_id(),
@@ -874,10 +1055,13 @@ pub fn echo_app_environment(context: &Context, secrets: FuncTestsSecrets) -> Env
             action: Action::Create,
             default_domain: format!("{}.{}", generate_id(), secrets.DEFAULT_TEST_DOMAIN.unwrap()),
             public_port: 443,
+            protocol: RouterProtocol::Http,
+            namespace: None,
             custom_domains: vec![],
             routes: vec![Route {
                 path: "/".to_string(),
                 application_name: format!("{}-{}", "echo-app".to_string(), &suffix),
+                rewrite_target: None,
             }],
         }],
         databases: vec![],
@@ -908,7 +1092,7 @@ pub fn environment_only_http_server(context: &Context) -> Environment {
             git_credentials: Some(GitCredentials {
                 login: "x-access-token".to_string(),
                 access_token: "xxx".to_string(),
-                expired_at: Utc::now(),
+                expired_at: Utc::now() + Duration::hours(1),
             }),
             storage: vec![],
             environment_variables: vec![],
@@ -919,6 +1103,15 @@ pub fn environment_only_http_server(context: &Context) -> Environment {
             total_instances: 2,
             cpu_burst: "100m".to_string(),
             start_timeout_in_seconds: 60,
+            build_timeout_in_seconds: None,
+            build_args: BTreeMap::new(),
+            dockerfile_target: None,
+            force_build: false,
+            helm_value_overrides: BTreeMap::new(),
+            init_containers: vec![],
+            autoscaling: None,
+            probes: None,
+            namespace: None,
         }],
         routers: vec![],
         databases: vec![],
@@ -949,7 +1142,7 @@ pub fn environment_only_http_server_router(context: &Context, secrets: FuncTests
             git_credentials: Some(GitCredentials {
                 login: "x-access-token".to_string(),
                 access_token: "xxx".to_string(),
-                expired_at: Utc::now(),
+                expired_at: Utc::now() + Duration::hours(1),
             }),
             storage: vec![],
             environment_variables: vec![],
@@ -960,6 +1153,15 @@ pub fn environment_only_http_server_router(context: &Context, secrets: FuncTests
             total_instances: 2,
             cpu_burst: "100m".to_string(),
             start_timeout_in_seconds: 60,
+            build_timeout_in_seconds: None,
+            build_args: BTreeMap::new(),
+            dockerfile_target: None,
+            force_build: false,
+            helm_value_overrides: BTreeMap::new(),
+            init_containers: vec![],
+            autoscaling: None,
+            probes: None,
+            namespace: None,
         }],
         routers: vec![Router {
             id: generate_id(),
@@ -967,10 +1169,13 @@ pub fn environment_only_http_server_router(context: &Context, secrets: FuncTests
             action: Action::Create,
             default_domain: format!("{}.{}", generate_id(), secrets.DEFAULT_TEST_DOMAIN.unwrap()),
             public_port: 443,
+            protocol: RouterProtocol::Http,
+            namespace: None,
             custom_domains: vec![],
             routes: vec![Route {
                 path: "/".to_string(),
                 application_name: format!("{}-{}", "mini-http".to_string(), &suffix),
+                rewrite_target: None,
             }],
         }],
         databases: vec![],
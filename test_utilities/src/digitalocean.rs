@@ -3,7 +3,7 @@ use qovery_engine::cloud_provider::digitalocean::kubernetes::DoksOptions;
 use qovery_engine::cloud_provider::digitalocean::kubernetes::DOKS;
 use qovery_engine::cloud_provider::digitalocean::network::vpc::VpcInitKind;
 use qovery_engine::cloud_provider::digitalocean::DO;
-use qovery_engine::cloud_provider::TerraformStateCredentials;
+use qovery_engine::cloud_provider::{TerraformBackend, TerraformStateCredentials};
 use qovery_engine::container_registry::docr::DOCR;
 use qovery_engine::dns_provider::DnsProvider;
 use qovery_engine::engine::Engine;
@@ -97,6 +97,7 @@ pub fn cloud_provider_digitalocean(context: &Context) -> DO {
             access_key_id: secrets.TERRAFORM_AWS_ACCESS_KEY_ID.unwrap(),
             secret_access_key: secrets.TERRAFORM_AWS_SECRET_ACCESS_KEY.unwrap(),
             region: secrets.TERRAFORM_AWS_REGION.unwrap(),
+            backend: TerraformBackend::KubernetesSecret,
         },
     )
 }
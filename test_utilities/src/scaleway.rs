@@ -1,20 +1,22 @@
 use qovery_engine::cloud_provider::scaleway::application::Zone;
-use qovery_engine::cloud_provider::scaleway::kubernetes::node::{Node, NodeType};
+use qovery_engine::cloud_provider::scaleway::kubernetes::node::{NodePool, NodeType, DEFAULT_NODE_POOL_NAME};
 use qovery_engine::cloud_provider::scaleway::kubernetes::{Kapsule, KapsuleOptions};
 use qovery_engine::cloud_provider::scaleway::Scaleway;
-use qovery_engine::cloud_provider::TerraformStateCredentials;
+use qovery_engine::cloud_provider::{TerraformBackend, TerraformStateCredentials};
 use qovery_engine::container_registry::scaleway_container_registry::ScalewayCR;
 use qovery_engine::dns_provider::DnsProvider;
 use qovery_engine::engine::Engine;
-use qovery_engine::models::{Action, Application, Context, Environment, GitCredentials, Kind, Route, Router};
+use qovery_engine::models::{
+    Action, Application, Context, Environment, GitCredentials, Kind, Route, Router, RouterProtocol,
+};
 use qovery_engine::object_storage::scaleway_object_storage::{BucketDeleteStrategy, ScalewayOS};
 
 use crate::cloudflare::dns_provider_cloudflare;
 use crate::utilities::{build_platform_local_docker, generate_id, FuncTestsSecrets};
 
-use chrono::Utc;
+use chrono::{Duration, Utc};
+use std::collections::BTreeMap;
 use std::str::FromStr;
-use tracing::error;
 
 pub const ORGANIZATION_ID: &str = "cf8e78e6-159b-45b6-bfb5-2430c9505080";
 pub const SCW_TEST_CLUSTER_NAME: &str = "qovery-zb3a2b3b8";
@@ -24,20 +26,10 @@ pub const SCW_KUBERNETES_VERSION: &str = "1.18";
 
 pub fn container_registry_scw(context: &Context) -> ScalewayCR {
     let secrets = FuncTestsSecrets::new();
-    if secrets.SCALEWAY_ACCESS_KEY.is_none()
-        || secrets.SCALEWAY_SECRET_KEY.is_none()
-        || secrets.SCALEWAY_DEFAULT_PROJECT_ID.is_none()
-    {
-        error!("Please check your Vault connectivity (token/address) or SCALEWAY_ACCESS_KEY/SCALEWAY_SECRET_KEY/SCALEWAY_DEFAULT_PROJECT_ID envrionment variables are set");
-        std::process::exit(1)
-    }
     let random_id = generate_id();
-    let scw_secret_key = secrets
-        .SCALEWAY_SECRET_KEY
-        .expect("SCALEWAY_SECRET_KEY is not set in secrets");
-    let scw_default_project_id = secrets
-        .SCALEWAY_DEFAULT_PROJECT_ID
-        .expect("SCALEWAY_DEFAULT_PROJECT_ID is not set in secrets");
+    // missing secrets are reported by the credentials check of the engine
+    let scw_secret_key = secrets.SCALEWAY_SECRET_KEY.unwrap_or_default();
+    let scw_default_project_id = secrets.SCALEWAY_DEFAULT_PROJECT_ID.unwrap_or_default();
 
     ScalewayCR::new(
         context.clone(),
@@ -73,6 +65,7 @@ pub fn cloud_provider_scaleway(context: &Context) -> Scaleway {
                 .TERRAFORM_AWS_SECRET_ACCESS_KEY
                 .expect("TERRAFORM_AWS_SECRET_ACCESS_KEY is not set in secrets"),
             region: "eu-west-3".to_string(),
+            backend: TerraformBackend::KubernetesSecret,
         },
     )
 }
@@ -131,13 +124,13 @@ pub fn scw_object_storage(context: Context, region: Zone) -> ScalewayOS {
     )
 }
 
-pub fn scw_kubernetes_nodes() -> Vec<Node> {
+pub fn scw_kubernetes_nodes() -> Vec<NodePool> {
     // Note: Dev1M is a bit too small to handle engine + local docker, hence using Dev1L
-    scw_kubernetes_custom_nodes(10, NodeType::Dev1L)
+    vec![NodePool::new(DEFAULT_NODE_POOL_NAME, NodeType::Dev1L, 3, 10)]
 }
 
-pub fn scw_kubernetes_custom_nodes(count: usize, node_type: NodeType) -> Vec<Node> {
-    vec![Node::new(node_type); count]
+pub fn scw_kubernetes_custom_nodes(count: u16, node_type: NodeType) -> Vec<NodePool> {
+    vec![NodePool::new_with_fixed_size(DEFAULT_NODE_POOL_NAME, node_type, count)]
 }
 
 pub fn docker_scw_cr_engine(context: &Context) -> Engine {
@@ -165,7 +158,7 @@ pub fn scw_kubernetes_kapsule<'a>(
     context: &Context,
     cloud_provider: &'a Scaleway,
     dns_provider: &'a dyn DnsProvider,
-    nodes: Vec<Node>,
+    nodes: Vec<NodePool>,
 ) -> Kapsule<'a> {
     let secrets = FuncTestsSecrets::new();
     Kapsule::<'a>::new(
@@ -212,7 +205,7 @@ pub fn working_minimal_environment(context: &Context, secrets: FuncTestsSecrets)
             git_credentials: Some(GitCredentials {
                 login: "x-access-token".to_string(),
                 access_token: "xxx".to_string(),
-                expired_at: Utc::now(),
+                expired_at: Utc::now() + Duration::hours(1),
             }),
             storage: vec![],
             environment_variables: vec![],
@@ -223,6 +216,15 @@ pub fn working_minimal_environment(context: &Context, secrets: FuncTestsSecrets)
             total_instances: 2,
             cpu_burst: "100m".to_string(),
             start_timeout_in_seconds: 60,
+            build_timeout_in_seconds: None,
+            build_args: BTreeMap::new(),
+            dockerfile_target: None,
+            force_build: false,
+            helm_value_overrides: BTreeMap::new(),
+            init_containers: vec![],
+            autoscaling: None,
+            probes: None,
+            namespace: None,
         }],
         routers: vec![Router {
             id: generate_id(),
@@ -236,10 +238,13 @@ pub fn working_minimal_environment(context: &Context, secrets: FuncTestsSecrets)
                     .expect("DEFAULT_TEST_DOMAIN is not set in secrets")
             ),
             public_port: 443,
+            protocol: RouterProtocol::Http,
+            namespace: None,
             custom_domains: vec![],
             routes: vec![Route {
                 path: "/".to_string(),
                 application_name: format!("{}-{}", "simple-app".to_string(), &suffix),
+                rewrite_target: None,
             }],
         }],
         databases: vec![],
@@ -67,6 +67,8 @@ pub fn context() -> Context {
             }
         }),
         disable_pleco: Some(true),
+        rollback_partial_deploy: None,
+        clean_orphaned_helm_releases: None,
     };
 
     let enabled_features = vec![Features::LogsHistory, Features::MetricsHistory];
@@ -6,6 +6,7 @@ use qovery_engine::models::{
     Action, Clone2, Context, Database, DatabaseKind, Environment, EnvironmentAction, EnvironmentVariable, Kind,
 };
 use qovery_engine::transaction::TransactionResult;
+use std::collections::BTreeMap;
 use test_utilities::utilities::{init, FuncTestsSecrets};
 use tracing::{span, Level};
 
@@ -291,6 +292,18 @@ fn postgresql_deploy_a_working_environment_and_redeploy() {
             disk_size_in_gib: 10,
             database_instance_type: "db.t2.micro".to_string(),
             database_disk_type: "gp2".to_string(),
+            cluster_mode_enabled: false,
+            replicas: 0,
+            iops: None,
+            storage_throughput: None,
+            deletion_protection: false,
+            sentinel_enabled: false,
+            resources_overcommit_percent: None,
+            parameters: BTreeMap::new(),
+            require_tls: false,
+            tls_certificates: None,
+            deletion_timeout_in_seconds: None,
+            helm_value_overrides: BTreeMap::new(),
         }];
         environment.applications = environment
             .applications
@@ -303,22 +316,27 @@ fn postgresql_deploy_a_working_environment_and_redeploy() {
                     EnvironmentVariable {
                         key: "PG_HOST".to_string(),
                         value: database_host.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_PORT".to_string(),
                         value: database_port.clone().to_string(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_DBNAME".to_string(),
                         value: database_db_name.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_USERNAME".to_string(),
                         value: database_username.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_PASSWORD".to_string(),
                         value: database_password.clone(),
+                        is_secret: true,
                     },
                 ];
                 app
@@ -416,6 +434,24 @@ fn test_postgresql_configuration(
             disk_size_in_gib: 10,
             database_instance_type: "db.t2.micro".to_string(),
             database_disk_type: "gp2".to_string(),
+            cluster_mode_enabled: false,
+            replicas: 0,
+            iops: None,
+            storage_throughput: None,
+            deletion_protection: false,
+            sentinel_enabled: false,
+            resources_overcommit_percent: None,
+            parameters: BTreeMap::new(),
+            require_tls: false,
+            tls_certificates: None,
+            deletion_timeout_in_seconds: None,
+            helm_value_overrides: BTreeMap::new(),
+            deploy_timeout_in_seconds: None,
+            namespace: None,
+            clone_from: None,
+            backup_retention_days: None,
+            backup_window: None,
+            node_pool_label: None,
         }];
         environment.applications = environment
             .applications
@@ -429,22 +465,27 @@ fn test_postgresql_configuration(
                     EnvironmentVariable {
                         key: "PG_HOST".to_string(),
                         value: database_host.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_PORT".to_string(),
                         value: database_port.clone().to_string(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_DBNAME".to_string(),
                         value: database_db_name.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_USERNAME".to_string(),
                         value: database_username.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_PASSWORD".to_string(),
                         value: database_password.clone(),
+                        is_secret: true,
                     },
                 ];
                 app
@@ -591,6 +632,24 @@ fn test_mongodb_configuration(
             disk_size_in_gib: 10,
             database_instance_type: "db.t3.medium".to_string(),
             database_disk_type: "gp2".to_string(),
+            cluster_mode_enabled: false,
+            replicas: 0,
+            iops: None,
+            storage_throughput: None,
+            deletion_protection: false,
+            sentinel_enabled: false,
+            resources_overcommit_percent: None,
+            parameters: BTreeMap::new(),
+            require_tls: false,
+            tls_certificates: None,
+            deletion_timeout_in_seconds: None,
+            helm_value_overrides: BTreeMap::new(),
+            deploy_timeout_in_seconds: None,
+            namespace: None,
+            clone_from: None,
+            backup_retention_days: None,
+            backup_window: None,
+            node_pool_label: None,
         }];
         environment.applications = environment
             .applications
@@ -612,30 +671,37 @@ fn test_mongodb_configuration(
                     EnvironmentVariable {
                         key: "IS_DOCUMENTDB".to_string(),
                         value: is_documentdb.to_string(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "QOVERY_DATABASE_TESTING_DATABASE_FQDN".to_string(),
                         value: database_host.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "QOVERY_DATABASE_MY_DDB_CONNECTION_URI".to_string(),
                         value: database_uri.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "QOVERY_DATABASE_TESTING_DATABASE_PORT".to_string(),
                         value: database_port.clone().to_string(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "MONGODB_DBNAME".to_string(),
                         value: database_db_name.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "QOVERY_DATABASE_TESTING_DATABASE_USERNAME".to_string(),
                         value: database_username.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "QOVERY_DATABASE_TESTING_DATABASE_PASSWORD".to_string(),
                         value: database_password.clone(),
+                        is_secret: true,
                     },
                 ];
                 app
@@ -780,6 +846,24 @@ fn test_mysql_configuration(
             disk_size_in_gib: 10,
             database_instance_type: "db.t2.micro".to_string(),
             database_disk_type: "gp2".to_string(),
+            cluster_mode_enabled: false,
+            replicas: 0,
+            iops: None,
+            storage_throughput: None,
+            deletion_protection: false,
+            sentinel_enabled: false,
+            resources_overcommit_percent: None,
+            parameters: BTreeMap::new(),
+            require_tls: false,
+            tls_certificates: None,
+            deletion_timeout_in_seconds: None,
+            helm_value_overrides: BTreeMap::new(),
+            deploy_timeout_in_seconds: None,
+            namespace: None,
+            clone_from: None,
+            backup_retention_days: None,
+            backup_window: None,
+            node_pool_label: None,
         }];
         environment.applications = environment
             .applications
@@ -801,22 +885,27 @@ fn test_mysql_configuration(
                     EnvironmentVariable {
                         key: "MYSQL_HOST".to_string(),
                         value: database_host.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "MYSQL_PORT".to_string(),
                         value: database_port.clone().to_string(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "MYSQL_DBNAME".to_string(),
                         value: database_db_name.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "MYSQL_USERNAME".to_string(),
                         value: database_username.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "MYSQL_PASSWORD".to_string(),
                         value: database_password.clone(),
+                        is_secret: true,
                     },
                 ];
                 app
@@ -940,6 +1029,24 @@ fn test_redis_configuration(
             disk_size_in_gib: 10,
             database_instance_type: "cache.t3.micro".to_string(),
             database_disk_type: "gp2".to_string(),
+            cluster_mode_enabled: false,
+            replicas: 0,
+            iops: None,
+            storage_throughput: None,
+            deletion_protection: false,
+            sentinel_enabled: false,
+            resources_overcommit_percent: None,
+            parameters: BTreeMap::new(),
+            require_tls: false,
+            tls_certificates: None,
+            deletion_timeout_in_seconds: None,
+            helm_value_overrides: BTreeMap::new(),
+            deploy_timeout_in_seconds: None,
+            namespace: None,
+            clone_from: None,
+            backup_retention_days: None,
+            backup_window: None,
+            node_pool_label: None,
         }];
         environment.applications = environment
             .applications
@@ -962,22 +1069,27 @@ fn test_redis_configuration(
                     EnvironmentVariable {
                         key: "IS_ELASTICCACHE".to_string(),
                         value: is_elasticache.to_string(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "REDIS_HOST".to_string(),
                         value: database_host.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "REDIS_PORT".to_string(),
                         value: database_port.clone().to_string(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "REDIS_USERNAME".to_string(),
                         value: database_username.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "REDIS_PASSWORD".to_string(),
                         value: database_password.clone(),
+                        is_secret: true,
                     },
                 ];
                 app
@@ -1,4 +1,5 @@
 use ::function_name::named;
+use std::collections::BTreeMap;
 use tracing::{span, Level};
 
 use qovery_engine::models::{
@@ -59,6 +60,24 @@ fn test_postgresql_configuration(
             disk_size_in_gib: 10,
             database_instance_type: "not-used".to_string(),
             database_disk_type: "scw-sbv-ssd-0".to_string(),
+            cluster_mode_enabled: false,
+            replicas: 0,
+            iops: None,
+            storage_throughput: None,
+            deletion_protection: false,
+            sentinel_enabled: false,
+            resources_overcommit_percent: None,
+            parameters: BTreeMap::new(),
+            require_tls: false,
+            tls_certificates: None,
+            deletion_timeout_in_seconds: None,
+            helm_value_overrides: BTreeMap::new(),
+            deploy_timeout_in_seconds: None,
+            namespace: None,
+            clone_from: None,
+            backup_retention_days: None,
+            backup_window: None,
+            node_pool_label: None,
         }];
         environment.applications = environment
             .applications
@@ -72,22 +91,27 @@ fn test_postgresql_configuration(
                     EnvironmentVariable {
                         key: "PG_HOST".to_string(),
                         value: database_host.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_PORT".to_string(),
                         value: database_port.clone().to_string(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_DBNAME".to_string(),
                         value: database_db_name.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_USERNAME".to_string(),
                         value: database_username.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "PG_PASSWORD".to_string(),
                         value: database_password.clone(),
+                        is_secret: true,
                     },
                 ];
                 app
@@ -197,6 +221,24 @@ fn test_mongodb_configuration(
             disk_size_in_gib: 10,
             database_instance_type: "not-used".to_string(),
             database_disk_type: "scw-sbv-ssd-0".to_string(),
+            cluster_mode_enabled: false,
+            replicas: 0,
+            iops: None,
+            storage_throughput: None,
+            deletion_protection: false,
+            sentinel_enabled: false,
+            resources_overcommit_percent: None,
+            parameters: BTreeMap::new(),
+            require_tls: false,
+            tls_certificates: None,
+            deletion_timeout_in_seconds: None,
+            helm_value_overrides: BTreeMap::new(),
+            deploy_timeout_in_seconds: None,
+            namespace: None,
+            clone_from: None,
+            backup_retention_days: None,
+            backup_window: None,
+            node_pool_label: None,
         }];
 
         environment.applications = environment
@@ -211,26 +253,32 @@ fn test_mongodb_configuration(
                     EnvironmentVariable {
                         key: "QOVERY_DATABASE_TESTING_DATABASE_FQDN".to_string(),
                         value: database_host.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "QOVERY_DATABASE_MY_DDB_CONNECTION_URI".to_string(),
                         value: database_uri.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "QOVERY_DATABASE_TESTING_DATABASE_PORT".to_string(),
                         value: database_port.clone().to_string(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "MONGODB_DBNAME".to_string(),
                         value: database_db_name.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "QOVERY_DATABASE_TESTING_DATABASE_USERNAME".to_string(),
                         value: database_username.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "QOVERY_DATABASE_TESTING_DATABASE_PASSWORD".to_string(),
                         value: database_password.clone(),
+                        is_secret: true,
                     },
                 ];
                 app
@@ -353,6 +401,24 @@ fn test_mysql_configuration(
             disk_size_in_gib: 10,
             database_instance_type: "not-used".to_string(),
             database_disk_type: "scw-sbv-ssd-0".to_string(),
+            cluster_mode_enabled: false,
+            replicas: 0,
+            iops: None,
+            storage_throughput: None,
+            deletion_protection: false,
+            sentinel_enabled: false,
+            resources_overcommit_percent: None,
+            parameters: BTreeMap::new(),
+            require_tls: false,
+            tls_certificates: None,
+            deletion_timeout_in_seconds: None,
+            helm_value_overrides: BTreeMap::new(),
+            deploy_timeout_in_seconds: None,
+            namespace: None,
+            clone_from: None,
+            backup_retention_days: None,
+            backup_window: None,
+            node_pool_label: None,
         }];
         environment.applications = environment
             .applications
@@ -374,22 +440,27 @@ fn test_mysql_configuration(
                     EnvironmentVariable {
                         key: "MYSQL_HOST".to_string(),
                         value: database_host.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "MYSQL_PORT".to_string(),
                         value: database_port.clone().to_string(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "MYSQL_DBNAME".to_string(),
                         value: database_db_name.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "MYSQL_USERNAME".to_string(),
                         value: database_username.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "MYSQL_PASSWORD".to_string(),
                         value: database_password.clone(),
+                        is_secret: true,
                     },
                 ];
                 app
@@ -492,6 +563,24 @@ fn test_redis_configuration(
             disk_size_in_gib: 10,
             database_instance_type: "not-used".to_string(),
             database_disk_type: "scw-sbv-ssd-0".to_string(),
+            cluster_mode_enabled: false,
+            replicas: 0,
+            iops: None,
+            storage_throughput: None,
+            deletion_protection: false,
+            sentinel_enabled: false,
+            resources_overcommit_percent: None,
+            parameters: BTreeMap::new(),
+            require_tls: false,
+            tls_certificates: None,
+            deletion_timeout_in_seconds: None,
+            helm_value_overrides: BTreeMap::new(),
+            deploy_timeout_in_seconds: None,
+            namespace: None,
+            clone_from: None,
+            backup_retention_days: None,
+            backup_window: None,
+            node_pool_label: None,
         }];
         environment.applications = environment
             .applications
@@ -514,22 +603,27 @@ fn test_redis_configuration(
                     EnvironmentVariable {
                         key: "IS_ELASTICCACHE".to_string(),
                         value: is_elasticache.to_string(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "REDIS_HOST".to_string(),
                         value: database_host.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "REDIS_PORT".to_string(),
                         value: database_port.clone().to_string(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "REDIS_USERNAME".to_string(),
                         value: database_username.clone(),
+                        is_secret: false,
                     },
                     EnvironmentVariable {
                         key: "REDIS_PASSWORD".to_string(),
                         value: database_password.clone(),
+                        is_secret: true,
                     },
                 ];
                 app